@@ -10,6 +10,12 @@ DEFERred.
 "use strict";
 
 const API_ENDPOINT = "/admin";
+
+// Regex for extracting filename from Content-Disposition header.
+const FILENAME = /; filename="([^"]+)"/;
+// Time (in ms) to wait for an object to start downloading before its
+// ObjectURL is revoked.
+const DOWNLOAD_DELAY = 5000;
 const STATE = {
     error_count: 0
 };
@@ -22,6 +28,13 @@ const DATA = {
     users: new Map(),
     courses: new Map(),
     completion: new Map(),
+    equivalents: new Map(),
+    // Only restricted Admins are in here.
+    permissions: new Map(),
+    // Unames of Teachers in training mode.
+    training: new Set(),
+    webhooks: new Map(),
+    report_templates: new Map(),
 };
 
 const DISPLAY = {
@@ -40,37 +53,79 @@ const DISPLAY = {
     course_tbody:  document.querySelector("table#course-table > tbody"),
     course_edit:   document.getElementById("alter-course"),
     course_upload: document.getElementById("upload-course-dialog"),
+    relevel: document.getElementById("relevel-dialog"),
+    relevel_effects: document.getElementById("relevel-effects"),
+    equiv_tbody:   document.querySelector("table#equiv-table > tbody"),
+    transfer_upload: document.getElementById("upload-transfer-dialog"),
+    auth_restore: document.getElementById("restore-auth-dialog"),
     chapter_edit:  document.getElementById("alter-chapter"),
     student_reset: document.getElementById("reset-students"),
+    archives_tbody: document.querySelector("table#year-archives > tbody"),
+    roster_sync: document.getElementById("roster-sync"),
     history_year: document.querySelector("tbody#add-completion-history input[name='year']"),
+    test_tbody: document.querySelector("table#test-request-table > tbody"),
+    show_delivered: document.getElementById("show-delivered-tests"),
+    webhook_tbody: document.querySelector("table#webhook-table > tbody"),
+    delivery_tbody: document.querySelector("table#webhook-delivery-table > tbody"),
+    failed_email_tbody: document.querySelector("table#failed-email-table > tbody"),
+    report_template_tbody: document.querySelector("table#report-template-table > tbody"),
+    preview_uname: document.getElementById("report-template-preview-uname"),
+    preview_term: document.getElementById("report-template-preview-term"),
 };
 
+function load_users(j) {
+    DATA.users = new Map();
+    UTIL.clear(DISPLAY.admin_tbody);
+    UTIL.clear(DISPLAY.boss_tbody);
+    UTIL.clear(DISPLAY.teacher_tbody);
+    UTIL.clear(DISPLAY.student_tbody);
+    for(const u of j) {
+        add_user_to_display(u);
+    }
+}
+
 function populate_users(r) {
+    const archive = r.headers.get("x-camp-archive");
+    if(archive) {
+        console.log(`Student data archived to ${archive}`);
+    }
     r.json()
     .then(j => {
         console.log("populate-users response:");
         console.log(j);
-
-        DATA.users = new Map();
-        UTIL.clear(DISPLAY.admin_tbody);
-        UTIL.clear(DISPLAY.boss_tbody);
-        UTIL.clear(DISPLAY.teacher_tbody);
-        UTIL.clear(DISPLAY.student_tbody);
-        for(const u of j) {
-            add_user_to_display(u);
-        }
+        load_users(j);
     }).catch(RQ.add_err);
 }
 
+function load_completion(j) {
+    for(const [name, vec] of Object.entries(j)) {
+        DATA.completion.set(name, vec);
+    }
+}
+
 function populate_completion(r) {
     r.json()
     .then(j => {
         console.log("populate-completion response:");
         console.log(j);
+        load_completion(j);
+    }).catch(RQ.add_err);
+}
 
-        for(const [name, vec] of Object.entries(j)) {
-            DATA.completion.set(name, vec);
-        }
+/*
+Everything the page needs to start with, in one response. Courses go
+before equivalents, which look up course titles.
+*/
+function bootstrap(r) {
+    r.json()
+    .then(j => {
+        console.log("bootstrap response:", j);
+        load_courses(j.courses);
+        load_permissions(j.permissions);
+        load_training(j.training);
+        load_users(j.users);
+        load_completion(j.completion);
+        load_equivalents(j.equivalents);
     }).catch(RQ.add_err);
 }
 
@@ -90,7 +145,7 @@ function field_response(r) {
     if(!r.ok) {
         r.text()
         .then(t => {
-            const err_txt = `${t}\n(${r.status}: ${r.statusText})`;
+            const err_txt = `${UTIL.error_text(t)}\n(${r.status}: ${r.statusText})`;
             RQ.add_err(err_txt);
         }
         ).catch(e => {
@@ -113,14 +168,62 @@ function field_response(r) {
         return;
     }
     switch(action) {
+        case "bootstrap":
+            bootstrap(r); break;
+        case "confirm-action":
+            CONFIRM.respond(r); break;
         case "populate-users":
             populate_users(r); break;
+        case "preview-reset":
+            preview_reset(r); break;
+        case "populate-archives":
+            populate_archives(r); break;
+        case "export-archive":
+            save_download(r, "archive"); break;
+        case "roster-preview":
+            preview_roster(r); break;
+        case "roster-apply":
+            roster_applied(r); break;
         case "populate-courses":
             populate_courses(r); break;
+        case "preview-relevel":
+            preview_relevel(r); break;
         case "populate-completion":
             populate_completion(r); break;
         case "update-completion":
             update_completion(r); break;
+        case "populate-equivalents":
+            populate_equivalents(r); break;
+        case "populate-permissions":
+            populate_permissions(r); break;
+        case "populate-training":
+            populate_training(r); break;
+        case "populate-test-requests":
+            populate_test_requests(r); break;
+        case "populate-webhooks":
+            populate_webhooks(r); break;
+        case "webhook-deliveries":
+            populate_deliveries(r); break;
+        case "failed-emails":
+            populate_failed_emails(r); break;
+        case "populate-report-templates":
+            populate_report_templates(r); break;
+        case "preview-report-template":
+            save_download(r, "report preview"); break;
+        case "check-uname":
+            checked_uname(r); break;
+        case "check-email":
+            checked_email(r); break;
+        case "support-bundle":
+            save_download(r, "support bundle"); break;
+        case "export-auth":
+            save_download(r, "auth DB export"); break;
+        case "download-students":
+            save_download(r, "student roster"); break;
+        case "verify-auth":
+            verified_auth(r); break;
+        case "import-auth":
+            imported_auth(r); break;
         default:
             const e_n = STATE.next_error();
             const err_txt = `Unrecognized x-camp-action header: ${action}. (See console error #${e_n})`;
@@ -131,6 +234,7 @@ function field_response(r) {
 }
 
 function request_action(action, body, description, extra_headers) {
+    CONFIRM.remember(action, body, description, extra_headers);
     const headers = { "x-camp-action": action };
     if(extra_headers) {
         for(const [name, value] of Object.entries(extra_headers)) {
@@ -171,6 +275,99 @@ that is, the stuff on the "Staff" and "Students" tabs.
 
 */
 
+/*
+Checking unames and email addresses as they're entered.
+
+When a `uname` or `email` input in one of the user dialogs changes, the
+server is asked whether the new value is okay. A bad or taken uname marks
+the input invalid (so the form won't submit); an address someone else
+already has just gets marked, because users are allowed to share.
+*/
+const USER_DIALOGS = ["alter-admin", "alter-boss", "alter-teacher", "alter-student"];
+
+function clear_name_check(input) {
+    input.setCustomValidity("");
+    input.classList.remove("in-use");
+    input.removeAttribute("title");
+}
+
+/*
+Return the input in the currently-open user dialog with the given name,
+if its value is still `value` (it may have changed since the check was
+requested).
+*/
+function checked_input(name, value) {
+    const input = document.querySelector(`dialog[open] input[name='${name}']`);
+    if(input && input.value.trim() == value) {
+        return input;
+    } else {
+        return null;
+    }
+}
+
+function checked_uname(r) {
+    r.json()
+    .then(j => {
+        console.log("check-uname response:", j);
+        const input = checked_input("uname", j.value);
+        if(!input || input.disabled) {
+            return;
+        }
+        if(j.problem) {
+            input.setCustomValidity(j.problem);
+        } else if(j.existing.length > 0) {
+            const [uname, role] = j.existing[0];
+            input.setCustomValidity(`There is already a ${role} with uname ${uname}.`);
+        } else {
+            input.setCustomValidity("");
+        }
+        input.reportValidity();
+    }).catch(RQ.add_err);
+}
+
+function checked_email(r) {
+    r.json()
+    .then(j => {
+        console.log("check-email response:", j);
+        const input = checked_input("email", j.value);
+        if(!input) {
+            return;
+        }
+        clear_name_check(input);
+        if(j.problem) {
+            input.setCustomValidity(j.problem);
+            input.reportValidity();
+            return;
+        }
+        const uname = input.form.elements['uname'].value;
+        const others = j.existing
+            .filter(([u, _]) => u != uname)
+            .map(([u, role]) => `${u} (${role})`);
+        if(others.length > 0) {
+            input.classList.add("in-use");
+            input.title = `Also used by ${others.join(", ")}.`;
+        }
+    }).catch(RQ.add_err);
+}
+
+for(const form_name of USER_DIALOGS) {
+    const form = document.forms[form_name];
+    for(const [name, action] of [["uname", "check-uname"], ["email", "check-email"]]) {
+        const input = form.elements[name];
+        input.addEventListener("input", () => clear_name_check(input));
+        input.addEventListener("change", () => {
+            const value = input.value.trim();
+            if(value && !input.disabled) {
+                request_action(action, value, `Checking ${name} ${value}...`);
+            }
+        });
+    }
+    document.getElementById(form_name).addEventListener("close", () => {
+        clear_name_check(form.elements['uname']);
+        clear_name_check(form.elements['email']);
+    });
+}
+
 function make_user_edit_button_td(uname, edit_func) {
     const butt = document.createElement("button");
     butt.setAttribute("data-uname", uname);
@@ -196,6 +393,7 @@ function add_user_to_display(u) {
         tr.setAttribute("data-uname", v.uname);
         tr.appendChild(UTIL.text_td(v.uname));
         tr.appendChild(UTIL.text_td(v.email));
+        tr.appendChild(UTIL.text_td(permissions_text(v.uname)));
         tr.appendChild(make_user_edit_button_td(v.uname, edit_admin));
 
         DISPLAY.admin_tbody.appendChild(tr);
@@ -221,6 +419,7 @@ function add_user_to_display(u) {
         tr.appendChild(UTIL.text_td(v.uname));
         tr.appendChild(UTIL.text_td(v.email));
         tr.appendChild(UTIL.text_td(u.Teacher.name));
+        tr.appendChild(UTIL.text_td(training_text(v.uname)));
         tr.appendChild(make_user_edit_button_td(v.uname, edit_teacher));
 
         DISPLAY.teacher_tbody.appendChild(tr);
@@ -260,6 +459,54 @@ This both prevents the uname from being changed (unames should
 never be changed) and also signals the difference between adding
 new and updating existing users.
 */
+/*
+How an Admin's permissions are shown in the Admin table.
+*/
+function permissions_text(uname) {
+    const perms = DATA.permissions.get(uname);
+    if(!perms) {
+        return "all";
+    } else if(perms.length == 0) {
+        return "read only";
+    } else {
+        return perms.join(", ");
+    }
+}
+
+function load_permissions(j) {
+    DATA.permissions = new Map(Object.entries(j));
+    for(const tr of DISPLAY.admin_tbody.querySelectorAll("tr")) {
+        const uname = tr.getAttribute("data-uname");
+        UTIL.set_text(tr.children[2], permissions_text(uname));
+    }
+}
+
+function populate_permissions(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-permissions response:", j);
+        load_permissions(j);
+    }).catch(RQ.add_err);
+}
+
+/*
+Check the boxes in the alter-admin form for the given permissions (`null`
+or `undefined` meaning unrestricted).
+*/
+function set_permission_inputs(perms) {
+    const form = document.forms['alter-admin'];
+    form.elements['unrestricted'].checked = !perms;
+    for(const ipt of form.querySelectorAll("input[name='perm']")) {
+        ipt.checked = perms ? perms.includes(ipt.value) : false;
+        ipt.disabled = !perms;
+    }
+}
+
+document.getElementById("alter-admin-unrestricted")
+    .addEventListener("change", (evt) => {
+        set_permission_inputs(evt.target.checked ? null : []);
+    });
+
 function edit_admin(evt) {
     const uname = this.getAttribute("data-uname");
     const form = document.forms['alter-admin'];
@@ -271,12 +518,20 @@ function edit_admin(evt) {
         form.elements['uname'].value = u.uname;
         form.elements['uname'].disabled = true;
         form.elements['email'].value = u.email;
+        set_permission_inputs(DATA.permissions.get(uname));
+        form.elements['unrestricted'].disabled = false;
         del.disabled = false
     } else {
         form.elements['uname'].disabled = false;
         for(const ipt of form.elements) {
-            ipt.value = "";
+            if(ipt.type != "checkbox") {
+                ipt.value = "";
+            }
         }
+        // New Admins start out unrestricted; they can be restricted once
+        // they exist.
+        set_permission_inputs(null);
+        form.elements['unrestricted'].disabled = true;
         del.disabled = true;
     }
 
@@ -317,9 +572,22 @@ function edit_admin_submit() {
         }
     };
 
+    let perms = null;
+    if(!form.elements['unrestricted'].checked) {
+        perms = data.getAll("perm");
+    }
+    const old_perms = DATA.permissions.get(uname) || null;
+
     DISPLAY.admin_edit.close();
     if(uname_input.disabled) {
         request_action("update-user", u, `Updating user ${uname}...`);
+        if(JSON.stringify(perms) != JSON.stringify(old_perms)) {
+            request_action(
+                "set-permissions",
+                { "uname": uname, "permissions": perms },
+                `Setting ${uname}'s permissions...`
+            );
+        }
     } else {
         request_action("add-user", u, `Adding user ${uname}...`);
     }
@@ -450,7 +718,11 @@ function edit_teacher(evt) {
     const uname = this.getAttribute("data-uname");
     const form = document.forms['alter-teacher'];
     const del = document.getElementById("delete-teacher");
+    const reset = document.getElementById("reset-sandbox");
+    const reassign = document.getElementById("reassign-students");
+    const reassign_to = document.getElementById("reassign-students-to");
     del.setAttribute("data-uname", uname);
+    UTIL.clear(reassign_to);
     
     if(uname) {
         const u = DATA.users.get(uname)['Teacher'];
@@ -458,11 +730,37 @@ function edit_teacher(evt) {
         form.elements['uname'].disabled = true;
         form.elements['email'].value = u.base.email;
         form.elements['name'].value = u.name;
+        form.elements['training'].checked = DATA.training.has(uname);
+        form.elements['training'].disabled = false;
+        reset.setAttribute("data-uname", uname);
+        reset.disabled = !DATA.training.has(uname);
+        const others = [];
+        for(const [other, ou] of DATA.users) {
+            if(other != uname && ou['Teacher']) { others.push(ou['Teacher']); }
+        }
+        others.sort((a, b) => a.name.localeCompare(b.name));
+        for(const t of others) {
+            const opt = document.createElement("option");
+            opt.value = t.base.uname;
+            opt.appendChild(document.createTextNode(`${t.name} (${t.base.uname})`));
+            reassign_to.appendChild(opt);
+        }
+        reassign.setAttribute("data-uname", uname);
+        reassign.disabled = (others.length == 0);
         del.disabled = false;
     } else {
         for(const ipt of form.elements) {
-            ipt.value = "";
+            if(ipt.type != "checkbox") {
+                ipt.value = "";
+            }
         }
+        // New Teachers can be put into training mode once they exist.
+        form.elements['training'].checked = false;
+        form.elements['training'].disabled = true;
+        reset.removeAttribute("data-uname");
+        reset.disabled = true;
+        reassign.removeAttribute("data-uname");
+        reassign.disabled = true;
         del.removeAttribute("data-uname");
         del.disabled = true;
     }
@@ -496,9 +794,18 @@ function edit_teacher_submit() {
         }
     };
 
+    const training = form.elements['training'].checked;
+
     DISPLAY.teacher_edit.close();
     if(uname_input.disabled) {
         request_action("update-user", u, `Updating user ${uname}...`);
+        if(training != DATA.training.has(uname)) {
+            request_action(
+                "set-training",
+                { "uname": uname, "training": training },
+                `Turning training mode ${training ? "on" : "off"} for ${uname}...`
+            );
+        }
     } else {
         request_action("add-user", u, `Adding user ${uname}...`);
     }
@@ -524,6 +831,60 @@ async function delete_teacher_submit(evt) {
 document.getElementById("delete-teacher")
     .addEventListener("click", delete_teacher_submit);
 
+async function reassign_students_submit(evt) {
+    evt.preventDefault();
+    const from = this.getAttribute("data-uname");
+    const to = document.getElementById("reassign-students-to").value;
+    if(!to) { return; }
+    const q = `Move all of ${from}'s students to ${to}?`;
+    if(await are_you_sure(q)) {
+        DISPLAY.teacher_edit.close();
+        request_action(
+            "reassign-students",
+            { "from": from, "to": to },
+            `Moving ${from}'s students to ${to}...`
+        );
+    }
+}
+
+document.getElementById("reassign-students")
+    .addEventListener("click", reassign_students_submit);
+
+/*
+How whether a Teacher is in training mode is shown in the Teacher table.
+*/
+function training_text(uname) {
+    return DATA.training.has(uname) ? "yes" : "";
+}
+
+function load_training(j) {
+    DATA.training = new Set(j);
+    for(const tr of DISPLAY.teacher_tbody.querySelectorAll("tr")) {
+        const uname = tr.getAttribute("data-uname");
+        UTIL.set_text(tr.children[3], training_text(uname));
+    }
+}
+
+function populate_training(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-training response:", j);
+        load_training(j);
+    }).catch(RQ.add_err);
+}
+
+async function reset_sandbox_submit(evt) {
+    const uname = this.getAttribute("data-uname");
+    const q = `Are you sure you want to throw away everything ${uname} has done in training mode?`;
+    if(await are_you_sure(q)) {
+        DISPLAY.teacher_edit.close();
+        request_action("reset-sandbox", uname, `Resetting ${uname}'s sandbox...`);
+    }
+}
+
+document.getElementById("reset-sandbox")
+    .addEventListener("click", reset_sandbox_submit);
+
 function populate_teacher_selector(teacher_uname) {
     let sel = document.getElementById("alter-student-teacher");
     
@@ -713,6 +1074,11 @@ document.getElementById("upload-students")
         DISPLAY.student_upload.showModal();
     });
 
+document.getElementById("download-students")
+    .addEventListener("click", () => {
+        request_action("download-students", null, "Downloading student roster.");
+    });
+
 function upload_students_submit(evt) {
     const form = document.forms["upload-students"];
     const data = new FormData(form);
@@ -731,6 +1097,29 @@ function upload_students_submit(evt) {
 document.getElementById("upload-students-confirm")
     .addEventListener("click",upload_students_submit);
 
+document.getElementById("upload-transfer")
+    .addEventListener("click", () => {
+        DISPLAY.transfer_upload.showModal();
+    });
+
+function upload_transfer_submit(evt) {
+    const form = document.forms["upload-transfer"];
+    const data = new FormData(form);
+    const file = data.get("file");
+
+    UTIL.get_file_as_text(file)
+    .then((text) => {
+        DISPLAY.transfer_upload.close();
+        request_action("import-transfer", text, `Importing transfer credit...`);
+    })
+    .catch((err) => {
+        RQ.add_err(`Error opening local file: ${err}`);
+    })
+}
+
+document.getElementById("upload-transfer-confirm")
+    .addEventListener("click", upload_transfer_submit);
+
 
 /*
 
@@ -751,6 +1140,9 @@ function edit_course(evt) {
         form.elements['title'].value = c.title;
         form.elements['level'].value = c.level;
         form.elements['book'].value = c.book || "";
+        const cons = c.constraints || {};
+        form.elements['min_gap'].value = cons.min_gap || "";
+        form.elements['max_per_week'].value = cons.max_per_week || "";
         del.setAttribute("data-sym", sym);
         del.disabled = false;
     } else {
@@ -785,6 +1177,11 @@ function edit_course_submit() {
     }
     let book = data.get("book").trim();
     if(book == "") { book = null; }
+    // Blank constraint inputs mean "no constraint".
+    const constraints = {
+        "min_gap": Number(data.get("min_gap")) || null,
+        "max_per_week": Number(data.get("max_per_week")) || null,
+    };
 
     let c = DATA.courses.get(sym);
     if(c) {
@@ -794,6 +1191,7 @@ function edit_course_submit() {
         c.title = title;
         c.level = level;
         c.book = book;
+        c.constraints = constraints;
         // This function doesn't alter any chapters, so we empty this and
         // send less unnecessary data to the server.
         c.chapters = [];
@@ -809,6 +1207,7 @@ function edit_course_submit() {
             "title": title,
             "level": level,
             "book": book,
+            "constraints": constraints,
             "chapters": [], // No chapters yet!
         };
     }
@@ -1079,47 +1478,105 @@ function populate_course_table_row(c) {
     tr.appendChild(td);
 }
 
+function load_courses(j) {
+    DATA.courses = new Map();
+    UTIL.clear(DISPLAY.course_tbody);
+    const list = document.getElementById("course-names");
+    UTIL.clear(list);
+
+    for(const c of j) {
+        DATA.courses.set(c.sym, c);
+
+        // Create and populate <TR> element to hold course metadata.
+        let tr = document.createElement("tr");
+        tr.setAttribute("data-sym", c.sym);
+        DISPLAY.course_tbody.appendChild(tr);
+        populate_course_table_row(c);
+
+        // Create and populate <TR> (and nested single <TD>)
+        // to hold chapter table.
+        tr = document.createElement("tr");
+        tr.setAttribute("data-chapters", c.sym);
+        const td = document.createElement("td");
+        td.setAttribute("colspan", "6");
+        tr.appendChild(td);
+        DISPLAY.course_tbody.appendChild(tr);
+        populate_course_chapters(c);
+
+        // Add an <OPTION> to the course names <DATALIST>
+        let book_text = "";
+        if(c.book) { book_text = ` (${c.book})`; }
+        const opt_text = `${c.sym}: ${c.title}${book_text}`;
+        const opt = document.createElement("option");
+        opt.value = c.sym;
+        UTIL.set_text(opt, opt_text);
+        list.appendChild(opt);
+    }
+}
+
 function populate_courses(r) {
     r.json()
     .then(j => {
         console.log("populate-courses response:", j);
+        load_courses(j);
+    }).catch(RQ.add_err);
+}
 
-        DATA.courses = new Map();
-        UTIL.clear(DISPLAY.course_tbody);
-        const list = document.getElementById("course-names");
-        UTIL.clear(list);
-
-        for(const c of j) {
-            DATA.courses.set(c.sym, c);
+function load_equivalents(j) {
+    DATA.equivalents = new Map(Object.entries(j));
+    UTIL.clear(DISPLAY.equiv_tbody);
+    for(const [ext, sym] of DATA.equivalents) {
+        const tr = document.createElement("tr");
+        tr.appendChild(UTIL.text_td(ext));
+        const crs = DATA.courses.get(sym);
+        const symtd = UTIL.text_td(sym);
+        if(crs) { symtd.setAttribute("title", crs.title); }
+        tr.appendChild(symtd);
 
-            // Create and populate <TR> element to hold course metadata.
-            let tr = document.createElement("tr");
-            tr.setAttribute("data-sym", c.sym);
-            DISPLAY.course_tbody.appendChild(tr);
-            populate_course_table_row(c);
+        const td = document.createElement("td");
+        const butt = document.createElement("button");
+        butt.setAttribute("data-ext", ext);
+        UTIL.label("remove", butt);
+        butt.addEventListener("click", delete_equivalent);
+        td.appendChild(butt);
+        tr.appendChild(td);
 
-            // Create and populate <TR> (and nested single <TD>)
-            // to hold chapter table.
-            tr = document.createElement("tr");
-            tr.setAttribute("data-chapters", c.sym);
-            const td = document.createElement("td");
-            td.setAttribute("colspan", "6");
-            tr.appendChild(td);
-            DISPLAY.course_tbody.appendChild(tr);
-            populate_course_chapters(c);
+        DISPLAY.equiv_tbody.appendChild(tr);
+    }
+}
 
-            // Add an <OPTION> to the course names <DATALIST>
-            let book_text = "";
-            if(c.book) { book_text = ` (${c.book})`; }
-            const opt_text = `${c.sym}: ${c.title}${book_text}`;
-            const opt = document.createElement("option");
-            opt.value = c.sym;
-            UTIL.set_text(opt, opt_text);
-            list.appendChild(opt);
-        }
+function populate_equivalents(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-equivalents response:", j);
+        load_equivalents(j);
     }).catch(RQ.add_err);
 }
 
+function add_equivalent(evt) {
+    evt.preventDefault();
+    const ext_ipt = document.querySelector("tbody#add-equiv input[name='ext']");
+    const sym_ipt = document.querySelector("tbody#add-equiv input[name='sym']");
+    const ext = ext_ipt.value.trim();
+    const sym = sym_ipt.value.trim();
+    if(!ext || !sym) {
+        RQ.add_err("Both the other school's course and our course are required.");
+        return;
+    }
+
+    ext_ipt.value = "";
+    sym_ipt.value = "";
+    request_action("set-equivalent", [ext, sym], `Making ${ext} equivalent to ${sym}...`);
+}
+
+document.getElementById("add-equiv-add")
+    .addEventListener("click", add_equivalent);
+
+function delete_equivalent(evt) {
+    const ext = this.getAttribute("data-ext");
+    request_action("set-equivalent", [ext, ""], `Removing equivalent for ${ext}...`);
+}
+
 document.getElementById("upload-course")
     .addEventListener("click", () => {
         DISPLAY.course_upload.showModal();
@@ -1143,9 +1600,78 @@ function upload_course_submit(evt) {
 document.getElementById("upload-course-confirm")
     .addEventListener("click", upload_course_submit);
 
+document.getElementById("relevel-courses")
+    .addEventListener("click", () => {
+        document.forms["relevel"].reset();
+        UTIL.clear(DISPLAY.relevel_effects);
+        document.getElementById("relevel-confirm").disabled = true;
+        DISPLAY.relevel.showModal();
+    });
+document.getElementById("relevel-cancel")
+    .addEventListener("click", (evt) => {
+        evt.preventDefault();
+        DISPLAY.relevel.close();
+    });
+document.getElementById("relevel-file")
+    .addEventListener("change", function() {
+        if(this.files.length == 0) { return; }
+        UTIL.get_file_as_text(this.files[0])
+        .then((text) => {
+            document.forms["relevel"].elements["levels"].value = text;
+            document.getElementById("relevel-confirm").disabled = true;
+        })
+        .catch((err) => {
+            RQ.add_err(`Error opening local file: ${err}`);
+        });
+    });
+document.forms["relevel"].elements["levels"]
+    .addEventListener("input", () => {
+        // Whatever gets applied should have been previewed first.
+        document.getElementById("relevel-confirm").disabled = true;
+    });
+document.getElementById("relevel-preview")
+    .addEventListener("click", (evt) => {
+        evt.preventDefault();
+        const text = document.forms["relevel"].elements["levels"].value;
+        request_action("preview-relevel", text, "Checking new course levels.");
+    });
+document.getElementById("relevel-confirm")
+    .addEventListener("click", (evt) => {
+        evt.preventDefault();
+        const text = document.forms["relevel"].elements["levels"].value;
+        DISPLAY.relevel.close();
+        request_action("relevel-courses", text, "Re-leveling courses.");
+    });
+
+function preview_relevel(r) {
+    r.json()
+    .then(j => {
+        console.log("preview-relevel response:", j);
+
+        const effects = DISPLAY.relevel_effects;
+        UTIL.clear(effects);
+        effects.appendChild(roster_list(
+            "Change", j.changes, x => `${x.sym} (${x.title}) from ${x.old} to ${x.new}`
+        ));
+        if(j.ties.length > 0) {
+            effects.appendChild(roster_list(
+                "Same level (chapters will be interleaved)", j.ties, x => x.join(", ")
+            ));
+        }
+        effects.appendChild(roster_list(
+            "Goals reordered", j.students,
+            x => `${x.uname} (${x.rest} ${x.last}, ${x.teacher}): ${x.n_moved} goal(s)`
+        ));
+        document.getElementById("relevel-confirm").disabled = (j.changes.length == 0);
+    })
+    .catch(RQ.add_err);
+}
+
 document.getElementById("reset-students-button")
     .addEventListener("click", () => {
+        UTIL.clear(DISPLAY.archives_tbody);
         DISPLAY.student_reset.showModal();
+        request_action("populate-archives", null, "Fetching archived years.");
     });
 document.getElementById("reset-students-cancel")
     .addEventListener("click", (evt) => {
@@ -1155,13 +1681,264 @@ document.getElementById("reset-students-cancel")
 document.getElementById("reset-students-confirm")
     .addEventListener("click", async function(evt) {
         evt.preventDefault();
-        const q = "Are you sure you want to exercise the scorced-earth nuclear option on all student Goals and Student User records?";
-        if(await are_you_sure(q)) {
-            DISPLAY.student_reset.close();
-            request_action("reset-students", null, "Deleting all student data.")
+        DISPLAY.student_reset.close();
+        request_action("preview-reset", null, "Previewing student data reset.");
+    });
+
+document.getElementById("support-bundle")
+    .addEventListener("click", () => {
+        request_action("support-bundle", null, "Gathering support bundle.");
+    });
+
+document.getElementById("export-auth")
+    .addEventListener("click", () => {
+        request_action("export-auth", null, "Exporting auth DB.");
+    });
+
+document.getElementById("restore-auth")
+    .addEventListener("click", () => {
+        DISPLAY.auth_restore.showModal();
+    });
+
+/* The export being restored, held between checking it and importing it. */
+let AUTH_EXPORT = null;
+
+function restore_auth_submit(evt) {
+    const form = document.forms["restore-auth"];
+    const data = new FormData(form);
+    const file = data.get("file");
+
+    UTIL.get_file_as_text(file)
+    .then((text) => {
+        DISPLAY.auth_restore.close();
+        AUTH_EXPORT = text;
+        request_action("verify-auth", text, "Checking auth DB export.");
+    })
+    .catch((err) => {
+        RQ.add_err(`Error opening local file: ${err}`);
+    })
+}
+
+document.getElementById("restore-auth-confirm")
+    .addEventListener("click", restore_auth_submit);
+
+function list_or_none(unames) {
+    return unames.length > 0 ? unames.join(", ") : "(none)";
+}
+
+async function verified_auth(r) {
+    let j;
+    try {
+        j = await r.json();
+    } catch(e) {
+        RQ.add_err(e);
+        return;
+    }
+    console.log("verify-auth response:", j);
+
+    if(j.salt_mismatches.length > 0) {
+        AUTH_EXPORT = null;
+        RQ.add_err(`This export (made ${j.created}) can't be restored, because these users' salts have changed since: ${j.salt_mismatches.join(", ")}`);
+        return;
+    }
+
+    const q = `This export was made ${j.created}. It will restore the passwords of ${j.n_matched} of its ${j.n_records} users. Users not in the data DB, who will be skipped: ${list_or_none(j.not_in_store)}. Users not in the export, whose passwords will stay as they are: ${list_or_none(j.not_in_export)}. Restore it?`;
+    if(await are_you_sure(q)) {
+        request_action("import-auth", AUTH_EXPORT, "Restoring auth DB.");
+    }
+    AUTH_EXPORT = null;
+}
+
+async function imported_auth(r) {
+    let j;
+    try {
+        j = await r.json();
+    } catch(e) {
+        RQ.add_err(e);
+        return;
+    }
+    console.log("import-auth response:", j);
+}
+
+function save_download(r, what) {
+    r.blob()
+    .then(blob => {
+        const fname = r.headers.get("Content-Disposition").match(FILENAME)[1];
+        const file_url = window.URL.createObjectURL(blob);
+        const link = document.createElement("A");
+        link.href = file_url;
+        link.download = fname;
+        link.click();
+        setTimeout(() => window.URL.revokeObjectURL(file_url), DOWNLOAD_DELAY);
+    })
+    .catch(e => {
+        console.log(e);
+        RQ.add_err(`There was an error downloading the ${what}; see the console for details.`);
+    });
+}
+
+function export_archive(evt) {
+    evt.preventDefault();
+    const id = this.getAttribute("data-id");
+    request_action("export-archive", id, "Downloading archive.");
+}
+
+function populate_archives(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-archives response:", j);
+        UTIL.clear(DISPLAY.archives_tbody);
+        if(j.length == 0) {
+            const tr = document.createElement("tr");
+            const td = UTIL.text_td("(nothing has been archived yet)");
+            td.setAttribute("colspan", 4);
+            tr.appendChild(td);
+            DISPLAY.archives_tbody.appendChild(tr);
+        }
+        for(const a of j) {
+            const tr = document.createElement("tr");
+            tr.appendChild(UTIL.text_td(a.year));
+            tr.appendChild(UTIL.text_td(a.archived));
+            tr.appendChild(UTIL.text_td(`${Math.ceil(a.size / 1024)} KiB`));
+            const td = document.createElement("td");
+            const butt = document.createElement("button");
+            butt.setAttribute("data-id", a.id);
+            UTIL.label("download", butt);
+            butt.addEventListener("click", export_archive);
+            td.appendChild(butt);
+            tr.appendChild(td);
+            DISPLAY.archives_tbody.appendChild(tr);
+        }
+    }).catch(RQ.add_err);
+}
+
+async function preview_reset(r) {
+    let j;
+    try {
+        j = await r.json();
+    } catch(e) {
+        RQ.add_err(e);
+        return;
+    }
+    console.log("preview-reset response:");
+    console.log(j);
+
+    const counts = Object.entries(j.counts)
+        .map(([table, n]) => `${table}: ${n}`)
+        .join(", ");
+    const unames = j.unames.length > 0 ? j.unames.join(", ") : "(none)";
+    const q = `Are you sure you want to exercise the scorched-earth nuclear option? This will delete ${counts} rows, belonging to these students: ${unames}. (Everything will be archived first.)`;
+    if(await are_you_sure(q)) {
+        request_action("reset-students", j.token, "Deleting all student data.");
+    }
+}
+
+document.getElementById("roster-sync-button")
+    .addEventListener("click", () => {
+        request_action("roster-preview", null, "Comparing roster with Students.");
+    });
+document.getElementById("roster-sync-cancel")
+    .addEventListener("click", (evt) => {
+        evt.preventDefault();
+        DISPLAY.roster_sync.close();
+    });
+document.getElementById("roster-sync-fetch")
+    .addEventListener("click", (evt) => {
+        evt.preventDefault();
+        DISPLAY.roster_sync.close();
+        request_action("roster-fetch", null, "Fetching roster.");
+    });
+document.getElementById("roster-sync-confirm")
+    .addEventListener("click", async function(evt) {
+        evt.preventDefault();
+        const form = document.forms["roster-sync"];
+        const body = {
+            "token": form.elements["token"].value,
+            "withdraw": form.elements["withdraw"].checked,
+        };
+        DISPLAY.roster_sync.close();
+        if(body.withdraw) {
+            const q = "Withdrawn Students will be deleted, along with all their goals.";
+            if(!(await are_you_sure(q))) {
+                return;
+            }
         }
+        request_action("roster-apply", body, "Applying roster changes.");
     });
 
+function roster_list(title, items, describe) {
+    const div = document.createElement("div");
+    const h = document.createElement("h3");
+    UTIL.set_text(h, `${title} (${items.length})`);
+    div.appendChild(h);
+    const ul = document.createElement("ul");
+    for(const item of items) {
+        const li = document.createElement("li");
+        UTIL.set_text(li, describe(item));
+        ul.appendChild(li);
+    }
+    div.appendChild(ul);
+    return div;
+}
+
+function preview_roster(r) {
+    r.json()
+    .then(j => {
+        console.log("roster-preview response:", j);
+
+        const status = document.getElementById("roster-sync-status");
+        const changes = document.getElementById("roster-sync-changes");
+        const form = document.forms["roster-sync"];
+        UTIL.clear(changes);
+        form.elements["withdraw"].checked = false;
+        form.elements["token"].value = "";
+        document.getElementById("roster-sync-fetch").disabled = !j.configured;
+        document.getElementById("roster-sync-confirm").disabled = true;
+
+        let msg = j.configured ? "" : "Roster sync is not configured. ";
+        if(j.state.last_error) {
+            msg = msg + `The last fetch failed: ${j.state.last_error} `;
+        }
+        if(!j.diff) {
+            UTIL.set_text(status, msg + "No roster has been fetched yet.");
+            DISPLAY.roster_sync.showModal();
+            return;
+        }
+        msg = msg + `Roster fetched ${j.state.fetched}.`;
+        if(j.state.applied) {
+            msg = msg + ` Changes last applied ${j.state.applied}.`;
+        }
+        UTIL.set_text(status, msg);
+
+        const d = j.diff;
+        const name = x => `${x.uname} (${x.rest} ${x.last})`;
+        changes.appendChild(roster_list("Add", d.adds, x => `${name(x)} to ${x.teacher}`));
+        changes.appendChild(roster_list("Move", d.moves, x => `${name(x)} from ${x.from} to ${x.to}`));
+        changes.appendChild(roster_list("Withdraw", d.withdrawals, x => `${name(x)} from ${x.teacher}`));
+        if(d.problems.length > 0) {
+            changes.appendChild(roster_list("Skipped", d.problems, x => x));
+        }
+
+        form.elements["token"].value = d.token;
+        const any = d.adds.length + d.moves.length + d.withdrawals.length > 0;
+        document.getElementById("roster-sync-confirm").disabled = !any;
+        DISPLAY.roster_sync.showModal();
+    })
+    .catch(RQ.add_err);
+}
+
+function roster_applied(r) {
+    r.json()
+    .then(j => {
+        console.log("roster-apply response:", j);
+        for(const f of j.failed) {
+            RQ.add_err(`Unable to apply roster change for ${f.uname}: ${f.error}`);
+        }
+        request_action("populate-users", null, "Refreshing Users.");
+    })
+    .catch(RQ.add_err);
+}
+
 function add_completion(evt) {
     evt.preventDefault();
 
@@ -1215,6 +1992,319 @@ function delete_completion(evt) {
     request_action("delete-completion", null, desc, extra_headers);
 }
 
+function make_test_status_button(id, status) {
+    const butt = document.createElement("button");
+    butt.setAttribute("data-id", id);
+    butt.setAttribute("data-status", status);
+    UTIL.label(status, butt);
+    butt.addEventListener("click", set_test_status);
+    return butt;
+}
+
+function load_test_requests(j) {
+    UTIL.clear(DISPLAY.test_tbody);
+    for(const t of j) {
+        const tr = document.createElement("tr");
+        tr.setAttribute("data-status", t.status);
+        tr.appendChild(UTIL.text_td(t.requested));
+        const stud = UTIL.text_td(`${t.last}, ${t.rest}`);
+        stud.setAttribute("title", t.uname);
+        tr.appendChild(stud);
+        tr.appendChild(UTIL.text_td(t.teacher));
+        const crs_td = UTIL.text_td(t.sym);
+        const crs = DATA.courses.get(t.sym);
+        if(crs) { crs_td.setAttribute("title", crs.title); }
+        tr.appendChild(crs_td);
+        tr.appendChild(UTIL.text_td(t.seq));
+        tr.appendChild(UTIL.text_td(t.status));
+
+        const td = document.createElement("td");
+        if(t.status == "requested") {
+            td.appendChild(make_test_status_button(t.id, "printed"));
+        }
+        if(t.status != "delivered") {
+            td.appendChild(make_test_status_button(t.id, "delivered"));
+        }
+        tr.appendChild(td);
+
+        DISPLAY.test_tbody.appendChild(tr);
+    }
+}
+
+function populate_test_requests(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-test-requests response:", j);
+        load_test_requests(j);
+    }).catch(RQ.add_err);
+}
+
+function request_test_requests() {
+    const body = DISPLAY.show_delivered.checked ? "all" : "";
+    request_action("populate-test-requests", body, "Fetching test requests...");
+}
+
+function set_test_status(evt) {
+    const body = {
+        id: Number(this.getAttribute("data-id")),
+        status: this.getAttribute("data-status"),
+        all: DISPLAY.show_delivered.checked,
+    };
+    request_action("set-test-status", body, `Marking test ${body.status}...`);
+}
+
+document.getElementById("refresh-test-requests")
+    .addEventListener("click", request_test_requests);
+DISPLAY.show_delivered.addEventListener("change", request_test_requests);
+
+function load_webhooks(j) {
+    DATA.webhooks = new Map(j.map(h => [h.id, h]));
+    UTIL.clear(DISPLAY.webhook_tbody);
+    for(const h of j) {
+        const tr = document.createElement("tr");
+        tr.appendChild(UTIL.text_td(h.url));
+        tr.appendChild(UTIL.text_td(h.events.join(", ")));
+        tr.appendChild(UTIL.text_td(h.secret));
+        tr.appendChild(UTIL.text_td(h.active ? "yes" : "no"));
+
+        const td = document.createElement("td");
+        const toggle = document.createElement("button");
+        toggle.setAttribute("data-id", h.id);
+        UTIL.label(h.active ? "deactivate" : "activate", toggle);
+        toggle.addEventListener("click", toggle_webhook);
+        td.appendChild(toggle);
+        const del = document.createElement("button");
+        del.setAttribute("data-id", h.id);
+        UTIL.label("remove", del);
+        del.addEventListener("click", delete_webhook);
+        td.appendChild(del);
+        tr.appendChild(td);
+
+        DISPLAY.webhook_tbody.appendChild(tr);
+    }
+}
+
+function populate_webhooks(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-webhooks response:", j);
+        load_webhooks(j);
+    }).catch(RQ.add_err);
+}
+
+function load_deliveries(j) {
+    UTIL.clear(DISPLAY.delivery_tbody);
+    for(const d of j) {
+        const tr = document.createElement("tr");
+        tr.setAttribute("data-status", d.status);
+        tr.appendChild(UTIL.text_td(d.created));
+        tr.appendChild(UTIL.text_td(d.url));
+        tr.appendChild(UTIL.text_td(d.event));
+        tr.appendChild(UTIL.text_td(d.status));
+        tr.appendChild(UTIL.text_td(d.attempts));
+        tr.appendChild(UTIL.text_td(d.next_attempt || ""));
+        const resp = UTIL.text_td(d.response || (d.error ? "error" : ""));
+        if(d.error) { resp.setAttribute("title", d.error); }
+        tr.appendChild(resp);
+
+        DISPLAY.delivery_tbody.appendChild(tr);
+    }
+}
+
+function populate_deliveries(r) {
+    r.json()
+    .then(j => {
+        console.log("webhook-deliveries response:", j);
+        load_deliveries(j);
+    }).catch(RQ.add_err);
+}
+
+function request_deliveries() {
+    request_action("webhook-deliveries", "", "Fetching webhook deliveries...");
+}
+
+function load_failed_emails(j) {
+    UTIL.clear(DISPLAY.failed_email_tbody);
+    for(const f of j) {
+        const tr = document.createElement("tr");
+        tr.appendChild(UTIL.text_td(f.created));
+        tr.appendChild(UTIL.text_td(f.about));
+        tr.appendChild(UTIL.text_td(f.to));
+        tr.appendChild(UTIL.text_td(f.subject));
+        tr.appendChild(UTIL.text_td(f.attempts));
+        tr.appendChild(UTIL.text_td(f.error || ""));
+
+        DISPLAY.failed_email_tbody.appendChild(tr);
+    }
+}
+
+function populate_failed_emails(r) {
+    r.json()
+    .then(j => {
+        console.log("failed-emails response:", j);
+        load_failed_emails(j);
+    }).catch(RQ.add_err);
+}
+
+function request_failed_emails() {
+    request_action("failed-emails", "", "Fetching failed emails...");
+}
+
+function add_webhook(evt) {
+    evt.preventDefault();
+    const url_ipt = document.querySelector("tbody#add-webhook input[name='url']");
+    const boxes = document.querySelectorAll("tbody#add-webhook input[name='event']");
+    const url = url_ipt.value.trim();
+    const events = [];
+    for(const box of boxes) {
+        if(box.checked) { events.push(box.value); }
+    }
+    if(!url || events.length == 0) {
+        RQ.add_err("A webhook needs a URL and at least one event.");
+        return;
+    }
+
+    url_ipt.value = "";
+    for(const box of boxes) { box.checked = false; }
+    request_action("add-webhook", { url: url, events: events }, `Adding webhook for ${url}...`);
+}
+
+function toggle_webhook(evt) {
+    const h = DATA.webhooks.get(Number(this.getAttribute("data-id")));
+    const body = {
+        id: h.id,
+        url: h.url,
+        events: h.events,
+        active: !h.active,
+    };
+    const verb = h.active ? "Deactivating" : "Activating";
+    request_action("update-webhook", body, `${verb} webhook for ${h.url}...`);
+}
+
+async function delete_webhook(evt) {
+    const h = DATA.webhooks.get(Number(this.getAttribute("data-id")));
+    if(await are_you_sure(`Remove the webhook for ${h.url}, and its delivery log?`)) {
+        request_action("delete-webhook", String(h.id), `Removing webhook for ${h.url}...`);
+    }
+}
+
+document.getElementById("add-webhook-add")
+    .addEventListener("click", add_webhook);
+document.getElementById("refresh-webhook-deliveries")
+    .addEventListener("click", request_deliveries);
+document.getElementById("refresh-failed-emails")
+    .addEventListener("click", request_failed_emails);
+document.getElementById("webhook-tab-radio")
+    .addEventListener("change", () => {
+        request_action("populate-webhooks", "", "Fetching webhooks...");
+        request_deliveries();
+        request_failed_emails();
+    });
+
+/*
+
+REPORT TEMPLATES section
+
+*/
+
+function load_report_templates(j) {
+    DATA.report_templates = new Map(j.map(t => [t.id, t]));
+    UTIL.clear(DISPLAY.report_template_tbody);
+    if(j.length == 0) {
+        const tr = document.createElement("tr");
+        const td = UTIL.text_td("(none uploaded; the built-in templates are in use)");
+        td.setAttribute("colspan", "6");
+        tr.appendChild(td);
+        DISPLAY.report_template_tbody.appendChild(tr);
+    }
+    for(const t of j) {
+        const tr = document.createElement("tr");
+        tr.appendChild(UTIL.text_td(t.kind));
+        tr.appendChild(UTIL.text_td(t.label));
+        tr.appendChild(UTIL.text_td(t.uploaded));
+        tr.appendChild(UTIL.text_td(t.uploaded_by));
+        tr.appendChild(UTIL.text_td(t.active ? "yes" : "no"));
+
+        const td = document.createElement("td");
+        const prev = document.createElement("button");
+        prev.setAttribute("data-id", t.id);
+        UTIL.label("preview", prev);
+        prev.addEventListener("click", preview_report_template);
+        td.appendChild(prev);
+        const toggle = document.createElement("button");
+        toggle.setAttribute("data-id", t.id);
+        UTIL.label(t.active ? "use built-in" : "activate", toggle);
+        toggle.addEventListener("click", toggle_report_template);
+        td.appendChild(toggle);
+        tr.appendChild(td);
+
+        DISPLAY.report_template_tbody.appendChild(tr);
+    }
+}
+
+function populate_report_templates(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-report-templates response:", j);
+        load_report_templates(j);
+    }).catch(RQ.add_err);
+}
+
+function upload_report_template(evt) {
+    evt.preventDefault();
+    const row = document.querySelector("tbody#add-report-template");
+    const kind = row.querySelector("select[name='kind']").value;
+    const label_ipt = row.querySelector("input[name='label']");
+    const file_ipt = row.querySelector("input[name='file']");
+    const label = label_ipt.value.trim();
+    if(!label || file_ipt.files.length == 0) {
+        RQ.add_err("A report template needs a label and a file.");
+        return;
+    }
+
+    UTIL.get_file_as_text(file_ipt.files[0])
+    .then((text) => {
+        label_ipt.value = "";
+        file_ipt.value = "";
+        const body = { kind: kind, label: label, body: text };
+        request_action("upload-report-template", body, `Uploading ${kind} template ${label}...`);
+    })
+    .catch((err) => {
+        RQ.add_err(`Error opening local file: ${err}`);
+    });
+}
+
+function preview_report_template(evt) {
+    const t = DATA.report_templates.get(Number(this.getAttribute("data-id")));
+    const uname = DISPLAY.preview_uname.value.trim();
+    if(!uname) {
+        RQ.add_err("Enter the uname of a student to preview the template with.");
+        DISPLAY.preview_uname.focus();
+        return;
+    }
+    const body = { id: t.id, uname: uname };
+    if(DISPLAY.preview_term.value) { body.term = DISPLAY.preview_term.value; }
+    request_action("preview-report-template", body, `Previewing ${t.label} for ${uname}...`);
+}
+
+function toggle_report_template(evt) {
+    const t = DATA.report_templates.get(Number(this.getAttribute("data-id")));
+    if(t.active) {
+        const body = { kind: t.kind, id: null };
+        request_action("activate-report-template", body, `Using built-in ${t.kind} template...`);
+    } else {
+        const body = { kind: t.kind, id: t.id };
+        request_action("activate-report-template", body, `Activating ${t.label}...`);
+    }
+}
+
+document.getElementById("add-report-template-upload")
+    .addEventListener("click", upload_report_template);
+document.getElementById("report-template-tab-radio")
+    .addEventListener("change", () => {
+        request_action("populate-report-templates", "", "Fetching report templates...");
+    });
+
 /*
 
 PAGE LOAD SECTION
@@ -1224,7 +2314,6 @@ PAGE LOAD SECTION
 console.log(DISPLAY);
 
 UTIL.ensure_on_load(() => {
-    request_action("populate-users", "", "Fetching User data...");
-    request_action("populate-completion", "", "Fetching Course completion history...");
-    request_action("populate-courses", "", "Fetching Course data...");
+    request_action("bootstrap", "", "Fetching Users, Courses, and completion history...");
+    request_test_requests();
 });
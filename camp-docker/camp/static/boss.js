@@ -3,12 +3,6 @@
 
 const API_ENDPOINT = "/boss";
 
-// Regex for extracting filename from Content-Disposition header.
-const FILENAME = /; filename="([^"]+)"/;
-// Time (in ms) to wait for an object to start downloading before its
-// ObjectURL is revoked.
-const DOWNLOAD_DELAY = 5000;
-
 // Sorting functions for sorting pace calendar tables.
 const SORTS = {
     "name": (a, b) => a.getAttribute("data-name").localeCompare(b.getAttribute("data-name")),
@@ -93,6 +87,19 @@ const DISPLAY = {
     "email_edit_submit": document.getElementById("edit-email-confirm"),
     "pdf_view": document.getElementById("view-pdf"),
     "completion_view": document.getElementById("view-completion"),
+    "what_if": document.getElementById("what-if"),
+    "what_if_answer": document.getElementById("what-if-answer"),
+    "announcements": document.getElementById("announcements"),
+    "announcement_list": document.getElementById("announcement-list"),
+    "at_risk": document.getElementById("at-risk"),
+    "at_risk_summary": document.getElementById("at-risk-summary"),
+    "at_risk_list": document.getElementById("at-risk-list"),
+    "teacher_email": document.getElementById("teacher-email"),
+    "email_health": document.getElementById("email-health"),
+    "email_health_warning": document.getElementById("email-health-warning"),
+    "email_health_summary": document.getElementById("email-health-summary"),
+    "email_health_failures": document.getElementById("email-health-failures"),
+    "teacher_email_list": document.getElementById("teacher-email-list"),
 }
 
 function edit_email(r) {
@@ -102,6 +109,10 @@ function edit_email(r) {
         UTIL.set_text(name_span, j.student_name);
         DISPLAY.email_text.value = j.text;
         DISPLAY.email_edit_submit.setAttribute("data-uname", j.uname);
+        if(j.unsubscribed) {
+            RQ.add_err(`${j.student_name}'s parent has unsubscribed from progress emails; this email will not be sent.`);
+            return;
+        }
         DISPLAY.edit_dialog.showModal();
 
     }).catch(RQ.add_err)
@@ -122,17 +133,17 @@ function display_pdf(r) {
     });
 }
 
-function save_archive(r) {
-    r.blob()
-    .then(blob => {
-        const fname = r.headers.get("Content-Disposition").match(FILENAME)[1];
-        const file_url = window.URL.createObjectURL(blob);
+// The archive is written to a temporary file on the server; following the
+// link (instead of fetching it here) lets the browser's download manager
+// resume the download if it gets interrupted.
+function follow_archive_link(r) {
+    r.json()
+    .then(j => {
+        console.log("archive-link response:", j);
         const link = document.createElement("A");
-        link.href = file_url;
-        link.download = fname;
+        link.href = j.link;
+        link.download = j.filename;
         link.click();
-        // Give it a chance to get the download started. Is this hacky? Yes.
-        setTimeout(() => window.URL.revokeObjectURL(file_url), DOWNLOAD_DELAY);
     })
     .catch(e => {
         console.log(e),
@@ -140,12 +151,162 @@ function save_archive(r) {
     });
 }
 
+function show_what_if(r) {
+    r.json()
+    .then(j => {
+        const pct = x => Math.round(x * 10) / 10;
+        const exam = Math.round(j.exam_fraction * 100);
+        let answer = `Test average ${pct(j.tests)}, exam worth ${exam}%, ${j.notices} notice(s): `;
+        if(j.required === null) {
+            answer += "the exam doesn't count, so it makes no difference.";
+        } else if(j.required > 100) {
+            answer += `needs ${pct(j.required)} on the exam, which would take extra credit.`;
+        } else if(j.required <= 0) {
+            answer += `gets at least ${j.target} no matter what.`;
+        } else {
+            answer += `needs at least ${pct(j.required)} on the exam.`;
+        }
+        UTIL.set_text(DISPLAY.what_if_answer, answer);
+    }).catch(RQ.add_err);
+}
+
+function populate_announcements(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-announcements response:", j);
+
+        const today = new Date().toISOString().slice(0, 10);
+        UTIL.clear(DISPLAY.announcement_list);
+        for(const a of j) {
+            const tr = document.createElement("tr");
+            for(const text of [a.message, a.roles.join(", "), a.starts, a.ends]) {
+                tr.appendChild(UTIL.text_td(text));
+            }
+
+            const td = document.createElement("td");
+            if(a.ends < today) {
+                tr.setAttribute("class", "expired");
+            } else {
+                const butt = document.createElement("button");
+                butt.setAttribute("data-id", a.id);
+                UTIL.label("expire", butt);
+                butt.addEventListener("click", expire_announcement);
+                td.appendChild(butt);
+            }
+            tr.appendChild(td);
+
+            DISPLAY.announcement_list.appendChild(tr);
+        }
+    }).catch(RQ.add_err);
+}
+
+function populate_at_risk(r) {
+    r.json()
+    .then(j => {
+        console.log("at-risk response:", j);
+
+        const pct = x => Math.round(x * 10) / 10;
+        UTIL.set_text(
+            DISPLAY.at_risk_summary,
+            `${j.term} semester, projected below ${pct(j.threshold)}: ${j.students.length} student(s).`
+        );
+        UTIL.clear(DISPLAY.at_risk_list);
+        for(const s of j.students) {
+            const tr = document.createElement("tr");
+            const exam = (s.exam === null) ? "" : pct(s.exam);
+            const cols = [
+                `${s.last}, ${s.rest}`, s.teacher, pct(s.tests), s.notices, exam,
+                `${Math.round(s.remaining * 100)}%`, pct(s.projected), pct(s.best),
+            ];
+            for(const text of cols) {
+                tr.appendChild(UTIL.text_td(text));
+            }
+            // Even perfect scores from here on won't get them there.
+            if(s.best < j.threshold) {
+                tr.setAttribute("class", "out-of-reach");
+            }
+            DISPLAY.at_risk_list.appendChild(tr);
+        }
+    }).catch(RQ.add_err);
+}
+
+function populate_email_health(r) {
+    r.json()
+    .then(j => {
+        console.log("email-health response:", j);
+
+        const when = secs => (secs === null) ? "never" : new Date(secs * 1000).toLocaleString();
+        UTIL.set_text(DISPLAY.email_health_warning, j.warning || "");
+        UTIL.clear(DISPLAY.email_health_summary);
+        const rows = [
+            ["backend", `${j.backend} (${j.destination})`],
+            ["last sent", when(j.last_success)],
+            ["sending now", j.pending],
+            ["sent", j.sent],
+            ["failed", j.failed],
+            ["failed since last sent", j.consecutive_failures],
+        ];
+        for(const [label, value] of rows) {
+            const tr = document.createElement("tr");
+            tr.appendChild(UTIL.text_td(label));
+            tr.appendChild(UTIL.text_td(value));
+            DISPLAY.email_health_summary.appendChild(tr);
+        }
+
+        UTIL.clear(DISPLAY.email_health_failures);
+        for(const f of j.recent_failures) {
+            const tr = document.createElement("tr");
+            tr.appendChild(UTIL.text_td(when(f.at)));
+            tr.appendChild(UTIL.text_td(f.reason));
+            DISPLAY.email_health_failures.appendChild(tr);
+        }
+    }).catch(RQ.add_err);
+}
+
+function compose_teacher_email(r) {
+    r.json()
+    .then(j => {
+        console.log("compose-teacher-email response:", j);
+
+        const form = document.forms["teacher-email"];
+        form.elements["subject"].value = j.subject;
+        form.elements["text"].value = j.text;
+        UTIL.clear(DISPLAY.teacher_email_list);
+        for(const t of j.teachers) {
+            const li = document.createElement("li");
+            const box = document.createElement("input");
+            box.setAttribute("type", "checkbox");
+            box.setAttribute("id", `teacher-email-${t.uname}`);
+            box.setAttribute("name", "unames");
+            box.setAttribute("value", t.uname);
+            li.appendChild(box);
+            const lab = document.createElement("label");
+            lab.setAttribute("for", box.id);
+            UTIL.set_text(lab, `${t.name} (${t.n_behind} of ${t.n_students} behind)`);
+            li.appendChild(lab);
+            DISPLAY.teacher_email_list.appendChild(li);
+        }
+        DISPLAY.teacher_email.showModal();
+    }).catch(RQ.add_err);
+}
+
+function report_teacher_email(r) {
+    r.json()
+    .then(j => {
+        console.log("email-teachers response:", j);
+
+        for(const f of j.failed) {
+            RQ.add_err(`Unable to email ${f.uname}: ${f.error}`);
+        }
+    }).catch(RQ.add_err);
+}
+
 function field_response(r) {
     if(!r.ok) {
         r.text()
         .then(t => {
             const status = r.statusText || "[no reason phrase (HTTP/2 is boring)]";
-            const err_text = `${t}\n(${r.status}: ${status})`;
+            const err_text = `${UTIL.error_text(t)}\n(${r.status}: ${status})`;
             RQ.add_err(err_text);
         }).catch(e => {
             console.log("Uncaught error:", e);
@@ -167,8 +328,20 @@ function field_response(r) {
             edit_email(r); break;
         case "download-pdf":
             display_pdf(r); break;
-        case "download-archive":
-            save_archive(r); break;
+        case "archive-link":
+            follow_archive_link(r); break;
+        case "what-if":
+            show_what_if(r); break;
+        case "populate-announcements":
+            populate_announcements(r); break;
+        case "email-health":
+            populate_email_health(r); break;
+        case "at-risk":
+            populate_at_risk(r); break;
+        case "compose-teacher-email":
+            compose_teacher_email(r); break;
+        case "email-teachers":
+            report_teacher_email(r); break;
         case "none": /* No action required, obviously. */
             break;
         default:
@@ -257,6 +430,34 @@ for(const butt of document.querySelectorAll("tr.extra button.download-report"))
     butt.addEventListener("click", download_report);
 }
 
+function open_what_if(evt) {
+    evt.preventDefault();
+    const confirm = document.getElementById("what-if-confirm");
+    confirm.setAttribute("data-uname", this.getAttribute("data-uname"));
+    UTIL.set_text(document.getElementById("what-if-name"), this.getAttribute("data-name"));
+    UTIL.set_text(DISPLAY.what_if_answer, "");
+    DISPLAY.what_if.showModal();
+}
+
+for(const butt of document.querySelectorAll("tr.extra button.what-if")) {
+    butt.addEventListener("click", open_what_if);
+}
+
+document.getElementById("what-if-confirm").addEventListener("click", function(evt) {
+    evt.preventDefault();
+    const data = new FormData(document.forms["what-if"]);
+    const body = {
+        "uname": this.getAttribute("data-uname"),
+        "term": data.get("term"),
+        "target": Number(data.get("target")),
+    };
+    request_action("what-if", body, "Calculating required exam score.");
+});
+document.getElementById("what-if-cancel").addEventListener("click", evt => {
+    evt.preventDefault();
+    DISPLAY.what_if.close();
+});
+
 document.getElementById("view-pdf-cancel")
     .addEventListener("click", evt => {
         evt.preventDefault();
@@ -304,4 +505,124 @@ document.getElementById("show-completion").addEventListener("click", evt => {
 document.getElementById("view-completion-close").addEventListener("click", evt => {
     evt.preventDefault();
     DISPLAY.completion_view.close();
-});
\ No newline at end of file
+});
+
+document.getElementById("show-announcements").addEventListener("click", evt => {
+    evt.preventDefault();
+    request_action("populate-announcements", null, "Fetching announcements.");
+    DISPLAY.announcements.showModal();
+});
+document.getElementById("announcements-close").addEventListener("click", evt => {
+    evt.preventDefault();
+    DISPLAY.announcements.close();
+});
+
+document.getElementById("announcements-add").addEventListener("click", evt => {
+    evt.preventDefault();
+    const form = document.forms["announcements"];
+    if(!form.reportValidity()) {
+        return;
+    }
+    const data = new FormData(form);
+    const body = {
+        "message": data.get("message").trim(),
+        "roles": data.getAll("roles"),
+        "starts": data.get("starts"),
+        "ends": data.get("ends"),
+    };
+    if(body.roles.length == 0) {
+        RQ.add_err("An announcement has to be shown to someone.");
+        return;
+    }
+    request_action("add-announcement", body, "Posting announcement.");
+    form.reset();
+});
+
+function request_at_risk() {
+    const data = new FormData(document.forms["at-risk"]);
+    const body = {};
+    if(data.get("term")) {
+        body.term = data.get("term");
+    }
+    if(data.get("threshold")) {
+        body.threshold = Number(data.get("threshold"));
+    }
+    request_action("at-risk", body, "Projecting semester grades.");
+}
+
+document.getElementById("show-at-risk").addEventListener("click", evt => {
+    evt.preventDefault();
+    request_at_risk();
+    DISPLAY.at_risk.showModal();
+});
+document.getElementById("at-risk-refresh").addEventListener("click", evt => {
+    evt.preventDefault();
+    request_at_risk();
+});
+document.getElementById("at-risk-close").addEventListener("click", evt => {
+    evt.preventDefault();
+    DISPLAY.at_risk.close();
+});
+
+function show_email_health(evt) {
+    evt.preventDefault();
+    request_action("email-health", null, "Checking on email.");
+    DISPLAY.email_health.showModal();
+}
+
+document.getElementById("show-email-health").addEventListener("click", show_email_health);
+document.getElementById("email-warning-details")?.addEventListener("click", show_email_health);
+document.getElementById("email-health-refresh").addEventListener("click", evt => {
+    evt.preventDefault();
+    request_action("email-health", null, "Checking on email.");
+});
+document.getElementById("email-health-close").addEventListener("click", evt => {
+    evt.preventDefault();
+    DISPLAY.email_health.close();
+});
+
+document.getElementById("show-teacher-email").addEventListener("click", evt => {
+    evt.preventDefault();
+    request_action("compose-teacher-email", null, "Composing Teacher email.");
+});
+document.getElementById("teacher-email-cancel").addEventListener("click", evt => {
+    evt.preventDefault();
+    DISPLAY.teacher_email.close();
+});
+
+document.getElementById("teacher-email-send").addEventListener("click", evt => {
+    evt.preventDefault();
+    const form = document.forms["teacher-email"];
+    if(!form.reportValidity()) {
+        return;
+    }
+    const data = new FormData(form);
+    let recipients = "all";
+    switch(data.get("recipients")) {
+        case "lag":
+            recipients = { "lag": Number(data.get("lag")) / 100 };
+            break;
+        case "unames":
+            recipients = { "unames": data.getAll("unames") };
+            if(recipients.unames.length == 0) {
+                RQ.add_err("Check the Teachers to send the email to.");
+                return;
+            }
+            break;
+    }
+    const body = {
+        "subject": data.get("subject"),
+        "text": data.get("text"),
+        "recipients": recipients,
+    };
+    request_action("email-teachers", body, "Emailing Teachers.");
+    DISPLAY.teacher_email.close();
+});
+
+async function expire_announcement(evt) {
+    evt.preventDefault();
+    const id = this.getAttribute("data-id");
+    if(await are_you_sure("Stop showing this announcement?")) {
+        request_action("expire-announcement", id, "Expiring announcement.");
+    }
+}
@@ -10,6 +10,9 @@ const CAL = {
     target_div: document.getElementById("calendar-display"),
     year_selector: document.getElementById("cal-year"),
     date_form: document.forms["cal-dates-form"],
+    year_form: document.forms["cal-academic-year-form"],
+    exam_form: document.forms["cal-exam-windows-form"],
+    exam_tbody: document.querySelector("table#cal-exam-windows > tbody"),
     month_names: {
         0: "Jan",
         1: "Feb",
@@ -25,6 +28,10 @@ const CAL = {
         11: "Dec",
     },
     current_academic_year: function() {
+        // Set by the server from the Admin-supplied academic year, if any.
+        if(ACADEMIC_YEAR) {
+            return ACADEMIC_YEAR;
+        }
         const today = new Date();
         if(today.getMonth() < 7) {
             return today.getFullYear() - 1;
@@ -159,7 +166,7 @@ CAL.set_local = function(r) {
     if(!r.ok) {
         r.text()
         .then(t => {
-            const err_txt = `${t}\n(${r.status}: ${r.statusText})`;
+            const err_txt = `${UTIL.error_text(t)}\n(${r.status}: ${r.statusText})`;
             RQ.add_err(err_txt);
         }
         ).catch(e => {
@@ -204,6 +211,91 @@ CAL.populate_dates = function(r) {
     .catch(RQ.add_err)
 }
 
+CAL.populate_years = function(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-years body:", j)
+        const current = j.find(y => y.active);
+        for(const input of CAL.year_form.elements) {
+            if(input.name) {
+                input.value = current ? current[input.name] : "";
+            }
+        }
+    })
+    .catch(RQ.add_err)
+}
+
+CAL.save_year = function(evt) {
+    evt.preventDefault();
+    const elts = CAL.year_form.elements;
+    const data = {
+        label: elts["label"].value.trim(),
+        starts: elts["starts"].value,
+        ends: elts["ends"].value,
+        active: true,
+    };
+    if(!data.label || !data.starts || !data.ends) {
+        RQ.add_err("The academic year needs a label, a start date, and an end date.");
+        return;
+    }
+    CAL.request_action("set-year", data, `Setting academic year ${data.label}.`);
+}
+
+CAL.populate_exam_windows = function(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-exam-windows body:", j)
+        UTIL.clear(CAL.exam_tbody);
+        for(const w of j) {
+            const tr = document.createElement("tr");
+            tr.appendChild(UTIL.text_td(w.name));
+            tr.appendChild(UTIL.text_td(w.start));
+            tr.appendChild(UTIL.text_td(w.end));
+            const td = document.createElement("td");
+            const butt = document.createElement("button");
+            butt.setAttribute("data-id", w.id);
+            butt.setAttribute("data-name", w.name);
+            UTIL.label("delete", butt);
+            butt.addEventListener("click", CAL.delete_exam_window);
+            td.appendChild(butt);
+            tr.appendChild(td);
+            CAL.exam_tbody.appendChild(tr);
+        }
+    })
+    .catch(RQ.add_err)
+}
+
+CAL.add_exam_window = function(evt) {
+    evt.preventDefault();
+    const elts = CAL.exam_form.elements;
+    const data = {
+        name: elts["name"].value.trim(),
+        start: elts["start"].value,
+        end: elts["end"].value,
+    };
+    if(!data.name || !data.start || !data.end) {
+        RQ.add_err("An exam window needs a name, a first day, and a last day.");
+        return;
+    }
+    if(data.end < data.start) {
+        RQ.add_err("An exam window can't end before it starts.");
+        return;
+    }
+    for(const name of ["name", "start", "end"]) {
+        elts[name].value = "";
+    }
+    CAL.request_action("add-exam-window", data, `Adding exam window ${data.name}.`);
+}
+
+CAL.delete_exam_window = async function(evt) {
+    evt.preventDefault();
+    const id = this.getAttribute("data-id");
+    const name = this.getAttribute("data-name");
+    if(await are_you_sure(`Delete exam window ${name}?`)) {
+        CAL.request_action("delete-exam-window", id, `Deleting exam window ${name}.`);
+    }
+}
+
 CAL.update_date = function(evt) {
     const data = [this.name, this.value];
     CAL.request_action("set-date", data, `Setting ${this.name}.`);
@@ -213,7 +305,7 @@ CAL.field_response = function(r) {
     if(!r.ok) {
         r.text()
         .then(t => {
-            const err_txt = `${t}\n(${r.status}: ${r.statusText})`;
+            const err_txt = `${UTIL.error_text(t)}\n(${r.status}: ${r.statusText})`;
             RQ.add_err(err_txt);
         }
         ).catch(e => {
@@ -238,6 +330,10 @@ CAL.field_response = function(r) {
         CAL.set_local(r);
     } else if(action == "populate-dates") {
         CAL.populate_dates(r);
+    } else if(action == "populate-years") {
+        CAL.populate_years(r);
+    } else if(action == "populate-exam-windows") {
+        CAL.populate_exam_windows(r);
     } else {
         const e_n = STATE.next_error();
         const err_txt = `CAL: Unrecognized x-camp-action header: ${action}. (See console error #${e_n})`;
@@ -246,11 +342,12 @@ CAL.field_response = function(r) {
     }
 }
 
-CAL.request_action = function(action, body, description) {
+CAL.request_action = function(action, body, description, extra_headers) {
     const options = {
         method: "POST",
         headers: {
             "x-camp-action": action,
+            ...extra_headers,
         }
     };
 
@@ -282,6 +379,24 @@ CAL.update_cal = function() {
     CAL.request_action("update-cal", Array.from(CAL.dates), "Updating calendar.")
 }
 
+CAL.upload_cal = function() {
+    const form = document.forms["upload-calendar"];
+    const data = new FormData(form);
+    const file = data.get("file");
+    const mode = data.get("mode");
+
+    UTIL.get_file_as_text(file)
+    .then((text) => {
+        document.getElementById("upload-calendar-dialog").close();
+        CAL.request_action(
+            "upload-calendar", text, "Uploading calendar.", { "x-camp-mode": mode }
+        );
+    })
+    .catch((err) => {
+        RQ.add_err(`Error opening local file: ${err}`);
+    })
+}
+
 document.getElementById("cal-prev-year")
     .addEventListener("click", () => {
         const new_year = Number(CAL.year_selector.value) - 1;
@@ -313,7 +428,19 @@ document.getElementById("cal-tab-radio")
             CAL.populate_year(CAL.target_div, cur_year);
             CAL.request_action("populate-cal", "", "Fetching calendar.");
             CAL.request_action("populate-dates", "", "Fetching dates.")
+            CAL.request_action("populate-years", "", "Fetching academic years.")
+            CAL.request_action("populate-exam-windows", "", "Fetching exam windows.")
         }
 });
 document.getElementById("cal-update")
-    .addEventListener("click", CAL.update_cal);
\ No newline at end of file
+    .addEventListener("click", CAL.update_cal);
+document.getElementById("cal-year-save")
+    .addEventListener("click", CAL.save_year);
+document.getElementById("cal-exam-window-add")
+    .addEventListener("click", CAL.add_exam_window);
+document.getElementById("cal-upload")
+    .addEventListener("click", () => {
+        document.getElementById("upload-calendar-dialog").showModal();
+    });
+document.getElementById("upload-calendar-confirm")
+    .addEventListener("click", CAL.upload_cal);
\ No newline at end of file
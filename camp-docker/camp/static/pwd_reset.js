@@ -41,7 +41,7 @@ function make_request(req, desc, on_success) {
         if(!r.ok) {
             r.text()
             .then(t => {
-                add_err(`Request Error: ${t} (${r.status}: ${r.statusText})`);
+                add_err(`Request Error: ${UTIL.error_text(t)} (${r.status}: ${r.statusText})`);
             }).catch(e => {
                 add_err(`Page Error: ${e}`);
             });
@@ -9,7 +9,14 @@ const DATA = {
     chapters: new Map(),
     paces: new Map(),
     goals: new Map(),
+    // The Teacher's custom chapters, by id.
+    customs: new Map(),
     traits: [],
+    // When the pace data was current; see sync_changes().
+    as_of: null,
+    // Which state of the course data we have; sent back with requests so
+    // the server can refuse changes based on out-of-date weights.
+    courses_token: null,
 };
 const DISPLAY = {
     course_list_div: document.getElementById("course-info"),
@@ -17,13 +24,21 @@ const DISPLAY = {
     course_list_genl: document.querySelector("table#genl-courses > tbody"),
     course_list_hs: document.querySelector("table#hs-courses > tbody"),
     calbox: document.getElementById("cals"),
+    announcements: document.getElementById("announcements"),
     upload_goals: document.getElementById("upload-goals-dialog"),
     goal_edit: document.getElementById("edit-goal"),
     goal_edit_meta: document.getElementById("edit-goal-meta"),
     course_input: document.getElementById("edit-goal-course"),
     seq_input: document.getElementById("edit-goal-seq"),
+    custom_input: document.getElementById("edit-goal-custom"),
+    customs_edit: document.getElementById("custom-chapters-dialog"),
+    absences_edit: document.getElementById("absences-dialog"),
+    absences_meta: document.getElementById("absences-meta"),
+    avoid_absences: document.getElementById("avoid-absences"),
     goal_complete: document.getElementById("complete-goal"),
     goal_complete_meta: document.getElementById("complete-goal-meta"),
+    goals_delete: document.getElementById("delete-goals"),
+    goals_delete_meta: document.getElementById("delete-goals-meta"),
     sidecar_edit: document.getElementById("edit-sidecar"),
     report_edit: document.getElementById("edit-report"),
     pdf_view: document.getElementById("view-pdf"),
@@ -92,9 +107,74 @@ function input_label_pair(label, id, name, type) {
 
 const PCAL_COLS = ["course", "chapter", "due", "done", "tries", "score", "edit"];
 
-function row_from_goal(g) {
+function is_waived(cal, sym, seq) {
+    return cal.waived.some(w => w.sym == sym && w.seq == seq);
+}
+
+function waive_button(uname, sym, seq, waived) {
+    const butt = document.createElement("button");
+    butt.setAttribute("data-uname", uname);
+    butt.setAttribute("data-sym", sym);
+    butt.setAttribute("data-seq", seq);
+    butt.setAttribute("data-waived", waived ? "true" : "false");
+    butt.setAttribute("title", waived ? "un-waive chapter" : "waive chapter");
+    UTIL.label("\u2298", butt);
+    butt.addEventListener("click", toggle_waiver);
+    return butt;
+}
+
+/*  Row for a waived chapter the student has no goal for. */
+function row_from_waiver(uname, w) {
+    const crs = DATA.courses.get(w.sym);
+    const chp = DATA.chapters.get(crs.chapters[w.seq]);
+
+    const tr = document.createElement("tr");
+    tr.setAttribute("class", "waived");
+
+    const ctd = UTIL.text_td(crs.title);
+    ctd.setAttribute("title", crs.book);
+    tr.appendChild(ctd);
+    const chtd = UTIL.text_td(chp.title);
+    if(chp.subject) { chtd.setAttribute("title", chp.subject); }
+    tr.appendChild(chtd);
+    const wtd = UTIL.text_td("waived");
+    wtd.setAttribute("colspan", "4");
+    tr.appendChild(wtd);
+
+    const etd = document.createElement("td");
+    etd.setAttribute("class", "edit");
+    etd.appendChild(waive_button(uname, w.sym, w.seq, true));
+    tr.appendChild(etd);
+
+    return tr;
+}
+
+/*
+The titles of the course and chapter a goal is for. Custom goals are listed
+under the "Custom" course, with the title of their custom chapter.
+*/
+function goal_names(g) {
+    if(g.custom != null) {
+        const cus = DATA.customs.get(g.custom);
+        return {
+            course: "Custom",
+            book: "",
+            chapter: cus ? cus.title : `custom chapter #${g.custom}`,
+            subject: null,
+        };
+    }
     const crs = DATA.courses.get(g.sym);
     const chp = DATA.chapters.get(crs.chapters[g.seq]);
+    return {
+        course: crs.title,
+        book: crs.book,
+        chapter: chp.title,
+        subject: chp.subject,
+    };
+}
+
+function row_from_goal(g) {
+    const names = goal_names(g);
 
     const tr = document.createElement("tr");
     tr.setAttribute("data-id", g.id);
@@ -127,22 +207,24 @@ function row_from_goal(g) {
         }
     }
 
-    const ctd = UTIL.text_td(crs.title);
-    ctd.setAttribute("title", crs.book);
+    const ctd = UTIL.text_td(names.course);
+    ctd.setAttribute("title", names.book);
     tr.appendChild(ctd);
 
-    let chtext = chp.title;
+    let chtext = names.chapter;
     if(g.rev) { chtext = chtext + " R"; }
     if(g.inc) { chtext = chtext + " I"; }
+    if(g.part) { chtext = `${chtext} (part ${g.part} of ${g.parts})`; }
     const chtd = UTIL.text_td(chtext)
-    if(chp.subject) { chtd.setAttribute("title", chp.subject); }
+    if(names.subject) { chtd.setAttribute("title", names.subject); }
     tr.appendChild(chtd);
 
     const duetd = UTIL.text_td(g.due || "")
     duetd.setAttribute("class", "due");
     tr.appendChild(duetd);
-    const donetd = UTIL.text_td(g.done || "");
+    const donetd = UTIL.text_td(g.done || (g.progress != null ? `${g.progress}%` : ""));
     donetd.setAttribute("class", "done");
+    if(!g.done && g.progress != null) { donetd.classList.add("progress"); }
     tr.appendChild(donetd);
     const triestd = UTIL.text_td(g.tries || "")
     triestd.setAttribute("class", "tries");
@@ -150,12 +232,50 @@ function row_from_goal(g) {
     const scoretd = document.createElement("td");
     if(g.score) {
         UTIL.set_text(scoretd, `${g.score} (${score2pct(g.score)})`);
+        if(g.rubric?.length) {
+            scoretd.setAttribute("title", rubric2text(g.rubric).replaceAll("\n", "; "));
+        }
     }
     scoretd.setAttribute("class", "score");
     tr.appendChild(scoretd);
 
     const etd = document.createElement("td");
     etd.setAttribute("class", "edit");
+    if(g.requested && !g.done) {
+        tr.classList.add("requested");
+        const dismiss = document.createElement("button");
+        dismiss.setAttribute("data-id", g.id);
+        dismiss.setAttribute("title", "student says this is done (click to dismiss)");
+        UTIL.label("\u270b", dismiss);
+        dismiss.addEventListener("click", dismiss_request);
+        etd.appendChild(dismiss);
+    }
+    if(g.test) {
+        tr.classList.add(`test-${g.test}`);
+        chtd.setAttribute("title", `${chtd.title || names.chapter} (test ${g.test})`);
+        UTIL.set_text(chtd, `${chtext} \u{1f4c4}`);
+    }
+    if(g.sym && !g.done) {
+        const test = document.createElement("button");
+        test.setAttribute("data-id", g.id);
+        if(g.test == "requested") {
+            test.setAttribute("title", "withdraw test request");
+            test.addEventListener("click", cancel_test);
+        } else {
+            test.setAttribute("title", "request printed test");
+            test.addEventListener("click", request_test);
+        }
+        UTIL.label("\u{1f5a8}", test);
+        etd.appendChild(test);
+    }
+    if(!g.done) {
+        const progress = document.createElement("button");
+        progress.setAttribute("data-id", g.id);
+        progress.setAttribute("title", "record progress");
+        UTIL.label("%", progress);
+        progress.addEventListener("click", set_progress);
+        etd.appendChild(progress);
+    }
     const complete = document.createElement("button");
     complete.setAttribute("data-id", g.id);
     complete.setAttribute("title", "complete goal");
@@ -168,6 +288,14 @@ function row_from_goal(g) {
     UTIL.label("\u270e", edit);
     edit.addEventListener("click", edit_goal);
     etd.appendChild(edit);
+    if(!g.part) {
+        const split = document.createElement("button");
+        split.setAttribute("data-id", g.id);
+        split.setAttribute("title", "split goal");
+        UTIL.label("\u2702", split);
+        split.addEventListener("click", split_goal);
+        etd.appendChild(split);
+    }
     tr.appendChild(etd);
 
     return tr;
@@ -249,11 +377,21 @@ function make_calendar_footer(cal) {
     autobutt.setAttribute("data-uname", cal.uname);
     autobutt.addEventListener("click", autopace);
     last_div.appendChild(autobutt);
+    const absbutt = document.createElement("button");
+    UTIL.label("absences", absbutt);
+    absbutt.setAttribute("data-uname", cal.uname);
+    absbutt.addEventListener("click", edit_absences);
+    last_div.appendChild(absbutt);
     const sidecarbutt = document.createElement("button");
     UTIL.label("report info", sidecarbutt);
     sidecarbutt.setAttribute("data-uname", cal.uname);
     sidecarbutt.addEventListener("click", edit_sidecar);
     last_div.appendChild(sidecarbutt);
+    const prune = document.createElement("button");
+    UTIL.label("delete some goals", prune);
+    prune.setAttribute("data-uname", cal.uname);
+    prune.addEventListener("click", delete_goals);
+    last_div.appendChild(prune);
     const nuke = document.createElement("button");
     UTIL.label("clear all goals", nuke);
     nuke.setAttribute("data-uname", cal.uname);
@@ -480,8 +618,26 @@ function make_calendar_table(cal) {
     let n_due = 0;
     let n_done = 0;
 
+    for(const w of cal.waived) {
+        if(!cal.goals.some(g => g.sym == w.sym && g.seq == w.seq)) {
+            tbody.appendChild(row_from_waiver(cal.uname, w));
+        }
+    }
+
     for(const g of cal.goals) {
-        tbody.appendChild(row_from_goal(g));
+        const tr = row_from_goal(g);
+        // Custom chapters can't be waived.
+        const waived = g.custom == null && is_waived(cal, g.sym, g.seq);
+        if(g.custom == null) {
+            tr.querySelector("td.edit").appendChild(
+                waive_button(cal.uname, g.sym, g.seq, waived)
+            );
+        }
+        tbody.appendChild(tr);
+        if(waived) {
+            tr.classList.add("waived");
+            continue;
+        }
         if(g.done) {
             n_done += 1;
         }
@@ -514,6 +670,19 @@ function make_calendar_table(cal) {
     UTIL.set_text(hist, "History");
     hist.addEventListener("click", request_history);
     prog.appendChild(hist);
+    prog.appendChild(document.createTextNode(" · "));
+    const dl = document.createElement("a");
+    dl.setAttribute("data-uname", cal.uname);
+    UTIL.set_text(dl, "CSV");
+    dl.addEventListener("click", request_goals_csv);
+    prog.appendChild(dl);
+    prog.appendChild(document.createTextNode(" · "));
+    const ics = document.createElement("a");
+    ics.setAttribute("data-uname", cal.uname);
+    ics.setAttribute("title", "due dates, for calendar apps");
+    UTIL.set_text(ics, "iCal");
+    ics.addEventListener("click", request_ical);
+    prog.appendChild(ics);
     prog.appendChild(document.createElement("br"));
     const numbers = document.createElement("span");
     let lead_pct = ratio2pct(cal.done_weight - cal.due_weight, cal.total_weight);
@@ -566,95 +735,111 @@ function make_calendar_table(cal) {
     return tab;
 }
 
+function load_courses(j) {
+    j.sort((a, b) => a.level - b.level);
+
+    DATA.courses = new Map();
+    DATA.chapters = new Map();
+    const list = document.getElementById("course-names");
+    UTIL.clear(list);
+    UTIL.clear(DISPLAY.course_list_genl);
+    UTIL.clear(DISPLAY.course_list_hs);
+
+    for(const crs of j) {
+        let chaps = new Array();
+        for(const chp of crs.chapters) {
+            DATA.chapters.set(chp.id, chp);
+            chaps[chp.seq] = chp.id;
+        }
+        crs.chapters = chaps;
+        DATA.courses.set(crs.sym, crs);
+
+        let book_text = "";
+        if(crs.book) { book_text = ` (${crs.book})`; }
+        const option_text = `${crs.sym}: ${crs.title}${book_text}`;
+        const opt = document.createElement("option");
+        opt.value = crs.sym;
+        UTIL.set_text(opt, option_text);
+        list.appendChild(opt);
+
+        const tr = document.createElement("tr");
+        tr.appendChild(UTIL.text_td(crs.sym));
+        const titletd = UTIL.text_td(crs.title);
+        if(crs.book) {
+            const cite = document.createElement("cite");
+            UTIL.set_text(cite, crs.book);
+            titletd.appendChild(cite);
+        }
+        tr.appendChild(titletd);
+        if(crs.level < 9.0) {
+            DISPLAY.course_list_genl.appendChild(tr);
+        } else {
+            DISPLAY.course_list_hs.appendChild(tr);
+        }
+    }
+}
+
 function populate_courses(r) {
     r.json()
     .then(j => {
         console.log("populate-courses response:", j);
-        j.sort((a, b) => a.level - b.level);
-
-        DATA.courses = new Map();
-        DATA.chapters = new Map();
-        const list = document.getElementById("course-names");
-        UTIL.clear(list);
-        UTIL.clear(DISPLAY.course_list_genl);
-        UTIL.clear(DISPLAY.course_list_hs);
-
-        for(const crs of j) {
-            let chaps = new Array();
-            for(const chp of crs.chapters) {
-                DATA.chapters.set(chp.id, chp);
-                chaps[chp.seq] = chp.id;
-            }
-            crs.chapters = chaps;
-            DATA.courses.set(crs.sym, crs);
+        load_courses(j);
+        request_action("populate-goals", "", "Populating pace calendars.");
+    })
+    .catch(log_numbered_error);
+}
 
-            let book_text = "";
-            if(crs.book) { book_text = ` (${crs.book})`; }
-            const option_text = `${crs.sym}: ${crs.title}${book_text}`;
-            const opt = document.createElement("option");
-            opt.value = crs.sym;
-            UTIL.set_text(opt, option_text);
-            list.appendChild(opt);
+function load_goals(j) {
+    DATA.paces = new Map();
+    DATA.goals = new Map();
+    UTIL.clear(DISPLAY.calbox);
+
+    j.sort((a, b) => {
+        if(a.last < b.last) {
+            return -1; 
+        } else if(a.last > b.last) {
+            return 1;
+        } else if(a.rest < b.rest) {
+            return -1;
+        } else if(a.rest > b.rest) {
+            return 1;
+        } else {
+            return 0;
+        }
+    })
 
-            const tr = document.createElement("tr");
-            tr.appendChild(UTIL.text_td(crs.sym));
-            const titletd = UTIL.text_td(crs.title);
-            if(crs.book) {
-                const cite = document.createElement("cite");
-                UTIL.set_text(cite, crs.book);
-                titletd.appendChild(cite);
-            }
-            tr.appendChild(titletd);
-            if(crs.level < 9.0) {
-                DISPLAY.course_list_genl.appendChild(tr);
-            } else {
-                DISPLAY.course_list_hs.appendChild(tr);
-            }
+    for(const p of j) {
+        DATA.paces.set(p.uname, p);
+        for(const g of p.goals) {
+            g.uname = p.uname;
+            DATA.goals.set(g.id, g);
         }
 
-        request_action("populate-goals", "", "Populating pace calendars.")
-    })
-    .catch(log_numbered_error);
+        const tab = make_calendar_table(p);
+        DISPLAY.calbox.appendChild(tab);
+    }
 }
 
 function populate_goals(r) {
     r.json()
     .then(j => {
         console.log("populate-goals response:", j);
-
-        DATA.paces = new Map();
-        DATA.goals = new Map();
-        UTIL.clear(DISPLAY.calbox);
-
-        j.sort((a, b) => {
-            if(a.last < b.last) {
-                return -1; 
-            } else if(a.last > b.last) {
-                return 1;
-            } else if(a.rest < b.rest) {
-                return -1;
-            } else if(a.rest > b.rest) {
-                return 1;
-            } else {
-                return 0;
-            }
-        })
-
-        for(const p of j) {
-            DATA.paces.set(p.uname, p);
-            for(const g of p.goals) {
-                g.uname = p.uname;
-                DATA.goals.set(g.id, g);
-            }
-
-            const tab = make_calendar_table(p);
-            DISPLAY.calbox.appendChild(tab);
-        }
+        load_goals(j);
     })
     .catch(log_numbered_error);
 }
 
 function replace_pace(r) {
+    const warning = r.headers.get("x-camp-warning");
+    if(warning) {
+        RQ.add_err(warning);
+    }
+    const deleted = r.headers.get("x-camp-deleted");
+    if(deleted == "0") {
+        RQ.add_err("No goals matched; nothing was deleted.");
+    } else if(deleted) {
+        console.log(`Deleted ${deleted} goals (${r.headers.get("x-camp-archived")} archived).`);
+    }
     r.json()
     .then(j => {
         console.log("update-pace response:", j);
@@ -675,61 +860,217 @@ function replace_pace(r) {
     .catch(log_numbered_error);
 }
 
+function load_dates(j) {
+    DATA.dates = new Map();
+    for(const [name, dstr] of Object.entries(j)) {
+        DATA.dates.set(name, UTIL.iso2date(dstr));
+    }
+}
+
 function populate_dates(r) {
     r.json()
     .then(j => {
         console.log("populate-dates response:", j);
-
-        DATA.dates = new Map();
-        for(const [name, dstr] of Object.entries(j)) {
-            DATA.dates.set(name, UTIL.iso2date(dstr));
-        }
+        load_dates(j);
     })
     .catch(log_numbered_error);
 }
 
+function load_traits(j) {
+    DATA.traits = j;
+    const cont = DISPLAY.sidecar_edit.querySelector("fieldset#trait-container");
+    let n = 0;
+    for(const trait of DATA.traits) {
+        const fid = `edit-sidecar-trait-${n}`;
+        const fipt = document.createElement("input");
+        fipt.setAttribute("data-trait", trait);
+        fipt.setAttribute("data-term", "fall");
+        fipt.id = fid;
+        const flab = document.createElement("label");
+        flab.setAttribute("for", fid);
+        flab.setAttribute("class", "r");
+        UTIL.set_text(flab, trait);
+        cont.appendChild(flab);
+        cont.appendChild(fipt);
+
+        n = n + 1;
+
+        const sid = `edit-sidecar-trait-${n}`;
+        const sipt = document.createElement("input");
+        sipt.setAttribute("data-trait", trait);
+        sipt.setAttribute("data-term", "spring");
+        sipt.id = sid;
+        const slab = document.createElement("label");
+        slab.setAttribute("for", sid);
+        slab.setAttribute("class", "l");
+        UTIL.set_text(slab, trait);
+        cont.appendChild(sipt);
+        cont.appendChild(slab);
+
+        n = n + 1;
+    }
+}
+
 function populate_traits(r) {
     r.json()
     .then(j => {
         console.log("populate-traits response:", j);
+        load_traits(j);
+    })
+    .catch(log_numbered_error);
+}
+
+/*
+Keep the Teacher's custom chapters, list them in the custom chapters
+dialog, and offer them in the goal editing dialog.
+*/
+function load_customs(j) {
+    DATA.customs = new Map();
+    const tbody = DISPLAY.customs_edit.querySelector("table#custom-chapters-list > tbody");
+    UTIL.clear(tbody);
+    const select = DISPLAY.custom_input;
+    UTIL.clear(select);
+    const none = document.createElement("option");
+    none.value = "";
+    UTIL.set_text(none, "(none)");
+    select.appendChild(none);
+
+    for(const cus of j) {
+        DATA.customs.set(cus.id, cus);
+
+        const opt = document.createElement("option");
+        opt.value = cus.id;
+        UTIL.set_text(opt, cus.title);
+        select.appendChild(opt);
+
+        const tr = document.createElement("tr");
+        tr.appendChild(UTIL.text_td(cus.title));
+        tr.appendChild(UTIL.text_td(cus.weight));
+        const etd = document.createElement("td");
+        const edit = document.createElement("button");
+        edit.setAttribute("data-id", cus.id);
+        edit.setAttribute("title", "edit custom chapter");
+        UTIL.label("\u270e", edit);
+        edit.addEventListener("click", edit_custom);
+        etd.appendChild(edit);
+        const del = document.createElement("button");
+        del.setAttribute("data-id", cus.id);
+        del.setAttribute("title", "delete custom chapter");
+        UTIL.label("\u2717", del);
+        del.addEventListener("click", delete_custom);
+        etd.appendChild(del);
+        tr.appendChild(etd);
+        tbody.appendChild(tr);
+    }
+}
+
+/*
+Changing a custom chapter can change the titles and weights of goals, so
+the pace calendars get reloaded too.
+*/
+function populate_customs(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-custom-chapters response:", j);
+        load_customs(j);
+        document.forms["custom-chapter"].reset();
+        request_action("populate-goals", "", "Populating pace calendars.");
+    })
+    .catch(log_numbered_error);
+}
+
+function load_announcements(j) {
+    UTIL.clear(DISPLAY.announcements);
+    for(const a of j) {
+        const div = document.createElement("div");
+        UTIL.set_text(div, a.message);
+        DISPLAY.announcements.appendChild(div);
+    }
+}
+
+/*
+Everything the page needs to start with, in one response. Goals go last,
+because drawing the pace calendars needs the courses.
+*/
+function bootstrap(r) {
+    r.json()
+    .then(j => {
+        console.log("bootstrap response:", j);
+        load_dates(j.dates);
+        load_traits(j.traits);
+        load_courses(j.courses);
+        load_customs(j.customs);
+        load_goals(j.goals);
+        load_announcements(j.announcements);
+        DATA.as_of = j.as_of;
+    })
+    .catch(log_numbered_error);
+}
+
+/*
+Bring the pace calendars up to date with a "changes-since" response:
+replace the paces of students who have changed (adding any who are new),
+and remove students who have left.
+*/
+function sync_changes(r) {
+    r.json()
+    .then(j => {
+        console.log("changes-since response:", j);
+
+        for(const uname of j.departed) {
+            remove_pace(uname);
+        }
+        for(const p of j.goals) {
+            const current_tab = document.querySelector(`table.pace[data-uname="${p.uname}"]`);
+            const was_expanded = current_tab
+                && current_tab.querySelector("tr.extra").style.display == "table-row";
+            remove_pace(p.uname);
+
+            DATA.paces.set(p.uname, p);
+            for(const g of p.goals) {
+                g.uname = p.uname;
+                DATA.goals.set(g.id, g);
+            }
 
-        DATA.traits = j;
-        const cont = DISPLAY.sidecar_edit.querySelector("fieldset#trait-container");
-        let n = 0;
-        for(const trait of DATA.traits) {
-            const fid = `edit-sidecar-trait-${n}`;
-            const fipt = document.createElement("input");
-            fipt.setAttribute("data-trait", trait);
-            fipt.setAttribute("data-term", "fall");
-            fipt.id = fid;
-            const flab = document.createElement("label");
-            flab.setAttribute("for", fid);
-            flab.setAttribute("class", "r");
-            UTIL.set_text(flab, trait);
-            cont.appendChild(flab);
-            cont.appendChild(fipt);
-
-            n = n + 1;
-
-            const sid = `edit-sidecar-trait-${n}`;
-            const sipt = document.createElement("input");
-            sipt.setAttribute("data-trait", trait);
-            sipt.setAttribute("data-term", "spring");
-            sipt.id = sid;
-            const slab = document.createElement("label");
-            slab.setAttribute("for", sid);
-            slab.setAttribute("class", "l");
-            UTIL.set_text(slab, trait);
-            cont.appendChild(sipt);
-            cont.appendChild(slab);
-
-            n = n + 1;
+            const tab = make_calendar_table(p);
+            // Keep the tables in name order, like load_goals() does.
+            const next = Array.from(DISPLAY.calbox.querySelectorAll("table.pace"))
+                .find(t => {
+                    const q = DATA.paces.get(t.getAttribute("data-uname"));
+                    return q && (q.last > p.last || (q.last == p.last && q.rest > p.rest));
+                });
+            DISPLAY.calbox.insertBefore(tab, next || null);
+            if(was_expanded) {
+                tab.querySelector("button.expander").click();
+            }
         }
 
+        DATA.as_of = j.as_of;
     })
     .catch(log_numbered_error);
 }
 
+function remove_pace(uname) {
+    const p = DATA.paces.get(uname);
+    if(p) {
+        for(const g of p.goals) {
+            DATA.goals.delete(g.id);
+        }
+        DATA.paces.delete(uname);
+    }
+    const tab = document.querySelector(`table.pace[data-uname="${uname}"]`);
+    if(tab) {
+        tab.remove();
+    }
+}
+
+// Catch up on anything that's changed while the page was in the background.
+document.addEventListener("visibilitychange", () => {
+    if(document.visibilityState == "visible" && DATA.as_of) {
+        request_action("changes-since", DATA.as_of, "Checking for changes...");
+    }
+});
+
 function make_complete_checkbox(crs, term) {
     const box = document.createElement("input");
     box.type = "checkbox";
@@ -781,6 +1122,21 @@ async function show_sidecar(r) {
         ipt.value = score;
     }
 
+    const vis = car.visibility || {};
+    for(const section of ["facts", "mastery", "social"]) {
+        form.elements[`vis-${section}`].value = vis[section] || "student";
+    }
+    for(const term of ["fall", "spring", "summer"]) {
+        form.elements[`note-${term}`].value = "";
+        form.elements[`note-${term}-vis`].value = "parent";
+    }
+    for(const note of (car.notes || [])) {
+        const term = note.term.toLowerCase();
+        form.elements[`note-${term}`].value = note.text;
+        form.elements[`note-${term}-vis`].value = note.visibility;
+    }
+    form.elements["audience"].value = "parent";
+
 /*     form["complete-fall"].value = car.fall_complete;
     form["complete-spring"].value = car.spring_complete; */
 
@@ -794,10 +1150,11 @@ async function show_sidecar(r) {
     UTIL.clear(goal_rows);
     for(const g of pace.goals) {
         const tr = document.createElement("tr");
-        const course = DATA.courses.get(g.sym);
-        course_symbols.add(g.sym);
-        const chapt = DATA.chapters.get(course.chapters[g.seq]);
-        const ch_name = `${course.title} ${chapt.title}`;
+        if(g.custom == null) {
+            course_symbols.add(g.sym);
+        }
+        const names = goal_names(g);
+        const ch_name = `${names.course} ${names.chapter}`;
 
         let td = document.createElement("td");
         UTIL.set_text(td, ch_name);
@@ -926,13 +1283,24 @@ document.getElementById("edit-sidecar-spring")
     .addEventListener("click", save_sidecar);
 document.getElementById("edit-sidecar-summer")
     .addEventListener("click", save_sidecar);
+document.getElementById("edit-sidecar-copy-draft")
+    .addEventListener("click", copy_draft);
 
 function field_response(r) {
     if(!r.ok) {
+        // This conflict just needs the user's go-ahead.
+        if(r.status == 409 && r.headers.get("x-camp-action") == "copy-draft") {
+            confirm_copy_draft(r);
+            return;
+        }
         r.text()
         .then(t => {
-            const err_txt = `${t}\n(${r.status}: ${r.statusText})`;
+            const err_txt = `${UTIL.error_text(t)}\n(${r.status}: ${r.statusText})`;
             RQ.add_err(err_txt);
+            // 409 means our course data is out of date.
+            if(r.status == 409) {
+                request_action("populate-courses", "", "Reloading course information.");
+            }
         }
         ).catch(e => {
             const e_n = next_err();
@@ -953,17 +1321,29 @@ function field_response(r) {
         RQ.add_err(err_txt);
         return;
     }
+    const courses_token = r.headers.get("x-camp-courses");
+    if(courses_token) {
+        DATA.courses_token = courses_token;
+    }
     switch(action) {
+        case "bootstrap":
+            bootstrap(r); break;
+        case "confirm-action":
+            CONFIRM.respond(r); break;
         case "populate-courses":
             populate_courses(r); break;
         case "populate-goals":
             populate_goals(r); break;
         case "update-pace":
             replace_pace(r); break;
+        case "changes-since":
+            sync_changes(r); break;
         case "populate-dates":
             populate_dates(r); break;
         case "populate-traits":
             populate_traits(r); break;
+        case "populate-custom-chapters":
+            populate_customs(r); break;
         case "show-sidecar":
             show_sidecar(r); break;
         case "edit-markdown":
@@ -972,6 +1352,16 @@ function field_response(r) {
             show_pdf(r); break;
         case "student-history":
             show_history(r); break;
+        case "goals-template":
+            save_goals_template(r); break;
+        case "populate-absences":
+            populate_absences(r); break;
+        case "download-goals":
+            save_goals_template(r, `goals-${r.headers.get("x-camp-student")}.csv`); break;
+        case "download-students":
+            save_goals_template(r, "students.csv"); break;
+        case "download-ical":
+            save_goals_template(r, `pace-${r.headers.get("x-camp-student")}.ics`); break;
         case "none":
             /* Don't do anything. This is a success that requires no action. */
             break;
@@ -985,7 +1375,14 @@ function field_response(r) {
 }
 
 function request_action(action, body, description, extra_headers) {
+    CONFIRM.remember(action, body, description, extra_headers);
     const headers = { "x-camp-action": action };
+    if(DATA.courses_token) {
+        headers["x-camp-courses"] = DATA.courses_token;
+    }
+    if(DISPLAY.avoid_absences.checked) {
+        headers["x-camp-avoid-absences"] = "true";
+    }
     if(extra_headers) {
         for(const [name, value] of Object.entries(extra_headers)) {
             headers[name] = value;
@@ -1019,9 +1416,7 @@ function request_action(action, body, description, extra_headers) {
 
 
 UTIL.ensure_on_load(() => {
-    request_action("populate-courses", "", "Fetching Course data.");
-    request_action("populate-dates", "", "Fetching calendar events.");
-    request_action("populate-traits", "", "Fetching list of social/emotional traits.");
+    request_action("bootstrap", "", "Fetching courses, calendars, and goals.");
 });
 
 document.getElementById("course-info-show")
@@ -1048,6 +1443,150 @@ document.getElementById("upload-goals")
         DISPLAY.upload_goals.showModal();
     })
 
+document.getElementById("download-students")
+    .addEventListener("click", () => {
+        request_action("download-students", "", "Downloading student roster.");
+    });
+
+document.getElementById("custom-chapters")
+    .addEventListener("click", () => {
+        document.forms["custom-chapter"].reset();
+        DISPLAY.customs_edit.showModal();
+    });
+
+function edit_custom(evt) {
+    evt.preventDefault();
+    const form = document.forms["custom-chapter"];
+    const cus = DATA.customs.get(Number(this.getAttribute("data-id")));
+    form.elements["id"].value = cus.id;
+    form.elements["title"].value = cus.title;
+    form.elements["weight"].value = cus.weight;
+}
+
+async function delete_custom(evt) {
+    evt.preventDefault();
+    const cus = DATA.customs.get(Number(this.getAttribute("data-id")));
+    if(!await are_you_sure(`Are you sure you want to delete the custom chapter "${cus.title}"?`)) {
+        return;
+    }
+    request_action("delete-custom-chapter", String(cus.id),
+        `Deleting custom chapter "${cus.title}".`);
+}
+
+function custom_submit(evt) {
+    evt.preventDefault();
+    const form = document.forms["custom-chapter"];
+    const id = Number(form.elements["id"].value) || 0;
+    const title = form.elements["title"].value.trim();
+    const weight = Number(form.elements["weight"].value);
+    if(!title) {
+        RQ.add_err("A custom chapter needs a title.");
+        return;
+    }
+    if(!(weight > 0 && weight <= 1)) {
+        RQ.add_err("A custom chapter's weight must be more than 0 and at most 1.");
+        return;
+    }
+
+    const body = { "id": id, "title": title, "weight": weight };
+    if(id) {
+        request_action("update-custom-chapter", body, `Updating custom chapter "${title}".`);
+    } else {
+        request_action("add-custom-chapter", body, `Adding custom chapter "${title}".`);
+    }
+}
+
+function edit_absences(evt) {
+    evt.preventDefault();
+    const uname = this.getAttribute("data-uname");
+    const cal = DATA.paces.get(uname);
+    const form = document.forms["absence"];
+    form.reset();
+    form.elements["uname"].value = uname;
+    UTIL.set_text(DISPLAY.absences_meta, `${cal.rest} ${cal.last}`);
+    UTIL.clear(DISPLAY.absences_edit.querySelector("table#absences-list > tbody"));
+    DISPLAY.absences_edit.showModal();
+    request_action("populate-absences", uname, `Fetching absences for ${cal.rest} ${cal.last}.`);
+}
+
+function populate_absences(r) {
+    r.json()
+    .then(j => {
+        console.log("populate-absences response:", j);
+        const form = document.forms["absence"];
+        if(form.elements["uname"].value != j.uname) { return; }
+        const tbody = DISPLAY.absences_edit.querySelector("table#absences-list > tbody");
+        UTIL.clear(tbody);
+        for(const a of j.absences) {
+            const tr = document.createElement("tr");
+            tr.appendChild(UTIL.text_td(a.start));
+            tr.appendChild(UTIL.text_td(a.end));
+            tr.appendChild(UTIL.text_td(a.reason));
+            const etd = document.createElement("td");
+            const del = document.createElement("button");
+            del.setAttribute("data-id", a.id);
+            del.setAttribute("title", "delete absence");
+            UTIL.label("\u2717", del);
+            del.addEventListener("click", delete_absence);
+            etd.appendChild(del);
+            tr.appendChild(etd);
+            tbody.appendChild(tr);
+        }
+    })
+    .catch(log_numbered_error);
+}
+
+function absence_submit(evt) {
+    evt.preventDefault();
+    const form = document.forms["absence"];
+    const start = form.elements["start"].value;
+    const end = form.elements["end"].value || start;
+    if(!start) {
+        RQ.add_err("An absence needs a starting date.");
+        return;
+    }
+    if(end < start) {
+        RQ.add_err("An absence can't end before it starts.");
+        return;
+    }
+
+    const body = {
+        "uname": form.elements["uname"].value,
+        "start": start,
+        "end": end,
+        "reason": form.elements["reason"].value.trim(),
+    };
+    form.elements["start"].value = "";
+    form.elements["end"].value = "";
+    form.elements["reason"].value = "";
+    request_action("add-absence", body, `Recording absence from ${start} through ${end}.`);
+}
+
+function delete_absence(evt) {
+    evt.preventDefault();
+    const body = {
+        "uname": document.forms["absence"].elements["uname"].value,
+        "id": Number(this.getAttribute("data-id")),
+    };
+    request_action("delete-absence", body, "Deleting absence.");
+}
+
+document.getElementById("absence-confirm")
+    .addEventListener("click", absence_submit);
+document.getElementById("absences-close")
+    .addEventListener("click", evt => {
+        evt.preventDefault();
+        DISPLAY.absences_edit.close();
+    });
+
+document.getElementById("custom-chapter-confirm")
+    .addEventListener("click", custom_submit);
+document.getElementById("custom-chapters-close")
+    .addEventListener("click", evt => {
+        evt.preventDefault();
+        DISPLAY.customs_edit.close();
+    });
+
 function upload_goals_submit(evt) {
     evt.preventDefault();
     const form = document.forms["upload-goals"];
@@ -1069,6 +1608,25 @@ function upload_goals_submit(evt) {
 
 document.getElementById("upload-goals-confirm")
     .addEventListener("click", upload_goals_submit);
+document.getElementById("upload-goals-template")
+    .addEventListener("click", evt => {
+        evt.preventDefault();
+        request_action("goals-template", "", "Generating goals template.");
+    });
+
+function save_goals_template(r, filename = "goals.csv") {
+    r.blob()
+    .then(blob => {
+        const file_url = window.URL.createObjectURL(blob);
+        const link = document.createElement("A");
+        link.href = file_url;
+        link.download = filename;
+        link.click();
+        // Give the download a chance to start before letting go of the blob.
+        setTimeout(() => window.URL.revokeObjectURL(file_url), 5000);
+    })
+    .catch(log_numbered_error);
+}
 document.getElementById("upload-goals-cancel")
     .addEventListener("click", evt => {
         evt.preventDefault();
@@ -1128,19 +1686,23 @@ function edit_goal(evt) {
         const g = DATA.goals.get(id);
         form.elements["id"].value = id;
         form.elements["course"].value = g.sym;
-        form.elements["seq"].value = g.seq;
+        form.elements["seq"].value = g.custom == null ? g.seq : "";
+        form.elements["custom"].value = g.custom ?? "";
         form.elements["due"].value = g.due;
         form.elements["review"].checked = g.rev;
         form.elements["incomplete"].checked = g.inc;
         del.disabled = false;
         del.setAttribute("data-id", id);
-        populate_seq_list();
+        if(g.custom == null) {
+            populate_seq_list();
+        }
         confirm.removeAttribute("data-uname");
     } else {
         for(const ipt of form.elements) {
             if(ipt.value) { ipt.value = null; }
             if(ipt.checked) { ipt.checked = false; }
         }
+        form.elements["custom"].value = "";
         del.disabled = true;
         del.removeAttribute("data-id");
         const uname = this.getAttribute("data-uname");
@@ -1165,24 +1727,29 @@ function edit_goal(evt) {
 }
 
 function edit_goal_submit(evt) {
+    evt.preventDefault();
     const form = document.forms["edit-goal"];
     const uname = this.getAttribute("data-uname") || "";
     const id = Number(form.elements["id"].value) || 0;
-    const sym = form.elements["course"].value?.trim() || "";
-    const course = DATA.courses.get(sym);
-    const seq = Number(form.elements["seq"].value) || 0;
-    const chapt = course.chapters[seq];
-    if(sym == "") {
-        RQ,add_err("You must select a valid course.");
-        return;
-    } else if(!course) {
-        RQ.add_err(`"${sym} is not a valid course symbol.`);
-        return;
-    }
-    if(!chapt) {
-        const err = `You must select a valid chapter number for course "${sym}": ${course.title} (${course.book}).`
-        RQ.add_err(err);
-        return;
+    // A custom chapter, if one is chosen, takes the place of the course
+    // and chapter.
+    const custom = Number(form.elements["custom"].value) || null;
+    const sym = custom ? "" : (form.elements["course"].value?.trim() || "");
+    const seq = custom ? 0 : (Number(form.elements["seq"].value) || 0);
+    if(!custom) {
+        const course = DATA.courses.get(sym);
+        if(sym == "") {
+            RQ.add_err("You must select a valid course or custom chapter.");
+            return;
+        } else if(!course) {
+            RQ.add_err(`"${sym} is not a valid course symbol.`);
+            return;
+        }
+        if(!course.chapters[seq]) {
+            const err = `You must select a valid chapter number for course "${sym}": ${course.title} (${course.book}).`
+            RQ.add_err(err);
+            return;
+        }
     }
 
     // Pre-fill default values for a new goal.
@@ -1204,6 +1771,7 @@ function edit_goal_submit(evt) {
     g["id"] = id;
     g["sym"] = sym;
     g["seq"] = seq;
+    g["custom"] = custom;
     g["rev"] = form.elements["review"].checked;
     g["inc"] = form.elements["incomplete"].checked;
     g["due"] = form.elements["due"].value || null;
@@ -1212,7 +1780,8 @@ function edit_goal_submit(evt) {
     if(form.elements["id"].value) {
         request_action("update-goal", g, `Updating Goal ${id}`);
     } else {
-        request_action("add-goal", g, `Adding new Goal: ${sym}, ${seq} for ${uname}`);
+        const what = custom ? DATA.customs.get(custom).title : `${sym}, ${seq}`;
+        request_action("add-goal", g, `Adding new Goal: ${what} for ${uname}`);
     }
     
 }
@@ -1228,10 +1797,20 @@ document.getElementById("edit-goal-confirm")
 async function delete_goal_submit(evt) {
     const id = this.getAttribute("data-id");
     const g = DATA.goals.get(Number(id));
-    const crs = DATA.courses.get(g.sym);
-    const chp = DATA.chapters.get(crs.chapters[g.seq]);
-    const q = `Are you sure you want to delete ${crs.title} ${chp.title} for ${g.uname}?.`;
-    if(await are_you_sure(q)) {
+    const names = goal_names(g);
+    const q = `Are you sure you want to delete ${names.course} ${names.chapter} for ${g.uname}?.`;
+    if(!await are_you_sure(q)) {
+        return;
+    }
+    if(g.done || g.score) {
+        const q2 = `${g.uname} has already completed ${names.course} ${names.chapter}; deleting it will remove it from their grades. (A copy will be archived.) Are you really sure?`;
+        if(!await are_you_sure(q2)) {
+            return;
+        }
+        DISPLAY.goal_edit.close();
+        const body = { "id": Number(id), "force": true };
+        request_action("delete-goal", body, `Deleting completed Goal #${id}.`);
+    } else {
         DISPLAY.goal_edit.close();
         request_action("delete-goal", id, `Deleting Goal #${id}.`);
     }
@@ -1240,6 +1819,92 @@ async function delete_goal_submit(evt) {
 document.getElementById("delete-goal")
     .addEventListener("click", delete_goal_submit);
 
+/*
+Rubric breakdowns are edited as text, one component per line, like
+
+Setup: 3/4
+Accuracy: 5/6
+*/
+function rubric2text(rubric) {
+    return (rubric || []).map(r => `${r.name}: ${r.points}/${r.max}`).join("\n");
+}
+
+/* Returns null if any line isn't in the above format. */
+function text2rubric(text) {
+    const rubric = [];
+    for(const line of text.split("\n")) {
+        if(!line.trim()) { continue; }
+        const m = line.match(/^(.+):\s*([0-9.]+)\s*\/\s*([0-9.]+)\s*$/);
+        if(!m) { return null; }
+        rubric.push({
+            "name": m[1].trim(),
+            "points": Number(m[2]),
+            "max": Number(m[3]),
+        });
+    }
+    return rubric;
+}
+
+function split_goal(evt) {
+    const id = Number(this.getAttribute("data-id"));
+    const g = DATA.goals.get(id);
+    const n = Number(window.prompt("Split this goal into how many parts?", "2"));
+    if(!n) { return; }
+    if(n < 2) {
+        RQ.add_err("A goal must be split into at least two parts.");
+        return;
+    }
+
+    const body = {
+        id: id,
+        due: Array(n).fill(g.due || null),
+    };
+    request_action("split-goal", body, `Splitting Goal #${id} into ${n} parts.`);
+}
+
+function set_progress(evt) {
+    const id = Number(this.getAttribute("data-id"));
+    const g = DATA.goals.get(id);
+    const current = g.progress != null ? String(g.progress) : "";
+    const text = window.prompt("Percent done (leave blank to clear):", current);
+    if(text === null) { return; }
+
+    let progress = null;
+    if(text.trim()) {
+        progress = Number(text.trim().replace(/%$/, ""));
+        if(!Number.isInteger(progress) || progress < 0 || progress > 100) {
+            RQ.add_err(`${text} isn't a whole percentage from 0 to 100.`);
+            return;
+        }
+    }
+    request_action("set-progress", { id: id, progress: progress },
+        `Recording progress on Goal #${id}.`);
+}
+
+async function dismiss_request(evt) {
+    evt.preventDefault();
+    const id = Number(this.getAttribute("data-id"));
+    const q = "Dismiss the student's request that this goal be marked done?";
+    if(await are_you_sure(q)) {
+        request_action("dismiss-request", String(id), `Dismissing request for Goal #${id}.`);
+    }
+}
+
+function request_test(evt) {
+    const g = DATA.goals.get(Number(this.getAttribute("data-id")));
+    const body = { uname: g.uname, sym: g.sym, seq: g.seq };
+    request_action("request-test", body, `Requesting test of ${g.sym} ch. ${g.seq}.`);
+}
+
+async function cancel_test(evt) {
+    const g = DATA.goals.get(Number(this.getAttribute("data-id")));
+    const q = `Withdraw the request for a printed test of ${g.sym} ch. ${g.seq}?`;
+    if(await are_you_sure(q)) {
+        const body = { uname: g.uname, sym: g.sym, seq: g.seq };
+        request_action("cancel-test", body, `Withdrawing test request for ${g.sym} ch. ${g.seq}.`);
+    }
+}
+
 function complete_goal(evt) {
     const id = this.getAttribute("data-id");
     const form = document.forms["complete-goal"];
@@ -1253,6 +1918,7 @@ function complete_goal(evt) {
     }
     form.elements["tries"].value = g.tries;
     form.elements["score"].value = g.score;
+    form.elements["rubric"].value = rubric2text(g.rubric);
 
     DISPLAY.goal_complete.showModal();
 }
@@ -1279,9 +1945,15 @@ function complete_goal_submit(evt) {
         return;
     }
 
+    let rubric = [];
     if(valid_score) {
         if(!tries) { tries = 1;}
         score = data.get("score");
+        rubric = text2rubric(data.get("rubric"));
+        if(!rubric) {
+            RQ.add_err("Each line of the rubric should look like \"Setup: 3/4\".");
+            return;
+        }
     } else {
         tries = null;
     }
@@ -1289,6 +1961,7 @@ function complete_goal_submit(evt) {
     g.done = done;
     g.score = score;
     g.tries = tries;
+    g.rubric = rubric;
 
     DISPLAY.goal_complete.close();
     request_action("update-goal", g, `Marking Goal $${g.id} complete.`);
@@ -1358,6 +2031,77 @@ async function clear_goals(evt) {
     }
 }
 
+function delete_goals(evt) {
+    evt.preventDefault();
+    const uname = this.getAttribute("data-uname");
+    const cal = DATA.paces.get(uname);
+    const form = document.forms["delete-goals"];
+
+    form.reset();
+    form.elements["uname"].value = uname;
+    UTIL.set_text(DISPLAY.goals_delete_meta, `${cal.rest} ${cal.last}`);
+    DISPLAY.goals_delete.showModal();
+}
+
+async function delete_goals_submit(evt) {
+    evt.preventDefault();
+    const form = document.forms["delete-goals"];
+    const data = new FormData(form);
+    const uname = data.get("uname");
+    const cal = DATA.paces.get(uname);
+
+    const body = { "uname": uname, "unstarted": Boolean(data.get("unstarted")) };
+    const sym = data.get("course").trim();
+    if(sym) {
+        if(!DATA.courses.get(sym)) {
+            RQ.add_err(`"${sym}" is not a valid course symbol.`);
+            return;
+        }
+        body.sym = sym;
+    }
+    if(data.get("term")) {
+        body.term = data.get("term");
+    }
+    if(!(body.sym || body.term || body.unstarted)) {
+        RQ.add_err("Choose a course, a term, or only unstarted goals (or use \"clear all goals\").");
+        return;
+    }
+
+    DISPLAY.goals_delete.close();
+    if(!body.unstarted) {
+        const q = `This will delete completed goals of ${cal.rest} ${cal.last}, too.`;
+        if(!(await are_you_sure(q))) {
+            return;
+        }
+    }
+    request_action("delete-goals", body, `Deleting goals for ${cal.rest} ${cal.last}.`);
+}
+
+document.getElementById("delete-goals-cancel")
+    .addEventListener("click", evt => {
+        evt.preventDefault();
+        DISPLAY.goals_delete.close();
+    });
+document.getElementById("delete-goals-confirm")
+    .addEventListener("click", delete_goals_submit);
+
+function toggle_waiver(evt) {
+    evt.preventDefault();
+    const uname = this.getAttribute("data-uname");
+    const cal = DATA.paces.get(uname);
+    const body = {
+        "uname": uname,
+        "sym": this.getAttribute("data-sym"),
+        "seq": Number(this.getAttribute("data-seq")),
+        "waived": this.getAttribute("data-waived") != "true",
+    };
+    const verb = body.waived ? "Waiving" : "Un-waiving";
+    request_action(
+        "waive-chapter", body,
+        `${verb} ${body.sym} chapter ${body.seq} for ${cal.rest} ${cal.last}.`
+    );
+}
+
 function edit_sidecar(evt) {
     evt.preventDefault();
     const uname = this.getAttribute("data-uname");
@@ -1423,8 +2167,26 @@ function save_sidecar(evt) {
     }).filter(x => Boolean(x));
     sc["mastery"] = mastery;
 
+    sc["visibility"] = {
+        "facts": data.get("vis-facts"),
+        "mastery": data.get("vis-mastery"),
+        "social": data.get("vis-social"),
+    };
+    sc["notes"] = [];
+    for(const [t, term_name] of [["fall", "Fall"], ["spring", "Spring"], ["summer", "Summer"]]) {
+        const text = data.get(`note-${t}`).trim();
+        if(text) {
+            sc["notes"].push({
+                "term": term_name,
+                "text": text,
+                "visibility": data.get(`note-${t}-vis`),
+            });
+        }
+    }
+
     const extra_headers = {
         "x-camp-term": term,
+        "x-camp-audience": data.get("audience"),
     };
 
     const p = DATA.paces.get(uname);
@@ -1454,6 +2216,38 @@ function edit_markdown(r) {
     .catch(log_numbered_error);
 }
 
+function copy_draft(evt) {
+    evt.preventDefault();
+    const uname = document.forms["edit-sidecar"].elements["uname"].value;
+    const p = DATA.paces.get(uname);
+    DISPLAY.sidecar_edit.close();
+    request_action(
+        "copy-draft", null,
+        `Copying fall report draft for ${p.rest} ${p.last}.`,
+        { "x-camp-student": uname }
+    );
+}
+
+async function confirm_copy_draft(r) {
+    const uname = r.headers.get("x-camp-student");
+    let text;
+    try {
+        text = await r.text();
+    } catch(e) {
+        log_numbered_error(e);
+        return;
+    }
+    const q = `${UTIL.error_text(text)} Do you want to replace it?`;
+    if(await are_you_sure(q)) {
+        const p = DATA.paces.get(uname);
+        request_action(
+            "copy-draft", { "force": true },
+            `Replacing spring report draft for ${p.rest} ${p.last}.`,
+            { "x-camp-student": uname }
+        );
+    }
+}
+
 async function save_markdown(evt) {
     const form = document.forms["edit-report"];
     const data = new FormData(form);
@@ -1531,6 +2325,30 @@ function request_history(evt) {
     request_action("student-history", null, desc, extra_headers);
 }
 
+function request_goals_csv(evt) {
+    evt.preventDefault();
+    const uname = this.getAttribute("data-uname");
+
+    const extra_headers = {
+        "x-camp-student": uname,
+    };
+    const pace = DATA.paces.get(uname);
+    const desc = `Downloading goals for ${pace.rest} ${pace.last}.`;
+    request_action("download-goals", null, desc, extra_headers);
+}
+
+function request_ical(evt) {
+    evt.preventDefault();
+    const uname = this.getAttribute("data-uname");
+
+    const extra_headers = {
+        "x-camp-student": uname,
+    };
+    const pace = DATA.paces.get(uname);
+    const desc = `Downloading calendar for ${pace.rest} ${pace.last}.`;
+    request_action("download-ical", null, desc, extra_headers);
+}
+
 async function show_history(r) {
     let comp = null;
     await r.json().then(j => { comp = j; })
@@ -38,6 +38,26 @@ const UTIL = {
         return p;
     },
 
+    /*
+    Get the human-readable message out of the body of an API error
+    response, which should be a JSON object like
+
+    { "code": "bad-request", "message": "...", "detail": ... }
+
+    Anything else is returned as-is.
+    */
+    error_text: function(text) {
+        try {
+            const j = JSON.parse(text);
+            if(j && typeof(j.message) == "string") {
+                return j.message;
+            }
+        } catch(e) {
+            /* Not JSON; fall through. */
+        }
+        return text;
+    },
+
     date_re: /^[^T]+/,
 
     iso2date: function (isostr) {
@@ -121,6 +141,91 @@ async function are_you_sure(question) {
     return p;
 }
 
+/*
+Actions that destroy data have to be confirmed by typing in a code the
+server sends back (with an x-camp-action of "confirm-action") instead of
+doing them. CONFIRM remembers the most recent request for each action, so
+it can be made again with the code in an x-camp-confirm header.
+
+Each page's request_action() should call CONFIRM.remember(), and its
+response handler should pass "confirm-action" responses to
+CONFIRM.respond().
+*/
+const CONFIRM = {
+    requests: new Map(),
+};
+CONFIRM.remember = function(action, body, description, extra_headers) {
+    CONFIRM.requests.set(action, {
+        body: body,
+        description: description,
+        extra_headers: extra_headers,
+    });
+}
+CONFIRM.ask = async function(summary, token, expires) {
+    const dialog = document.getElementById("type-to-confirm");
+    const form = document.forms["type-to-confirm"];
+    const ipt = form.elements["token"];
+    const ok = document.getElementById("type-to-confirm-confirm");
+    dialog.returnValue = "cancel";
+
+    UTIL.set_text(document.getElementById("type-to-confirm-summary"), summary);
+    UTIL.set_text(document.getElementById("type-to-confirm-token"), token);
+    UTIL.set_text(
+        document.getElementById("type-to-confirm-minutes"),
+        String(Math.floor(expires / 60))
+    );
+    ipt.value = "";
+    ok.disabled = true;
+    ipt.oninput = () => {
+        ok.disabled = (ipt.value.trim().toUpperCase() != token);
+    };
+    // Otherwise Enter would submit the form with its first button.
+    ipt.onkeydown = (evt) => {
+        if(evt.key == "Enter") {
+            evt.preventDefault();
+            if(!ok.disabled) { ok.click(); }
+        }
+    };
+
+    const p = new Promise((resolve, _) => {
+        dialog.onclose = () => {
+            resolve(dialog.returnValue == "ok" ? ipt.value.trim() : null);
+        };
+        document.getElementById("type-to-confirm-cancel").onclick = () => {
+            dialog.returnValue = "cancel";
+            dialog.close();
+        };
+        ok.onclick = () => {
+            dialog.returnValue = "ok";
+            dialog.close();
+        };
+    });
+    dialog.showModal();
+    ipt.focus();
+    return p;
+}
+CONFIRM.respond = async function(r) {
+    let j;
+    try {
+        j = await r.json();
+    } catch(e) {
+        RQ.add_err(e);
+        return;
+    }
+    console.log("confirm-action response:", j);
+
+    const req = CONFIRM.requests.get(j.action);
+    if(!req) {
+        RQ.add_err(`Asked to confirm ${j.action}, which wasn't requested.`);
+        return;
+    }
+    const typed = await CONFIRM.ask(j.summary, j.token, j.expires);
+    if(typed === null) { return; }
+
+    const headers = Object.assign({}, req.extra_headers, { "x-camp-confirm": typed });
+    request_action(j.action, req.body, req.description, headers);
+}
+
 const RQ = {
     id: 0,
     pending: new Map(),
@@ -0,0 +1,211 @@
+/*!
+Anonymization of names and email addresses, for demos and screenshots.
+
+Logging in with the *anonymize* box checked marks the issued key (see
+[`Glob::set_anonymized`](crate::config::Glob::set_anonymized)), and every
+request made with that key is handled inside [`scope`]. While it's in
+effect, the display structs that get serialized into pages and API
+responses swap real names and addresses for fake ones as they're
+serialized (with the [`ser`] functions, as `#[serde(serialize_with)]`),
+and the few places that build names by hand call [`last`], [`rest`],
+[`name`], or [`email`] directly. Nothing in either database changes.
+
+The fakes are deterministic: the same real name always gets the same fake
+one, so a student looks the same from page to page and session to
+session. Unames are left alone, because the frontends use them to refer
+to users.
+
+Emails and reports are never anonymized, even when they're generated
+during an anonymized request, because they go to (or are kept for) real
+people; their data structs don't use the [`ser`] functions.
+*/
+use std::{borrow::Cow, future::Future};
+
+static LAST_NAMES: &[&str] = &[
+    "Abbott", "Alvarez", "Bennett", "Brooks", "Castillo", "Chen", "Dalton", "Diaz",
+    "Ellison", "Farrow", "Fischer", "Garner", "Gupta", "Hale", "Holloway", "Ibarra",
+    "Jensen", "Kaur", "Keller", "Lambert", "Larsen", "Mahoney", "Medina", "Nakamura",
+    "Novak", "Okafor", "Ortega", "Pearce", "Quinn", "Ramos", "Russo", "Sato",
+    "Sheridan", "Sokolov", "Thornton", "Usher", "Vance", "Whitaker", "Yilmaz", "Zimmer",
+];
+
+static FIRST_NAMES: &[&str] = &[
+    "Ada", "Amir", "Bea", "Caleb", "Cora", "Dev", "Elena", "Eli", "Farah", "Felix",
+    "Gia", "Gus", "Hana", "Hugo", "Iris", "Ivan", "June", "Kai", "Lena", "Leo",
+    "Mara", "Milo", "Nia", "Noah", "Opal", "Omar", "Pia", "Rafe", "Rosa", "Sam",
+    "Tess", "Theo", "Uma", "Vera", "Wes", "Willa", "Xavi", "Yara", "Zane", "Zoe",
+];
+
+tokio::task_local! {
+    static ANONYMIZE: bool;
+}
+
+/// Whether the current request is being anonymized.
+pub fn active() -> bool {
+    ANONYMIZE.try_with(|on| *on).unwrap_or(false)
+}
+
+/// Run `f` with anonymization turned on (or not).
+pub async fn scope<F: Future>(on: bool, f: F) -> F::Output {
+    ANONYMIZE.scope(on, f).await
+}
+
+/// Pick an element of `list` determined by `real` (and `salt`, so different
+/// kinds of names from the same string don't line up).
+fn pick(list: &'static [&'static str], salt: &str, real: &str) -> &'static str {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(real.trim().to_lowercase().as_bytes());
+    let bytes = hasher.finalize();
+    let mut n = [0u8; 8];
+    n.copy_from_slice(&bytes.as_bytes()[..8]);
+    list[(u64::from_le_bytes(n) % list.len() as u64) as usize]
+}
+
+fn fake_last(real: &str) -> &'static str {
+    pick(LAST_NAMES, "last", real)
+}
+
+fn fake_first(real: &str) -> &'static str {
+    pick(FIRST_NAMES, "first", real)
+}
+
+fn fake_email(real: &str) -> String {
+    // Parent fields can hold several addresses.
+    real.split(',')
+        .map(|addr| addr.trim())
+        .filter(|addr| !addr.is_empty())
+        .map(|addr| {
+            format!(
+                "{}.{}@example.com",
+                pick(FIRST_NAMES, "email-first", addr).to_lowercase(),
+                pick(LAST_NAMES, "email-last", addr).to_lowercase()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A last name, anonymized if the current request is.
+pub fn last(real: &str) -> Cow<'_, str> {
+    if active() {
+        Cow::Borrowed(fake_last(real))
+    } else {
+        Cow::Borrowed(real)
+    }
+}
+
+/// The rest of a name (first name, middle initial, etc.), anonymized if
+/// the current request is.
+pub fn rest(real: &str) -> Cow<'_, str> {
+    if active() {
+        Cow::Borrowed(fake_first(real))
+    } else {
+        Cow::Borrowed(real)
+    }
+}
+
+/// A whole name (like a Teacher's display name), anonymized if the current
+/// request is.
+pub fn name(real: &str) -> Cow<'_, str> {
+    if active() {
+        Cow::Owned(format!(
+            "{} {}",
+            pick(FIRST_NAMES, "name-first", real),
+            pick(LAST_NAMES, "name-last", real)
+        ))
+    } else {
+        Cow::Borrowed(real)
+    }
+}
+
+/// An email address (or comma-separated list of them), anonymized if the
+/// current request is.
+pub fn email(real: &str) -> Cow<'_, str> {
+    if active() {
+        Cow::Owned(fake_email(real))
+    } else {
+        Cow::Borrowed(real)
+    }
+}
+
+/**
+Functions for `#[serde(serialize_with = "...")]` on fields holding names
+and email addresses, like
+```ignore
+#[derive(Serialize)]
+struct StudentData<'a> {
+    uname: &'a str,
+    #[serde(serialize_with = "anon::ser::last")]
+    last: &'a str,
+}
+```
+*/
+pub mod ser {
+    use serde::Serializer;
+
+    pub fn last<T: AsRef<str>, S: Serializer>(v: &T, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&super::last(v.as_ref()))
+    }
+
+    pub fn rest<T: AsRef<str>, S: Serializer>(v: &T, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&super::rest(v.as_ref()))
+    }
+
+    pub fn name<T: AsRef<str>, S: Serializer>(v: &T, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&super::name(v.as_ref()))
+    }
+
+    pub fn email<T: AsRef<str>, S: Serializer>(v: &T, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&super::email(v.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::user::{BaseUser, Role};
+
+    #[tokio::test]
+    async fn anonymized_users() {
+        let base = BaseUser {
+            uname: "frog".to_owned(),
+            role: Role::Student,
+            salt: String::new(),
+            email: "fred.frog@gmail.com".to_owned(),
+        };
+        let u = base.into_student(
+            "Frog".to_owned(),
+            "Frederick".to_owned(),
+            "berro".to_owned(),
+            "ferd.frog@gmail.com, fran.frog@gmail.com".to_owned(),
+            None,
+            None,
+            0.2,
+            0.2,
+            0,
+            0,
+        );
+
+        let real = serde_json::to_value(&u).unwrap();
+        let fake = scope(true, async { serde_json::to_value(&u).unwrap() }).await;
+        let again = scope(true, async { serde_json::to_value(&u).unwrap() }).await;
+        assert_eq!(fake, again);
+
+        let real = &real["Student"];
+        let fake = &fake["Student"];
+        assert_eq!(real["last"], "Frog");
+        assert_eq!(fake["base"]["uname"], "frog");
+        assert_eq!(fake["teacher"], "berro");
+        assert!(LAST_NAMES.contains(&fake["last"].as_str().unwrap()));
+        assert!(FIRST_NAMES.contains(&fake["rest"].as_str().unwrap()));
+        assert!(fake["base"]["email"].as_str().unwrap().ends_with("@example.com"));
+        let parents = fake["parent"].as_str().unwrap();
+        assert_eq!(parents.matches("@example.com").count(), 2);
+        assert!(!parents.contains("frog"));
+
+        assert!(!active());
+        assert_eq!(name("Mr Berro"), "Mr Berro");
+    }
+}
@@ -15,21 +15,83 @@ CREATE TABLE keys (
     uname     TEXT REFERENCES users,
     last_used TIMESTAMP
 );
+
+CREATE TABLE failures (
+    uname        TEXT PRIMARY KEY REFERENCES users,
+    attempts     INTEGER,
+    locked_until TIMESTAMP
+);
+
+CREATE TABLE meta (
+    key   TEXT PRIMARY KEY,
+    value TEXT
+);
 ```
 
 Additionally, each `uname` should have a short `salt` string associated with
 it (stored separately somewhere) for use in password hashing.
+
+Passwords are hashed with Argon2id (at the costs set by
+[`Db::set_password_hashing`]), with the user's salt as Argon2's secret
+input, so a hash still only works alongside the salt it was made with.
+These hashes are stored as PHC strings (`$argon2id$v=19$m=...`), which
+carry their own scheme, costs, and random salt. Hashes from before Argon2
+(64 hex digits of the [`blake3`] hash of the password and salt) are still
+accepted, and the next time such a user logs in, their password is hashed
+again with Argon2id and stored that way. The same happens to Argon2 hashes
+made with costs other than the current ones.
+
+Since every API request has its key checked, successful key checks can be
+remembered for a short time (see [`Db::set_key_cache_seconds`]), so a
+user clicking around doesn't cost a database round trip per click. Cached
+validations are forgotten whenever a user's keys are revoked, their
+password changes, or they're deleted.
 */
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
 use blake3::Hasher;
+use deadpool_postgres::Pool;
 use rand::{distributions, Rng};
+use serde::{Deserialize, Serialize};
 use tokio_postgres::{types::Type, Client, NoTls, Transaction};
 
+use crate::{
+    pool::{new_pool, PgClient},
+    DbSanity,
+};
+
 // Defaults for [`Db`] key generation; new `Db`s will be instantiated with
 // these values, which can be changed later.
 const DEFAULT_KEY_LENGTH: usize = 32;
 const DEFAULT_KEY_CHARS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
 const DEFAULT_KEY_LIFE_SECONDS: u64 = 20 * 60; // 20 minutes
 
+// Defaults for [`Db`] login lockout behavior. After `DEFAULT_LOCKOUT_THRESHOLD`
+// consecutive failed password attempts, a user is locked out for
+// `DEFAULT_LOCKOUT_SECONDS`; each subsequent failure doubles the lockout
+// period, up to a maximum of `DEFAULT_LOCKOUT_MAX_SECONDS`.
+const DEFAULT_LOCKOUT_THRESHOLD: u32 = 5;
+const DEFAULT_LOCKOUT_SECONDS: u64 = 60; // 1 minute
+const DEFAULT_LOCKOUT_MAX_SECONDS: u64 = 60 * 60; // 1 hour
+
+/// Salt used to hash the password in a [`Db::dummy_check`].
+const DUMMY_SALT: &str = "dummy";
+
+/// The key cache gets swept of expired entries whenever it grows past
+/// this size.
+const KEY_CACHE_SWEEP_SIZE: usize = 1024;
+
 /*
 The method [`Db::ensure_db_schema`] will (attempt to) ensure the backing
 Postgres store contains the necessary tables.
@@ -41,6 +103,8 @@ corresponding query in `SCHEMA` to create the appropriate table.
 static SCHEMA_TEST: &[&str] = &[
     "SELECT FROM information_schema.tables WHERE table_name = 'users'",
     "SELECT FROM information_schema.tables WHERE table_name = 'keys'",
+    "SELECT FROM information_schema.tables WHERE table_name = 'failures'",
+    "SELECT FROM information_schema.tables WHERE table_name = 'meta'",
 ];
 
 static SCHEMA: &[&str] = &[
@@ -53,11 +117,57 @@ static SCHEMA: &[&str] = &[
         uname TEXT REFERENCES users,
         last_used TIMESTAMP
     )",
+    "CREATE TABLE failures (
+        uname TEXT PRIMARY KEY REFERENCES users,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        locked_until TIMESTAMP
+    )",
+    "CREATE TABLE meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    )",
 ];
 
-/// Used to hash passwords with the [`blake3`] algorithm, both when storing
-/// hashed password text in the database and when comparing provided passwords
-/// with stored hashes.
+/// How hard to work at hashing each password; see the module-level docs.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct PasswordHashing {
+    /// Memory used (in KiB). Default is 19456 (19 MiB).
+    pub memory_kib: u32,
+    /// Number of passes over that memory. Default is 2.
+    pub iterations: u32,
+    /// Number of lanes hashed in parallel. Default is 1.
+    pub parallelism: u32,
+}
+
+impl Default for PasswordHashing {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl PasswordHashing {
+    fn params(&self) -> Result<Params, DbError> {
+        Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|e| DbError(format!("Invalid password hashing parameters: {}", &e)))
+    }
+}
+
+/// Whether a password matched a stored hash.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Verified {
+    No,
+    Yes,
+    /// It matched, but the hash should be replaced with a current one.
+    Stale,
+}
+
+/// The pre-Argon2 password hash: [`blake3`] of the password followed by
+/// the salt, as hex. Now only used to check hashes from before Argon2.
 fn hash_with_salt(pwd: &str, salt: &[u8]) -> String {
     let mut hasher = Hasher::new();
     hasher.update(pwd.as_bytes());
@@ -66,6 +176,87 @@ fn hash_with_salt(pwd: &str, salt: &[u8]) -> String {
     String::from(hash.to_hex().as_str())
 }
 
+/// Compare two hashes in time that depends only on their lengths (which
+/// will be the same for any two hashes generated by [`hash_with_salt`]), so
+/// response times don't leak how much of a guessed password's hash matched.
+fn hashes_match(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn argon2_with_secret(salt: &str, params: Params) -> Result<Argon2<'_>, DbError> {
+    Argon2::new_with_secret(salt.as_bytes(), Algorithm::Argon2id, Version::V0x13, params)
+        .map_err(|e| DbError(format!("Unable to set up password hashing: {}", &e)))
+}
+
+/// Hash `pwd` with Argon2id (and a fresh random salt), using `salt` as the
+/// secret input; see the module-level docs.
+fn hash_password(pwd: &str, salt: &str, params: Params) -> Result<String, DbError> {
+    let argon = argon2_with_secret(salt, params)?;
+    let phc_salt = SaltString::generate(&mut OsRng);
+    argon
+        .hash_password(pwd.as_bytes(), &phc_salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| DbError(format!("Error hashing password: {}", &e)))
+}
+
+/// Check `pwd` (and `salt`) against the `stored` hash, whichever scheme it
+/// uses. Argon2 hashes made with costs other than `params` are stale.
+fn verify_password(stored: &str, pwd: &str, salt: &str, params: Params) -> Verified {
+    if !stored.starts_with("$argon2") {
+        return match hashes_match(stored, &hash_with_salt(pwd, salt.as_bytes())) {
+            true => Verified::Stale,
+            false => Verified::No,
+        };
+    }
+
+    let hash = match PasswordHash::new(stored) {
+        Ok(hash) => hash,
+        Err(e) => {
+            log::error!("Unreadable password hash {:?}: {}", stored, &e);
+            return Verified::No;
+        }
+    };
+    let argon = match argon2_with_secret(salt, params.clone()) {
+        Ok(argon) => argon,
+        Err(e) => {
+            log::error!("{}", &e);
+            return Verified::No;
+        }
+    };
+    if argon.verify_password(pwd.as_bytes(), &hash).is_err() {
+        return Verified::No;
+    }
+
+    let current = hash.algorithm == Algorithm::Argon2id.ident()
+        && Params::try_from(&hash)
+            .map(|p| {
+                (p.m_cost(), p.t_cost(), p.p_cost())
+                    == (params.m_cost(), params.t_cost(), params.p_cost())
+            })
+            .unwrap_or(false);
+    if current {
+        Verified::Yes
+    } else {
+        Verified::Stale
+    }
+}
+
+/// Run the (deliberately slow) password hashing function `f` somewhere it
+/// won't hold up other requests.
+async fn off_thread<T, F>(f: F) -> Result<T, DbError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| DbError(format!("Password hashing task failed: {}", &e)))
+}
+
 /**
 Errors returned by this module. Some are passed along from the underlying
 [`tokio_postgres`] crate; some are sui-generis strings.
@@ -123,6 +314,87 @@ pub enum AuthResult {
     NoSuchUser,
     BadPassword,
     InvalidKey,
+    /// Too many consecutive failed password attempts; the user is locked
+    /// out for the contained number of seconds.
+    Locked(i64),
+}
+
+/// How well the key cache is working; see [`Db::key_cache_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+pub struct KeyCacheStats {
+    /// Key checks answered from the cache.
+    pub hits: u64,
+    /// Key checks that had to go to the database.
+    pub misses: u64,
+    /// Validations currently cached (some of which may have expired).
+    pub entries: usize,
+}
+
+/// Recently-validated `(uname, key)` pairs, and when each validation
+/// expires.
+struct KeyCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<(String, String), Instant>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl KeyCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether `key` has been validated for `uname` recently enough. Counts
+    /// as a hit or a miss.
+    fn check(&self, uname: &str, key: &str) -> bool {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let k = (uname.to_owned(), key.to_owned());
+        let hit = match entries.get(&k) {
+            Some(expires) if *expires > now => true,
+            Some(_) => {
+                entries.remove(&k);
+                false
+            }
+            None => false,
+        };
+        if hit {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        hit
+    }
+
+    fn insert(&self, uname: &str, key: &str) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= KEY_CACHE_SWEEP_SIZE {
+            entries.retain(|_, expires| *expires > now);
+        }
+        entries.insert((uname.to_owned(), key.to_owned()), now + self.ttl);
+    }
+
+    /// Forget every cached validation for any of `unames`.
+    fn forget(&self, unames: &[&str]) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|(uname, _), _| !unames.contains(&uname.as_str()));
+    }
+
+    fn stats(&self) -> KeyCacheStats {
+        KeyCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
 }
 
 /**
@@ -149,6 +421,18 @@ pub struct Db {
     key_chars: Vec<char>,
     key_length: usize,
     key_life: String,
+    key_life_seconds: u64,
+    /// `None` if key checks aren't being cached.
+    key_cache: Option<KeyCache>,
+    lockout_threshold: u32,
+    lockout_seconds: u64,
+    lockout_max_seconds: u64,
+    environment: String,
+    dummy_checks: bool,
+    /// Argon2 costs for new password hashes.
+    hash_params: Params,
+    /// `None` unless connections are pooled; see [`Db::set_pool_size`].
+    pool: Option<Pool>,
 }
 
 impl Db {
@@ -164,6 +448,16 @@ impl Db {
             key_chars,
             key_length,
             key_life,
+            key_life_seconds: DEFAULT_KEY_LIFE_SECONDS,
+            key_cache: None,
+            lockout_threshold: DEFAULT_LOCKOUT_THRESHOLD,
+            lockout_seconds: DEFAULT_LOCKOUT_SECONDS,
+            lockout_max_seconds: DEFAULT_LOCKOUT_MAX_SECONDS,
+            environment: crate::DEFAULT_ENVIRONMENT.to_owned(),
+            dummy_checks: true,
+            // The defaults are known to be valid.
+            hash_params: PasswordHashing::default().params().unwrap(),
+            pool: None,
         }
     }
 
@@ -178,6 +472,74 @@ impl Db {
     }
     pub fn set_key_life(&mut self, seconds: u64) {
         self.key_life = format!("{} seconds", &seconds);
+        self.key_life_seconds = seconds;
+        if let Some(cache) = &self.key_cache {
+            let cache_seconds = cache.ttl.as_secs();
+            self.set_key_cache_seconds(cache_seconds);
+        }
+    }
+    /**
+    Remember successful key checks for this many seconds (but never longer
+    than keys live). Zero (the default) turns caching off.
+
+    Cached checks don't update the key's `last_used` time, so a key is only
+    kept alive by the checks that miss the cache; this is fine as long as
+    the cache lifetime is much shorter than the key lifetime.
+    */
+    pub fn set_key_cache_seconds(&mut self, seconds: u64) {
+        let seconds = seconds.min(self.key_life_seconds);
+        self.key_cache = if seconds == 0 {
+            None
+        } else {
+            Some(KeyCache::new(Duration::from_secs(seconds)))
+        };
+    }
+    /// A threshold of zero disables lockout entirely.
+    pub fn set_lockout_threshold(&mut self, attempts: u32) {
+        self.lockout_threshold = attempts;
+    }
+    pub fn set_lockout_seconds(&mut self, seconds: u64) {
+        self.lockout_seconds = seconds;
+    }
+    pub fn set_lockout_max_seconds(&mut self, seconds: u64) {
+        self.lockout_max_seconds = seconds;
+    }
+    pub fn set_environment(&mut self, environment: &str) {
+        self.environment = environment.to_owned();
+    }
+    /// Whether [`Db::dummy_check`] should actually do anything.
+    pub fn set_dummy_checks(&mut self, enabled: bool) {
+        self.dummy_checks = enabled;
+    }
+    /// Set the costs of hashing passwords. Passwords hashed at other costs
+    /// are hashed again the next time their users log in.
+    pub fn set_password_hashing(&mut self, hashing: &PasswordHashing) -> Result<(), DbError> {
+        self.hash_params = hashing.params()?;
+        Ok(())
+    }
+    /// Keep a pool of up to `size` connections, instead of opening a new one
+    /// for each interaction (see [`pool`](crate::pool)). Zero turns pooling
+    /// off.
+    pub fn set_pool_size(&mut self, size: usize) -> Result<(), DbError> {
+        self.pool = match size {
+            0 => None,
+            n => Some(new_pool(&self.connection_string, n)?),
+        };
+        Ok(())
+    }
+
+    /// Length of the lockout period triggered by the given number of
+    /// consecutive failed attempts, or `None` if it doesn't trigger one.
+    ///
+    /// The period doubles with each failure beyond the threshold, capped
+    /// at `self.lockout_max_seconds`.
+    fn lockout_period(&self, attempts: u32) -> Option<u64> {
+        if self.lockout_threshold == 0 || attempts < self.lockout_threshold {
+            return None;
+        }
+        let doublings = (attempts - self.lockout_threshold).min(32);
+        let secs = self.lockout_seconds.saturating_mul(1u64 << doublings);
+        Some(secs.min(self.lockout_max_seconds))
     }
 
     /// Generate a new authentication key based on the current values of
@@ -191,17 +553,25 @@ impl Db {
     }
 
     /**
-    Open and return a connection to this database.
+    Open (or take from the pool) and return a connection to this database.
 
     This is necessary for methods that require an
     [`&Transaction`](Transaction) as an argument.
     */
-    pub async fn connect(&self) -> Result<Client, DbError> {
+    pub async fn connect(&self) -> Result<PgClient, DbError> {
         log::trace!(
             "Db::connect() called w/connection string: {:?}",
             &self.connection_string
         );
 
+        if let Some(pool) = &self.pool {
+            return pool
+                .get()
+                .await
+                .map(PgClient::Pooled)
+                .map_err(|e| format!("Connection error: {}", &e).into());
+        }
+
         match tokio_postgres::connect(&self.connection_string, NoTls).await {
             Ok((client, connection)) => {
                 log::trace!("    ...connection successful.");
@@ -212,7 +582,7 @@ impl Db {
                         log::trace!("tokio connection runtime drops.");
                     }
                 });
-                Ok(client)
+                Ok(PgClient::Direct(client))
             }
             Err(e) => {
                 log::trace!("    ...connection failed: {:?}", &e);
@@ -285,9 +655,17 @@ impl Db {
 
         let owned_unames: Vec<String> = unames.iter().map(|s| String::from(*s)).collect();
 
-        let hashes: Vec<String> = std::iter::zip(passwords, salts)
-            .map(|(pwd, salt)| hash_with_salt(pwd, salt.as_bytes()))
+        let pairs: Vec<(String, String)> = std::iter::zip(passwords, salts)
+            .map(|(pwd, salt)| (pwd.to_string(), salt.to_string()))
             .collect();
+        let params = self.hash_params.clone();
+        let hashes: Vec<String> = off_thread(move || {
+            pairs
+                .iter()
+                .map(|(pwd, salt)| hash_password(pwd, salt, params.clone()))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .await??;
 
         let preexisting_user_query = t
             .prepare_typed(
@@ -368,6 +746,8 @@ impl Db {
     pub async fn delete_users(&self, t: &Transaction<'_>, unames: &[&str]) -> Result<u64, DbError> {
         log::trace!("Db::delete_users( {:?} ) called", &unames);
 
+        self.forget_cached_keys(unames);
+
         let owned_unames: Vec<String> = unames.iter().map(|s| String::from(*s)).collect();
 
         let n_keys = t
@@ -375,6 +755,9 @@ impl Db {
             .await?;
         log::trace!("Deleted {} keys.", &n_keys);
 
+        t.execute("DELETE FROM failures WHERE uname = ANY($1)", &[&owned_unames])
+            .await?;
+
         let n_users = t
             .execute("DELETE FROM users WHERE uname = ANY($1)", &[&owned_unames])
             .await?;
@@ -383,6 +766,38 @@ impl Db {
         Ok(n_users)
     }
 
+    /// Return the contents of the `meta` table and the number of rows in each
+    /// table.
+    pub async fn get_sanity(&self) -> Result<DbSanity, DbError> {
+        log::trace!("Db::get_sanity() called.");
+
+        let client = self.connect().await?;
+        let mut sanity = DbSanity::default();
+        for row in client.query("SELECT key, value FROM meta", &[]).await?.iter() {
+            sanity.meta.insert(row.get("key"), row.get("value"));
+        }
+        for table in ["users", "keys", "failures", "meta"] {
+            let row = client
+                .query_one(&format!("SELECT COUNT(*) FROM {}", table), &[])
+                .await
+                .map_err(|e| format!("Error counting {}: {}", table, &e))?;
+            sanity.row_counts.insert(table.to_owned(), row.get(0));
+        }
+
+        Ok(sanity)
+    }
+
+    /// Return the `uname`s of all users in the database.
+    pub async fn get_unames(&self) -> Result<HashSet<String>, DbError> {
+        log::trace!("Db::get_unames() called.");
+
+        let client = self.connect().await?;
+        let rows = client.query("SELECT uname FROM users", &[]).await?;
+        let unames: HashSet<String> = rows.iter().map(|row| row.get("uname")).collect();
+
+        Ok(unames)
+    }
+
     pub async fn check_password(
         &self,
         uname: &str,
@@ -396,7 +811,31 @@ impl Db {
             salt
         );
 
-        let current_hash = hash_with_salt(password, salt.as_bytes());
+        let client = self.connect().await?;
+        self.check_password_tracked(&client, uname, password, salt)
+            .await
+    }
+
+    /**
+    Check whether the provided `(uname, password, salt)` combination is valid
+    without recording the attempt or respecting any lockout.
+
+    This is for internal housekeeping (like checking whether the default
+    Admin is still using the default password at startup); anything that
+    services an actual login should use [`Db::check_password`] instead.
+    */
+    pub async fn check_password_untracked(
+        &self,
+        uname: &str,
+        password: &str,
+        salt: &str,
+    ) -> Result<AuthResult, DbError> {
+        log::trace!(
+            "Db::check_password_untracked( {:?}, {:?}, {:?} ) called.",
+            uname,
+            password,
+            salt
+        );
 
         let client = self.connect().await?;
 
@@ -415,15 +854,242 @@ impl Db {
             }
             Ok(Some(row)) => {
                 let stored_hash: String = row.get("hash");
-                if stored_hash == current_hash {
-                    Ok(AuthResult::Ok)
-                } else {
-                    Ok(AuthResult::BadPassword)
+                match self.verify(stored_hash, password, salt).await? {
+                    Verified::No => Ok(AuthResult::BadPassword),
+                    Verified::Yes | Verified::Stale => Ok(AuthResult::Ok),
+                }
+            }
+        }
+    }
+
+    /// Check `password` (and `salt`) against the `stored` hash.
+    async fn verify(
+        &self,
+        stored: String,
+        password: &str,
+        salt: &str,
+    ) -> Result<Verified, DbError> {
+        let (password, salt) = (password.to_owned(), salt.to_owned());
+        let params = self.hash_params.clone();
+        off_thread(move || verify_password(&stored, &password, &salt, params)).await
+    }
+
+    /// Replace `uname`'s `stale` password hash with a current one (unless
+    /// it's been changed in the meantime).
+    async fn rehash(
+        &self,
+        client: &Client,
+        uname: &str,
+        stale: &str,
+        password: &str,
+        salt: &str,
+    ) -> Result<(), DbError> {
+        let (password, salt) = (password.to_owned(), salt.to_owned());
+        let params = self.hash_params.clone();
+        let new_hash = off_thread(move || hash_password(&password, &salt, params)).await??;
+        let n = client
+            .execute(
+                "UPDATE users SET hash = $1 WHERE uname = $2 AND hash = $3",
+                &[&new_hash, &uname, &stale],
+            )
+            .await?;
+        if n > 0 {
+            log::info!("Upgraded password hash of {:?}.", uname);
+        }
+        Ok(())
+    }
+
+    /**
+    Check a password while honoring (and updating) the user's record of
+    failed login attempts.
+
+    If the user is currently locked out, the password is checked (so this
+    takes as long as it otherwise would), but it doesn't matter whether it's
+    right. A bad password increments the user's count of consecutive failures (and
+    possibly triggers a lockout); a good one clears it.
+    */
+    async fn check_password_tracked(
+        &self,
+        client: &Client,
+        uname: &str,
+        password: &str,
+        salt: &str,
+    ) -> Result<AuthResult, DbError> {
+        if let Some(secs) = self.lockout_remaining(client, uname).await? {
+            log::info!("User {:?} is locked out for {} more seconds.", uname, &secs);
+            if let Some(row) = client
+                .query_opt("SELECT hash FROM users WHERE uname = $1", &[&uname])
+                .await?
+            {
+                let stored_hash: String = row.try_get("hash")?;
+                std::hint::black_box(self.verify(stored_hash, password, salt).await?);
+            }
+            return Ok(AuthResult::Locked(secs));
+        }
+
+        let stored_hash: String = match client
+            .query_opt("SELECT hash FROM users WHERE uname = $1", &[&uname])
+            .await
+        {
+            Err(e) => {
+                let estr = format!("Error querying user {:?}: {}", uname, &e);
+                log::error!("{}", &estr);
+                return Err(DbError(estr));
+            }
+            Ok(None) => {
+                log::trace!("User {:?} doesn't exist.", uname);
+                return Ok(AuthResult::NoSuchUser);
+            }
+            Ok(Some(row)) => row.get("hash"),
+        };
+
+        let verified = self.verify(stored_hash.clone(), password, salt).await?;
+        if verified != Verified::No {
+            client
+                .execute("DELETE FROM failures WHERE uname = $1", &[&uname])
+                .await?;
+            if verified == Verified::Stale {
+                // The login is good either way; the old hash will do until
+                // next time.
+                if let Err(e) = self.rehash(client, uname, &stored_hash, password, salt).await {
+                    log::error!("Error upgrading password hash of {:?}: {}", uname, &e);
                 }
             }
+            return Ok(AuthResult::Ok);
+        }
+
+        let row = client
+            .query_one(
+                "INSERT INTO failures (uname, attempts) VALUES ($1, 1)
+                ON CONFLICT (uname) DO UPDATE
+                SET attempts = failures.attempts + 1
+                RETURNING attempts",
+                &[&uname],
+            )
+            .await?;
+        let attempts: i32 = row.try_get("attempts")?;
+        log::trace!("User {:?} has {} consecutive failed attempts.", uname, &attempts);
+
+        match self.lockout_period(attempts.max(0) as u32) {
+            None => Ok(AuthResult::BadPassword),
+            Some(secs) => {
+                log::warn!(
+                    "Locking out {:?} for {} seconds after {} failed attempts.",
+                    uname,
+                    &secs,
+                    &attempts
+                );
+                let secs_str = format!("{} seconds", &secs);
+                client
+                    .execute(
+                        "UPDATE failures
+                        SET locked_until = now() + ($1 || ' ')::INTERVAL
+                        WHERE uname = $2",
+                        &[&secs_str, &uname],
+                    )
+                    .await?;
+                Ok(AuthResult::Locked(secs as i64))
+            }
+        }
+    }
+
+    /**
+    Do roughly the same work as checking a password, but for a `uname`
+    that doesn't exist, and without any result.
+
+    Login handlers should call this when asked to log in a user who isn't
+    in the system, so that the failure takes about as long as it would for
+    a real user with the wrong password, and response times can't be used
+    to discover which unames exist. It does nothing if disabled with
+    [`Db::set_dummy_checks`].
+    */
+    pub async fn dummy_check(&self, uname: &str, password: &str) {
+        if !self.dummy_checks {
+            return;
+        }
+        log::trace!("Db::dummy_check( {:?}, [ password ] ) called.", uname);
+
+        let (pwd, params) = (password.to_owned(), self.hash_params.clone());
+        let hash = match off_thread(move || hash_password(&pwd, DUMMY_SALT, params)).await {
+            Ok(Ok(hash)) => std::hint::black_box(hash),
+            Ok(Err(e)) | Err(e) => {
+                log::error!("Error hashing for dummy password check: {}", &e);
+                return;
+            }
+        };
+        let client = match self.connect().await {
+            Ok(client) => client,
+            Err(e) => {
+                log::error!("Error connecting for dummy password check: {}", &e);
+                return;
+            }
+        };
+        if let Err(e) = self.lockout_remaining(&client, uname).await {
+            log::error!("Error in dummy password check: {}", &e);
+        }
+        if let Ok(Some(row)) = client
+            .query_opt("SELECT hash FROM users WHERE uname = $1", &[&uname])
+            .await
+        {
+            let stored_hash: String = row.get("hash");
+            std::hint::black_box(hashes_match(&stored_hash, &hash));
+        }
+    }
+
+    /// Return the number of seconds remaining in the given user's lockout
+    /// period, if any.
+    async fn lockout_remaining(&self, client: &Client, uname: &str) -> Result<Option<i64>, DbError> {
+        let row = client
+            .query_opt(
+                "SELECT CEIL(EXTRACT(EPOCH FROM (locked_until - now())))::BIGINT AS remaining
+                FROM failures
+                WHERE uname = $1
+                AND locked_until > now()",
+                &[&uname],
+            )
+            .await?;
+
+        match row {
+            None => Ok(None),
+            Some(row) => Ok(Some(row.try_get("remaining")?)),
         }
     }
 
+    /**
+    Return the number of consecutive failed login attempts recorded for
+    the given user and the number of seconds remaining in any current
+    lockout.
+    */
+    pub async fn get_lockout(&self, uname: &str) -> Result<(u32, Option<i64>), DbError> {
+        log::trace!("Db::get_lockout( {:?} ) called.", uname);
+
+        let client = self.connect().await?;
+        let attempts: i32 = match client
+            .query_opt("SELECT attempts FROM failures WHERE uname = $1", &[&uname])
+            .await?
+        {
+            None => 0,
+            Some(row) => row.try_get("attempts")?,
+        };
+        let remaining = self.lockout_remaining(&client, uname).await?;
+
+        Ok((attempts.max(0) as u32, remaining))
+    }
+
+    /// Clear any record of failed login attempts (and therefore any lockout)
+    /// for the given user.
+    pub async fn clear_failures(&self, uname: &str) -> Result<(), DbError> {
+        log::trace!("Db::clear_failures( {:?} ) called.", uname);
+
+        let client = self.connect().await?;
+        let n = client
+            .execute("DELETE FROM failures WHERE uname = $1", &[&uname])
+            .await?;
+        log::trace!("Cleared {} failure records.", &n);
+
+        Ok(())
+    }
+
     /// Issue a key without checking whether a password is valid first.
     ///
     /// This is so an authentication token (that is, a key) can be sent via
@@ -464,28 +1130,15 @@ impl Db {
             salt
         );
 
-        let current_hash = hash_with_salt(password, salt.as_bytes());
-
         let client = self.connect().await?;
 
-        match client
-            .query_opt("SELECT hash FROM users WHERE uname = $1", &[&uname])
-            .await
+        match self
+            .check_password_tracked(&client, uname, password, salt)
+            .await?
         {
-            Err(e) => {
-                let estr = format!("Error querying user {:?}: {}", uname, &e);
-                log::error!("{}", &estr);
-                return Err(DbError(estr));
-            }
-            Ok(None) => {
-                log::trace!("User {:?} doesn't exist.", uname);
-                return Ok(AuthResult::NoSuchUser);
-            }
-            Ok(Some(row)) => {
-                let stored_hash: String = row.get("hash");
-                if stored_hash != current_hash {
-                    return Ok(AuthResult::BadPassword);
-                }
+            AuthResult::Ok => { /* Proceed to issue a key. */ }
+            x => {
+                return Ok(x);
             }
         }
 
@@ -514,6 +1167,12 @@ impl Db {
     pub async fn check_key(&self, uname: &str, key: &str) -> Result<AuthResult, DbError> {
         log::trace!("Db::check_key( {:?}, {:?} ) called.", uname, key);
 
+        if let Some(cache) = &self.key_cache {
+            if cache.check(uname, key) {
+                return Ok(AuthResult::Ok);
+            }
+        }
+
         let client = self.connect().await?;
         let key = match client
             .query_opt(
@@ -541,9 +1200,61 @@ impl Db {
             )
             .await?;
 
+        if let Some(cache) = &self.key_cache {
+            cache.insert(uname, &key);
+        }
+
         Ok(AuthResult::Ok)
     }
 
+    /// Forget any cached key validations for the given users (so their next
+    /// key checks go to the database).
+    pub fn forget_cached_keys(&self, unames: &[&str]) {
+        if let Some(cache) = &self.key_cache {
+            cache.forget(unames);
+        }
+    }
+
+    /// Hit rate (and size) of the key cache, or `None` if keys aren't being
+    /// cached.
+    pub fn key_cache_stats(&self) -> Option<KeyCacheStats> {
+        self.key_cache.as_ref().map(|c| c.stats())
+    }
+
+    /**
+    Revoke the given `key` of `uname` (as when logging out), or all of their
+    keys if `key` is `None`.
+
+    Returns the number of keys revoked.
+    */
+    pub async fn revoke_keys(&self, uname: &str, key: Option<&str>) -> Result<u64, DbError> {
+        log::trace!("Db::revoke_keys( {:?}, {:?} ) called.", uname, key);
+
+        let client = self.connect().await?;
+        let n = match key {
+            Some(key) => {
+                client
+                    .execute(
+                        "DELETE FROM keys WHERE uname = $1 AND key = $2",
+                        &[&uname, &key],
+                    )
+                    .await?
+            }
+            None => {
+                client
+                    .execute("DELETE FROM keys WHERE uname = $1", &[&uname])
+                    .await?
+            }
+        };
+        log::trace!("Revoked {} keys.", &n);
+
+        // Only now that the keys are gone from the database; forgetting them
+        // any sooner would let a key check in the meantime cache them again.
+        self.forget_cached_keys(&[uname]);
+
+        Ok(n)
+    }
+
     /// Delete any keys that have been unused for longer than `self.key_life`.
     pub async fn cull_old_keys(&self) -> Result<usize, DbError> {
         log::trace!("Db::cull_old_keys() called.");
@@ -573,8 +1284,11 @@ impl Db {
             salt
         );
 
-        let new_hash = hash_with_salt(password, salt.as_bytes());
+        let (pwd, owned_salt) = (password.to_owned(), salt.to_owned());
+        let params = self.hash_params.clone();
+        let new_hash = off_thread(move || hash_password(&pwd, &owned_salt, params)).await??;
         let client = self.connect().await?;
+        self.forget_cached_keys(&[uname]);
 
         match client
             .execute(
@@ -601,13 +1315,136 @@ impl Db {
         }
     }
 
+    /// Every user's `(uname, hash)`, in order of uname, for exporting (see
+    /// [`auth_export`](crate::auth_export)).
+    pub async fn get_hashes(&self) -> Result<Vec<(String, String)>, DbError> {
+        log::trace!("Db::get_hashes() called.");
+
+        let client = self.connect().await?;
+        let rows = client
+            .query("SELECT uname, hash FROM users ORDER BY uname", &[])
+            .await?;
+        let hashes = rows
+            .iter()
+            .map(|row| (row.get("uname"), row.get("hash")))
+            .collect();
+        Ok(hashes)
+    }
+
+    /**
+    Restore the given `(uname, hash)` pairs (as from [`Db::get_hashes`]),
+    adding users who aren't in the database and replacing the hashes of
+    those who are, all in one transaction. Nobody else is touched.
+
+    Returns the number of users restored.
+    */
+    pub async fn restore_hashes(&self, hashes: &[(&str, &str)]) -> Result<u64, DbError> {
+        log::trace!("Db::restore_hashes( [ {} hashes ] ) called.", hashes.len());
+
+        let unames: Vec<&str> = hashes.iter().map(|(uname, _)| *uname).collect();
+        self.forget_cached_keys(&unames);
+
+        let mut client = self.connect().await?;
+        let t = client
+            .transaction()
+            .await
+            .map_err(|e| format!("Auth DB unable to begin transaction: {}", &e))?;
+        let stmt = t
+            .prepare_typed(
+                "INSERT INTO users (uname, hash) VALUES ($1, $2)
+                ON CONFLICT (uname) DO UPDATE SET hash = EXCLUDED.hash",
+                &[Type::TEXT, Type::TEXT],
+            )
+            .await
+            .map_err(|e| format!("Unable to prepare statement to restore users: {}", &e))?;
+
+        let mut n_restored: u64 = 0;
+        for (uname, hash) in hashes.iter() {
+            n_restored += t
+                .execute(&stmt, &[uname, hash])
+                .await
+                .map_err(|e| format!("Error restoring user {:?}: {}", uname, &e))?;
+        }
+        t.commit()
+            .await
+            .map_err(|e| format!("Error committing restored users: {}", &e))?;
+
+        log::trace!("Restored {} users.", &n_restored);
+        Ok(n_restored)
+    }
+
+    /// Read the environment tag stored in the database, if there is one.
+    async fn read_environment(&self, client: &Client) -> Result<Option<String>, DbError> {
+        if client
+            .query_opt(
+                "SELECT FROM information_schema.tables WHERE table_name = 'meta'",
+                &[],
+            )
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+
+        let opt_row = client
+            .query_opt("SELECT value FROM meta WHERE key = 'environment'", &[])
+            .await?;
+        Ok(opt_row.map(|row| row.get("value")))
+    }
+
+    /**
+    Tag the database as belonging to this `Db`'s environment if it isn't
+    tagged already; return an error if it's tagged as belonging to some
+    other environment.
+    */
+    pub async fn claim_environment(&self) -> Result<(), DbError> {
+        log::trace!("Db::claim_environment() called.");
+
+        let client = self.connect().await?;
+        match self.read_environment(&client).await? {
+            Some(env) if env == self.environment => Ok(()),
+            Some(env) => Err(DbError(format!(
+                "Auth DB belongs to environment {:?}, but this is {:?}.",
+                &env, &self.environment
+            ))),
+            None => {
+                client
+                    .execute(
+                        "INSERT INTO meta (key, value) VALUES ('environment', $1)",
+                        &[&self.environment],
+                    )
+                    .await
+                    .map_err(|e| format!("Unable to tag environment: {}", &e))?;
+                log::info!("Auth DB tagged as {:?} environment.", &self.environment);
+                Ok(())
+            }
+        }
+    }
+
+    /// Return an error if the database is tagged with an environment other
+    /// than this `Db`'s. An untagged database passes.
+    pub async fn check_environment(&self) -> Result<(), DbError> {
+        log::trace!("Db::check_environment() called.");
+
+        let client = self.connect().await?;
+        match self.read_environment(&client).await? {
+            Some(env) if env != self.environment => Err(DbError(format!(
+                "Refusing to touch Auth DB belonging to environment {:?} from {:?}.",
+                &env, &self.environment
+            ))),
+            _ => Ok(()),
+        }
+    }
+
     /**
-    Drop both database tables.
+    Drop all database tables.
 
-    This is largely for cleanup after testing.
+    This is largely for cleanup after testing. It will refuse to run against
+    a database tagged with a different environment.
     */
     pub async fn nuke_database(&self) -> Result<(), DbError> {
         log::trace!("Db::nuke_database() called");
+        self.check_environment().await?;
         let mut client = self.connect().await?;
         let t = client
             .transaction()
@@ -615,6 +1452,14 @@ impl Db {
             .map_err(|e| format!("Auth DB Unable to begin transaction: {}", &e))?;
 
         let mut n_rows: u64 = 0;
+        n_rows += t
+            .execute("DROP TABLE IF EXISTS meta", &[])
+            .await
+            .map_err(|e| format!("Error dropping meta table: {}", &e))?;
+        n_rows += t
+            .execute("DROP TABLE failures", &[])
+            .await
+            .map_err(|e| format!("Error dropping failures table: {}", &e))?;
         n_rows += t
             .execute("DROP TABLE keys", &[])
             .await
@@ -648,6 +1493,61 @@ mod tests {
     static TEST_CONNECTION: &str =
         "host=localhost user=camp_test password='camp_test' dbname=camp_auth_test";
 
+    #[test]
+    fn hash_comparison() {
+        let a = hash_with_salt("booga", b"asdf");
+        assert!(hashes_match(&a, &hash_with_salt("booga", b"asdf")));
+        assert!(!hashes_match(&a, &hash_with_salt("booga", b"hjkl")));
+        assert!(!hashes_match(&a, &hash_with_salt("purple", b"asdf")));
+        assert!(!hashes_match(&a, &a[1..]));
+        assert!(!hashes_match(&a, ""));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn upgrade_hashes() -> Result<(), UnifiedError> {
+        ensure_logging();
+
+        let mut db = Db::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await?;
+        let (uname, pwd, salt) = (USERS[0], PASSWORDS[0], SALTS[0]);
+        let legacy = hash_with_salt(pwd, salt.as_bytes());
+        db.restore_hashes(&[(uname, legacy.as_str())]).await?;
+        async fn stored(db: &Db) -> String {
+            db.get_hashes().await.unwrap().remove(0).1
+        }
+
+        // Checking a password outside of a login leaves the hash alone.
+        assert_eq!(db.check_password_untracked(uname, pwd, salt).await?, AuthResult::Ok);
+        assert_eq!(stored(&db).await, legacy);
+        assert_eq!(db.check_password(uname, "wrong", salt).await?, AuthResult::BadPassword);
+        assert_eq!(stored(&db).await, legacy);
+
+        assert_eq!(db.check_password(uname, pwd, salt).await?, AuthResult::Ok);
+        let upgraded = stored(&db).await;
+        assert!(upgraded.starts_with("$argon2id$v=19$m=19456,t=2,p=1$"));
+        assert_eq!(db.check_password(uname, pwd, salt).await?, AuthResult::Ok);
+        assert_eq!(stored(&db).await, upgraded);
+        assert_eq!(db.check_password(uname, pwd, "hjkl").await?, AuthResult::BadPassword);
+
+        db.set_password_hashing(&PasswordHashing {
+            memory_kib: 8 * 1024,
+            ..Default::default()
+        })?;
+        assert_eq!(db.check_password(uname, pwd, salt).await?, AuthResult::Ok);
+        assert!(stored(&db).await.starts_with("$argon2id$v=19$m=8192,t=2,p=1$"));
+
+        assert!(db
+            .set_password_hashing(&PasswordHashing {
+                iterations: 0,
+                ..Default::default()
+            })
+            .is_err());
+
+        db.nuke_database().await?;
+        Ok(())
+    }
+
     #[tokio::test]
     #[ignore]
     #[serial]
@@ -674,6 +1574,10 @@ mod tests {
         );
         t.commit().await?;
 
+        let unames = db.get_unames().await?;
+        assert_eq!(unames.len(), n_users);
+        assert!(USERS.iter().all(|u| unames.contains(*u)));
+
         for n in 0..n_users {
             let (uname, pwd, salt) = (USERS[n], PASSWORDS[n], SALTS[n]);
             assert_eq!(db.check_password(uname, pwd, salt).await?, AuthResult::Ok);
@@ -693,6 +1597,10 @@ mod tests {
         let t = client.transaction().await?;
         db.delete_users(&t, &USERS[1..]).await?;
         t.commit().await?;
+        assert_eq!(
+            db.get_unames().await?,
+            HashSet::from([USERS[0].to_owned()])
+        );
 
         assert_eq!(
             db.check_password(USERS[1], PASSWORDS[1], SALTS[1])
@@ -705,7 +1613,7 @@ mod tests {
 
         match db.check_password(USERS[1], PASSWORDS[1], SALTS[1]).await {
             Err(_) => { /* this is okay */ }
-            x @ _ => {
+            x => {
                 panic!("Expected Err(_), got {:?}", &x);
             }
         }
@@ -713,6 +1621,45 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn restore_hashes() -> Result<(), UnifiedError> {
+        ensure_logging();
+
+        let db = Db::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await?;
+        let mut client = db.connect().await?;
+        let t = client.transaction().await?;
+        db.add_users(&t, USERS, PASSWORDS, SALTS).await?;
+        t.commit().await?;
+
+        let hashes = db.get_hashes().await?;
+        let unames: Vec<&str> = hashes.iter().map(|(u, _)| u.as_str()).collect();
+        assert_eq!(&unames, USERS);
+
+        db.set_password(USERS[0], "forgotten", SALTS[0]).await?;
+        let t = client.transaction().await?;
+        db.delete_users(&t, &USERS[2..]).await?;
+        t.commit().await?;
+
+        let restore: Vec<(&str, &str)> = hashes
+            .iter()
+            .filter(|(uname, _)| uname != USERS[1])
+            .map(|(uname, hash)| (uname.as_str(), hash.as_str()))
+            .collect();
+        assert_eq!(db.restore_hashes(&restore).await?, 2);
+        assert_eq!(db.get_hashes().await?, hashes);
+        for n in 0..USERS.len() {
+            assert_eq!(
+                db.check_password(USERS[n], PASSWORDS[n], SALTS[n]).await?,
+                AuthResult::Ok
+            );
+        }
+
+        db.nuke_database().await?;
+        Ok(())
+    }
+
     #[tokio::test]
     #[serial]
     async fn issue_keys() -> Result<(), UnifiedError> {
@@ -734,7 +1681,7 @@ mod tests {
             .await?
         {
             AuthResult::Key(k) => k,
-            x @ _ => {
+            x => {
                 panic!("Expected AuthResult::Key(_), got {:?}", &x);
             }
         };
@@ -756,7 +1703,7 @@ mod tests {
             .unwrap()
         {
             AuthResult::Key(k) => k,
-            x @ _ => {
+            x => {
                 panic!("Expected AuthResult::Key(_), got {:?}", &x);
             }
         };
@@ -771,4 +1718,127 @@ mod tests {
         db.nuke_database().await?;
         Ok(())
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn key_cache() -> Result<(), UnifiedError> {
+        ensure_logging();
+
+        let mut db = Db::new(TEST_CONNECTION.to_owned());
+        assert_eq!(db.key_cache_stats(), None);
+        db.set_key_cache_seconds(60);
+        db.ensure_db_schema().await.unwrap();
+        let mut client = db.connect().await?;
+        let t = client.transaction().await?;
+        db.add_users(&t, USERS, PASSWORDS, SALTS).await.unwrap();
+        t.commit().await?;
+
+        let mut keys: Vec<String> = Vec::new();
+        for n in 0..2 {
+            match db
+                .check_password_and_issue_key(USERS[n], PASSWORDS[n], SALTS[n])
+                .await?
+            {
+                AuthResult::Key(k) => keys.push(k),
+                x => {
+                    panic!("Expected AuthResult::Key(_), got {:?}", &x);
+                }
+            }
+        }
+
+        // Miss, then hit.
+        assert_eq!(db.check_key(USERS[0], &keys[0]).await?, AuthResult::Ok);
+        assert_eq!(db.check_key(USERS[0], &keys[0]).await?, AuthResult::Ok);
+        // Invalid keys aren't cached.
+        assert_eq!(
+            db.check_key(USERS[0], "wrong_key").await?,
+            AuthResult::InvalidKey
+        );
+        assert_eq!(db.check_key(USERS[1], &keys[1]).await?, AuthResult::Ok);
+        assert_eq!(
+            db.key_cache_stats(),
+            Some(KeyCacheStats {
+                hits: 1,
+                misses: 3,
+                entries: 2
+            })
+        );
+
+        // Revoking a key has to take effect right away, cache or no.
+        assert_eq!(db.revoke_keys(USERS[0], Some(&keys[0])).await?, 1);
+        assert_eq!(
+            db.check_key(USERS[0], &keys[0]).await?,
+            AuthResult::InvalidKey
+        );
+        // Other users' cached keys are unaffected.
+        assert_eq!(db.check_key(USERS[1], &keys[1]).await?, AuthResult::Ok);
+        assert_eq!(db.key_cache_stats().unwrap().hits, 2);
+
+        db.set_password(USERS[1], "new_password", SALTS[1]).await?;
+        assert_eq!(db.key_cache_stats().unwrap().entries, 0);
+
+        // The cache can't outlive keys.
+        db.set_key_life(1);
+        assert_eq!(db.key_cache.as_ref().unwrap().ttl, Duration::from_secs(1));
+
+        db.nuke_database().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn lockout() -> Result<(), UnifiedError> {
+        ensure_logging();
+
+        let mut db = Db::new(TEST_CONNECTION.to_owned());
+        db.set_lockout_threshold(2);
+        db.set_lockout_seconds(60);
+        db.set_lockout_max_seconds(90);
+        db.ensure_db_schema().await?;
+        let mut client = db.connect().await?;
+        let t = client.transaction().await?;
+        db.add_users(&t, USERS, PASSWORDS, SALTS).await?;
+        t.commit().await?;
+
+        let (uname, pwd, salt) = (USERS[0], PASSWORDS[0], SALTS[0]);
+
+        assert_eq!(
+            db.check_password(uname, "wrong", salt).await?,
+            AuthResult::BadPassword
+        );
+        // A successful attempt resets the count.
+        assert_eq!(db.check_password(uname, pwd, salt).await?, AuthResult::Ok);
+        assert_eq!(db.get_lockout(uname).await?, (0, None));
+
+        assert_eq!(
+            db.check_password(uname, "wrong", salt).await?,
+            AuthResult::BadPassword
+        );
+        assert_eq!(
+            db.check_password(uname, "wrong", salt).await?,
+            AuthResult::Locked(60)
+        );
+        // Even the correct password is refused while locked out.
+        match db.check_password_and_issue_key(uname, pwd, salt).await? {
+            AuthResult::Locked(_) => { /* this is okay */ }
+            x => {
+                panic!("Expected AuthResult::Locked(_), got {:?}", &x);
+            }
+        }
+        // Other users are unaffected.
+        assert_eq!(
+            db.check_password(USERS[1], PASSWORDS[1], SALTS[1]).await?,
+            AuthResult::Ok
+        );
+
+        assert_eq!(db.lockout_period(3), Some(90));
+        assert_eq!(db.lockout_period(1), None);
+
+        db.clear_failures(uname).await?;
+        assert_eq!(db.get_lockout(uname).await?, (0, None));
+        assert_eq!(db.check_password(uname, pwd, salt).await?, AuthResult::Ok);
+
+        db.nuke_database().await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,319 @@
+/*!
+Exporting and restoring the auth DB, for rehearsing disaster recovery.
+
+The auth DB only holds password hashes; the salts that go with them are in
+the data DB's users table. So an export (see [`export`]) bundles each
+user's hash with their salt, and before anything is restored, the salts in
+the export are checked against the data DB (see [`verify`]): a hash
+restored alongside a salt that has since changed could never match a
+password again.
+
+Exports are encrypted (with AES-256-GCM) under a key derived from the
+`auth_export_key` option in the config file, which should be a long random
+string kept somewhere other than with the exports. Without it set, nothing
+can be exported or restored. An export looks like
+```text
+CAMP-AUTH-EXPORT 1
+[ base64-encoded nonce and ciphertext ]
+```
+Session keys aren't exported; they'd all have expired by the time an
+export was restored anyway.
+*/
+use std::collections::{HashMap, HashSet};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use time::Date;
+
+use crate::{config::Glob, user::User};
+
+/// First line of every export; also authenticated along with the contents.
+const HEADER: &str = "CAMP-AUTH-EXPORT 1";
+/// Context string for deriving the encryption key; see
+/// [`blake3::derive_key`].
+const KEY_CONTEXT: &str = "camp 2023-01-01 auth DB export key";
+/// Shortest `auth_export_key` allowed.
+pub const MIN_KEY_LENGTH: usize = 16;
+
+/// One user's auth DB record, with their salt from the data DB.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ExportRecord {
+    pub uname: String,
+    pub hash: String,
+    /// `None` if the user isn't in the data DB.
+    pub salt: Option<String>,
+}
+
+/// The (decrypted) contents of an export.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct AuthExport {
+    pub created: Date,
+    pub records: Vec<ExportRecord>,
+}
+
+fn cipher_key(key: &str) -> LessSafeKey {
+    let bytes = blake3::derive_key(KEY_CONTEXT, key.as_bytes());
+    // An AES-256 key is 32 bytes, which is what `derive_key()` produces.
+    LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &bytes).unwrap())
+}
+
+impl AuthExport {
+    /// Encrypt this under `key`.
+    pub fn seal(&self, key: &str) -> Result<String, String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| "Unable to generate nonce.".to_owned())?;
+        let mut data = serde_json::to_vec(self)
+            .map_err(|e| format!("Error serializing auth DB export: {}", &e))?;
+        cipher_key(key)
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(HEADER),
+                &mut data,
+            )
+            .map_err(|_| "Error encrypting auth DB export.".to_owned())?;
+
+        let mut bytes = nonce.to_vec();
+        bytes.append(&mut data);
+        Ok(format!("{}\n{}\n", HEADER, STANDARD.encode(&bytes)))
+    }
+
+    /// Decrypt an export that was encrypted under `key`.
+    pub fn open(text: &str, key: &str) -> Result<AuthExport, String> {
+        let body = match text.trim().split_once('\n') {
+            Some((header, body)) if header.trim() == HEADER => body,
+            _ => {
+                return Err("This isn't an auth DB export.".to_owned());
+            }
+        };
+        let body: String = body.split_whitespace().collect();
+        let mut bytes = STANDARD
+            .decode(body)
+            .map_err(|e| format!("Error decoding auth DB export: {}", &e))?;
+        if bytes.len() < NONCE_LEN {
+            return Err("Auth DB export is truncated.".to_owned());
+        }
+
+        let mut data = bytes.split_off(NONCE_LEN);
+        // `bytes` is now exactly `NONCE_LEN` long.
+        let nonce = Nonce::try_assume_unique_for_key(&bytes).unwrap();
+        let plain = cipher_key(key)
+            .open_in_place(nonce, Aad::from(HEADER), &mut data)
+            .map_err(|_| {
+                "Unable to decrypt auth DB export; either it was made with a \
+                different auth_export_key, or it has been altered."
+                    .to_owned()
+            })?;
+        serde_json::from_slice(plain).map_err(|e| format!("Error reading auth DB export: {}", &e))
+    }
+}
+
+/// How an export compares with the users in the data DB.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Verification {
+    /// When the export was made.
+    pub created: Option<Date>,
+    /// Number of records in the export.
+    pub n_records: usize,
+    /// Number of records whose salts match the data DB's.
+    pub n_matched: usize,
+    /// Users whose salts in the export differ from the data DB's.
+    pub salt_mismatches: Vec<String>,
+    /// Records of users who aren't in the data DB.
+    pub not_in_store: Vec<String>,
+    /// Users in the data DB without records in the export.
+    pub not_in_export: Vec<String>,
+}
+
+impl Verification {
+    /// Whether the export is safe to restore.
+    pub fn ok(&self) -> bool {
+        self.salt_mismatches.is_empty()
+    }
+}
+
+/// Check the salts in the export `exp` against those of the data DB's
+/// `users`.
+pub fn verify(exp: &AuthExport, users: &HashMap<String, User>) -> Verification {
+    let mut v = Verification {
+        created: Some(exp.created),
+        n_records: exp.records.len(),
+        ..Default::default()
+    };
+    let mut seen: HashSet<&str> = HashSet::with_capacity(exp.records.len());
+    for rec in exp.records.iter() {
+        seen.insert(&rec.uname);
+        match users.get(&rec.uname) {
+            None => v.not_in_store.push(rec.uname.clone()),
+            Some(u) if Some(u.salt()) == rec.salt.as_deref() => v.n_matched += 1,
+            Some(_) => v.salt_mismatches.push(rec.uname.clone()),
+        }
+    }
+    v.not_in_export = users
+        .keys()
+        .filter(|uname| !seen.contains(uname.as_str()))
+        .cloned()
+        .collect();
+
+    v.salt_mismatches.sort();
+    v.not_in_store.sort();
+    v.not_in_export.sort();
+    v
+}
+
+/// Read the encrypted export `text` and check it against the data DB.
+pub fn read(text: &str, glob: &Glob) -> Result<(AuthExport, Verification), String> {
+    let key = glob
+        .auth_export_key
+        .as_deref()
+        .ok_or_else(|| "No auth_export_key is configured.".to_owned())?;
+    let exp = AuthExport::open(text, key)?;
+    let v = verify(&exp, &glob.users);
+    Ok((exp, v))
+}
+
+/// Export the auth DB, encrypted.
+pub async fn export(glob: &Glob) -> Result<String, String> {
+    let key = glob
+        .auth_export_key
+        .as_deref()
+        .ok_or_else(|| "No auth_export_key is configured.".to_owned())?;
+    let hashes = glob
+        .auth()
+        .read()
+        .await
+        .get_hashes()
+        .await
+        .map_err(|e| format!("Error reading auth DB: {}", &e))?;
+
+    let records = hashes
+        .into_iter()
+        .map(|(uname, hash)| ExportRecord {
+            salt: glob.users.get(&uname).map(|u| u.salt().to_owned()),
+            uname,
+            hash,
+        })
+        .collect();
+    let exp = AuthExport {
+        created: crate::now(),
+        records,
+    };
+    exp.seal(key)
+}
+
+/**
+Restore the users in the encrypted export `text` to the auth DB, if every
+salt in it matches the data DB's. Records of users who aren't in the data
+DB are left out.
+
+Returns the verification and the number of users restored.
+*/
+pub async fn restore(text: &str, glob: &Glob) -> Result<(Verification, u64), String> {
+    let (exp, v) = read(text, glob)?;
+    if !v.ok() {
+        return Err(format!(
+            "The salts of these users don't match the data DB, so their \
+            passwords wouldn't work if restored: {}",
+            v.salt_mismatches.join(", ")
+        ));
+    }
+
+    let hashes: Vec<(&str, &str)> = exp
+        .records
+        .iter()
+        .filter(|rec| glob.users.contains_key(&rec.uname))
+        .map(|rec| (rec.uname.as_str(), rec.hash.as_str()))
+        .collect();
+    let n = glob
+        .auth()
+        .read()
+        .await
+        .restore_hashes(&hashes)
+        .await
+        .map_err(|e| format!("Error restoring auth DB: {}", &e))?;
+    Ok((v, n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::BaseUser;
+    use time::macros::date;
+
+    fn sample() -> AuthExport {
+        AuthExport {
+            created: date!(2023 - 01 - 20),
+            records: vec![
+                ExportRecord {
+                    uname: "admin".to_owned(),
+                    hash: "abc123".to_owned(),
+                    salt: Some("asdf".to_owned()),
+                },
+                ExportRecord {
+                    uname: "gone".to_owned(),
+                    hash: "def456".to_owned(),
+                    salt: None,
+                },
+                ExportRecord {
+                    uname: "boss".to_owned(),
+                    hash: "789abc".to_owned(),
+                    salt: Some("hjkl".to_owned()),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn sealed_exports() {
+        let key = "correct horse battery staple";
+        let exp = sample();
+        let text = exp.seal(key).unwrap();
+        assert!(text.starts_with(HEADER));
+        assert!(!text.contains("abc123"));
+        assert_eq!(AuthExport::open(&text, key).unwrap(), exp);
+
+        assert!(AuthExport::open(&text, "incorrect horse battery staple").is_err());
+        let altered = text.replacen('A', "B", 1).replacen('a', "b", 1);
+        assert!(AuthExport::open(&altered, key).is_err());
+        assert!(AuthExport::open("uname,hash\nadmin,abc123", key).is_err());
+    }
+
+    #[test]
+    fn verified_salts() {
+        let user = |uname: &str, salt: &str| {
+            let base = BaseUser {
+                uname: uname.to_owned(),
+                role: crate::user::Role::Admin,
+                salt: salt.to_owned(),
+                email: String::new(),
+            };
+            (uname.to_owned(), base.into_admin())
+        };
+        let users: HashMap<String, User> = [
+            user("admin", "asdf"),
+            user("boss", "qwer"),
+            user("new", "zxcv"),
+        ]
+        .into_iter()
+        .collect();
+
+        let v = verify(&sample(), &users);
+        assert_eq!(
+            v,
+            Verification {
+                created: Some(date!(2023 - 01 - 20)),
+                n_records: 3,
+                n_matched: 1,
+                salt_mismatches: vec!["boss".to_owned()],
+                not_in_store: vec!["gone".to_owned()],
+                not_in_export: vec!["new".to_owned()],
+            }
+        );
+        assert!(!v.ok());
+    }
+}
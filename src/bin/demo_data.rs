@@ -5,7 +5,7 @@ to allow some experimentation.
 Fake production data can be found in `crate_root/fakeprod_data`.
 */
 use std::{fs::File, io::Read, path::Path};
-use std::io::{self, ErrorKind};
+use std::io;
 
 use futures::stream::TryStreamExt;
 use hyper::{Body, Client, Request};
@@ -34,6 +34,8 @@ static GOALS_CSV: &str   = "demo/goals.csv";
 static TEMP_TEACHER_UNAME: &str = "no";
 static TEMP_TEACHER_NAME:  &str = "Nobody";
 static TEMP_TEACHER_PWD:   &str = "nothing";
+/// Who the audit log says did what this does.
+static DEMO_ACTOR: &str = "demo_data";
 
 /**
 CSV file format:
@@ -176,7 +178,7 @@ async fn read_key(uri: &str, uname: &str, pwd: &str) -> Result<String, String>
     }
     let reader = StreamReader::new(
         resp.into_body()
-        .map_err(|e| io::Error::new(ErrorKind::Other, e))
+        .map_err(io::Error::other)
     );
     let reader = BufReader::new(reader);
 
@@ -206,15 +208,15 @@ async fn load_dates_and_write_calendar<P: AsRef<Path>, Q: AsRef<Path>>(
 ) {
     // Read and deserialize calendar file to dates.
     let p = cal_path.as_ref();
-    let file_bytes = std::fs::read(p).expect(
-        &format!("Unable to read calendar file: {:?}", p.display())
+    let file_bytes = std::fs::read(p).unwrap_or_else(
+        |e| panic!("Unable to read calendar file: {:?}: {}", p.display(), &e)
     );
-    let date_strs: Vec<&str> = serde_json::from_slice(&file_bytes).expect(&format!(
-        "Unable to deserialize contents of {:?} as JSON.", p.display()
+    let date_strs: Vec<&str> = serde_json::from_slice(&file_bytes).unwrap_or_else(|e| panic!(
+        "Unable to deserialize contents of {:?} as JSON: {}", p.display(), &e
     ));
     let dates: Vec<Date> = date_strs.into_iter()
-        .map(|s| Date::parse(s, DATE_FMT).expect(&format!(
-            "Unable to parse {:?} (from file {:?}) as Date.", s, p.display()
+        .map(|s| Date::parse(s, DATE_FMT).unwrap_or_else(|e| panic!(
+            "Unable to parse {:?} (from file {:?}) as Date: {}", s, p.display(), &e
         )))
         .collect();
     
@@ -225,22 +227,23 @@ async fn load_dates_and_write_calendar<P: AsRef<Path>, Q: AsRef<Path>>(
 
     // Read and deserialize special dates file.
     let p = dates_path.as_ref();
-    let file_bytes = std::fs::read(p).expect(
-        &format!("Unable to read dates file: {:?}", p.display())
+    let file_bytes = std::fs::read(p).unwrap_or_else(
+        |e| panic!("Unable to read dates file: {:?}: {}", p.display(), &e)
     );
-    let date_strs: Vec<Vec<&str>> = serde_json::from_slice(&file_bytes).expect(&format!(
-        "Unable to deserialize contents of {:?} as JSON.", p.display()
-    ));
+    let date_strs: Vec<Vec<&str>> = serde_json::from_slice(&file_bytes)
+        .unwrap_or_else(|e| panic!(
+            "Unable to deserialize contents of {:?} as JSON: {}", p.display(), &e
+        ));
 
     let data = glob.data();
     let store = data.read().await;
     for kvp in date_strs.into_iter() {
         let name = kvp[0];
-        let day = Date::parse(kvp[1], DATE_FMT).expect(&format!(
-            "Unable to parse {:?} date {:?} as Date.", name, kvp[1]
+        let day = Date::parse(kvp[1], DATE_FMT).unwrap_or_else(|e| panic!(
+            "Unable to parse {:?} date {:?} as Date: {}", name, kvp[1], &e
         ));
-        store.set_date(name, &day).await.expect(&format!(
-            "Error inserting {:?} date {} into database.", name, &day
+        store.set_date(name, &day).await.unwrap_or_else(|e| panic!(
+            "Error inserting {:?} date {} into database: {}", name, &day, &e
         ));
     }
 }
@@ -289,12 +292,12 @@ async fn autopace_students(
             email: "nobody@nowhere.not".into(),
         };
         let u = u.into_teacher(TEMP_TEACHER_NAME.into());
-        glob.insert_user(&u).await.unwrap();
+        glob.insert_user(DEMO_ACTOR, &u).await.unwrap();
         glob.refresh_users().await.unwrap();
         glob.update_password(TEMP_TEACHER_UNAME, TEMP_TEACHER_PWD).await.unwrap();
     }
 
-    force_reload(&uri, &admin_uname, &admin_key).await;
+    force_reload(uri, admin_uname, admin_key).await;
     let key = read_key(uri, TEMP_TEACHER_UNAME, TEMP_TEACHER_PWD).await.unwrap();
 
     println!("Pacing student calendars...");
@@ -327,7 +330,7 @@ async fn autopace_students(
         }
     }
     println!("Deleting temporary teacher...");
-    glob.delete_user(TEMP_TEACHER_UNAME).await.unwrap();
+    glob.delete_user(DEMO_ACTOR, TEMP_TEACHER_UNAME).await.unwrap();
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -348,16 +351,16 @@ async fn main() -> Result<(), UnifiedError> {
     let mut glob = config::load_configuration(CONFIG).await?;
     let (uri, uname, pwd) = {
         let cf_bytes = std::fs::read(CONFIG)
-            .expect(&format!("Error reading from {:?}", CONFIG));
+            .unwrap_or_else(|e| panic!("Error reading from {:?}: {}", CONFIG, &e));
         let cf: ConfigFile = toml::from_slice(&cf_bytes)
-            .expect(&format!("Unable to deserialize contents of {:?}", CONFIG));
-        let admin = cf.admin_uname.expect(&format!(
+            .unwrap_or_else(|e| panic!("Unable to deserialize contents of {:?}: {}", CONFIG, &e));
+        let admin = cf.admin_uname.unwrap_or_else(|| panic!(
             "Must have admin_uname= option set in {:?}", CONFIG
         ));
-        let pwd = cf.admin_password.expect(&format!(
+        let pwd = cf.admin_password.unwrap_or_else(|| panic!(
             "Must have admin_password= option set in {:?}", CONFIG
         ));
-        let uri = cf.uri.expect(&format!(
+        let uri = cf.uri.unwrap_or_else(|| panic!(
             "Must have uri= option set in {:?}", CONFIG
         ));
         (uri, admin, pwd)
@@ -375,7 +378,7 @@ async fn main() -> Result<(), UnifiedError> {
 
     let (users, pwds) = csv_file_to_staff(File::open(STAFF_CSV).unwrap())?;
     for u in users.iter() {
-        glob.insert_user(u).await?;
+        glob.insert_user(DEMO_ACTOR, u).await?;
     }
     glob.refresh_courses().await?;
     glob.refresh_users().await?;
@@ -0,0 +1,395 @@
+/*!
+Hammering a running server with the kinds of requests it gets most, to find
+out how much it can take before the school year starts.
+
+This expects the server to be running with the demo data loaded (see the
+`demo_data` binary), because it uses the same configuration and staff
+files to know where to connect and whom to log in as. It needs the
+`loadtest` feature:
+
+```text
+cargo run --release --features loadtest --bin loadtest -- [ CONCURRENCY ] [ ROUNDS ]
+```
+
+Each of `CONCURRENCY` (default 8) workers does `ROUNDS` (default 25)
+rounds of:
+
+  * logging in as a Teacher,
+  * requesting all that Teacher's students' goals (`populate-goals`),
+  * adding a goal to one of their students (`add-goal`), then deleting it
+    again so repeated runs don't pile up goals,
+  * logging in as the Boss.
+
+When all workers are done, it reports the latency percentiles of each kind
+of request.
+*/
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use hyper::{client::HttpConnector, Body, Client, Request, StatusCode};
+use serde_json::{json, Value};
+use simplelog::{ColorChoice, TermLogger, TerminalMode};
+
+use camp::config::ConfigFile;
+
+static CONFIG: &str = "demo/config.toml";
+static STAFF_CSV: &str = "demo/staff.csv";
+static STUDENT_CSV: &str = "demo/students.csv";
+
+/// Chapter added (and then deleted) as a goal in each round. It's in one
+/// of the demo courses.
+static GOAL_SYM: &str = "pha2";
+const GOAL_SEQ: i16 = 1;
+
+const DEFAULT_CONCURRENCY: usize = 8;
+const DEFAULT_ROUNDS: usize = 25;
+
+/// Percentiles of request latency to report.
+const PERCENTILES: &[f64] = &[50.0, 90.0, 99.0];
+
+/// Whom to log in as.
+struct Staff {
+    boss: (String, String),
+    /// Each Teacher's uname, password, and the unames of their students.
+    teachers: Vec<(String, String, Vec<String>)>,
+}
+
+/**
+Read Boss and Teacher credentials from the demo staff file (the same one
+read by the `demo_data` binary), and match the Teachers up with their
+students from the demo students file. Teachers without students are left
+out.
+*/
+fn read_staff() -> Result<Staff, String> {
+    let mut students: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .has_headers(false)
+        .from_reader(
+            File::open(STUDENT_CSV)
+                .map_err(|e| format!("Unable to open {:?}: {}", STUDENT_CSV, &e))?,
+        );
+    for res in csv_reader.records() {
+        let rec = res.map_err(|e| format!("Error reading {:?}: {}", STUDENT_CSV, &e))?;
+        if let (Some(uname), Some(tuname)) = (rec.get(0), rec.get(5)) {
+            if !uname.is_empty() {
+                students
+                    .entry(tuname.to_owned())
+                    .or_default()
+                    .push(uname.to_owned());
+            }
+        }
+    }
+
+    let mut boss: Option<(String, String)> = None;
+    let mut teachers: Vec<(String, String, Vec<String>)> = Vec::new();
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .has_headers(true)
+        .from_reader(
+            File::open(STAFF_CSV).map_err(|e| format!("Unable to open {:?}: {}", STAFF_CSV, &e))?,
+        );
+    for res in csv_reader.records() {
+        let rec = res.map_err(|e| format!("Error reading {:?}: {}", STAFF_CSV, &e))?;
+        let (role, uname, pwd) = match (rec.get(0), rec.get(1), rec.get(3)) {
+            (Some(role), Some(uname), Some(pwd)) => (role, uname.to_owned(), pwd.to_owned()),
+            _ => continue,
+        };
+        match role {
+            "b" | "B" => {
+                boss = Some((uname, pwd));
+            }
+            "t" | "T" => {
+                if let Some(studs) = students.remove(&uname) {
+                    teachers.push((uname, pwd, studs));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let boss = boss.ok_or_else(|| format!("No Boss in {:?}.", STAFF_CSV))?;
+    if teachers.is_empty() {
+        return Err(format!("No Teachers with students in {:?}.", STAFF_CSV));
+    }
+
+    Ok(Staff { boss, teachers })
+}
+
+/// Latencies of successful requests of one kind, and a count of the
+/// unsuccessful ones.
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+impl Samples {
+    fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        self.errors += other.errors;
+    }
+
+    /// Nearest-rank percentile; `latencies` must be sorted.
+    fn percentile(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = ((p / 100.0) * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.clamp(1, self.latencies.len()) - 1]
+    }
+}
+
+/// Everything one worker (or all of them together) measured, by request
+/// kind.
+#[derive(Default)]
+struct Report(BTreeMap<&'static str, Samples>);
+
+impl Report {
+    fn record(&mut self, kind: &'static str, res: &Result<(Duration, String), String>) {
+        let samples = self.0.entry(kind).or_default();
+        match res {
+            Ok((t, _)) => samples.latencies.push(*t),
+            Err(e) => {
+                log::warn!("{} request failed: {}", kind, e);
+                samples.errors += 1;
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Report) {
+        for (kind, samples) in other.0.into_iter() {
+            self.0.entry(kind).or_default().merge(samples);
+        }
+    }
+
+    fn print(mut self, elapsed: Duration) {
+        let mut n_total: usize = 0;
+        print!("{:<16} {:>7} {:>7}", "request", "ok", "errors");
+        for p in PERCENTILES.iter() {
+            print!(" {:>9}", format!("p{}", p));
+        }
+        println!(" {:>9}", "max");
+
+        for (kind, samples) in self.0.iter_mut() {
+            samples.latencies.sort_unstable();
+            n_total += samples.latencies.len() + samples.errors;
+            print!(
+                "{:<16} {:>7} {:>7}",
+                kind,
+                samples.latencies.len(),
+                samples.errors
+            );
+            for p in PERCENTILES.iter() {
+                print!(" {:>7.1}ms", millis(samples.percentile(*p)));
+            }
+            println!(" {:>7.1}ms", millis(samples.percentile(100.0)));
+        }
+
+        println!(
+            "{} requests in {:.1}s ({:.1} requests/s)",
+            &n_total,
+            elapsed.as_secs_f64(),
+            n_total as f64 / elapsed.as_secs_f64()
+        );
+    }
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// Send a request, returning how long it took to get the full response, and
+/// the response body. Unsuccessful responses are errors.
+async fn timed(
+    client: &Client<HttpConnector>,
+    req: Request<Body>,
+) -> Result<(Duration, String), String> {
+    let start = Instant::now();
+    let resp = client
+        .request(req)
+        .await
+        .map_err(|e| format!("Error sending request: {}", &e))?;
+    let status = resp.status();
+    let body = hyper::body::to_bytes(resp.into_body())
+        .await
+        .map_err(|e| format!("Error reading response body: {}", &e))?;
+    let elapsed = start.elapsed();
+
+    let body = String::from_utf8_lossy(&body).into_owned();
+    if status == StatusCode::OK {
+        Ok((elapsed, body))
+    } else {
+        Err(format!("{}: {}", &status, &body))
+    }
+}
+
+fn login_request(uri: &str, uname: &str, pwd: &str) -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri(format!("{}/login", uri))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from(format!("uname={}&password={}", uname, pwd)))
+        .unwrap()
+}
+
+/// Pick the auth key out of the page served upon login.
+fn find_key(page: &str) -> Option<String> {
+    page.lines()
+        .filter_map(|line| line.trim().strip_prefix("key: \""))
+        .filter_map(|chunk| chunk.strip_suffix('"'))
+        .map(String::from)
+        .next()
+}
+
+fn teacher_request(uri: &str, uname: &str, key: &str, action: &str, body: String) -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri(format!("{}/teacher", uri))
+        .header("x-camp-uname", uname)
+        .header("x-camp-key", key)
+        .header("x-camp-action", action)
+        .header("x-camp-request-id", 0)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// Return the largest goal id in an `update-pace` response, which should be
+/// that of the goal just added.
+fn newest_goal_id(body: &str) -> Option<i64> {
+    let pace: Value = serde_json::from_str(body).ok()?;
+    pace.get("goals")?
+        .as_array()?
+        .iter()
+        .filter_map(|g| g.get("id").and_then(Value::as_i64))
+        .max()
+}
+
+async fn worker(
+    n: usize,
+    rounds: usize,
+    client: Client<HttpConnector>,
+    uri: Arc<str>,
+    staff: Arc<Staff>,
+) -> Report {
+    let uri: &str = &uri;
+    let mut report = Report::default();
+
+    for round in 0..rounds {
+        let (tuname, tpwd, studs) = &staff.teachers[(n + round) % staff.teachers.len()];
+        let suname = &studs[round % studs.len()];
+
+        let res = timed(&client, login_request(uri, tuname, tpwd)).await;
+        report.record("login", &res);
+        let key = match res.ok().and_then(|(_, page)| find_key(&page)) {
+            Some(key) => key,
+            None => continue,
+        };
+
+        let req = teacher_request(uri, tuname, &key, "populate-goals", String::new());
+        report.record("populate-goals", &timed(&client, req).await);
+
+        let goal = json!({
+            "id": 0,
+            "uname": suname,
+            "sym": GOAL_SYM,
+            "seq": GOAL_SEQ,
+            "rev": false,
+            "inc": false,
+            "due": null,
+            "done": null,
+            "tries": null,
+            "weight": 0.0,
+            "score": null,
+        });
+        let req = teacher_request(uri, tuname, &key, "add-goal", goal.to_string());
+        let res = timed(&client, req).await;
+        report.record("add-goal", &res);
+        if let Some(id) = res.ok().and_then(|(_, body)| newest_goal_id(&body)) {
+            let req = teacher_request(uri, tuname, &key, "delete-goal", id.to_string());
+            report.record("delete-goal", &timed(&client, req).await);
+        }
+
+        let (buname, bpwd) = &staff.boss;
+        let res = timed(&client, login_request(uri, buname, bpwd)).await;
+        report.record("boss login", &res);
+    }
+
+    report
+}
+
+fn arg_or(n: usize, default: usize) -> usize {
+    match std::env::args().nth(n) {
+        Some(s) => s
+            .parse()
+            .unwrap_or_else(|_| panic!("Argument {} ({:?}) must be a number.", &n, &s)),
+        None => default,
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let log_cfg = simplelog::ConfigBuilder::new()
+        .add_filter_allow_str("loadtest")
+        .build();
+    TermLogger::init(
+        camp::log_level_from_env(),
+        log_cfg,
+        TerminalMode::Stdout,
+        ColorChoice::Auto,
+    )
+    .unwrap();
+
+    let concurrency = arg_or(1, DEFAULT_CONCURRENCY);
+    let rounds = arg_or(2, DEFAULT_ROUNDS);
+
+    let uri: Arc<str> = {
+        let cf_bytes = std::fs::read(CONFIG)
+            .unwrap_or_else(|e| panic!("Error reading from {:?}: {}", CONFIG, &e));
+        let cf: ConfigFile = toml::from_slice(&cf_bytes)
+            .unwrap_or_else(|e| panic!("Unable to deserialize contents of {:?}: {}", CONFIG, &e));
+        cf.uri
+            .unwrap_or_else(|| panic!("Must have uri= option set in {:?}", CONFIG))
+            .trim_end_matches('/')
+            .into()
+    };
+    let staff = Arc::new(read_staff()?);
+
+    println!(
+        "{} workers doing {} rounds each against {}",
+        &concurrency, &rounds, &uri
+    );
+
+    let client = Client::new();
+    let start = Instant::now();
+    let workers: Vec<_> = (0..concurrency)
+        .map(|n| {
+            tokio::spawn(worker(
+                n,
+                rounds,
+                client.clone(),
+                uri.clone(),
+                staff.clone(),
+            ))
+        })
+        .collect();
+
+    let mut total = Report::default();
+    for handle in workers.into_iter() {
+        let report = handle
+            .await
+            .map_err(|e| format!("Load test worker failed: {}", &e))?;
+        total.merge(report);
+    }
+    let elapsed = start.elapsed();
+    total.print(elapsed);
+
+    Ok(())
+}
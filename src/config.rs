@@ -6,7 +6,7 @@ the [`auth`] and [`store`](crate::store) databases, and therefore is in the best
 to moderate interactions with both kinds of data.
 */
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::Write,
     io::Cursor,
     net::SocketAddr,
@@ -16,23 +16,44 @@ use std::{
 };
 
 use rand::{distributions, Rng};
-use serde::Deserialize;
-use time::Date;
+use serde::{Deserialize, Serialize};
+use time::{Date, Duration};
 use tokio::sync::RwLock;
-use tokio_postgres::types::{ToSql, Type};
+use tokio_postgres::types::ToSql;
 
 use crate::{
     academic_year_from_start_year,
     auth,
+    auth_export,
     auth::AuthResult,
-    course::{Chapter, Course},
-    hist::HistEntry,
+    course::{catalog_token, Chapter, Course, Custom},
+    DateStyle,
+    downloads::Downloads,
+    email,
+    email::{EmailSender, LogSender, SendgridSender, SmtpSecurity, SmtpSender},
+    hist::{HistEntry, TransferRecord},
     inter,
+    inter::confirm::Confirmations,
+    inter::rate_limit::{RateLimit, RateLimiter},
     MiniString,
-    pace::{Goal, Pace, Source, Term},
+    pace::{
+        count_reordered, distribute_due_dates, outside_absences, AcademicCalendar,
+        Absence, BookCh, CustomCh, Goal, Pace, Source, SummaryLabels, Term, Waiver, WeightDelta,
+    },
+    render,
+    render::{BuiltinRenderer, PandocRenderer, ReportRenderer},
+    roster,
+    roster::{Failure, RosterApplied, RosterDiff, RosterState, RosterSync},
     SMALLSTORE,
-    store::Store,
+    startup,
+    startup::{SharedStatus, StartupRetry},
+    store::{
+        deferred, AcademicYear, DataStore, LagSnapshot, NukePreview, PruneStats, Pruned,
+        Retention, Store,
+    },
     user::{Role, Student, User},
+    webhook,
+    webhook::{Event, StudentLagging},
     UnifiedError,
 };
 
@@ -87,6 +108,42 @@ fn bad_uname(uname: &str) -> bool {
 static BAD_UNAME_MSG: &str =
     "A uname can only contain alphanumeric ASCII characters: a-z, A-Z, or 0-9.";
 
+/// What's wrong with `uname` as a new user's uname, if anything (not
+/// counting whether someone already has it).
+fn uname_problem(uname: &str) -> Option<String> {
+    if uname.is_empty() {
+        Some("A uname can't be blank.".to_owned())
+    } else if bad_uname(uname) {
+        Some(BAD_UNAME_MSG.to_owned())
+    } else {
+        None
+    }
+}
+
+/**
+What's wrong with `email` as a user's email address, if anything.
+
+This isn't an attempt to validate addresses, just to catch the obvious
+typos (and the characters [`has_bad_chars`] doesn't like).
+*/
+fn email_problem(email: &str) -> Option<String> {
+    if has_bad_chars(email) {
+        return Some(format!("Email addresses {}", BAD_CHARS_MSG));
+    }
+    if email.chars().any(char::is_whitespace) {
+        return Some("Email addresses can't contain spaces.".to_owned());
+    }
+    let looks_okay = match email.split_once('@') {
+        Some((user, domain)) => !user.is_empty() && !domain.is_empty() && !domain.contains('@'),
+        None => false,
+    };
+    if looks_okay {
+        None
+    } else {
+        Some(format!("{:?} doesn't look like an email address.", email))
+    }
+}
+
 /**
 The purpose of this struct is to be deserialized directly from a TOML
 configuration file.
@@ -109,6 +166,16 @@ pub struct ConfigFile {
     /// Connection string for the data database [`store::Store`](crate::store::Store).
     /// See again the `tokio::postgres` documentation.
     pub data_db_connect_string: Option<String>,
+    /// Most connections to keep open to each database. Default is 0, which
+    /// opens a new connection for each interaction instead of pooling them;
+    /// see [`pool`](crate::pool).
+    pub db_pool_size: Option<usize>,
+    /// Rows per statement when inserting many students at once (like from
+    /// an uploaded CSV file). Default is 500.
+    pub db_insert_chunk: Option<usize>,
+    /// Most statements in flight at once when inserting many students.
+    /// Default is 4.
+    pub db_insert_concurrency: Option<usize>,
     /// User name of the default Admin user account who should be guaranteed
     /// to exist.
     pub admin_uname: Option<String>,
@@ -130,7 +197,23 @@ pub struct ConfigFile {
     pub sendgrid_uri: Option<String>,
     /// Value of the `Authorization` header required in a Sendgrid request in
     /// order to send email.
+    #[serde(default)]
     pub sendgrid_auth_string: String,
+    /// Which [`EmailSender`](crate::email::EmailSender) to send email with:
+    /// "sendgrid" (the default), "smtp", or "log".
+    pub email_backend: Option<String>,
+    /// Host name of the SMTP server; required if `email_backend` is "smtp".
+    pub smtp_host: Option<String>,
+    /// Port of the SMTP server. Defaults to the usual port for
+    /// `smtp_security`.
+    pub smtp_port: Option<u16>,
+    /// How to secure the connection to the SMTP server: "starttls" (the
+    /// default), "tls", or "none".
+    pub smtp_security: Option<SmtpSecurity>,
+    /// User name for the SMTP server, if it requires one.
+    pub smtp_username: Option<String>,
+    /// Password for the SMTP server, to go with `smtp_username`.
+    pub smtp_password: Option<String>,
     /// List of social/emotional/behavioral goals included in reports.
     pub social_traits: Option<Vec<String>>,
     /// Host to bind the TCP listening socket to.
@@ -141,6 +224,13 @@ pub struct ConfigFile {
     pub port: Option<u16>,
     /// Directory with [`handlebars`] templates.
     pub templates_dir: Option<String>,
+    /// Directory of static files, served under `/static`. Default is
+    /// `"static/"`.
+    pub static_dir: Option<String>,
+    /// Whether templates should link to fingerprinted (and so cacheable
+    /// forever) URLs for static files; see
+    /// [`assets`](crate::inter::assets). Default is `true`.
+    pub fingerprint_assets: Option<bool>,
     /// URI for making Pandoc requests to render report markdown into PDFs.
     pub pandoc_uri: Option<String>,
     /// Authorization token for the Pandoc API endpoint.
@@ -148,6 +238,111 @@ pub struct ConfigFile {
     /// "From" format for Pandoc requests. This is largely for experimenting
     /// with different extensions.
     pub pandoc_format: Option<String>,
+    /// Which [`ReportRenderer`](crate::render::ReportRenderer) to use to turn
+    /// reports into PDFs: either "pandoc" (the default) or "builtin".
+    pub report_renderer: Option<String>,
+    /// Number of consecutive failed login attempts before a user is
+    /// temporarily locked out. Zero disables lockout. Default is 5.
+    pub lockout_threshold: Option<u32>,
+    /// Length (in seconds) of the first lockout period. Each subsequent
+    /// failed attempt doubles it. Default is 60.
+    pub lockout_seconds: Option<u64>,
+    /// Maximum length (in seconds) of a lockout period. Default is 3600.
+    pub lockout_max_seconds: Option<u64>,
+    /// How hard to work at hashing passwords; see [`auth::PasswordHashing`]
+    /// for the keys this table may contain.
+    pub password_hashing: Option<auth::PasswordHashing>,
+    /// How hard to throttle login and password reset requests; see
+    /// [`RateLimit`] for the keys this table may contain.
+    pub rate_limit: Option<RateLimit>,
+    /// How long (in seconds) to remember that a user's key checked out,
+    /// rather than checking it against the auth DB on every request. Zero
+    /// turns this caching off. Default is 30.
+    pub key_cache_seconds: Option<u64>,
+    /// Secret from which the key that encrypts auth DB exports is derived;
+    /// see [`auth_export`](crate::auth_export). Exports can't be made or
+    /// restored without it. It should be a long random string.
+    pub auth_export_key: Option<String>,
+    /// Name of the environment this instance runs in (like "production"
+    /// or "test"). Both databases get tagged with this value the first time
+    /// they're used, and the server will refuse to start against a database
+    /// tagged with a different one. Default is "production".
+    pub environment: Option<String>,
+    /// Hour (0-23, UTC) on Monday mornings to send Teachers their weekly
+    /// summary email. If not present, no summaries are sent.
+    pub weekly_digest_hour: Option<u8>,
+    /// Who gets emailed when a student finishes all of their scheduled
+    /// goals: `"off"`, `"teacher"` (the default), or `"teacher-and-boss"`.
+    pub completion_email: Option<CompletionEmail>,
+    /// Whether students see their scores (and the test average and grade
+    /// lines) when they look at their own calendars. If `false`, they only
+    /// see which goals are done; Teachers, Bosses, and reports still see
+    /// everything. Default is `true`.
+    pub student_scores: Option<bool>,
+    /// How dates are displayed in calendars, emails, and reports: one of
+    /// "short" (the default), "iso", "us", "eu", or "long". (The locale
+    /// names "en-US" and "en-GB" are also accepted.) See
+    /// [`DateStyle`](crate::DateStyle).
+    pub date_format: Option<String>,
+    /// Whether to create auth DB entries (with random passwords) at startup
+    /// for any users found in the data DB without one. Such users will have
+    /// to reset their passwords before they can log in. Default is `false`,
+    /// in which case discrepancies are only logged.
+    pub create_missing_auth: Option<bool>,
+    /// Whether login attempts for nonexistent users should do the same
+    /// password hashing and database work as those for real users, so the
+    /// two can't be told apart by timing. Default is `true`.
+    pub dummy_auth_checks: Option<bool>,
+    /// Number of failed (4xx or 5xx) API requests to keep in the data DB for
+    /// debugging, with their (sanitized) bodies and responses. Default is 0,
+    /// which records nothing.
+    pub failure_journal: Option<usize>,
+    /// Shortest password (in characters) users may choose for themselves.
+    /// Default is 8.
+    pub min_password_length: Option<usize>,
+    /// Directory where data is archived before the yearly reset deletes
+    /// it. Default is `"archives/"`.
+    pub archive_dir: Option<String>,
+    /// Directory where generated downloads (like the Boss's report
+    /// archives) are kept for an hour, so interrupted downloads can be
+    /// resumed. It's emptied when the server starts. Default is
+    /// `"downloads/"`.
+    pub download_dir: Option<String>,
+    /// Labels for grading periods and their summary lines in pace
+    /// calendars and reports. See [`SummaryLabels`] for the keys this
+    /// table may contain; any omitted keep their defaults.
+    pub summary_labels: Option<SummaryLabels>,
+    /// Projected semester grade (in percent) below which a student shows up
+    /// in the `at-risk` report. Default is 70.
+    pub failing_threshold: Option<f32>,
+    /// How many days to keep report drafts, journaled failures, and other
+    /// data that piles up, before a daily cleanup deletes it. See
+    /// [`Retention`] for the keys this table may contain.
+    pub retention: Option<Retention>,
+    /// Who error pages tell users to contact, like `"the main office at
+    /// 555-0123"`. Default is none; the pages just suggest telling an
+    /// administrator.
+    pub support_contact: Option<String>,
+    /// Where and when to fetch the school information system's nightly
+    /// roster export; see [`RosterSync`]. Default is none, which turns
+    /// roster sync off.
+    pub roster_sync: Option<RosterSync>,
+    /// Whether to upgrade a data DB with an older schema at startup, rather
+    /// than refusing to start. Default is `false`; back the database up
+    /// before turning this on.
+    pub auto_migrate: Option<bool>,
+    /// How long to keep trying to connect to each database at startup,
+    /// serving a maintenance page meanwhile. See [`StartupRetry`] for the
+    /// keys this table may contain; by default, camp keeps trying forever.
+    pub startup_retry: Option<StartupRetry>,
+    /// Which Tokio runtime to serve on: `"multi-thread"` or
+    /// `"current-thread"`. Default is `"multi-thread"` in release builds and
+    /// `"current-thread"` in debug builds. (Read by [`RuntimeCfg`], before
+    /// the rest of this file.)
+    pub runtime: Option<RuntimeFlavor>,
+    /// Number of worker threads for the multi-threaded runtime. Default is
+    /// the number of CPU cores. Ignored by the current-thread runtime.
+    pub worker_threads: Option<usize>,
 }
 
 /**
@@ -160,17 +355,54 @@ pub struct Cfg {
     pub uri: String,
     pub auth_db_connect_string: String,
     pub data_db_connect_string: String,
+    pub db_pool_size: usize,
+    pub db_insert_chunk: usize,
+    pub db_insert_concurrency: usize,
     pub default_admin_uname: String,
     pub default_admin_password: String,
     pub default_admin_email: String,
     pub sendgrid_uri: hyper::Uri,
     pub sendgrid_auth_string: String,
+    pub email_backend: String,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_security: SmtpSecurity,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
     pub social_traits: Vec<String>,
     pub addr: SocketAddr,
     pub templates_dir: PathBuf,
+    pub static_dir: PathBuf,
+    pub fingerprint_assets: bool,
     pub pandoc_uri: hyper::Uri,
     pub pandoc_auth: String,
     pub pandoc_format: Option<String>,
+    pub report_renderer: String,
+    pub lockout_threshold: u32,
+    pub lockout_seconds: u64,
+    pub lockout_max_seconds: u64,
+    pub password_hashing: auth::PasswordHashing,
+    pub rate_limit: RateLimit,
+    pub key_cache_seconds: u64,
+    pub auth_export_key: Option<String>,
+    pub environment: String,
+    pub weekly_digest_hour: Option<u8>,
+    pub completion_email: CompletionEmail,
+    pub student_scores: bool,
+    pub date_style: DateStyle,
+    pub create_missing_auth: bool,
+    pub dummy_auth_checks: bool,
+    pub failure_journal: usize,
+    pub min_password_length: usize,
+    pub archive_dir: PathBuf,
+    pub download_dir: PathBuf,
+    pub summary_labels: SummaryLabels,
+    pub failing_threshold: f32,
+    pub retention: Retention,
+    pub support_contact: Option<String>,
+    pub roster_sync: Option<RosterSync>,
+    pub auto_migrate: bool,
+    pub startup_retry: StartupRetry,
 }
 
 impl std::default::Default for Cfg {
@@ -183,11 +415,21 @@ impl std::default::Default for Cfg {
             data_db_connect_string:
                 "host=localhost user=camp_test password='camp_test' dbname=camp_store_test"
                     .to_owned(),
+            db_pool_size: 0,
+            // 0 leaves the `Store`'s defaults alone.
+            db_insert_chunk: 0,
+            db_insert_concurrency: 0,
             default_admin_uname: "root".to_owned(),
             default_admin_password: "toot".to_owned(),
             default_admin_email: "admin@camp.not.an.address".to_owned(),
             sendgrid_uri: "https://api.sendgrid.com/v3/mail/send".parse().unwrap(),
             sendgrid_auth_string: "".to_owned(),
+            email_backend: email::SENDGRID.to_owned(),
+            smtp_host: None,
+            smtp_port: None,
+            smtp_security: SmtpSecurity::default(),
+            smtp_username: None,
+            smtp_password: None,
             social_traits: vec![
                 "Class Participation".to_owned(),
                 "Leadership".to_owned(),
@@ -200,9 +442,37 @@ impl std::default::Default for Cfg {
             ],
             addr: SocketAddr::new("0.0.0.0".parse().unwrap(), 8001),
             templates_dir: PathBuf::from("templates/"),
+            static_dir: PathBuf::from("static/"),
+            fingerprint_assets: true,
             pandoc_uri: "http://localhost:8002/".parse().unwrap(),
             pandoc_auth: "".to_owned(),
             pandoc_format: None,
+            report_renderer: render::PANDOC.to_owned(),
+            lockout_threshold: 5,
+            lockout_seconds: 60,
+            lockout_max_seconds: 60 * 60,
+            password_hashing: auth::PasswordHashing::default(),
+            rate_limit: RateLimit::default(),
+            key_cache_seconds: 30,
+            auth_export_key: None,
+            environment: crate::DEFAULT_ENVIRONMENT.to_owned(),
+            weekly_digest_hour: None,
+            completion_email: CompletionEmail::default(),
+            student_scores: true,
+            date_style: DateStyle::default(),
+            create_missing_auth: false,
+            dummy_auth_checks: true,
+            failure_journal: 0,
+            min_password_length: 8,
+            archive_dir: PathBuf::from("archives/"),
+            download_dir: PathBuf::from("downloads/"),
+            summary_labels: SummaryLabels::default(),
+            failing_threshold: 70.0,
+            retention: Retention::default(),
+            support_contact: None,
+            roster_sync: None,
+            auto_migrate: false,
+            startup_retry: StartupRetry::default(),
         }
     }
 }
@@ -228,6 +498,15 @@ impl Cfg {
         if let Some(s) = cf.data_db_connect_string {
             c.data_db_connect_string = s;
         }
+        if let Some(n) = cf.db_pool_size {
+            c.db_pool_size = n;
+        }
+        if let Some(n) = cf.db_insert_chunk {
+            c.db_insert_chunk = n;
+        }
+        if let Some(n) = cf.db_insert_concurrency {
+            c.db_insert_concurrency = n;
+        }
         if let Some(s) = cf.admin_uname {
             c.default_admin_uname = s;
         }
@@ -241,6 +520,26 @@ impl Cfg {
             c.sendgrid_uri = hyper::Uri::try_from(s.as_str())
                 .map_err(|e| format!("Error parsing {:?} as Sendgrid URI: {}", &s, &e))?;
         }
+        if let Some(s) = cf.email_backend {
+            match s.as_str() {
+                email::SENDGRID | email::SMTP | email::LOG => {
+                    c.email_backend = s;
+                }
+                _ => {
+                    return Err(format!("Unrecognized email_backend: {:?}", &s));
+                }
+            }
+        }
+        c.smtp_host = cf.smtp_host;
+        c.smtp_port = cf.smtp_port;
+        if let Some(x) = cf.smtp_security {
+            c.smtp_security = x;
+        }
+        c.smtp_username = cf.smtp_username;
+        c.smtp_password = cf.smtp_password;
+        if c.email_backend == email::SMTP && c.smtp_host.is_none() {
+            return Err("email_backend \"smtp\" requires smtp_host.".to_owned());
+        }
         if let Some(v) = cf.social_traits {
             c.social_traits = v;
         }
@@ -273,6 +572,12 @@ impl Cfg {
         if let Some(s) = cf.templates_dir {
             c.templates_dir = PathBuf::from(&s);
         }
+        if let Some(s) = cf.static_dir {
+            c.static_dir = PathBuf::from(&s);
+        }
+        if let Some(b) = cf.fingerprint_assets {
+            c.fingerprint_assets = b;
+        }
         if let Some(s) = cf.pandoc_uri {
             c.pandoc_uri = s
                 .parse()
@@ -282,11 +587,244 @@ impl Cfg {
             c.pandoc_auth = s;
         }
         c.pandoc_format = cf.pandoc_format;
+        if let Some(s) = cf.report_renderer {
+            match s.as_str() {
+                render::PANDOC | render::BUILTIN => {
+                    c.report_renderer = s;
+                }
+                _ => {
+                    return Err(format!("Unrecognized report_renderer: {:?}", &s));
+                }
+            }
+        }
+        if let Some(n) = cf.lockout_threshold {
+            c.lockout_threshold = n;
+        }
+        if let Some(n) = cf.lockout_seconds {
+            c.lockout_seconds = n;
+        }
+        if let Some(n) = cf.lockout_max_seconds {
+            c.lockout_max_seconds = n;
+        }
+        if let Some(h) = cf.password_hashing {
+            c.password_hashing = h;
+        }
+        if let Some(r) = cf.rate_limit {
+            c.rate_limit = r;
+        }
+        if let Some(n) = cf.key_cache_seconds {
+            c.key_cache_seconds = n;
+        }
+        if let Some(s) = cf.auth_export_key {
+            if s.chars().count() < auth_export::MIN_KEY_LENGTH {
+                return Err(format!(
+                    "auth_export_key must be at least {} characters long.",
+                    auth_export::MIN_KEY_LENGTH
+                ));
+            }
+            c.auth_export_key = Some(s);
+        }
+        if let Some(s) = cf.environment {
+            c.environment = s;
+        }
+        if let Some(n) = cf.weekly_digest_hour {
+            if n > 23 {
+                return Err(format!("weekly_digest_hour must be 0-23, not {}.", &n));
+            }
+            c.weekly_digest_hour = Some(n);
+        }
+        if let Some(x) = cf.completion_email {
+            c.completion_email = x;
+        }
+        if let Some(b) = cf.student_scores {
+            c.student_scores = b;
+        }
+        if let Some(s) = cf.date_format {
+            c.date_style = DateStyle::from_str(&s)?;
+        }
+        if let Some(b) = cf.create_missing_auth {
+            c.create_missing_auth = b;
+        }
+        if let Some(b) = cf.dummy_auth_checks {
+            c.dummy_auth_checks = b;
+        }
+        if let Some(n) = cf.failure_journal {
+            c.failure_journal = n;
+        }
+        if let Some(n) = cf.min_password_length {
+            c.min_password_length = n;
+        }
+        if let Some(s) = cf.archive_dir {
+            c.archive_dir = PathBuf::from(&s);
+        }
+        if let Some(s) = cf.download_dir {
+            c.download_dir = PathBuf::from(&s);
+        }
+        if let Some(labels) = cf.summary_labels {
+            c.summary_labels = labels;
+        }
+        if let Some(x) = cf.failing_threshold {
+            c.failing_threshold = x;
+        }
+        if let Some(r) = cf.retention {
+            c.retention = r;
+        }
+        if let Some(s) = cf.support_contact {
+            c.support_contact = Some(s);
+        }
+        if let Some(r) = cf.roster_sync {
+            c.roster_sync = Some(r);
+        }
+        if let Some(b) = cf.auto_migrate {
+            c.auto_migrate = b;
+        }
+        if let Some(r) = cf.startup_retry {
+            c.startup_retry = r;
+        }
 
         Ok(c)
     }
 }
 
+/// Who gets congratulated when a student finishes all of their scheduled
+/// goals; see [`completion`](crate::inter::completion).
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompletionEmail {
+    Off,
+    #[default]
+    Teacher,
+    TeacherAndBoss,
+}
+
+/// Which flavor of Tokio runtime the server runs on.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuntimeFlavor {
+    CurrentThread,
+    MultiThread,
+}
+
+impl Default for RuntimeFlavor {
+    fn default() -> Self {
+        if cfg!(debug_assertions) {
+            RuntimeFlavor::CurrentThread
+        } else {
+            RuntimeFlavor::MultiThread
+        }
+    }
+}
+
+/**
+Runtime settings from the configuration file.
+
+These are read separately from (and before) the rest of the configuration,
+because loading the rest of the configuration requires a runtime.
+*/
+#[derive(Debug, Default, Deserialize)]
+pub struct RuntimeCfg {
+    #[serde(default)]
+    pub runtime: RuntimeFlavor,
+    pub worker_threads: Option<usize>,
+}
+
+impl RuntimeCfg {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file_contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read config file: {}", &e))?;
+        let rc: RuntimeCfg = toml::from_str(&file_contents)
+            .map_err(|e| format!("Unable to deserialize runtime config: {}", &e))?;
+
+        if rc.worker_threads == Some(0) {
+            return Err("worker_threads must be at least 1.".to_owned());
+        }
+
+        Ok(rc)
+    }
+
+    /// Build the configured runtime.
+    pub fn build(&self) -> std::io::Result<tokio::runtime::Runtime> {
+        match self.runtime {
+            RuntimeFlavor::CurrentThread => tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build(),
+            RuntimeFlavor::MultiThread => {
+                let mut builder = tokio::runtime::Builder::new_multi_thread();
+                if let Some(n) = self.worker_threads {
+                    builder.worker_threads(n);
+                }
+                builder.enable_all().build()
+            }
+        }
+    }
+}
+
+/// Discrepancies between the users in the data DB and those in the auth DB,
+/// as found by [`Glob::reconcile_auth`].
+#[derive(Debug, Default)]
+pub struct AuthDrift {
+    /// Users in the data DB with no auth DB entry (and thus no way to log in).
+    pub missing_auth: Vec<String>,
+    /// Auth DB entries with no corresponding user in the data DB.
+    pub orphaned_auth: Vec<String>,
+    /// How many of the `missing_auth` users had auth DB entries created.
+    pub n_created: usize,
+}
+
+/// Whether a uname or email address would do for a new user, as reported by
+/// [`Glob::check_uname`] and [`Glob::check_email`].
+#[derive(Debug, PartialEq, Serialize)]
+pub struct NameCheck {
+    /// The uname or address that was checked.
+    pub value: String,
+    /// What's wrong with it, regardless of who has it.
+    pub problem: Option<String>,
+    /// The unames and roles of users who already have it. (It's available
+    /// if this is empty.)
+    pub existing: Vec<(String, Role)>,
+}
+
+/// A course whose level would change, as reported by
+/// [`Glob::preview_relevel`].
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LevelChange {
+    pub sym: String,
+    pub title: String,
+    pub old: f32,
+    pub new: f32,
+}
+
+/// A student some of whose goals would be reordered by re-leveling courses.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Reordered {
+    pub uname: String,
+    #[serde(serialize_with = "crate::anon::ser::last")]
+    pub last: String,
+    #[serde(serialize_with = "crate::anon::ser::rest")]
+    pub rest: String,
+    pub teacher: String,
+    /// How many of their goals would end up in a different place.
+    pub n_moved: usize,
+}
+
+/// The effects of re-leveling courses, as reported by
+/// [`Glob::preview_relevel`].
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct RelevelPreview {
+    /// Only the courses whose levels would actually change.
+    pub changes: Vec<LevelChange>,
+    /// Groups of courses (at least one of them changed) that would share a
+    /// level, and so have their chapters interleaved in students' paces.
+    pub ties: Vec<Vec<String>>,
+    pub students: Vec<Reordered>,
+}
+
+// The `Glob` (and so everything in it) is shared between the runtime's
+// worker threads; this won't compile if anything in it stops being safe to.
+const _: fn() = assert_send_sync::<Glob>;
+fn assert_send_sync<T: Send + Sync>() {}
+
 /**
 The `Glob` contains all the global variables and state the server process
 and its handlers need to function.
@@ -304,20 +842,94 @@ reflected in its profusion of methods.
 pub struct Glob {
     auth: Arc<RwLock<auth::Db>>,
     data: Arc<RwLock<Store>>,
+    /// The same `Store` as `data` (except in tests); see [`Glob::store`].
+    store: Arc<dyn DataStore>,
     pub uri: String,
-    pub sendgrid_uri: hyper::Uri,
-    pub sendgrid_auth: String,
-    pub calendar: Vec<Date>,
+    /// How email gets sent; see [`email`].
+    pub mailer: Arc<dyn EmailSender>,
+    /// How sending email through the `mailer` has been going.
+    pub email_health: Arc<email::Health>,
+    /// Sends queued email; `None` in sandboxes and tests, which send their
+    /// email (that is, log it) right away. See [`email::Outbox`].
+    pub outbox: Option<Arc<email::Outbox>>,
+    pub calendar: AcademicCalendar,
     pub dates: HashMap<String, Date>,
+    /// Academic year metadata, earliest first; see [`Glob::current_year`].
+    pub academic_years: Vec<AcademicYear>,
     pub courses: HashMap<i64, Course>,
     pub course_syms: HashMap<String, i64>,
+    /// Teachers' custom chapters, by `id`; these get refreshed along with
+    /// `courses`.
+    pub customs: HashMap<i64, Custom>,
+    /// Identifies the current state of `courses`; see
+    /// [`catalog_token`](crate::course::catalog_token).
+    pub course_token: String,
     pub users: HashMap<String, User>,
+    /// Unames of the users with each [`Role`], sorted; rebuilt along with
+    /// `users` by [`Glob::refresh_users`].
+    role_index: HashMap<Role, Vec<String>>,
+    /// Unames of each Teacher's students, sorted; also rebuilt along with
+    /// `users`.
+    class_index: HashMap<String, Vec<String>>,
+    /// Teachers in training mode, whose Teacher API requests go to their
+    /// sandboxes; see [`Glob::sandbox`].
+    pub training: HashSet<String>,
     pub addr: SocketAddr,
     pub pwd_chars: Vec<char>,
-    pub pandoc_uri: hyper::Uri,
-    pub pandoc_auth: String,
     pub social_traits: Vec<String>,
-    pub pandoc_format: Option<String>,
+    pub renderer: Arc<dyn ReportRenderer>,
+    pub weekly_digest_hour: Option<u8>,
+    /// Who gets congratulated when a student finishes their goals.
+    pub completion_email: CompletionEmail,
+    /// Whether students get to see their own scores.
+    pub student_scores: bool,
+    pub date_style: DateStyle,
+    /// Background work waiting to be done; see [`deferred`].
+    pub deferred: Arc<deferred::Queue>,
+    /// How many failed API requests to journal; see
+    /// [`journal`](crate::inter::journal).
+    pub failure_journal: usize,
+    /// Used to sign parents' unsubscribe links; see
+    /// [`unsubscribe`](crate::inter::unsubscribe).
+    pub unsubscribe_secret: String,
+    /// Encrypts auth DB exports; see [`auth_export`].
+    pub auth_export_key: Option<String>,
+    /// Shortest password users may choose; see [`check_password_policy`].
+    pub min_password_length: usize,
+    /// Where the yearly reset archives data before deleting it.
+    pub archive_dir: PathBuf,
+    /// What grading periods and summary lines are called.
+    pub summary_labels: SummaryLabels,
+    /// Default threshold (in percent) for the
+    /// [`at_risk`](crate::inter::at_risk) report.
+    pub failing_threshold: f32,
+    /// How long to keep data that piles up; see
+    /// [`retention`](crate::inter::retention).
+    pub retention: Retention,
+    /// What the daily cleanups have deleted so far.
+    pub prune_stats: std::sync::Mutex<PruneStats>,
+    /// Where to fetch the SIS roster from, if anywhere; see
+    /// [`roster`](crate::roster).
+    pub roster_sync: Option<RosterSync>,
+    /// The most recently fetched roster, awaiting review.
+    pub roster: std::sync::Mutex<RosterState>,
+    /// Keys of sessions that asked (at login) to have names and email
+    /// addresses anonymized; see [`anon`](crate::anon).
+    anonymized: std::sync::Mutex<HashSet<String>>,
+    /// Directory of static files (served under `/static`).
+    pub static_dir: PathBuf,
+    /// Throttles login and password reset requests; see
+    /// [`rate_limit`](crate::inter::rate_limit).
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Tokens confirming destructive actions; see
+    /// [`confirm`](crate::inter::confirm).
+    pub confirmations: Arc<Confirmations>,
+    /// Sends webhook deliveries; `None` (and so no webhooks fire) in
+    /// sandboxes and tests. See [`webhook`].
+    pub webhooks: Option<Arc<webhook::Outbox>>,
+    /// Generated files waiting to be downloaded; see
+    /// [`downloads`](crate::downloads).
+    pub downloads: Arc<Downloads>,
 }
 
 impl<'a> Glob {
@@ -329,6 +941,157 @@ impl<'a> Glob {
     pub fn data(&self) -> Arc<RwLock<Store>> {
         self.data.clone()
     }
+    /// Return a handle to the data DB as a [`DataStore`], which is what
+    /// anything that can be done through one should use, so it can be tested
+    /// without a database.
+    pub fn store(&self) -> Arc<dyn DataStore> {
+        self.store.clone()
+    }
+
+    /// Set whether requests made with the auth `key` are anonymized (see
+    /// [`anon`](crate::anon)).
+    pub fn set_anonymized(&self, key: &str, on: bool) {
+        let mut keys = self.anonymized.lock().unwrap();
+        if on {
+            keys.insert(key.to_owned());
+        } else {
+            keys.remove(key);
+        }
+    }
+
+    /// Whether requests made with the auth `key` are anonymized.
+    pub fn is_anonymized(&self, key: &str) -> bool {
+        self.anonymized.lock().unwrap().contains(key)
+    }
+
+    /**
+    Build a `Glob` for the training sandbox of the Teacher `tuname` (see
+    [`Store::sandboxed`]), to handle their requests while they're in
+    training mode.
+
+    Its data DB handles use the sandbox, so its users and custom chapters
+    are read from there; everything else is copied from this `Glob`,
+    except that it only logs the emails it would send, and never syncs the
+    roster.
+    */
+    pub async fn sandbox(&self, tuname: &str) -> Result<Glob, UnifiedError> {
+        log::trace!("Glob::sandbox( {:?} ) called.", tuname);
+
+        // This also fails if the sandbox doesn't exist, which keeps a
+        // missing sandbox from falling through to the live tables.
+        let store = {
+            let data = self.data.read().await;
+            data.sync_sandbox_students(tuname).await?;
+            data.sandboxed(tuname)
+        };
+        let data = Arc::new(RwLock::new(store));
+        let mut glob = Glob {
+            auth: self.auth.clone(),
+            store: data.clone(),
+            data,
+            uri: self.uri.clone(),
+            mailer: Arc::new(LogSender),
+            email_health: Arc::new(email::Health::new()),
+            outbox: None,
+            calendar: self.calendar.clone(),
+            dates: self.dates.clone(),
+            academic_years: self.academic_years.clone(),
+            courses: self.courses.clone(),
+            course_syms: self.course_syms.clone(),
+            customs: HashMap::new(),
+            course_token: self.course_token.clone(),
+            users: HashMap::new(),
+            role_index: HashMap::new(),
+            class_index: HashMap::new(),
+            training: self.training.clone(),
+            addr: self.addr,
+            pwd_chars: self.pwd_chars.clone(),
+            social_traits: self.social_traits.clone(),
+            renderer: self.renderer.clone(),
+            weekly_digest_hour: self.weekly_digest_hour,
+            completion_email: self.completion_email,
+            student_scores: self.student_scores,
+            date_style: self.date_style,
+            deferred: self.deferred.clone(),
+            failure_journal: self.failure_journal,
+            unsubscribe_secret: self.unsubscribe_secret.clone(),
+            auth_export_key: self.auth_export_key.clone(),
+            min_password_length: self.min_password_length,
+            archive_dir: self.archive_dir.clone(),
+            summary_labels: self.summary_labels.clone(),
+            failing_threshold: self.failing_threshold,
+            retention: self.retention.clone(),
+            prune_stats: std::sync::Mutex::new(PruneStats::default()),
+            roster_sync: None,
+            roster: std::sync::Mutex::new(RosterState::default()),
+            anonymized: std::sync::Mutex::new(HashSet::new()),
+            static_dir: self.static_dir.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            confirmations: self.confirmations.clone(),
+            webhooks: None,
+            downloads: self.downloads.clone(),
+        };
+
+        glob.customs = glob
+            .store
+            .get_custom_chapters()
+            .await
+            .map_err(|e| format!("Error retrieving custom chapters from sandbox: {}", &e))?;
+        glob.refresh_users().await?;
+
+        Ok(glob)
+    }
+
+    /// Schedule background recomputation of the statistics for the courses
+    /// with the given `syms`.
+    pub fn defer_course_stats<S, I>(&self, syms: I)
+    where
+        S: AsRef<str>,
+        I: IntoIterator<Item = S>,
+    {
+        self.deferred.schedule(
+            syms.into_iter()
+                .map(|s| deferred::Job::CourseStats(s.as_ref().to_owned())),
+        );
+    }
+
+    /**
+    Queue a delivery of `event`, with the details in `data`, to every
+    webhook that wants it, and wake the [`webhook::Outbox`] to send them.
+
+    Nothing should fail because a webhook couldn't be queued, so errors
+    are only logged.
+    */
+    pub async fn fire_webhook<T: Serialize>(&self, event: Event, data: &T) {
+        let outbox = match &self.webhooks {
+            Some(outbox) => outbox,
+            None => {
+                return;
+            }
+        };
+        let payload = match webhook::payload(event, data) {
+            Ok(payload) => payload,
+            Err(e) => {
+                log::error!("Error composing {} webhook payload: {}", &event, &e);
+                return;
+            }
+        };
+
+        match self
+            .data
+            .read()
+            .await
+            .queue_webhook_deliveries(event, &payload)
+            .await
+        {
+            Ok(0) => {}
+            Ok(n) => {
+                log::trace!("Queued {} {} webhook deliveries.", &n, &event);
+                outbox.wake();
+            }
+            Err(e) => log::error!("Error queueing {} webhook deliveries: {}", &event, &e),
+        }
+    }
 
     /// Generate a random password (for inserting a new user).
     fn random_password(&self, length: usize) -> String {
@@ -343,24 +1106,117 @@ impl<'a> Glob {
     pub async fn refresh_users(&mut self) -> Result<(), String> {
         log::trace!("Glob::refresh_users() called.");
         let new_users = self
-            .data
-            .read()
-            .await
+            .store
             .get_users()
             .await
             .map_err(|e| format!("Error retrieving users from Data DB: {}", &e))?;
         self.users = new_users;
+        self.index_users();
         Ok(())
     }
 
-    /// Retrieve all `Course` data from the database and replace the contents
-    /// of the current `.courses` map with it.
+    /// Rebuild the indices of `.users` by role and by teacher.
+    fn index_users(&mut self) {
+        let mut role_index: HashMap<Role, Vec<String>> = HashMap::new();
+        let mut class_index: HashMap<String, Vec<String>> = HashMap::new();
+        for (uname, u) in self.users.iter() {
+            role_index.entry(u.role()).or_default().push(uname.clone());
+            if let User::Student(s) = u {
+                class_index
+                    .entry(s.teacher.clone())
+                    .or_default()
+                    .push(uname.clone());
+            }
+        }
+        for unames in role_index.values_mut().chain(class_index.values_mut()) {
+            unames.sort();
+        }
+        self.role_index = role_index;
+        self.class_index = class_index;
+    }
+
+    /// Return the unames of all the users with the given `role`, in order.
+    pub fn unames_with_role(&self, role: Role) -> &[String] {
+        self.role_index
+            .get(&role)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Return all the users with the given `role`, in order of uname.
+    pub fn users_with_role(&'a self, role: Role) -> impl Iterator<Item = &'a User> + 'a {
+        self.unames_with_role(role)
+            .iter()
+            .filter_map(|uname| self.users.get(uname))
+    }
+
+    /**
+    Compare the users in the data DB (as currently held in `.users`) with
+    the unames present in the auth DB, and log any discrepancies.
+
+    If `create_missing` is true, users with no auth DB entry get one with a
+    random password, which means they'll have to go through the password
+    reset process to log in. Orphaned auth DB entries are never removed.
+    */
+    pub async fn reconcile_auth(&self, create_missing: bool) -> Result<AuthDrift, UnifiedError> {
+        log::trace!("Glob::reconcile_auth( {} ) called.", &create_missing);
+
+        let auth = self.auth.read().await;
+        let auth_unames = auth.get_unames().await?;
+
+        let mut drift = AuthDrift::default();
+        for uname in self.users.keys() {
+            if !auth_unames.contains(uname) {
+                log::warn!("User {:?} is in the data DB but not the auth DB.", uname);
+                drift.missing_auth.push(uname.clone());
+            }
+        }
+        for uname in auth_unames.iter() {
+            if !self.users.contains_key(uname) {
+                log::warn!("User {:?} is in the auth DB but not the data DB.", uname);
+                drift.orphaned_auth.push(uname.clone());
+            }
+        }
+        drift.missing_auth.sort();
+        drift.orphaned_auth.sort();
+
+        if create_missing && !drift.missing_auth.is_empty() {
+            let passwords: Vec<String> = drift
+                .missing_auth
+                .iter()
+                .map(|_| self.random_password(32))
+                .collect();
+            let mut salts: Vec<&str> = Vec::with_capacity(drift.missing_auth.len());
+            for uname in drift.missing_auth.iter() {
+                // Every uname in `missing_auth` came from `self.users`.
+                salts.push(self.users[uname].salt());
+            }
+            let uname_refs: Vec<&str> = drift.missing_auth.iter().map(|s| s.as_str()).collect();
+            let pword_refs: Vec<&str> = passwords.iter().map(|s| s.as_str()).collect();
+
+            let mut client = auth.connect().await?;
+            let t = client.transaction().await?;
+            let n = auth
+                .add_users(&t, &uname_refs, &pword_refs, &salts)
+                .await?;
+            t.commit().await?;
+            drift.n_created = n as usize;
+            log::info!(
+                "Created {} auth DB entries; those users must reset their passwords.",
+                &n
+            );
+        }
+
+        Ok(drift)
+    }
+
+    /// Retrieve all `Course` data (and all custom chapters) from the database
+    /// and replace the contents of the current `.courses` (and `.customs`)
+    /// map with it.
     pub async fn refresh_courses(&mut self) -> Result<(), String> {
         log::trace!("Glob::refresh_courses() called.");
         let new_courses = self
-            .data
-            .read()
-            .await
+            .store
             .get_courses()
             .await
             .map_err(|e| format!("Error retrieving course information from Data DB: {}", &e))?;
@@ -371,22 +1227,30 @@ impl<'a> Glob {
             .map(|(id, crs)| (crs.sym.clone(), *id))
             .collect();
         self.course_syms = new_sym_map;
+        self.course_token = catalog_token(self.courses.values());
+        self.customs = self
+            .store
+            .get_custom_chapters()
+            .await
+            .map_err(|e| format!("Error retrieving custom chapters from Data DB: {}", &e))?;
         Ok(())
     }
 
-    /// Refresh the internal list of instructional days from the values stored
-    /// in the database.
+    /// Refresh the internal list of instructional days (and exam windows)
+    /// from the values stored in the database.
     pub async fn refresh_calendar(&mut self) -> Result<(), String> {
         log::trace!("Glob::refresh_calendar() called.");
         let new_dates = self
-            .data
-            .read()
-            .await
+            .store
             .get_calendar()
             .await
             .map_err(|e| format!("Error retrieving calendar dates from Data DB: {}", &e))?;
-        self.calendar = new_dates;
-        self.calendar.sort();
+        let windows = self
+            .store
+            .get_exam_windows()
+            .await
+            .map_err(|e| format!("Error retrieving exam windows from Data DB: {}", &e))?;
+        self.calendar = AcademicCalendar::new(new_dates).with_exam_windows(windows);
         Ok(())
     }
 
@@ -394,9 +1258,7 @@ impl<'a> Glob {
     pub async fn refresh_dates(&mut self) -> Result<(), String> {
         log::trace!("Glob::refresh_dates() called.");
         let new_dates = self
-            .data
-            .read()
-            .await
+            .store
             .get_dates()
             .await
             .map_err(|e| format!("Error retrieving special dates from Data DB: {}", &e))?;
@@ -404,18 +1266,45 @@ impl<'a> Glob {
         Ok(())
     }
 
+    /// Refresh the academic year metadata with the values from the database.
+    pub async fn refresh_academic_years(&mut self) -> Result<(), String> {
+        log::trace!("Glob::refresh_academic_years() called.");
+        self.academic_years = self
+            .store
+            .get_academic_years()
+            .await
+            .map_err(|e| format!("Error retrieving academic years from Data DB: {}", &e))?;
+        Ok(())
+    }
+
+    /// The academic year an Admin has marked as current, if any.
+    pub fn current_year(&self) -> Option<&AcademicYear> {
+        self.academic_years.iter().find(|y| y.active)
+    }
+
     /// Return the current academic year's starting year.
+    ///
+    /// This comes from the [`current_year`](Glob::current_year) if there
+    /// is one, and is otherwise guessed from the first day of the calendar.
     pub fn academic_year(&self) -> i32 {
+        if let Some(y) = self.current_year() {
+            return y.start_year();
+        }
         match self.calendar.first() {
             Some(d) => d.year(),
             None => 0i32,
         }
     }
 
-    /// Return a string representation of the current academic year.
+    /// Return a string representation of the current academic year: the
+    /// [`current_year`](Glob::current_year)'s label, if there is one, or
+    /// else one made up from the first day of the calendar.
     ///
     /// For example: `"2022--2023"`
     pub fn academic_year_string(&self) -> MiniString<SMALLSTORE> {
+        if let Some(y) = self.current_year() {
+            return MiniString::from(y.label.as_str());
+        }
         match self.calendar.first() {
             Some(d) => crate::academic_year_from_start_date(d),
             None => crate::academic_year_from_start_year(0),
@@ -470,28 +1359,58 @@ impl<'a> Glob {
         Ok(())
     }
 
+    /// Check whether `uname` would do for a new user: whether it's a valid
+    /// uname, and who (if anyone) already has it.
+    pub async fn check_uname(&self, uname: &str) -> Result<NameCheck, UnifiedError> {
+        log::trace!("Glob::check_uname( {:?} ) called.", uname);
+
+        let existing = match self.data.read().await.get_user_role(uname).await? {
+            Some(role) => vec![(uname.to_owned(), role)],
+            None => Vec::new(),
+        };
+
+        Ok(NameCheck {
+            value: uname.to_owned(),
+            problem: uname_problem(uname),
+            existing,
+        })
+    }
+
+    /// Check whether `email` would do for a new user: whether it looks like
+    /// an address, and who (if anyone) already uses it.
+    ///
+    /// Users are allowed to share addresses, so it's up to the caller whether
+    /// an address already in use is a problem.
+    pub async fn check_email(&self, email: &str) -> Result<NameCheck, UnifiedError> {
+        log::trace!("Glob::check_email( {:?} ) called.", email);
+
+        let existing = self.data.read().await.get_users_by_email(email).await?;
+
+        Ok(NameCheck {
+            value: email.to_owned(),
+            problem: email_problem(email),
+            existing,
+        })
+    }
+
     /// Insert the given user into both the auth and the data databases.
     ///
     /// This takes advantage of the fact that it's necessary to insert into
     /// the data DB and get back a salt string before the user info can be
-    /// inserted into the auth DB.
-    pub async fn insert_user(&self, u: &User) -> Result<(), UnifiedError> {
-        log::trace!("Glob::insert_user( {:?} ) called.", u);
+    /// inserted into the auth DB. `actor` is who's doing it, for the audit log.
+    pub async fn insert_user(&self, actor: &str, u: &User) -> Result<(), UnifiedError> {
+        log::trace!("Glob::insert_user( {:?}, {:?} ) called.", actor, u);
 
-        if bad_uname(u.uname()) {
-            return Err(BAD_UNAME_MSG.to_string().into());
+        if let Some(msg) = uname_problem(u.uname()) {
+            return Err(msg.into());
         }
 
         match u {
-            User::Teacher(ref t) => {
-                if has_bad_chars(&t.name) {
-                    return Err(format!("Names {}", BAD_CHARS_MSG).into());
-                }
+            User::Teacher(ref t) if has_bad_chars(&t.name) => {
+                return Err(format!("Names {}", BAD_CHARS_MSG).into());
             }
-            User::Student(ref s) => {
-                if has_bad_chars(&s.last) || has_bad_chars(&s.rest) {
-                    return Err(format!("Names {}", BAD_CHARS_MSG).into());
-                }
+            User::Student(ref s) if has_bad_chars(&s.last) || has_bad_chars(&s.rest) => {
+                return Err(format!("Names {}", BAD_CHARS_MSG).into());
             }
             _ => { /* We don't need to check anything else. */ }
         }
@@ -533,6 +1452,8 @@ impl<'a> Glob {
         )
         })?;
 
+        let role = u.role().to_string();
+        self.log_action(actor, "insert-user", u.uname(), Some(&role)).await;
         Ok(())
     }
 
@@ -618,20 +1539,17 @@ impl<'a> Glob {
         Ok(())
     }
 
-    /// Update the user data associated with `u.uname()` with the other data in `u`.
-    pub async fn update_user(&self, u: &User) -> Result<(), UnifiedError> {
-        log::trace!("Glob::update_user( {:?} ) called.", u);
+    /// Update the user data associated with `u.uname()` with the other data
+    /// in `u`, on behalf of `actor`.
+    pub async fn update_user(&self, actor: &str, u: &User) -> Result<(), UnifiedError> {
+        log::trace!("Glob::update_user( {:?}, {:?} ) called.", actor, u);
 
         match u {
-            User::Teacher(ref t) => {
-                if has_bad_chars(&t.name) {
-                    return Err(format!("Names {}", BAD_CHARS_MSG).into());
-                }
+            User::Teacher(ref t) if has_bad_chars(&t.name) => {
+                return Err(format!("Names {}", BAD_CHARS_MSG).into());
             }
-            User::Student(ref s) => {
-                if has_bad_chars(&s.last) || has_bad_chars(&s.rest) {
-                    return Err(format!("Names {}", BAD_CHARS_MSG).into());
-                }
+            User::Student(ref s) if has_bad_chars(&s.last) || has_bad_chars(&s.rest) => {
+                return Err(format!("Names {}", BAD_CHARS_MSG).into());
             }
             _ => { /* We don't need to check anything else. */ }
         }
@@ -688,12 +1606,14 @@ impl<'a> Glob {
 
         t.commit().await?;
 
+        self.log_action(actor, "update-user", u.uname(), None).await;
         Ok(())
     }
 
-    /// Delete from the database all information associated with user name `uname`.
-    pub async fn delete_user(&self, uname: &str) -> Result<(), UnifiedError> {
-        log::trace!("Glob::delete_user( {:?} ) called.", uname);
+    /// Delete from the database all information associated with user name
+    /// `uname`, on behalf of `actor`.
+    pub async fn delete_user(&self, actor: &str, uname: &str) -> Result<(), UnifiedError> {
+        log::trace!("Glob::delete_user( {:?}, {:?} ) called.", actor, uname);
 
         {
             let u = match self.users.get(uname) {
@@ -716,6 +1636,10 @@ impl<'a> Glob {
                     }
                     return Err(UnifiedError::String(estr));
                 }
+                // Their sandbox would otherwise outlive them.
+                if self.training.contains(uname) {
+                    self.data.read().await.set_training(uname, false).await?;
+                }
             }
         }
 
@@ -738,9 +1662,85 @@ impl<'a> Glob {
             ).into());
         }
 
+        self.log_action(actor, "delete-user", uname, None).await;
         Ok(())
     }
 
+    /**
+    Move all of Teacher `from_t`'s students to Teacher `to_t` (like when
+    `from_t` leaves), on behalf of `actor`, and return the unames of the
+    students moved.
+
+    This doesn't refresh `self.users`; the caller should do that. If either
+    of them isn't a Teacher, or they're the same Teacher, the error is a
+    `UnifiedError::String`.
+    */
+    pub async fn reassign_students(
+        &self,
+        actor: &str,
+        from_t: &str,
+        to_t: &str,
+    ) -> Result<Vec<String>, UnifiedError> {
+        log::trace!(
+            "Glob::reassign_students( {:?}, {:?}, {:?} ) called.",
+            actor,
+            from_t,
+            to_t
+        );
+
+        if from_t == to_t {
+            return Err(format!("Students can't be moved from {:?} to themselves.", from_t).into());
+        }
+        for uname in [from_t, to_t] {
+            match self.users.get(uname) {
+                Some(User::Teacher(_)) => {}
+                Some(u) => {
+                    return Err(format!("{:?} is not a Teacher ({}).", uname, u.role()).into());
+                }
+                None => {
+                    return Err(format!("There is no Teacher {:?}.", uname).into());
+                }
+            }
+        }
+
+        let moved = self.data.read().await.reassign_students(from_t, to_t).await?;
+
+        let detail = format!("{} students to {}", moved.len(), to_t);
+        self.log_action(actor, "reassign-students", from_t, Some(&detail)).await;
+        Ok(moved)
+    }
+
+    /**
+    Record in the audit log (see [`Store::log_action`]) that `actor` did
+    `action` to `target`.
+
+    Failing to record it is logged, but isn't an error; whatever was done
+    has already been done by the time it's recorded.
+    */
+    pub async fn log_action(&self, actor: &str, action: &str, target: &str, detail: Option<&str>) {
+        let res = self
+            .data
+            .read()
+            .await
+            .log_action(actor, action, target, detail)
+            .await;
+        if let Err(e) = res {
+            log::error!(
+                "Unable to audit {:?} {:?} {:?}: {}",
+                actor,
+                action,
+                target,
+                &e.display()
+            );
+        }
+    }
+
+    /// Return an explanation of why `pwd` isn't an acceptable password for
+    /// user `uname` to choose, if it isn't.
+    pub fn check_password_policy(&self, uname: &str, pwd: &str) -> Result<(), String> {
+        check_password_policy(uname, pwd, self.min_password_length)
+    }
+
     /// Set user `uname` to authenticate with the given `new_password`.
     pub async fn update_password(
         &self,
@@ -769,16 +1769,13 @@ impl<'a> Glob {
             teacher_uname
         );
 
-        let mut stud_refs: Vec<&User> = Vec::new();
-        for (_, u) in self.users.iter() {
-            if let User::Student(ref s) = u {
-                if s.teacher == teacher_uname {
-                    stud_refs.push(u);
-                }
-            }
+        match self.class_index.get(teacher_uname) {
+            Some(unames) => unames
+                .iter()
+                .filter_map(|uname| self.users.get(uname))
+                .collect(),
+            None => Vec::new(),
         }
-
-        stud_refs
     }
 
     /**
@@ -929,36 +1926,124 @@ impl<'a> Glob {
         }
     }
 
-    /// Insert the given slice of Goals into the database.
-    pub async fn insert_goals(&self, goals: &[Goal]) -> Result<usize, UnifiedError> {
-        log::trace!("Glob::insert_goals( [ {} Goals ] ) called.", &goals.len());
+    /**
+    Check the new course `levels` (`(sym, level)` pairs, as from
+    [`levels_from_csv`](crate::course::levels_from_csv)) and work out what
+    setting them would do: which courses actually change, which would end
+    up sharing a level, and whose goals would be put in a different order.
 
-        // First we want to check the unames and courses on all the goals and
-        // ensure those exist before we start trying to insert. This will
-        // allow us to produce a better error message for the user.
-        {
-            let mut unk_users: HashSet<String> = HashSet::new();
-            let mut unk_courses: HashSet<String> = HashSet::new();
-            for g in goals.iter() {
-                match self.users.get(&g.uname) {
-                    Some(User::Student(_)) => { /* This is what we hope is true! */ }
-                    _ => {
-                        unk_users.insert(g.uname.clone());
-                    }
-                }
+    Fails if any of the symbols isn't a course.
+    */
+    pub async fn preview_relevel(
+        &self,
+        levels: &[(String, f32)],
+    ) -> Result<RelevelPreview, UnifiedError> {
+        log::trace!("Glob::preview_relevel( {:?} ) called.", levels);
+
+        let old: HashMap<String, f32> = self
+            .courses
+            .values()
+            .map(|crs| (crs.sym.clone(), crs.level))
+            .collect();
+        let mut new = old.clone();
+
+        let mut preview = RelevelPreview::default();
+        for (sym, level) in levels.iter() {
+            let crs = self
+                .course_by_sym(sym)
+                .ok_or_else(|| format!("There is no course with symbol {:?}.", sym))?;
+            if crs.level != *level {
+                preview.changes.push(LevelChange {
+                    sym: sym.clone(),
+                    title: crs.title.clone(),
+                    old: crs.level,
+                    new: *level,
+                });
+                new.insert(sym.clone(), *level);
+            }
+        }
+        if preview.changes.is_empty() {
+            return Ok(preview);
+        }
+
+        let mut by_level: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+        for (sym, level) in new.iter() {
+            by_level.entry(level.to_bits()).or_default().push(sym.clone());
+        }
+        for (_, mut syms) in by_level.into_iter() {
+            if syms.len() > 1 && syms.iter().any(|s| preview.changes.iter().any(|c| &c.sym == s)) {
+                syms.sort();
+                preview.ties.push(syms);
+            }
+        }
+
+        let changed: HashSet<&str> = preview.changes.iter().map(|c| c.sym.as_str()).collect();
+        for tuname in self.unames_with_role(Role::Teacher) {
+            let goals = self.store.get_goals_by_teacher(tuname).await?;
+            let mut by_student: BTreeMap<String, Vec<Goal>> = BTreeMap::new();
+            for g in goals.into_iter() {
+                by_student.entry(g.uname.clone()).or_default().push(g);
+            }
+            for (uname, goals) in by_student.into_iter() {
+                let touched = goals.iter().any(|g| match &g.source {
+                    Source::Book(b) => changed.contains(b.sym.as_str()),
+                    Source::Custom(_) => false,
+                });
+                if !touched {
+                    continue;
+                }
+                let n_moved = count_reordered(&goals, &old, &new);
+                if let (true, Some(User::Student(s))) = (n_moved > 0, self.users.get(&uname)) {
+                    preview.students.push(Reordered {
+                        uname: uname.clone(),
+                        last: s.last.clone(),
+                        rest: s.rest.clone(),
+                        teacher: s.teacher.clone(),
+                        n_moved,
+                    });
+                }
+            }
+        }
+        preview.students.sort_by(|a, b| {
+            (&a.teacher, &a.last, &a.rest).cmp(&(&b.teacher, &b.last, &b.rest))
+        });
+
+        Ok(preview)
+    }
+
+    /// Insert the given slice of Goals into the database.
+    pub async fn insert_goals(&self, goals: &[Goal]) -> Result<usize, UnifiedError> {
+        log::trace!("Glob::insert_goals( [ {} Goals ] ) called.", &goals.len());
+
+        // First we want to check the unames and courses on all the goals and
+        // ensure those exist before we start trying to insert. This will
+        // allow us to produce a better error message for the user.
+        {
+            let mut unk_users: HashSet<String> = HashSet::new();
+            let mut unk_courses: HashSet<String> = HashSet::new();
+            let mut unk_customs: HashSet<i64> = HashSet::new();
+            for g in goals.iter() {
+                match self.users.get(&g.uname) {
+                    Some(User::Student(_)) => { /* This is what we hope is true! */ }
+                    _ => {
+                        unk_users.insert(g.uname.clone());
+                    }
+                }
                 match g.source {
                     Source::Book(ref bch) => {
-                        if self.course_syms.get(&bch.sym).is_none() {
+                        if !self.course_syms.contains_key(&bch.sym) {
                             unk_courses.insert(bch.sym.clone());
                         }
                     }
-                    _ => {
-                        return Err("Custom Courses not yet supported.".to_owned().into());
+                    Source::Custom(CustomCh(id)) => {
+                        if !self.customs.contains_key(&id) {
+                            unk_customs.insert(id);
+                        }
                     }
                 }
             }
 
-            if !(unk_users.is_empty() && unk_courses.is_empty()) {
+            if !(unk_users.is_empty() && unk_courses.is_empty() && unk_customs.is_empty()) {
                 let mut estr = String::new();
                 if !unk_users.is_empty() {
                     writeln!(
@@ -982,12 +2067,126 @@ impl<'a> Glob {
                             .map_err(|e| format!("Error preparing error message: {}!!!", &e))?;
                     }
                 }
+                if !unk_customs.is_empty() {
+                    writeln!(
+                        &mut estr,
+                        "The following ids do not belong to known custom chapters:"
+                    )
+                    .map_err(|e| format!("Error preparing error message: {}!!!", &e))?;
+                    for id in unk_customs.iter() {
+                        writeln!(&mut estr, "{}", id)
+                            .map_err(|e| format!("Error preparing error message: {}!!!", &e))?;
+                    }
+                }
 
                 return Err(estr.into());
             }
         }
 
-        let n_inserted = self.data.read().await.insert_goals(goals).await?;
+        let n_inserted = self.store.insert_goals(goals).await?;
+        Ok(n_inserted)
+    }
+
+    /**
+    Assign a Student an entire Course (or a contiguous range of its Chapters)
+    at once, generating one [`Goal`] per Chapter.
+
+    `chapters`, if supplied, is an inclusive `(first, last)` range of chapter
+    numbers. The new Goals' due dates are distributed (proportionally by
+    weight) among the instructional days between `start` and `end`
+    (inclusive), which default to the beginning and end of the calendar,
+    except those during exam windows or any of the given `absences` of the
+    Student's.
+    All Goals are inserted in a single transaction.
+
+    Returns the number of Goals inserted.
+    */
+    pub async fn enroll_course(
+        &self,
+        uname: &str,
+        sym: &str,
+        chapters: Option<(i16, i16)>,
+        start: Option<Date>,
+        end: Option<Date>,
+        absences: &[Absence],
+    ) -> Result<usize, UnifiedError> {
+        log::trace!(
+            "Glob::enroll_course( {:?}, {:?}, {:?}, {:?}, {:?}, [ {} absences ] ) called.",
+            uname,
+            sym,
+            &chapters,
+            &start,
+            &end,
+            absences.len()
+        );
+
+        match self.users.get(uname) {
+            Some(User::Student(_)) => { /* This is what we hope is true! */ }
+            _ => {
+                return Err(format!("{:?} is not a Student in the database.", uname).into());
+            }
+        }
+
+        let crs = self
+            .course_by_sym(sym)
+            .ok_or_else(|| format!("{:?} is not a known course symbol.", sym))?;
+        let crs_weight = match crs.weight {
+            Some(w) if w > 0.0001 => w,
+            _ => {
+                return Err(format!(
+                    "Course {:?} ({}) doesn't have its weights set.",
+                    &crs.sym, &crs.title
+                )
+                .into());
+            }
+        };
+
+        let mut goals: Vec<Goal> = crs
+            .all_chapters()
+            .filter(|ch| match chapters {
+                Some((first, last)) => ch.seq >= first && ch.seq <= last,
+                None => true,
+            })
+            .map(|ch| Goal {
+                id: 0,
+                uname: uname.to_owned(),
+                source: Source::Book(BookCh {
+                    sym: crs.sym.clone(),
+                    seq: ch.seq,
+                    level: crs.level,
+                }),
+                review: false,
+                incomplete: false,
+                due: None,
+                done: None,
+                tries: None,
+                weight: ch.weight / crs_weight,
+                score: None,
+                tags: Vec::new(),
+                rubric: Vec::new(),
+                part: None,
+                progress: None,
+                requested: false,
+                test: None,
+            })
+            .collect();
+        if goals.is_empty() {
+            return Err(format!(
+                "Course {:?} ({}) has no chapters in the requested range.",
+                &crs.sym, &crs.title
+            )
+            .into());
+        }
+        goals.sort_by_key(|g| match &g.source {
+            Source::Book(bch) => bch.seq,
+            _ => 0,
+        });
+
+        let dates = self.calendar.schedulable(self.calendar.window(start, end));
+        let dates = outside_absences(&dates, absences);
+        distribute_due_dates(&mut goals, &dates)?;
+
+        let n_inserted = self.store.insert_goals(&goals).await?;
         Ok(n_inserted)
     }
 
@@ -1012,12 +2211,128 @@ impl<'a> Glob {
             }
         };
 
-        let goals = self.data.read().await.get_goals_by_student(uname).await?;
+        let (goals, waived) = self.store.get_pace_bundle(uname).await?;
 
-        let p = Pace::new(stud, teach, goals, self)?;
+        let p = Pace::new(stud, teach, goals, waived, self)?;
         Ok(p)
     }
 
+    /**
+    Waive (if `waived` is `true`) or un-waive the chapter `w` for the Student
+    `uname`; `by` is the uname of the user responsible.
+
+    Returns whether anything changed.
+    */
+    pub async fn set_waiver(
+        &self,
+        uname: &str,
+        w: &Waiver,
+        waived: bool,
+        by: &str,
+    ) -> Result<bool, UnifiedError> {
+        log::trace!(
+            "Glob::set_waiver( {:?}, {:?}, {}, {:?} ) called.",
+            uname,
+            w,
+            &waived,
+            by
+        );
+
+        match self.users.get(uname) {
+            Some(User::Student(_)) => {}
+            _ => {
+                return Err(format!("{:?} is not a Student in the database.", uname).into());
+            }
+        }
+        // Only check the chapter exists when waiving, so waivers for deleted
+        // chapters can still be removed.
+        if waived {
+            let crs = self
+                .course_by_sym(&w.sym)
+                .ok_or_else(|| format!("{:?} is not a course symbol.", &w.sym))?;
+            if crs.chapter(w.seq).is_none() {
+                return Err(format!(
+                    "Course {:?} ({}) does not have a chapter {}.",
+                    &w.sym, &crs.title, &w.seq
+                )
+                .into());
+            }
+        }
+
+        let changed = self
+            .data
+            .read()
+            .await
+            .set_waiver(uname, w, waived, by)
+            .await?;
+        Ok(changed)
+    }
+
+    /**
+    Reload `Course` data from the database and report how the weights of the
+    goals of the students `unames` (only those from the course `sym`, if
+    given) change as a result.
+
+    `Goal` weights aren't stored; they're derived from chapter weights (and
+    waivers, and split goals' fractions) every time a [`Pace`] is built, so
+    the students' `Pace`s are built before and after reloading, and their
+    goals compared. After this, every `Pace` will be built with the current
+    weights. Only `Goal`s whose weights actually change are reported.
+    */
+    pub async fn recompute_weights(
+        &mut self,
+        unames: &[String],
+        sym: Option<&str>,
+    ) -> Result<Vec<WeightDelta>, UnifiedError> {
+        log::trace!(
+            "Glob::recompute_weights( [ {} unames ], {:?} ) called.",
+            &unames.len(),
+            &sym
+        );
+
+        let mut old: HashMap<i64, f32> = HashMap::new();
+        for uname in unames.iter() {
+            // Stale course data might not even make a Pace; then the old
+            // weights just aren't known.
+            match self.get_pace_by_student(uname).await {
+                Ok(p) => old.extend(p.goals.iter().map(|g| (g.id, g.weight))),
+                Err(e) => {
+                    log::warn!("Unable to build Pace for {:?} before reloading: {}", uname, &e);
+                }
+            }
+        }
+
+        self.refresh_courses().await?;
+
+        let mut deltas: Vec<WeightDelta> = Vec::new();
+        for uname in unames.iter() {
+            let p = self.get_pace_by_student(uname).await?;
+            for g in p.goals.iter() {
+                let bch = match &g.source {
+                    Source::Book(bch) => bch,
+                    Source::Custom(_) => continue,
+                };
+                if matches!(sym, Some(sym) if sym != bch.sym) {
+                    continue;
+                }
+                let old = old.get(&g.id).copied();
+                if matches!(old, Some(w) if (w - g.weight).abs() <= 0.0001) {
+                    continue;
+                }
+                deltas.push(WeightDelta {
+                    id: g.id,
+                    uname: g.uname.clone(),
+                    sym: bch.sym.clone(),
+                    seq: bch.seq,
+                    old,
+                    new: g.weight,
+                });
+            }
+        }
+
+        Ok(deltas)
+    }
+
     /// Get [`Pace`]s for all Students who have the Teacher with the given `uname`.
     pub async fn get_paces_by_teacher(&self, tuname: &str) -> Result<Vec<Pace>, UnifiedError> {
         log::trace!("Glob::get_paces_by_teacher( {:?} ) called.", tuname);
@@ -1031,7 +2346,10 @@ impl<'a> Glob {
 
         let students = self.get_students_by_teacher(tuname);
 
-        let mut goals = self.data.read().await.get_goals_by_teacher(tuname).await?;
+        let (mut goals, mut waivers) = tokio::try_join!(
+            self.store.get_goals_by_teacher(tuname),
+            self.store.get_waivers_by_teacher(tuname)
+        )?;
 
         let mut goal_map: HashMap<String, Vec<Goal>> = HashMap::with_capacity(students.len());
 
@@ -1046,7 +2364,7 @@ impl<'a> Glob {
         }
 
         for s in students {
-            if goal_map.get(s.uname()).is_none() {
+            if !goal_map.contains_key(s.uname()) {
                 goal_map.insert(s.uname().to_string(), vec![]);
             }
         }
@@ -1064,7 +2382,8 @@ impl<'a> Glob {
                 }
             };
 
-            let p = match Pace::new(s, teach.clone(), v, self) {
+            let waived = waivers.remove(&uname).unwrap_or_default();
+            let p = match Pace::new(s, teach.clone(), v, waived, self) {
                 Ok(p) => p,
                 Err(e) => {
                     log::error!("Error generating Pace calendar for {:?}: {}", &uname, &e);
@@ -1078,132 +2397,225 @@ impl<'a> Glob {
         Ok(cals)
     }
 
-    pub async fn get_reports_archive_by_teacher(
-        &self,
-        tuname: &str,
-        term: Term,
-    ) -> Result<Option<Vec<u8>>, UnifiedError> {
-        use std::io::Write;
-        use zip::{write::FileOptions, CompressionMethod, ZipWriter};
-        log::trace!(
-            "Glob::get_reports_archive_by_teacher( {:?} ) called.",
-            tuname
-        );
+    /**
+    Record a [`LagSnapshot`] of every Teacher's students for the week
+    containing `today` (except those already recorded for that week).
+
+    Students who are behind pace this week but weren't in their previous
+    snapshot (or have none) fire a `student-lagging` webhook.
+
+    Returns the number of snapshots recorded.
+    */
+    pub async fn snapshot_lags(&self, today: &Date) -> Result<usize, UnifiedError> {
+        log::trace!("Glob::snapshot_lags( {} ) called.", today);
+
+        let days_since_monday = today.weekday().number_days_from_monday() as i64;
+        let week = today.saturating_sub(Duration::days(days_since_monday));
+
+        let mut paces: Vec<Pace> = Vec::new();
+        for tuname in self.unames_with_role(Role::Teacher) {
+            paces.extend(self.get_paces_by_teacher(tuname).await?);
+        }
 
-        /*
-        Okay, Praise Be To Cthulhu, this is disgusting.
-
-        First of all, the whole idea of this function is Not The Right Thing.
-        The Right Thing would have been to implement a function that returns an
-        async Stream of PDF files on the `Store` struct, and push this whole
-        encoding of a ZIP archive into the `inter::boss` module. But I don't
-        think I have the time and experience to learn how to do that in a
-        not-horribly-brittle way, so you have this, in a sort shove-one-half-
-        of-the-abstraction-down-a-layer-and-one-half-of-the-abstraction-up-
-        a-layer meeting-in-the-middle compromise of which I am not proud.
-
-        Also, the implementation itself is the kind of disgusting, labyrinthine
-        thing I find myself writing when trying to be asynchronously clever.
-        */
-
-        let term_str = term.as_str();
-        let stud_refs = self.get_students_by_teacher(tuname);
-        let params: Vec<[&(dyn ToSql + Sync); 2]> = stud_refs
+        let snaps: Vec<(&str, LagSnapshot)> = paces
             .iter()
-            .map(|u| match u {
-                User::Student(s) => Some(s),
-                _ => None,
+            .map(|p| {
+                let snap = LagSnapshot {
+                    week,
+                    lag: p.lag(),
+                    done_weight: p.done_weight,
+                    due_weight: p.due_weight,
+                    total_weight: p.total_weight,
+                    progress_weight: p.progress_weight(),
+                };
+                (p.student.base.uname.as_str(), snap)
             })
-            .filter(|s| s.is_some())
-            .map(|s| {
-                let p: [&(dyn ToSql + Sync); 2] = [
-                    &s.unwrap().base.uname,
-                    &term_str
-                ];
-                p
+            .collect();
+
+        let unames: Vec<&str> = snaps.iter().map(|(uname, _)| *uname).collect();
+        let history = self.data.read().await.get_lag_history(&unames).await?;
+        let lagging = |s: &LagSnapshot| s.done_weight < s.due_weight;
+        let newly_lagging: Vec<(&Pace, &LagSnapshot)> = paces
+            .iter()
+            .zip(snaps.iter())
+            .filter(|(_, (uname, snap))| {
+                lagging(snap)
+                    && match history.get(*uname).and_then(|h| h.last()) {
+                        None => true,
+                        // Already recorded this week, so already checked.
+                        Some(prev) if prev.week >= week => false,
+                        Some(prev) => !lagging(prev),
+                    }
             })
+            .map(|(p, (_, snap))| (p, snap))
             .collect();
 
-        if params.is_empty() {
-            return Err(format!("Teacher {:?} doesn't have any students.", tuname).into());
+        let n = self.data.read().await.insert_lag_snapshots(&snaps).await?;
+
+        for (p, snap) in newly_lagging.into_iter() {
+            let data = StudentLagging {
+                student: (&p.student).into(),
+                snapshot: snap,
+            };
+            self.fire_webhook(Event::StudentLagging, &data).await;
         }
-        let file_buff: Vec<u8> = Vec::new();
-        let zip_opts = FileOptions::default().compression_method(CompressionMethod::Stored);
-        let mut zip = ZipWriter::new(std::io::Cursor::new(file_buff));
-        let data = self.data();
-        let reader = data.read().await;
-        let mut client = reader.connect().await?;
-        let t = client.transaction().await?;
-        let stmt = t.prepare_typed(
-            "SELECT doc FROM reports WHERE uname = $1 AND term = $2",
-            &[Type::TEXT, Type::TEXT]
-        ).await?;
-
-        let mut uname_n: usize = 0;
-        let mut fut = t.query_opt(&stmt, &params[uname_n]);
-        uname_n += 1;
-        while uname_n < params.len() {
-            if let Ok(Some(row)) = fut.await {
-                fut = t.query_opt(&stmt, &params[uname_n]);
-                if let Ok(doc) = row.try_get("doc") {
-                    zip.start_file(format!("{}.pdf", stud_refs[uname_n - 1].uname()), zip_opts)
-                        .map_err(|e| {
-                            format!(
-                                "Error starting write of {}.pdf to archive: {}",
-                                stud_refs[uname_n - 1].uname(),
-                                &e
-                            )
-                        })?;
-                    if let Err(e) = zip.write(doc) {
-                        return Err(format!(
-                            "Error writing {}.pdf to archive: {}",
-                            stud_refs[uname_n - 1].uname(),
-                            &e
-                        )
-                        .into());
-                    }
-                }
-            } else {
-                fut = t.query_opt(&stmt, &params[uname_n]);
+
+        Ok(n)
+    }
+
+    /// Delete data that has been kept longer than `self.retention` says to,
+    /// and auth keys that have expired, and add what was deleted (or the
+    /// error) to `self.prune_stats`.
+    pub async fn prune(&self) -> Result<Pruned, UnifiedError> {
+        log::trace!("Glob::prune() called.");
+
+        let res: Result<Pruned, UnifiedError> = async {
+            let mut pruned = self.data.read().await.prune(&self.retention).await?;
+            pruned.keys = self.auth.read().await.cull_old_keys().await? as u64;
+            Ok(pruned)
+        }
+        .await;
+
+        let recorded = res.as_ref().map(Pruned::clone).map_err(|e| e.to_string());
+        self.prune_stats
+            .lock()
+            .unwrap()
+            .record(&recorded, time::OffsetDateTime::now_utc().to_string());
+
+        res
+    }
+
+    /// Fetch the SIS roster (see [`roster`](crate::roster)) and keep it for
+    /// an Admin to review, returning how it differs from the current
+    /// students. A failure to fetch is kept too, for Admins to see.
+    pub async fn fetch_roster(&self) -> Result<RosterDiff, UnifiedError> {
+        log::trace!("Glob::fetch_roster() called.");
+
+        let rs = self
+            .roster_sync
+            .as_ref()
+            .ok_or_else(|| "Roster sync is not configured.".to_owned())?;
+        let res = async {
+            let text = roster::fetch(rs).await?;
+            Student::vec_from_csv_reader(text.as_bytes())
+        }
+        .await;
+
+        let now = time::OffsetDateTime::now_utc().to_string();
+        let mut state = self.roster.lock().unwrap();
+        match res {
+            Ok(students) => {
+                state.fetched = Some(now);
+                state.last_error = None;
+                state.students = students;
+                Ok(roster::diff(&state.students, &self.users))
+            }
+            Err(e) => {
+                state.last_error = Some(format!("{}: {}", &now, &e));
+                Err(e.into())
             }
-            uname_n += 1;
         }
-        
-        let mut n_reports: usize = 0;
+    }
 
-        if let Ok(Some(row)) = fut.await {
-            if let Ok(doc) = row.try_get("doc") {
-                zip.start_file(
-                    format!("{}.pdf", stud_refs.last().unwrap().uname()),
-                    zip_opts,
-                )
-                .map_err(|e| {
-                    format!(
-                        "Error starting write of {}.pdf to archive: {}",
-                        stud_refs[uname_n - 1].uname(),
-                        &e
-                    )
-                })?;
-                if let Err(e) = zip.write(doc) {
-                    return Err(format!(
-                        "Error writing {}.pdf to archive: {}",
-                        stud_refs.last().unwrap().uname(),
-                        &e
-                    )
-                    .into());
+    /// How the most recently fetched roster differs from the current
+    /// students, if one has been fetched.
+    pub fn roster_diff(&self) -> Option<RosterDiff> {
+        let state = self.roster.lock().unwrap();
+        state.fetched.as_ref()?;
+        Some(roster::diff(&state.students, &self.users))
+    }
+
+    /**
+    Apply the changes from the most recently fetched roster: add the new
+    students, move students to their new Teachers, and (if `withdraw` is
+    `true`) delete the students who have withdrawn.
+
+    `token` must be the `token` from the [`RosterDiff`] that was reviewed;
+    if the roster or the students have changed since, nothing is done.
+    The changes are audited as the Admin `actor`'s.
+    Each change is made on its own, so one that fails doesn't stop the rest.
+    Either way, [`Glob::refresh_users`] should be called afterward.
+    */
+    pub async fn apply_roster(
+        &self,
+        actor: &str,
+        token: &str,
+        withdraw: bool,
+    ) -> Result<RosterApplied, UnifiedError> {
+        log::trace!(
+            "Glob::apply_roster( {:?}, {:?}, {} ) called.",
+            actor,
+            token,
+            &withdraw
+        );
+
+        let (d, students) = {
+            let state = self.roster.lock().unwrap();
+            if state.fetched.is_none() {
+                return Err("No roster has been fetched yet.".to_owned().into());
+            }
+            (
+                roster::diff(&state.students, &self.users),
+                state.students.clone(),
+            )
+        };
+        if d.token != token.trim() {
+            return Err(
+                "The roster or the students have changed since it was reviewed; review it again."
+                    .to_owned()
+                    .into(),
+            );
+        }
+
+        let mut applied = RosterApplied::default();
+        let fail = |uname: &str, e: UnifiedError| Failure {
+            uname: uname.to_owned(),
+            error: e.to_string(),
+        };
+
+        for a in d.adds.iter() {
+            // Every add in the diff came from a student on the roster.
+            let s = students.iter().find(|s| s.base.uname == a.uname).unwrap();
+            match self.insert_user(actor, &User::Student(s.clone())).await {
+                Ok(()) => applied.added.push(a.uname.clone()),
+                Err(e) => applied.failed.push(fail(&a.uname, e)),
+            }
+        }
+
+        for m in d.moves.iter() {
+            let res = match self.users.get(&m.uname) {
+                Some(User::Student(s)) => {
+                    let mut s = s.clone();
+                    s.teacher = m.to.clone();
+                    self.update_user(actor, &User::Student(s)).await
                 }
-                n_reports += 1;
+                _ => Err(format!("{:?} is not a Student.", &m.uname).into()),
+            };
+            match res {
+                Ok(()) => applied.moved.push(m.uname.clone()),
+                Err(e) => applied.failed.push(fail(&m.uname, e)),
             }
         }
 
-        if n_reports == 0 {
-            Ok(None)
-        } else {
-            match zip.finish() {
-                Ok(cursor) => Ok(Some(cursor.into_inner())),
-                Err(e) => Err(format!("Error finalizing archive: {}", &e).into()),
+        if withdraw {
+            for w in d.withdrawals.iter() {
+                match self.delete_user(actor, &w.uname).await {
+                    Ok(()) => applied.withdrawn.push(w.uname.clone()),
+                    Err(e) => applied.failed.push(fail(&w.uname, e)),
+                }
             }
         }
+
+        self.roster.lock().unwrap().applied = Some(time::OffsetDateTime::now_utc().to_string());
+        log::info!(
+            "Applied roster: {} added, {} moved, {} withdrawn, {} failed.",
+            applied.added.len(),
+            applied.moved.len(),
+            applied.withdrawn.len(),
+            applied.failed.len()
+        );
+
+        Ok(applied)
     }
 
     pub async fn get_student_completion_history(
@@ -1267,6 +2679,18 @@ impl<'a> Glob {
         let mut client = self.data.read().await.connect().await?;
         let t = client.transaction().await?;
 
+        // Other schools' course identifiers are credited as the equivalent
+        // course of ours.
+        let course = match Store::resolve_course(&t, course).await? {
+            Some(sym) => sym,
+            None => {
+                return Err(format!(
+                    "{:?} is neither a course nor equivalent to one.", course
+                ).into());
+            },
+        };
+        let course = course.as_str();
+
         if let Some(row) = t.query_opt(
             "SELECT year, term FROM completion
             WHERE uname = $1 AND courses = $2",
@@ -1295,6 +2719,33 @@ impl<'a> Glob {
         Ok(())
     }
 
+    /**
+    Add the courses in the CSV `text` (see
+    [`TransferRecord::vec_from_csv_reader`]) to students' completion
+    histories, crediting other schools' courses as their equivalents.
+
+    Returns the number of courses added, and the number skipped because
+    they were already in the student's history.
+    */
+    pub async fn import_transfer_credit(
+        &self,
+        text: &str
+    ) -> Result<(usize, usize), UnifiedError> {
+        log::trace!("Glob::import_transfer_credit( [ {} bytes ] ) called.", text.len());
+
+        let records = TransferRecord::vec_from_csv_reader(text.as_bytes())?;
+        for rec in records.iter() {
+            if !matches!(self.users.get(&rec.uname), Some(User::Student(_))) {
+                return Err(format!(
+                    "{:?} is not a student in the database.", &rec.uname
+                ).into());
+            }
+        }
+
+        let counts = self.data().read().await.import_transfer_credit(&records).await?;
+        Ok(counts)
+    }
+
     pub async fn delete_completion(
         &self,
         uname: &str,
@@ -1331,29 +2782,87 @@ impl<'a> Glob {
         Ok(())
     }
 
+    /// Report what [`Glob::yearly_data_nuke`] would delete.
+    pub async fn yearly_nuke_preview(&self) -> Result<NukePreview, UnifiedError> {
+        log::trace!("Glob::yearly_nuke_preview() called.");
+
+        let preview = self.data.read().await.yearly_nuke_preview().await?;
+        Ok(preview)
+    }
+
     /**
     Delete all Student Goals, sidecar info, and report data (but _not_
     course completion data).
 
     This is meant to clear the database out between academic years. This
     does _not_ remove any Students from the database.
+
+    `token` must be the `token` from a [`Glob::yearly_nuke_preview`] of the
+    data as it currently stands. Everything is archived before it's deleted,
+    both in the database (see [`Store::archive_academic_year`]) and to a
+    file in the `archive_dir`; the path to that file is returned.
     */
-    pub async fn yearly_data_nuke(&self) -> Result<(), UnifiedError> {
-        log::trace!("Glob::yearly_data_nuke() called.");
-    
+    pub async fn yearly_data_nuke(&self, token: &str) -> Result<PathBuf, UnifiedError> {
+        log::trace!("Glob::yearly_data_nuke( {:?} ) called.", token);
+
         let data_arc = self.data();
         let data = data_arc.read().await;
+        data.check_environment().await?;
         let mut client = data.connect().await?;
         let t = client.transaction().await?;
-    
+
+        let preview = Store::yearly_nuke_preview_in(&t).await?;
+        if preview.token != token.trim() {
+            return Err(
+                "Confirmation token doesn't match the current data; preview the reset again."
+                    .to_owned()
+                    .into(),
+            );
+        }
+
+        let label = self.academic_year_string();
+        let (_, archive) = Store::archive_academic_year(&t, label.as_str()).await?;
+        std::fs::create_dir_all(&self.archive_dir).map_err(|e| {
+            format!(
+                "Unable to create archive directory {}: {}",
+                self.archive_dir.display(),
+                &e
+            )
+        })?;
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        // The year's label goes in the file name, so archives from different
+        // years are easy to tell apart.
+        let year: String = label
+            .as_str()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        let path = self
+            .archive_dir
+            .join(format!("pre-nuke-{}-{}-{}.json", &year, crate::now(), &secs));
+        std::fs::write(&path, archive)
+            .map_err(|e| format!("Unable to write archive {}: {}", path.display(), &e))?;
+        log::info!("Pre-nuke archive written to {}.", path.display());
+
         let _ = tokio::try_join!(
             Store::yearly_clear_sidecars(&t),
             Store::yearly_clear_goals(&t),
-        ).map_err(|e| format!(
-            "Error clearing yearly data from database: {}", &e
-        ))?;
-    
-        Ok(())
+            Store::yearly_clear_completion_notices(&t),
+            Store::yearly_clear_lag_history(&t),
+            Store::yearly_clear_waivers(&t),
+            Store::yearly_clear_absences(&t),
+            Store::yearly_clear_test_requests(&t),
+        )
+        .map_err(|e| format!("Error clearing yearly data from database: {}", &e))?;
+
+        t.commit()
+            .await
+            .map_err(|e| format!("Error committing yearly data deletion: {}", &e))?;
+
+        Ok(path)
     }
 
 /*     pub async fn yearly_data_nuke(&mut self) -> Result<(), UnifiedError> {
@@ -1421,30 +2930,101 @@ async fn insert_default_admin_into_auth_db(
     Ok(())
 }
 
+/**
+Passwords users choose for themselves must be at least `min_length`
+characters long, must not be all whitespace, and must not be (ignoring
+case) their user name.
+
+This doesn't apply to passwords generated for new users.
+*/
+pub fn check_password_policy(uname: &str, pwd: &str, min_length: usize) -> Result<(), String> {
+    if pwd.chars().count() < min_length {
+        return Err(format!(
+            "Passwords must be at least {} characters long.",
+            &min_length
+        ));
+    }
+    if pwd.trim().is_empty() {
+        return Err("Passwords can't be entirely whitespace.".to_owned());
+    }
+    if pwd.to_lowercase() == uname.to_lowercase() {
+        return Err("Your password can't be your user name.".to_owned());
+    }
+    Ok(())
+}
+
 /// Loads system configuration and ensures all appropriate database tables
 /// exist.
 ///
-/// Also assures existence of default admin.
+/// Also assures existence of default admin, and checks that the auth and
+/// data DBs agree on which users exist.
 pub async fn load_configuration<P: AsRef<Path>>(path: P) -> Result<Glob, UnifiedError> {
+    load_configuration_waiting(path, &SharedStatus::default()).await
+}
+
+/// Like [`load_configuration`], but keeping `status` up to date while
+/// waiting for the databases to come up (see [`startup`]).
+pub async fn load_configuration_waiting<P: AsRef<Path>>(
+    path: P,
+    status: &SharedStatus,
+) -> Result<Glob, UnifiedError> {
     let cfg = Cfg::from_file(path.as_ref())?;
     log::info!("Configuration file read:\n{:#?}", &cfg);
 
     log::trace!("Checking state of auth DB...");
-    let auth_db = auth::Db::new(cfg.auth_db_connect_string.clone());
+    let mut auth_db = auth::Db::new(cfg.auth_db_connect_string.clone());
+    auth_db.set_lockout_threshold(cfg.lockout_threshold);
+    auth_db.set_lockout_seconds(cfg.lockout_seconds);
+    auth_db.set_lockout_max_seconds(cfg.lockout_max_seconds);
+    auth_db.set_password_hashing(&cfg.password_hashing)?;
+    auth_db.set_environment(&cfg.environment);
+    auth_db.set_dummy_checks(cfg.dummy_auth_checks);
+    auth_db.set_key_cache_seconds(cfg.key_cache_seconds);
+    auth_db.set_pool_size(cfg.db_pool_size)?;
+    startup::wait_for("auth DB", &cfg.startup_retry, status, || async {
+        auth_db.connect().await.map(|_| ()).map_err(|e| e.to_string())
+    })
+    .await?;
     if let Err(e) = auth_db.ensure_db_schema().await {
         let estr = format!("Unable to ensure state of auth DB: {}", &e);
         return Err(estr.into());
     }
+    if let Err(e) = auth_db.claim_environment().await {
+        let estr = format!("Auth DB environment mismatch: {}", &e);
+        return Err(estr.into());
+    }
     log::trace!("...auth DB okay.");
     let n_old_keys = auth_db.cull_old_keys().await?;
     log::info!("Removed {} expired keys from Auth DB.", &n_old_keys);
 
     log::trace!("Checking state of data DB...");
-    let data_db = Store::new(cfg.data_db_connect_string.clone());
+    let mut data_db = Store::new(cfg.data_db_connect_string.clone());
+    data_db.set_environment(&cfg.environment);
+    data_db.set_pool_size(cfg.db_pool_size)?;
+    data_db.set_bulk_insert(cfg.db_insert_chunk, cfg.db_insert_concurrency);
+    startup::wait_for("data DB", &cfg.startup_retry, status, || async {
+        data_db.connect().await.map(|_| ()).map_err(|e| e.to_string())
+    })
+    .await?;
+    if let Err(e) = data_db.migrate_schema(cfg.auto_migrate).await {
+        let estr = format!("Data DB schema mismatch: {}", &e);
+        return Err(estr.into());
+    }
     if let Err(e) = data_db.ensure_db_schema().await {
         let estr = format!("Unable to ensure state of data DB: {}", &e);
         return Err(estr.into());
     }
+    if let Err(e) = data_db.claim_environment().await {
+        let estr = format!("Data DB environment mismatch: {}", &e);
+        return Err(estr.into());
+    }
+    let unsubscribe_secret = match data_db.get_unsubscribe_secret().await {
+        Ok(secret) => secret,
+        Err(e) => {
+            let estr = format!("Unable to read unsubscribe link secret: {}", &e);
+            return Err(estr.into());
+        }
+    };
     log::trace!("...data DB okay.");
 
     log::trace!("Checking existence of default Admin in data DB...");
@@ -1478,7 +3058,7 @@ pub async fn load_configuration<P: AsRef<Path>>(path: P) -> Result<Glob, Unified
 
     log::trace!("Checking existence of default Admin in auth DB...");
     match auth_db
-        .check_password(
+        .check_password_untracked(
             default_admin.uname(),
             &cfg.default_admin_password,
             default_admin.salt(),
@@ -1526,24 +3106,92 @@ pub async fn load_configuration<P: AsRef<Path>>(path: P) -> Result<Glob, Unified
     }
     log::trace!("Default Admin OK in auth DB.");
 
+    let renderer: Arc<dyn ReportRenderer> = match cfg.report_renderer.as_str() {
+        render::BUILTIN => Arc::new(BuiltinRenderer),
+        _ => Arc::new(PandocRenderer::new(
+            cfg.pandoc_uri,
+            cfg.pandoc_auth,
+            cfg.pandoc_format,
+        )),
+    };
+    log::info!("Rendering reports with {:?}.", renderer.name());
+
+    let mailer: Arc<dyn EmailSender> = match cfg.email_backend.as_str() {
+        email::SMTP => {
+            let credentials = cfg
+                .smtp_username
+                .map(|uname| (uname, cfg.smtp_password.unwrap_or_default()));
+            Arc::new(SmtpSender::new(
+                // `Cfg::from_file()` ensures this is set.
+                cfg.smtp_host.unwrap_or_default(),
+                cfg.smtp_port,
+                cfg.smtp_security,
+                credentials,
+            )?)
+        }
+        email::LOG => {
+            log::warn!("Emails will only be logged, not sent.");
+            Arc::new(LogSender)
+        }
+        _ => Arc::new(SendgridSender::new(
+            cfg.sendgrid_uri,
+            cfg.sendgrid_auth_string,
+        )),
+    };
+    log::info!(
+        "Sending email with {:?} ({}).",
+        mailer.name(),
+        &mailer.destination()
+    );
+
+    let data = Arc::new(RwLock::new(data_db));
     let mut glob = Glob {
         uri: cfg.uri,
         auth: Arc::new(RwLock::new(auth_db)),
-        data: Arc::new(RwLock::new(data_db)),
-        sendgrid_uri: cfg.sendgrid_uri,
-        sendgrid_auth: cfg.sendgrid_auth_string,
+        store: data.clone(),
+        data,
+        mailer,
+        email_health: Arc::new(email::Health::new()),
+        outbox: Some(Arc::new(email::Outbox::new())),
         dates: HashMap::new(),
-        calendar: Vec::new(),
+        calendar: AcademicCalendar::default(),
+        academic_years: Vec::new(),
         courses: HashMap::new(),
         course_syms: HashMap::new(),
+        customs: HashMap::new(),
+        course_token: String::new(),
         users: HashMap::new(),
+        role_index: HashMap::new(),
+        class_index: HashMap::new(),
+        training: HashSet::new(),
         addr: cfg.addr,
         pwd_chars: DEFAULT_PASSWORD_CHARS.chars().collect(),
-        pandoc_uri: cfg.pandoc_uri,
-        pandoc_auth: cfg.pandoc_auth,
         social_traits: cfg.social_traits,
-        pandoc_format: cfg.pandoc_format,
+        renderer,
+        weekly_digest_hour: cfg.weekly_digest_hour,
+        completion_email: cfg.completion_email,
+        student_scores: cfg.student_scores,
+        date_style: cfg.date_style,
+        deferred: Arc::new(deferred::Queue::new()),
+        failure_journal: cfg.failure_journal,
+        unsubscribe_secret,
+        auth_export_key: cfg.auth_export_key,
+        min_password_length: cfg.min_password_length,
+        archive_dir: cfg.archive_dir,
+        summary_labels: cfg.summary_labels,
+        failing_threshold: cfg.failing_threshold,
+        retention: cfg.retention,
+        prune_stats: std::sync::Mutex::new(PruneStats::default()),
+        roster_sync: cfg.roster_sync,
+        roster: std::sync::Mutex::new(RosterState::default()),
+        anonymized: std::sync::Mutex::new(HashSet::new()),
+        static_dir: cfg.static_dir.clone(),
+        rate_limiter: Arc::new(RateLimiter::new(cfg.rate_limit)),
+        confirmations: Arc::new(Confirmations::new()),
+        webhooks: Some(Arc::new(webhook::Outbox::new())),
+        downloads: Arc::new(Downloads::new(cfg.download_dir)),
     };
+    glob.downloads.clear()?;
 
     glob.refresh_courses().await?;
     log::info!("Retrieved {} courses from data DB.", glob.courses.len());
@@ -1551,6 +3199,23 @@ pub async fn load_configuration<P: AsRef<Path>>(path: P) -> Result<Glob, Unified
     glob.refresh_users().await?;
     log::info!("Retrieved {} users from data DB.", glob.users.len());
 
+    glob.training = glob.data.read().await.get_training().await?;
+    if !glob.training.is_empty() {
+        log::info!("Teachers in training mode: {:?}", &glob.training);
+    }
+
+    let drift = glob.reconcile_auth(cfg.create_missing_auth).await?;
+    if drift.missing_auth.is_empty() && drift.orphaned_auth.is_empty() {
+        log::info!("Auth DB and data DB users agree.");
+    } else {
+        log::warn!(
+            "{} users lack auth DB entries ({} created); {} auth DB entries lack users.",
+            drift.missing_auth.len(),
+            &drift.n_created,
+            drift.orphaned_auth.len()
+        );
+    }
+
     glob.refresh_calendar().await?;
     log::info!(
         "Retrieved {} instructional days from data DB.",
@@ -1561,7 +3226,93 @@ pub async fn load_configuration<P: AsRef<Path>>(path: P) -> Result<Glob, Unified
     log::info!("Retrieved {} special dates from data DB.", glob.dates.len());
     log::debug!("special dates:\n{:#?}\n", &glob.dates);
 
+    glob.refresh_academic_years().await?;
+    match glob.current_year() {
+        Some(y) => log::info!(
+            "Current academic year is {:?} ({} to {}).",
+            &y.label, &y.starts, &y.ends
+        ),
+        None => log::info!("No current academic year set; guessing from the calendar."),
+    }
+
     inter::init(&cfg.templates_dir)?;
+    inter::error_page::init(cfg.support_contact.clone());
+
+    // Not being able to fingerprint static files shouldn't keep the server
+    // from starting; they'll just be linked (and cached) unversioned.
+    if cfg.fingerprint_assets {
+        match inter::assets::init(&cfg.static_dir) {
+            Ok(n) => log::info!("Fingerprinted {} static files.", &n),
+            Err(e) => log::warn!("Unable to fingerprint static files: {}", &e),
+        }
+    }
+
+    Ok(glob)
+}
+
+/**
+Build a `Glob` with default configuration whose [`DataStore`] is `store`,
+for testing things that only need what a `DataStore` provides.
+
+Its handles to the actual databases point at the test databases, but
+nothing is checked or loaded from them. Completion emails are off, because
+sending them claims notices in the data DB.
+*/
+#[cfg(test)]
+pub async fn in_memory(store: crate::store::MemStore) -> Result<Glob, UnifiedError> {
+    let cfg = Cfg::default();
+    let mut glob = Glob {
+        uri: cfg.uri,
+        auth: Arc::new(RwLock::new(auth::Db::new(cfg.auth_db_connect_string))),
+        data: Arc::new(RwLock::new(Store::new(cfg.data_db_connect_string))),
+        store: Arc::new(store),
+        mailer: Arc::new(LogSender),
+        email_health: Arc::new(email::Health::new()),
+        outbox: None,
+        dates: HashMap::new(),
+        calendar: AcademicCalendar::default(),
+        academic_years: Vec::new(),
+        courses: HashMap::new(),
+        course_syms: HashMap::new(),
+        customs: HashMap::new(),
+        course_token: String::new(),
+        users: HashMap::new(),
+        role_index: HashMap::new(),
+        class_index: HashMap::new(),
+        training: HashSet::new(),
+        addr: cfg.addr,
+        pwd_chars: DEFAULT_PASSWORD_CHARS.chars().collect(),
+        social_traits: cfg.social_traits,
+        renderer: Arc::new(BuiltinRenderer),
+        weekly_digest_hour: cfg.weekly_digest_hour,
+        completion_email: CompletionEmail::Off,
+        student_scores: cfg.student_scores,
+        date_style: cfg.date_style,
+        deferred: Arc::new(deferred::Queue::new()),
+        failure_journal: cfg.failure_journal,
+        unsubscribe_secret: String::new(),
+        auth_export_key: None,
+        min_password_length: cfg.min_password_length,
+        archive_dir: cfg.archive_dir,
+        summary_labels: cfg.summary_labels,
+        failing_threshold: cfg.failing_threshold,
+        retention: cfg.retention,
+        prune_stats: std::sync::Mutex::new(PruneStats::default()),
+        roster_sync: cfg.roster_sync,
+        roster: std::sync::Mutex::new(RosterState::default()),
+        anonymized: std::sync::Mutex::new(HashSet::new()),
+        static_dir: cfg.static_dir,
+        rate_limiter: Arc::new(RateLimiter::new(cfg.rate_limit)),
+        confirmations: Arc::new(Confirmations::new()),
+        webhooks: None,
+        downloads: Arc::new(Downloads::new(cfg.download_dir)),
+    };
+
+    glob.refresh_courses().await?;
+    glob.refresh_users().await?;
+    glob.refresh_calendar().await?;
+    glob.refresh_dates().await?;
+    glob.refresh_academic_years().await?;
 
     Ok(glob)
 }
@@ -1615,4 +3366,377 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn name_problems() {
+        use config::{email_problem, uname_problem};
+
+        assert_eq!(uname_problem("jsmith2"), None);
+        assert!(uname_problem("").is_some());
+        assert!(uname_problem("j.smith").is_some());
+        assert!(uname_problem("jsmíth").is_some());
+
+        assert_eq!(email_problem("jsmith@camelotacademy.org"), None);
+        assert!(email_problem("jsmith").is_some());
+        assert!(email_problem("@camelotacademy.org").is_some());
+        assert!(email_problem("jsmith@").is_some());
+        assert!(email_problem("j@smith@camelotacademy.org").is_some());
+        assert!(email_problem("j smith@camelotacademy.org").is_some());
+        assert!(email_problem("<jsmith@camelotacademy.org>").is_some());
+    }
+
+    #[tokio::test]
+    async fn user_indices() {
+        use crate::store::MemStore;
+        use crate::user::{BaseUser, Role};
+
+        let base = |uname: &str, role: Role| BaseUser {
+            uname: uname.to_owned(),
+            role,
+            salt: String::new(),
+            email: format!("{}@camelotacademy.org", uname),
+        };
+        let student = |uname: &str, tuname: &str| {
+            base(uname, Role::Student).into_student(
+                uname.to_owned(),
+                uname.to_owned(),
+                tuname.to_owned(),
+                String::new(),
+                None,
+                None,
+                0.2,
+                0.2,
+                0,
+                0,
+            )
+        };
+        let users = [
+            base("boss", Role::Boss).into_boss(),
+            base("berro", Role::Teacher).into_teacher("Mr Berro".to_owned()),
+            base("adams", Role::Teacher).into_teacher("Ms Adams".to_owned()),
+            student("toad", "berro"),
+            student("frog", "berro"),
+            student("newt", "adams"),
+        ];
+        let glob = config::in_memory(MemStore::new().with_users(users))
+            .await
+            .unwrap();
+
+        assert_eq!(glob.unames_with_role(Role::Teacher), ["adams", "berro"]);
+        assert_eq!(glob.unames_with_role(Role::Student).len(), 3);
+        assert!(glob.unames_with_role(Role::Admin).is_empty());
+        let bosses: Vec<&str> = glob.users_with_role(Role::Boss).map(|u| u.uname()).collect();
+        assert_eq!(bosses, ["boss"]);
+
+        let studs: Vec<&str> = glob
+            .get_students_by_teacher("berro")
+            .iter()
+            .map(|u| u.uname())
+            .collect();
+        assert_eq!(studs, ["frog", "toad"]);
+        assert!(glob.get_students_by_teacher("boss").is_empty());
+    }
+
+    #[tokio::test]
+    async fn relevel_preview() {
+        use crate::course::Course;
+        use crate::pace::{BookCh, Goal, Source};
+        use crate::store::MemStore;
+        use crate::user::{BaseUser, Role};
+        use time::macros::date;
+
+        let base = |uname: &str, role: Role| BaseUser {
+            uname: uname.to_owned(),
+            role,
+            salt: String::new(),
+            email: format!("{}@camelotacademy.org", uname),
+        };
+        let student = |uname: &str, tuname: &str| {
+            base(uname, Role::Student).into_student(
+                uname.to_owned(),
+                uname.to_owned(),
+                tuname.to_owned(),
+                String::new(),
+                None,
+                None,
+                0.2,
+                0.2,
+                0,
+                0,
+            )
+        };
+        let goal = |id: i64, uname: &str, sym: &str, due: Option<Date>| Goal {
+            id,
+            uname: uname.to_owned(),
+            source: Source::Book(BookCh {
+                sym: sym.to_owned(),
+                seq: 1,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due,
+            done: None,
+            tries: None,
+            weight: 0.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        };
+        let course = |n: usize| {
+            Course::from_reader(std::fs::File::open(format!("test/good_course_{}.mix", n)).unwrap())
+                .unwrap()
+        };
+
+        let users = [
+            base("berro", Role::Teacher).into_teacher("Mr Berro".to_owned()),
+            base("adams", Role::Teacher).into_teacher("Ms Adams".to_owned()),
+            student("frog", "berro"),
+            student("toad", "berro"),
+            student("newt", "adams"),
+        ];
+        let goals = [
+            goal(1, "frog", "dgh", None),
+            goal(2, "frog", "pc", None),
+            goal(3, "toad", "dgh", None),
+            goal(4, "newt", "dgh", Some(date!(2022 - 09 - 06))),
+            goal(5, "newt", "pc", Some(date!(2022 - 09 - 07))),
+        ];
+        // pc is 12.1; dgh is 10.6.
+        let store = MemStore::new()
+            .with_users(users)
+            .with_courses([course(0), course(2)])
+            .with_goals(&goals)
+            .unwrap();
+        let glob = config::in_memory(store).await.unwrap();
+
+        let levels = |pairs: &[(&str, f32)]| -> Vec<(String, f32)> {
+            pairs.iter().map(|(s, x)| (s.to_string(), *x)).collect()
+        };
+
+        let same = glob.preview_relevel(&levels(&[("pc", 12.1)])).await.unwrap();
+        assert_eq!(same, config::RelevelPreview::default());
+        assert!(glob.preview_relevel(&levels(&[("nope", 1.0)])).await.is_err());
+
+        // Moving pc ahead of dgh only reorders frog's undated goals.
+        let preview = glob
+            .preview_relevel(&levels(&[("pc", 10.0), ("dgh", 10.6)]))
+            .await
+            .unwrap();
+        assert_eq!(preview.changes.len(), 1);
+        assert_eq!((preview.changes[0].old, preview.changes[0].new), (12.1, 10.0));
+        assert!(preview.ties.is_empty());
+        let moved: Vec<(&str, usize)> = preview
+            .students
+            .iter()
+            .map(|s| (s.uname.as_str(), s.n_moved))
+            .collect();
+        assert_eq!(moved, [("frog", 2)]);
+
+        let tied = glob.preview_relevel(&levels(&[("pc", 10.6)])).await.unwrap();
+        assert_eq!(tied.ties, vec![vec!["dgh".to_owned(), "pc".to_owned()]]);
+    }
+
+    #[tokio::test]
+    async fn recompute_weights() {
+        use crate::course::Course;
+        use crate::pace::{BookCh, Goal, Source};
+        use crate::store::MemStore;
+        use crate::user::{BaseUser, Role};
+
+        let base = |uname: &str, role: Role| BaseUser {
+            uname: uname.to_owned(),
+            role,
+            salt: String::new(),
+            email: format!("{}@camelotacademy.org", uname),
+        };
+        let frog = base("frog", Role::Student).into_student(
+            "Frog".to_owned(),
+            "Frederick".to_owned(),
+            "berro".to_owned(),
+            String::new(),
+            None,
+            None,
+            0.2,
+            0.2,
+            0,
+            0,
+        );
+        let goal = |seq: i16| Goal {
+            id: 0,
+            uname: "frog".to_owned(),
+            source: Source::Book(BookCh {
+                sym: "pc".to_owned(),
+                seq,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due: None,
+            done: None,
+            tries: None,
+            weight: 0.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        };
+        let crs =
+            Course::from_reader(std::fs::File::open("test/good_course_0.mix").unwrap()).unwrap();
+        let store = MemStore::new()
+            .with_users([base("berro", Role::Teacher).into_teacher("Mr Berro".to_owned()), frog])
+            .with_courses([crs])
+            .with_goals(&[goal(1), goal(2)])
+            .unwrap();
+        let mut glob = config::in_memory(store).await.unwrap();
+        let current: Vec<f32> = glob
+            .get_pace_by_student("frog")
+            .await
+            .unwrap()
+            .goals
+            .iter()
+            .map(|g| g.weight)
+            .collect();
+
+        // Nothing has changed yet.
+        let unames = ["frog".to_owned()];
+        assert!(glob.recompute_weights(&unames, None).await.unwrap().is_empty());
+
+        // Pretend chapter 1's weight was doubled since the courses were
+        // loaded; everything in the course gets reweighted.
+        let id = *glob.course_syms.get("pc").unwrap();
+        let stale = glob.courses.remove(&id).unwrap();
+        let chapters = stale
+            .all_chapters()
+            .map(|ch| {
+                let mut ch = ch.clone();
+                if ch.seq == 1 {
+                    ch.weight *= 2.0;
+                }
+                ch
+            })
+            .collect();
+        glob.courses.insert(id, stale.with_chapters(chapters));
+        let before: Vec<f32> = glob
+            .get_pace_by_student("frog")
+            .await
+            .unwrap()
+            .goals
+            .iter()
+            .map(|g| g.weight)
+            .collect();
+        assert!(before.iter().zip(current.iter()).all(|(a, b)| (a - b).abs() > 0.0001));
+
+        let deltas = glob.recompute_weights(&unames, Some("pc")).await.unwrap();
+        let changes: Vec<(i16, Option<f32>, f32)> =
+            deltas.iter().map(|d| (d.seq, d.old, d.new)).collect();
+        assert_eq!(
+            changes,
+            [(1, Some(before[0]), current[0]), (2, Some(before[1]), current[1])]
+        );
+        assert!(glob.recompute_weights(&unames, Some("pc")).await.unwrap().is_empty());
+        assert!(glob.recompute_weights(&unames, Some("nope")).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn academic_year_metadata() {
+        use crate::store::{AcademicYear, MemStore};
+        use time::macros::date;
+
+        let store =
+            MemStore::new().with_calendar(vec![date!(2022 - 09 - 06), date!(2022 - 09 - 07)]);
+        let glob = config::in_memory(store).await.unwrap();
+        assert!(glob.current_year().is_none());
+        assert_eq!(glob.academic_year(), 2022);
+        assert_eq!(glob.academic_year_string().as_str(), "2022--2023");
+
+        // Explicit metadata beats guessing from the calendar.
+        let years = vec![
+            AcademicYear {
+                label: "SY 2021".to_owned(),
+                starts: date!(2021 - 08 - 30),
+                ends: date!(2022 - 06 - 03),
+                active: false,
+            },
+            AcademicYear {
+                label: "SY 2022".to_owned(),
+                starts: date!(2022 - 08 - 29),
+                ends: date!(2023 - 06 - 02),
+                active: true,
+            },
+        ];
+        let store = MemStore::new()
+            .with_calendar(vec![date!(2023 - 01 - 03)])
+            .with_academic_years(years);
+        let glob = config::in_memory(store).await.unwrap();
+        assert_eq!(glob.current_year().unwrap().label, "SY 2022");
+        assert_eq!(glob.academic_year(), 2022);
+        assert_eq!(glob.academic_year_string().as_str(), "SY 2022");
+    }
+
+    #[test]
+    fn password_policy() {
+        use config::check_password_policy;
+
+        assert!(check_password_policy("jsmith", "correct horse", 8).is_ok());
+        assert!(check_password_policy("jsmith", "short", 8).is_err());
+        assert!(check_password_policy("jsmith", "ñandúñandú", 10).is_ok());
+        assert!(check_password_policy("jsmith", "          ", 8).is_err());
+        assert!(check_password_policy("jsmith", "JSmith", 4).is_err());
+    }
+
+    #[test]
+    fn runtime_config() {
+        use config::{RuntimeCfg, RuntimeFlavor};
+
+        let rc: RuntimeCfg = toml::from_str("uri = \"http://localhost\"").unwrap();
+        assert_eq!(rc.runtime, RuntimeFlavor::default());
+
+        let rc: RuntimeCfg =
+            toml::from_str("runtime = \"multi-thread\"\nworker_threads = 2").unwrap();
+        assert_eq!(rc.runtime, RuntimeFlavor::MultiThread);
+        let rt = rc.build().unwrap();
+        assert_eq!(rt.block_on(async { 2 + 2 }), 4);
+
+        let rc: RuntimeCfg = toml::from_str("runtime = \"current-thread\"").unwrap();
+        assert_eq!(rc.runtime, RuntimeFlavor::CurrentThread);
+        assert!(toml::from_str::<RuntimeCfg>("runtime = \"fast\"").is_err());
+    }
+
+    #[test]
+    fn email_config() {
+        use config::Cfg;
+        use email::SmtpSecurity;
+
+        let path = std::env::temp_dir().join("camp_email_config_test.toml");
+        let read = |text: &str| {
+            std::fs::write(&path, text).unwrap();
+            Cfg::from_file(&path)
+        };
+
+        let c = read("sendgrid_auth_string = \"Bearer whatever\"").unwrap();
+        assert_eq!(c.email_backend, email::SENDGRID);
+
+        let c = read("email_backend = \"smtp\"\nsmtp_host = \"mail.example.com\"").unwrap();
+        assert_eq!(c.email_backend, email::SMTP);
+        assert_eq!(c.smtp_security, SmtpSecurity::Starttls);
+        let c = read(
+            "email_backend = \"smtp\"\nsmtp_host = \"localhost\"\nsmtp_security = \"none\"",
+        )
+        .unwrap();
+        assert_eq!(c.smtp_security, SmtpSecurity::None);
+
+        assert!(read("email_backend = \"smtp\"").is_err());
+        assert!(read("email_backend = \"carrier-pigeon\"").is_err());
+        assert_eq!(read("email_backend = \"log\"").unwrap().email_backend, email::LOG);
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -8,6 +8,9 @@ title = "Core Precalculus"
 sym = "pc"
 book = "Precalculus: Functions and Graphs"
 level = 12.1
+# Optional scheduling constraints (see `Constraints`).
+min_gap = 5
+max_per_week = 2
 
 # Last three columns are optional.
 # Weights will default to 1.0, titles will default to "Chapter N", and
@@ -147,7 +150,9 @@ fn read_toml_csv_split<R: Read>(r: R) -> Result<(usize, String, usize, String),
 /// Represents the material covered by a "custom" [`Goal`](crate::pace::Goal)
 /// not represented by a Chapter in any current Courses in the database.
 ///
-/// This is currently not supported, but may be in the future.
+/// Teachers create these for one-off assignments; see the Teacher API's
+/// `add-custom-chapter` action.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Custom {
     /// Database primary key.
     pub id: i64,
@@ -248,6 +253,50 @@ struct CourseHeader {
     sym: String,
     book: String,
     level: f32,
+    min_gap: Option<i16>,
+    max_per_week: Option<i16>,
+}
+
+/**
+Optional limits on how a course's chapters can be scheduled (because, say,
+students need time to study between chapter tests).
+
+These are checked (see
+[`Pace::constraint_violations`](crate::pace::Pace::constraint_violations))
+when a Teacher sets due dates, either by hand or by autopacing.
+*/
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Constraints {
+    /// The fewest instructional days allowed between consecutive chapters'
+    /// due dates.
+    pub min_gap: Option<i16>,
+    /// The most chapters that can be due in any one (Monday-to-Sunday) week.
+    pub max_per_week: Option<i16>,
+}
+
+impl Constraints {
+    /// Whether there are no constraints at all.
+    pub fn is_empty(&self) -> bool {
+        self.min_gap.is_none() && self.max_per_week.is_none()
+    }
+
+    /// Ensure the constraints that are set are sensible (that is, positive).
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(n) = self.min_gap {
+            if n < 1 {
+                return Err(format!("Minimum gap must be at least 1 day (not {}).", n));
+            }
+        }
+        if let Some(n) = self.max_per_week {
+            if n < 1 {
+                return Err(format!(
+                    "Maximum chapters per week must be at least 1 (not {}).",
+                    n
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 /**
@@ -255,7 +304,7 @@ A `Course` represents the requirements for a single academic year-long course
 of Mathematics. This is almost universally some chunk of chapters (or partial
 chapters) from a single textbook.
 */
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Course {
     pub id: i64,
     pub sym: String,
@@ -263,6 +312,8 @@ pub struct Course {
     pub title: String,
     pub level: f32,
     pub weight: Option<f32>,
+    #[serde(default)]
+    pub constraints: Constraints,
     chapters: Vec<Chapter>,
 }
 
@@ -347,6 +398,11 @@ impl Course {
             return Err("Course file contains no chapters.".to_owned());
         }
         let weight = Some(chapters.iter().map(|ch| ch.weight).sum());
+        let constraints = Constraints {
+            min_gap: head.min_gap,
+            max_per_week: head.max_per_week,
+        };
+        constraints.check()?;
 
         let c = Course {
             id: 0,
@@ -355,6 +411,7 @@ impl Course {
             title: head.title,
             level: head.level,
             weight,
+            constraints,
             chapters,
         };
         Ok(c)
@@ -368,10 +425,19 @@ impl Course {
             title,
             level,
             weight: None,
+            constraints: Constraints::default(),
             chapters: Vec::new(),
         }
     }
 
+    /// Builder-pattern method to set the course's scheduling `Constraints`.
+    pub fn with_constraints(self, constraints: Constraints) -> Self {
+        Self {
+            constraints,
+            ..self
+        }
+    }
+
     /// Builder-pattern method to add `Chapter`s after the fact.
     pub fn with_chapters(self, chapters: Vec<Chapter>) -> Self {
         let mut new = self;
@@ -385,12 +451,7 @@ impl Course {
         // Right now this is a linear search. This may change in the future
         // if the data structure holding `Chapter`s becomes something other
         // than a `Vec`, but I'm not too woried about performance here.
-        for ch in (&self.chapters).iter() {
-            if ch.seq == n {
-                return Some(ch);
-            }
-        }
-        None
+        self.chapters.iter().find(|ch| ch.seq == n)
     }
 
     /// Return an iterator over all the `&Chapter`s.
@@ -399,6 +460,89 @@ impl Course {
     }
 }
 
+/**
+A short token identifying the state of a course catalog, for clients to
+tell whether the course data they loaded is still current.
+
+The token changes when a course or chapter is added, removed, renumbered,
+or reweighted (anything that would change the weights of students'
+`Goal`s), or a course is re-leveled (which can change the order of
+students' `Goal`s), but not when only titles or other display information
+change.
+*/
+pub fn catalog_token<'a, I>(courses: I) -> String
+where
+    I: IntoIterator<Item = &'a Course>,
+{
+    let mut courses: Vec<&Course> = courses.into_iter().collect();
+    courses.sort_by_key(|crs| crs.id);
+
+    let mut hasher = blake3::Hasher::new();
+    for crs in courses.iter() {
+        hasher.update(
+            format!("{}:{}:{:?}:{};", &crs.id, &crs.sym, &crs.weight, &crs.level).as_bytes(),
+        );
+        for ch in crs.chapters.iter() {
+            hasher.update(format!("{}:{}:{};", &ch.id, &ch.seq, &ch.weight).as_bytes());
+        }
+    }
+    hasher.finalize().to_hex()[..16].to_owned()
+}
+
+/**
+Read a file of new course levels, one course per line, like
+
+```text
+#sym, level
+pc,   12.1
+phg,  10.6
+```
+
+Returns `(sym, level)` pairs in the order they appear. Blank lines and lines
+starting with `#` are ignored.
+*/
+pub fn levels_from_csv<R: Read>(r: R) -> Result<Vec<(String, f32)>, String> {
+    log::trace!("levels_from_csv(...) called.");
+
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .has_headers(false)
+        .from_reader(r);
+
+    let mut levels: Vec<(String, f32)> = Vec::new();
+    for (n, res) in csv_reader.records().enumerate() {
+        let record = res.map_err(|e| match e.position() {
+            Some(p) => format!("Error on line {}: {}", p.line(), &e),
+            None => format!("Error in CSV record {}: {}", &n, &e),
+        })?;
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        let line = record.position().map(|p| p.line()).unwrap_or(n as u64);
+
+        let (sym, level) = match (record.get(0), record.get(1), record.len()) {
+            (Some(sym), Some(level), 2) => (sym, level),
+            _ => {
+                return Err(format!("Line {} should be a course symbol and a level.", &line));
+            }
+        };
+        let level: f32 = match level.parse() {
+            Ok(x) if f32::is_finite(x) => x,
+            _ => {
+                return Err(format!("Line {}: {:?} is not a valid level.", &line, level));
+            }
+        };
+        if levels.iter().any(|(s, _)| s == sym) {
+            return Err(format!("Line {}: course {:?} appears more than once.", &line, sym));
+        }
+        levels.push((sym.to_owned(), level));
+    }
+
+    Ok(levels)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -457,6 +601,53 @@ mod tests {
         assert_eq!(chapt, format!("{:#?}", crs.chapter(4).unwrap()));
     }
 
+    #[test]
+    fn catalog_tokens() {
+        let open = |n: usize| {
+            let f = fs::File::open(format!("test/good_course_{}.mix", n)).unwrap();
+            let mut crs = Course::from_reader(f).unwrap();
+            crs.id = n as i64;
+            crs
+        };
+        let (a, b) = (open(0), open(1));
+        let token = catalog_token([&a, &b]);
+        assert_eq!(token.len(), 16);
+        assert_eq!(catalog_token([&b, &a]), token);
+
+        let mut retitled = a.clone();
+        retitled.title = "Something Else".to_owned();
+        assert_eq!(catalog_token([&retitled, &b]), token);
+
+        let mut chapters = a.chapters.clone();
+        chapters[0].weight += 1.0;
+        let reweighted = a.clone().with_chapters(chapters);
+        assert_ne!(catalog_token([&reweighted, &b]), token);
+        assert_ne!(catalog_token([&a]), token);
+
+        let mut releveled = a.clone();
+        releveled.level += 0.1;
+        assert_ne!(catalog_token([&releveled, &b]), token);
+    }
+
+    #[test]
+    fn read_levels() {
+        let text = "#sym, level
+        pc, 12.1
+
+        phg,10.6
+        ";
+        assert_eq!(
+            levels_from_csv(text.as_bytes()).unwrap(),
+            vec![("pc".to_owned(), 12.1), ("phg".to_owned(), 10.6)]
+        );
+
+        assert!(levels_from_csv("pc, twelve".as_bytes()).is_err());
+        assert!(levels_from_csv("pc, NaN".as_bytes()).is_err());
+        assert!(levels_from_csv("pc, 12.1, 3".as_bytes()).is_err());
+        let e = levels_from_csv("pc, 12.1\nphg, 10.6\npc, 12.2".as_bytes()).unwrap_err();
+        assert!(e.contains("line 3") || e.contains("Line 3"));
+    }
+
     #[test]
     fn make_course_serialized() {
         use serde_json::to_writer_pretty;
@@ -0,0 +1,190 @@
+/*!
+Generated files kept on disk for a little while, so big downloads can be
+resumed.
+
+Boss report archives can be large, and downloads over school Wi-Fi fail
+partway through often enough that starting over is a real problem. So
+instead of streaming the archive in the response to the API request, the
+server writes it to a file in the `download_dir`, and responds with a link
+(`/archive/{token}`) that serves that file with support for `Range`
+requests (see [`boss::serve_archive`](crate::inter::boss::serve_archive)),
+which browsers use to pick up interrupted downloads where they left off.
+
+The token in the link is the only thing protecting the file, so it's long
+and random, and the file only lasts [`TTL`]. Tokens are only kept in
+memory; the directory is cleared when the server starts (see
+[`Downloads::clear`]), and expired files are deleted whenever a new one is
+made.
+*/
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use rand::{distributions::Alphanumeric, Rng};
+
+/// How long a download is available.
+pub const TTL: Duration = Duration::from_secs(60 * 60);
+const TOKEN_LENGTH: usize = 32;
+
+#[derive(Debug)]
+struct Download {
+    path: PathBuf,
+    /// What the file should be called when it's downloaded.
+    filename: String,
+    expires: SystemTime,
+}
+
+/// The files available for download; see the module-level documentation.
+#[derive(Debug)]
+pub struct Downloads {
+    dir: PathBuf,
+    files: Mutex<HashMap<String, Download>>,
+}
+
+impl Downloads {
+    /// Keep downloads in `dir`. Nothing is done to the directory until
+    /// it's needed.
+    pub fn new(dir: PathBuf) -> Downloads {
+        Downloads {
+            dir,
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Delete everything in the directory, like the files left from before
+    /// a restart (whose tokens have been forgotten).
+    pub fn clear(&self) -> Result<(), String> {
+        self.files.lock().unwrap().clear();
+        match std::fs::remove_dir_all(&self.dir) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!(
+                "Unable to clear download directory {}: {}",
+                self.dir.display(),
+                &e
+            )),
+        }
+    }
+
+    /**
+    Pick a new token, and return it along with the path where its file
+    should be written, which has the same `extension` as the file that will
+    be downloaded. The file isn't available until it's
+    [`publish`](Downloads::publish)ed.
+
+    Expired downloads are deleted first.
+    */
+    pub async fn reserve(&self, extension: &str) -> Result<(String, PathBuf), String> {
+        self.purge().await;
+        tokio::fs::create_dir_all(&self.dir).await.map_err(|e| {
+            format!(
+                "Unable to create download directory {}: {}",
+                self.dir.display(),
+                &e
+            )
+        })?;
+
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        let path = self.dir.join(format!("{}.{}", &token, extension));
+        Ok((token, path))
+    }
+
+    /// Make the (completely written) file at `path` available through
+    /// `token` for the next [`TTL`], to be saved as `filename`.
+    pub fn publish(&self, token: String, path: PathBuf, filename: String) {
+        self.publish_for(token, path, filename, TTL);
+    }
+
+    fn publish_for(&self, token: String, path: PathBuf, filename: String, ttl: Duration) {
+        let dl = Download {
+            path,
+            filename,
+            expires: SystemTime::now() + ttl,
+        };
+        self.files.lock().unwrap().insert(token, dl);
+    }
+
+    /// The path of the file for `token`, and what it should be saved as, if
+    /// it hasn't expired.
+    pub fn get(&self, token: &str) -> Option<(PathBuf, String)> {
+        let files = self.files.lock().unwrap();
+        match files.get(token) {
+            Some(dl) if dl.expires > SystemTime::now() => {
+                Some((dl.path.clone(), dl.filename.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Forget and delete the expired downloads.
+    async fn purge(&self) {
+        let now = SystemTime::now();
+        let expired: Vec<PathBuf> = {
+            let mut files = self.files.lock().unwrap();
+            let mut expired = Vec::new();
+            files.retain(|_, dl| {
+                if dl.expires > now {
+                    true
+                } else {
+                    expired.push(dl.path.clone());
+                    false
+                }
+            });
+            expired
+        };
+
+        for path in expired.iter() {
+            remove(path).await;
+        }
+    }
+}
+
+/// Delete the file at `path`, logging (but otherwise ignoring) any error.
+pub async fn remove(path: &Path) {
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        log::error!("Unable to delete download {}: {}", path.display(), &e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn downloads() {
+        let dir = std::env::temp_dir().join("camp_test_downloads");
+        let dls = Downloads::new(dir.clone());
+        dls.clear().unwrap();
+
+        let (token, path) = dls.reserve("zip").await.unwrap();
+        assert_eq!(token.len(), TOKEN_LENGTH);
+        assert!(path.starts_with(&dir));
+        assert_eq!(path.extension().unwrap(), "zip");
+        // Not until it's published.
+        assert!(dls.get(&token).is_none());
+        std::fs::write(&path, b"PK").unwrap();
+        dls.publish(token.clone(), path.clone(), "berro_fall.zip".to_owned());
+        assert_eq!(dls.get(&token), Some((path.clone(), "berro_fall.zip".to_owned())));
+        assert!(dls.get("nope").is_none());
+
+        // Expired downloads disappear, files and all.
+        let (old, old_path) = dls.reserve("zip").await.unwrap();
+        std::fs::write(&old_path, b"PK").unwrap();
+        dls.publish_for(old.clone(), old_path.clone(), "old.zip".to_owned(), Duration::ZERO);
+        assert!(dls.get(&old).is_none());
+        dls.reserve("zip").await.unwrap();
+        assert!(!old_path.exists());
+        assert!(path.exists());
+
+        dls.clear().unwrap();
+        assert!(!dir.exists());
+        assert!(dls.get(&token).is_none());
+    }
+}
@@ -0,0 +1,702 @@
+/*!
+Sending email.
+
+The emails the system sends (password resets, parent progress emails,
+teachers' weekly digests, completion notices) are rendered from the JSON
+templates into [`Email`]s, and an [`EmailSender`] sends them. Which sender
+gets used is selected by the `email_backend` option in the configuration
+file:
+
+  * `"sendgrid"` (the default) POSTs each email to the
+    [Sendgrid](https://sendgrid.com/) API at `sendgrid_uri`, authorized with
+    `sendgrid_auth_string`.
+  * `"smtp"` sends each email through the SMTP server at `smtp_host` (see
+    [`SmtpSender`] for the other options).
+  * `"log"` doesn't send anything; it just logs each email. This is for
+    development and testing.
+
+Whichever sender is used, every send goes through [`Health::send`], which
+keeps track of how sending has been going, so the Boss can find out email
+is broken before the parents do.
+
+Parent emails, password resets, and completion notices aren't sent while
+the request that triggered them waits; they're queued in the data DB's
+`outbox` table (see [`Store::queue_email`](crate::store::Store::queue_email)),
+and the [`Outbox`] sends them in the background, retrying failures with
+exponential backoff (see [`retry_delay`]). Emails that still haven't gone
+through after [`MAX_ATTEMPTS`] are kept for Admins to look at (the
+`failed-emails` action).
+*/
+use std::{
+    collections::{BTreeMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lettre::{
+    message::{
+        header::{ContentType, HeaderName, HeaderValue},
+        Mailbox, Message,
+    },
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, RwLock};
+
+use crate::{
+    config::Glob,
+    store::{QueuedEmail, Store},
+};
+
+/// Value of the `email_backend` config option that selects [`SendgridSender`].
+pub const SENDGRID: &str = "sendgrid";
+/// Value of the `email_backend` config option that selects [`SmtpSender`].
+pub const SMTP: &str = "smtp";
+/// Value of the `email_backend` config option that selects [`LogSender`].
+pub const LOG: &str = "log";
+
+/// How long to wait on an SMTP server before giving up on an email.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many of the most recent failures [`Health`] remembers.
+const RECENT_FAILURES: usize = 10;
+
+/// How many times the [`Outbox`] tries to send an email before giving up.
+pub const MAX_ATTEMPTS: i16 = 8;
+/// How long (in seconds) the [`Outbox`] waits before the first retry; each
+/// retry after that waits twice as long as the one before.
+const FIRST_RETRY: u64 = 60;
+/// Most emails the [`Outbox`] takes from the queue at once.
+const BATCH: i64 = 20;
+/// How often the [`Outbox`] looks for retries that have come due, when
+/// nothing wakes it sooner.
+const POLL: Duration = Duration::from_secs(30);
+
+/// An email address and the name that goes with it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Address {
+    pub email: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+impl Address {
+    /// The [`Mailbox`]es this stands for. Parent email fields sometimes have
+    /// more than one address, separated by commas or semicolons; each
+    /// address gets the same name.
+    fn mailboxes(&self) -> Result<Vec<Mailbox>, String> {
+        let name = match self.name.trim() {
+            "" => None,
+            name => Some(name.to_owned()),
+        };
+        let boxes = self
+            .email
+            .split([',', ';'])
+            .map(str::trim)
+            .filter(|addr| !addr.is_empty())
+            .map(|addr| {
+                addr.parse()
+                    .map(|addr| Mailbox::new(name.clone(), addr))
+                    .map_err(|e| format!("Invalid email address {:?}: {}", addr, &e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if boxes.is_empty() {
+            return Err(format!("No email address for {:?}.", &self.name));
+        }
+        Ok(boxes)
+    }
+}
+
+/// An email, as rendered from one of the JSON email templates.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Email {
+    pub to: Vec<Address>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cc: Vec<Address>,
+    pub from: Address,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<Address>,
+    pub subject: String,
+    /// Extra headers (like `List-Unsubscribe`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+impl Email {
+    /// Read an `Email` from a rendered JSON email template.
+    pub fn from_json(json: &str) -> Result<Email, String> {
+        serde_json::from_str(json).map_err(|e| format!("Error reading rendered email: {}", &e))
+    }
+
+    /// Everyone this is going to, for logging.
+    pub fn recipients(&self) -> String {
+        let addrs: Vec<&str> = self
+            .to
+            .iter()
+            .chain(self.cc.iter())
+            .map(|a| a.email.as_str())
+            .collect();
+        addrs.join(", ")
+    }
+
+    /// Build the message to send through an SMTP server.
+    fn message(&self) -> Result<Message, String> {
+        let mut builder = Message::builder()
+            .from(self.from.mailboxes()?.remove(0))
+            .subject(self.subject.as_str());
+        for a in self.to.iter() {
+            for mbox in a.mailboxes()? {
+                builder = builder.to(mbox);
+            }
+        }
+        for a in self.cc.iter() {
+            for mbox in a.mailboxes()? {
+                builder = builder.cc(mbox);
+            }
+        }
+        if let Some(a) = &self.reply_to {
+            builder = builder.reply_to(a.mailboxes()?.remove(0));
+        }
+        for (name, value) in self.headers.iter() {
+            let name = HeaderName::new_from_ascii(name.clone())
+                .map_err(|e| format!("Invalid email header name {:?}: {}", name, &e))?;
+            builder = builder.raw_header(HeaderValue::new(name, value.clone()));
+        }
+
+        builder
+            .header(ContentType::TEXT_PLAIN)
+            .body(self.body.clone())
+            .map_err(|e| format!("Error building email: {}", &e))
+    }
+}
+
+/// Boxed future returned by [`EmailSender::send`].
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = Result<(), String>> + Send + 'a>>;
+
+/// Something that can send an [`Email`].
+pub trait EmailSender: Send + Sync {
+    /// The name by which this sender is selected in the config file.
+    fn name(&self) -> &'static str;
+
+    /// Where this sends email, for the support bundle. This shouldn't include
+    /// any credentials.
+    fn destination(&self) -> String;
+
+    /// Send the `email`.
+    fn send(&self, email: Email) -> SendFuture<'_>;
+}
+
+/// Sends email by POSTing it to the Sendgrid API.
+pub struct SendgridSender {
+    uri: hyper::Uri,
+    auth: String,
+}
+
+impl SendgridSender {
+    /// `auth` is the value of the `Authorization` header Sendgrid requires.
+    pub fn new(uri: hyper::Uri, auth: String) -> Self {
+        Self { uri, auth }
+    }
+
+    async fn request(&self, email: Email) -> Result<(), String> {
+        use hyper::{Body, Client, Method, Request};
+
+        let json_body = serde_json::to_string(&email)
+            .map_err(|e| format!("Error serializing email: {}", &e))?;
+        log::debug!("Sendgrid request body:\n{}", &json_body);
+
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+        let client: Client<_, hyper::Body> = Client::builder().build(https);
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(&self.uri)
+            .header("Authorization", &self.auth)
+            .header("Content-Type", "application/json")
+            .body(Body::from(json_body))
+            .map_err(|e| format!("Error building sendgrid request: {}", &e))?;
+
+        let resp = client
+            .request(req)
+            .await
+            .map_err(|e| format!("Error sending sendgrid request: {}", &e))?;
+
+        if resp.status() == 202 {
+            Ok(())
+        } else {
+            Err(format!(
+                "Sendgrid returned {} response (expected 202).",
+                &resp.status()
+            ))
+        }
+    }
+}
+
+impl EmailSender for SendgridSender {
+    fn name(&self) -> &'static str {
+        SENDGRID
+    }
+
+    fn destination(&self) -> String {
+        self.uri.to_string()
+    }
+
+    fn send(&self, email: Email) -> SendFuture<'_> {
+        Box::pin(self.request(email))
+    }
+}
+
+/// How to secure the connection to the SMTP server.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmtpSecurity {
+    /// TLS from the start of the connection (default port 465).
+    Tls,
+    /// Upgrade the connection with `STARTTLS`, which the server must support
+    /// (default port 587).
+    #[default]
+    Starttls,
+    /// No encryption at all (default port 25). Only for servers on the same
+    /// host or network.
+    None,
+}
+
+/**
+Sends email through an SMTP server.
+
+Configured by these options in the config file:
+
+  * `smtp_host`: the server's host name (required)
+  * `smtp_port`: defaults to the usual port for `smtp_security`
+  * `smtp_security`: `"starttls"` (the default), `"tls"`, or `"none"`
+  * `smtp_username` and `smtp_password`: credentials, if the server wants
+    them
+*/
+pub struct SmtpSender {
+    host: String,
+    port: Option<u16>,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpSender {
+    pub fn new(
+        host: String,
+        port: Option<u16>,
+        security: SmtpSecurity,
+        credentials: Option<(String, String)>,
+    ) -> Result<Self, String> {
+        let mut builder = match security {
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)
+                .map_err(|e| format!("Error configuring SMTP relay {:?}: {}", &host, &e))?,
+            SmtpSecurity::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)
+                    .map_err(|e| format!("Error configuring SMTP relay {:?}: {}", &host, &e))?
+            }
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+        };
+        if let Some(port) = port {
+            builder = builder.port(port);
+        }
+        if let Some((uname, password)) = credentials {
+            builder = builder.credentials(Credentials::new(uname, password));
+        }
+        let transport = builder.timeout(Some(SMTP_TIMEOUT)).build();
+
+        Ok(Self {
+            host,
+            port,
+            transport,
+        })
+    }
+
+    async fn request(&self, email: Email) -> Result<(), String> {
+        let msg = email.message()?;
+        self.transport
+            .send(msg)
+            .await
+            .map_err(|e| format!("Error sending email through {:?}: {}", &self.host, &e))?;
+        Ok(())
+    }
+}
+
+impl EmailSender for SmtpSender {
+    fn name(&self) -> &'static str {
+        SMTP
+    }
+
+    fn destination(&self) -> String {
+        match self.port {
+            Some(port) => format!("{}:{}", &self.host, &port),
+            None => self.host.clone(),
+        }
+    }
+
+    fn send(&self, email: Email) -> SendFuture<'_> {
+        Box::pin(self.request(email))
+    }
+}
+
+/// "Sends" email by logging it. For development.
+pub struct LogSender;
+
+impl EmailSender for LogSender {
+    fn name(&self) -> &'static str {
+        LOG
+    }
+
+    fn destination(&self) -> String {
+        "log".to_owned()
+    }
+
+    fn send(&self, email: Email) -> SendFuture<'_> {
+        Box::pin(async move {
+            log::info!(
+                "Not sending email (email_backend is {:?}):\nTo: {}\nSubject: {}\n\n{}",
+                LOG,
+                email.recipients(),
+                &email.subject,
+                &email.body
+            );
+            Ok(())
+        })
+    }
+}
+
+/// A failed attempt to send an email.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Failure {
+    /// When it failed, in seconds since the Unix epoch.
+    pub at: u64,
+    pub reason: String,
+}
+
+/// A snapshot of [`Health`], for reporting to the Boss.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct HealthReport {
+    /// The `name()` of the [`EmailSender`] in use.
+    pub backend: &'static str,
+    pub destination: String,
+    /// When an email was last sent successfully, in seconds since the Unix
+    /// epoch.
+    pub last_success: Option<u64>,
+    /// Number of emails currently waiting on the backend.
+    pub pending: usize,
+    pub sent: u64,
+    pub failed: u64,
+    /// Number of failures since the last successful send.
+    pub consecutive_failures: u64,
+    /// The most recent failures, newest first.
+    pub recent_failures: Vec<Failure>,
+}
+
+#[derive(Default)]
+struct HealthState {
+    last_success: Option<u64>,
+    pending: usize,
+    sent: u64,
+    failed: u64,
+    consecutive_failures: u64,
+    recent_failures: VecDeque<Failure>,
+}
+
+/// Keeps track of how sending email has been going.
+#[derive(Default)]
+pub struct Health {
+    state: Mutex<HealthState>,
+}
+
+/// Counts an email as pending until it's dropped (even if the request
+/// sending it is abandoned partway through).
+struct PendingSend<'a>(&'a Health);
+
+impl Drop for PendingSend<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().pending -= 1;
+    }
+}
+
+impl Health {
+    pub fn new() -> Health {
+        Health::default()
+    }
+
+    fn start(&self) -> PendingSend<'_> {
+        self.state.lock().unwrap().pending += 1;
+        PendingSend(self)
+    }
+
+    /// Record the result of an attempted send at `now` (in seconds since the
+    /// Unix epoch).
+    fn record_at(&self, res: &Result<(), String>, now: u64) {
+        let mut state = self.state.lock().unwrap();
+        match res {
+            Ok(()) => {
+                state.sent += 1;
+                state.consecutive_failures = 0;
+                state.last_success = Some(now);
+            }
+            Err(e) => {
+                state.failed += 1;
+                state.consecutive_failures += 1;
+                if state.recent_failures.len() >= RECENT_FAILURES {
+                    state.recent_failures.pop_back();
+                }
+                state.recent_failures.push_front(Failure {
+                    at: now,
+                    reason: e.clone(),
+                });
+            }
+        }
+    }
+
+    /// Send the `email` with `sender`, keeping track of how it goes.
+    pub async fn send(&self, sender: &dyn EmailSender, email: Email) -> Result<(), String> {
+        let _pending = self.start();
+        let res = sender.send(email).await;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.record_at(&res, now);
+        res
+    }
+
+    /// Report how sending has been going (through `sender`).
+    pub fn report(&self, sender: &dyn EmailSender) -> HealthReport {
+        let state = self.state.lock().unwrap();
+        HealthReport {
+            backend: sender.name(),
+            destination: sender.destination(),
+            last_success: state.last_success,
+            pending: state.pending,
+            sent: state.sent,
+            failed: state.failed,
+            consecutive_failures: state.consecutive_failures,
+            recent_failures: state.recent_failures.iter().cloned().collect(),
+        }
+    }
+
+    /// If the most recent attempt to send an email failed, a warning saying
+    /// so, for the Boss.
+    pub fn warning(&self) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        if state.consecutive_failures == 0 {
+            return None;
+        }
+        let reason = state
+            .recent_failures
+            .front()
+            .map(|f| f.reason.as_str())
+            .unwrap_or_default();
+        Some(format!(
+            "Email isn't being sent: the last {} attempt{} failed. Most recent error: {}",
+            &state.consecutive_failures,
+            if state.consecutive_failures == 1 { "" } else { "s" },
+            reason
+        ))
+    }
+}
+
+/// How long (in seconds) to wait before retrying an email that has failed
+/// to send `attempts` times, or `None` if it's time to give up.
+pub fn retry_delay(attempts: i16) -> Option<u64> {
+    if !(1..MAX_ATTEMPTS).contains(&attempts) {
+        return None;
+    }
+    Some(FIRST_RETRY << (attempts - 1))
+}
+
+/// Sends queued email in the background; see the module-level docs.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    notify: Notify,
+}
+
+impl Outbox {
+    pub fn new() -> Outbox {
+        Outbox::default()
+    }
+
+    /// Let the outbox know there's new email to send.
+    pub fn wake(&self) {
+        self.notify.notify_one();
+    }
+
+    /// Send the emails in `due` through `mailer`, and record how each went
+    /// in `health` and the database.
+    async fn send_all(
+        mailer: &dyn EmailSender,
+        health: &Health,
+        data: &RwLock<Store>,
+        due: &[QueuedEmail],
+    ) {
+        for q in due.iter() {
+            let res = match Email::from_json(&q.email) {
+                Ok(email) => health
+                    .send(mailer, email)
+                    .await
+                    .map_err(|e| (e, retry_delay(q.attempts + 1))),
+                // It's not going to get any better.
+                Err(e) => Err((e, None)),
+            };
+            let res = match res {
+                Ok(()) => data.read().await.record_email_sent(q.id).await,
+                Err((e, retry_in)) => {
+                    log::warn!("Email #{} about {} failed to send: {}", &q.id, &q.about, &e);
+                    if retry_in.is_none() {
+                        log::error!("Giving up on email #{} about {}.", &q.id, &q.about);
+                    }
+                    data.read().await.record_email_failure(q.id, &e, retry_in).await
+                }
+            };
+            if let Err(e) = res {
+                log::error!("Error recording attempt to send email #{}: {}", &q.id, &e);
+            }
+        }
+    }
+
+    /**
+    Send email as it's queued (and retries as they come due), forever.
+
+    This is meant to be `tokio::spawn()`ed when the server starts up.
+    */
+    pub async fn run(self: Arc<Self>, glob: Arc<RwLock<Glob>>) {
+        loop {
+            // Sending can take a while, so the `Glob` isn't kept locked.
+            let (mailer, health, data) = {
+                let glob = glob.read().await;
+                (glob.mailer.clone(), glob.email_health.clone(), glob.data())
+            };
+            let res = data.read().await.get_due_emails(BATCH).await;
+            match res {
+                Ok(due) if !due.is_empty() => {
+                    Outbox::send_all(mailer.as_ref(), &health, &data, &due).await;
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!("Error reading queued email: {}", &e);
+                }
+            }
+            tokio::select! {
+                _ = self.notify.notified() => {},
+                _ = tokio::time::sleep(POLL) => {},
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static RENDERED: &str = r#"{
+    "to": [
+        {"email": "mom@example.com; dad@example.com", "name": "Parent of Fred Frog"}
+    ],
+    "cc": [ {"email": "berro@camelotacademy.org", "name": "Mr Berro"} ],
+    "from": { "email": "mathbot@camelotacademy.org", "name": "CAMP Math Bot"},
+    "reply_to": {"email": "no-reply@camelotacademy.org", "name": "Do Not Reply"},
+    "subject": "Fred Frog's Math Progress",
+    "headers": {"List-Unsubscribe": "<https://camp.camelotacademy.org/unsubscribe>"},
+    "body": "Fred is doing fine."
+}"#;
+
+    #[test]
+    fn smtp_messages() {
+        let email = Email::from_json(RENDERED).unwrap();
+        assert_eq!(
+            email.recipients(),
+            "mom@example.com; dad@example.com, berro@camelotacademy.org"
+        );
+
+        let msg = email.message().unwrap();
+        assert_eq!(msg.envelope().to().len(), 3);
+        let text = String::from_utf8(msg.formatted()).unwrap();
+        assert!(text.contains("List-Unsubscribe: <https://camp.camelotacademy.org/unsubscribe>"));
+        assert!(text.contains("Reply-To: \"Do Not Reply\" <no-reply@camelotacademy.org>"));
+        assert!(text.contains("Fred is doing fine."));
+
+        let mut bad = email.clone();
+        bad.to[0].email = "nobody".to_owned();
+        assert!(bad.message().is_err());
+    }
+
+    #[test]
+    fn sendgrid_bodies() {
+        // What goes to Sendgrid is what the template rendered.
+        let email = Email::from_json(RENDERED).unwrap();
+        let json = serde_json::to_string(&email).unwrap();
+        let rendered: serde_json::Value = serde_json::from_str(RENDERED).unwrap();
+        let resent: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(rendered, resent);
+    }
+
+    /// Fails to send anything.
+    struct BrokenSender;
+
+    impl EmailSender for BrokenSender {
+        fn name(&self) -> &'static str {
+            "broken"
+        }
+
+        fn destination(&self) -> String {
+            "nowhere".to_owned()
+        }
+
+        fn send(&self, _email: Email) -> SendFuture<'_> {
+            Box::pin(async { Err("connection refused".to_owned()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn email_health() {
+        let health = Health::new();
+        let email = Email::from_json(RENDERED).unwrap();
+        assert!(health.warning().is_none());
+
+        health.send(&LogSender, email.clone()).await.unwrap();
+        let report = health.report(&LogSender);
+        assert_eq!((report.backend, report.sent, report.failed), (LOG, 1, 0));
+        assert!(report.last_success.is_some());
+        assert!(health.warning().is_none());
+
+        for _ in 0..12 {
+            assert!(health.send(&BrokenSender, email.clone()).await.is_err());
+        }
+        let report = health.report(&BrokenSender);
+        assert_eq!((report.sent, report.failed, report.pending), (1, 12, 0));
+        assert_eq!(report.consecutive_failures, 12);
+        assert_eq!(report.recent_failures.len(), RECENT_FAILURES);
+        assert_eq!(report.recent_failures[0].reason, "connection refused");
+        assert!(health.warning().unwrap().contains("last 12 attempts failed"));
+
+        {
+            let _pending = health.start();
+            assert_eq!(health.report(&LogSender).pending, 1);
+        }
+        assert_eq!(health.report(&LogSender).pending, 0);
+
+        health.record_at(&Ok(()), 1_700_000_000);
+        assert_eq!(health.report(&LogSender).last_success, Some(1_700_000_000));
+        assert!(health.warning().is_none());
+    }
+
+    #[test]
+    fn retries() {
+        assert_eq!(retry_delay(0), None);
+        assert_eq!(retry_delay(1), Some(60));
+        assert_eq!(retry_delay(2), Some(120));
+        assert_eq!(retry_delay(MAX_ATTEMPTS - 1), Some(60 * 64));
+        assert_eq!(retry_delay(MAX_ATTEMPTS), None);
+    }
+}
@@ -1,7 +1,11 @@
 /*!
 Student course completion history.
 */
-use std::cmp::{Ord, Ordering, PartialOrd};
+use std::{
+    cmp::{Ord, Ordering, PartialOrd},
+    io::Read,
+    str::FromStr,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -28,3 +32,122 @@ impl PartialOrd for HistEntry {
         Some(self.cmp(other))
     }
 }
+
+/**
+A course a (transfer) student completed somewhere else, to be added to
+their completion history.
+
+The `course` is either the `sym` of one of our courses or an external
+course identifier with an equivalent course on record (see
+[`Store::get_course_equivalents`](crate::store::Store::get_course_equivalents)).
+*/
+#[derive(Debug, PartialEq)]
+pub struct TransferRecord {
+    pub uname: String,
+    pub course: String,
+    pub year: i32,
+    pub term: Term,
+}
+
+impl TransferRecord {
+    fn from_csv_line(row: &csv::StringRecord) -> Result<TransferRecord, String> {
+        let field = |n: usize, name: &str| match row.get(n) {
+            Some(s) if !s.is_empty() => Ok(s),
+            _ => Err(format!("missing {}", name)),
+        };
+
+        let uname = field(0, "uname")?.to_owned();
+        let course = field(1, "course")?.to_owned();
+        let year_str = field(2, "year")?;
+        let year: i32 = year_str
+            .parse()
+            .map_err(|_| format!("{:?} is not a valid year", year_str))?;
+        let term = Term::from_str(field(3, "term")?)?;
+
+        Ok(TransferRecord {
+            uname,
+            course,
+            year,
+            term,
+        })
+    }
+
+    /**
+    Read transfer records from CSV data with the columns
+
+    ```csv
+    #uname, course,     year, term
+    jsmith, ALG-1A,     2021, Fall
+    jsmith, pha,        2021, Spring
+    ```
+
+    where `year` is the year in which the academic year started. Blank lines
+    and lines beginning with `#` are ignored.
+    */
+    pub fn vec_from_csv_reader<R: Read>(r: R) -> Result<Vec<TransferRecord>, String> {
+        log::trace!("TransferRecord::vec_from_csv_reader(...) called.");
+
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .trim(csv::Trim::All)
+            .flexible(false)
+            .has_headers(false)
+            .from_reader(r);
+
+        let mut records: Vec<TransferRecord> = Vec::new();
+        for (n, res) in csv_reader.records().enumerate() {
+            let record = res.map_err(|e| match e.position() {
+                Some(p) => format!("Error on line {}: {}", p.line(), &e),
+                None => format!("Error in CSV record {}: {}", &n, &e),
+            })?;
+            let rec =
+                TransferRecord::from_csv_line(&record).map_err(|e| match record.position() {
+                    Some(p) => format!("Error on line {}: {}", p.line(), &e),
+                    None => format!("Error in CSV record {}: {}", &n, &e),
+                })?;
+            records.push(rec);
+        }
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_csv() {
+        let csv = "#uname, course, year, term
+        jsmith, ALG-1A, 2021, Fall
+
+        jsmith, pha, 2021, spring\n";
+        let recs = TransferRecord::vec_from_csv_reader(csv.as_bytes()).unwrap();
+        assert_eq!(
+            recs,
+            vec![
+                TransferRecord {
+                    uname: "jsmith".to_owned(),
+                    course: "ALG-1A".to_owned(),
+                    year: 2021,
+                    term: Term::Fall,
+                },
+                TransferRecord {
+                    uname: "jsmith".to_owned(),
+                    course: "pha".to_owned(),
+                    year: 2021,
+                    term: Term::Spring,
+                },
+            ]
+        );
+
+        let err =
+            TransferRecord::vec_from_csv_reader("jsmith, pha, 21-22, Fall".as_bytes()).unwrap_err();
+        assert!(err.contains("line 1"));
+        assert!(TransferRecord::vec_from_csv_reader("jsmith, , 2021, Fall".as_bytes()).is_err());
+        assert!(
+            TransferRecord::vec_from_csv_reader("jsmith, pha, 2021, Winter".as_bytes()).is_err()
+        );
+        assert!(TransferRecord::vec_from_csv_reader("jsmith, pha, 2021".as_bytes()).is_err());
+    }
+}
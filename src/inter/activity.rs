@@ -0,0 +1,197 @@
+/*!
+A feed of what's changed recently for one student, for their Teacher:
+goals added, changed, or deleted (by anyone), edits to the student's
+account, and their reports being finalized, released, or withdrawn.
+
+It's drawn from the audit log (see
+[`Store::get_audit_log`](crate::store::Store::get_audit_log)), but only
+the kinds of entries in [`TEACHER_ACTIONS`]; Teachers respond to the
+`student-activity` action with [`respond`].
+*/
+use axum::{
+    http::header::{HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{respond_bad_request, text_500};
+use crate::{
+    config::Glob,
+    store::{AuditEntry, AuditFilter},
+    user::User,
+};
+
+/// The audit log actions a Teacher sees in a student's activity feed.
+pub const TEACHER_ACTIONS: &[&str] = &[
+    "insert-user",
+    "update-user",
+    "add-goal",
+    "update-goal",
+    "delete-goal",
+    "finalize-report",
+    "copy-draft",
+    "release-report",
+    "withdraw-report",
+];
+/// Number of entries in a page if the request doesn't specify.
+const DEFAULT_LIMIT: i64 = 50;
+/// Largest page a request may ask for.
+const MAX_LIMIT: i64 = 200;
+
+/// Body of a `student-activity` request.
+#[derive(Debug, Deserialize, PartialEq)]
+struct ActivityRequest {
+    uname: String,
+    #[serde(default)]
+    offset: i64,
+    limit: Option<i64>,
+}
+
+impl ActivityRequest {
+    fn from_body(body: Option<String>) -> Result<ActivityRequest, String> {
+        let text = match body.as_deref().map(str::trim) {
+            None | Some("") => {
+                return Err("Request needs a body with the uname of the student.".to_owned());
+            }
+            Some(text) => text,
+        };
+        let mut req: ActivityRequest = if text.starts_with('{') {
+            serde_json::from_str(text)
+                .map_err(|e| format!("Unable to read activity request: {}", &e))?
+        } else {
+            ActivityRequest {
+                uname: text.to_owned(),
+                offset: 0,
+                limit: None,
+            }
+        };
+        req.offset = req.offset.max(0);
+        req.limit = Some(req.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT));
+
+        Ok(req)
+    }
+}
+
+/// Response to a `student-activity` request.
+#[derive(Debug, Serialize)]
+struct Activity {
+    uname: String,
+    offset: i64,
+    limit: i64,
+    /// Whether there are older entries past this page.
+    more: bool,
+    entries: Vec<AuditEntry>,
+}
+
+/**
+Respond with a page of the recent activity (newest first) of one of the
+Teacher `tuname`'s students.
+
+```text
+x-camp-action: student-activity
+```
+The body is either just the student's uname, or like
+```json
+{ "uname": "frog", "offset": 50, "limit": 50 }
+```
+The response looks like
+```json
+{
+    "uname": "frog",
+    "offset": 0,
+    "limit": 50,
+    "more": false,
+    "entries": [
+        {
+            "id": 1234,
+            "at": "2023-03-01 17:04:12",
+            "actor": "root",
+            "action": "update-user",
+            "target": "frog",
+            "detail": null
+        }
+    ]
+}
+```
+where each entry is an [`AuditEntry`].
+*/
+pub async fn respond(tuname: &str, body: Option<String>, glob: &Glob) -> Response {
+    let req = match ActivityRequest::from_body(body) {
+        Ok(req) => req,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+    match glob.users.get(&req.uname) {
+        Some(User::Student(s)) if s.teacher == tuname => {}
+        _ => {
+            return respond_bad_request(format!("{:?} is not your student.", &req.uname));
+        }
+    }
+
+    let limit = req.limit.unwrap_or(DEFAULT_LIMIT);
+    let filter = AuditFilter {
+        actions: Some(TEACHER_ACTIONS.iter().map(|a| a.to_string()).collect()),
+        target: Some(req.uname.clone()),
+        // One extra, to tell whether there are more.
+        limit: Some(limit + 1),
+        offset: Some(req.offset),
+        ..Default::default()
+    };
+    let mut entries = match glob.data().read().await.get_audit_log(&filter).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Error retrieving activity of {:?}: {}", &req.uname, &e);
+            return text_500(Some(format!("Unable to retrieve activity: {}", &e)));
+        }
+    };
+    let more = entries.len() as i64 > limit;
+    entries.truncate(limit as usize);
+
+    let data = Activity {
+        uname: req.uname,
+        offset: req.offset,
+        limit,
+        more,
+        entries,
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("student-activity"),
+        )],
+        Json(data),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn activity_requests() {
+        let req = |body: &str| ActivityRequest::from_body(Some(body.to_owned()));
+
+        assert_eq!(
+            req(" frog\n").unwrap(),
+            ActivityRequest {
+                uname: "frog".to_owned(),
+                offset: 0,
+                limit: Some(DEFAULT_LIMIT),
+            }
+        );
+        let r = req(r#"{"uname": "frog", "offset": 50, "limit": 10}"#).unwrap();
+        assert_eq!((r.offset, r.limit), (50, Some(10)));
+        let r = req(r#"{"uname": "frog", "offset": -3, "limit": 100000}"#).unwrap();
+        assert_eq!((r.offset, r.limit), (0, Some(MAX_LIMIT)));
+
+        assert!(req("").is_err());
+        assert!(ActivityRequest::from_body(None).is_err());
+        assert!(req(r#"{"offset": 50}"#).is_err());
+    }
+}
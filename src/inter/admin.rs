@@ -1,5 +1,9 @@
 /*!
 Subcrate for interoperation with Admin users.
+
+Admins can be restricted to some subset of [`Permission`]s (see
+[`needs`] for which actions need which); Admins without any restrictions
+on record can do anything.
 */
 use std::collections::HashMap;
 use std::io::Cursor;
@@ -15,11 +19,19 @@ use serde_json::json;
 use time::Date;
 use tokio::sync::RwLock;
 
+use super::extract::{CampAction, CampCourse, CampHeader, CampStudent, CampUname};
 use super::*;
 use crate::config::Glob;
 use crate::hist::HistEntry;
-use crate::course::{Chapter, Course};
-use crate::{auth::AuthResult, user::*, DATE_FMT};
+use crate::course::{levels_from_csv, Chapter, Course};
+use crate::store::{AcademicYear, AuditFilter};
+use crate::{
+    anon,
+    auth::AuthResult,
+    pace::{ExamWindow, TestStatus},
+    user::*,
+    UnifiedError, DATE_FMT,
+};
 
 /**
 Determine whether the Admin's login credentials check out, then send the
@@ -60,6 +72,9 @@ pub async fn login(base: BaseUser, form: LoginData, glob: Arc<RwLock<Glob>>) ->
         Ok(AuthResult::BadPassword) => {
             return respond_bad_password(&base.uname);
         }
+        Ok(AuthResult::Locked(secs)) => {
+            return respond_locked_out(&base.uname, secs);
+        }
         Ok(x) => {
             log::warn!(
                 "auth::Db::check_password_and_issue_key( {:?}, {:?}, [ Glob ] ) returned {:?}, which shouldn't happen.",
@@ -69,9 +84,13 @@ pub async fn login(base: BaseUser, form: LoginData, glob: Arc<RwLock<Glob>>) ->
         }
     };
 
+    let glob = glob.read().await;
+    glob.set_anonymized(&auth_key, form.anonymize());
     let data = json!({
         "uname": &base.uname,
-        "key": &auth_key
+        "key": &auth_key,
+        "year": glob.current_year().map(|y| &y.label),
+        "year_start": glob.academic_year(),
     });
 
     serve_template(StatusCode::OK, "admin", &data, vec![])
@@ -86,25 +105,18 @@ of the `x-camp-action` header for executing updates and generating
 responses.
 
 A previous layer should have already ensured that the Admin's key
-checks out.
+checks out. Actions the Admin doesn't have the [`Permission`]s for get
+403'd, and ones that destroy data have to be confirmed first (see
+[`confirm`](super::confirm)).
 */
 pub async fn api(
+    CampUname(uname): CampUname,
+    CampAction(action): CampAction,
     headers: HeaderMap,
     body: Option<String>,
     Extension(glob): Extension<Arc<RwLock<Glob>>>,
 ) -> Response {
-    let uname: &str = match headers.get("x-camp-uname") {
-        Some(uname) => match uname.to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                return text_500(None);
-            }
-        },
-        None => {
-            return text_500(None);
-        }
-    };
-
+    let uname = uname.as_str();
     let u = {
         let glob = glob.read().await;
         if let Some(u) = glob.users.get(uname) {
@@ -117,51 +129,122 @@ pub async fn api(
     match u {
         User::Admin(_) => { /* Okay, request may proceed. */ }
         _ => {
-            return (
-                StatusCode::FORBIDDEN,
-                "Who is this? What's your operating number?".to_owned(),
-            )
+            return ApiError::forbidden("Who is this? What's your operating number?".to_owned())
                 .into_response();
         }
     };
 
-    let action = match headers.get("x-camp-action") {
-        Some(act) => match act.to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                return respond_bad_request("x-camp-action header unrecognizable.".to_owned());
-            }
+    let res = glob.read().await.data().read().await.get_admin_permissions(uname).await;
+    let perms = match res {
+        Ok(perms) => perms,
+        Err(e) => {
+            log::error!("Error retrieving permissions for Admin {:?}: {}", uname, &e);
+            return text_500(Some(format!("Unable to check your permissions: {}", &e)));
         },
-        None => {
-            return respond_bad_request("Request must have an x-camp-action header.".to_owned());
-        }
     };
+    if let Err(e) = check_permissions(action.as_str(), perms.as_deref()) {
+        log::warn!("Admin {:?} denied {:?}: {}", uname, &action, &e);
+        return ApiError::forbidden(e).into_response();
+    }
+    if perms.is_some()
+        && touches_admin(action.as_str(), body.as_deref(), &glob.read().await.users)
+    {
+        log::warn!("Restricted Admin {:?} denied {:?} on an Admin.", uname, &action);
+        return ApiError::forbidden(
+            "Only unrestricted Admins may add, change, or delete Admins.".to_owned(),
+        )
+        .into_response();
+    }
+    if let Err(resp) =
+        super::confirm::interlock(action.as_str(), uname, &headers, body.as_deref(), &glob).await
+    {
+        return resp;
+    }
 
-    match action {
+    match action.as_str() {
+        "bootstrap" => bootstrap(glob.clone()).await,
         "populate-users" => populate_users(glob.clone()).await,
         "populate-admins" => populate_role(glob.clone(), Role::Admin).await,
         "populate-bosses" => populate_role(glob.clone(), Role::Boss).await,
-        "add-user" => add_user(body, glob.clone()).await,
-        "update-user" => update_user(body, glob.clone()).await,
-        "delete-user" => delete_user(body, glob.clone()).await,
+        "add-user" => add_user(uname, body, glob.clone()).await,
+        "update-user" => update_user(uname, body, glob.clone()).await,
+        "delete-user" => delete_user(uname, body, glob.clone()).await,
+        "reassign-students" => reassign_students(uname, body, glob.clone()).await,
+        "unlock-user" => unlock_user(body, glob.clone()).await,
+        "check-uname" => check_name("check-uname", body, glob.clone()).await,
+        "check-email" => check_name("check-email", body, glob.clone()).await,
         "upload-students" => upload_students(body, glob.clone()).await,
+        "download-students" => download_students(glob.clone()).await,
         "populate-courses" => populate_courses(glob.clone()).await,
         "upload-course" => upload_course(body, glob.clone()).await,
         "add-course" => add_course(body, glob.clone()).await,
         "delete-course" => delete_course(body, glob.clone()).await,
         "update-course" => update_course(body, glob.clone()).await,
+        "preview-relevel" => preview_relevel(body, glob.clone()).await,
+        "relevel-courses" => relevel_courses(body, glob.clone()).await,
         "add-chapters" => add_chapters(body, glob.clone()).await,
         "update-chapter" => update_chapter(body, glob.clone()).await,
+        "reorder-chapters" => reorder_chapters(body, glob.clone()).await,
         "delete-chapter" => delete_chapter(body, glob.clone()).await,
+        "recompute-weights" => recompute_weights(body, None, glob.clone()).await,
+        "waive-chapter" => waive_chapter(uname, body, glob.clone()).await,
+        "populate-course-stats" => populate_course_stats(glob.clone()).await,
+        "recompute-course-stats" => recompute_course_stats(glob.clone()).await,
         "populate-cal" => populate_calendar(glob.clone()).await,
         "update-cal" => update_calendar(body, glob.clone()).await,
+        "upload-calendar" => upload_calendar(&headers, body, glob.clone()).await,
         "populate-dates" => populate_dates(glob.clone()).await,
         "set-date" => set_date(body, glob.clone()).await,
+        "populate-years" => populate_years(glob.clone()).await,
+        "set-year" => set_year(body, glob.clone()).await,
+        "delete-year" => delete_year(body, glob.clone()).await,
+        "populate-exam-windows" => populate_exam_windows(glob.clone()).await,
+        "add-exam-window" => add_exam_window(body, glob.clone()).await,
+        "delete-exam-window" => delete_exam_window(body, glob.clone()).await,
         "populate-completion" => populate_completion(glob.clone()).await,
         "add-completion" => add_completion(body, &headers, glob.clone()).await,
         "delete-completion" => delete_completion(&headers, glob.clone()).await,
-        "reset-students" => reset_students(glob.clone()).await,
+        "import-transfer" => import_transfer(body, glob.clone()).await,
+        "populate-equivalents" => populate_equivalents(glob.clone()).await,
+        "set-equivalent" => set_equivalent(body, glob.clone()).await,
+        "preview-reset" => preview_reset(glob.clone()).await,
+        "reset-students" => reset_students(body, glob.clone()).await,
+        "populate-archives" => populate_archives(glob.clone()).await,
+        "export-archive" => export_archive(body, glob.clone()).await,
         "refresh-all" => refresh_wrapper(glob.clone()).await,
+        "populate-failures" => populate_failures(glob.clone()).await,
+        "audit-log" => audit_log(body, glob.clone()).await,
+        "key-cache-stats" => key_cache_stats(glob.clone()).await,
+        "failed-emails" => failed_emails(glob.clone()).await,
+        "retention-stats" => super::retention::respond(glob.clone()).await,
+        "chapter-durations" => super::durations::respond(body, glob.clone()).await,
+        "roster-preview" => super::roster::preview(glob.clone()).await,
+        "roster-fetch" => super::roster::fetch_now(glob.clone()).await,
+        "roster-apply" => super::roster::apply(uname, body, glob.clone()).await,
+        "revoke-keys" => revoke_keys(body, glob.clone()).await,
+        "support-bundle" => support_bundle(glob.clone()).await,
+        "export-auth" => export_auth(uname, glob.clone()).await,
+        "verify-auth" => verify_auth(body, glob.clone()).await,
+        "import-auth" => import_auth(uname, body, glob.clone()).await,
+        "inspect-failure" => inspect_failure(body, glob.clone()).await,
+        "populate-permissions" => populate_permissions(glob.clone()).await,
+        "set-permissions" => set_permissions(uname, body, glob.clone()).await,
+        "populate-training" => populate_training(glob.clone()).await,
+        "set-training" => set_training(uname, body, glob.clone()).await,
+        "reset-sandbox" => reset_sandbox(uname, body, glob.clone()).await,
+        "populate-test-requests" => populate_test_requests(body, glob.clone()).await,
+        "set-test-status" => set_test_status(body, glob.clone()).await,
+        "populate-webhooks" => super::webhooks::populate(glob.clone()).await,
+        "add-webhook" => super::webhooks::add(body, glob.clone()).await,
+        "update-webhook" => super::webhooks::update(body, glob.clone()).await,
+        "delete-webhook" => super::webhooks::delete(body, glob.clone()).await,
+        "webhook-deliveries" => super::webhooks::deliveries(glob.clone()).await,
+        "populate-report-templates" => super::report_templates::populate(glob.clone()).await,
+        "upload-report-template" => {
+            super::report_templates::upload(uname, body, glob.clone()).await
+        }
+        "preview-report-template" => super::report_templates::preview(body, glob.clone()).await,
+        "activate-report-template" => super::report_templates::activate(body, glob.clone()).await,
         x => respond_bad_request(format!(
             "{:?} is not a recognizable x-camp-action value.",
             x
@@ -169,6 +252,113 @@ pub async fn api(
     }
 }
 
+/// What an Admin needs to be allowed to take an action.
+#[derive(Debug, PartialEq)]
+enum Needs {
+    /// Any Admin may.
+    Nothing,
+    /// Admins need all of these.
+    All(&'static [Permission]),
+    /// Only unrestricted Admins may.
+    Unrestricted,
+}
+
+/**
+Which [`Permission`]s the Admin API action `action` needs.
+
+Reading things (the `populate-*` actions) is open to every Admin, except
+for past years' archived data. Looking at diagnostics and changing Admins'
+permissions are only for unrestricted Admins, as is anything not listed
+here, so new actions are safe until someone decides otherwise. (Restricted
+Admins also can't do anything to other Admins; see [`touches_admin`].)
+*/
+fn needs(action: &str) -> Needs {
+    use Permission::*;
+
+    match action {
+        "bootstrap" | "populate-users" | "populate-admins" | "populate-bosses"
+        | "populate-courses" | "populate-course-stats" | "populate-cal"
+        | "populate-dates" | "populate-years" | "populate-exam-windows"
+        | "populate-completion" | "populate-equivalents"
+        | "populate-permissions" | "refresh-all" | "check-uname"
+        | "check-email" | "chapter-durations" | "populate-training"
+        | "populate-test-requests" | "download-students" | "populate-archives"
+        | "populate-report-templates" | "preview-report-template" => Needs::Nothing,
+
+        "add-user" | "update-user" | "unlock-user" | "upload-students"
+        | "revoke-keys" | "waive-chapter" | "add-completion" | "reassign-students"
+        | "import-transfer" | "roster-preview" | "roster-fetch" | "set-training"
+        | "reset-sandbox" | "set-test-status" | "export-archive" => Needs::All(&[Users]),
+        "delete-user" | "delete-completion" | "preview-reset"
+        | "reset-students" | "roster-apply" => Needs::All(&[Users, Destructive]),
+
+        "upload-course" | "add-course" | "update-course" | "add-chapters"
+        | "update-chapter" | "reorder-chapters" | "recompute-weights"
+        | "recompute-course-stats" | "set-equivalent" | "preview-relevel"
+        | "relevel-courses" => Needs::All(&[Courses]),
+        "delete-course" | "delete-chapter" => Needs::All(&[Courses, Destructive]),
+
+        "update-cal" | "upload-calendar" | "set-date" | "set-year"
+        | "add-exam-window" => Needs::All(&[Calendar]),
+        "delete-year" | "delete-exam-window" => Needs::All(&[Calendar, Destructive]),
+
+        _ => Needs::Unrestricted,
+    }
+}
+
+/**
+Whether the Admin API action `action` (with request `body`) would add,
+change, or otherwise act on an Admin, given the current `users`.
+
+Restricted Admins mustn't be able to do that: a new Admin has no
+permissions on record, and so is unrestricted, and an unrestricted Admin
+whose email address gets changed can have their password reset out from
+under them.
+*/
+fn touches_admin(action: &str, body: Option<&str>, users: &HashMap<String, User>) -> bool {
+    let is_admin = |uname: &str| matches!(users.get(uname.trim()), Some(User::Admin(_)));
+
+    match action {
+        "add-user" | "update-user" => match body.map(serde_json::from_str::<User>) {
+            Some(Ok(u)) => u.role() == Role::Admin || is_admin(u.uname()),
+            // Bodies that aren't Users get turned away by the action itself.
+            _ => false,
+        },
+        "delete-user" | "unlock-user" | "revoke-keys" => body.map(is_admin).unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Check whether an Admin with the given permissions (`None` being
+/// unrestricted) may take the action `action`.
+fn check_permissions(action: &str, perms: Option<&[Permission]>) -> Result<(), String> {
+    let perms = match perms {
+        None => { return Ok(()); },
+        Some(perms) => perms,
+    };
+
+    match needs(action) {
+        Needs::Nothing => Ok(()),
+        Needs::Unrestricted => Err(format!(
+            "Only unrestricted Admins may {:?}.", action
+        )),
+        Needs::All(needed) => {
+            let missing: Vec<String> = needed.iter()
+                .filter(|p| !perms.contains(p))
+                .map(|p| p.to_string())
+                .collect();
+            if missing.is_empty() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "You need the {} permission(s) to {:?}.",
+                    missing.join(" and "), action
+                ))
+            }
+        },
+    }
+}
+
 /**
 Generate a response for the frontend to populate data about all users of
 the given [`Role`].
@@ -179,8 +369,7 @@ async fn populate_role(glob: Arc<RwLock<Glob>>, role: Role) -> Response {
     let glob = glob.read().await;
     let users: Vec<&User> = glob
         .users
-        .iter()
-        .map(|(_, u)| u)
+        .values()
         .filter(|&u| u.role() == role)
         .collect();
 
@@ -195,6 +384,108 @@ async fn populate_role(glob: Arc<RwLock<Glob>>, role: Role) -> Response {
         .into_response()
 }
 
+/// All users, in the order the frontend displays them.
+fn sorted_users(glob: &Glob) -> Vec<&User> {
+    let mut users: Vec<&User> = glob.users.values().collect();
+    users.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    users
+}
+
+/// All courses, in order of level.
+fn sorted_courses(glob: &Glob) -> Vec<&Course> {
+    let mut courses: Vec<&Course> = glob.courses.values().collect();
+    courses.sort_by(|a, b| {
+        a.level
+            .partial_cmp(&b.level)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    courses
+}
+
+/**
+Respond with everything the Admin page needs when it first loads, so it
+doesn't have to make a `populate-*` request for each piece.
+
+Request requirements:
+```text
+x-camp-action: bootstrap
+```
+
+The response body is a JSON object with the fields
+```json
+{
+    "users": [ /* as populate-users */ ],
+    "courses": [ /* as populate-courses */ ],
+    "completion": { /* as populate-completion */ },
+    "equivalents": { /* as populate-equivalents */ },
+    "permissions": { /* as populate-permissions */ },
+    "training": [ /* as populate-training */ ]
+}
+```
+The completion histories, course equivalents, and Admin permissions are
+fetched from the database concurrently.
+*/
+async fn bootstrap(glob: Arc<RwLock<Glob>>) -> Response {
+    log::trace!("bootstrap( Glob ) called.");
+
+    let glob = glob.read().await;
+    let data = glob.data();
+    let data = data.read().await;
+
+    let (completion, equivalents, permissions) = tokio::join!(
+        data.get_all_completion_histories(),
+        data.get_course_equivalents(),
+        data.get_all_admin_permissions()
+    );
+    let completion = match completion {
+        Ok(map) => map,
+        Err(e) => {
+            log::error!(
+                "Error attempting to retrieve all completion histories: {}", &e
+            );
+            return text_500(Some(format!(
+                "Error retrieving completion history: {}", &e
+            )));
+        },
+    };
+    let equivalents = match equivalents {
+        Ok(map) => map,
+        Err(e) => {
+            log::error!("Error retrieving course equivalents: {}", &e);
+            return text_500(Some(format!(
+                "Error retrieving course equivalents: {}", &e
+            )));
+        },
+    };
+    let permissions = match permissions {
+        Ok(map) => map,
+        Err(e) => {
+            log::error!("Error retrieving Admin permissions: {}", &e);
+            return text_500(Some(format!(
+                "Error retrieving Admin permissions: {}", &e
+            )));
+        },
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("bootstrap"),
+            ),
+        ],
+        Json(json!({
+            "users": sorted_users(&glob),
+            "courses": sorted_courses(&glob),
+            "completion": completion,
+            "equivalents": equivalents,
+            "permissions": permissions,
+            "training": training_data(&glob),
+        })),
+    ).into_response()
+}
+
 /**
 Generate a response for the frontend to populate data about all users
 of the system.
@@ -208,8 +499,7 @@ async fn populate_users(glob: Arc<RwLock<Glob>>) -> Response {
     log::trace!("populate_all( Glob ) called.");
 
     let glob = glob.read().await;
-    let mut users: Vec<&User> = glob.users.iter().map(|(_, u)| u).collect();
-    users.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let users = sorted_users(&glob);
 
     (
         StatusCode::OK,
@@ -275,7 +565,7 @@ x-camp-action: add-user
 With a body that should JSON-deserialize into the [`User`] data
 in question.
 */
-async fn add_user(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+async fn add_user(auname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
     let body = match body {
         Some(body) => body,
         None => {
@@ -293,7 +583,7 @@ async fn add_user(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
 
     {
         let mut glob = glob.write().await;
-        if let Err(e) = glob.insert_user(&u).await {
+        if let Err(e) = glob.insert_user(auname, &u).await {
             log::error!("Error inserting new user ({:?})into database: {}", &u, &e,);
             return text_500(Some(format!("Unable to insert User into database: {}", &e)));
         }
@@ -307,6 +597,63 @@ async fn add_user(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
     populate_users(glob).await
 }
 
+/**
+Respond with whether the uname or email address in the body of the request
+would do for a new user: what's wrong with it, and who already has it. See
+[`NameCheck`](crate::config::NameCheck).
+
+The Admin page checks as new users' details get filled in, so nobody finds
+out a uname is taken only after filling out the whole form.
+
+Req'ments:
+```text
+x-camp-action: check-uname
+```
+or
+```text
+x-camp-action: check-email
+```
+*/
+async fn check_name(
+    action: &'static str,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>
+) -> Response {
+    let value = match body.as_deref().map(str::trim) {
+        Some(v) if !v.is_empty() => v.to_owned(),
+        _ => {
+            return respond_bad_request("Request requires a value to check.".to_owned());
+        },
+    };
+
+    let res = {
+        let glob = glob.read().await;
+        if action == "check-email" {
+            glob.check_email(&value).await
+        } else {
+            glob.check_uname(&value).await
+        }
+    };
+    let check = match res {
+        Ok(check) => check,
+        Err(e) => {
+            log::error!("Error checking {:?} ({}): {}", &value, action, &e);
+            return text_500(Some(format!("Unable to check {:?}: {}", &value, &e)));
+        },
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static(action),
+            ),
+        ],
+        Json(check),
+    ).into_response()
+}
+
 /**
 Respond to a request to add multiple Students from data in CSV format.
 
@@ -347,6 +694,28 @@ async fn upload_students(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Respo
     populate_users(glob).await
 }
 
+/**
+Respond with a CSV roster of every Student, in the same format accepted
+by the `upload-students` action.
+
+Request requirements:
+```text
+x-camp-action: download-students
+```
+*/
+async fn download_students(glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let students: Vec<&Student> = glob
+        .users_with_role(Role::Student)
+        .filter_map(|u| match u {
+            User::Student(s) => Some(s),
+            _ => None,
+        })
+        .collect();
+
+    respond_students_csv(&students)
+}
+
 /**
 Respond to a request to update a User's data.
 
@@ -358,9 +727,19 @@ The request body should be a JSON-deserializable `User` struct with the
 `uname` of the user whose data should be updated with the rest of the
 data in the struct.
 
-This action can't change the [`Role`] of a user.
+This action can't change the [`Role`] of a user, and can't be done in an
+anonymized session (see [`anon`](crate::anon)), where the user's details
+the frontend would send back are fake.
 */
-async fn update_user(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+async fn update_user(auname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    if anon::active() {
+        return ApiError::forbidden(
+            "Users can't be edited while names are anonymized; log in again without it."
+                .to_owned(),
+        )
+        .into_response();
+    }
+
     let body = match body {
         Some(body) => body,
         None => {
@@ -378,7 +757,7 @@ async fn update_user(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response
 
     {
         let mut glob = glob.write().await;
-        if let Err(e) = glob.update_user(&u).await {
+        if let Err(e) = glob.update_user(auname, &u).await {
             log::error!("Error updating user {:?}: {}", &u, &e,);
             return text_500(Some(e.to_string()));
         }
@@ -401,7 +780,7 @@ x-camp-action: delete-user
 ```
 Body should be `uname` of user to be deleted.
 */
-async fn delete_user(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+async fn delete_user(auname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
     let uname = match body {
         Some(uname) => uname,
         None => {
@@ -413,13 +792,81 @@ async fn delete_user(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response
 
     {
         let glob = glob.read().await;
-        if let Err(e) = glob.delete_user(&uname).await {
+        if let Err(e) = glob.delete_user(auname, &uname).await {
             log::error!("Error deleting user {:?}: {}", uname, &e);
             return text_500(Some(e.to_string()));
         }
     }
     {
-        if let Err(e) = glob.write().await.refresh_users().await {
+        let mut glob = glob.write().await;
+        glob.training.remove(&uname);
+        if let Err(e) = glob.refresh_users().await {
+            log::error!("Error refreshing user hash from database: {}", &e);
+            return text_500(Some("Unable to reread users from database.".to_owned()));
+        }
+    }
+
+    populate_users(glob).await
+}
+
+/// Deserializes the body of a `reassign-students` request.
+#[derive(Debug, Deserialize)]
+struct ReassignData {
+    from: String,
+    to: String,
+}
+
+/**
+Move all of one Teacher's students to another, like before deleting a
+Teacher who has left.
+
+```text
+x-camp-action: reassign-students
+```
+With a JSON body like
+```json
+{ "from": "berro", "to": "irfan" }
+```
+Responds like `populate-users`.
+*/
+async fn reassign_students(
+    auname: &str,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let rdata: ReassignData = match body.as_deref().map(serde_json::from_str) {
+        Some(Ok(rdata)) => rdata,
+        Some(Err(e)) => {
+            return respond_bad_request(format!("Unable to read reassignment: {}", &e));
+        }
+        None => {
+            return respond_bad_request(
+                "Request needs a JSON body with the Teachers to move students from and to."
+                    .to_owned(),
+            );
+        }
+    };
+
+    {
+        let mut glob = glob.write().await;
+        match glob.reassign_students(auname, &rdata.from, &rdata.to).await {
+            Ok(moved) => {
+                log::info!(
+                    "Moved {} students from {:?} to {:?}.",
+                    moved.len(),
+                    &rdata.from,
+                    &rdata.to
+                );
+            }
+            Err(UnifiedError::String(e)) => {
+                return respond_bad_request(e);
+            }
+            Err(e) => {
+                log::error!("Error reassigning students {:?}: {}", &rdata, &e);
+                return text_500(Some(e.to_string()));
+            }
+        }
+        if let Err(e) = glob.refresh_users().await {
             log::error!("Error refreshing user hash from database: {}", &e);
             return text_500(Some("Unable to reread users from database.".to_owned()));
         }
@@ -428,6 +875,35 @@ async fn delete_user(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response
     populate_users(glob).await
 }
 
+/**
+Clear a user's record of failed login attempts, lifting any lockout.
+
+Request body should be the uname of the user to unlock.
+*/
+async fn unlock_user(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let uname = match body {
+        Some(uname) => uname,
+        None => {
+            return respond_bad_request(
+                "Request must include the uname to unlock as a body.".to_owned(),
+            );
+        }
+    };
+
+    {
+        let glob = glob.read().await;
+        if !glob.users.contains_key(&uname) {
+            return respond_bad_request(format!("There is no user with uname {:?}.", &uname));
+        }
+        if let Err(e) = glob.auth().read().await.clear_failures(&uname).await {
+            log::error!("Error clearing failed login attempts for {:?}: {}", &uname, &e);
+            return text_500(Some(format!("Unable to unlock user {:?}.", &uname)));
+        }
+    }
+
+    populate_users(glob).await
+}
+
 //
 //
 // This section is for dealing with COURSES.
@@ -442,14 +918,7 @@ or altering `Course`s) use this function to generate their responses.
 */
 async fn populate_courses(glob: Arc<RwLock<Glob>>) -> Response {
     let glob = glob.read().await;
-
-    let mut courses: Vec<&Course> = glob.courses.iter().map(|(_, c)| c).collect();
-
-    courses.sort_by(|a, b| {
-        a.level
-            .partial_cmp(&b.level)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
+    let courses = sorted_courses(&glob);
 
     (
         StatusCode::OK,
@@ -568,6 +1037,9 @@ async fn add_course(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
     if let Err(e) = Glob::check_course_for_bad_chars(&crs) {
         return respond_bad_request(e);
     }
+    if let Err(e) = crs.constraints.check() {
+        return respond_bad_request(e);
+    }
 
     {
         let glob = glob.read().await;
@@ -619,6 +1091,9 @@ async fn update_course(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Respons
     if let Err(e) = Glob::check_course_for_bad_chars(&crs) {
         return respond_bad_request(e);
     }
+    if let Err(e) = crs.constraints.check() {
+        return respond_bad_request(e);
+    }
 
     {
         let glob = glob.read().await;
@@ -631,49 +1106,158 @@ async fn update_course(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Respons
     refresh_and_repopulate_courses(glob).await
 }
 
-/**
-Respond to a request to delete a `Course` (and all its constituent `Chapter`s).
-
-Will fail if there are currently any assigned `Goal`s of that `Chapter`.
-
-Req's:
-```text
-x-camp-action: delete-course
-```
-Body should be the `sym` of the `Course` in question.
-*/
-async fn delete_course(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+/// Read the new course levels from the body of a `preview-relevel` or
+/// `relevel-courses` request.
+#[allow(clippy::result_large_err)]
+fn read_levels(body: Option<String>) -> Result<Vec<(String, f32)>, Response> {
     let body = match body {
         Some(body) => body,
         None => {
-            return respond_bad_request("Request requires sym of Course in body.".to_owned());
+            return Err(respond_bad_request(
+                "Request requires a body of course symbols and levels.".to_owned(),
+            ));
         }
     };
 
-    {
-        match glob.read().await.delete_course(&body).await {
-            Ok((n_crs, n_ch)) => {
-                log::trace!("Deleted {} Course, {} Chapters from Data DB.", n_crs, n_ch);
-            }
-            Err(e) => {
-                return text_500(Some(e.to_string()));
-            }
-        };
+    match levels_from_csv(body.as_bytes()) {
+        Ok(levels) if levels.is_empty() => Err(respond_bad_request(
+            "Request doesn't contain any course levels.".to_owned(),
+        )),
+        Ok(levels) => Ok(levels),
+        Err(e) => Err(respond_bad_request(format!("Unable to read course levels: {}", &e))),
     }
-
-    refresh_and_repopulate_courses(glob).await
 }
 
 /**
-Respond to a request to simultaneously add multiple `Chapter`s to a `Course`.
-
-These will generally come in with only meaningful `ch.course_id` and `ch.seq`
-values set, the rest to be filled-in with defaults (and _maybe_ improved
-later.)
+Respond to a request to preview the effects of changing the levels of one
+or more courses (see [`Glob::preview_relevel`]).
 
 Req'ments:
 ```text
-x-camp-action: add-chapters
+x-camp-action: preview-relevel
+```
+
+The body should be lines of `sym, level` (see
+[`levels_from_csv`](crate::course::levels_from_csv)). Response body looks like
+```json
+{
+    "changes": [ { "sym": "pc", "title": "Core Precalculus", "old": 12.1, "new": 12.3 } ],
+    "ties": [ [ "hpc", "pc" ] ],
+    "students": [
+        { "uname": "frog", "last": "Frog", "rest": "Fred", "teacher": "berro", "n_moved": 4 }
+    ]
+}
+```
+*/
+async fn preview_relevel(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let levels = match read_levels(body) {
+        Ok(levels) => levels,
+        Err(resp) => { return resp; },
+    };
+
+    let preview = match glob.read().await.preview_relevel(&levels).await {
+        Ok(preview) => preview,
+        Err(UnifiedError::String(e)) => { return respond_bad_request(e); },
+        Err(e) => {
+            log::error!("Error previewing course levels {:?}: {}", &levels, &e);
+            return text_500(Some(format!("Error previewing new course levels: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("preview-relevel"),
+        )],
+        Json(preview),
+    )
+        .into_response()
+}
+
+/**
+Respond to a request to change the levels of one or more courses, all at
+once. Teachers' pages notice the course data has changed (see
+[`Glob::course_token`]), and reload it and their students' paces.
+
+Req'ments:
+```text
+x-camp-action: relevel-courses
+```
+
+The body is the same as for `preview-relevel`; the response is the same as
+for `populate-courses`.
+*/
+async fn relevel_courses(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let levels = match read_levels(body) {
+        Ok(levels) => levels,
+        Err(resp) => { return resp; },
+    };
+
+    {
+        let glob = glob.read().await;
+        let changes = match glob.preview_relevel(&levels).await {
+            Ok(preview) => preview.changes,
+            Err(UnifiedError::String(e)) => { return respond_bad_request(e); },
+            Err(e) => {
+                log::error!("Error checking course levels {:?}: {}", &levels, &e);
+                return text_500(Some(format!("Error checking new course levels: {}", &e)));
+            }
+        };
+        let levels: Vec<(String, f32)> = changes.into_iter().map(|c| (c.sym, c.new)).collect();
+        if let Err(e) = glob.data().read().await.set_course_levels(&levels).await {
+            log::error!("Error setting course levels {:?}: {}", &levels, &e);
+            return text_500(Some(format!("Unable to set course levels: {}", &e)));
+        }
+        log::info!("Re-leveled {} course(s): {:?}", levels.len(), &levels);
+    }
+
+    refresh_and_repopulate_courses(glob).await
+}
+
+/**
+Respond to a request to delete a `Course` (and all its constituent `Chapter`s).
+
+Will fail if there are currently any assigned `Goal`s of that `Chapter`.
+
+Req's:
+```text
+x-camp-action: delete-course
+```
+Body should be the `sym` of the `Course` in question.
+*/
+async fn delete_course(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request("Request requires sym of Course in body.".to_owned());
+        }
+    };
+
+    {
+        match glob.read().await.delete_course(&body).await {
+            Ok((n_crs, n_ch)) => {
+                log::trace!("Deleted {} Course, {} Chapters from Data DB.", n_crs, n_ch);
+            }
+            Err(e) => {
+                return text_500(Some(e.to_string()));
+            }
+        };
+    }
+
+    refresh_and_repopulate_courses(glob).await
+}
+
+/**
+Respond to a request to simultaneously add multiple `Chapter`s to a `Course`.
+
+These will generally come in with only meaningful `ch.course_id` and `ch.seq`
+values set, the rest to be filled-in with defaults (and _maybe_ improved
+later.)
+
+Req'ments:
+```text
+x-camp-action: add-chapters
 ```
 The body should JSON-decode to a `Vec` of the relevant `Chapter` data.
 */
@@ -794,6 +1378,91 @@ async fn update_chapter(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Respon
     refresh_and_repopulate_courses(glob).await
 }
 
+/// Deserializes the body of a `reorder-chapters` request.
+#[derive(Debug, Deserialize)]
+struct ChapterOrder {
+    sym: String,
+    ids: Vec<i64>,
+}
+
+/**
+Put the chapters of a course in a new order.
+
+The body should look like
+```json
+{ "sym": "pha", "ids": [ 12, 11, 13, 14 ] }
+```
+where `ids` lists the ids of _all_ the course's chapters in their new
+order. The chapters' existing sequence numbers get reassigned in this
+order, and any goals referring to moved chapters are changed to follow
+them (see [`Store::reorder_chapters`](crate::store::Store::reorder_chapters)).
+*/
+async fn reorder_chapters(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires application/json body with course symbol and chapter ids."
+                    .to_owned(),
+            );
+        }
+    };
+
+    let order: ChapterOrder = match serde_json::from_str(&body) {
+        Ok(order) => order,
+        Err(e) => {
+            log::error!(
+                "Error deserializing JSON {:?} as ChapterOrder: {}",
+                &body,
+                &e
+            );
+            return respond_bad_request(format!("Unable to deserialize chapter order: {}", &e));
+        }
+    };
+
+    {
+        let glob = glob.read().await;
+        let crs = match glob.course_by_sym(&order.sym) {
+            Some(crs) => crs,
+            None => {
+                return respond_bad_request(format!("No course with symbol {:?}.", &order.sym));
+            }
+        };
+        let mut given = order.ids.clone();
+        given.sort_unstable();
+        let mut existing: Vec<i64> = crs.all_chapters().map(|ch| ch.id).collect();
+        existing.sort_unstable();
+        if given != existing {
+            return respond_bad_request(format!(
+                "Chapter ids must list each of the {} chapters of {:?} exactly once.",
+                &existing.len(),
+                &order.sym
+            ));
+        }
+
+        let data = glob.data();
+        let res = data
+            .read()
+            .await
+            .reorder_chapters(&order.sym, &order.ids)
+            .await;
+        match res {
+            Ok(n) => {
+                log::info!(
+                    "Reordered chapters of {:?}; {} goals remapped.",
+                    &order.sym,
+                    &n
+                );
+            }
+            Err(e) => {
+                return text_500(Some(format!("Unable to reorder chapters: {}", &e)));
+            }
+        }
+    }
+
+    refresh_and_repopulate_courses(glob).await
+}
+
 //
 //
 // This section is for dealing with the CALENDAR.
@@ -814,6 +1483,7 @@ async fn populate_calendar(glob: Arc<RwLock<Glob>>) -> Response {
         .read()
         .await
         .calendar
+        .days()
         .iter()
         .map(|d| format!("{}", d))
         .collect();
@@ -908,6 +1578,145 @@ async fn update_calendar(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Respo
     refresh_and_repopulate_calendar(glob).await
 }
 
+/// Longest span (in days) an uploaded calendar may cover; anything longer
+/// is more likely a typo in a year than a real academic year.
+const MAX_CALENDAR_SPAN: i64 = 366;
+
+/**
+Read a list of calendar days, one per line or separated by commas (so a
+one-column CSV file or a row of dates will both do), in "2021-01-27"
+format. Blank lines and lines starting with `#` are ignored, as is a first
+line of just `day` or `date` (a CSV header).
+
+Returns the days in order, without duplicates.
+*/
+fn parse_calendar_upload(text: &str) -> Result<Vec<Date>, String> {
+    let mut dates: Vec<Date> = Vec::new();
+    let mut first = true;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if first && matches!(line.to_lowercase().as_str(), "day" | "date") {
+            first = false;
+            continue;
+        }
+        first = false;
+
+        for field in line.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            match Date::parse(field, DATE_FMT) {
+                Ok(d) => dates.push(d),
+                Err(_) => {
+                    return Err(format!(
+                        "Line {}: {:?} isn't a date in YYYY-MM-DD format.",
+                        n + 1,
+                        field
+                    ));
+                }
+            }
+        }
+    }
+
+    dates.sort();
+    dates.dedup();
+    Ok(dates)
+}
+
+/// Check that the (sorted) `dates` could plausibly be an academic year's
+/// working days.
+fn check_calendar_span(dates: &[Date]) -> Result<(), String> {
+    match (dates.first(), dates.last()) {
+        (Some(first), Some(last)) => {
+            if (*last - *first).whole_days() > MAX_CALENDAR_SPAN {
+                Err(format!(
+                    "The calendar would run from {} to {}, which is longer than \
+                    an academic year; check the years of the dates.",
+                    first, last
+                ))
+            } else {
+                Ok(())
+            }
+        }
+        _ => Err("There are no dates in the uploaded calendar.".to_owned()),
+    }
+}
+
+/**
+Respond to a request to upload the list of working days for the current
+academic year from a list of dates (see [`parse_calendar_upload`] for the
+format), instead of clicking them all in on the calendar.
+
+```text
+x-camp-action: upload-calendar
+x-camp-mode: merge      // optional
+```
+
+By default the uploaded days replace the calendar; with `x-camp-mode:
+merge`, they're added to the days already in it. Either way, the change is
+made in one transaction, and is refused if the resulting calendar would
+span more than a year. The response is the same as for `populate-cal`.
+*/
+async fn upload_calendar(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires a text body with a list of dates.".to_owned(),
+            );
+        }
+    };
+    let merge = match headers.get("x-camp-mode").map(|v| v.to_str()) {
+        None | Some(Ok("replace")) => false,
+        Some(Ok("merge")) => true,
+        Some(v) => {
+            return respond_bad_request(format!(
+                "x-camp-mode must be \"replace\" or \"merge\", not {:?}.",
+                v
+            ));
+        }
+    };
+
+    let dates = match parse_calendar_upload(&body) {
+        Ok(dates) => dates,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    {
+        let glob = glob.read().await;
+        let span_check = if merge {
+            let mut all: Vec<Date> = glob.calendar.days().to_vec();
+            all.extend_from_slice(&dates);
+            all.sort();
+            check_calendar_span(&all)
+        } else {
+            check_calendar_span(&dates)
+        };
+        if let Err(e) = span_check {
+            return respond_bad_request(e);
+        }
+
+        let data = glob.data();
+        let reader = data.read().await;
+        let res = if merge {
+            reader.add_to_calendar(&dates).await.map(|_| ())
+        } else {
+            reader.set_calendar(&dates).await.map(|_| ())
+        };
+        if let Err(e) = res {
+            return text_500(Some(format!("Unable to update calendar: {}", &e)));
+        }
+    }
+
+    refresh_and_repopulate_calendar(glob).await
+}
+
 /**
 Generate a `Response` to send all "special dates" to the frontend.
 
@@ -1013,148 +1822,1733 @@ async fn set_date(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
 }
 
 /**
-Respond to a request to delete all student data (all data from the `students`
-table in the database, along with all associated entries in the `users` table,
-as well as all goals.)
-
-Use sparingly.
+Respond with the academic year metadata, earliest year first.
 
 ```text
-x-camp-action: reset-students
+x-camp-action: populate-years
 ```
-*/
-async fn reset_students(glob: Arc<RwLock<Glob>>) -> Response {
-    {
-        let mut glob = glob.write().await;
 
-        let res = glob.yearly_data_nuke().await;
+Response body looks like
+```json
+[
+    {
+        "label": "2022--2023",
+        "starts": "2022-08-29",
+        "ends": "2023-06-02",
+        "active": true
+    },
+    ...
+]
+```
+*/
+async fn populate_years(glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
 
-        if let Err(e) = glob.refresh_users().await {
-            let mut estr = format!(
-                "There was an error refreshing User data from the database: {}",
-                &e
-            );
-            if let Err(e) = res {
-                estr = format!("{}\n{}", &estr, &e);
-            }
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-years"),
+        )],
+        Json(&glob.academic_years),
+    )
+        .into_response()
+}
 
-            return text_500(Some(estr));
-        }
+/// Refresh the `Glob`'s academic years and send them back, after they've
+/// been changed.
+async fn refresh_and_repopulate_years(glob: Arc<RwLock<Glob>>) -> Response {
+    if let Err(e) = glob.write().await.refresh_academic_years().await {
+        log::error!("Error calling Glob::refresh_academic_years(): {}", &e);
+        return text_500(Some("Error retrieving academic years from database.".to_owned()));
     }
 
-    populate_users(glob).await
+    populate_years(glob).await
 }
 
-async fn populate_completion(glob: Arc<RwLock<Glob>>) -> Response {
-    let map = match glob.read().await.data().read().await
-        .get_all_completion_histories().await
-    {
-        Ok(map) => map,
-        Err(e) => {
-            log::error!(
-                "Error attempting to retrieve all completion histories: {}", &e
-            );
-
-            return text_500(Some(format!(
-                "Error retrieving completion history: {}", &e
-            )));
-        },
-    };
-
-    (
-        StatusCode::OK,
-        [
-            (
-                HeaderName::from_static("x-camp-action"),
-                HeaderValue::from_static("populate-completion"),
-            ),
-        ],
-        Json(map),
-    ).into_response()
-}
+/**
+Respond to a request to add or update (by label) an academic year. Making
+a year `active` makes it the current one, in place of whichever year was
+current before.
 
-async fn add_completion(
-    body: Option<String>,
-    headers: &HeaderMap,
-    glob: Arc<RwLock<Glob>>
-) -> Response {
-    let uname = match get_head("x-camp-student", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return respond_bad_request(e); },
-    };
+```text
+x-camp-action: set-year
+```
+Body should be a JSON object like one element of the `populate-years`
+response. Responds like `populate-years`.
+*/
+async fn set_year(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
     let body = match body {
         Some(body) => body,
         None => {
             return respond_bad_request(
-                "Request requires a JSON body with course and term info.".to_owned(),
+                "Request requires a JSON body describing an academic year.".to_owned(),
             );
         }
     };
-    let hist: HistEntry = match serde_json::from_str(&body) {
+    let year: AcademicYear = match serde_json::from_str(&body) {
+        Ok(y) => y,
+        Err(e) => {
+            return respond_bad_request(format!("Unable to read academic year: {}", &e));
+        }
+    };
+    if let Err(e) = year.check() {
+        return respond_bad_request(e);
+    }
+
+    {
+        let glob = glob.read().await;
+        let data = glob.data();
+        let res = data.read().await.set_academic_year(&year).await;
+        if let Err(e) = res {
+            log::error!("Error saving academic year {:?}: {}", &year, &e);
+            return text_500(Some(format!("Error saving academic year: {}", &e)));
+        }
+    }
+
+    refresh_and_repopulate_years(glob).await
+}
+
+/**
+Respond to a request to delete an academic year.
+
+```text
+x-camp-action: delete-year
+```
+Body should be the label of the year. Responds like `populate-years`.
+*/
+async fn delete_year(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let label = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires the label of the academic year as a body.".to_owned(),
+            );
+        }
+    };
+
+    {
+        let glob = glob.read().await;
+        let data = glob.data();
+        let res = data.read().await.delete_academic_year(label.trim()).await;
+        match res {
+            Ok(true) => {}
+            Ok(false) => {
+                return respond_bad_request(format!(
+                    "There is no academic year {:?}.",
+                    label.trim()
+                ));
+            }
+            Err(e) => {
+                log::error!("Error deleting academic year {:?}: {}", &label, &e);
+                return text_500(Some(format!("Error deleting academic year: {}", &e)));
+            }
+        }
+    }
+
+    refresh_and_repopulate_years(glob).await
+}
+
+/**
+Respond with the exam windows (stretches of instructional days on which
+nothing should be due), in order.
+
+```text
+x-camp-action: populate-exam-windows
+```
+
+Response body looks like
+```json
+[
+    {
+        "id": 3,
+        "name": "Fall Exams",
+        "start": "2022-12-12",
+        "end": "2022-12-16"
+    },
+    ...
+]
+```
+*/
+async fn populate_exam_windows(glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-exam-windows"),
+        )],
+        Json(glob.calendar.exam_windows()),
+    )
+        .into_response()
+}
+
+/// Refresh the `Glob`'s calendar (which holds the exam windows) and send
+/// the exam windows back, after they've been changed.
+async fn refresh_and_repopulate_exam_windows(glob: Arc<RwLock<Glob>>) -> Response {
+    if let Err(e) = glob.write().await.refresh_calendar().await {
+        log::error!("Error calling Glob::refresh_calendar(): {}", &e);
+        return text_500(Some("Error retrieving exam windows from database.".to_owned()));
+    }
+
+    populate_exam_windows(glob).await
+}
+
+/**
+Respond to a request to add an exam window. Goals already due during it
+are left alone, but nothing new will be scheduled then.
+
+```text
+x-camp-action: add-exam-window
+```
+Body should be a JSON object like one element of the
+`populate-exam-windows` response, without the `id`. Responds like
+`populate-exam-windows`.
+*/
+async fn add_exam_window(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires a JSON body describing an exam window.".to_owned(),
+            );
+        }
+    };
+    let window: ExamWindow = match serde_json::from_str(&body) {
+        Ok(w) => w,
+        Err(e) => {
+            return respond_bad_request(format!("Unable to read exam window: {}", &e));
+        }
+    };
+    if let Err(e) = window.check() {
+        return respond_bad_request(e);
+    }
+
+    {
+        let glob = glob.read().await;
+        let data = glob.data();
+        let res = data.read().await.insert_exam_window(&window).await;
+        if let Err(e) = res {
+            log::error!("Error saving exam window {:?}: {}", &window, &e);
+            return text_500(Some(format!("Error saving exam window: {}", &e)));
+        }
+    }
+
+    refresh_and_repopulate_exam_windows(glob).await
+}
+
+/**
+Respond to a request to delete an exam window.
+
+```text
+x-camp-action: delete-exam-window
+```
+Body should be the `id` of the window. Responds like
+`populate-exam-windows`.
+*/
+async fn delete_exam_window(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let id: i64 = match body.as_deref().map(|s| s.trim().parse()) {
+        Some(Ok(id)) => id,
+        Some(Err(e)) => {
+            return respond_bad_request(format!("Unable to read exam window id: {}", &e));
+        }
+        None => {
+            return respond_bad_request(
+                "Request requires the id of the exam window as a body.".to_owned(),
+            );
+        }
+    };
+
+    {
+        let glob = glob.read().await;
+        let data = glob.data();
+        let res = data.read().await.delete_exam_window(id).await;
+        match res {
+            Ok(true) => {}
+            Ok(false) => {
+                return respond_bad_request(format!("There is no exam window #{}.", &id));
+            }
+            Err(e) => {
+                log::error!("Error deleting exam window {}: {}", &id, &e);
+                return text_500(Some(format!("Error deleting exam window: {}", &e)));
+            }
+        }
+    }
+
+    refresh_and_repopulate_exam_windows(glob).await
+}
+
+/**
+Respond to a request to preview the effects of the `reset-students` action.
+
+```text
+x-camp-action: preview-reset
+```
+
+Responds with a JSON [`NukePreview`](crate::store::NukePreview); its
+`token` must be sent as the body of the `reset-students` request.
+*/
+async fn preview_reset(glob: Arc<RwLock<Glob>>) -> Response {
+    let preview = match glob.read().await.yearly_nuke_preview().await {
+        Ok(preview) => preview,
+        Err(e) => {
+            log::error!("Error previewing yearly data nuke: {}", &e);
+            return text_500(Some(format!("Error previewing reset: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("preview-reset"),
+        )],
+        Json(preview),
+    )
+        .into_response()
+}
+
+/**
+Respond to a request to delete all student data (all Goals, along with
+report drafts and the sidecar data that goes with them).
+
+Use sparingly.
+
+```text
+x-camp-action: reset-students
+```
+
+The body must be the `token` from a `preview-reset` response generated
+since the data last changed. Everything is archived first; the response
+is the same as `populate-users`, with the path to the archive in the
+`x-camp-archive` header.
+*/
+async fn reset_students(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let token = match body {
+        Some(token) => token,
+        None => {
+            return respond_bad_request(
+                "Request requires a confirmation token from a reset preview.".to_owned(),
+            );
+        }
+    };
+
+    let archive = {
+        let mut glob = glob.write().await;
+
+        let res = glob.yearly_data_nuke(&token).await;
+
+        if let Err(e) = glob.refresh_users().await {
+            let mut estr = format!(
+                "There was an error refreshing User data from the database: {}",
+                &e
+            );
+            if let Err(e) = res {
+                estr = format!("{}\n{}", &estr, &e);
+            }
+
+            return text_500(Some(estr));
+        }
+
+        match res {
+            Ok(path) => path,
+            Err(e) => {
+                log::error!("Error performing yearly data nuke: {}", &e);
+                return respond_bad_request(e.to_string());
+            }
+        }
+    };
+
+    let mut resp = populate_users(glob).await;
+    if let Ok(hval) = HeaderValue::from_str(&archive.display().to_string()) {
+        resp.headers_mut()
+            .insert(HeaderName::from_static("x-camp-archive"), hval);
+    }
+    resp
+}
+
+/**
+Respond with the details of the data saved by each `reset-students`, most
+recent first.
+
+```text
+x-camp-action: populate-archives
+```
+
+The body of the response is a JSON array of
+[`YearArchive`](crate::store::YearArchive)s.
+*/
+async fn populate_archives(glob: Arc<RwLock<Glob>>) -> Response {
+    let archives = match glob.read().await.data().read().await.get_year_archives().await {
+        Ok(archives) => archives,
+        Err(e) => {
+            log::error!("Error reading year archives: {}", &e);
+            return text_500(Some(format!("Error reading archives: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-archives"),
+        )],
+        Json(archives),
+    )
+        .into_response()
+}
+
+/**
+Respond with the data saved by a previous `reset-students`, as a JSON file
+to download.
+
+```text
+x-camp-action: export-archive
+```
+
+The body should be the `id` of the archive (from `populate-archives`). The
+file is a JSON object whose keys are table names and whose values are
+arrays of rows.
+*/
+async fn export_archive(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request("Request requires id of archive in body.".to_owned());
+        }
+    };
+
+    let id: i64 = match body.trim().parse() {
+        Ok(n) => n,
+        Err(e) => {
+            return respond_bad_request(format!(
+                "Unable to parse body of request {:?} as archive id: {}",
+                &body, &e
+            ));
+        }
+    };
+
+    let res = glob.read().await.data().read().await.get_year_archive(id).await;
+    let (archive, dump) = match res {
+        Ok(Some(found)) => found,
+        Ok(None) => {
+            return ApiError::not_found(format!("There is no archive with id {}.", &id))
+                .into_response();
+        }
+        Err(e) => {
+            log::error!("Error reading year archive {}: {}", &id, &e);
+            return text_500(Some(format!("Error reading archive: {}", &e)));
+        }
+    };
+
+    let year: String = archive
+        .year
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    let disposition = format!("attachment; filename=\"camp-archive-{}-{}.json\"", &year, &id);
+
+    (
+        StatusCode::OK,
+        [
+            (
+                axum::http::header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            ),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                // The year has been reduced to ASCII alphanumerics and
+                // punctuation above.
+                HeaderValue::from_str(&disposition).unwrap(),
+            ),
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("export-archive"),
+            ),
+        ],
+        dump,
+    )
+        .into_response()
+}
+
+async fn populate_completion(glob: Arc<RwLock<Glob>>) -> Response {
+    let map = match glob.read().await.data().read().await
+        .get_all_completion_histories().await
+    {
+        Ok(map) => map,
+        Err(e) => {
+            log::error!(
+                "Error attempting to retrieve all completion histories: {}", &e
+            );
+
+            return text_500(Some(format!(
+                "Error retrieving completion history: {}", &e
+            )));
+        },
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("populate-completion"),
+            ),
+        ],
+        Json(map),
+    ).into_response()
+}
+
+async fn add_completion(
+    body: Option<String>,
+    headers: &HeaderMap,
+    glob: Arc<RwLock<Glob>>
+) -> Response {
+    let uname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires a JSON body with course and term info.".to_owned(),
+            );
+        }
+    };
+    let hist: HistEntry = match serde_json::from_str(&body) {
         Ok(hist) => hist,
         Err(e) => {
-            log::error!(
-                "Unable to deserialize completion history data for {:?}: {}\nData: {:?}",
-                uname, &e, &body
-            );
+            log::error!(
+                "Unable to deserialize completion history data for {:?}: {}\nData: {:?}",
+                uname, &e, &body
+            );
+            return respond_bad_request(format!(
+                "Unable to deserialize completion history data: {}", &e
+            ));
+        },
+    };
+
+    if let Err(e) = glob.read().await.add_completion(
+        &uname, hist.year, hist.term, &hist.sym
+    ).await {
+        log::error!(
+            "Error attempting to add completion data for {:?}: {}\nData: {:?}",
+            uname, &e, &hist
+        );
+        return text_500(Some(e.to_string()));
+    }
+
+    update_completion(&uname, glob).await
+}
+
+async fn delete_completion(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let uname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let sym = match CampCourse::from_headers(headers) {
+        Ok(CampCourse(sym)) => sym,
+        Err(resp) => { return resp; },
+    };
+
+    if let Err(e) = glob.read().await.delete_completion(&uname, &sym).await {
+        log::error!(
+            "Error attempting to remove course {:?} from the completion history for {:?}: {}",
+            sym, uname, &e
+        );
+        return text_500(Some(e.to_string()));
+    };
+
+    update_completion(&uname, glob).await
+}
+
+/**
+Respond with the map of other schools' course identifiers to the `sym`s of
+our equivalent courses.
+
+```json
+{ "ALG-1A": "pha", "GEOM": "geo" }
+```
+*/
+async fn populate_equivalents(glob: Arc<RwLock<Glob>>) -> Response {
+    let res = glob.read().await.data().read().await.get_course_equivalents().await;
+    let map = match res {
+        Ok(map) => map,
+        Err(e) => {
+            log::error!("Error retrieving course equivalents: {}", &e);
+            return text_500(Some(format!(
+                "Error retrieving course equivalents: {}", &e
+            )));
+        },
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("populate-equivalents"),
+            ),
+        ],
+        Json(map),
+    ).into_response()
+}
+
+/**
+Set the course equivalent to an external course identifier. The body should
+be a tuple of (identifier, course `sym`) strings:
+
+```json
+("ALG-1A", "pha")
+```
+
+An empty `sym` removes the identifier's equivalence.
+*/
+async fn set_equivalent(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires a body with tuple of (identifier, sym) strings.".to_owned(),
+            );
+        }
+    };
+
+    let (ext, sym): (String, String) = match serde_json::from_str(&body) {
+        Ok(pair) => pair,
+        Err(e) => {
+            return respond_bad_request(format!(
+                "Unable to deserialize identifier and sym: {}", &e
+            ));
+        }
+    };
+    let (ext, sym) = (ext.trim(), sym.trim());
+    if ext.is_empty() {
+        return respond_bad_request("The course identifier can't be blank.".to_owned());
+    }
+
+    {
+        let glob = glob.read().await;
+        let data = glob.data();
+        let data = data.read().await;
+        if sym.is_empty() {
+            if let Err(e) = data.delete_course_equivalent(ext).await {
+                log::error!("Error deleting course equivalent for {:?}: {}", ext, &e);
+                return text_500(Some(e.to_string()));
+            }
+        } else {
+            if glob.course_by_sym(sym).is_none() {
+                return respond_bad_request(format!("There is no course {:?}.", sym));
+            }
+            if let Err(e) = data.set_course_equivalent(ext, sym).await {
+                log::error!("Error setting course equivalent {:?} -> {:?}: {}", ext, sym, &e);
+                return text_500(Some(e.to_string()));
+            }
+        }
+    }
+
+    populate_equivalents(glob).await
+}
+
+/**
+Add other schools' courses (translated to our equivalents) to students'
+completion histories. The body should be CSV text; see
+[`TransferRecord::vec_from_csv_reader`](crate::hist::TransferRecord::vec_from_csv_reader).
+
+Responds with everyone's completion history (like `populate-completion`).
+*/
+async fn import_transfer(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request("Request requires a CSV body.".to_owned());
+        }
+    };
+
+    match glob.read().await.import_transfer_credit(&body).await {
+        Ok((added, skipped)) => {
+            log::info!(
+                "Imported {} transfer courses ({} already recorded).", added, skipped
+            );
+        },
+        Err(e) => {
+            log::error!(
+                "Error importing transfer credit: {}\n\nCSV text:\n\n{}\n", &e, &body
+            );
+            return respond_bad_request(e.to_string());
+        },
+    }
+
+    populate_completion(glob).await
+}
+
+/**
+Respond to a `waive-chapter` request (see [`set_waiver`]) with the
+student's waived chapters.
+
+Response body looks like
+```json
+{ "uname": "jsmith", "waived": [ { "sym": "pha1", "seq": 3 }, ... ] }
+```
+*/
+async fn waive_chapter(uname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let suname = match set_waiver(body, uname, None, &glob).await {
+        Ok(suname) => suname,
+        Err(resp) => { return resp; },
+    };
+
+    let waived = match glob.data().read().await.get_waivers_by_student(&suname).await {
+        Ok(waived) => waived,
+        Err(e) => {
+            log::error!("Error retrieving waivers for {:?}: {}", &suname, &e);
+            return text_500(Some(format!("Unable to retrieve waivers: {}", &e)));
+        },
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("update-waivers"),
+        )],
+        Json(json!({ "uname": suname, "waived": waived })),
+    ).into_response()
+}
+
+/**
+Send the cached per-course statistics, along with the state of the
+background queue that keeps them up to date.
+
+Req'ments:
+```text
+x-camp-action: populate-course-stats
+```
+
+Response body looks like
+```json
+{
+    "stats": [ { "sym": "pha", "n_students": 12, "n_goals": 130, "n_done": 61 }, ... ],
+    "progress": { "pending": 0, "running": null, "completed": 4, "failed": 0, "last_error": null }
+}
+```
+*/
+async fn populate_course_stats(glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+
+    let stats = match glob.data().read().await.get_course_stats().await {
+        Ok(stats) => stats,
+        Err(e) => {
+            log::error!("Error retrieving course stats: {}", &e);
+            return text_500(Some(format!("Unable to retrieve course stats: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-course-stats"),
+        )],
+        Json(json!({
+            "stats": stats,
+            "progress": glob.deferred.progress(),
+        })),
+    )
+        .into_response()
+}
+
+/**
+Schedule a background recomputation of the statistics for every course,
+then respond as to `populate-course-stats`.
+
+Req'ments:
+```text
+x-camp-action: recompute-course-stats
+```
+*/
+async fn recompute_course_stats(glob: Arc<RwLock<Glob>>) -> Response {
+    {
+        let glob = glob.read().await;
+        glob.defer_course_stats(glob.course_syms.keys());
+    }
+
+    populate_course_stats(glob).await
+}
+
+async fn refresh_all(glob: Arc<RwLock<Glob>>) -> Result<(), String> {
+    let mut glob = glob.write().await;
+
+    glob.refresh_users().await?;
+    glob.refresh_courses().await?;
+    glob.refresh_calendar().await?;
+    glob.refresh_dates().await?;
+    glob.refresh_academic_years().await?;
+
+    Ok(())
+}
+
+async fn refresh_wrapper(glob: Arc<RwLock<Glob>>) -> Response {
+    match refresh_all(glob).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(e) => text_500(Some(e)),
+    }
+
+}
+/**
+Report how well the cache of key checks is working.
+
+Req'ments:
+```text
+x-camp-action: key-cache-stats
+```
+
+Response body looks like
+```json
+{ "enabled": true, "hits": 950, "misses": 50, "entries": 12, "hit_rate": 0.95 }
+```
+(`hit_rate` is `null` before any keys have been checked, and everything but
+`enabled` is missing if the cache is turned off.)
+*/
+async fn key_cache_stats(glob: Arc<RwLock<Glob>>) -> Response {
+    let stats = glob.read().await.auth().read().await.key_cache_stats();
+
+    let body = match stats {
+        Some(stats) => {
+            let n_checks = stats.hits + stats.misses;
+            let hit_rate = if n_checks > 0 {
+                Some(stats.hits as f64 / n_checks as f64)
+            } else {
+                None
+            };
+            json!({
+                "enabled": true,
+                "hits": stats.hits,
+                "misses": stats.misses,
+                "entries": stats.entries,
+                "hit_rate": hit_rate,
+            })
+        },
+        None => json!({ "enabled": false }),
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("key-cache-stats"),
+        )],
+        Json(body),
+    ).into_response()
+}
+
+/// Number of failed emails returned by the `failed-emails` action.
+const FAILED_EMAILS: i64 = 200;
+
+/**
+Respond with the most recent outgoing emails that failed for good (after
+running out of retries, or because they couldn't be parsed), newest first.
+*/
+async fn failed_emails(glob: Arc<RwLock<Glob>>) -> Response {
+    let res = glob.read().await.data().read().await.get_failed_emails(FAILED_EMAILS).await;
+    let failed = match res {
+        Ok(failed) => failed,
+        Err(e) => {
+            log::error!("Error retrieving failed emails: {}", &e);
+            return text_500(Some(format!("Unable to retrieve failed emails: {}", &e)));
+        },
+    };
+
+    let body: Vec<serde_json::Value> = failed.iter().map(|f| {
+        let (to, subject) = match crate::email::Email::from_json(&f.email) {
+            Ok(email) => (email.recipients(), email.subject),
+            Err(_) => (String::new(), String::new()),
+        };
+        json!({
+            "id": f.id,
+            "about": &f.about,
+            "to": to,
+            "subject": subject,
+            "created": &f.created,
+            "attempts": f.attempts,
+            "error": &f.error,
+        })
+    }).collect();
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("failed-emails"),
+        )],
+        Json(body),
+    ).into_response()
+}
+
+/**
+Send a ZIP archive of redacted diagnostic information to attach to bug
+reports; see [`support`](super::support) for what's in it.
+
+Req'ments:
+```text
+x-camp-action: support-bundle
+```
+*/
+async fn support_bundle(glob: Arc<RwLock<Glob>>) -> Response {
+    let data = match support::bundle(&*glob.read().await).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Error generating support bundle: {}", &e);
+            return text_500(Some(format!("Error generating support bundle: {}", &e)));
+        },
+    };
+
+    let mut fname = crate::now().to_string();
+    fname.retain(|c| c.is_ascii_digit());
+    let disposition = format!("attachment; filename=\"camp-support-{}.zip\"", &fname);
+    let disposition_value = match HeaderValue::from_str(&disposition) {
+        Ok(val) => val,
+        Err(e) => {
+            log::error!(
+                "Error generating Content-Disposition header value ({:?}): {}",
+                &disposition, &e
+            );
+            return text_500(Some(format!(
+                "Error generating Content-Disposition header value: {}", &e
+            )));
+        },
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                axum::http::header::CONTENT_TYPE,
+                HeaderValue::from_static("application/zip"),
+            ),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                disposition_value,
+            ),
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("support-bundle"),
+            ),
+        ],
+        data
+    ).into_response()
+}
+
+/**
+Send an encrypted export of the auth DB (see
+[`auth_export`](crate::auth_export)) as a download.
+
+Req'ments:
+```text
+x-camp-action: export-auth
+```
+*/
+async fn export_auth(uname: &str, glob: Arc<RwLock<Glob>>) -> Response {
+    let text = match crate::auth_export::export(&*glob.read().await).await {
+        Ok(text) => text,
+        Err(e) => {
+            log::error!("Error exporting auth DB: {}", &e);
+            return text_500(Some(e));
+        },
+    };
+    log::warn!("Admin {:?} exported the auth DB.", uname);
+
+    let mut fname = crate::now().to_string();
+    fname.retain(|c| c.is_ascii_digit());
+    let disposition = format!("attachment; filename=\"camp-auth-{}.txt\"", &fname);
+
+    (
+        StatusCode::OK,
+        [
+            (
+                axum::http::header::CONTENT_TYPE,
+                HeaderValue::from_static("text/plain"),
+            ),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                // This is only ASCII digits and punctuation.
+                HeaderValue::from_str(&disposition).unwrap(),
+            ),
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("export-auth"),
+            ),
+        ],
+        text
+    ).into_response()
+}
+
+/**
+Check an auth DB export against the users in the data DB without
+restoring anything.
+
+Req'ments:
+```text
+x-camp-action: verify-auth
+```
+with the export as the body.
+
+Response body looks like
+```json
+{
+    "created": "2023-01-20",
+    "n_records": 214,
+    "n_matched": 211,
+    "salt_mismatches": ["jsmith"],
+    "not_in_store": ["graduated"],
+    "not_in_export": ["newkid"]
+}
+```
+Users in `salt_mismatches` have had their salts changed since the export
+was made; an export with any of them can't be restored.
+*/
+async fn verify_auth(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request("Request needs an auth DB export in the body.".to_owned());
+        },
+    };
+
+    let v = match crate::auth_export::read(&body, &*glob.read().await) {
+        Ok((_, v)) => v,
+        Err(e) => { return respond_bad_request(e); },
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("verify-auth"),
+        )],
+        Json(v),
+    ).into_response()
+}
+
+/**
+Restore the auth DB from an export, if its salts all match the data DB
+(see [`verify_auth`]). Users not in the data DB are left out, and users
+not in the export are left alone.
+
+Req'ments:
+```text
+x-camp-action: import-auth
+```
+with the export as the body.
+
+Responds like `verify-auth`, with an additional `"n_restored"` field.
+*/
+async fn import_auth(uname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request("Request needs an auth DB export in the body.".to_owned());
+        },
+    };
+
+    let (v, n) = match crate::auth_export::restore(&body, &*glob.read().await).await {
+        Ok(x) => x,
+        Err(e) => {
+            log::error!("Error restoring auth DB: {}", &e);
+            return respond_bad_request(e);
+        },
+    };
+    log::warn!(
+        "Admin {:?} restored {} users to the auth DB from an export made {:?}.",
+        uname, &n, &v.created
+    );
+
+    let mut body = json!(v);
+    body["n_restored"] = json!(n);
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("import-auth"),
+        )],
+        Json(body),
+    ).into_response()
+}
+
+/**
+Revoke all of a user's keys, logging them out everywhere.
+
+Req'ments:
+```text
+x-camp-action: revoke-keys
+```
+with the user's uname as the body.
+*/
+async fn revoke_keys(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let uname = match body {
+        Some(uname) => uname,
+        None => { return respond_bad_request("Request needs a uname in the body.".to_owned()); },
+    };
+
+    let glob = glob.read().await;
+    if !glob.users.contains_key(&uname) {
+        return respond_bad_request(format!("There is no user {:?}.", &uname));
+    }
+
+    let res = glob.auth().read().await.revoke_keys(&uname, None).await;
+    match res {
+        Ok(n) => {
+            log::info!("Revoked {} keys of {:?}.", &n, &uname);
+            (
+                StatusCode::OK,
+                [(
+                    HeaderName::from_static("x-camp-action"),
+                    HeaderValue::from_static("none"),
+                )],
+                format!("Revoked {} keys of {:?}.", &n, &uname),
+            ).into_response()
+        },
+        Err(e) => {
+            log::error!("Error revoking keys of {:?}: {}", &uname, &e);
+            text_500(Some(format!("Unable to revoke keys: {}", &e)))
+        },
+    }
+}
+
+/**
+Send the list of journaled failed requests (newest first, without their
+bodies); see [`journal`](crate::inter::journal).
+
+Req'ments:
+```text
+x-camp-action: populate-failures
+```
+*/
+async fn populate_failures(glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+
+    let failures = match glob
+        .data()
+        .read()
+        .await
+        .get_failed_requests(glob.failure_journal)
+        .await
+    {
+        Ok(failures) => failures,
+        Err(e) => {
+            log::error!("Error retrieving failed request journal: {}", &e);
+            return text_500(Some(format!("Unable to retrieve failed requests: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-failures"),
+        )],
+        Json(failures),
+    )
+        .into_response()
+}
+
+/**
+Send entries from the audit log (newest first); see
+[`Store::log_action`](crate::store::Store::log_action).
+
+Req'ments:
+```text
+x-camp-action: audit-log
+```
+The body, if any, should JSON-deserialize into an [`AuditFilter`], like
+```json
+{ "actor": "berro", "target": "frog", "limit": 50, "offset": 50 }
+```
+All fields are optional.
+*/
+async fn audit_log(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let filter: AuditFilter = match body.as_deref().map(str::trim) {
+        None | Some("") => AuditFilter::default(),
+        Some(body) => match serde_json::from_str(body) {
+            Ok(filter) => filter,
+            Err(e) => {
+                return respond_bad_request(format!("Unable to read audit log filter: {}", &e));
+            }
+        },
+    };
+
+    let res = glob.read().await.data().read().await.get_audit_log(&filter).await;
+    let entries = match res {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Error retrieving audit log ({:?}): {}", &filter, &e);
+            return text_500(Some(format!("Unable to retrieve audit log: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("audit-log"),
+        )],
+        Json(entries),
+    )
+        .into_response()
+}
+
+/**
+Send the full details (including request and response bodies) of a single
+journaled failed request.
+
+Req'ments:
+```text
+x-camp-action: inspect-failure
+```
+Body should be the id of the journal entry.
+*/
+async fn inspect_failure(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request("Request requires id of failure in body.".to_owned());
+        }
+    };
+
+    let id: i64 = match body.trim().parse() {
+        Ok(n) => n,
+        Err(e) => {
             return respond_bad_request(format!(
-                "Unable to deserialize completion history data: {}", &e
+                "Unable to parse body of request {:?} as failure id: {}",
+                &body, &e
             ));
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .get_failed_request(id)
+        .await;
+    let failure = match res {
+        Ok(Some(failure)) => failure,
+        Ok(None) => {
+            return respond_bad_request(format!("No journaled failure with id {}.", &id));
+        }
+        Err(e) => {
+            log::error!("Error retrieving failed request {}: {}", &id, &e);
+            return text_500(Some(format!("Unable to retrieve failed request: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("inspect-failure"),
+        )],
+        Json(failure),
+    )
+        .into_response()
+}
+
+/**
+Respond with the permissions of every restricted Admin, as a JSON object
+mapping unames to arrays of [`Permission`]s. Admins who aren't in it are
+unrestricted.
+
+```text
+x-camp-action: populate-permissions
+```
+*/
+async fn populate_permissions(glob: Arc<RwLock<Glob>>) -> Response {
+    let res = glob.read().await.data().read().await.get_all_admin_permissions().await;
+    let map = match res {
+        Ok(map) => map,
+        Err(e) => {
+            log::error!("Error retrieving Admin permissions: {}", &e);
+            return text_500(Some(format!(
+                "Error retrieving Admin permissions: {}", &e
+            )));
         },
     };
 
-    if let Err(e) = glob.read().await.add_completion(
-        uname, hist.year, hist.term, &hist.sym
-    ).await {
-        log::error!(
-            "Error attempting to add completion data for {:?}: {}\nData: {:?}",
-            uname, &e, &hist
-        );
-        return text_500(Some(e.to_string()));
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("populate-permissions"),
+            ),
+        ],
+        Json(map),
+    ).into_response()
+}
+
+/// Deserializes the body of a `set-permissions` request.
+#[derive(Debug, Deserialize)]
+struct PermissionsData {
+    uname: String,
+    /// `None` lifts all restrictions.
+    permissions: Option<Vec<Permission>>,
+}
+
+/**
+Restrict an Admin to the given permissions, or lift their restrictions.
+
+```text
+x-camp-action: set-permissions
+```
+with a JSON body like
+```json
+{ "uname": "office", "permissions": ["Users", "Calendar"] }
+```
+where `"permissions": null` makes the Admin unrestricted. Admins can't
+change their own permissions (so there's always at least one unrestricted
+Admin to undo any mistakes). Responds like `populate-permissions`.
+*/
+async fn set_permissions(
+    requester: &str,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires a JSON body with the Admin's uname and permissions.".to_owned(),
+            );
+        }
+    };
+    let mut pdata: PermissionsData = match serde_json::from_str(&body) {
+        Ok(pdata) => pdata,
+        Err(e) => {
+            return respond_bad_request(format!(
+                "Unable to deserialize permissions: {}", &e
+            ));
+        }
+    };
+
+    if pdata.uname == requester {
+        return respond_bad_request("You can't change your own permissions.".to_owned());
     }
+    if let Some(perms) = pdata.permissions.as_mut() {
+        perms.sort();
+        perms.dedup();
+    }
+
+    {
+        let glob = glob.read().await;
+        match glob.users.get(&pdata.uname) {
+            Some(User::Admin(_)) => {},
+            _ => {
+                return respond_bad_request(format!(
+                    "{:?} is not an Admin.", &pdata.uname
+                ));
+            },
+        }
+
+        let res = glob.data().read().await
+            .set_admin_permissions(&pdata.uname, pdata.permissions.as_deref()).await;
+        if let Err(e) = res {
+            log::error!("Error setting permissions {:?}: {}", &pdata, &e);
+            return text_500(Some(format!("Unable to set permissions: {}", &e)));
+        }
+    }
+    log::info!(
+        "Admin {:?} set {:?}'s permissions to {:?}.",
+        requester, &pdata.uname, &pdata.permissions
+    );
 
-    update_completion(uname, glob).await
+    populate_permissions(glob).await
 }
 
-async fn delete_completion(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
-    let uname = match get_head("x-camp-student", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return respond_bad_request(e); },
+/// The unames of the Teachers in training mode, in order.
+fn training_data(glob: &Glob) -> Vec<&str> {
+    let mut unames: Vec<&str> = glob.training.iter().map(String::as_str).collect();
+    unames.sort_unstable();
+    unames
+}
+
+/**
+Respond with the unames of the Teachers in training mode (see
+[`Glob::sandbox`]), as a JSON array.
+
+```text
+x-camp-action: populate-training
+```
+*/
+async fn populate_training(glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("populate-training"),
+            ),
+        ],
+        Json(training_data(&glob)),
+    ).into_response()
+}
+
+/// Deserializes the body of a `set-training` request.
+#[derive(Debug, Deserialize)]
+struct TrainingData {
+    uname: String,
+    training: bool,
+}
+
+/// Check that `uname` is a Teacher's.
+#[allow(clippy::result_large_err)]
+fn check_teacher(uname: &str, glob: &Glob) -> Result<(), Response> {
+    match glob.users.get(uname) {
+        Some(User::Teacher(_)) => Ok(()),
+        _ => Err(respond_bad_request(format!("{:?} is not a Teacher.", uname))),
+    }
+}
+
+/**
+Put a Teacher into training mode, with a fresh sandbox copied from their
+class, or take them out of it, throwing their sandbox away.
+
+```text
+x-camp-action: set-training
+```
+with a JSON body like
+```json
+{ "uname": "berro", "training": true }
+```
+Putting a Teacher who's already in training mode into it leaves their
+sandbox alone. Responds like `populate-training`.
+*/
+async fn set_training(
+    requester: &str,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires a JSON body with the Teacher's uname.".to_owned(),
+            );
+        }
     };
-    let sym = match get_head("x-camp-course", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return respond_bad_request(e); },
+    let tdata: TrainingData = match serde_json::from_str(&body) {
+        Ok(tdata) => tdata,
+        Err(e) => {
+            return respond_bad_request(format!(
+                "Unable to deserialize training mode setting: {}", &e
+            ));
+        }
     };
 
-    if let Err(e) = glob.read().await.delete_completion(uname, sym).await {
-        log::error!(
-            "Error attempting to remove course {:?} from the completion history for {:?}: {}",
-            sym, uname, &e
-        );
-        return text_500(Some(e.to_string()));
+    {
+        let mut glob = glob.write().await;
+        if let Err(resp) = check_teacher(&tdata.uname, &glob) {
+            return resp;
+        }
+
+        let res = glob.data().read().await
+            .set_training(&tdata.uname, tdata.training).await;
+        if let Err(e) = res {
+            log::error!("Error setting training mode {:?}: {}", &tdata, &e);
+            return text_500(Some(format!("Unable to set training mode: {}", &e)));
+        }
+        if tdata.training {
+            glob.training.insert(tdata.uname.clone());
+        } else {
+            glob.training.remove(&tdata.uname);
+        }
+    }
+    log::info!(
+        "Admin {:?} turned training mode {} for {:?}.",
+        requester, if tdata.training { "on" } else { "off" }, &tdata.uname
+    );
+
+    populate_training(glob).await
+}
+
+/**
+Throw away everything a Teacher in training mode has done in their sandbox,
+and start it over from their class as it is now.
+
+```text
+x-camp-action: reset-sandbox
+```
+with the Teacher's uname as the body. Responds like `populate-training`.
+*/
+async fn reset_sandbox(
+    requester: &str,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let tuname = match body {
+        Some(uname) => uname,
+        None => {
+            return respond_bad_request("Request needs a uname in the body.".to_owned());
+        },
     };
 
-    update_completion(uname, glob).await
+    {
+        let glob = glob.read().await;
+        if let Err(resp) = check_teacher(&tuname, &glob) {
+            return resp;
+        }
+
+        let res = glob.data().read().await.reset_sandbox(&tuname).await;
+        match res {
+            Ok(true) => {},
+            Ok(false) => {
+                return respond_bad_request(format!(
+                    "{:?} isn't in training mode.", &tuname
+                ));
+            },
+            Err(e) => {
+                log::error!("Error resetting sandbox of {:?}: {}", &tuname, &e);
+                return text_500(Some(format!("Unable to reset sandbox: {}", &e)));
+            },
+        }
+    }
+    log::info!("Admin {:?} reset the sandbox of {:?}.", requester, &tuname);
+
+    populate_training(glob).await
 }
 
-async fn refresh_all(glob: Arc<RwLock<Glob>>) -> Result<(), String> {
-    let mut glob = glob.write().await;
+/**
+Respond with the queue of Teachers' requests for printed chapter tests
+(see [`Store::request_test`](crate::store::Store::request_test)), oldest
+first, as a JSON array of [`TestRequest`](crate::store::TestRequest)s.
 
-    glob.refresh_users().await?;
-    glob.refresh_courses().await?;
-    glob.refresh_calendar().await?;
-    glob.refresh_dates().await?;
+```text
+x-camp-action: populate-test-requests
+```
+Delivered tests are left out, unless the body is `all`.
+*/
+async fn populate_test_requests(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let all = matches!(body.as_deref().map(str::trim), Some("all"));
 
-    Ok(())
+    let res = glob.read().await.data().read().await.get_test_requests(all).await;
+    let requests = match res {
+        Ok(requests) => requests,
+        Err(e) => {
+            log::error!("Error retrieving test requests: {}", &e);
+            return text_500(Some(format!("Unable to retrieve test requests: {}", &e)));
+        },
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("populate-test-requests"),
+            ),
+        ],
+        Json(requests),
+    ).into_response()
 }
 
-async fn refresh_wrapper(glob: Arc<RwLock<Glob>>) -> Response {
-    match refresh_all(glob).await {
-        Ok(()) => StatusCode::OK.into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            e
-        ).into_response(),
+/// Deserializes the body of a `set-test-status` request.
+#[derive(Debug, Deserialize)]
+struct TestStatusData {
+    id: i64,
+    status: TestStatus,
+    /// Whether to respond with delivered tests, too.
+    #[serde(default)]
+    all: bool,
+}
+
+/**
+Mark a requested test printed or delivered (or back to requested).
+
+```text
+x-camp-action: set-test-status
+```
+With a JSON body like
+```json
+{ "id": 12, "status": "printed", "all": false }
+```
+(`all` is optional.)
+
+Responds with the queue, like `populate-test-requests`; delivered tests
+are included if `all` is true.
+*/
+async fn set_test_status(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let sdata: TestStatusData = match body.as_deref().map(serde_json::from_str) {
+        Some(Ok(sdata)) => sdata,
+        Some(Err(e)) => {
+            return respond_bad_request(format!("Unable to read test status: {}", &e));
+        },
+        None => {
+            return respond_bad_request(
+                "Request needs a JSON body with the id and status.".to_owned(),
+            );
+        },
+    };
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .set_test_status(sdata.id, sdata.status)
+        .await;
+    match res {
+        Ok(true) => {},
+        Ok(false) => {
+            return ApiError::not_found(format!("There is no test request #{}.", &sdata.id))
+                .into_response();
+        },
+        Err(e) => {
+            log::error!("Error setting status of test request {:?}: {}", &sdata, &e);
+            return text_500(Some(format!("Unable to update test request: {}", &e)));
+        },
+    }
+
+    let body = sdata.all.then(|| "all".to_owned());
+    populate_test_requests(body, glob).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admin_permissions() {
+        use Permission::*;
+
+        // Unrestricted Admins can do anything, even nonsense.
+        assert!(check_permissions("support-bundle", None).is_ok());
+        assert!(check_permissions("frobnicate", None).is_ok());
+
+        let office = [Users, Calendar];
+        assert!(check_permissions("populate-courses", Some(&office)).is_ok());
+        assert!(check_permissions("add-user", Some(&office)).is_ok());
+        assert!(check_permissions("reassign-students", Some(&office)).is_ok());
+        assert!(check_permissions("reassign-students", Some(&[Calendar])).is_err());
+        assert!(check_permissions("set-date", Some(&office)).is_ok());
+        assert!(check_permissions("upload-calendar", Some(&office)).is_ok());
+        assert!(check_permissions("set-year", Some(&office)).is_ok());
+        assert!(check_permissions("populate-years", Some(&[])).is_ok());
+        assert!(check_permissions("delete-year", Some(&[Users])).is_err());
+        assert!(check_permissions("add-exam-window", Some(&office)).is_ok());
+        assert!(check_permissions("populate-exam-windows", Some(&[])).is_ok());
+        assert!(check_permissions("delete-exam-window", Some(&[Users])).is_err());
+        // Deleting calendar data takes Destructive, too.
+        assert!(check_permissions("delete-year", Some(&office)).is_err());
+        assert!(check_permissions("delete-exam-window", Some(&office)).is_err());
+        assert!(check_permissions("delete-exam-window", Some(&[Calendar, Destructive])).is_ok());
+        assert!(check_permissions("export-archive", Some(&[])).is_err());
+        assert!(check_permissions("export-archive", Some(&office)).is_ok());
+        assert!(check_permissions("populate-archives", Some(&[])).is_ok());
+        assert!(check_permissions("reset-sandbox", Some(&office)).is_ok());
+        assert!(check_permissions("update-course", Some(&office)).is_err());
+        assert!(check_permissions("delete-user", Some(&office)).is_err());
+        assert!(check_permissions("support-bundle", Some(&Permission::ALL)).is_err());
+        assert!(check_permissions("set-permissions", Some(&Permission::ALL)).is_err());
+        assert!(check_permissions("frobnicate", Some(&Permission::ALL)).is_err());
+        assert!(check_permissions("audit-log", Some(&Permission::ALL)).is_err());
+        assert!(check_permissions("populate-webhooks", Some(&Permission::ALL)).is_err());
+        assert!(check_permissions("failed-emails", Some(&Permission::ALL)).is_err());
+        assert!(check_permissions("preview-report-template", Some(&[])).is_ok());
+        assert!(check_permissions("upload-report-template", Some(&Permission::ALL)).is_err());
+        assert!(check_permissions("activate-report-template", Some(&Permission::ALL)).is_err());
+
+        let registrar = [Users, Destructive];
+        assert!(check_permissions("delete-user", Some(&registrar)).is_ok());
+        assert!(check_permissions("delete-course", Some(&registrar)).is_err());
+        assert!(check_permissions("populate-cal", Some(&[])).is_ok());
+        assert!(check_permissions("populate-training", Some(&[])).is_ok());
+        assert!(check_permissions("set-test-status", Some(&[])).is_err());
+        assert!(check_permissions("set-test-status", Some(&registrar)).is_ok());
+        assert!(check_permissions("set-training", Some(&[])).is_err());
+        assert!(check_permissions("update-cal", Some(&[])).is_err());
+    }
+
+    #[test]
+    fn admin_targets() {
+        let users: HashMap<String, User> = [
+            r#"{"Admin": {"uname": "root", "role": "Admin", "salt": "", "email": "r@camp"}}"#,
+            r#"{"Boss": {"uname": "boss", "role": "Boss", "salt": "", "email": "b@camp"}}"#,
+        ]
+        .iter()
+        .map(|j| {
+            let u: User = serde_json::from_str(j).unwrap();
+            (u.uname().to_owned(), u)
+        })
+        .collect();
+
+        let new_admin = r#"{"Admin": {"uname": "me2", "role": "Admin", "salt": "", "email": "x"}}"#;
+        assert!(touches_admin("add-user", Some(new_admin), &users));
+        // Taking over an Admin's account by changing their email address.
+        let hijack = r#"{"Admin": {"uname": "root", "role": "Admin", "salt": "", "email": "x"}}"#;
+        assert!(touches_admin("update-user", Some(hijack), &users));
+        // Even claiming to be some other kind of user.
+        let sneaky = r#"{"Boss": {"uname": "root", "role": "Boss", "salt": "", "email": "x"}}"#;
+        assert!(touches_admin("update-user", Some(sneaky), &users));
+        assert!(touches_admin("delete-user", Some("root"), &users));
+        assert!(touches_admin("revoke-keys", Some("root"), &users));
+        assert!(touches_admin("unlock-user", Some("root"), &users));
+
+        let boss = r#"{"Boss": {"uname": "boss", "role": "Boss", "salt": "", "email": "x"}}"#;
+        assert!(!touches_admin("update-user", Some(boss), &users));
+        assert!(!touches_admin("delete-user", Some("boss"), &users));
+        assert!(!touches_admin("delete-user", None, &users));
+        assert!(!touches_admin("populate-users", Some("root"), &users));
+    }
+
+    #[tokio::test]
+    async fn reassign_input_errors() {
+        let users: Vec<User> = [
+            r#"{"Teacher": {"base": {"uname": "berro", "role": "Teacher", "salt": "",
+                "email": "b@camp"}, "name": "Mr Berro"}}"#,
+            r#"{"Boss": {"uname": "boss", "role": "Boss", "salt": "", "email": "x@camp"}}"#,
+        ]
+        .iter()
+        .map(|j| serde_json::from_str(j).unwrap())
+        .collect();
+        let store = crate::store::MemStore::new().with_users(users);
+        let glob = Arc::new(RwLock::new(crate::config::in_memory(store).await.unwrap()));
+
+        let reassign = |from: &str, to: &str| {
+            let body = format!(r#"{{"from": {:?}, "to": {:?}}}"#, from, to);
+            reassign_students("admin", Some(body), glob.clone())
+        };
+        assert_eq!(reassign("berro", "berro").await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(reassign("berro", "boss").await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(reassign("nobody", "berro").await.status(), StatusCode::BAD_REQUEST);
+        let resp = reassign_students("admin", Some("berro".to_owned()), glob.clone()).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn calendar_uploads() {
+        use time::macros::date;
+
+        let text = "day\n2022-09-07\n\n# Labor Day off\n2022-09-06, 2022-09-08\n2022-09-07\n";
+        let dates = parse_calendar_upload(text).unwrap();
+        assert_eq!(
+            dates,
+            vec![date!(2022 - 09 - 06), date!(2022 - 09 - 07), date!(2022 - 09 - 08)]
+        );
+        assert!(check_calendar_span(&dates).is_ok());
+
+        let e = parse_calendar_upload("2022-09-06\n9/7/2022\n").unwrap_err();
+        assert!(e.starts_with("Line 2:"));
+        // Only the first line can be a header.
+        assert!(parse_calendar_upload("2022-09-06\nday\n").is_err());
+
+        assert!(check_calendar_span(&[]).is_err());
+        let typo = parse_calendar_upload("2022-09-06\n2032-06-01").unwrap();
+        assert!(check_calendar_span(&typo).is_err());
+    }
+}
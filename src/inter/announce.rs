@@ -0,0 +1,226 @@
+/*!
+Announcements the Boss posts to the tops of Teachers' and students' pages.
+
+The Boss API's `populate-announcements`, `add-announcement`, and
+`expire-announcement` actions manage them. The Teacher page gets the active
+ones in its `bootstrap` response, and the student page has them rendered
+right into it (see [`active`]).
+*/
+use std::sync::Arc;
+
+use axum::{
+    http::header::{HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use tokio::sync::RwLock;
+
+use super::{respond_bad_request, text_500};
+use crate::{config::Glob, store::Announcement, user::Role};
+
+/// The Roles whose pages can show announcements.
+const AUDIENCES: &[Role] = &[Role::Teacher, Role::Student];
+
+/// Return the announcements users with the given `role` should see today.
+///
+/// Failing to fetch them shouldn't keep anyone from their page, so errors
+/// are logged and treated as there being none.
+pub async fn active(role: Role, glob: &Glob) -> Vec<Announcement> {
+    let res = glob
+        .data()
+        .read()
+        .await
+        .get_active_announcements(role, &crate::now())
+        .await;
+
+    match res {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("Error retrieving {} announcements: {}", &role, &e);
+            Vec::new()
+        }
+    }
+}
+
+/// Make sure an announcement from the Boss is one we can show.
+fn validate(a: &Announcement) -> Result<(), String> {
+    if a.message.trim().is_empty() {
+        return Err("Announcement has no message.".to_owned());
+    }
+    if a.roles.is_empty() {
+        return Err("Announcement has no audience.".to_owned());
+    }
+    if let Some(r) = a.roles.iter().find(|r| !AUDIENCES.contains(r)) {
+        return Err(format!("Announcements can't be shown to {}s.", r));
+    }
+    if a.ends < a.starts {
+        return Err(format!(
+            "Announcement would end ({}) before it starts ({}).",
+            &a.ends, &a.starts
+        ));
+    }
+    Ok(())
+}
+
+/**
+Respond with every announcement, most recently started first.
+
+Req'ments:
+```text
+x-camp-action: populate-announcements
+```
+*/
+pub async fn populate(glob: Arc<RwLock<Glob>>) -> Response {
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .get_announcements()
+        .await;
+    let announcements = match res {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("Error retrieving announcements: {}", &e);
+            return text_500(Some(format!("Unable to retrieve announcements: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-announcements"),
+        )],
+        Json(announcements),
+    )
+        .into_response()
+}
+
+/**
+Post a new announcement.
+
+Req'ments:
+```text
+x-camp-action: add-announcement
+```
+with a JSON body like
+```json
+{
+    "message": "No school Monday.",
+    "roles": ["Teacher", "Student"],
+    "starts": "2022-10-03",
+    "ends": "2022-10-10"
+}
+```
+*/
+pub async fn add(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request must have a JSON body describing the announcement.".to_owned(),
+            );
+        }
+    };
+    let mut a: Announcement = match serde_json::from_str(&body) {
+        Ok(a) => a,
+        Err(e) => {
+            return respond_bad_request(format!("Unable to deserialize announcement: {}", &e));
+        }
+    };
+    a.message = a.message.trim().to_owned();
+    if let Err(e) = validate(&a) {
+        return respond_bad_request(e);
+    }
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .insert_announcement(&a)
+        .await;
+    if let Err(e) = res {
+        log::error!("Error inserting announcement {:?}: {}", &a, &e);
+        return text_500(Some(format!("Unable to save announcement: {}", &e)));
+    }
+
+    populate(glob).await
+}
+
+/**
+Stop showing the announcement whose id is the body of the request.
+
+Req'ments:
+```text
+x-camp-action: expire-announcement
+```
+*/
+pub async fn expire(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let id: i64 = match body.as_deref().map(|s| s.trim().parse()) {
+        Some(Ok(id)) => id,
+        _ => {
+            return respond_bad_request(
+                "Request body must be the id of the announcement to expire.".to_owned(),
+            );
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .expire_announcement(id, &crate::now())
+        .await;
+    match res {
+        Ok(true) => populate(glob).await,
+        Ok(false) => {
+            respond_bad_request(format!("There is no current announcement with id {}.", &id))
+        }
+        Err(e) => {
+            log::error!("Error expiring announcement {}: {}", &id, &e);
+            text_500(Some(format!("Unable to expire announcement: {}", &e)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use time::macros::date;
+
+    #[test]
+    fn validate_announcements() {
+        let good = Announcement {
+            id: 0,
+            message: "No school Monday.".to_owned(),
+            roles: vec![Role::Student],
+            starts: date!(2022 - 10 - 3),
+            ends: date!(2022 - 10 - 3),
+        };
+        assert!(validate(&good).is_ok());
+
+        let mut bad = good.clone();
+        bad.message = "  ".to_owned();
+        assert!(validate(&bad).is_err());
+
+        let mut bad = good.clone();
+        bad.roles = vec![];
+        assert!(validate(&bad).is_err());
+
+        let mut bad = good.clone();
+        bad.roles = vec![Role::Teacher, Role::Admin];
+        assert!(validate(&bad).is_err());
+
+        let mut bad = good.clone();
+        bad.ends = date!(2022 - 10 - 2);
+        assert!(validate(&bad).is_err());
+    }
+}
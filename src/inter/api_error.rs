@@ -0,0 +1,149 @@
+/*!
+Errors from the JSON APIs (the Admin, Boss, Teacher, and Student APIs).
+
+Every error response from an API has a JSON body like
+```json
+{
+    "code": "bad-request",
+    "message": "There is no course \"pha\".",
+    "detail": null
+}
+```
+so frontends can tell kinds of errors apart without reading the message,
+which is meant for people. `code` follows the response's HTTP status (see
+[`ApiError::code`]); `detail` is anything else that might help a frontend
+act on the error (like which line of an upload was bad), or `null`.
+
+(The HTML routes have error pages instead; see [`error_page`](super::error_page).)
+*/
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::TEXT_500;
+
+/// An error response from one of the JSON APIs; see the module-level docs.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ApiError {
+    #[serde(skip)]
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    detail: Option<Value>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: String) -> ApiError {
+        ApiError {
+            status,
+            code: ApiError::code(status),
+            message,
+            detail: None,
+        }
+    }
+
+    /// The `code` that goes with each HTTP `status`.
+    pub fn code(status: StatusCode) -> &'static str {
+        match status {
+            StatusCode::BAD_REQUEST => "bad-request",
+            StatusCode::UNAUTHORIZED => "unauthorized",
+            StatusCode::FORBIDDEN => "forbidden",
+            StatusCode::NOT_FOUND => "not-found",
+            StatusCode::CONFLICT => "conflict",
+            StatusCode::PAYLOAD_TOO_LARGE => "too-large",
+            StatusCode::TOO_MANY_REQUESTS => "too-many-requests",
+            StatusCode::SERVICE_UNAVAILABLE => "unavailable",
+            s if s.is_client_error() => "client-error",
+            _ => "internal",
+        }
+    }
+
+    pub fn bad_request(message: String) -> ApiError {
+        ApiError::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn unauthorized(message: String) -> ApiError {
+        ApiError::new(StatusCode::UNAUTHORIZED, message)
+    }
+
+    pub fn forbidden(message: String) -> ApiError {
+        ApiError::new(StatusCode::FORBIDDEN, message)
+    }
+
+    pub fn not_found(message: String) -> ApiError {
+        ApiError::new(StatusCode::NOT_FOUND, message)
+    }
+
+    pub fn conflict(message: String) -> ApiError {
+        ApiError::new(StatusCode::CONFLICT, message)
+    }
+
+    /// An INTERNAL SERVER ERROR, with a generic message if there's no
+    /// better one.
+    pub fn internal(message: Option<String>) -> ApiError {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            message.unwrap_or_else(|| TEXT_500.to_owned()),
+        )
+    }
+
+    /// Attach some machine-readable `detail` to the error.
+    pub fn with_detail<T: Serialize>(mut self, detail: &T) -> ApiError {
+        match serde_json::to_value(detail) {
+            Ok(v) => {
+                self.detail = Some(v);
+            }
+            Err(e) => {
+                log::error!("Unable to serialize ApiError detail: {}", &e);
+            }
+        }
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(&self)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_error_envelopes() {
+        let e = ApiError::forbidden("The student \"frog\" is not yours.".to_owned());
+        assert_eq!(e.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            serde_json::to_value(&e).unwrap(),
+            serde_json::json!({
+                "code": "forbidden",
+                "message": "The student \"frog\" is not yours.",
+                "detail": null,
+            })
+        );
+
+        let e = ApiError::bad_request("Bad line.".to_owned()).with_detail(&[3, 5]);
+        assert_eq!(
+            serde_json::to_value(&e).unwrap(),
+            serde_json::json!({ "code": "bad-request", "message": "Bad line.", "detail": [3, 5] })
+        );
+
+        let e = ApiError::internal(None);
+        assert_eq!(e.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(e.message(), TEXT_500);
+        assert_eq!(ApiError::code(StatusCode::GONE), "client-error");
+    }
+}
@@ -0,0 +1,219 @@
+/*!
+Cache-busting for the files served from the static directory.
+
+At startup, [`init`] hashes every file in the static directory into a
+manifest. The `{{asset "teacher.js"}}` template helper (see
+[`helpers`](super::helpers)) then links to a "fingerprinted" version of
+the file's name, with (the start of) its hash inserted before the
+extension, like `/static/teacher.0123456789abcdef.js`. When a file changes,
+so does its URL, so browsers can be told to cache fingerprinted files
+forever.
+
+The [`cache_control`] middleware sits in front of the static file service;
+it maps fingerprinted paths back to the real files and marks them
+immutable. Everything else gets `Cache-Control: no-cache`, so browsers at
+least check for a new version every time.
+
+If the manifest isn't initialized (because the `fingerprint_assets`
+configuration option is `false`, say), the helper just emits plain paths.
+*/
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use axum::{
+    http::header::{HeaderValue, CACHE_CONTROL},
+    http::{uri::PathAndQuery, Request, Uri},
+    middleware::Next,
+    response::Response,
+};
+use once_cell::sync::OnceCell;
+
+/// Where the static directory is served from.
+const PREFIX: &str = "/static/";
+/// How many hex digits of a file's hash go in its fingerprint.
+const FINGERPRINT_LEN: usize = 16;
+
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const NO_CACHE: &str = "no-cache";
+
+/// Maps each file's path (relative to the static directory, with `/`
+/// separators) to its fingerprint.
+static MANIFEST: OnceCell<HashMap<String, String>> = OnceCell::new();
+
+/// Recursively hash every file under `dir` into `manifest`, keyed by its
+/// path relative to `root`.
+fn hash_dir(root: &Path, dir: &Path, manifest: &mut HashMap<String, String>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("Unable to read directory {}: {}", dir.display(), &e))?;
+    for ent in entries {
+        let path: PathBuf = ent
+            .map_err(|e| format!("Error reading entry in {}: {}", dir.display(), &e))?
+            .path();
+        if path.is_dir() {
+            hash_dir(root, &path, manifest)?;
+            continue;
+        }
+
+        let bytes = std::fs::read(&path)
+            .map_err(|e| format!("Unable to read {}: {}", path.display(), &e))?;
+        let hash = blake3::hash(&bytes).to_hex();
+        let key: Vec<String> = path
+            .strip_prefix(root)
+            .map_err(|e| format!("{} not under {}: {}", path.display(), root.display(), &e))?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        manifest.insert(key.join("/"), hash[..FINGERPRINT_LEN].to_owned());
+    }
+
+    Ok(())
+}
+
+/// Build the manifest of fingerprints for every file in `static_dir`.
+/// Returns the number of files fingerprinted.
+pub fn init<P: AsRef<Path>>(static_dir: P) -> Result<usize, String> {
+    if let Some(manifest) = MANIFEST.get() {
+        log::warn!("Asset manifest already initialized; ignoring.");
+        return Ok(manifest.len());
+    }
+
+    let static_dir = static_dir.as_ref();
+    let mut manifest: HashMap<String, String> = HashMap::new();
+    hash_dir(static_dir, static_dir, &mut manifest)?;
+    let n = manifest.len();
+    log::debug!("Asset manifest:\n{:#?}", &manifest);
+
+    MANIFEST
+        .set(manifest)
+        .map_err(|_| "Asset manifest already initialized.".to_owned())?;
+    Ok(n)
+}
+
+/// Insert `fingerprint` into the file name at the end of `path`, before
+/// the extension (if any).
+fn fingerprinted(path: &str, fingerprint: &str) -> String {
+    let name_start = path.rfind('/').map(|n| n + 1).unwrap_or(0);
+    match path[name_start..].rfind('.') {
+        Some(0) | None => format!("{}.{}", path, fingerprint),
+        Some(n) => {
+            let (stem, ext) = path.split_at(name_start + n);
+            format!("{}.{}{}", stem, fingerprint, ext)
+        }
+    }
+}
+
+fn is_fingerprint(s: &str) -> bool {
+    s.len() == FINGERPRINT_LEN
+        && s.bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// The inverse of [`fingerprinted`]: if `path` has a fingerprint in it,
+/// return the path without it, and the fingerprint.
+fn split_fingerprint(path: &str) -> Option<(String, &str)> {
+    let name_start = path.rfind('/').map(|n| n + 1).unwrap_or(0);
+    let mut parts: Vec<&str> = path[name_start..].split('.').collect();
+
+    // The fingerprint is either just before the extension or, for files
+    // without extensions, at the very end.
+    let n = parts.len();
+    let idx = if n > 2 && is_fingerprint(parts[n - 2]) {
+        n - 2
+    } else if n > 1 && is_fingerprint(parts[n - 1]) {
+        n - 1
+    } else {
+        return None;
+    };
+    if parts[0].is_empty() {
+        return None;
+    }
+
+    let fp = parts.remove(idx);
+    Some((format!("{}{}", &path[..name_start], parts.join(".")), fp))
+}
+
+/// The URL at which to link the file at `path` in the static directory:
+/// fingerprinted if it's in the manifest, plain otherwise.
+pub fn url(path: &str) -> String {
+    let path = path.trim_start_matches('/');
+    match MANIFEST.get().and_then(|m| m.get(path)) {
+        Some(fp) => format!("{}{}", PREFIX, fingerprinted(path, fp)),
+        None => format!("{}{}", PREFIX, path),
+    }
+}
+
+/**
+Middleware for the static file service. Requests for fingerprinted paths
+whose fingerprints are current are rewritten to the plain path and get
+far-future cache headers; everything else is told to revalidate.
+
+This expects to see paths with the static directory's mount point already
+stripped off (as they are inside a `Router::nest()`).
+*/
+pub async fn cache_control<B>(mut req: Request<B>, next: Next<B>) -> Response {
+    let mut immutable = false;
+    if let Some(manifest) = MANIFEST.get() {
+        let uri = req.uri();
+        if let Some((plain, fp)) = split_fingerprint(uri.path()) {
+            if manifest
+                .get(plain.trim_start_matches('/'))
+                .map(|s| s.as_str())
+                == Some(fp)
+            {
+                let pq = match uri.query() {
+                    Some(q) => format!("{}?{}", &plain, q),
+                    None => plain,
+                };
+                let mut parts = uri.clone().into_parts();
+                parts.path_and_query = pq.parse::<PathAndQuery>().ok();
+                if let Ok(new_uri) = Uri::from_parts(parts) {
+                    *req.uri_mut() = new_uri;
+                    immutable = true;
+                }
+            }
+        }
+    }
+
+    let mut resp = next.run(req).await;
+    let value = if immutable && resp.status().is_success() {
+        IMMUTABLE
+    } else {
+        NO_CACHE
+    };
+    resp.headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static(value));
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprints() {
+        let fp = "0123456789abcdef";
+        for (path, fpath) in [
+            ("teacher.js", "teacher.0123456789abcdef.js"),
+            ("help/help.css", "help/help.0123456789abcdef.css"),
+            ("jquery.min.js", "jquery.min.0123456789abcdef.js"),
+            ("LICENSE", "LICENSE.0123456789abcdef"),
+            ("v1.2/README", "v1.2/README.0123456789abcdef"),
+        ] {
+            assert_eq!(fingerprinted(path, fp), fpath);
+            assert_eq!(
+                split_fingerprint(&format!("/{}", fpath)),
+                Some((format!("/{}", path), fp))
+            );
+        }
+
+        assert_eq!(split_fingerprint("/teacher.js"), None);
+        assert_eq!(split_fingerprint("/jquery.min.js"), None);
+        assert_eq!(split_fingerprint("/teacher.0123456789ABCDEF.js"), None);
+        assert_eq!(split_fingerprint("/.0123456789abcdef.js"), None);
+
+        // Without a manifest, paths come out plain.
+        assert_eq!(url("teacher.js"), "/static/teacher.js");
+    }
+}
@@ -0,0 +1,321 @@
+/*!
+Early warning of students who are headed for a failing semester grade.
+
+For each student, the semester grade is projected (see
+[`project_semester_grade`]) from their test average so far, the weight of
+the semester's goals they have yet to do, and the exam fraction; those
+projected to finish below a threshold (by default the `failing_threshold`
+from the config file) are listed, worst first.
+
+Both the Boss and Teacher APIs respond to the `at-risk` action with this
+report (see [`respond`]): the Boss's covers every student, and a Teacher's
+covers their own.
+*/
+use axum::{
+    http::header::{HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use time::Date;
+
+use super::{respond_bad_request, text_500};
+use crate::{
+    anon,
+    config::Glob,
+    pace::{project_semester_grade, Pace, PaceDisplay, Term},
+};
+
+/// Optional body of an `at-risk` request.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+struct AtRiskRequest {
+    /// `"fall"` or `"spring"`; defaults to the current semester.
+    term: Option<String>,
+    /// Projected grade (in percent) below which students are listed;
+    /// defaults to the configured `failing_threshold`.
+    threshold: Option<f32>,
+}
+
+/// One student in the response to an `at-risk` request. All grades and
+/// scores are in percent.
+#[derive(Debug, Serialize)]
+struct AtRisk {
+    uname: String,
+    #[serde(serialize_with = "anon::ser::last")]
+    last: String,
+    #[serde(serialize_with = "anon::ser::rest")]
+    rest: String,
+    teacher: String,
+    tests: f32,
+    notices: i16,
+    exam_fraction: f32,
+    exam: Option<f32>,
+    /// Fraction of the semester's scheduled weight not done yet.
+    remaining: f32,
+    projected: f32,
+    /// Grade with perfect scores on everything that's left.
+    best: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct AtRiskReport {
+    term: Term,
+    threshold: f32,
+    students: Vec<AtRisk>,
+}
+
+/// Work out which semester and threshold the report should use.
+fn parse_request(
+    body: Option<&str>,
+    today: &Date,
+    end_fall: &Date,
+    default_threshold: f32,
+) -> Result<(Term, f32), String> {
+    let req: AtRiskRequest = match body.map(str::trim) {
+        Some(body) if !body.is_empty() => serde_json::from_str(body)
+            .map_err(|e| format!("Unable to deserialize at-risk request: {}", &e))?,
+        _ => AtRiskRequest::default(),
+    };
+
+    let term = match req.term.as_deref() {
+        Some(s) => match s.parse::<Term>()? {
+            Term::Summer => {
+                return Err("There are no semester grades in the Summer term.".to_owned());
+            }
+            term => term,
+        },
+        None if today < end_fall => Term::Fall,
+        None => Term::Spring,
+    };
+
+    let threshold = req.threshold.unwrap_or(default_threshold);
+    if !threshold.is_finite() {
+        return Err(format!("{} is not a valid threshold.", &threshold));
+    }
+
+    Ok((term, threshold))
+}
+
+/// Count the goals in the given semester that `p`'s student has done, and
+/// sum the weights of those and of the ones they have yet to do. Like in
+/// [`PaceDisplay`], done goals count toward the semester they were done in,
+/// and the rest toward the semester they're due in.
+fn semester_work(p: &Pace, term: Term, end_fall: &Date, end_spring: &Date) -> (usize, f32, f32) {
+    let in_term = |d: &Date| match term {
+        Term::Fall => d < end_fall,
+        _ => d >= end_fall && d < end_spring,
+    };
+
+    let mut n_done: usize = 0;
+    let mut done_weight: f32 = 0.0;
+    let mut remaining_weight: f32 = 0.0;
+    for g in p.goals.iter().filter(|g| !p.is_waived(g)) {
+        match (&g.done, &g.due) {
+            (Some(d), _) if in_term(d) => {
+                n_done += 1;
+                done_weight += g.weight;
+            }
+            (None, Some(d)) if in_term(d) => remaining_weight += g.weight,
+            _ => {}
+        }
+    }
+
+    (n_done, done_weight, remaining_weight)
+}
+
+/// Project `p`'s student's grade for `term`, if they've done anything in it
+/// to project from.
+fn project(
+    p: &Pace,
+    glob: &Glob,
+    term: Term,
+    end_fall: &Date,
+    end_spring: &Date,
+) -> Result<Option<AtRisk>, String> {
+    let (n_done, done_weight, remaining_weight) = semester_work(p, term, end_fall, end_spring);
+    if n_done == 0 {
+        return Ok(None);
+    }
+    let pd = PaceDisplay::from(p, glob)?;
+    let (tests, exam_frac, exam, notices) = match term {
+        Term::Fall => (
+            pd.fall_tests,
+            pd.fall_exam_frac,
+            pd.fall_exam,
+            pd.fall_notices,
+        ),
+        _ => (
+            pd.spring_tests,
+            pd.spring_exam_frac,
+            pd.spring_exam,
+            pd.spring_notices,
+        ),
+    };
+    let (projected, best) = project_semester_grade(
+        tests,
+        done_weight,
+        remaining_weight,
+        exam_frac,
+        exam,
+        notices,
+    );
+    let total_weight = done_weight + remaining_weight;
+    let remaining = if total_weight > 0.0 {
+        remaining_weight / total_weight
+    } else {
+        0.0
+    };
+
+    Ok(Some(AtRisk {
+        uname: p.student.base.uname.clone(),
+        last: p.student.last.clone(),
+        rest: p.student.rest.clone(),
+        teacher: p.student.teacher.clone(),
+        tests: 100.0 * tests,
+        notices,
+        exam_fraction: exam_frac,
+        exam: exam.map(|f| 100.0 * f),
+        remaining,
+        projected: 100.0 * projected,
+        best: 100.0 * best,
+    }))
+}
+
+/**
+Respond with the students of the Teachers with the given `tunames` whose
+semester grades are projected to fall below the threshold.
+
+Header:
+```text
+x-camp-action: at-risk
+```
+
+The body is optional; it may be JSON like
+```json
+{ "term": "fall", "threshold": 75 }
+```
+where either field may be left out to use the current semester or the
+configured threshold.
+
+Response body looks like
+```json
+{
+    "term": "Fall",
+    "threshold": 70.0,
+    "students": [
+        {
+            "uname": "jsmith", "last": "Smith", "rest": "Jane", "teacher": "bob",
+            "tests": 64.5, "notices": 1, "exam_fraction": 0.2, "exam": null,
+            "remaining": 0.4, "projected": 63.5, "best": 78.2
+        },
+        ...
+    ]
+}
+```
+Students are sorted by projected grade, lowest first. A `best` below the
+threshold means the student can't reach it anymore.
+*/
+pub(super) async fn respond(tunames: Vec<&str>, body: Option<String>, glob: &Glob) -> Response {
+    let (end_fall, end_spring) = match (glob.dates.get("end-fall"), glob.dates.get("end-spring")) {
+        (Some(f), Some(s)) => (*f, *s),
+        _ => {
+            return text_500(Some(
+                "Semester end dates have not been set by Admin.".to_owned(),
+            ));
+        }
+    };
+    let (term, threshold) = match parse_request(
+        body.as_deref(),
+        &crate::now(),
+        &end_fall,
+        glob.failing_threshold,
+    ) {
+        Ok(x) => x,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    let mut students: Vec<AtRisk> = Vec::new();
+    for tuname in tunames.iter() {
+        let paces = match glob.get_paces_by_teacher(tuname).await {
+            Ok(paces) => paces,
+            Err(e) => {
+                log::error!("Error retrieving paces for Teacher {:?}: {}", tuname, &e);
+                return text_500(Some(format!(
+                    "Unable to retrieve pace information for {:?}: {}",
+                    tuname, &e
+                )));
+            }
+        };
+
+        for p in paces.iter() {
+            match project(p, glob, term, &end_fall, &end_spring) {
+                Ok(Some(a)) if a.projected < threshold => students.push(a),
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!(
+                        "Error projecting grade for Student {:?}: {}",
+                        &p.student.base.uname,
+                        &e
+                    );
+                    return text_500(Some(format!(
+                        "Error projecting grade for {:?}: {}",
+                        &p.student.base.uname, &e
+                    )));
+                }
+            }
+        }
+    }
+    students.sort_by(|a, b| a.projected.total_cmp(&b.projected));
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("at-risk"),
+        )],
+        Json(AtRiskReport {
+            term,
+            threshold,
+            students,
+        }),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use time::macros::date;
+
+    #[test]
+    fn at_risk_requests() {
+        let end_fall = date!(2023 - 01 - 13);
+        let fall_day = date!(2022 - 11 - 01);
+        let spring_day = date!(2023 - 03 - 01);
+
+        assert_eq!(
+            parse_request(None, &fall_day, &end_fall, 70.0),
+            Ok((Term::Fall, 70.0))
+        );
+        assert_eq!(
+            parse_request(Some(" "), &spring_day, &end_fall, 70.0),
+            Ok((Term::Spring, 70.0))
+        );
+        assert_eq!(
+            parse_request(Some(r#"{"term": "fall"}"#), &spring_day, &end_fall, 70.0),
+            Ok((Term::Fall, 70.0))
+        );
+        assert_eq!(
+            parse_request(Some(r#"{"threshold": 75}"#), &fall_day, &end_fall, 70.0),
+            Ok((Term::Fall, 75.0))
+        );
+        assert!(parse_request(Some(r#"{"term": "summer"}"#), &fall_day, &end_fall, 70.0).is_err());
+        assert!(parse_request(Some(r#"{"term": "winter"}"#), &fall_day, &end_fall, 70.0).is_err());
+        assert!(parse_request(Some("75"), &fall_day, &end_fall, 70.0).is_err());
+    }
+}
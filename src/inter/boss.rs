@@ -11,31 +11,40 @@ use std::{
 };
 
 use axum::{
-    extract::Extension,
+    extract::{Extension, Path},
     http::header,
+    http::Request,
     http::header::{HeaderMap, HeaderName},
     response::{IntoResponse, Response},
     Json,
 };
-use futures::stream::{FuturesUnordered, StreamExt};
+use futures::stream::{self, FuturesUnordered, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use smallstr::SmallString;
-use time::{format_description::FormatItem, macros::format_description, Date};
-use tokio::sync::RwLock;
+use time::Date;
+use tokio::{io::AsyncWriteExt, sync::RwLock};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
 
+use super::extract::{
+    CampAction, CampHeader, CampStudent, CampTeacher, CampTerm, CampUname,
+};
+use super::zip_stream::ZipEncoder;
 use super::*;
 use crate::{
+    anon,
     auth::AuthResult,
     config::Glob,
+    format_date,
     hist::HistEntry,
-    pace::{GoalDisplay, GoalStatus, Pace, PaceDisplay, RowDisplay, Term},
-    store::Store,
-    user::{BaseUser, User},
-    MiniString, MEDSTORE, SMALLSTORE,
+    pace::{
+        required_exam_score, AcademicCalendar, GoalDisplay, GoalStatus, Pace, PaceDisplay,
+        RowDisplay, Term,
+    },
+    store::{FinalStream, Store},
+    user::{BaseUser, Role, Student, User},
+    DateStyle, MiniString, MEDSTORE, SMALLSTORE,
 };
 
-const DATE_FMT: &[FormatItem] = format_description!("[month repr:short] [day]");
-
 /**
 Ensure a Boss's login credentials check out, generate 'em a key, and serve
 the Boss view.
@@ -68,6 +77,9 @@ pub async fn login(base: BaseUser, form: LoginData, glob: Arc<RwLock<Glob>>) ->
         Ok(AuthResult::BadPassword) => {
             return respond_bad_password(&base.uname);
         }
+        Ok(AuthResult::Locked(secs)) => {
+            return respond_locked_out(&base.uname, secs);
+        }
         Ok(x) => {
             log::warn!(
                 "auth::Db::check_password( {:?}, {:?}, {:?} ) returned {:?}, which shouldn't happen.",
@@ -76,12 +88,13 @@ pub async fn login(base: BaseUser, form: LoginData, glob: Arc<RwLock<Glob>>) ->
             return respond_bad_password(&base.uname);
         }
     };
+    glob.read().await.set_anonymized(&auth_key, form.anonymize());
 
     let calendar_string = match make_boss_calendars(glob.clone()).await {
         Ok(s) => s,
         Err(e) => {
             log::error!("Error attempting to write boss calendars: {}", &e);
-            return respond_login_error(StatusCode::INTERNAL_SERVER_ERROR, &e);
+            return html_500();
         }
     };
 
@@ -89,7 +102,7 @@ pub async fn login(base: BaseUser, form: LoginData, glob: Arc<RwLock<Glob>>) ->
         Ok(s) => s,
         Err(e) => {
             log::error!("Error attempting to generate boss archive buttons: {}", &e);
-            return respond_login_error(StatusCode::INTERNAL_SERVER_ERROR, &e);
+            return html_500();
         }
     };
 
@@ -97,7 +110,7 @@ pub async fn login(base: BaseUser, form: LoginData, glob: Arc<RwLock<Glob>>) ->
         Ok(s) => s,
         Err(e) => {
             log::error!("Error attempting to generate boss course completion histories: {}", &e);
-            return respond_login_error(StatusCode::INTERNAL_SERVER_ERROR, &e);
+            return html_500();
         }
     };
 
@@ -107,6 +120,7 @@ pub async fn login(base: BaseUser, form: LoginData, glob: Arc<RwLock<Glob>>) ->
         "calendars": calendar_string,
         "archives": archive_buttons_string,
         "completion_rows": histories_string,
+        "email_warning": glob.read().await.email_health.warning(),
     });
 
     serve_raw_template(StatusCode::OK, "boss", &data, vec![])
@@ -116,6 +130,7 @@ pub async fn login(base: BaseUser, form: LoginData, glob: Arc<RwLock<Glob>>) ->
 #[derive(Serialize)]
 struct TeacherData<'a> {
     uname: &'a str,
+    #[serde(serialize_with = "anon::ser::name")]
     name: &'a str,
 }
 
@@ -125,10 +140,10 @@ async fn make_archive_buttons(glob: Arc<RwLock<Glob>>) -> Result<String, String>
     let glob = glob.read().await;
 
     let mut output: Vec<u8> = Vec::new();
-    for (uname, u) in glob.users.iter() {
+    for u in glob.users_with_role(Role::Teacher) {
         if let User::Teacher(t) = u {
             let td = TeacherData {
-                uname: uname,
+                uname: &t.base.uname,
                 name: &t.name,
             };
             write_template("boss_archive_button", &td, &mut output)
@@ -144,66 +159,44 @@ async fn make_archive_buttons(glob: Arc<RwLock<Glob>>) -> Result<String, String>
 /// Holds data for rendering the `"boss_goal_row"` template.
 #[derive(Serialize)]
 struct GoalData<'a> {
-    row_class: &'a str,
-    row_bad: &'a str,
+    status: &'a str,
+    bad: bool,
     course: &'a str,
     book: &'a str,
     chapter: &'a str,
-    review: &'a str,
-    incomplete: &'a str,
-    due: MiniString<SMALLSTORE>,
-    done: MiniString<SMALLSTORE>,
-    score: MiniString<SMALLSTORE>,
+    review: bool,
+    incomplete: bool,
+    due: Option<Date>,
+    done: Option<Date>,
+    score: Option<f32>,
+    date_style: DateStyle,
 }
 
 /// Render the `"boss_goal_row"` template to a [`Write`]r.
-fn write_cal_goal<W: Write>(g: &GoalDisplay, buff: W) -> Result<(), String> {
-    let row_class = match g.status {
+pub(super) fn write_cal_goal<W: Write>(
+    g: &GoalDisplay,
+    style: DateStyle,
+    buff: W,
+) -> Result<(), String> {
+    let status = match g.status {
         GoalStatus::Done => "done",
         GoalStatus::Late => "late",
         GoalStatus::Overdue => "overdue",
         GoalStatus::Yet => "yet",
     };
 
-    let row_bad = if g.inc && g.done.is_none() {
-        " bad"
-    } else {
-        ""
-    };
-
-    let review = if g.rev { " R " } else { "" };
-    let incomplete = if g.inc { " I " } else { "" };
-
-    let mut due: MiniString<SMALLSTORE> = MiniString::new();
-    if let Some(d) = g.due {
-        d.format_into(&mut due, DATE_FMT)
-            .map_err(|e| format!("Error writing due date {:?}: {}", &d, &e))?;
-    }
-
-    let mut done: MiniString<SMALLSTORE> = MiniString::new();
-    if let Some(d) = g.done {
-        d.format_into(&mut done, DATE_FMT)
-            .map_err(|e| format!("Error writing done date {:?}: {}", &d, &e))?;
-    }
-
-    let mut score: MiniString<SMALLSTORE> = MiniString::new();
-    if let Some(f) = g.score {
-        let pct = (100.0 * f).round() as i32;
-        write!(&mut score, "{} %", &pct)
-            .map_err(|e| format!("Error writing score {:?}: {}", &pct, &e))?;
-    }
-
     let data = GoalData {
-        row_class,
-        row_bad,
-        review,
-        incomplete,
-        due,
-        done,
-        score,
+        status,
+        bad: g.inc && g.done.is_none(),
         course: g.course,
         book: g.book,
-        chapter: g.title,
+        chapter: &g.title,
+        review: g.rev,
+        incomplete: g.inc,
+        due: g.due,
+        done: g.done,
+        score: g.score,
+        date_style: style,
     };
 
     write_raw_template("boss_goal_row", &data, buff)
@@ -212,21 +205,27 @@ fn write_cal_goal<W: Write>(g: &GoalDisplay, buff: W) -> Result<(), String> {
 /// Holds the data for rendering the `"boss_pace_table"` template.
 #[derive(Serialize)]
 struct PaceData<'a> {
-    table_class: SmallString<MEDSTORE>,
     uname: &'a str,
     name: String,
+    #[serde(serialize_with = "anon::ser::rest")]
     rest: &'a str,
     tuname: &'a str,
+    #[serde(serialize_with = "anon::ser::name")]
     teacher: &'a str,
+    previously_inc: bool,
+    /// Whether less weight is done than is due.
+    lagging: bool,
+    /// Whether fewer goals are done than are due.
+    short: bool,
     n_done: usize,
     n_due: usize,
-    lag: i32,
-    lagstr: SmallString<SMALLSTORE>,
+    /// Weight done minus weight due, as a fraction of the weight scheduled.
+    lag: f32,
     rows: String,
 }
 
 /// Render the `"boss_pace_table"` template to a [`Write`]r.
-fn write_cal_table<W: Write>(p: &Pace, glob: &Glob, mut buff: W) -> Result<(), String> {
+pub(super) fn write_cal_table<W: Write>(p: &Pace, glob: &Glob, mut buff: W) -> Result<(), String> {
     log::trace!(
         "make_cal_table( [ {:?} Pace], [ Glob ] ) called.",
         &p.student.base.uname
@@ -239,35 +238,24 @@ fn write_cal_table<W: Write>(p: &Pace, glob: &Glob, mut buff: W) -> Result<(), S
         )
     })?;
 
-    let mut table_class: SmallString<MEDSTORE> = SmallString::from_str("cal");
-    if pd.previously_inc {
-        write!(&mut table_class, " inc")
-            .map_err(|e| format!("Error writing table class: {}", &e))?;
-    }
-    if pd.weight_done < pd.weight_due {
-        write!(&mut table_class, " lag")
-            .map_err(|e| format!("Error writing table class: {}", &e))?;
-    }
-    if pd.n_done < pd.n_due {
-        write!(&mut table_class, " count")
-            .map_err(|e| format!("Error writing table class: {}", &e))?;
-    }
-
-    let name = format!("{}, {}", pd.last, pd.rest);
+    let name = format!("{}, {}", anon::last(pd.last), anon::rest(pd.rest));
 
     let lag = if pd.weight_scheduled.abs() < 0.001 {
-        0
+        0.0
     } else {
-        (100.0 * (pd.weight_done - pd.weight_due) / pd.weight_scheduled) as i32
+        (pd.weight_done - pd.weight_due) / pd.weight_scheduled
     };
-    let mut lagstr: SmallString<SMALLSTORE> = SmallString::new();
-    write!(&mut lagstr, "{:+}%", &lag).map_err(|e| format!("Error writing lag string: {}", &e))?;
 
     let mut rows: Vec<u8> = Vec::new();
     for row in pd.rows.iter() {
         match row {
             RowDisplay::Goal(g) => {
-                write_cal_goal(g, &mut rows).map_err(|e| format!(
+                write_cal_goal(g, glob.date_style, &mut rows).map_err(|e| format!(
+                    "Error writing cal for {:?}: {}", &p.student.base.uname, &e
+                ))?;
+            },
+            RowDisplay::Waived(w) => {
+                write_template("waived_row", &w, &mut rows).map_err(|e| format!(
                     "Error writing cal for {:?}: {}", &p.student.base.uname, &e
                 ))?;
             },
@@ -287,15 +275,16 @@ fn write_cal_table<W: Write>(p: &Pace, glob: &Glob, mut buff: W) -> Result<(), S
     })?;
 
     let data = PaceData {
-        table_class,
         name,
         lag,
-        lagstr,
         rows,
         uname: pd.uname,
         rest: pd.rest,
         tuname: pd.tuname,
         teacher: pd.teacher,
+        previously_inc: pd.previously_inc,
+        lagging: pd.weight_done < pd.weight_due,
+        short: pd.n_done < pd.n_due,
         n_done: pd.n_done,
         n_due: pd.n_due,
     };
@@ -308,23 +297,8 @@ pub async fn make_boss_calendars(glob: Arc<RwLock<Glob>>) -> Result<String, Stri
     log::trace!("make_boss_page( [ Glob ] ) called.");
 
     let glob = glob.read().await;
-    let tunames: Vec<&str> = glob
-        .users
-        .iter()
-        .map(|(uname, user)| match user {
-            User::Teacher(_) => Some(uname),
-            _ => None,
-        })
-        .filter(|opt| opt.is_some())
-        .map(|ok| ok.unwrap().as_str())
-        .collect();
-
-    let n_students: usize = glob
-        .users
-        .iter()
-        .map(|(_, u)| matches!(u, User::Student(_)))
-        .filter(|b| *b)
-        .count();
+    let tunames = glob.unames_with_role(Role::Teacher);
+    let n_students = glob.unames_with_role(Role::Student).len();
 
     let mut paces: Vec<Pace> = Vec::with_capacity(n_students);
     {
@@ -380,7 +354,7 @@ async fn generate_boss_histories(glob: Arc<RwLock<Glob>>) -> Result<String, Stri
                 continue;
             },
         };
-        let name = format!("{}, {}", &stud.last, &stud.rest);
+        let name = format!("{}, {}", anon::last(&stud.last), anon::rest(&stud.rest));
         kidmap.insert(name, (uname, hist));
     }
 
@@ -425,25 +399,18 @@ async fn generate_boss_histories(glob: Arc<RwLock<Glob>>) -> Result<String, Stri
 
 /// Handle "Boss API" requests. Requests to "/boss" get routed here.
 ///
-/// Right now the only API calls the Boss can make have to do with sending
-/// autogenerated emails to parents.
+/// Most of the API calls the Boss can make have to do with sending
+/// autogenerated emails to parents, or with reports (including releasing
+/// finalized reports to students and parents; see [`reports`]). The Boss can
+/// also post announcements (see [`announce`]).
 pub async fn api(
+    CampUname(uname): CampUname,
+    CampAction(action): CampAction,
     headers: HeaderMap,
     body: Option<String>,
     Extension(glob): Extension<Arc<RwLock<Glob>>>,
 ) -> Response {
-    let uname: &str = match headers.get("x-camp-uname") {
-        Some(uname) => match uname.to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                return text_500(None);
-            }
-        },
-        None => {
-            return text_500(None);
-        }
-    };
-
+    let uname = uname.as_str();
     let u = {
         let glob = glob.read().await;
         if let Some(u) = glob.users.get(uname) {
@@ -456,33 +423,31 @@ pub async fn api(
     match u {
         User::Boss(_) => { /* Okay, request may proceed. */ }
         _ => {
-            return (
-                StatusCode::FORBIDDEN,
-                "Who is this? What's your operating number?".to_owned(),
-            )
+            return ApiError::forbidden("Who is this? What's your operating number?".to_owned())
                 .into_response();
         }
     };
 
-    let action = match headers.get("x-camp-action") {
-        Some(act) => match act.to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                return respond_bad_request("x-camp-action header unrecognizable.".to_owned());
-            }
-        },
-        None => {
-            return respond_bad_request("Request must have an x-camp-action header.".to_owned());
-        }
-    };
-
-    match action {
+    match action.as_str() {
         "compose-email" => compose_email(body, glob.clone()).await,
         "send-email" => send_email(body, glob.clone()).await,
         "email-all" => email_all(glob.clone()).await,
+        "compose-teacher-email" => staff_email::compose(glob.clone()).await,
+        "email-teachers" => staff_email::send(u.email(), body, glob.clone()).await,
+        "list-reports" => list_reports(&headers, glob.clone()).await,
         "download-report" => download_report(&headers, glob.clone()).await,
         "report-archive" => download_archive(&headers, glob.clone()).await,
+        "list-releases" => reports::list(&headers, glob.clone()).await,
+        "release-reports" => reports::release(uname, &headers, glob.clone()).await,
+        "withdraw-report" => reports::withdraw(uname, &headers, glob.clone()).await,
         "populate-histories" => populate_histories(glob.clone()).await,
+        "what-if" => what_if(body, glob.clone()).await,
+        "lag-history" => lag_history(body, glob.clone()).await,
+        "at-risk" => at_risk(body, glob.clone()).await,
+        "populate-announcements" => announce::populate(glob.clone()).await,
+        "add-announcement" => announce::add(body, glob.clone()).await,
+        "expire-announcement" => announce::expire(body, glob.clone()).await,
+        "email-health" => email_health(glob.clone()).await,
         x => respond_bad_request(format!(
             "{:?} is not a recognizable x-camp-action value.",
             x
@@ -496,22 +461,28 @@ pub async fn api(
 struct EmailData<'a> {
     uname: &'a str,
     full_name: String,
-    date: MiniString<MEDSTORE>,
+    date: MiniString<SMALLSTORE>,
     n_done: usize,
     n_due_str: MiniString<MEDSTORE>,
     n_scheduled: usize,
     last_done_statement: String,
     service_uri: &'a str,
+    unsubscribe_uri: &'a str,
     teacher: &'a str,
     temail: &'a str,
 }
 
 /// Generate the body of a parent email.
-fn generate_email(pd: PaceDisplay<'_>, service_uri: &str, today: &Date) -> Result<String, String> {
+fn generate_email(
+    pd: PaceDisplay<'_>,
+    service_uri: &str,
+    unsubscribe_uri: &str,
+    style: DateStyle,
+    cal: &AcademicCalendar,
+    today: &Date,
+) -> Result<String, String> {
     let full_name = format!("{} {}", pd.rest, pd.last);
-    let mut date: MiniString<MEDSTORE> = MiniString::new();
-    today
-        .format_into(&mut date, DATE_FMT)
+    let date = format_date(style, today)
         .map_err(|e| format!("Error formatting today's date: {}", &e))?;
     let mut n_due_str: MiniString<MEDSTORE> = MiniString::new();
     match pd.n_due {
@@ -525,7 +496,7 @@ fn generate_email(pd: PaceDisplay<'_>, service_uri: &str, today: &Date) -> Resul
             None => {
                 return Err("last_completed_goal index does *not* exist in rows vector!".to_owned());
             }
-            Some(RowDisplay::Summary(_)) => {
+            Some(RowDisplay::Summary(_)) | Some(RowDisplay::Waived(_)) => {
                 return Err(
                     "last_completed_goal index references a summary row, not a goal row!"
                         .to_owned(),
@@ -537,18 +508,11 @@ fn generate_email(pd: PaceDisplay<'_>, service_uri: &str, today: &Date) -> Resul
             .done
             .ok_or_else(|| "Last Goal marked as 'done' but doesn't have a done date!".to_owned())?;
 
-        let mut last_date_str: MiniString<MEDSTORE> = MiniString::new();
         let mut last_date_delta: MiniString<MEDSTORE> = MiniString::new();
         let mut last_due_delta: MiniString<MEDSTORE> = MiniString::new();
 
-        last_goal_date
-            .format_into(&mut last_date_str, DATE_FMT)
-            .map_err(|e| {
-                format!(
-                    "Error formatting last due date {:?}: {}",
-                    &last_goal_date, &e
-                )
-            })?;
+        let last_date_str = format_date(style, &last_goal_date)
+            .map_err(|e| format!("Error formatting last due date: {}", &e))?;
 
         match (last_goal_date - *today).whole_days() {
             i @ 2..=i64::MAX => write!(&mut last_date_delta, "in {} days", i),
@@ -560,7 +524,7 @@ fn generate_email(pd: PaceDisplay<'_>, service_uri: &str, today: &Date) -> Resul
         .map_err(|e| format!("Error writing time since last goal: {}", &e))?;
 
         match &last_goal.due {
-            Some(done) => match (*done - last_goal_date).whole_days() {
+            Some(due) => match -cal.lateness(due, &last_goal_date) {
                 i @ 2..=i64::MAX => write!(&mut last_due_delta, "{} days early", &i),
                 1 => write!(&mut last_due_delta, "one day early"),
                 0 => write!(&mut last_due_delta, "on time"),
@@ -585,6 +549,7 @@ fn generate_email(pd: PaceDisplay<'_>, service_uri: &str, today: &Date) -> Resul
         n_due_str,
         last_done_statement,
         service_uri,
+        unsubscribe_uri,
         uname: pd.uname,
         n_done: pd.n_done,
         n_scheduled: pd.n_scheduled,
@@ -596,12 +561,16 @@ fn generate_email(pd: PaceDisplay<'_>, service_uri: &str, today: &Date) -> Resul
 }
 
 /// Structure for sending/receiving parent email text to/from the frontend
-/// for editing before making a Sendgrid request to actually send the email.
+/// for editing before actually sending the email.
 #[derive(Deserialize, Serialize)]
 struct EmailEnvelope {
     uname: String,
     student_name: Option<String>,
     text: String,
+    /// Whether the parent has unsubscribed from progress emails (in which
+    /// case sending will be refused).
+    #[serde(default)]
+    unsubscribed: bool,
 }
 
 /**
@@ -623,7 +592,7 @@ async fn compose_email(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Respons
         }
     };
 
-    let (text, student_name) = {
+    let (text, student_name, unsubscribed) = {
         let glob = glob.read().await;
         let p = match glob.get_pace_by_student(&uname).await {
             Ok(p) => p,
@@ -652,10 +621,26 @@ async fn compose_email(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Respons
             }
         };
 
+        let unsubscribed = match glob.data().read().await.get_email_optouts().await {
+            Ok(optouts) => optouts.contains(&p.student.parent.to_lowercase()),
+            Err(e) => {
+                log::error!("Error retrieving email opt-outs: {}", &e);
+                return text_500(Some(format!("Error retrieving email opt-outs: {}", &e)));
+            }
+        };
+
         let student_name = format!("{} {}", pd.rest, pd.last);
         let today = crate::now();
-
-        let text = match generate_email(pd, &glob.uri, &today) {
+        let unsubscribe_uri = unsubscribe::link(&glob, &p.student.parent);
+
+        let text = match generate_email(
+            pd,
+            &glob.uri,
+            &unsubscribe_uri,
+            glob.date_style,
+            &glob.calendar,
+            &today,
+        ) {
             Ok(text) => text,
             Err(e) => {
                 log::error!(
@@ -668,13 +653,14 @@ async fn compose_email(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Respons
             }
         };
 
-        (text, student_name)
+        (text, student_name, unsubscribed)
     };
 
     let data = EmailEnvelope {
         uname,
         student_name: Some(student_name),
         text,
+        unsubscribed,
     };
 
     (
@@ -689,15 +675,17 @@ async fn compose_email(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Respons
 }
 
 /// Data required to render the `"boss_parent_email"` template, generating the
-/// JSON body of a Sendgrid request to send a parent email.
+/// JSON [`Email`](crate::email::Email) to send to a parent.
 #[derive(Debug, Serialize)]
-struct SendgridData<'a> {
+struct ParentEmailData<'a> {
     /// parent email address
     pub parent: &'a str,
     /// student name
     pub name: &'a str,
     /// text of the email (as rendered from the `"boss_email"` template)
     pub text: &'a str,
+    /// link for the `List-Unsubscribe` header
+    pub unsubscribe: &'a str,
 }
 
 /**
@@ -709,6 +697,12 @@ x-camp-action: send-email
 ```
 Body should JSON-deserialize to an `EmailEnvelope` with the appropriate
 `text` body and `uname` user name.
+
+The email is queued, to be sent in the background (and retried if it
+fails; see [`email`](crate::email)), so a successful response means it's
+on its way, not that it's been delivered.
+
+Emails to parents who have unsubscribed are refused.
 */
 async fn send_email(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
     let body = match body {
@@ -752,29 +746,45 @@ async fn send_email(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
             }
         };
 
+        match glob.data().read().await.get_email_optouts().await {
+            Ok(optouts) if optouts.contains(&stud.parent.to_lowercase()) => {
+                return respond_bad_request(format!(
+                    "{} {}'s parent ({}) has unsubscribed from progress emails.",
+                    &stud.rest, &stud.last, &stud.parent
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("Error retrieving email opt-outs: {}", &e);
+                return text_500(Some(format!("Error retrieving email opt-outs: {}", &e)));
+            }
+        }
+
         let mut name: MiniString<MEDSTORE> = MiniString::new();
         if let Err(e) = write!(&mut name, "{} {}", &stud.rest, &stud.last) {
             log::error!("Error writing student name as MiniString: {}", &e);
             return text_500(Some(format!("Error writing student name: {}", &e)));
         }
 
-        let data = SendgridData {
+        let unsubscribe_uri = unsubscribe::link(&glob, &stud.parent);
+        let data = ParentEmailData {
             parent: &stud.parent,
             name: name.as_str(),
             text: &env.text,
+            unsubscribe: &unsubscribe_uri,
         };
 
         let request_body = match render_json_template("boss_parent_email", &data) {
             Ok(bod) => bod,
             Err(e) => {
                 log::error!("Error rendering template: {}\ndata: {:?}", &e, &data);
-                return text_500(Some(format!("Error generating sendgrid request: {}", &e)));
+                return text_500(Some(format!("Error generating email: {}", &e)));
             }
         };
 
-        if let Err(e) = make_sendgrid_request(request_body, &glob, name).await {
-            log::error!("Error making Sendgrid request: {}", &e);
-            return text_500(Some(format!("Error making Sendgrid request: {}", &e)));
+        if let Err(e) = queue_rendered_email(request_body, &glob, name).await {
+            log::error!("Error sending parent email: {}", &e);
+            return text_500(Some(format!("Error sending email: {}", &e)));
         }
     }
 
@@ -788,30 +798,40 @@ async fn send_email(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
         .into_response()
 }
 
-/// Directly generate a JSON Sendgrid request body (bypassing the round-trip)
+/// Directly generate a JSON parent email (bypassing the round-trip)
 /// to the frontend for editing.
 ///
 /// This is used when auto-emailing parents of _all_ students at once.
-fn sendgrid_request_from_pace(p: &Pace, glob: &Glob, today: &Date) -> Result<String, String> {
+fn email_from_pace(p: &Pace, glob: &Glob, today: &Date) -> Result<String, String> {
     let pd = PaceDisplay::from(p, glob)
         .map_err(|e| format!("Error generating pace display info: {}", &e))?;
-    let email_body = generate_email(pd, &glob.uri, today)
-        .map_err(|e| format!("Error generating email: {}", &e))?;
+    let unsubscribe_uri = unsubscribe::link(glob, &p.student.parent);
+    let email_body = generate_email(
+        pd,
+        &glob.uri,
+        &unsubscribe_uri,
+        glob.date_style,
+        &glob.calendar,
+        today,
+    )
+    .map_err(|e| format!("Error generating email: {}", &e))?;
     let name = format!("{}, {}", &p.student.rest, &p.student.last);
-    let data = SendgridData {
+    let data = ParentEmailData {
         parent: &p.student.parent,
         name: &name,
         text: &email_body,
+        unsubscribe: &unsubscribe_uri,
     };
     render_json_template("boss_parent_email", &data)
-        .map_err(|e| format!("Error rendering Sendgrid request template: {}", &e))
+        .map_err(|e| format!("Error rendering parent email template: {}", &e))
 }
 
 /**
 Respond to a request to email the parents of _all_ students.
 
 This does not allow for editing any of the emails like sending them
-individually does.
+individually does. Parents who have unsubscribed are skipped; the body
+of a successful response lists their students.
 
 Req'ments:
 ```
@@ -822,19 +842,18 @@ Use sparingly.
 */
 async fn email_all(glob: Arc<RwLock<Glob>>) -> Response {
     let mut failures: Vec<String> = Vec::new();
+    let mut skipped: Vec<String> = Vec::new();
 
     {
         let glob = glob.read().await;
-        let tunames: Vec<&str> = glob
-            .users
-            .iter()
-            .map(|(uname, user)| match user {
-                User::Teacher(_) => Some(uname),
-                _ => None,
-            })
-            .filter(|opt| opt.is_some())
-            .map(|ok| ok.unwrap().as_str())
-            .collect();
+        let optouts = match glob.data().read().await.get_email_optouts().await {
+            Ok(optouts) => optouts,
+            Err(e) => {
+                log::error!("Error retrieving email opt-outs: {}", &e);
+                return text_500(Some(format!("Error retrieving email opt-outs: {}", &e)));
+            }
+        };
+        let tunames = glob.unames_with_role(Role::Teacher);
 
         {
             let mut retrievals = FuturesUnordered::new();
@@ -850,7 +869,11 @@ async fn email_all(glob: Arc<RwLock<Glob>>) -> Response {
                 match res {
                     Ok(mut pace_vec) => {
                         for p in pace_vec.drain(..) {
-                            match sendgrid_request_from_pace(&p, &glob, &today) {
+                            if optouts.contains(&p.student.parent.to_lowercase()) {
+                                skipped.push(format!("{}, {}", &p.student.last, &p.student.rest));
+                                continue;
+                            }
+                            match email_from_pace(&p, &glob, &today) {
                                 Ok(req_body) => {
                                     let mut name: MiniString<MEDSTORE> = MiniString::new();
                                     if let Err(e) = write!(
@@ -865,7 +888,7 @@ async fn email_all(glob: Arc<RwLock<Glob>>) -> Response {
                                         failures.push(estr);
                                         continue;
                                     }
-                                    sends.push(make_sendgrid_request(req_body, &glob, name));
+                                    sends.push(queue_rendered_email(req_body, &glob, name));
                                 }
                                 Err(e) => {
                                     let estr =
@@ -891,12 +914,22 @@ async fn email_all(glob: Arc<RwLock<Glob>>) -> Response {
     }
 
     if failures.is_empty() {
+        skipped.sort();
+        let body = if skipped.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "Skipped the following students, whose parents have unsubscribed:\n{}",
+                skipped.join("\n")
+            )
+        };
         (
             StatusCode::OK,
             [(
                 HeaderName::from_static("x-camp-action"),
                 HeaderValue::from_static("none"),
             )],
+            body,
         )
             .into_response()
     } else {
@@ -905,39 +938,88 @@ async fn email_all(glob: Arc<RwLock<Glob>>) -> Response {
             failures.join("\n")
         );
 
-        (
+        ApiError::new(
             StatusCode::from_u16(512).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
             err_body,
         )
-            .into_response()
+        .with_detail(&failures)
+        .into_response()
     }
 }
 
-async fn download_report(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
-    let suname = match get_head("x-camp-student", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return respond_bad_request(e); },
-    };
-    let term = match get_head("x-camp-term", headers) {
-        Ok(term) => term,
-        Err(e) => { return respond_bad_request(e); },
+/**
+Respond with information about finalized reports (term, when it was
+finalized, number of pages, and size in bytes; see
+[`ReportMeta`](crate::store::ReportMeta)), either for all students or, if
+the `x-camp-student` header is present, just for that student.
+
+Headers:
+```text
+x-camp-action: list-reports
+x-camp-student: [ optional uname of student ]
+```
+*/
+async fn list_reports(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+
+    let suname = match CampStudent::from_headers_opt(headers) {
+        Ok(suname) => suname.map(|CampStudent(uname)| uname),
+        Err(resp) => { return resp; },
     };
-    let term = match Term::from_str(term) {
-        Ok(term) => term,
-        Err(e) => {
-            log::warn!(
-                "Invalid x-camp-term value ({:?}) in attempt to download report for {:?}: {}",
-                term, suname, &e
-            );
+    if let Some(uname) = &suname {
+        if !matches!(glob.users.get(uname), Some(User::Student(_))) {
             return respond_bad_request(format!(
-                "Invalid x-camp-term value {:?}: {}", term, &e
+                "{:?} is not the user name of a student in the system.", uname
             ));
+        }
+    }
+
+    let res = glob.data().read().await.get_report_meta(suname.as_deref()).await;
+    let metas = match res {
+        Ok(metas) => metas,
+        Err(e) => {
+            log::error!("Error retrieving report metadata for {:?}: {}", &suname, &e);
+            return text_500(Some(format!("Unable to retrieve report information: {}", &e)));
         },
     };
 
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("list-reports"),
+        )],
+        Json(metas),
+    ).into_response()
+}
+
+/**
+Respond with the finalized PDF report of the given student for the given
+term.
+
+Headers:
+```text
+x-camp-action: download-report
+x-camp-student: [ uname of student ]
+x-camp-term: [ one of "Fall", "Spring", "Summer" ]
+```
+
+If known, the time the report was finalized and its number of pages are
+sent back in the `x-camp-finalized` and `x-camp-pages` headers.
+*/
+async fn download_report(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let suname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let term = match CampTerm::from_headers(headers) {
+        Ok(CampTerm(term)) => term,
+        Err(resp) => { return resp; },
+    };
+
     let glob = glob.read().await;
 
-    let stud = match glob.users.get(suname) {
+    let stud = match glob.users.get(&suname) {
         Some(User::Student(s)) => s,
         _ => {
             log::warn!(
@@ -977,16 +1059,13 @@ async fn download_report(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Respon
             },
         };
 
-        let pdf_data = match Store::get_final(&t, suname, term).await {
+        let pdf_data = match Store::get_final(&t, &suname, term).await {
             Ok(Some(v)) => v,
             Ok(None) => {
-                return (
-                    StatusCode::NOT_FOUND,
-                    format!(
-                        "{} {} does not yet have a {} report in the system.",
-                        &stud.rest, &stud.last, &term
-                    ),
-                ).into_response();
+                return ApiError::not_found(format!(
+                    "{} {} does not yet have a {} report in the system.",
+                    &stud.rest, &stud.last, &term
+                )).into_response();
             },
             Err(e) => {
                 log::error!(
@@ -1018,7 +1097,16 @@ async fn download_report(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Respon
     let suname_header = headers.get("x-camp-student").unwrap().clone();
     let term_header = headers.get("x-camp-term").unwrap().clone();
 
-    (
+    let meta = match glob.data().read().await.get_report_meta(Some(&suname)).await {
+        Ok(metas) => metas.into_iter().find(|m| m.term == term),
+        Err(e) => {
+            // The PDF itself is more important than its metadata.
+            log::error!("Error retrieving report metadata for {:?}: {}", suname, &e);
+            None
+        },
+    };
+
+    let mut response = (
         StatusCode::OK,
         [
             (
@@ -1043,33 +1131,60 @@ async fn download_report(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Respon
             ),
         ],
         pdf_data
-    ).into_response()
+    ).into_response();
+
+    if let Some(meta) = meta {
+        let headers = response.headers_mut();
+        if let Some(Ok(val)) = meta.finalized.map(|s| HeaderValue::from_str(&s)) {
+            headers.insert("x-camp-finalized", val);
+        }
+        if let Some(n) = meta.n_pages {
+            headers.insert("x-camp-pages", HeaderValue::from(n));
+        }
+    }
+
+    response
 }
 
+/**
+Write a ZIP archive of the finalized reports of one Teacher's students for
+one term to a temporary file (see [`downloads`](crate::downloads)), and
+respond with a link to it. The archive is written as the reports are read,
+so it's never all in memory at once, and it's served from the file by
+[`serve_archive`], so an interrupted download can be resumed.
+
+Req'ments:
+```text
+x-camp-action: report-archive
+x-camp-teacher: [ teacher uname ]
+x-camp-term: [ "fall" | "spring" | "summer" ]
+```
+
+Response:
+```text
+x-camp-action: archive-link
+```
+```json
+{
+    "link": "https://camp.camelotacademy.org/archive/aBc...",
+    "filename": "berro_fall.zip",
+    "size": 1234567
+}
+```
+The link works for an hour.
+*/
 async fn download_archive(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
-    let tuname = match get_head("x-camp-teacher", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return respond_bad_request(e); },
+    let tuname = match CampTeacher::from_headers(headers) {
+        Ok(CampTeacher(uname)) => uname,
+        Err(resp) => { return resp; },
     };
-    let term_str = match get_head("x-camp-term", headers) {
-        Ok(term) => term,
-        Err(e) => { return respond_bad_request(e); },
-    };
-    let term = match Term::from_str(term_str) {
-        Ok(term) => term,
-        Err(e) => {
-            log::warn!(
-                "Invalid x-camp-term value ({:?}) in attempt to download report for {:?}: {}",
-                term_str, tuname, &e
-            );
-            return respond_bad_request(format!(
-                "Invalid x-camp-term value {:?}: {}", term_str, &e
-            ));
-        },
+    let term = match CampTerm::from_headers(headers) {
+        Ok(CampTerm(term)) => term,
+        Err(resp) => { return resp; },
     };
 
     let glob = glob.read().await;
-    let t = match glob.users.get(tuname) {
+    let t = match glob.users.get(&tuname) {
         Some(User::Teacher(ref t)) => t,
         _ => {
             return respond_bad_request(format!(
@@ -1077,64 +1192,192 @@ async fn download_archive(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Respo
             ));
         },
     };
-    let data = match glob.get_reports_archive_by_teacher(tuname, term).await {
-        Ok(Some(bytes)) => bytes,
-        Ok(None) => {
-            return (
-                StatusCode::NOT_FOUND,
-                format!(
-                    "{} does not have any {} reports completed.",
-                    &t.name, term.as_str()
-                ),
-            ).into_response();
-        },
+    let unames: Vec<String> = glob
+        .get_students_by_teacher(&tuname)
+        .iter()
+        .map(|u| u.uname().to_owned())
+        .collect();
+    let no_reports = || {
+        ApiError::not_found(format!(
+            "{} does not have any {} reports completed.",
+            &t.name, term.as_str()
+        )).into_response()
+    };
+    if unames.is_empty() {
+        return no_reports();
+    }
+
+    let finals = glob.data().read().await.stream_finals(unames, term).await;
+    let mut finals = match finals {
+        Ok(finals) => finals,
         Err(e) => {
+            log::error!(
+                "Error attempting to read {} reports of {:?}'s students: {}",
+                term.as_str(), tuname, &e
+            );
+            return text_500(Some(format!("Error generating archive: {}", &e)));
+        },
+    };
+    // Wait for the first report, so we can respond with a 404 if there
+    // isn't one.
+    let first = match finals.next().await {
+        Some(Ok(first)) => first,
+        None => { return no_reports(); },
+        Some(Err(e)) => {
             log::error!(
                 "Error attempting to generate {} report archive for {:?}: {}",
-                term_str, tuname, &e
+                term.as_str(), tuname, &e
             );
-            return text_500(Some(format!(
-                "Error generating archive: {}", &e
-            )));
+            return text_500(Some(format!("Error generating archive: {}", &e)));
         },
     };
+    // Don't hold the Glob while the archive is written.
+    let downloads = glob.downloads.clone();
+    let uri = glob.uri.clone();
+    drop(glob);
 
-    let disposition_str = format!(
-        "attachment; filename=\"{}_{}.zip\"", tuname, term_str
-    );
-    let disposition_value = match HeaderValue::from_str(&disposition_str) {
-        Ok(val) => val,
+    let (token, path) = match downloads.reserve("zip").await {
+        Ok(x) => x,
+        Err(e) => {
+            log::error!("{}", &e);
+            return text_500(Some(format!("Error generating archive: {}", &e)));
+        },
+    };
+    let size = match write_archive(&path, zip_reports(tuname.clone(), first, finals)).await {
+        Ok(size) => size,
         Err(e) => {
             log::error!(
-                "Error generating Content-Disposition header value ({:?}): {}",
-                &disposition_str, &e
+                "Error writing {} report archive for {:?} to {}: {}",
+                term.as_str(), tuname, path.display(), &e
             );
-            return text_500(Some(format!(
-                "Error generating Content-Disposition header value: {}", &e
-            )));
+            crate::downloads::remove(&path).await;
+            return text_500(Some(format!("Error generating archive: {}", &e)));
         },
     };
 
+    let filename = format!("{}_{}.zip", tuname, term.as_str().to_lowercase());
+    let link = format!("{}/archive/{}", uri.trim_end_matches('/'), &token);
+    downloads.publish(token, path, filename.clone());
+
     (
         StatusCode::OK,
-        [
-            (
-                header::CONTENT_TYPE,
-                HeaderValue::from_static("application/zip"),
-            ),
-            (
-                header::CONTENT_DISPOSITION,
-                disposition_value,
-            ),
-            (
-                HeaderName::from_static("x-camp-action"),
-                HeaderValue::from_static("download-archive"),
-            ),
-        ],
-        data
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("archive-link"),
+        )],
+        Json(serde_json::json!({
+            "link": link,
+            "filename": filename,
+            "size": size,
+        }))
     ).into_response()
 }
 
+/// Write the `chunks` of an archive to a new file at `path`, returning the
+/// size of the file.
+async fn write_archive(
+    path: &std::path::Path,
+    chunks: impl Stream<Item = Result<Vec<u8>, String>>,
+) -> Result<u64, String> {
+    let mut f = tokio::fs::File::create(path).await
+        .map_err(|e| format!("Unable to create file: {}", &e))?;
+    futures::pin_mut!(chunks);
+    let mut size: u64 = 0;
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk?;
+        f.write_all(&chunk).await
+            .map_err(|e| format!("Unable to write file: {}", &e))?;
+        size += chunk.len() as u64;
+    }
+    f.flush().await
+        .map_err(|e| format!("Unable to write file: {}", &e))?;
+
+    Ok(size)
+}
+
+/**
+Handler for GET requests to "/archive/{token}", which serves a report
+archive written by [`download_archive`] while its link is good.
+
+`Range` requests are honored (as are `If-Modified-Since` and friends), so
+browsers can resume interrupted downloads.
+*/
+pub async fn serve_archive(
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    Extension(glob): Extension<Arc<RwLock<Glob>>>,
+) -> Response {
+    log::trace!("boss::serve_archive( [ token ] ) called.");
+
+    let downloads = glob.read().await.downloads.clone();
+    let (path, filename) = match downloads.get(&token) {
+        Some(x) => x,
+        None => { return error_page::respond(StatusCode::NOT_FOUND); },
+    };
+
+    let mut req = Request::new(axum::body::Body::empty());
+    *req.headers_mut() = headers;
+    let mut response = match ServeFile::new(&path).oneshot(req).await {
+        Ok(resp) => resp.map(axum::body::boxed),
+        Err(e) => {
+            log::error!("Error serving archive {}: {}", path.display(), &e);
+            return error_page::respond(StatusCode::INTERNAL_SERVER_ERROR);
+        },
+    };
+
+    let disposition = format!("attachment; filename=\"{}\"", &filename);
+    match HeaderValue::from_str(&disposition) {
+        Ok(val) => {
+            response.headers_mut().insert(header::CONTENT_DISPOSITION, val);
+        },
+        Err(e) => {
+            log::error!(
+                "Error generating Content-Disposition header value ({:?}): {}",
+                &disposition, &e
+            );
+        },
+    }
+
+    response
+}
+
+/**
+Encode the `first` report and the rest of the `finals` as a ZIP archive of
+`uname.pdf` files, a chunk per report, as they come. If anything goes
+wrong partway through, the stream ends with the error.
+*/
+fn zip_reports(
+    tuname: String,
+    first: (String, Vec<u8>),
+    finals: FinalStream,
+) -> impl Stream<Item = Result<Vec<u8>, String>> + Send + 'static {
+    let finals = stream::iter(std::iter::once(Ok(first))).chain(finals);
+
+    stream::unfold(Some((ZipEncoder::new(), finals)), move |state| {
+        let tuname = tuname.clone();
+        async move {
+            let (mut zip, mut finals) = state?;
+            let res = match finals.next().await {
+                Some(Ok((uname, doc))) => zip
+                    .add(&format!("{}.pdf", &uname), &doc)
+                    .map(|chunk| (chunk, Some((zip, finals)))),
+                Some(Err(e)) => Err(e.to_string()),
+                None => {
+                    log::debug!("Archived {} reports of {:?}'s students.", zip.len(), &tuname);
+                    zip.finish().map(|chunk| (chunk, None))
+                }
+            };
+            match res {
+                Ok((chunk, state)) => Some((Ok(chunk), state)),
+                Err(e) => {
+                    log::error!("Error streaming report archive for {:?}: {}", &tuname, &e);
+                    Some((Err(e), None))
+                }
+            }
+        }
+    })
+}
+
 async fn populate_histories(glob: Arc<RwLock<Glob>>) -> Response {
     let map = {
         let glob = glob.read().await;
@@ -1158,4 +1401,230 @@ async fn populate_histories(glob: Arc<RwLock<Glob>>) -> Response {
         ],
         Json(map)
     ).into_response()
-}
\ No newline at end of file
+}
+
+/// A request to work out what exam score a student needs.
+#[derive(Debug, Deserialize)]
+struct WhatIfRequest {
+    uname: String,
+    /// `"fall"` or `"spring"`
+    term: String,
+    /// desired semester grade, in percent
+    target: f32,
+}
+
+/// The answer to a `WhatIfRequest`. All grades and scores are in percent.
+#[derive(Debug, Serialize)]
+struct WhatIfResult {
+    uname: String,
+    term: Term,
+    target: f32,
+    tests: f32,
+    exam_fraction: f32,
+    notices: i16,
+    /// Exam score required to reach `target`; `None` if the exam is worth
+    /// nothing. May be over 100 (out of reach) or under 0 (assured).
+    required: Option<f32>,
+}
+
+/**
+Work out what exam score a student needs to earn a given semester grade,
+given their current test average and notices.
+
+Req'ments:
+```text
+x-camp-action: what-if
+```
+Body should JSON-deserialize to a `WhatIfRequest`.
+*/
+async fn what_if(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request must have application/json body with uname, term, and target."
+                    .to_owned(),
+            );
+        }
+    };
+
+    let req: WhatIfRequest = match serde_json::from_str(&body) {
+        Ok(req) => req,
+        Err(e) => {
+            return respond_bad_request(format!("Unable to deserialize what-if request: {}", &e));
+        }
+    };
+    let term = match Term::from_str(&req.term) {
+        Ok(Term::Summer) => {
+            return respond_bad_request("There is no exam in the Summer term.".to_owned());
+        }
+        Ok(term) => term,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+    if !req.target.is_finite() {
+        return respond_bad_request(format!("{} is not a valid target grade.", &req.target));
+    }
+
+    let glob = glob.read().await;
+    match glob.users.get(&req.uname) {
+        Some(User::Student(_)) => { /* Okay. */ }
+        _ => {
+            return respond_bad_request(format!("{:?} is not a Student.", &req.uname));
+        }
+    }
+    let p = match glob.get_pace_by_student(&req.uname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("Error getting pace for Student {:?}: {}", &req.uname, &e);
+            return text_500(Some(format!(
+                "Error retrieving pace information for {:?}: {}",
+                &req.uname, &e
+            )));
+        }
+    };
+    let pd = match PaceDisplay::from(&p, &glob) {
+        Ok(pd) => pd,
+        Err(e) => {
+            log::error!(
+                "Error generating PaceDisplay info for Student {:?}: {}",
+                &req.uname,
+                &e
+            );
+            return text_500(Some(format!(
+                "Error generating pace display information for {:?}: {}",
+                &req.uname, &e
+            )));
+        }
+    };
+
+    let (tests, exam_frac, notices) = match term {
+        Term::Fall => (pd.fall_tests, pd.fall_exam_frac, pd.fall_notices),
+        _ => (pd.spring_tests, pd.spring_exam_frac, pd.spring_notices),
+    };
+    let required = required_exam_score(tests, exam_frac, notices, req.target / 100.0);
+
+    let data = WhatIfResult {
+        uname: req.uname,
+        term,
+        target: req.target,
+        tests: 100.0 * tests,
+        exam_fraction: exam_frac,
+        notices,
+        required: required.map(|f| 100.0 * f),
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("what-if"),
+        )],
+        Json(data),
+    )
+        .into_response()
+}
+
+/**
+Respond with every student whose semester grade is projected to fall below
+the threshold; see [`at_risk::respond`].
+
+Req'ments:
+```text
+x-camp-action: at-risk
+```
+*/
+async fn at_risk(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+
+    let tunames: Vec<&str> = glob
+        .unames_with_role(Role::Teacher)
+        .iter()
+        .map(String::as_str)
+        .collect();
+
+    at_risk::respond(tunames, body, &glob).await
+}
+
+/**
+Respond with how sending email has been going; see
+[`HealthReport`](crate::email::HealthReport).
+
+Req'ments:
+```text
+x-camp-action: email-health
+```
+
+Response body looks like
+```json
+{
+    "backend": "smtp",
+    "destination": "mail.camelotacademy.org:587",
+    "last_success": 1697040000,
+    "pending": 0,
+    "sent": 212,
+    "failed": 3,
+    "consecutive_failures": 3,
+    "recent_failures": [ { "at": 1697043600, "reason": "..." }, ... ],
+    "warning": "Email isn't being sent: ..."
+}
+```
+*/
+async fn email_health(glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let report = glob.email_health.report(glob.mailer.as_ref());
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("email-health"),
+        )],
+        Json(json!({
+            "backend": report.backend,
+            "destination": report.destination,
+            "last_success": report.last_success,
+            "pending": report.pending,
+            "sent": report.sent,
+            "failed": report.failed,
+            "consecutive_failures": report.consecutive_failures,
+            "recent_failures": report.recent_failures,
+            "warning": glob.email_health.warning(),
+        })),
+    )
+        .into_response()
+}
+
+/**
+Respond with the weekly lag history of the student whose uname is the body
+of the request, or of every student if there's no body; see
+[`lag_history::respond`].
+
+Req'ments:
+```text
+x-camp-action: lag-history
+```
+*/
+async fn lag_history(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+
+    let students: Vec<&Student> = match body.as_deref().map(str::trim) {
+        Some(uname) if !uname.is_empty() => match glob.users.get(uname) {
+            Some(User::Student(s)) => vec![s],
+            _ => {
+                return respond_bad_request(format!("{:?} is not a Student.", uname));
+            }
+        },
+        _ => glob
+            .users
+            .values()
+            .filter_map(|u| match u {
+                User::Student(s) => Some(s),
+                _ => None,
+            })
+            .collect(),
+    };
+
+    lag_history::respond(students, &glob).await
+}
@@ -0,0 +1,193 @@
+/*!
+Congratulatory emails for students who finish the year's goals.
+
+Whenever a change to a student's goals leaves every scheduled goal done
+(see [`Pace::is_complete`]), their Teacher gets a short email saying so
+(and, if the `completion_email` configuration option is
+`"teacher-and-boss"`, so does every Boss). Each student is only celebrated
+once per year; see
+[`Store::claim_completion_notice`](crate::store::Store::claim_completion_notice).
+
+The text is rendered from the `completion_notice` (`.txt`) template and
+wrapped in an [`Email`](crate::email::Email) by the `completion_email`
+(`.json`) template.
+*/
+use std::sync::Arc;
+
+use serde::Serialize;
+use time::Date;
+use tokio::sync::RwLock;
+
+use super::{send_rendered_email, render_json_template, render_raw_template};
+use crate::{
+    config::{CompletionEmail, Glob},
+    pace::Pace,
+    user::{Role, User},
+    DateStyle, MiniString,
+};
+
+#[derive(Debug, Serialize)]
+struct Recipient<'a> {
+    email: &'a str,
+    name: &'a str,
+}
+
+/// Data for rendering the `completion_notice` template.
+#[derive(Debug, Serialize)]
+struct NoticeData<'a> {
+    teacher: &'a str,
+    student: String,
+    n_goals: usize,
+    date: Date,
+    date_style: DateStyle,
+    service_uri: &'a str,
+}
+
+/// Data for rendering the `completion_email` template.
+#[derive(Debug, Serialize)]
+struct NoticeEmail<'a> {
+    to: Recipient<'a>,
+    cc: Vec<Recipient<'a>>,
+    student: &'a str,
+    text: &'a str,
+}
+
+/// Render the email congratulating `p`'s Teacher.
+fn compose(p: &Pace, glob: &Glob, today: &Date) -> Result<String, String> {
+    let student = format!("{} {}", &p.student.rest, &p.student.last);
+    let data = NoticeData {
+        teacher: &p.teacher.name,
+        student: student.clone(),
+        n_goals: p.goals.iter().filter(|g| g.due.is_some()).count(),
+        date: *today,
+        date_style: glob.date_style,
+        service_uri: &glob.uri,
+    };
+    let text = render_raw_template("completion_notice", &data)?;
+
+    let mut cc: Vec<Recipient> = Vec::new();
+    if glob.completion_email == CompletionEmail::TeacherAndBoss {
+        for u in glob.users_with_role(Role::Boss) {
+            if let User::Boss(b) = u {
+                cc.push(Recipient {
+                    email: &b.email,
+                    name: &b.uname,
+                });
+            }
+        }
+    }
+
+    let data = NoticeEmail {
+        to: Recipient {
+            email: &p.teacher.base.email,
+            name: &p.teacher.name,
+        },
+        cc,
+        student: &student,
+        text: &text,
+    };
+    render_json_template("completion_email", &data)
+}
+
+/**
+If `p` is complete and nobody has been congratulated about it yet this
+year, queue up an email congratulating the student's Teacher.
+
+Usually the notice is claimed and the email put in the outbox in one
+transaction (see [`Store::queue_completion_notice`]), so it gets sent
+(and retried) like any other queued email. Without an outbox (like in a
+sandbox), it's sent in the background instead (so this needs `glob_lock`,
+the lock around `glob`, to hand off to the sending task). Nothing here is
+fatal to the request that changed the goals, so errors are only logged;
+if sending fails, the notice is released, so the next goal update will try
+again.
+
+[`Store::queue_completion_notice`]: crate::store::Store::queue_completion_notice
+*/
+pub async fn notify_if_complete(p: &Pace, glob: &Glob, glob_lock: &Arc<RwLock<Glob>>) {
+    if glob.completion_email == CompletionEmail::Off || !p.is_complete() {
+        return;
+    }
+
+    let uname = p.student.base.uname.clone();
+    if let Some(outbox) = glob.outbox.as_ref() {
+        let body = match compose(p, glob, &crate::now()) {
+            Ok(body) => body,
+            Err(e) => {
+                log::error!("Error composing completion notice for {:?}: {}", &uname, &e);
+                return;
+            }
+        };
+        let res = glob
+            .data()
+            .read()
+            .await
+            .queue_completion_notice(&uname, &body)
+            .await;
+        match res {
+            Ok(true) => {
+                log::info!("Queued completion notice for {:?}.", &uname);
+                outbox.wake();
+            }
+            Ok(false) => {}
+            Err(e) => {
+                log::error!("Error queueing completion notice for {:?}: {}", &uname, &e);
+            }
+        }
+        return;
+    }
+
+    match glob
+        .data()
+        .read()
+        .await
+        .claim_completion_notice(&uname)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return;
+        }
+        Err(e) => {
+            log::error!("Error claiming completion notice for {:?}: {}", &uname, &e);
+            return;
+        }
+    }
+
+    let body = match compose(p, glob, &crate::now()) {
+        Ok(body) => body,
+        Err(e) => {
+            log::error!("Error composing completion notice for {:?}: {}", &uname, &e);
+            release(glob, &uname).await;
+            return;
+        }
+    };
+
+    let glob_lock = glob_lock.clone();
+    tokio::spawn(async move {
+        let glob = glob_lock.read().await;
+        match send_rendered_email(body, &glob, MiniString::from(uname.as_str())).await {
+            Ok(()) => {
+                log::info!("Sent completion notice for {:?}.", &uname);
+            }
+            Err(e) => {
+                log::error!("Error sending completion notice for {:?}: {}", &uname, &e);
+                release(&glob, &uname).await;
+            }
+        }
+    });
+}
+
+/// Release the completion notice claimed for `uname` (after failing to
+/// send it).
+async fn release(glob: &Glob, uname: &str) {
+    if let Err(e) = glob
+        .data()
+        .read()
+        .await
+        .release_completion_notice(uname)
+        .await
+    {
+        log::error!("Error releasing completion notice for {:?}: {}", uname, &e);
+    }
+}
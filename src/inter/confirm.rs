@@ -0,0 +1,591 @@
+/*!
+A safety interlock for actions that destroy data.
+
+The first request for one of these actions (see [`describe`]) doesn't do
+anything. It's answered with
+```text
+x-camp-action: confirm-action
+```
+and a body like
+```json
+{
+    "action": "delete-course",
+    "summary": "Delete Course pha (Pre-Algebra) and its 14 chapters.",
+    "token": "K7QX3M",
+    "expires": 120
+}
+```
+The action only goes through when the same user makes the same request
+(same action, body, and `x-camp-student` and `x-camp-course` headers)
+again, with the `token` in an `x-camp-confirm` header, within `expires`
+seconds. Each token works once. The frontends make the user type the
+token in, so a destructive action can't just be clicked through.
+
+Tokens are only kept in memory, so a restart forgets them (and anyone in
+the middle of confirming something has to start over).
+*/
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use super::{respond_bad_request, text_500};
+use crate::{anon, auth_export, config::Glob, user::User};
+
+/// How long (in seconds) a confirmation token is good for.
+pub const TTL_SECS: u64 = 120;
+
+/// Characters confirmation tokens are made of: no lowercase (the check
+/// ignores case), and nothing easily mistaken for something else.
+const TOKEN_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const TOKEN_LEN: usize = 6;
+
+/// Request headers that, along with the body, say what an action would do
+/// something to.
+const TARGET_HEADERS: &[&str] = &["x-camp-student", "x-camp-course"];
+
+/// A request waiting to be confirmed.
+#[derive(Debug)]
+struct Pending {
+    uname: String,
+    action: String,
+    target: blake3::Hash,
+    expires: Instant,
+}
+
+/// Confirmation tokens that have been issued and not yet used.
+#[derive(Debug, Default)]
+pub struct Confirmations {
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl Confirmations {
+    pub fn new() -> Confirmations {
+        Confirmations::default()
+    }
+
+    /// Issue (at `now`) a token confirming that `uname` wants to take
+    /// `action` on `target`.
+    fn issue_at(&self, uname: &str, action: &str, target: blake3::Hash, now: Instant) -> String {
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, p| p.expires > now);
+
+        let mut rng = rand::thread_rng();
+        let token = loop {
+            let token: String = (0..TOKEN_LEN)
+                .map(|_| TOKEN_CHARS[rng.gen_range(0..TOKEN_CHARS.len())] as char)
+                .collect();
+            if !pending.contains_key(&token) {
+                break token;
+            }
+        };
+
+        pending.insert(
+            token.clone(),
+            Pending {
+                uname: uname.to_owned(),
+                action: action.to_owned(),
+                target,
+                expires: now + Duration::from_secs(TTL_SECS),
+            },
+        );
+        token
+    }
+
+    /**
+    Use up `token` (at `now`), checking that it was issued to `uname` for
+    taking `action` on `target`, and hasn't expired.
+
+    A token is used up even when the check fails, so they can't be guessed.
+    */
+    fn redeem_at(
+        &self,
+        token: &str,
+        uname: &str,
+        action: &str,
+        target: blake3::Hash,
+        now: Instant,
+    ) -> Result<(), String> {
+        let token = token.trim().to_uppercase();
+        let p = match self.pending.lock().unwrap().remove(&token) {
+            Some(p) => p,
+            None => {
+                return Err(format!(
+                    "{:?} isn't a current confirmation code (they only work once). \
+                    Please start over.",
+                    &token
+                ));
+            }
+        };
+
+        if p.expires <= now {
+            Err("That confirmation code has expired. Please start over.".to_owned())
+        } else if p.uname != uname || p.action != action || p.target != target {
+            Err("That confirmation code was for something else. Please start over.".to_owned())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Hash together what the request with the given `headers` and `body`
+/// would take its action on.
+fn target(headers: &HeaderMap, body: Option<&str>) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new();
+    for name in TARGET_HEADERS.iter() {
+        let val = headers.get(*name).map(|v| v.as_bytes()).unwrap_or(&[]);
+        hasher.update(&(val.len() as u64).to_le_bytes());
+        hasher.update(val);
+    }
+    hasher.update(body.unwrap_or("").as_bytes());
+    hasher.finalize()
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+    headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or("")
+}
+
+/// Just the part of a `roster-apply` body that matters here.
+#[derive(Deserialize)]
+struct RosterWithdraw {
+    #[serde(default)]
+    withdraw: bool,
+}
+
+/// Just the part of a `delete-goal` body that matters here. (A body that's
+/// only a goal id doesn't force anything.)
+#[derive(Deserialize)]
+struct ForcedDelete {
+    id: i64,
+    #[serde(default)]
+    force: bool,
+}
+
+/// The body of a `delete-goals` request.
+#[derive(Deserialize)]
+struct GoalsToDelete {
+    uname: String,
+    sym: Option<String>,
+    term: Option<String>,
+    #[serde(default)]
+    unstarted: bool,
+}
+
+impl GoalsToDelete {
+    /// Describe which goals of `who` these are, like `"the unstarted goals of
+    /// Jo Smith from Course pha1 due in the Spring term"`.
+    fn describe(&self, who: &str) -> String {
+        let mut desc = if self.unstarted {
+            format!("the unstarted goals of {}", who)
+        } else {
+            format!("the goals (completed or not) of {}", who)
+        };
+        if let Some(sym) = &self.sym {
+            desc.push_str(&format!(" from Course {}", sym));
+        }
+        if let Some(term) = &self.term {
+            desc.push_str(&format!(" due in the {} term", term));
+        }
+        desc
+    }
+}
+
+/**
+If `action` (requested by `uname`, with `headers` and `body`) destroys
+data, return a description of what exactly it would destroy; otherwise,
+`None`.
+
+These are the Admin API's `delete-user`, `delete-course`,
+`delete-chapter`, `delete-completion`, `delete-year`,
+`delete-exam-window`, `import-auth`, and `reset-students` actions, and
+`roster-apply` when it withdraws students, along with the Teacher API's
+`clear-goals` and `delete-goals` actions, and `delete-goal` when it's
+forced (to delete a completed goal).
+*/
+pub async fn describe(
+    action: &str,
+    uname: &str,
+    headers: &HeaderMap,
+    body: Option<&str>,
+    glob: &Glob,
+) -> Result<Option<String>, String> {
+    let body = body.unwrap_or("").trim();
+
+    let desc = match action {
+        "delete-user" => match glob.users.get(body) {
+            Some(User::Student(s)) => format!(
+                "Delete Student {} {} ({}), along with all of their goals and reports.",
+                anon::rest(&s.rest),
+                anon::last(&s.last),
+                body
+            ),
+            Some(u) => format!("Delete {} {}.", u.role(), body),
+            None => format!("Delete user {:?}.", body),
+        },
+
+        "delete-course" => match glob.course_by_sym(body) {
+            Some(crs) => format!(
+                "Delete Course {} ({}) and its {} chapters.",
+                &crs.sym,
+                &crs.title,
+                crs.all_chapters().count()
+            ),
+            None => format!("Delete Course {:?}.", body),
+        },
+
+        "delete-chapter" => {
+            let id: Option<i64> = body.parse().ok();
+            let found = glob.courses.values().find_map(|crs| {
+                crs.all_chapters()
+                    .find(|ch| Some(ch.id) == id)
+                    .map(|ch| (crs, ch))
+            });
+            match found {
+                Some((crs, ch)) => format!(
+                    "Delete Chapter {} ({}) of Course {}.",
+                    &ch.seq, &ch.title, &crs.sym
+                ),
+                None => format!("Delete Chapter #{}.", body),
+            }
+        }
+
+        "delete-completion" => format!(
+            "Remove Course {:?} from the completion history of {:?}.",
+            header_str(headers, "x-camp-course"),
+            header_str(headers, "x-camp-student")
+        ),
+
+        "delete-year" => match glob.academic_years.iter().find(|y| y.label == body) {
+            Some(y) => format!(
+                "Delete Academic Year {} ({} to {}).",
+                &y.label, &y.starts, &y.ends
+            ),
+            None => format!("Delete Academic Year {:?}.", body),
+        },
+
+        "delete-exam-window" => {
+            let id: Option<i64> = body.parse().ok();
+            match glob.calendar.exam_windows().iter().find(|w| Some(w.id) == id) {
+                Some(w) => format!(
+                    "Delete Exam Window {} ({} to {}).",
+                    &w.name, &w.start, &w.end
+                ),
+                None => format!("Delete Exam Window #{}.", body),
+            }
+        }
+
+        "import-auth" => match auth_export::read(body, glob) {
+            Ok((_, v)) => format!(
+                "Replace the passwords of {} users with the ones in the export made {}.",
+                &v.n_matched,
+                v.created.map(|d| d.to_string()).as_deref().unwrap_or("on an unknown date")
+            ),
+            // The action itself will say what's wrong with it.
+            Err(_) => "Replace users' passwords with the ones in an export.".to_owned(),
+        },
+
+        "reset-students" => {
+            let preview = glob.yearly_nuke_preview().await.map_err(|e| e.to_string())?;
+            format!(
+                "Delete all student data: {} goals, and the reports, of {} students.",
+                preview.counts.get("goals").copied().unwrap_or(0),
+                preview.unames.len()
+            )
+        }
+
+        "roster-apply" => match serde_json::from_str::<RosterWithdraw>(body) {
+            Ok(RosterWithdraw { withdraw: true }) => {
+                "Apply the roster changes, deleting the students who have withdrawn \
+                (along with all of their goals and reports)."
+                    .to_owned()
+            }
+            _ => {
+                return Ok(None);
+            }
+        },
+
+        "clear-goals" => match glob.users.get(body) {
+            Some(User::Student(s)) if s.teacher == uname => {
+                let n = glob
+                    .data()
+                    .read()
+                    .await
+                    .get_goals_by_student(body)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .len();
+                format!(
+                    "Delete all {} goals of {} {}.",
+                    &n,
+                    anon::rest(&s.rest),
+                    anon::last(&s.last)
+                )
+            }
+            _ => format!("Delete all the goals of {:?}.", body),
+        },
+
+        "delete-goal" => match serde_json::from_str::<ForcedDelete>(body) {
+            Ok(ForcedDelete { id, force: true }) => format!(
+                "Delete Goal #{}, even if it has been completed (removing it from \
+                the student's grades).",
+                id
+            ),
+            _ => {
+                return Ok(None);
+            }
+        },
+
+        "delete-goals" => match serde_json::from_str::<GoalsToDelete>(body) {
+            Ok(gtd) => {
+                let who = match glob.users.get(&gtd.uname) {
+                    Some(User::Student(s)) if s.teacher == uname => {
+                        format!("{} {}", anon::rest(&s.rest), anon::last(&s.last))
+                    }
+                    _ => format!("{:?}", &gtd.uname),
+                };
+                format!("Delete {}.", &gtd.describe(&who))
+            }
+            // The action itself will complain about the body.
+            Err(_) => {
+                return Ok(None);
+            }
+        },
+
+        _ => {
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(desc))
+}
+
+/**
+Hold up `action` (requested by `uname`, with `headers` and `body`) if it
+destroys data and hasn't been confirmed; see the module-level docs.
+
+Returns `Ok(())` if the action may go ahead, and otherwise the response to
+send instead.
+*/
+pub async fn interlock(
+    action: &str,
+    uname: &str,
+    headers: &HeaderMap,
+    body: Option<&str>,
+    glob: &Arc<RwLock<Glob>>,
+) -> Result<(), Response> {
+    let glob = glob.read().await;
+    let summary = match describe(action, uname, headers, body, &glob).await {
+        Ok(Some(summary)) => summary,
+        Ok(None) => {
+            return Ok(());
+        }
+        Err(e) => {
+            log::error!("Error describing {:?} for confirmation: {}", action, &e);
+            return Err(text_500(Some(format!(
+                "Unable to check what this would do: {}",
+                &e
+            ))));
+        }
+    };
+
+    let target = target(headers, body);
+    let now = Instant::now();
+
+    if let Some(token) = headers.get("x-camp-confirm") {
+        let token = token.to_str().unwrap_or("");
+        return match glob
+            .confirmations
+            .redeem_at(token, uname, action, target, now)
+        {
+            Ok(()) => {
+                log::info!("{:?} confirmed {:?}: {}", uname, action, &summary);
+                Ok(())
+            }
+            Err(e) => {
+                log::warn!("{:?} failed to confirm {:?}: {}", uname, action, &e);
+                Err(respond_bad_request(e))
+            }
+        };
+    }
+
+    let token = glob.confirmations.issue_at(uname, action, target, now);
+    let body = json!({
+        "action": action,
+        "summary": summary,
+        "token": token,
+        "expires": TTL_SECS,
+    });
+
+    Err((
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("confirm-action"),
+        )],
+        Json(body),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let c = Confirmations::new();
+        let now = Instant::now();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-camp-student", HeaderValue::from_static("jsmith"));
+        headers.insert("x-camp-course", HeaderValue::from_static("pha"));
+        let t = target(&headers, None);
+
+        let tok = c.issue_at("admin", "delete-completion", t, now);
+        assert_eq!(tok.len(), TOKEN_LEN);
+        // Typed in by hand, with stray whitespace and lowercase.
+        let typed = format!(" {} ", tok.to_lowercase());
+        assert!(c
+            .redeem_at(&typed, "admin", "delete-completion", t, now)
+            .is_ok());
+        // Tokens only work once.
+        assert!(c.redeem_at(&tok, "admin", "delete-completion", t, now).is_err());
+
+        // They're only good for the same user, action, and target...
+        headers.insert("x-camp-student", HeaderValue::from_static("kdoe"));
+        let other = target(&headers, None);
+        assert_ne!(t, other);
+        let tok = c.issue_at("admin", "delete-completion", t, now);
+        assert!(c.redeem_at(&tok, "admin", "delete-completion", other, now).is_err());
+        let tok = c.issue_at("admin", "delete-completion", t, now);
+        assert!(c.redeem_at(&tok, "boss", "delete-completion", t, now).is_err());
+        let tok = c.issue_at("admin", "delete-user", t, now);
+        assert!(c.redeem_at(&tok, "admin", "delete-completion", t, now).is_err());
+
+        // ...and only for a little while.
+        let tok = c.issue_at("admin", "delete-completion", t, now);
+        let later = now + Duration::from_secs(TTL_SECS + 1);
+        assert!(c.redeem_at(&tok, "admin", "delete-completion", t, later).is_err());
+        // Expired ones get forgotten.
+        c.issue_at("admin", "delete-completion", t, later);
+        assert_eq!(c.pending.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn targets() {
+        let headers = HeaderMap::new();
+        assert_eq!(target(&headers, Some("pha")), target(&headers, Some("pha")));
+        assert_ne!(target(&headers, Some("pha")), target(&headers, Some("geo")));
+        assert_eq!(target(&headers, None), target(&headers, Some("")));
+    }
+
+    #[tokio::test]
+    async fn admin_deletions() {
+        use time::macros::date;
+
+        let mut glob = crate::config::in_memory(crate::store::MemStore::new())
+            .await
+            .unwrap();
+        glob.academic_years = vec![crate::store::AcademicYear {
+            label: "2022-23".to_owned(),
+            starts: date!(2022 - 08 - 22),
+            ends: date!(2023 - 06 - 02),
+            active: true,
+        }];
+        glob.calendar = crate::pace::AcademicCalendar::new(vec![]).with_exam_windows(vec![
+            crate::pace::ExamWindow {
+                id: 3,
+                name: "Fall Exams".to_owned(),
+                start: date!(2022 - 12 - 19),
+                end: date!(2022 - 12 - 21),
+            },
+        ]);
+        let key = "correct horse battery staple";
+        glob.auth_export_key = Some(key.to_owned());
+        let export = auth_export::AuthExport {
+            created: date!(2023 - 01 - 20),
+            records: vec![],
+        }
+        .seal(key)
+        .unwrap();
+
+        let headers = HeaderMap::new();
+        let desc = |action: &'static str, body: String| {
+            let glob = &glob;
+            let headers = &headers;
+            async move {
+                describe(action, "admin", headers, Some(&body), glob)
+                    .await
+                    .unwrap()
+                    .unwrap()
+            }
+        };
+
+        assert_eq!(
+            desc("delete-year", "2022-23".to_owned()).await,
+            "Delete Academic Year 2022-23 (2022-08-22 to 2023-06-02)."
+        );
+        assert_eq!(
+            desc("delete-exam-window", "3".to_owned()).await,
+            "Delete Exam Window Fall Exams (2022-12-19 to 2022-12-21)."
+        );
+        // Even ones that don't exist have to be confirmed.
+        assert_eq!(
+            desc("delete-exam-window", "4".to_owned()).await,
+            "Delete Exam Window #4."
+        );
+        assert_eq!(
+            desc("import-auth", export).await,
+            "Replace the passwords of 0 users with the ones in the export made 2023-01-20."
+        );
+        assert_eq!(
+            desc("import-auth", "not an export".to_owned()).await,
+            "Replace users' passwords with the ones in an export."
+        );
+    }
+
+    #[tokio::test]
+    async fn goal_deletions() {
+        let glob = crate::config::in_memory(crate::store::MemStore::new())
+            .await
+            .unwrap();
+        let headers = HeaderMap::new();
+        let desc = |action: &'static str, body: &'static str| {
+            describe(action, "berro", &headers, Some(body), &glob)
+        };
+
+        // Deleting a goal only needs confirming when it's forced.
+        assert_eq!(desc("delete-goal", "12").await, Ok(None));
+        assert_eq!(desc("delete-goal", r#"{ "id": 12 }"#).await, Ok(None));
+        let forced = desc("delete-goal", r#"{ "id": 12, "force": true }"#).await;
+        assert!(forced.unwrap().unwrap().starts_with("Delete Goal #12,"));
+
+        // Deleting some of a student's goals always does.
+        let some = r#"{ "uname": "frog", "sym": "pha1", "unstarted": true }"#;
+        assert_eq!(
+            desc("delete-goals", some).await,
+            Ok(Some(
+                "Delete the unstarted goals of \"frog\" from Course pha1.".to_owned()
+            ))
+        );
+        let some = r#"{ "uname": "frog", "term": "Spring" }"#;
+        assert_eq!(
+            desc("delete-goals", some).await,
+            Ok(Some(
+                "Delete the goals (completed or not) of \"frog\" due in the Spring term."
+                    .to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,278 @@
+/*!
+The weekly summary ("digest") email sent to Teachers.
+
+Every Monday morning (at the hour given by the `weekly_digest_hour`
+configuration option, in UTC), each Teacher who hasn't opted out gets a
+short plain-text email listing
+
+  * students who are behind pace, and
+  * goals due in the coming week.
+
+The text is rendered from the `teacher_digest` (`.txt`) template and wrapped
+in an [`Email`](crate::email::Email) by the `teacher_digest_email` (`.json`)
+template.
+*/
+use std::sync::Arc;
+
+use serde::Serialize;
+use time::{Date, Duration};
+use tokio::sync::RwLock;
+
+use super::{render_json_template, render_raw_template};
+use crate::{
+    config::Glob,
+    email::{Email, EmailSender, Health},
+    pace::{Pace, Source},
+    user::{Role, Teacher, User},
+    DateStyle,
+};
+
+const SECS_PER_HOUR: u64 = 60 * 60;
+const SECS_PER_DAY: u64 = 24 * SECS_PER_HOUR;
+const SECS_PER_WEEK: u64 = 7 * SECS_PER_DAY;
+// The Unix epoch fell on a Thursday, so Monday is four days into an
+// epoch-aligned week.
+const MONDAY_OFFSET: u64 = 4 * SECS_PER_DAY;
+
+#[derive(Debug, Serialize)]
+struct BehindLine {
+    name: String,
+    n_done: usize,
+    n_due: usize,
+    /// weight done minus weight due, as a fraction of the total weight
+    lag: f32,
+    /// instructional days since the oldest overdue goal was due
+    days: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct DueLine {
+    name: String,
+    due: Date,
+    course: String,
+    seq: i16,
+}
+
+/// Data for rendering the `teacher_digest` template.
+#[derive(Debug, Serialize)]
+struct DigestData<'a> {
+    name: &'a str,
+    date: Date,
+    date_style: DateStyle,
+    behind: Vec<BehindLine>,
+    due: Vec<DueLine>,
+    service_uri: &'a str,
+}
+
+/// Data for rendering the `teacher_digest_email` template.
+#[derive(Debug, Serialize)]
+struct DigestEmail<'a> {
+    email: &'a str,
+    name: &'a str,
+    date: Date,
+    date_style: DateStyle,
+    text: &'a str,
+}
+
+/// Render the text of a Teacher's digest email from their students' `paces`.
+fn compose(t: &Teacher, paces: &[Pace], glob: &Glob, today: &Date) -> Result<String, String> {
+    let week_end = today.saturating_add(Duration::days(7));
+
+    let mut behind: Vec<BehindLine> = Vec::new();
+    let mut due: Vec<(Date, DueLine)> = Vec::new();
+
+    for p in paces.iter() {
+        let name = format!("{}, {}", &p.student.last, &p.student.rest);
+
+        if p.done_weight < p.due_weight {
+            let n_due = p
+                .goals
+                .iter()
+                .filter(|g| matches!(g.due, Some(d) if d < *today))
+                .count();
+            let n_done = p.goals.iter().filter(|g| g.done.is_some()).count();
+            behind.push(BehindLine {
+                name: name.clone(),
+                n_done,
+                n_due,
+                lag: p.lag(),
+                days: p.days_behind(&glob.calendar, today),
+            });
+        }
+
+        for g in p.goals.iter() {
+            let d = match (g.due, g.done) {
+                (Some(d), None) if d >= *today && d < week_end => d,
+                _ => continue,
+            };
+            let (course, seq) = match &g.source {
+                Source::Book(bch) => match glob.course_by_sym(&bch.sym) {
+                    Some(crs) => (crs.title.clone(), bch.seq),
+                    None => (bch.sym.clone(), bch.seq),
+                },
+                Source::Custom(_) => continue,
+            };
+            due.push((
+                d,
+                DueLine {
+                    name: name.clone(),
+                    due: d,
+                    course,
+                    seq,
+                },
+            ));
+        }
+    }
+
+    behind.sort_by(|a, b| a.lag.total_cmp(&b.lag));
+    due.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.name.cmp(&b.1.name)));
+
+    let data = DigestData {
+        name: &t.name,
+        date: *today,
+        date_style: glob.date_style,
+        behind,
+        due: due.into_iter().map(|(_, line)| line).collect(),
+        service_uri: &glob.uri,
+    };
+
+    render_raw_template("teacher_digest", &data)
+}
+
+/// A Teacher's digest email, ready to send.
+struct Digest {
+    /// Teacher it's for.
+    uname: String,
+    email: Email,
+}
+
+/// Compose the digest email for a single Teacher, if they have any students.
+async fn compose_one(t: &Teacher, glob: &Glob, today: &Date) -> Result<Option<Email>, String> {
+    let paces = glob
+        .get_paces_by_teacher(&t.base.uname)
+        .await
+        .map_err(|e| format!("Error retrieving paces: {}", &e))?;
+    if paces.is_empty() {
+        log::debug!("Teacher {:?} has no students; no digest.", &t.base.uname);
+        return Ok(None);
+    }
+
+    let text = compose(t, &paces, glob, today)?;
+    let data = DigestEmail {
+        email: &t.base.email,
+        name: &t.name,
+        date: *today,
+        date_style: glob.date_style,
+        text: &text,
+    };
+    let body = render_json_template("teacher_digest_email", &data)?;
+
+    Email::from_json(&body).map(Some)
+}
+
+/**
+Compose the digest email for every Teacher who hasn't opted out.
+
+Failures for individual Teachers are logged, but don't stop the rest from
+being composed.
+*/
+async fn compose_all(glob: &Glob) -> Result<Vec<Digest>, String> {
+    log::trace!("digest::compose_all( [ Glob ] ) called.");
+
+    let optouts = glob
+        .data()
+        .read()
+        .await
+        .get_digest_optouts()
+        .await
+        .map_err(|e| format!("Error retrieving digest opt-outs: {}", &e))?;
+
+    let today = crate::now();
+    let mut digests: Vec<Digest> = Vec::new();
+    for u in glob.users_with_role(Role::Teacher) {
+        let t = match u {
+            User::Teacher(t) => t,
+            _ => continue,
+        };
+        if optouts.contains(&t.base.uname) {
+            continue;
+        }
+
+        match compose_one(t, glob, &today).await {
+            Ok(Some(email)) => digests.push(Digest {
+                uname: t.base.uname.clone(),
+                email,
+            }),
+            Ok(None) => {}
+            Err(e) => {
+                log::error!("Error composing weekly digest for {:?}: {}", &t.base.uname, &e);
+            }
+        }
+    }
+
+    Ok(digests)
+}
+
+/**
+Send the composed `digests` through `mailer`, keeping track of how it goes
+in `health`.
+
+Returns the number of emails sent. Failures for individual Teachers are
+logged, but don't stop the rest from being sent.
+*/
+async fn send_all(digests: Vec<Digest>, mailer: &dyn EmailSender, health: &Health) -> usize {
+    let mut n_sent: usize = 0;
+    for d in digests.into_iter() {
+        match health.send(mailer, d.email).await {
+            Ok(()) => {
+                n_sent += 1;
+            }
+            Err(e) => {
+                log::error!("Error sending weekly digest to {:?}: {}", &d.uname, &e);
+            }
+        }
+    }
+    n_sent
+}
+
+/// Number of seconds from `now` (in seconds since the Unix epoch) until
+/// the next Monday at `hour` o'clock UTC.
+pub(super) fn secs_until_monday(now: u64, hour: u8) -> u64 {
+    let target = MONDAY_OFFSET + (hour as u64) * SECS_PER_HOUR;
+    let into_week = now % SECS_PER_WEEK;
+    match (target + SECS_PER_WEEK - into_week) % SECS_PER_WEEK {
+        0 => SECS_PER_WEEK,
+        n => n,
+    }
+}
+
+/**
+Send the digest every Monday at `hour` o'clock UTC, forever.
+
+This is meant to be `tokio::spawn()`ed when the server starts up.
+*/
+pub async fn run_weekly(glob: Arc<RwLock<Glob>>, hour: u8) {
+    loop {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let wait = secs_until_monday(now, hour);
+        log::info!("Next weekly digest in {} seconds.", &wait);
+        tokio::time::sleep(std::time::Duration::from_secs(wait)).await;
+
+        // Sending can take a while, so the `Glob` is only locked while the
+        // digests are composed.
+        let (res, mailer, health) = {
+            let glob = glob.read().await;
+            (compose_all(&glob).await, glob.mailer.clone(), glob.email_health.clone())
+        };
+        match res {
+            Ok(digests) => {
+                let n = send_all(digests, mailer.as_ref(), &health).await;
+                log::info!("Sent {} weekly digest emails.", &n);
+            }
+            Err(e) => log::error!("Error composing weekly digests: {}", &e),
+        }
+    }
+}
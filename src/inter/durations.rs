@@ -0,0 +1,274 @@
+/*!
+How long chapters actually take students, compared with how long their
+weights say they should.
+
+Each completed, scheduled goal's duration (see
+[`Pace::durations`](crate::pace::Pace::durations)) is measured in
+instructional days from the student's previous completion, and its expected
+duration is the share of the calendar its weight allots it (which is how
+due dates are spaced). The Admin API's `chapter-durations` action (see
+[`respond`]) totals these for each textbook chapter across every student,
+so Admins can see which chapters are weighted too light or too heavy.
+The same totals are what [`suggest`](super::suggest) bases its suggested
+schedules on.
+*/
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    http::header::{HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use super::{respond_bad_request, text_500};
+use crate::{
+    config::Glob,
+    pace::{GoalDuration, Source},
+    user::Role,
+};
+
+/// Optional body of a `chapter-durations` request.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+struct DurationsRequest {
+    /// Only report on the course with this symbol.
+    sym: Option<String>,
+}
+
+/// One chapter in the response to a `chapter-durations` request. All
+/// durations are in instructional days.
+#[derive(Debug, PartialEq, Serialize)]
+struct ChapterDuration {
+    sym: String,
+    seq: i16,
+    title: String,
+    /// Number of completed goals measured.
+    n: usize,
+    mean: f32,
+    median: f32,
+    expected: f32,
+    /// Total actual duration over total expected duration; above 1 means
+    /// the chapter takes longer than its weight allows for.
+    ratio: f32,
+}
+
+/// Actual and expected durations of the goals for one chapter.
+#[derive(Debug, Default)]
+pub(super) struct Samples {
+    pub actual: Vec<f32>,
+    pub expected: f32,
+}
+
+/// Add `durations` to the `Samples` for their chapters, leaving out custom
+/// chapters and courses other than `sym` (if given).
+fn collect(
+    samples: &mut BTreeMap<(String, i16), Samples>,
+    durations: &[GoalDuration<'_>],
+    sym: Option<&str>,
+) {
+    for d in durations.iter() {
+        let bch = match &d.goal.source {
+            Source::Book(bch) => bch,
+            Source::Custom(_) => continue,
+        };
+        if sym.map(|s| s != bch.sym).unwrap_or(false) {
+            continue;
+        }
+        let s = samples.entry((bch.sym.clone(), bch.seq)).or_default();
+        s.actual.push(d.actual);
+        s.expected += d.expected;
+    }
+}
+
+fn median(xs: &mut [f32]) -> f32 {
+    xs.sort_by(f32::total_cmp);
+    match xs.len() {
+        0 => 0.0,
+        n if n % 2 == 0 => (xs[n / 2 - 1] + xs[n / 2]) / 2.0,
+        n => xs[n / 2],
+    }
+}
+
+/// Summarize each chapter's `Samples`, those taking the longest relative
+/// to their weights first. `title` looks up the title of a chapter.
+fn summarize<F>(samples: BTreeMap<(String, i16), Samples>, title: F) -> Vec<ChapterDuration>
+where
+    F: Fn(&str, i16) -> Option<String>,
+{
+    let mut chapters: Vec<ChapterDuration> = samples
+        .into_iter()
+        .map(|((sym, seq), mut s)| {
+            let n = s.actual.len();
+            let total: f32 = s.actual.iter().sum();
+            let ratio = if s.expected > 0.0 {
+                total / s.expected
+            } else {
+                0.0
+            };
+            ChapterDuration {
+                title: title(&sym, seq).unwrap_or_default(),
+                sym,
+                seq,
+                n,
+                mean: total / n as f32,
+                median: median(&mut s.actual),
+                expected: s.expected / n as f32,
+                ratio,
+            }
+        })
+        .collect();
+
+    chapters.sort_by(|a, b| b.ratio.total_cmp(&a.ratio));
+    chapters
+}
+
+/// Gather the durations of every student's completed goals, for the
+/// chapters of the course `sym` only, if given.
+pub(super) async fn gather(
+    glob: &Glob,
+    sym: Option<&str>,
+) -> Result<BTreeMap<(String, i16), Samples>, String> {
+    let mut samples: BTreeMap<(String, i16), Samples> = BTreeMap::new();
+    for tuname in glob.unames_with_role(Role::Teacher) {
+        let paces = glob.get_paces_by_teacher(tuname).await.map_err(|e| {
+            log::error!("Error retrieving paces for Teacher {:?}: {}", tuname, &e);
+            format!(
+                "Unable to retrieve pace information for {:?}: {}",
+                tuname, &e
+            )
+        })?;
+        for p in paces.iter() {
+            collect(&mut samples, &p.durations(&glob.calendar), sym);
+        }
+    }
+    Ok(samples)
+}
+
+/**
+Respond with how long each textbook chapter has taken students compared
+with how long its weight allots it.
+
+Header:
+```text
+x-camp-action: chapter-durations
+```
+
+The body is optional; it may be JSON like
+```json
+{ "sym": "pha" }
+```
+to report on only one course.
+
+Response body looks like
+```json
+[
+    {
+        "sym": "pha", "seq": 7, "title": "Momentum", "n": 14,
+        "mean": 11.5, "median": 10.0, "expected": 5.8, "ratio": 1.98
+    },
+    ...
+]
+```
+Chapters are sorted by `ratio`, highest (that is, most underweighted)
+first.
+*/
+pub async fn respond(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let req: DurationsRequest = match body.as_deref().map(str::trim) {
+        Some(body) if !body.is_empty() => match serde_json::from_str(body) {
+            Ok(req) => req,
+            Err(e) => {
+                return respond_bad_request(format!(
+                    "Unable to deserialize chapter durations request: {}",
+                    &e
+                ));
+            }
+        },
+        _ => DurationsRequest::default(),
+    };
+    if let Some(sym) = req.sym.as_deref() {
+        if glob.course_by_sym(sym).is_none() {
+            return respond_bad_request(format!("There is no course with symbol {:?}.", sym));
+        }
+    }
+
+    let samples = match gather(&glob, req.sym.as_deref()).await {
+        Ok(samples) => samples,
+        Err(e) => {
+            return text_500(Some(e));
+        }
+    };
+
+    let chapters = summarize(samples, |sym, seq| {
+        glob.course_by_sym(sym)
+            .and_then(|crs| crs.chapter(seq))
+            .map(|ch| ch.title.clone())
+    });
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("chapter-durations"),
+        )],
+        Json(chapters),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chapter_summaries() {
+        let mut samples: BTreeMap<(String, i16), Samples> = BTreeMap::new();
+        samples.insert(
+            ("pc".to_owned(), 7),
+            Samples {
+                actual: vec![12.0, 4.0, 8.0],
+                expected: 12.0,
+            },
+        );
+        samples.insert(
+            ("pc".to_owned(), 1),
+            Samples {
+                actual: vec![2.0, 3.0],
+                expected: 10.0,
+            },
+        );
+
+        let chapters = summarize(samples, |_, seq| match seq {
+            7 => Some("Momentum".to_owned()),
+            _ => None,
+        });
+        assert_eq!(
+            chapters,
+            vec![
+                ChapterDuration {
+                    sym: "pc".to_owned(),
+                    seq: 7,
+                    title: "Momentum".to_owned(),
+                    n: 3,
+                    mean: 8.0,
+                    median: 8.0,
+                    expected: 4.0,
+                    ratio: 2.0,
+                },
+                ChapterDuration {
+                    sym: "pc".to_owned(),
+                    seq: 1,
+                    title: String::new(),
+                    n: 2,
+                    mean: 2.5,
+                    median: 2.5,
+                    expected: 5.0,
+                    ratio: 0.5,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+/*!
+Error pages for routes that respond with HTML.
+
+Every request to an HTML route gets a short random id (see [`request_id`];
+API requests already have ids, which are used instead), which is logged along with the path if the response is an error page, and
+shown on the page, along with the time and how to get help (the
+`support_contact` configuration option). The page itself never says what
+went wrong; that only goes in the log, where the id can be used to find it.
+
+The pages are the `error_500`, `error_404`, and `error_403` templates; if
+the right one can't be rendered, a static page is sent instead.
+*/
+use axum::{
+    http::header::HeaderValue,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use once_cell::sync::OnceCell;
+use rand::{distributions, Rng};
+use serde::Serialize;
+use time::{format_description::FormatItem, macros::format_description, OffsetDateTime};
+
+use super::TEMPLATES;
+
+/// Characters request ids are made of. Users may have to read these to
+/// someone, so there are no look-alikes like `l` and `1`.
+const ID_CHARS: &[char] = &[
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'j', 'k', 'm', 'n', 'p', 'q', 'r', 's', 't', 'u', 'v',
+    'w', 'x', 'y', 'z', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+const ID_LENGTH: usize = 10;
+
+const TIMESTAMP_FMT: &[FormatItem] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second] UTC");
+
+/// Text to be sent on an INTERNAL SERVER ERROR when the error page template
+/// can't be rendered.
+static HTML_500: &str = r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>camp | Error</title>
+<link rel="stylesheet" href="/static/camp.css">
+</head>
+<body>
+<h1>Internal Server Error</h1>
+<p>(Error 500)</p>
+<p>Something went wrong on our end. No further or more
+helpful information is available about the problem.</p>
+</body>
+</html>"#;
+
+/// Who to contact about errors, from the configuration.
+static SUPPORT_CONTACT: OnceCell<String> = OnceCell::new();
+
+/// Identifies the request being responded to.
+#[derive(Clone, Debug)]
+struct RequestTag {
+    id: String,
+    /// Method and path, like `"GET /kiosk"`.
+    what: String,
+}
+
+tokio::task_local! {
+    static REQUEST: RequestTag;
+}
+
+/// Set who error pages tell users to contact. Should be called (if at all)
+/// once, at startup.
+pub fn init(support_contact: Option<String>) {
+    if let Some(contact) = support_contact {
+        if SUPPORT_CONTACT.set(contact).is_err() {
+            log::warn!("Support contact already initialized; ignoring.");
+        }
+    }
+}
+
+fn new_id() -> String {
+    let dist = distributions::Slice::new(ID_CHARS).unwrap();
+    rand::thread_rng()
+        .sample_iter(&dist)
+        .take(ID_LENGTH)
+        .collect()
+}
+
+/// Middleware function to give each request that doesn't already have an
+/// `x-camp-request-id` (that is, each request to an HTML route) an id, which
+/// is sent back in that header and shown on any error page.
+pub async fn request_id<B>(req: Request<B>, next: Next<B>) -> Response {
+    let id = match req
+        .headers()
+        .get("x-camp-request-id")
+        .and_then(|v| v.to_str().ok())
+    {
+        Some(id) => id.to_owned(),
+        None => new_id(),
+    };
+    // Either it came from a header or it's made of `ID_CHARS`.
+    let id_header = HeaderValue::from_str(&id).unwrap();
+    let tag = RequestTag {
+        id,
+        what: format!("{} {}", req.method(), req.uri().path()),
+    };
+
+    let mut response = REQUEST.scope(tag, next.run(req)).await;
+    response
+        .headers_mut()
+        .insert("x-camp-request-id", id_header);
+    response
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorData<'a> {
+    code: u16,
+    reason: &'a str,
+    request_id: &'a str,
+    timestamp: String,
+    support: Option<&'a str>,
+}
+
+fn template_for(code: StatusCode) -> &'static str {
+    match code {
+        StatusCode::NOT_FOUND => "error_404",
+        StatusCode::FORBIDDEN => "error_403",
+        _ => "error_500",
+    }
+}
+
+/// Respond with the error page for `code`.
+pub fn respond(code: StatusCode) -> Response {
+    let tag = REQUEST
+        .try_with(RequestTag::clone)
+        .unwrap_or_else(|_| RequestTag {
+            id: new_id(),
+            what: "[unknown request]".to_owned(),
+        });
+    let level = if code.is_server_error() {
+        log::Level::Error
+    } else {
+        log::Level::Info
+    };
+    log::log!(
+        level,
+        "{} (request {}): served {} page.",
+        &tag.what,
+        &tag.id,
+        &code
+    );
+
+    let data = ErrorData {
+        code: code.as_u16(),
+        reason: code.canonical_reason().unwrap_or("Error"),
+        request_id: &tag.id,
+        timestamp: OffsetDateTime::now_utc()
+            .format(TIMESTAMP_FMT)
+            .unwrap_or_default(),
+        support: SUPPORT_CONTACT.get().map(String::as_str),
+    };
+
+    let name = template_for(code);
+    let res = match TEMPLATES.get() {
+        Some(h) => h.render(name, &data).map_err(|e| e.to_string()),
+        None => Err("templates not initialized".to_owned()),
+    };
+    match res {
+        Ok(body) => (code, Html(body)).into_response(),
+        Err(e) => {
+            log::error!("Error rendering template {:?}: {}", name, &e);
+            if code.is_server_error() {
+                (code, Html(HTML_500)).into_response()
+            } else {
+                (code, data.reason.to_owned()).into_response()
+            }
+        }
+    }
+}
+
+/// Handler for requests to paths that don't exist.
+pub async fn not_found() -> Response {
+    respond(StatusCode::NOT_FOUND)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn request_ids() {
+        let id = new_id();
+        assert_eq!(id.chars().count(), ID_LENGTH);
+        assert!(id.chars().all(|c| ID_CHARS.contains(&c)));
+        assert_ne!(id, new_id());
+
+        let tag = RequestTag {
+            id: id.clone(),
+            what: "GET /kiosk".to_owned(),
+        };
+        let seen = REQUEST
+            .scope(tag, async { REQUEST.with(|t| t.id.clone()) })
+            .await;
+        assert_eq!(seen, id);
+        assert!(REQUEST.try_with(|t| t.id.clone()).is_err());
+    }
+
+    #[test]
+    fn error_templates() {
+        assert_eq!(template_for(StatusCode::NOT_FOUND), "error_404");
+        assert_eq!(template_for(StatusCode::FORBIDDEN), "error_403");
+        assert_eq!(template_for(StatusCode::INTERNAL_SERVER_ERROR), "error_500");
+        assert_eq!(template_for(StatusCode::BAD_GATEWAY), "error_500");
+    }
+}
@@ -0,0 +1,190 @@
+/*!
+Typed access to the `x-camp-*` request headers.
+
+Each type here wraps the (parsed) value of one header. They're all axum
+extractors, so an API entry point can take, say, a [`CampAction`] argument
+and never see a request without a valid `x-camp-action` header. Handlers
+further down that only get a `&HeaderMap` can call
+[`CampHeader::from_headers`] instead.
+
+Either way, a missing, unreadable, or invalid header gets the same 400
+response, naming the header and what's wrong with it.
+*/
+use axum::{
+    async_trait,
+    extract::{FromRequest, RequestParts},
+    http::header::HeaderMap,
+    response::Response,
+};
+
+use super::{get_head, respond_bad_request};
+use crate::pace::Term;
+
+/// A value that can be parsed from a single `x-camp-*` header.
+pub trait CampHeader: Sized {
+    /// The header's name.
+    const NAME: &'static str;
+
+    /// Interpret the header's value.
+    fn parse(val: &str) -> Result<Self, String>;
+
+    /// Read and parse this header from `headers`, responding with a 400 if
+    /// it's missing or invalid.
+    // The error is the response handlers return as-is, so there's no point
+    // boxing it.
+    #[allow(clippy::result_large_err)]
+    fn from_headers(headers: &HeaderMap) -> Result<Self, Response> {
+        let val = get_head(Self::NAME, headers).map_err(respond_bad_request)?;
+        Self::parse(val).map_err(|e| {
+            respond_bad_request(format!(
+                "Invalid {:?} header value {:?}: {}",
+                Self::NAME,
+                val,
+                &e
+            ))
+        })
+    }
+
+    /// Like [`CampHeader::from_headers`], but returns `None` if the header
+    /// isn't present at all (an invalid value is still an error).
+    #[allow(clippy::result_large_err)]
+    fn from_headers_opt(headers: &HeaderMap) -> Result<Option<Self>, Response> {
+        if headers.contains_key(Self::NAME) {
+            Self::from_headers(headers).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Header values that are names (unames, actions, course symbols) must be
+/// nonempty and can't have leading or trailing whitespace.
+fn parse_name(val: &str) -> Result<String, String> {
+    if val.is_empty() {
+        Err("value is empty".to_owned())
+    } else if val.trim() != val {
+        Err("value has leading or trailing whitespace".to_owned())
+    } else {
+        Ok(val.to_owned())
+    }
+}
+
+macro_rules! name_header {
+    ($(#[$attr:meta])* $name:ident, $header:literal) => {
+        $(#[$attr])*
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct $name(pub String);
+
+        impl CampHeader for $name {
+            const NAME: &'static str = $header;
+
+            fn parse(val: &str) -> Result<Self, String> {
+                parse_name(val).map($name)
+            }
+        }
+    };
+}
+
+macro_rules! impl_from_request {
+    ($($name:ident),*) => {
+        $(
+            #[async_trait]
+            impl<B: Send> FromRequest<B> for $name {
+                type Rejection = Response;
+
+                async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Response> {
+                    $name::from_headers(req.headers())
+                }
+            }
+        )*
+    };
+}
+
+name_header!(
+    /// `x-camp-uname`: the user making the request.
+    CampUname,
+    "x-camp-uname"
+);
+name_header!(
+    /// `x-camp-action`: which API action is being requested.
+    CampAction,
+    "x-camp-action"
+);
+name_header!(
+    /// `x-camp-student`: the student the request is about.
+    CampStudent,
+    "x-camp-student"
+);
+name_header!(
+    /// `x-camp-teacher`: the Teacher the request is about.
+    CampTeacher,
+    "x-camp-teacher"
+);
+name_header!(
+    /// `x-camp-course`: the symbol of the course the request is about.
+    CampCourse,
+    "x-camp-course"
+);
+
+/// `x-camp-term`: the term (for reports and the like) the request is about.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CampTerm(pub Term);
+
+impl CampHeader for CampTerm {
+    const NAME: &'static str = "x-camp-term";
+
+    fn parse(val: &str) -> Result<Self, String> {
+        val.parse::<Term>().map(CampTerm)
+    }
+}
+
+impl_from_request!(
+    CampUname,
+    CampAction,
+    CampStudent,
+    CampTeacher,
+    CampCourse,
+    CampTerm
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::{header::HeaderValue, StatusCode};
+
+    #[test]
+    fn camp_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-camp-uname", HeaderValue::from_static("berro"));
+        headers.insert("x-camp-term", HeaderValue::from_static("spring"));
+        headers.insert("x-camp-student", HeaderValue::from_static(" frog"));
+        headers.insert("x-camp-action", HeaderValue::from_static(""));
+        headers.insert(
+            "x-camp-course",
+            HeaderValue::from_bytes(b"pha\xff").unwrap(),
+        );
+
+        assert_eq!(
+            CampUname::from_headers(&headers).unwrap(),
+            CampUname("berro".to_owned())
+        );
+        assert_eq!(
+            CampTerm::from_headers(&headers).unwrap(),
+            CampTerm(Term::Spring)
+        );
+        assert_eq!(CampTeacher::from_headers_opt(&headers).unwrap(), None);
+
+        for resp in [
+            CampStudent::from_headers(&headers).unwrap_err(),
+            CampAction::from_headers(&headers).unwrap_err(),
+            CampCourse::from_headers(&headers).unwrap_err(),
+            CampTeacher::from_headers(&headers).unwrap_err(),
+        ] {
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+
+        headers.insert("x-camp-term", HeaderValue::from_static("winter"));
+        assert!(CampTerm::from_headers_opt(&headers).is_err());
+    }
+}
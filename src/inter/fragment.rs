@@ -0,0 +1,143 @@
+/*!
+Rendering pieces of pace calendar HTML for frontends that would rather swap
+in server-rendered markup than rebuild tables client-side.
+
+A request opts into this by including the header
+```text
+x-camp-render: html-fragment
+```
+Handlers that support it will then respond with a rendered partial (using
+the same templates that draw the Boss view) instead of JSON.
+*/
+use axum::{
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+
+use super::boss::{write_cal_goal, write_cal_table};
+use super::write_template;
+use crate::{
+    config::Glob,
+    pace::{Pace, PaceDisplay, RowDisplay},
+};
+
+/// Name of the header used to request a rendered fragment.
+pub const RENDER_HEADER: &str = "x-camp-render";
+/// Value of the [`RENDER_HEADER`] that selects an HTML fragment.
+pub const HTML_FRAGMENT: &str = "html-fragment";
+
+/// Returns `true` if the request asked for an HTML fragment response.
+pub fn requested(headers: &HeaderMap) -> bool {
+    match headers.get(RENDER_HEADER) {
+        Some(val) => val.as_bytes() == HTML_FRAGMENT.as_bytes(),
+        None => false,
+    }
+}
+
+fn display<'a>(p: &'a Pace, glob: &'a Glob) -> Result<PaceDisplay<'a>, String> {
+    PaceDisplay::from(p, glob).map_err(|e| {
+        format!(
+            "Error generating PaceDisplay for {:?}: {}",
+            &p.student.base.uname, &e
+        )
+    })
+}
+
+fn into_string(buff: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(buff).map_err(|e| format!("Rendered fragment not valid UTF-8: {}", &e))
+}
+
+/// Render the complete pace table for a single student.
+pub fn pace_table(p: &Pace, glob: &Glob) -> Result<String, String> {
+    log::trace!(
+        "fragment::pace_table( [ {:?} Pace ], [ Glob ] ) called.",
+        &p.student.base.uname
+    );
+
+    let mut buff: Vec<u8> = Vec::new();
+    write_cal_table(p, glob, &mut buff)?;
+    into_string(buff)
+}
+
+/// Render the pace tables for several students, one after another.
+pub fn pace_tables(paces: &[Pace], glob: &Glob) -> Result<String, String> {
+    log::trace!(
+        "fragment::pace_tables( [ {} Paces ], [ Glob ] ) called.",
+        paces.len()
+    );
+
+    let mut buff: Vec<u8> = Vec::new();
+    for p in paces.iter() {
+        write_cal_table(p, glob, &mut buff)?;
+    }
+    into_string(buff)
+}
+
+/// Render the table row for the `Goal` with the given `id` in the given
+/// student's pace.
+pub fn goal_row(p: &Pace, id: i64, glob: &Glob) -> Result<String, String> {
+    log::trace!(
+        "fragment::goal_row( [ {:?} Pace ], {}, [ Glob ] ) called.",
+        &p.student.base.uname,
+        &id
+    );
+
+    let pd = display(p, glob)?;
+    let mut buff: Vec<u8> = Vec::new();
+    for row in pd.rows.iter() {
+        if let RowDisplay::Goal(g) = row {
+            if g.id == id {
+                write_cal_goal(g, glob.date_style, &mut buff)?;
+                return into_string(buff);
+            }
+        }
+    }
+
+    // Goals for waived chapters aren't displayed.
+    if p.goals.iter().any(|g| g.id == id && p.is_waived(g)) {
+        return Err(format!("Goal {} is for a waived chapter.", &id));
+    }
+
+    Err(format!(
+        "Student {:?} has no Goal with id {}.",
+        &p.student.base.uname, &id
+    ))
+}
+
+/// Render all of the semester summary rows in the given student's pace.
+pub fn summary_rows(p: &Pace, glob: &Glob) -> Result<String, String> {
+    log::trace!(
+        "fragment::summary_rows( [ {:?} Pace ], [ Glob ] ) called.",
+        &p.student.base.uname
+    );
+
+    let pd = display(p, glob)?;
+    let mut buff: Vec<u8> = Vec::new();
+    for row in pd.rows.iter() {
+        if let RowDisplay::Summary(s) = row {
+            write_template("boss_summary_row", s, &mut buff)?;
+        }
+    }
+    into_string(buff)
+}
+
+/// Wrap a rendered fragment in a response, tagged with the given
+/// `x-camp-action` value so the frontend knows where to put it.
+pub fn respond(action: &'static str, html: String) -> Response {
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static(action),
+            ),
+            (
+                HeaderName::from_static(RENDER_HEADER),
+                HeaderValue::from_static(HTML_FRAGMENT),
+            ),
+        ],
+        Html(html),
+    )
+        .into_response()
+}
@@ -0,0 +1,298 @@
+/*!
+Custom [`Handlebars`] helpers, so handlers can pass templates raw values
+and leave the formatting to the templates.
+
+These get registered on every `Handlebars` struct by
+[`init`](super::init).
+
+  * `{{date d}}` formats the date `d` (serialized as "YYYY-MM-DD") in a
+    [`DateStyle`]: the one given by a `style="..."` hash parameter, or
+    else the one in the template data's top-level `date_style` field, or
+    else the default. A missing or null date renders as nothing.
+
+  * `{{percent x}}` renders the fraction `x` as a whole-number percentage
+    ("0.857" becomes "86%"). `places=N` shows `N` decimal places, and
+    `sign=true` always shows the sign. A null renders as nothing.
+
+  * `{{plural n "goal"}}` renders "goal" if `n` is 1 and "goals"
+    otherwise; irregular plurals can be given as a third parameter, as in
+    `{{plural n "child" "children"}}`.
+
+  * `{{classes "cal" lag=is_lagging inc=was_inc}}` renders a
+    space-separated list of CSS classes: every (nonempty) positional
+    parameter, then (in alphabetical order) the name of every hash
+    parameter whose value is truthy.
+
+  * `{{asset "teacher.css"}}` renders the URL of a file in the static
+    directory, fingerprinted so it can be cached forever (see
+    [`assets`](super::assets)).
+*/
+use std::str::FromStr;
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperResult, JsonValue, Output, RenderContext, RenderError,
+};
+use time::Date;
+
+use crate::{format_date, DateStyle, DATE_FMT};
+
+/// Register all the helpers in this module on `h`.
+pub fn register(h: &mut Handlebars) {
+    h.register_helper("date", Box::new(date_helper));
+    h.register_helper("percent", Box::new(percent_helper));
+    h.register_helper("plural", Box::new(plural_helper));
+    h.register_helper("classes", Box::new(classes_helper));
+    h.register_helper("asset", Box::new(asset_helper));
+}
+
+/// Whether a value should count as "true" when deciding whether to
+/// include a class. (Unlike `{{#if}}`, zero counts as false.)
+fn truthy(v: &JsonValue) -> bool {
+    match v {
+        JsonValue::Null => false,
+        JsonValue::Bool(b) => *b,
+        JsonValue::Number(n) => n.as_f64().map(|f| f != 0.0).unwrap_or(false),
+        JsonValue::String(s) => !s.is_empty(),
+        JsonValue::Array(a) => !a.is_empty(),
+        JsonValue::Object(_) => true,
+    }
+}
+
+/// Which [`DateStyle`] the `date` helper should use.
+fn date_style(h: &Helper, ctx: &Context) -> Result<DateStyle, RenderError> {
+    let val = match h.hash_get("style") {
+        Some(v) => v.value(),
+        None => match ctx.data().get("date_style") {
+            Some(v) => v,
+            None => return Ok(DateStyle::default()),
+        },
+    };
+    match val.as_str() {
+        Some(s) => DateStyle::from_str(s).map_err(RenderError::new),
+        None => Err(RenderError::new(format!(
+            "date: style must be a string, not {}",
+            val
+        ))),
+    }
+}
+
+fn date_helper(
+    h: &Helper,
+    _: &Handlebars,
+    ctx: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let val = match h.param(0).map(|p| p.value()) {
+        None | Some(JsonValue::Null) => return Ok(()),
+        Some(v) => v,
+    };
+    let d = match val.as_str() {
+        Some(s) => Date::parse(s, DATE_FMT)
+            .map_err(|e| RenderError::new(format!("date: can't parse {:?}: {}", s, &e)))?,
+        None => {
+            return Err(RenderError::new(format!(
+                "date: expected a date, not {}",
+                val
+            )));
+        }
+    };
+    let style = date_style(h, ctx)?;
+    let s = format_date(style, &d).map_err(RenderError::new)?;
+    out.write(s.as_str())?;
+    Ok(())
+}
+
+fn percent_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let val = match h.param(0).map(|p| p.value()) {
+        None | Some(JsonValue::Null) => return Ok(()),
+        Some(v) => v,
+    };
+    let frac = val
+        .as_f64()
+        .ok_or_else(|| RenderError::new(format!("percent: expected a number, not {}", val)))?;
+    let places = match h.hash_get("places") {
+        Some(p) => p
+            .value()
+            .as_u64()
+            .ok_or_else(|| RenderError::new("percent: places must be a nonnegative integer"))?
+            as usize,
+        None => 0,
+    };
+    let sign = h
+        .hash_get("sign")
+        .map(|p| truthy(p.value()))
+        .unwrap_or(false);
+
+    let scale = 10f64.powi(places as i32);
+    let mut pct = (100.0 * frac * scale).round() / scale;
+    // Avoid rendering "-0%".
+    if pct == 0.0 {
+        pct = 0.0;
+    }
+    let s = if sign {
+        format!("{:+.*}%", places, pct)
+    } else {
+        format!("{:.*}%", places, pct)
+    };
+    out.write(&s)?;
+    Ok(())
+}
+
+fn plural_helper(
+    h: &Helper,
+    r: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let n = h
+        .param(0)
+        .and_then(|p| p.value().as_f64())
+        .ok_or_else(|| RenderError::new("plural: first parameter must be a number"))?;
+    let singular = h
+        .param(1)
+        .and_then(|p| p.value().as_str())
+        .ok_or_else(|| RenderError::new("plural: second parameter must be a string"))?;
+
+    let word = if n == 1.0 {
+        singular.to_owned()
+    } else {
+        match h.param(2).and_then(|p| p.value().as_str()) {
+            Some(plural) => plural.to_owned(),
+            None => format!("{}s", singular),
+        }
+    };
+    out.write(&r.get_escape_fn()(&word))?;
+    Ok(())
+}
+
+fn classes_helper(
+    h: &Helper,
+    r: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let mut classes: Vec<&str> = Vec::new();
+    for p in h.params().iter() {
+        match p.value() {
+            JsonValue::String(s) if !s.is_empty() => classes.push(s.as_str()),
+            JsonValue::String(_) | JsonValue::Null => {}
+            v => {
+                return Err(RenderError::new(format!(
+                    "classes: expected a class name, not {}",
+                    v
+                )));
+            }
+        }
+    }
+    for (name, val) in h.hash().iter() {
+        if truthy(val.value()) {
+            classes.push(name);
+        }
+    }
+
+    out.write(&r.get_escape_fn()(&classes.join(" ")))?;
+    Ok(())
+}
+
+fn asset_helper(
+    h: &Helper,
+    r: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let path = h
+        .param(0)
+        .and_then(|p| p.value().as_str())
+        .ok_or_else(|| RenderError::new("asset: parameter must be a path"))?;
+
+    out.write(&r.get_escape_fn()(&super::assets::url(path)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn render(template: &str, data: &JsonValue) -> String {
+        let mut h = Handlebars::new();
+        register(&mut h);
+        h.render_template(template, data).unwrap()
+    }
+
+    #[test]
+    fn helpers() {
+        let data = json!({
+            "date_style": "long",
+            "due": "2023-09-05",
+            "none": null,
+            "score": 0.857,
+            "lag": -0.0402,
+            "n": 1,
+            "m": 3,
+            "status": "late",
+            "bad": true,
+            "inc": false,
+            "count": 0,
+        });
+
+        assert_eq!(render("{{date due}}", &data), "September 5, 2023");
+        assert_eq!(render("{{date due style=\"iso\"}}", &data), "2023-09-05");
+        assert_eq!(render("[{{date none}}]", &data), "[]");
+        assert_eq!(
+            render(
+                "{{date due}}",
+                &json!({ "due": time::macros::date!(2023 - 09 - 05) })
+            ),
+            "Sep 05"
+        );
+        assert_eq!(
+            render(
+                "{{#each days}}{{date this}};{{/each}}",
+                &json!({ "date_style": "iso", "days": ["2023-09-05", "2023-09-06"] })
+            ),
+            "2023-09-05;2023-09-06;"
+        );
+
+        assert_eq!(render("{{percent score}}", &data), "86%");
+        assert_eq!(render("{{percent score places=1}}", &data), "85.7%");
+        assert_eq!(render("{{percent lag sign=true}}", &data), "-4%");
+        assert_eq!(render("{{percent 0.001 sign=true}}", &data), "+0%");
+        assert_eq!(render("[{{percent none}}]", &data), "[]");
+
+        assert_eq!(render("{{n}} {{plural n \"goal\"}}", &data), "1 goal");
+        assert_eq!(render("{{m}} {{plural m \"goal\"}}", &data), "3 goals");
+        assert_eq!(
+            render("{{plural m \"child\" \"children\"}}", &data),
+            "children"
+        );
+
+        assert_eq!(
+            render(
+                "{{classes status \"row\" inc=inc count=count bad=bad}}",
+                &data
+            ),
+            "late row bad"
+        );
+
+        assert_eq!(render("{{asset \"no/such/file.js\"}}", &data), "/static/no/such/file.js");
+
+        let mut h = Handlebars::new();
+        register(&mut h);
+        assert!(h.render_template("{{date score}}", &data).is_err());
+        assert!(h
+            .render_template("{{date due style=\"nope\"}}", &data)
+            .is_err());
+    }
+}
@@ -0,0 +1,198 @@
+/*!
+A student's pace calendar as an iCalendar (.ics) file, so families can
+add the due dates to Google Calendar (or anything else that reads
+iCalendar).
+
+Each scheduled goal becomes an all-day `VEVENT` on its due date, with the
+course and chapter as its summary. Every event's `UID` is derived from its
+goal's id, so calendar apps can match the events in a fresh copy of the
+file up with the ones from the last.
+
+Both the Teacher and Student APIs respond to the `download-ical` action
+with this file (see [`respond`]).
+*/
+use axum::{
+    http::header::{self, HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use time::{format_description::FormatItem, macros::format_description, OffsetDateTime};
+
+use crate::{
+    anon,
+    pace::{PaceDisplay, RowDisplay},
+};
+
+/// Format of the value of an all-day event's `DTSTART`.
+const ICAL_DATE_FMT: &[FormatItem] = format_description!("[year][month][day]");
+/// Format of a UTC timestamp (like `DTSTAMP`).
+const ICAL_STAMP_FMT: &[FormatItem] =
+    format_description!("[year][month][day]T[hour][minute][second]Z");
+
+/// Longest a content line may be, in octets, before it must be folded.
+const LINE_LEN: usize = 75;
+
+/// Escape `text` for use as an iCalendar TEXT value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Append the content line `line` to `ical`, folding it (without splitting
+/// any characters) so no piece is longer than [`LINE_LEN`] octets.
+fn push_line(ical: &mut String, line: &str) {
+    let mut len: usize = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > LINE_LEN {
+            ical.push_str("\r\n ");
+            // The leading space counts toward the continuation's length.
+            len = 1;
+        }
+        ical.push(c);
+        len += c.len_utf8();
+    }
+    ical.push_str("\r\n");
+}
+
+/**
+Render the scheduled goals of `pd` as an iCalendar file, with `stamp` (in
+[`ICAL_STAMP_FMT`]) as the time it was generated.
+
+Goals without due dates (and waived chapters) are left out.
+*/
+fn render(pd: &PaceDisplay, stamp: &str) -> Result<String, String> {
+    let name = format!("{} {}", anon::rest(pd.rest), anon::last(pd.last));
+
+    let mut ical = String::new();
+    push_line(&mut ical, "BEGIN:VCALENDAR");
+    push_line(&mut ical, "VERSION:2.0");
+    push_line(&mut ical, "PRODID:-//camp//pace calendar//EN");
+    push_line(&mut ical, "CALSCALE:GREGORIAN");
+    push_line(&mut ical, "METHOD:PUBLISH");
+    push_line(&mut ical, &format!("X-WR-CALNAME:{}", escape(&format!("{}: due dates", name))));
+
+    for g in pd.rows.iter().filter_map(|row| match row {
+        RowDisplay::Goal(g) => Some(g),
+        _ => None,
+    }) {
+        let due = match &g.due {
+            Some(d) => d,
+            None => continue,
+        };
+        let due = due
+            .format(&ICAL_DATE_FMT)
+            .map_err(|e| format!("Error formatting due date of goal {}: {}", &g.id, &e))?;
+        let summary = format!("{}: {}", g.course, &g.title);
+
+        push_line(&mut ical, "BEGIN:VEVENT");
+        push_line(&mut ical, &format!("UID:camp-goal-{}-{}", pd.uname, &g.id));
+        push_line(&mut ical, &format!("DTSTAMP:{}", stamp));
+        push_line(&mut ical, &format!("DTSTART;VALUE=DATE:{}", &due));
+        push_line(&mut ical, &format!("SUMMARY:{}", escape(&summary)));
+        if let Some(done) = &g.done {
+            push_line(&mut ical, &format!("DESCRIPTION:Done {}", done));
+        }
+        push_line(&mut ical, "TRANSP:TRANSPARENT");
+        push_line(&mut ical, "END:VEVENT");
+    }
+
+    push_line(&mut ical, "END:VCALENDAR");
+    Ok(ical)
+}
+
+/**
+Respond with the scheduled goals of `pd` as an iCalendar file.
+
+Header:
+```text
+x-camp-action: download-ical
+```
+*/
+pub fn respond(pd: &PaceDisplay) -> Response {
+    let stamp = match OffsetDateTime::now_utc().format(&ICAL_STAMP_FMT) {
+        Ok(stamp) => stamp,
+        Err(e) => {
+            log::error!("Error formatting iCalendar timestamp: {}", &e);
+            return super::text_500(None);
+        }
+    };
+    let ical = match render(pd, &stamp) {
+        Ok(ical) => ical,
+        Err(e) => {
+            log::error!("Error rendering iCalendar for {:?}: {}", pd.uname, &e);
+            return super::text_500(Some(format!("Unable to render calendar: {}", &e)));
+        }
+    };
+
+    let disposition = format!("attachment; filename=\"pace-{}.ics\"", pd.uname);
+    let disposition = match HeaderValue::from_str(&disposition) {
+        Ok(v) => v,
+        Err(_) => HeaderValue::from_static("attachment; filename=\"pace.ics\""),
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("download-ical"),
+            ),
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/calendar; charset=utf-8"),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        ical,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            escape("Ch. 3; ratios, rates\\proportions\r\nreview"),
+            "Ch. 3\\; ratios\\, rates\\\\proportions\\nreview"
+        );
+    }
+
+    #[test]
+    fn folding() {
+        let mut ical = String::new();
+        push_line(&mut ical, "SUMMARY:short");
+        assert_eq!(ical, "SUMMARY:short\r\n");
+
+        let long = format!("SUMMARY:{}", "\u{e9}".repeat(60));
+        let mut ical = String::new();
+        push_line(&mut ical, &long);
+        let lines: Vec<&str> = ical.trim_end().split("\r\n").collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| l.len() <= LINE_LEN));
+        assert!(lines[1..].iter().all(|l| l.starts_with(' ')));
+        let unfolded: String = lines.iter().map(|l| l.strip_prefix(' ').unwrap_or(l)).collect();
+        assert_eq!(unfolded, long);
+    }
+
+    #[test]
+    fn stamps() {
+        let d = time::macros::date!(2022 - 09 - 06);
+        assert_eq!(d.format(&ICAL_DATE_FMT).unwrap(), "20220906");
+        let t = time::macros::datetime!(2022 - 09 - 06 13:05:09 UTC);
+        assert_eq!(t.format(&ICAL_STAMP_FMT).unwrap(), "20220906T130509Z");
+    }
+}
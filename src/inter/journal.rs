@@ -0,0 +1,185 @@
+/*!
+Journaling of failed API requests.
+
+When users report that something "didn't save", there's usually nothing
+left to go on. If the `failure_journal` configuration option is nonzero,
+[`capture_failures`] keeps a copy of every API request that gets a 4xx or
+5xx response, along with the response, in the data DB (see
+[`Store::journal_failed_request`](crate::store::Store::journal_failed_request)).
+Admins can then list and inspect them with the `populate-failures` and
+`inspect-failure` actions.
+
+Only the method, path, `x-camp-uname` and `x-camp-action` headers, and
+bodies are kept. Bodies have the values of anything that looks like a
+password or key replaced with `[redacted]`, and are truncated to a
+reasonable length.
+*/
+use std::sync::Arc;
+
+use axum::{
+    body::{boxed, Body, Bytes, Full},
+    http::{header::HeaderMap, Request},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use super::respond_bad_request;
+use crate::{config::Glob, store::FailedRequest};
+
+/// Longest body (in bytes) that will be stored in its entirety.
+const MAX_BODY: usize = 16 * 1024;
+const REDACTED: &str = "[redacted]";
+/// Any JSON object key or form field name containing one of these (case
+/// insensitively) has its value redacted.
+const SENSITIVE: &[&str] = &["password", "pwd", "key", "salt", "token", "secret"];
+
+fn is_sensitive(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SENSITIVE.iter().any(|s| name.contains(s))
+}
+
+fn redact_json(v: &mut Value) {
+    match v {
+        Value::Object(map) => {
+            for (k, v) in map.iter_mut() {
+                if is_sensitive(k) {
+                    *v = Value::String(REDACTED.to_owned());
+                } else {
+                    redact_json(v);
+                }
+            }
+        }
+        Value::Array(vals) => {
+            for v in vals.iter_mut() {
+                redact_json(v);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn redact_form(text: &str) -> String {
+    text.split('&')
+        .map(|field| match field.split_once('=') {
+            Some((k, _)) if is_sensitive(k) => format!("{}={}", k, REDACTED),
+            _ => field.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn truncate(mut text: String) -> String {
+    if text.len() <= MAX_BODY {
+        return text;
+    }
+
+    let mut n = MAX_BODY;
+    while !text.is_char_boundary(n) {
+        n -= 1;
+    }
+    let n_cut = text.len() - n;
+    text.truncate(n);
+    text.push_str(&format!("\n[{} more bytes truncated]", &n_cut));
+    text
+}
+
+/// Turn a request or response body into something safe to store.
+fn sanitize(content_type: Option<&str>, bytes: &[u8]) -> Option<String> {
+    if bytes.is_empty() {
+        return None;
+    }
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(_) => {
+            return Some(format!("[{} bytes of non-UTF-8 data]", bytes.len()));
+        }
+    };
+
+    let text = if let Ok(mut v) = serde_json::from_str::<Value>(text) {
+        redact_json(&mut v);
+        v.to_string()
+    } else if matches!(content_type, Some(ct) if ct.starts_with("application/x-www-form-urlencoded"))
+    {
+        redact_form(text)
+    } else {
+        text.to_owned()
+    };
+
+    Some(truncate(text))
+}
+
+fn header_string(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_owned())
+}
+
+/**
+Middleware function to record failed requests in the journal (if the
+journal is enabled).
+
+This should wrap the key-authenticated API routes, so that authentication
+failures get recorded, too.
+*/
+pub async fn capture_failures(req: Request<Body>, next: Next<Body>) -> Response {
+    let glob: Arc<RwLock<Glob>> = req.extensions().get::<Arc<RwLock<Glob>>>().unwrap().clone();
+    let keep = glob.read().await.failure_journal;
+    if keep == 0 {
+        return next.run(req).await;
+    }
+
+    let (parts, body) = req.into_parts();
+    let req_bytes: Bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log::error!("Error reading request body: {}", &e);
+            return respond_bad_request("Unable to read request body.".to_owned());
+        }
+    };
+
+    let mut record = FailedRequest {
+        method: parts.method.to_string(),
+        path: parts.uri.path().to_owned(),
+        uname: header_string(&parts.headers, "x-camp-uname"),
+        action: header_string(&parts.headers, "x-camp-action"),
+        ..Default::default()
+    };
+    let content_type = header_string(&parts.headers, "content-type");
+
+    let req = Request::from_parts(parts, Body::from(req_bytes.clone()));
+    let response = next.run(req).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let resp_bytes: Bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log::error!("Error reading response body: {}", &e);
+            Bytes::new()
+        }
+    };
+
+    record.status = status.as_u16() as i16;
+    record.body = sanitize(content_type.as_deref(), &req_bytes);
+    record.response = sanitize(None, &resp_bytes);
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .journal_failed_request(&record, keep)
+        .await;
+    if let Err(e) = res {
+        log::error!("Unable to journal failed request: {}", &e);
+    }
+
+    Response::from_parts(parts, boxed(Full::from(resp_bytes)))
+}
@@ -0,0 +1,160 @@
+/*!
+A read-only classroom progress board, for projecting in class.
+
+A Teacher can generate a secret link (with the `kiosk` action of the
+Teacher API) to a page at "/kiosk/{token}" that shows, for each of their
+students, the goal they should be working on now, its due date, and
+whether they're on schedule. The page reloads itself every
+[`REFRESH_SECS`] seconds, and never shows scores or grades.
+
+Anyone with the link can see the board, so it only shows students' first
+names and last initials. Generating a new link invalidates the old one.
+*/
+use std::sync::Arc;
+
+use axum::{extract::Path, http::StatusCode, response::Response, Extension};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use super::{html_500, serve_template};
+use crate::{
+    config::Glob,
+    pace::{CustomCh, Pace, Source},
+    user::User,
+    DateStyle,
+};
+
+/// How often the dashboard page reloads itself.
+pub const REFRESH_SECS: u32 = 60;
+
+/// One student's line on the dashboard.
+#[derive(Debug, Serialize)]
+struct KioskRow {
+    name: String,
+    goal: String,
+    due: Option<time::Date>,
+    /// One of "ahead", "on-time", "behind", "done", or "none" (for use as
+    /// a CSS class).
+    status: &'static str,
+    n_done: usize,
+    n_total: usize,
+}
+
+/// Data for rendering the `kiosk` template.
+#[derive(Debug, Serialize)]
+struct KioskData<'a> {
+    teacher: &'a str,
+    updated: time::Date,
+    date_style: DateStyle,
+    refresh: u32,
+    rows: Vec<KioskRow>,
+}
+
+/// Summarize a student's `Pace` for the dashboard.
+fn kiosk_row(p: &Pace, glob: &Glob, today: &time::Date) -> KioskRow {
+    let name = match p.student.last.chars().next() {
+        Some(c) => format!("{} {}.", &p.student.rest, c),
+        None => p.student.rest.clone(),
+    };
+    let n_total = p.goals.len();
+    let n_done = p.goals.iter().filter(|g| g.done.is_some()).count();
+
+    let current = match p.goals.iter().find(|g| g.done.is_none()) {
+        Some(g) => g,
+        None => {
+            let status = if n_total == 0 { "none" } else { "done" };
+            return KioskRow {
+                name,
+                goal: String::new(),
+                due: None,
+                status,
+                n_done,
+                n_total,
+            };
+        }
+    };
+
+    let goal = match &current.source {
+        Source::Book(bch) => match glob.course_by_sym(&bch.sym) {
+            Some(crs) => match crs.chapter(bch.seq) {
+                Some(ch) => format!("{}: {}", &crs.title, &ch.title),
+                None => format!("{} ch. {}", &crs.title, &bch.seq),
+            },
+            None => format!("{} ch. {}", &bch.sym, &bch.seq),
+        },
+        Source::Custom(CustomCh(id)) => match glob.customs.get(id) {
+            Some(cus) => cus.title.clone(),
+            None => "custom goal".to_owned(),
+        },
+    };
+
+    let status = match &current.due {
+        Some(d) if d < today => "behind",
+        Some(_) if p.done_weight > p.due_weight => "ahead",
+        _ => "on-time",
+    };
+
+    KioskRow {
+        name,
+        goal,
+        due: current.due,
+        status,
+        n_done,
+        n_total,
+    }
+}
+
+/// Handler for GET requests to "/kiosk/{token}".
+pub async fn dashboard(
+    Path(token): Path<String>,
+    Extension(glob): Extension<Arc<RwLock<Glob>>>,
+) -> Response {
+    log::trace!("kiosk::dashboard( [ token ] ) called.");
+
+    let glob = glob.read().await;
+    let res = glob.data().read().await.get_kiosk_teacher(&token).await;
+    let tuname = match res {
+        Ok(Some(tuname)) => tuname,
+        Ok(None) => {
+            let data = json!({ "error": "This classroom display link is no longer valid." });
+            return serve_template(StatusCode::NOT_FOUND, "kiosk", &data, vec![]);
+        }
+        Err(e) => {
+            log::error!("Error looking up kiosk token: {}", &e);
+            return html_500();
+        }
+    };
+
+    let paces = match glob.get_paces_by_teacher(&tuname).await {
+        Ok(paces) => paces,
+        Err(e) => {
+            log::error!("Error retrieving paces for Teacher {:?}: {}", &tuname, &e);
+            return html_500();
+        }
+    };
+    let teacher = match glob.users.get(&tuname) {
+        Some(User::Teacher(t)) => t.name.as_str(),
+        _ => tuname.as_str(),
+    };
+
+    let today = crate::now();
+    let mut sorted: Vec<&Pace> = paces.iter().collect();
+    sorted.sort_by(|a, b| {
+        (&a.student.rest, &a.student.last).cmp(&(&b.student.rest, &b.student.last))
+    });
+    let rows: Vec<KioskRow> = sorted
+        .into_iter()
+        .map(|p| kiosk_row(p, &glob, &today))
+        .collect();
+
+    let data = KioskData {
+        teacher,
+        updated: today,
+        date_style: glob.date_style,
+        refresh: REFRESH_SECS,
+        rows,
+    };
+
+    serve_template(StatusCode::OK, "kiosk", &data, vec![])
+}
@@ -0,0 +1,129 @@
+/*!
+Weekly snapshots of students' lag, so the Boss and Teachers can see
+whether a student has been catching up.
+
+A background task (started with [`run_weekly`]) records every student's
+[`LagSnapshot`] at the start of each week (midnight UTC on Monday), and
+once when the server starts, in case it was down when the last one was
+due. Only the first snapshot each week is kept.
+
+Both the Boss and Teacher APIs respond to the `lag-history` action with
+the recorded series (see [`respond`]).
+*/
+use std::sync::Arc;
+
+use axum::{
+    http::header::{HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use super::{digest::secs_until_monday, text_500};
+use crate::{config::Glob, store::LagSnapshot, user::Student};
+
+/// One student's series in the response to a `lag-history` request.
+#[derive(Debug, Serialize)]
+struct StudentLag<'a> {
+    uname: &'a str,
+    last: &'a str,
+    rest: &'a str,
+    history: Vec<LagSnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+struct LagHistory<'a> {
+    students: Vec<StudentLag<'a>>,
+}
+
+/// Take this week's snapshot (if it hasn't been taken already).
+async fn snapshot(glob: &Glob) {
+    match glob.snapshot_lags(&crate::now()).await {
+        Ok(n) => log::info!("Recorded {} lag snapshots.", &n),
+        Err(e) => log::error!("Error recording lag snapshots: {}", &e),
+    }
+}
+
+/**
+Record lag snapshots now and then at the start of every week, forever.
+
+This is meant to be `tokio::spawn()`ed when the server starts up.
+*/
+pub async fn run_weekly(glob: Arc<RwLock<Glob>>) {
+    snapshot(&*glob.read().await).await;
+    loop {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let wait = secs_until_monday(now, 0);
+        log::info!("Next lag snapshot in {} seconds.", &wait);
+        tokio::time::sleep(std::time::Duration::from_secs(wait)).await;
+
+        snapshot(&*glob.read().await).await;
+    }
+}
+
+/**
+Respond with the lag histories of the given `students`.
+
+Header:
+```text
+x-camp-action: lag-history
+```
+
+Response body looks like
+```json
+{
+    "students": [
+        {
+            "uname": "jsmith", "last": "Smith", "rest": "Jane",
+            "history": [
+                {
+                    "week": "2022-09-05", "lag": -0.02,
+                    "done_weight": 0.03, "due_weight": 0.05, "total_weight": 1.0
+                },
+                ...
+            ]
+        },
+        ...
+    ]
+}
+```
+Students are sorted by name; those with no snapshots have an empty
+`history`.
+*/
+pub(super) async fn respond(mut students: Vec<&Student>, glob: &Glob) -> Response {
+    students.sort_by(|a, b| (&a.last, &a.rest).cmp(&(&b.last, &b.rest)));
+    let unames: Vec<&str> = students.iter().map(|s| s.base.uname.as_str()).collect();
+
+    let mut history = match glob.data().read().await.get_lag_history(&unames).await {
+        Ok(history) => history,
+        Err(e) => {
+            log::error!("Error retrieving lag history for {:?}: {}", &unames, &e);
+            return text_500(Some(format!("Unable to retrieve lag history: {}", &e)));
+        }
+    };
+
+    let students: Vec<StudentLag> = students
+        .iter()
+        .map(|s| StudentLag {
+            uname: &s.base.uname,
+            last: &s.last,
+            rest: &s.rest,
+            history: history.remove(&s.base.uname).unwrap_or_default(),
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("lag-history"),
+        )],
+        Json(LagHistory { students }),
+    )
+        .into_response()
+}
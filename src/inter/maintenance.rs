@@ -0,0 +1,84 @@
+/*!
+What the server serves while it's still starting up.
+
+Until [`load_configuration`](crate::config::load_configuration) has
+finished (which may mean waiting for a database; see
+[`startup`](crate::startup)), every request gets a SERVICE UNAVAILABLE
+response with a maintenance page that reloads itself every so often.
+The response's `x-camp-startup` header says what startup is waiting on,
+for health checks and monitoring.
+*/
+use axum::{
+    handler::Handler,
+    http::header::{HeaderName, HeaderValue, RETRY_AFTER},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    Extension, Router,
+};
+
+use crate::startup::{SharedStatus, StartupStatus};
+
+/// How often (in seconds) the maintenance page reloads, and clients are
+/// told to retry.
+const RETRY_SECS: &str = "10";
+
+static PAGE_HEAD: &str = r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="10">
+<title>camp | Starting Up</title>
+</head>
+<body>
+<h1>camp is starting up</h1>
+<p>This should only take a moment; this page will reload itself when
+camp is ready.</p>
+"#;
+
+static PAGE_TAIL: &str = "</body>\n</html>";
+
+/// A short description of what startup is waiting on. (The error itself
+/// may have connection details in it, so it only goes in the log.)
+fn describe(status: &StartupStatus) -> String {
+    match status.waiting_for.as_deref() {
+        Some(name) => format!("waiting for {} (attempt {})", name, &status.attempts),
+        None => "starting".to_owned(),
+    }
+}
+
+async fn maintenance_page(Extension(status): Extension<SharedStatus>) -> Response {
+    let what = describe(&status.lock().unwrap());
+    let body = format!("{}<p>(Currently {}.)</p>\n{}", PAGE_HEAD, &what, PAGE_TAIL);
+
+    let mut response = (StatusCode::SERVICE_UNAVAILABLE, Html(body)).into_response();
+    let headers = response.headers_mut();
+    headers.insert(RETRY_AFTER, HeaderValue::from_static(RETRY_SECS));
+    // `what` is made of a database name and a number.
+    if let Ok(v) = HeaderValue::from_str(&what) {
+        headers.insert(HeaderName::from_static("x-camp-startup"), v);
+    }
+    response
+}
+
+/// A `Router` that answers every request with the maintenance page.
+pub fn router(status: SharedStatus) -> Router {
+    Router::new()
+        .fallback(maintenance_page.into_service())
+        .layer(Extension(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn startup_descriptions() {
+        assert_eq!(describe(&StartupStatus::default()), "starting");
+        let status = StartupStatus {
+            waiting_for: Some("data DB".to_owned()),
+            attempts: 3,
+            last_error: Some("connection refused".to_owned()),
+        };
+        assert_eq!(describe(&status), "waiting for data DB (attempt 3)");
+    }
+}
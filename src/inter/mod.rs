@@ -6,7 +6,7 @@ Interoperation between the client (user) and server.
 use std::{fmt::Debug, io::Write, path::Path, sync::Arc};
 
 use axum::{
-    http::header::{HeaderMap, HeaderName, HeaderValue},
+    http::header::{self, HeaderMap, HeaderName, HeaderValue},
     http::{Request, StatusCode},
     middleware::Next,
     response::{Html, IntoResponse, Response},
@@ -14,16 +14,55 @@ use axum::{
 };
 use handlebars::Handlebars;
 use once_cell::sync::OnceCell;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use time::Date;
 use tokio::sync::RwLock;
 
-use crate::{auth::AuthResult, config::Glob, user::User, MiniString, MEDSTORE};
+use crate::{
+    anon,
+    auth::AuthResult,
+    config::Glob,
+    format_date,
+    user::{Student, User},
+    DateStyle, MiniString, MEDSTORE, SMALLSTORE,
+};
 
+pub mod activity;
 pub mod admin;
+pub mod announce;
+pub mod api_error;
+pub mod assets;
+pub mod at_risk;
 pub mod boss;
+pub mod completion;
+pub mod confirm;
+pub mod digest;
+pub mod durations;
+pub mod error_page;
+pub mod extract;
+pub mod fragment;
+pub mod ical;
+mod helpers;
+pub mod journal;
+pub mod kiosk;
+pub mod lag_history;
+pub mod maintenance;
+pub mod rate_limit;
+pub mod report_templates;
+pub mod reports;
+pub mod retention;
+pub mod roster;
 pub mod student;
+pub mod staff_email;
+pub mod suggest;
+pub mod support;
 pub mod teacher;
+pub mod unsubscribe;
+pub mod webhooks;
+pub mod zip_stream;
+
+pub use api_error::ApiError;
 
 /// [`Handlebars`] struct for rendering HTML-escaped text.
 static TEMPLATES: OnceCell<Handlebars> = OnceCell::new();
@@ -32,25 +71,8 @@ static JSON_TEMPLATES: OnceCell<Handlebars> = OnceCell::new();
 /// [`Handlebars`] struct for rendering unescaped text.
 static RAW_TEMPLATES: OnceCell<Handlebars> = OnceCell::new();
 
-/// Text to be sent on an INTERNAL SERVER ERROR when responding to a request
-/// that expects HTML.
-static HTML_500: &str = r#"<!doctype html>
-<html>
-<head>
-<meta charset="utf-8">
-<title>camp | Error</title>
-<link rel="stylesheet" href="/static/camp.css">
-</head>
-<body>
-<h1>Internal Server Error</h1>
-<p>(Error 500)</p>
-<p>Something went wrong on our end. No further or more
-helpful information is available about the problem.</p>
-</body>
-</html>"#;
-
-/// Default text to be sent on an INTERNAL SERVER ERROR when responding to a
-/// request that expects plain text.
+/// Default message sent with an INTERNAL SERVER ERROR from an API (see
+/// [`ApiError::internal`]).
 static TEXT_500: &str = "An internal error occurred; an appropriate response was inconstructable.";
 
 /**
@@ -94,11 +116,28 @@ fn get_head<'a>(name: &'static str, headers: &'a HeaderMap) -> Result<&'a str, S
     })
 }
 
+/// Format the days of a calendar as ISO ("2022-09-05") strings, to label
+/// the points of a [`Burndown`](crate::pace::Burndown) series.
+fn iso_dates(dates: &[Date]) -> Result<Vec<MiniString<SMALLSTORE>>, String> {
+    dates.iter().map(|d| format_date(DateStyle::Iso, d)).collect()
+}
+
 /// Data type to read the form data from a front-page login request.
 #[derive(serde::Deserialize, Debug)]
 pub struct LoginData {
     pub uname: String,
     pub password: String,
+    /// Present (as `"on"`) if the *anonymize* box was checked; see
+    /// [`anon`](crate::anon).
+    #[serde(default)]
+    pub anonymize: Option<String>,
+}
+
+impl LoginData {
+    /// Whether the session being logged into should be anonymized.
+    pub fn anonymize(&self) -> bool {
+        self.anonymize.is_some()
+    }
 }
 
 /// Escape function to be used by [`handlebars`] for escaping JSON data.
@@ -121,7 +160,8 @@ Initializes the resources used in this module. This function should be called
 before any functionality of this module or any of its submodules is used.
 
 Currently the only thing that happens here is loading the templates used by
-`serve_template()`, which will panic unless `init()` has been called first.
+`serve_template()`, which will panic unless `init()` has been called first,
+and registering the custom helpers (see [`helpers`]) they can use.
 
 The argument is the path to the directory where the templates used by
 `serve_template()` can be found.
@@ -135,6 +175,7 @@ pub fn init<P: AsRef<Path>>(template_dir: P) -> Result<(), String> {
     let template_dir = template_dir.as_ref();
 
     let mut h = Handlebars::new();
+    helpers::register(&mut h);
     #[cfg(debug_assertions)]
     h.set_dev_mode(true);
     h.register_templates_directory(".html", template_dir)
@@ -159,6 +200,7 @@ pub fn init<P: AsRef<Path>>(template_dir: P) -> Result<(), String> {
     })?;
 
     let mut j = Handlebars::new();
+    helpers::register(&mut j);
     #[cfg(debug_assertions)]
     j.set_dev_mode(true);
     j.register_templates_directory(".json", template_dir)
@@ -184,6 +226,7 @@ pub fn init<P: AsRef<Path>>(template_dir: P) -> Result<(), String> {
     })?;
 
     let mut r = Handlebars::new();
+    helpers::register(&mut r);
     #[cfg(debug_assertions)]
     r.set_dev_mode(true);
     r.register_templates_directory(".html", template_dir)
@@ -232,16 +275,18 @@ Return an HTML response in the case of an unrecoverable* error.
 
 (*"Unrecoverable" from the perspective of fielding the current request,
 not from the perspective of the program crashing.)
+
+Log the details before calling this; the page only shows the request id
+(see [`error_page`]).
 */
 pub fn html_500() -> Response {
-    (StatusCode::INTERNAL_SERVER_ERROR, Html(HTML_500)).into_response()
+    error_page::respond(StatusCode::INTERNAL_SERVER_ERROR)
 }
 
+/// Convenience function for generating a 500 response from an API; see
+/// [`ApiError`].
 pub fn text_500(text: Option<String>) -> Response {
-    match text {
-        Some(text) => (StatusCode::INTERNAL_SERVER_ERROR, text).into_response(),
-        None => (StatusCode::INTERNAL_SERVER_ERROR, TEXT_500.to_owned()).into_response(),
-    }
+    ApiError::internal(text).into_response()
 }
 
 /// Render a template with HTML-escaping.
@@ -275,6 +320,16 @@ pub fn render_raw_template<T: Serialize>(name: &str, data: &T) -> Result<String,
         .map_err(|e| format!("Error rendering raw template {:?}: {}", name, &e))
 }
 
+/// Render the template `text` (rather than one from the templates directory,
+/// like [`render_raw_template`] does) with no escaping.
+pub fn render_raw_string<T: Serialize>(text: &str, data: &T) -> Result<String, String> {
+    RAW_TEMPLATES
+        .get()
+        .unwrap()
+        .render_template(text, data)
+        .map_err(|e| format!("Error rendering template text: {}", &e))
+}
+
 /// Render a template with no escaping to a [`Write`]r.
 pub fn write_raw_template<T: Serialize, W: Write>(
     name: &str,
@@ -415,23 +470,64 @@ pub fn respond_bad_password(uname: &str) -> Response {
     serve_template(StatusCode::UNAUTHORIZED, "bad_password", &data, vec![])
 }
 
+/// Convenience function for generating a response to a login attempt by a
+/// user who has been locked out after too many failed attempts.
+///
+/// This is exactly the response to a bad password: unames that don't exist
+/// never get locked out, so saying so would reveal which ones do.
+pub fn respond_locked_out(uname: &str, seconds: i64) -> Response {
+    log::trace!("respond_locked_out( {:?}, {} ) called.", uname, &seconds);
+
+    respond_bad_password(uname)
+}
+
 /// Convenience function for generating a response to a key authentication
 /// failure.
 pub fn respond_bad_key() -> Response {
     log::trace!("respond_bad_key() called.");
 
-    (
-        StatusCode::UNAUTHORIZED,
-        "Invalid authorization key.".to_owned(),
-    )
-        .into_response()
+    ApiError::unauthorized("Invalid authorization key.".to_owned()).into_response()
 }
 
-/// Convenience function for generating a 400 response.
+/// Convenience function for generating a 400 response from an API; see
+/// [`ApiError`].
 pub fn respond_bad_request(msg: String) -> Response {
     log::trace!("respond_bad_request( {:?} ) called.", &msg);
 
-    (StatusCode::BAD_REQUEST, msg).into_response()
+    ApiError::bad_request(msg).into_response()
+}
+
+/// Respond to a `download-students` action (from either the Admin or
+/// Teacher API) with a roster of `students` in the CSV format accepted by
+/// `upload-students`; see [`Student::csv_from_students`].
+fn respond_students_csv(students: &[&Student]) -> Response {
+    let csv = match Student::csv_from_students(students) {
+        Ok(csv) => csv,
+        Err(e) => {
+            log::error!("Error generating student roster CSV: {}", &e);
+            return text_500(Some(e));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("download-students"),
+            ),
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/csv; charset=utf-8"),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                HeaderValue::from_static("attachment; filename=\"students.csv\""),
+            ),
+        ],
+        csv,
+    )
+        .into_response()
 }
 
 pub async fn log_request<B>(req: Request<B>, next: Next<B>) -> Response {
@@ -539,11 +635,7 @@ pub async fn key_authenticate<B>(req: Request<B>, next: Next<B>) -> Response {
             return text_500(None);
         }
         Ok(AuthResult::InvalidKey) => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                "Invalid authorization key.".to_owned(),
-            )
-                .into_response();
+            return respond_bad_key();
         }
         Ok(AuthResult::Ok) => {
             // This is the good path. We will just fall through and call the
@@ -558,82 +650,137 @@ pub async fn key_authenticate<B>(req: Request<B>, next: Next<B>) -> Response {
         }
     }
 
-    next.run(req).await
+    let anonymized = glob.read().await.is_anonymized(key);
+    anon::scope(anonymized, next.run(req)).await
+}
+
+/**
+Log out: revoke the key the request was made with.
+
+This sits behind [`key_authenticate`], so both headers are known to be
+present and valid.
+*/
+pub async fn logout(
+    headers: HeaderMap,
+    Extension(glob): Extension<Arc<RwLock<Glob>>>,
+) -> Response {
+    let (uname, key) = match (
+        get_head("x-camp-uname", &headers),
+        get_head("x-camp-key", &headers),
+    ) {
+        (Ok(uname), Ok(key)) => (uname, key),
+        (Err(e), _) | (_, Err(e)) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .auth()
+        .read()
+        .await
+        .revoke_keys(uname, Some(key))
+        .await;
+
+    glob.read().await.set_anonymized(key, false);
+
+    match res {
+        Ok(_) => (
+            StatusCode::OK,
+            [(
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("logout"),
+            )],
+            "Logged out.".to_owned(),
+        )
+            .into_response(),
+        Err(e) => {
+            log::error!("Error revoking key of {:?}: {}", uname, &e);
+            text_500(None)
+        }
+    }
 }
 
 /**
-Make an HTTP request to the [Sendgrid](https://sendgrid.com/) service to send
-an email.
+Send an email through the configured [`EmailSender`](crate::email::EmailSender),
+keeping track of how it goes in the [`Glob`]'s `email_health`.
 
-`json_body` should be a valid Sendgrid
-[Mail Send v3 request body](https://docs.sendgrid.com/api-reference/mail-send/mail-send),
-and the [`Glob`] should have your appropriate Sendgrid credentials.
+`json_body` should be one of the JSON email templates, rendered (see
+[`Email`](crate::email::Email)).
 
 The `student` parameter is only for generating nice(r) error messages.
 */
-pub async fn make_sendgrid_request(
+pub async fn send_rendered_email(
     json_body: String,
     glob: &Glob,
     student: MiniString<MEDSTORE>,
 ) -> Result<(), String> {
-    use hyper::{Body, Client, Method};
+    log::trace!(
+        "send_rendered_email( [ {} bytes of body ] ) called.",
+        json_body.len()
+    );
+
+    let email = crate::email::Email::from_json(&json_body)?;
+    glob.email_health
+        .send(glob.mailer.as_ref(), email)
+        .await
+        .map_err(|e| format!("Error sending email about {}: {}", &student, &e))
+}
+
+/**
+Queue an email to be sent by the [`Glob`]'s
+[`Outbox`](crate::email::Outbox), or, if it doesn't have one (like in a
+sandbox), send it right away with [`send_rendered_email`].
 
+The arguments are the same as for `send_rendered_email`, and the email is
+checked before it's queued, so the only errors that come up later are
+about sending it.
+*/
+pub async fn queue_rendered_email(
+    json_body: String,
+    glob: &Glob,
+    student: MiniString<MEDSTORE>,
+) -> Result<(), String> {
+    let outbox = match glob.outbox.as_ref() {
+        Some(outbox) => outbox,
+        None => {
+            return send_rendered_email(json_body, glob, student).await;
+        }
+    };
     log::trace!(
-        "make_sendgrid_request( [ {} bytes of body ] ) called.",
+        "queue_rendered_email( [ {} bytes of body ] ) called.",
         json_body.len()
     );
-    log::debug!("Sendgrid request body:\n{}", &json_body);
-
-    let https = hyper_rustls::HttpsConnectorBuilder::new()
-        .with_native_roots()
-        .https_or_http()
-        .enable_http1()
-        .build();
-    let client: Client<_, hyper::Body> = Client::builder().build(https);
-
-    let req = Request::builder()
-        .method(Method::POST)
-        .uri(&glob.sendgrid_uri)
-        .header("Authorization", &glob.sendgrid_auth)
-        .header("Content-Type", "application/json")
-        .body(Body::from(json_body))
-        .map_err(|e| format!("Error building sendgrid request: {}", &e))?;
-
-    let resp = client
-        .request(req)
+
+    crate::email::Email::from_json(&json_body)?;
+    glob.data()
+        .read()
         .await
-        .map_err(|e| format!("Error from sendgrid request: {}", &e))
-        .map_err(|e| format!("Error sending sendgrid request: {}", &e))?;
-
-    if resp.status() == 202 {
-        Ok(())
-    } else {
-        Err(format!(
-            "Sendgrid returned {} response (expected 202) while sending email about {}.",
-            &resp.status(),
-            &student
-        ))
-    }
+        .queue_email(student.as_str(), &json_body)
+        .await
+        .map_err(|e| format!("Error queueing email about {}: {}", &student, &e))?;
+    outbox.wake();
+    Ok(())
 }
 
-/// Generate (and send) a password reset email for the supplied [`User`].
+/// Generate (and queue) a password reset email for the supplied [`User`].
 ///
 /// This includes generating and registering a key to use in the password
-/// reset process.
-pub async fn generate_email(u: &User, glob: &Glob) -> Response {
+/// reset process. (The key goes in the auth DB, so it can't be issued in the
+/// same transaction the email is queued in.)
+pub async fn generate_email(u: &User, glob: &Glob) -> Result<(), String> {
     let key = match glob.auth().read().await.issue_key(u.uname()).await {
         Err(e) => {
-            log::error!("auth::Db::issue_key( {:?} ) returned {:?}", u.uname(), &e);
-            return text_500(None);
+            return Err(format!("auth::Db::issue_key( {:?} ) returned {:?}", u.uname(), &e));
         }
         Ok(AuthResult::Key(k)) => k,
         Ok(x) => {
-            log::warn!(
+            return Err(format!(
                 "auth::Db::issue_key( {:?} ) returned {:?}, which shouldn't happen.",
                 u.uname(),
                 &x
-            );
-            return text_500(None);
+            ));
         }
     };
 
@@ -664,23 +811,12 @@ pub async fn generate_email(u: &User, glob: &Glob) -> Response {
         _ => render_json_template("password_email", &data),
     };
 
-    let body = match render_res {
-        Err(e) => {
-            log::error!("Error rendering email template for {:?}: {}", u, &e);
-            return text_500(Some("Error generating email.".to_owned()));
-        }
-        Ok(body) => body,
-    };
+    let body = render_res
+        .map_err(|e| format!("Error rendering email template for {:?}: {}", u, &e))?;
 
     let name: MiniString<MEDSTORE> = MiniString::from(u.uname());
 
-    match make_sendgrid_request(body, glob, name).await {
-        Ok(()) => StatusCode::OK.into_response(),
-        Err(e) => {
-            log::error!("Error with Sendgrid request: {}", &e);
-            text_500(Some("Error generating email.".to_owned()))
-        }
-    }
+    queue_rendered_email(body, glob, name).await
 }
 
 /**
@@ -693,8 +829,16 @@ x-camp-uname: [ user's user name ]
 x-camp-key: [ auth key sent in password reset email]
 x-camp-password: [ the new requested password ]
 ```
+
+A `uname` that doesn't belong to any user (`u` is `None`) gets the same
+response as a real user with a bad key.
 */
-pub async fn update_password(u: &User, headers: &HeaderMap, glob: &Glob) -> Response {
+pub async fn update_password(
+    uname: &str,
+    u: Option<&User>,
+    headers: &HeaderMap,
+    glob: &Glob,
+) -> Response {
     let key = match headers.get("x-camp-key") {
         Some(k_val) => match k_val.to_str() {
             Ok(s) => s,
@@ -732,11 +876,11 @@ pub async fn update_password(u: &User, headers: &HeaderMap, glob: &Glob) -> Resp
     let auth = glob.auth();
     let auth_handle = auth.read().await;
 
-    match auth_handle.check_key(u.uname(), key).await {
+    match auth_handle.check_key(uname, key).await {
         Err(e) => {
             log::error!(
                 "auth::Db::check_key( {:?}, {:?} ) error: {}",
-                u.uname(),
+                uname,
                 key,
                 &e
             );
@@ -749,7 +893,7 @@ pub async fn update_password(u: &User, headers: &HeaderMap, glob: &Glob) -> Resp
         Ok(x) => {
             log::warn!(
                 "auth::Db::check_key( {:?}. {:?} ) returned {:?}, which shouldn't happen.",
-                u.uname(),
+                uname,
                 key,
                 &x
             );
@@ -757,6 +901,18 @@ pub async fn update_password(u: &User, headers: &HeaderMap, glob: &Glob) -> Resp
         }
     }
 
+    // A key can only have been issued to an actual user, but just in case.
+    let u = match u {
+        Some(u) => u,
+        None => {
+            return respond_bad_key();
+        }
+    };
+
+    if let Err(e) = glob.check_password_policy(u.uname(), new_pwd) {
+        return respond_bad_request(e);
+    }
+
     match auth_handle.set_password(u.uname(), new_pwd, u.salt()).await {
         Ok(()) => StatusCode::OK.into_response(),
         Err(e) => {
@@ -772,8 +928,336 @@ pub async fn update_password(u: &User, headers: &HeaderMap, glob: &Glob) -> Resp
     }
 }
 
-/// API endpoint for HTTP requests sent to "/pwd", which have to do with
-/// requesting and executing password resets.
+/**
+Respond to a logged-in user's request to change their own password.
+
+This request should have the following headers (in addition to the usual
+`x-camp-uname` and `x-camp-key`):
+```text
+x-camp-action: change-password
+x-camp-password: [ the user's current password ]
+x-camp-new-password: [ the new requested password ]
+```
+
+The current password is checked just like a login attempt (so wrong
+guesses count toward a lockout), and the new one has to satisfy the
+password policy (see [`Glob::check_password_policy`]).
+*/
+pub async fn change_password(u: &User, headers: &HeaderMap, glob: &Glob) -> Response {
+    let current = match get_head("x-camp-password", headers) {
+        Ok(pwd) => pwd,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+    let new_pwd = match get_head("x-camp-new-password", headers) {
+        Ok(pwd) => pwd,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    if let Err(e) = glob.check_password_policy(u.uname(), new_pwd) {
+        return respond_bad_request(e);
+    }
+
+    let auth = glob.auth();
+    let auth_handle = auth.read().await;
+
+    match auth_handle
+        .check_password(u.uname(), current, u.salt())
+        .await
+    {
+        Err(e) => {
+            log::error!(
+                "auth::Db::check_password( {:?}, ... ) error: {}",
+                u.uname(),
+                &e
+            );
+            return text_500(None);
+        }
+        Ok(AuthResult::Ok) => { /* Proceed. */ }
+        Ok(AuthResult::Locked(secs)) => {
+            return ApiError::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!(
+                    "Too many failed password attempts; try again in {} seconds.",
+                    &secs
+                ),
+            )
+            .with_detail(&json!({ "retry_after": secs }))
+            .into_response();
+        }
+        Ok(_) => {
+            return ApiError::unauthorized("Your current password is incorrect.".to_owned())
+                .into_response();
+        }
+    }
+
+    match auth_handle.set_password(u.uname(), new_pwd, u.salt()).await {
+        Ok(()) => (
+            StatusCode::OK,
+            [(
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("none"),
+            )],
+        )
+            .into_response(),
+        Err(e) => {
+            log::error!(
+                "auth::Db::set_password( {:?}, ... ) error: {}",
+                u.uname(),
+                &e
+            );
+            text_500(None)
+        }
+    }
+}
+
+/// Deserializes the body of a `recompute-weights` request; exactly one of
+/// the fields should be present.
+#[derive(Debug, Deserialize)]
+struct WeightScope {
+    sym: Option<String>,
+    uname: Option<String>,
+}
+
+/**
+Respond to a request to reload course data and recompute the weights of
+either every goal in a course or every goal of one student, reporting the
+goals whose weights changed (see
+[`Glob::recompute_weights`](crate::config::Glob::recompute_weights)).
+
+Editing chapters through the Admin API already reloads course data, so this
+only finds anything when course data has been changed some other way (like
+directly in the database, or by another instance of the server).
+
+Header:
+```text
+x-camp-action: recompute-weights
+```
+With a JSON body of either `{ "sym": "pha1" }` or `{ "uname": "jsmith" }`.
+
+This is available to both Admins and Teachers; when `tuname` is supplied
+(that is, when a Teacher makes the request), only goals belonging to that
+Teacher's students are considered.
+*/
+pub async fn recompute_weights(
+    body: Option<String>,
+    tuname: Option<&str>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request needs application/json body with a course or student.".to_owned(),
+            );
+        }
+    };
+
+    let scope: WeightScope = match serde_json::from_str(&body) {
+        Ok(scope) => scope,
+        Err(e) => {
+            log::error!("Error deserializing {:?} as WeightScope: {}", &body, &e);
+            return respond_bad_request(format!("Unable to read request: {}", &e));
+        }
+    };
+
+    let unames: Vec<String> = {
+        let glob = glob.read().await;
+        let data = glob.data();
+        let data = data.read().await;
+
+        let res = match (&scope.sym, &scope.uname) {
+            (Some(sym), None) => {
+                if glob.course_by_sym(sym).is_none() {
+                    return respond_bad_request(format!("There is no course {:?}.", sym));
+                }
+                data.get_goals_by_course(sym).await
+            }
+            (None, Some(uname)) => {
+                match glob.users.get(uname) {
+                    Some(User::Student(s)) => {
+                        if let Some(tuname) = tuname {
+                            if s.teacher != tuname {
+                                let estr = format!("The student {:?} is not yours.", uname);
+                                return ApiError::forbidden(estr).into_response();
+                            }
+                        }
+                    }
+                    _ => {
+                        return respond_bad_request(format!(
+                            "The uname {:?} does not belong to a student in the system.",
+                            uname
+                        ));
+                    }
+                }
+                data.get_goals_by_student(uname).await
+            }
+            _ => {
+                return respond_bad_request(
+                    "Request must specify exactly one of \"sym\" or \"uname\".".to_owned(),
+                );
+            }
+        };
+
+        let goals = match res {
+            Ok(goals) => goals,
+            Err(e) => {
+                log::error!("Error retrieving goals for {:?}: {}", &scope, &e);
+                return text_500(Some(format!("Error retrieving goals: {}", &e)));
+            }
+        };
+
+        let mut unames: Vec<String> = goals
+            .into_iter()
+            .map(|g| g.uname)
+            .filter(|uname| match (tuname, glob.users.get(uname)) {
+                (None, Some(User::Student(_))) => true,
+                (Some(tuname), Some(User::Student(s))) => s.teacher == tuname,
+                _ => false,
+            })
+            .collect();
+        unames.sort();
+        unames.dedup();
+        unames
+    };
+
+    let res = glob
+        .write()
+        .await
+        .recompute_weights(&unames, scope.sym.as_deref())
+        .await;
+    let deltas = match res {
+        Ok(deltas) => deltas,
+        Err(e) => {
+            log::error!("Error recomputing weights for {:?}: {}", &scope, &e);
+            return text_500(Some(format!("Error recomputing weights: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("recompute-weights"),
+        )],
+        axum::Json(deltas),
+    )
+        .into_response()
+}
+
+/// Deserializes the body of a `waive-chapter` request.
+#[derive(Debug, Deserialize)]
+struct WaiveData {
+    uname: String,
+    sym: String,
+    seq: i16,
+    waived: bool,
+}
+
+/**
+Waive (or un-waive) a chapter for a student, as requested in a
+`waive-chapter` request by the user `by`.
+
+Header:
+```text
+x-camp-action: waive-chapter
+```
+With a JSON body like
+```json
+{ "uname": "jsmith", "sym": "pha1", "seq": 3, "waived": true }
+```
+
+This is available to both Admins and Teachers; when `tuname` is supplied
+(that is, when a Teacher makes the request), the student must be one of
+that Teacher's. On success, returns the uname of the student (so the
+caller can respond with whatever the requester needs to see updated);
+otherwise returns the error response.
+*/
+async fn set_waiver(
+    body: Option<String>,
+    by: &str,
+    tuname: Option<&str>,
+    glob: &Glob,
+) -> Result<String, Response> {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return Err(respond_bad_request(
+                "Request needs application/json body with student and chapter.".to_owned(),
+            ));
+        }
+    };
+
+    let wdata: WaiveData = match serde_json::from_str(&body) {
+        Ok(wdata) => wdata,
+        Err(e) => {
+            log::error!("Error deserializing {:?} as WaiveData: {}", &body, &e);
+            return Err(respond_bad_request(format!(
+                "Unable to read request: {}",
+                &e
+            )));
+        }
+    };
+
+    match glob.users.get(&wdata.uname) {
+        Some(User::Student(s)) => {
+            if let Some(tuname) = tuname {
+                if s.teacher != tuname {
+                    let estr = format!("The student {:?} is not yours.", &wdata.uname);
+                    return Err(ApiError::forbidden(estr).into_response());
+                }
+            }
+        }
+        _ => {
+            return Err(respond_bad_request(format!(
+                "The uname {:?} does not belong to a student in the system.",
+                &wdata.uname
+            )));
+        }
+    }
+
+    let w = crate::pace::Waiver {
+        sym: wdata.sym,
+        seq: wdata.seq,
+    };
+    match glob.set_waiver(&wdata.uname, &w, wdata.waived, by).await {
+        Ok(changed) => {
+            log::trace!(
+                "{} set waiver of {:?} for {:?} to {} (changed: {}).",
+                by,
+                &w,
+                &wdata.uname,
+                &wdata.waived,
+                &changed
+            );
+        }
+        Err(e) => {
+            log::error!(
+                "Error setting waiver {:?} for {:?}: {}",
+                &w,
+                &wdata.uname,
+                &e
+            );
+            return Err(respond_bad_request(format!(
+                "Unable to change waiver: {}",
+                &e
+            )));
+        }
+    }
+
+    Ok(wdata.uname)
+}
+
+/**
+API endpoint for HTTP requests sent to "/pwd", which have to do with
+requesting and executing password resets.
+
+Responses are the same whether or not the `x-camp-uname` belongs to an
+actual user, so this can't be used to discover which unames exist.
+*/
 pub async fn password_reset(
     headers: HeaderMap,
     Extension(glob): Extension<Arc<RwLock<Glob>>>,
@@ -812,17 +1296,35 @@ pub async fn password_reset(
         }
     };
 
-    let glob = glob.read().await;
-    let u = match glob.users.get(uname) {
-        Some(u) => u,
-        None => {
-            return StatusCode::OK.into_response();
-        }
-    };
-
     match action {
-        "request-email" => generate_email(u, &glob).await,
-        "reset-password" => update_password(u, &headers, &glob).await,
+        "request-email" => {
+            let u = glob.read().await.users.get(uname).cloned();
+            match u {
+                // Sending the email takes long enough that waiting for it
+                // would reveal that the user exists, so it happens in the
+                // background, and any failure just gets logged.
+                Some(u) => {
+                    tokio::spawn(async move {
+                        let glob = glob.read().await;
+                        if let Err(e) = generate_email(&u, &glob).await {
+                            log::error!(
+                                "Error sending password reset email to {:?}: {}",
+                                u.uname(),
+                                &e
+                            );
+                        }
+                    });
+                }
+                None => {
+                    log::info!("Password reset email requested for nonexistent user {:?}.", uname);
+                }
+            }
+            StatusCode::OK.into_response()
+        }
+        "reset-password" => {
+            let glob = glob.read().await;
+            update_password(uname, glob.users.get(uname), &headers, &glob).await
+        }
         x => respond_bad_request(format!(
             "Unrecognized or invalid x-camp-action value: {:?}",
             &x
@@ -0,0 +1,347 @@
+/*!
+Throttling of login and password reset requests.
+
+The [`limit`] middleware sits in front of everything that takes a password
+(`/login`, `/student/key`, and `POST`s to `/student/basic`) and `/pwd`, and
+counts requests both by the client's IP address and by the uname they're
+for (from the `uname` field of a form or JSON body, or the `x-camp-uname`
+header). Once
+either one makes more than [`RateLimit::attempts`] requests in a window of
+[`RateLimit::window_secs`], it's turned away for
+[`RateLimit::lockout_secs`] with a TOO MANY REQUESTS response and a
+`Retry-After` header.
+
+This is separate from (and in front of) the auth DB's lockout after
+consecutive failed passwords (see [`Db`](crate::auth::Db)): it counts every
+attempt, good or bad, and forgets everything when the server restarts.
+
+Requests are counted by the address of the connection, so behind a reverse
+proxy every client looks the same, and only the per-uname limit is useful;
+in that case, set `attempts` high enough that the proxy's traffic as a
+whole doesn't trip it.
+*/
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::header::{HeaderValue, CONTENT_TYPE, RETRY_AFTER},
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use super::{respond_login_error, student::basic_login_form, text_500, ApiError};
+use crate::config::Glob;
+
+/// Once a [`RateLimiter`] is tracking this many clients and unames, it
+/// forgets the ones whose windows have passed.
+const PRUNE_SIZE: usize = 1024;
+
+/// How hard to throttle login and password reset requests.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct RateLimit {
+    /// Number of requests allowed from one IP address, or for one uname,
+    /// per window; 0 turns throttling off. Default is 10.
+    pub attempts: u32,
+    /// Length of the window (in seconds). Default is 60.
+    pub window_secs: u64,
+    /// How long (in seconds) a client or uname that goes over is turned
+    /// away. Default is 300.
+    pub lockout_secs: u64,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            attempts: 10,
+            window_secs: 60,
+            lockout_secs: 300,
+        }
+    }
+}
+
+/// What's being counted.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Key {
+    Ip(IpAddr),
+    Uname(String),
+}
+
+/// Requests counted for a single [`Key`].
+#[derive(Debug)]
+struct Count {
+    window_start: Instant,
+    n: u32,
+    locked_until: Option<Instant>,
+}
+
+impl Count {
+    /// Whether this has nothing left to remember at `now`.
+    fn stale(&self, now: Instant, window: Duration) -> bool {
+        match self.locked_until {
+            Some(t) => t <= now,
+            None => self.window_start + window <= now,
+        }
+    }
+}
+
+/// Keeps track of requests made against a [`RateLimit`].
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    counts: Mutex<HashMap<Key, Count>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> RateLimiter {
+        RateLimiter {
+            limit,
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a request for `key` at `now`; if it's over the limit, return
+    /// how long until it won't be.
+    fn hit(&self, counts: &mut HashMap<Key, Count>, key: Key, now: Instant) -> Option<Duration> {
+        let window = Duration::from_secs(self.limit.window_secs);
+        let count = counts.entry(key).or_insert(Count {
+            window_start: now,
+            n: 0,
+            locked_until: None,
+        });
+
+        if let Some(t) = count.locked_until {
+            if t > now {
+                return Some(t - now);
+            }
+            count.locked_until = None;
+            count.window_start = now;
+            count.n = 0;
+        }
+        if count.window_start + window <= now {
+            count.window_start = now;
+            count.n = 0;
+        }
+
+        count.n += 1;
+        if count.n > self.limit.attempts {
+            let lockout = Duration::from_secs(self.limit.lockout_secs);
+            count.locked_until = Some(now + lockout);
+            return Some(lockout);
+        }
+        None
+    }
+
+    /**
+    Count a request at `now` from the address `ip` for the user `uname`
+    (either of which might not be known).
+
+    Returns `Err` with how long the client should wait if either is over
+    the limit.
+    */
+    pub fn check_at(
+        &self,
+        ip: Option<IpAddr>,
+        uname: Option<&str>,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        if self.limit.attempts == 0 {
+            return Ok(());
+        }
+
+        let mut counts = self.counts.lock().unwrap();
+        if counts.len() >= PRUNE_SIZE {
+            let window = Duration::from_secs(self.limit.window_secs);
+            counts.retain(|_, c| !c.stale(now, window));
+        }
+
+        let by_ip = ip.and_then(|ip| self.hit(&mut counts, Key::Ip(ip), now));
+        let by_uname = uname.and_then(|u| self.hit(&mut counts, Key::Uname(u.to_owned()), now));
+        match by_ip.max(by_uname) {
+            Some(wait) => Err(wait),
+            None => Ok(()),
+        }
+    }
+
+    /// Count a request made just now; see [`RateLimiter::check_at`].
+    pub fn check(&self, ip: Option<IpAddr>, uname: Option<&str>) -> Result<(), Duration> {
+        self.check_at(ip, uname, Instant::now())
+    }
+}
+
+/// Just the part of a login form (or JSON body) we need.
+#[derive(Deserialize)]
+struct FormUname {
+    uname: String,
+}
+
+/// The `uname` field of a request body with the given `content_type`, if it
+/// has one.
+fn body_uname(content_type: &str, bytes: &[u8]) -> Option<String> {
+    let form = if content_type.starts_with("application/x-www-form-urlencoded") {
+        serde_urlencoded::from_bytes::<FormUname>(bytes).ok()
+    } else {
+        serde_json::from_slice::<FormUname>(bytes).ok()
+    };
+    form.map(|f| f.uname)
+}
+
+/// Respond to a throttled request: with a login error page for the login
+/// form, the basic student view's login form for that, or an [`ApiError`]
+/// for everything else (the password reset page's requests, and JSON API
+/// clients asking for keys).
+fn respond_throttled(path: &str, wait: Duration) -> Response {
+    // Round up, so clients don't come back a moment too soon.
+    let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    let minutes = secs.div_ceil(60);
+    let msg = format!(
+        "Too many attempts. Please wait {} minute{} before trying again.",
+        &minutes,
+        if minutes == 1 { "" } else { "s" }
+    );
+
+    let mut response = match path {
+        "/login" => respond_login_error(StatusCode::TOO_MANY_REQUESTS, &msg),
+        "/student/basic" => basic_login_form(StatusCode::TOO_MANY_REQUESTS, Some(&msg)),
+        _ => ApiError::new(StatusCode::TOO_MANY_REQUESTS, msg).into_response(),
+    };
+    response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(secs));
+    response
+}
+
+/// Middleware function to throttle requests per IP address and per uname;
+/// see the module-level docs.
+pub async fn limit(req: Request<Body>, next: Next<Body>) -> Response {
+    let limiter = match req.extensions().get::<Arc<RwLock<Glob>>>() {
+        Some(glob) => glob.read().await.rate_limiter.clone(),
+        None => {
+            log::error!("rate_limit::limit() called on a route without the Glob.");
+            return text_500(None);
+        }
+    };
+    let ip = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+
+    let content_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_owned();
+    let has_body = ["application/x-www-form-urlencoded", "application/json"]
+        .iter()
+        .any(|t| content_type.starts_with(t));
+
+    let (req, uname) = if has_body {
+        // The uname is in the body, which the handler still needs.
+        let (parts, body) = req.into_parts();
+        let bytes = match hyper::body::to_bytes(body).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log::error!("Error reading request body: {}", &e);
+                return text_500(None);
+            }
+        };
+        let uname = body_uname(&content_type, &bytes);
+        (Request::from_parts(parts, Body::from(bytes)), uname)
+    } else {
+        let uname = req
+            .headers()
+            .get("x-camp-uname")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+        (req, uname)
+    };
+
+    if let Err(wait) = limiter.check(ip, uname.as_deref()) {
+        log::warn!(
+            "Throttling {} request from {:?} for {:?} for {:?}.",
+            req.uri().path(),
+            &ip,
+            &uname,
+            &wait
+        );
+        return respond_throttled(req.uri().path(), wait);
+    }
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limits() {
+        let limiter = RateLimiter::new(RateLimit {
+            attempts: 3,
+            window_secs: 60,
+            lockout_secs: 300,
+        });
+        let ip: Option<IpAddr> = Some("10.0.0.1".parse().unwrap());
+        let other_ip: Option<IpAddr> = Some("10.0.0.2".parse().unwrap());
+        let t0 = Instant::now();
+        let secs = |n: u64| t0 + Duration::from_secs(n);
+
+        for n in 0..3 {
+            assert!(limiter.check_at(ip, Some("frog"), secs(n)).is_ok());
+        }
+        assert_eq!(
+            limiter.check_at(ip, Some("toad"), secs(3)),
+            Err(Duration::from_secs(300))
+        );
+        // "toad" was still counted, though "frog" is the one over the limit.
+        assert!(limiter.check_at(other_ip, Some("toad"), secs(4)).is_ok());
+        // The same uname from somewhere else is still turned away.
+        assert_eq!(
+            limiter.check_at(other_ip, Some("frog"), secs(5)),
+            Err(Duration::from_secs(300))
+        );
+        assert_eq!(
+            limiter.check_at(ip, None, secs(103)),
+            Err(Duration::from_secs(200))
+        );
+        // The lockout ends, and the count starts over.
+        for n in 0..3 {
+            assert!(limiter.check_at(ip, Some("frog"), secs(400 + n)).is_ok());
+        }
+
+        // A new window forgives earlier requests.
+        assert!(limiter.check_at(other_ip, None, secs(470)).is_ok());
+        assert!(limiter.check_at(other_ip, None, secs(530)).is_ok());
+
+        let off = RateLimiter::new(RateLimit {
+            attempts: 0,
+            ..Default::default()
+        });
+        for _ in 0..100 {
+            assert!(off.check_at(ip, Some("frog"), t0).is_ok());
+        }
+    }
+
+    #[test]
+    fn body_unames() {
+        let form = "application/x-www-form-urlencoded";
+        assert_eq!(
+            body_uname(form, b"uname=frog&password=hunter2"),
+            Some("frog".to_owned())
+        );
+        assert_eq!(
+            body_uname("application/json", br#"{"uname": "frog", "password": "hunter2"}"#),
+            Some("frog".to_owned())
+        );
+        assert_eq!(body_uname(form, b"password=hunter2"), None);
+        assert_eq!(body_uname("application/json", b"uname=frog"), None);
+    }
+}
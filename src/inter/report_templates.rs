@@ -0,0 +1,326 @@
+/*!
+Admin API actions for schools' own versions of the report templates (see
+[`report::TEMPLATE_KINDS`](crate::report::TEMPLATE_KINDS)).
+
+  * `populate-report-templates` lists every uploaded version.
+  * `upload-report-template` saves a new (inactive) version.
+  * `preview-report-template` renders a student's report with a version,
+    as a PDF, without changing anything.
+  * `activate-report-template` chooses which version of a kind gets used
+    (or goes back to the built-in one).
+
+Uploading and activating change every report the school produces, so
+those are only for unrestricted Admins.
+*/
+use std::sync::Arc;
+
+use axum::{
+    http::header::{self, HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use tokio::sync::RwLock;
+
+use super::{respond_bad_request, text_500, ApiError};
+use crate::{
+    config::Glob,
+    pace::Term,
+    report::{self, TEMPLATE_KINDS},
+    user::User,
+};
+
+/// Deserializes the body of an `upload-report-template` request.
+#[derive(Debug, Deserialize)]
+struct UploadData {
+    kind: String,
+    label: String,
+    body: String,
+}
+
+/// Deserializes the body of a `preview-report-template` request.
+#[derive(Debug, Deserialize)]
+struct PreviewData {
+    id: i64,
+    uname: String,
+    #[serde(default)]
+    term: Option<String>,
+}
+
+/// Deserializes the body of an `activate-report-template` request.
+#[derive(Debug, Deserialize)]
+struct ActivateData {
+    kind: String,
+    id: Option<i64>,
+}
+
+/// Read the JSON body of a request; `what` describes what it should hold.
+#[allow(clippy::result_large_err)]
+fn read_body<T: DeserializeOwned>(body: Option<String>, what: &str) -> Result<T, Response> {
+    match body.as_deref().map(serde_json::from_str) {
+        Some(Ok(data)) => Ok(data),
+        Some(Err(e)) => Err(respond_bad_request(format!(
+            "Unable to read {}: {}",
+            what, &e
+        ))),
+        None => Err(respond_bad_request(format!(
+            "Request needs a JSON body with the {}.",
+            what
+        ))),
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn check_kind(kind: &str) -> Result<(), Response> {
+    if TEMPLATE_KINDS.contains(&kind) {
+        Ok(())
+    } else {
+        Err(respond_bad_request(format!(
+            "{:?} is not a kind of report template; try one of {:?}.",
+            kind, TEMPLATE_KINDS
+        )))
+    }
+}
+
+/**
+Respond with every uploaded version of every report template.
+
+```text
+x-camp-action: populate-report-templates
+```
+
+Response body looks like
+```json
+[
+    {
+        "id": 3,
+        "kind": "report",
+        "label": "with the new crest",
+        "body": "# {{rest}} {{last}}\n...",
+        "uploaded": "2023-01-09 15:42:07",
+        "uploaded_by": "admin",
+        "active": true
+    }
+]
+```
+*/
+pub async fn populate(glob: Arc<RwLock<Glob>>) -> Response {
+    let res = glob.read().await.data().read().await.get_report_templates().await;
+    let templates = match res {
+        Ok(templates) => templates,
+        Err(e) => {
+            log::error!("Error retrieving report templates: {}", &e);
+            return text_500(Some(format!("Unable to retrieve report templates: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-report-templates"),
+        )],
+        Json(templates),
+    )
+        .into_response()
+}
+
+/**
+Save a new version of a report template. It isn't used until it's
+activated.
+
+```text
+x-camp-action: upload-report-template
+```
+With a JSON body like
+```json
+{
+    "kind": "report_summer",
+    "label": "summer, shorter",
+    "body": "# Summer Report\n..."
+}
+```
+Responds like `populate-report-templates`.
+*/
+pub async fn upload(uname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let udata: UploadData = match read_body(body, "report template") {
+        Ok(udata) => udata,
+        Err(resp) => {
+            return resp;
+        }
+    };
+    if let Err(resp) = check_kind(&udata.kind) {
+        return resp;
+    }
+    let label = udata.label.trim();
+    if label.is_empty() {
+        return respond_bad_request("A report template needs a label.".to_owned());
+    }
+    if let Err(e) = report::check_template(&udata.body) {
+        return respond_bad_request(e);
+    }
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .insert_report_template(&udata.kind, label, &udata.body, uname)
+        .await;
+    if let Err(e) = res {
+        log::error!("Error saving {:?} report template {:?}: {}", &udata.kind, label, &e);
+        return text_500(Some(format!("Unable to save report template: {}", &e)));
+    }
+
+    populate(glob).await
+}
+
+/**
+Render a student's report (as a parent would see it) with a version of a
+report template, as a PDF.
+
+```text
+x-camp-action: preview-report-template
+```
+With a JSON body like
+```json
+{ "id": 3, "uname": "jsmith", "term": "Spring" }
+```
+The `term` is optional; it defaults to Summer for `"report_summer"`
+templates and Fall otherwise.
+*/
+pub async fn preview(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let pdata: PreviewData = match read_body(body, "template id and student") {
+        Ok(pdata) => pdata,
+        Err(resp) => {
+            return resp;
+        }
+    };
+
+    let res = glob.read().await.data().read().await.get_report_template(pdata.id).await;
+    let template = match res {
+        Ok(Some(template)) => template,
+        Ok(None) => {
+            let estr = format!("There is no report template with id {}.", pdata.id);
+            return ApiError::not_found(estr).into_response();
+        }
+        Err(e) => {
+            log::error!("Error retrieving report template {}: {}", pdata.id, &e);
+            return text_500(Some(format!("Unable to retrieve report template: {}", &e)));
+        }
+    };
+
+    let term = match pdata.term.as_deref().map(str::parse::<Term>) {
+        Some(Ok(term)) => term,
+        Some(Err(e)) => {
+            return respond_bad_request(e);
+        }
+        None if template.kind == report::template_kind(Term::Summer) => Term::Summer,
+        None => Term::Fall,
+    };
+    if report::template_kind(term) != template.kind {
+        return respond_bad_request(format!(
+            "A {:?} template isn't used for {} reports.",
+            &template.kind, &term
+        ));
+    }
+
+    let glob = glob.read().await;
+    if !matches!(glob.users.get(&pdata.uname), Some(User::Student(_))) {
+        return ApiError::not_found(format!("There is no student {:?}.", &pdata.uname))
+            .into_response();
+    }
+
+    let text = match report::preview_report_markup(&pdata.uname, term, &template.body, &glob).await
+    {
+        Ok(text) => text,
+        Err(e) => {
+            return respond_bad_request(format!(
+                "Template {:?} doesn't render for {:?}: {}",
+                &template.label, &pdata.uname, &e
+            ));
+        }
+    };
+    let pdf = match report::render_markdown(text, &glob).await {
+        Ok(pdf) => pdf,
+        Err(e) => {
+            log::error!(
+                "Error rendering preview of report template {} for {:?}: {}",
+                &template.id,
+                &pdata.uname,
+                &e
+            );
+            return text_500(Some(format!("Error generating PDF file: {}", &e)));
+        }
+    };
+
+    let disposition = format!(
+        "attachment; filename=\"preview-{}-{}.pdf\"",
+        &template.id, &pdata.uname
+    );
+    let disposition = match HeaderValue::from_str(&disposition) {
+        Ok(v) => v,
+        Err(_) => HeaderValue::from_static("attachment; filename=\"preview.pdf\""),
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("preview-report-template"),
+            ),
+            (header::CONTENT_TYPE, HeaderValue::from_static("application/pdf")),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        pdf,
+    )
+        .into_response()
+}
+
+/**
+Make a version of a report template the one that gets used for that kind
+of report, or (with a `null` id) go back to the built-in template.
+
+```text
+x-camp-action: activate-report-template
+```
+With a JSON body like
+```json
+{ "kind": "report", "id": 3 }
+```
+Responds like `populate-report-templates`.
+*/
+pub async fn activate(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let adata: ActivateData = match read_body(body, "template kind and id") {
+        Ok(adata) => adata,
+        Err(resp) => {
+            return resp;
+        }
+    };
+    if let Err(resp) = check_kind(&adata.kind) {
+        return resp;
+    }
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .activate_report_template(&adata.kind, adata.id)
+        .await;
+    if let Err(e) = res {
+        log::error!(
+            "Error activating {:?} report template {:?}: {}",
+            &adata.kind,
+            &adata.id,
+            &e
+        );
+        return respond_bad_request(format!("Unable to activate report template: {}", &e));
+    }
+
+    populate(glob).await
+}
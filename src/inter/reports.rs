@@ -0,0 +1,459 @@
+/*!
+Getting finalized report PDFs to students and parents.
+
+Students and parents can only get a report once the Boss has released it
+(see [`releases`](crate::store::ReportRelease)):
+
+  * The Boss API's `release-reports` action releases finalized reports,
+    `withdraw-report` takes one back, and `list-releases` shows what has
+    been released, the link parents can use to download each report, and
+    how often each has been downloaded.
+  * The student JSON API's `released-reports` action lists the student's
+    released reports, and `download-report` sends one. The stored
+    finalized PDF is written for parents, so students instead get their
+    report freshly rendered with only what a student may see (see
+    [`Audience`]).
+  * Anyone with a report's link ("/report/{token}") can download it
+    without logging in (see [`download`]), so the links are only given to
+    the Boss to pass along to parents.
+
+Every download by a student or through a link is recorded, and logged.
+*/
+use std::sync::Arc;
+
+use axum::{
+    extract::Path,
+    http::header::{self, HeaderMap, HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use super::{
+    error_page,
+    extract::{CampHeader, CampStudent, CampTerm},
+    respond_bad_request, text_500, ApiError,
+};
+use crate::{
+    config::Glob,
+    pace::Term,
+    report::{self, Audience},
+    store::ReportRelease,
+    user::{Student, User},
+};
+
+/// A released report, with the link parents can download it from.
+#[derive(Debug, Serialize)]
+struct ReleaseData<'a> {
+    #[serde(flatten)]
+    release: &'a ReportRelease,
+    link: String,
+}
+
+/// The link to the released report with the given `token`.
+fn report_link(uri: &str, token: &str) -> String {
+    format!("{}/report/{}", uri.trim_end_matches('/'), token)
+}
+
+/// The `Content-Disposition` for a student's `term` report. (Unames could
+/// have anything in them, so they're left out of the file name.)
+fn disposition(term: Term) -> HeaderValue {
+    let val = format!(
+        "inline; filename=\"report_{}.pdf\"",
+        term.as_str().to_lowercase()
+    );
+    // `Term` names are ASCII letters.
+    HeaderValue::from_str(&val).unwrap()
+}
+
+fn respond_pdf(action: &'static str, term: Term, doc: Vec<u8>) -> Response {
+    (
+        StatusCode::OK,
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/pdf"),
+            ),
+            (header::CONTENT_DISPOSITION, disposition(term)),
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static(action),
+            ),
+        ],
+        doc,
+    )
+        .into_response()
+}
+
+/// Record a download, logging (but otherwise ignoring) any error, because
+/// the report is more important than the record.
+async fn record(glob: &Glob, uname: &str, term: Term, via: &str) {
+    log::info!("{} report of {:?} downloaded via {}.", &term, uname, via);
+    let res = glob
+        .data()
+        .read()
+        .await
+        .record_report_download(uname, term, via)
+        .await;
+    if let Err(e) = res {
+        log::error!(
+            "Error recording download of {} report of {:?}: {}",
+            &term,
+            uname,
+            &e
+        );
+    }
+}
+
+/// Handler for GET requests to "/report/{token}".
+pub async fn download(
+    Path(token): Path<String>,
+    Extension(glob): Extension<Arc<RwLock<Glob>>>,
+) -> Response {
+    log::trace!("reports::download( [ token ] ) called.");
+
+    let glob = glob.read().await;
+    let res = glob.data().read().await.get_report_by_token(&token).await;
+    match res {
+        Ok(Some((uname, term, doc))) => {
+            record(&glob, &uname, term, "link").await;
+            respond_pdf("download-report", term, doc)
+        }
+        Ok(None) => error_page::respond(StatusCode::NOT_FOUND),
+        Err(e) => {
+            log::error!("Error looking up report token: {}", &e);
+            error_page::respond(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Respond with the released reports of the student named by the
+/// `x-camp-student` header, or of every student if there isn't one.
+async fn respond_releases(suname: Option<&str>, glob: &Glob) -> Response {
+    let res = glob.data().read().await.get_releases(suname).await;
+    let releases = match res {
+        Ok(releases) => releases,
+        Err(e) => {
+            log::error!("Error retrieving report releases: {}", &e);
+            return text_500(Some(format!("Unable to retrieve released reports: {}", &e)));
+        }
+    };
+    let data: Vec<ReleaseData> = releases
+        .iter()
+        .map(|release| ReleaseData {
+            release,
+            link: report_link(&glob.uri, &release.token),
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("list-releases"),
+        )],
+        Json(data),
+    )
+        .into_response()
+}
+
+/// Read the optional `x-camp-student` header, checking that it names a
+/// student.
+#[allow(clippy::result_large_err)]
+fn optional_student(headers: &HeaderMap, glob: &Glob) -> Result<Option<String>, Response> {
+    let suname = CampStudent::from_headers_opt(headers)?.map(|CampStudent(uname)| uname);
+    if let Some(uname) = &suname {
+        if !matches!(glob.users.get(uname), Some(User::Student(_))) {
+            return Err(respond_bad_request(format!(
+                "{:?} is not the user name of a student in the system.",
+                uname
+            )));
+        }
+    }
+    Ok(suname)
+}
+
+/**
+Respond with the released reports, either for all students or, if the
+`x-camp-student` header is present, just for that student.
+
+Headers:
+```text
+x-camp-action: list-releases
+x-camp-student: [ optional uname of student ]
+```
+
+Response body looks like
+```json
+[
+    {
+        "uname": "jsmith", "term": "Fall", "token": "...",
+        "released_by": "boss", "released": "2023-01-20 15:04:11",
+        "downloads": 2, "last_downloaded": "2023-01-21 02:13:40",
+        "link": "https://camp.camelotacademy.org/report/..."
+    },
+    ...
+]
+```
+*/
+pub async fn list(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let suname = match optional_student(headers, &glob) {
+        Ok(suname) => suname,
+        Err(resp) => {
+            return resp;
+        }
+    };
+    respond_releases(suname.as_deref(), &glob).await
+}
+
+/**
+Release finalized reports for the given term: the named student's, if the
+`x-camp-student` header is present, otherwise every student's that hasn't
+been released yet. Responds as to `list-releases` (for all students).
+
+Headers:
+```text
+x-camp-action: release-reports
+x-camp-term: [ one of "Fall", "Spring", "Summer" ]
+x-camp-student: [ optional uname of student ]
+```
+*/
+pub async fn release(buname: &str, headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let term = match CampTerm::from_headers(headers) {
+        Ok(CampTerm(term)) => term,
+        Err(resp) => {
+            return resp;
+        }
+    };
+    let glob = glob.read().await;
+    let suname = match optional_student(headers, &glob) {
+        Ok(suname) => suname,
+        Err(resp) => {
+            return resp;
+        }
+    };
+
+    let unames: Option<Vec<String>> = suname.map(|s| vec![s]);
+    let res = glob
+        .data()
+        .read()
+        .await
+        .release_reports(term, unames.as_deref(), buname)
+        .await;
+    match res {
+        Ok(released) => {
+            log::info!(
+                "{:?} released {} {} reports: {:?}",
+                buname,
+                released.len(),
+                &term,
+                &released
+            );
+            let detail = term.to_string();
+            for suname in released.iter() {
+                glob.log_action(buname, "release-report", suname, Some(&detail))
+                    .await;
+            }
+        }
+        Err(e) => {
+            log::error!("Error releasing {} reports: {}", &term, &e);
+            return text_500(Some(format!("Unable to release reports: {}", &e)));
+        }
+    }
+
+    respond_releases(None, &glob).await
+}
+
+/**
+Withdraw the release of a student's report for the given term, so it can't
+be downloaded (even through its link) until it's released again. Responds
+as to `list-releases` (for all students).
+
+Headers:
+```text
+x-camp-action: withdraw-report
+x-camp-student: [ uname of student ]
+x-camp-term: [ one of "Fall", "Spring", "Summer" ]
+```
+*/
+pub async fn withdraw(buname: &str, headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let suname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => {
+            return resp;
+        }
+    };
+    let term = match CampTerm::from_headers(headers) {
+        Ok(CampTerm(term)) => term,
+        Err(resp) => {
+            return resp;
+        }
+    };
+
+    let glob = glob.read().await;
+    let res = glob
+        .data()
+        .read()
+        .await
+        .withdraw_report(&suname, term)
+        .await;
+    match res {
+        Ok(true) => {
+            log::info!("Release of {} report of {:?} withdrawn.", &term, &suname);
+            glob.log_action(buname, "withdraw-report", &suname, Some(&term.to_string()))
+                .await;
+        }
+        Ok(false) => {
+            return respond_bad_request(format!(
+                "The {} report of {:?} hasn't been released.",
+                &term, &suname
+            ));
+        }
+        Err(e) => {
+            log::error!(
+                "Error withdrawing {} report of {:?}: {}",
+                &term,
+                &suname,
+                &e
+            );
+            return text_500(Some(format!("Unable to withdraw report: {}", &e)));
+        }
+    }
+
+    respond_releases(None, &glob).await
+}
+
+/// One of a student's released reports, as listed for the student.
+#[derive(Debug, Serialize)]
+struct StudentRelease<'a> {
+    term: Term,
+    released: &'a str,
+}
+
+/**
+Respond with which of the student's reports have been released.
+
+Response body looks like
+```json
+[ { "term": "Fall", "released": "2023-01-20 15:04:11" } ]
+```
+*/
+pub(super) async fn student_list(s: &Student, glob: &Glob) -> Response {
+    let res = glob
+        .data()
+        .read()
+        .await
+        .get_releases(Some(&s.base.uname))
+        .await;
+    let releases = match res {
+        Ok(releases) => releases,
+        Err(e) => {
+            log::error!(
+                "Error retrieving released reports of {:?}: {}",
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+    let data: Vec<StudentRelease> = releases
+        .iter()
+        .map(|r| StudentRelease {
+            term: r.term,
+            released: &r.released,
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("released-reports"),
+        )],
+        Json(data),
+    )
+        .into_response()
+}
+
+/// Send the student their report for the term in the `x-camp-term`
+/// header, if it has been released, rendered for [`Audience::Student`].
+pub(super) async fn student_download(s: &Student, headers: &HeaderMap, glob: &Glob) -> Response {
+    let term = match CampTerm::from_headers(headers) {
+        Ok(CampTerm(term)) => term,
+        Err(resp) => {
+            return resp;
+        }
+    };
+
+    let res = glob
+        .data()
+        .read()
+        .await
+        .get_released_report(&s.base.uname, term)
+        .await;
+    match res {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return ApiError::not_found(format!("Your {} report isn't available yet.", &term))
+                .into_response();
+        }
+        Err(e) => {
+            log::error!(
+                "Error retrieving {} report of {:?}: {}",
+                &term,
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    }
+
+    let text =
+        match report::generate_report_markup(&s.base.uname, term, Audience::Student, glob).await {
+            Ok(text) => text,
+            Err(e) => {
+                log::error!(
+                    "Error generating student {} report of {:?}: {}",
+                    &term,
+                    &s.base.uname,
+                    &e
+                );
+                return text_500(None);
+            }
+        };
+    let doc = match report::render_markdown(text, glob).await {
+        Ok(doc) => doc,
+        Err(e) => {
+            log::error!(
+                "Error rendering student {} report of {:?}: {}",
+                &term,
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+
+    record(glob, &s.base.uname, term, "student").await;
+    respond_pdf("download-report", term, doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_links() {
+        assert_eq!(
+            report_link("https://camp.camelotacademy.org/", "abc123"),
+            "https://camp.camelotacademy.org/report/abc123"
+        );
+        assert_eq!(
+            disposition(Term::Spring),
+            "inline; filename=\"report_spring.pdf\""
+        );
+    }
+}
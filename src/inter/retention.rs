@@ -0,0 +1,145 @@
+/*!
+The daily cleanup of data that has been kept long enough.
+
+A background task (started with [`run_daily`]) calls
+[`Glob::prune`](crate::config::Glob::prune) when the server starts and then
+every day at the hour set in the `[retention]` table of the config file
+(see [`Retention`](crate::store::Retention)), logging what it deleted.
+
+The Admin API's `retention-stats` action reports the settings and what the
+cleanups have deleted since the server started (see [`respond`]).
+*/
+use std::sync::Arc;
+
+use axum::{
+    http::header::{HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::config::Glob;
+
+const SECS_PER_HOUR: u64 = 60 * 60;
+const SECS_PER_DAY: u64 = 24 * SECS_PER_HOUR;
+
+/// Number of seconds from `now` (in seconds since the Unix epoch) until
+/// the next `hour` o'clock UTC.
+pub(super) fn secs_until_hour(now: u64, hour: u8) -> u64 {
+    let target = (hour as u64 % 24) * SECS_PER_HOUR;
+    let into_day = now % SECS_PER_DAY;
+    match (target + SECS_PER_DAY - into_day) % SECS_PER_DAY {
+        0 => SECS_PER_DAY,
+        n => n,
+    }
+}
+
+/// Run a cleanup and log what it did.
+async fn prune(glob: &Glob) {
+    match glob.prune().await {
+        Ok(p) => log::info!(
+            "Pruned {} drafts, {} failed requests, {} deleted goals, {} departures, \
+            {} webhook deliveries, and {} keys.",
+            &p.drafts,
+            &p.failed_requests,
+            &p.deleted_goals,
+            &p.departures,
+            &p.webhook_deliveries,
+            &p.keys
+        ),
+        Err(e) => log::error!("Error pruning expired data: {}", &e),
+    }
+}
+
+/**
+Prune expired data now and then every day at the configured hour, forever.
+
+This is meant to be `tokio::spawn()`ed when the server starts up.
+*/
+pub async fn run_daily(glob: Arc<RwLock<Glob>>) {
+    prune(&*glob.read().await).await;
+    loop {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let wait = secs_until_hour(now, glob.read().await.retention.hour);
+        log::info!("Next cleanup in {} seconds.", &wait);
+        tokio::time::sleep(std::time::Duration::from_secs(wait)).await;
+
+        prune(&*glob.read().await).await;
+    }
+}
+
+/**
+Respond with the retention settings and what cleanups have deleted.
+
+Header:
+```text
+x-camp-action: retention-stats
+```
+
+Response body looks like
+```json
+{
+    "retention": {
+        "drafts": 0,
+        "failed_requests": 90,
+        "deleted_goals": 0,
+        "departures": 30,
+        "webhook_deliveries": 30,
+        "hour": 8
+    },
+    "stats": {
+        "runs": 3,
+        "last_run": "2022-10-05 8:00:00.0 +00:00:00",
+        "last": {
+            "drafts": 0, "failed_requests": 2, "deleted_goals": 0, "departures": 1,
+            "webhook_deliveries": 12, "keys": 4
+        },
+        "total": {
+            "drafts": 0, "failed_requests": 350, "deleted_goals": 0, "departures": 6,
+            "webhook_deliveries": 40, "keys": 19
+        },
+        "failed": 0,
+        "last_error": null
+    }
+}
+```
+*/
+pub async fn respond(glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let stats = glob.prune_stats.lock().unwrap().clone();
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("retention-stats"),
+        )],
+        Json(json!({
+            "retention": &glob.retention,
+            "stats": stats,
+        })),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_hour() {
+        assert_eq!(secs_until_hour(0, 8), 8 * SECS_PER_HOUR);
+        assert_eq!(secs_until_hour(8 * SECS_PER_HOUR, 8), SECS_PER_DAY);
+        assert_eq!(secs_until_hour(9 * SECS_PER_HOUR, 8), 23 * SECS_PER_HOUR);
+        assert_eq!(
+            secs_until_hour(SECS_PER_DAY + 7 * SECS_PER_HOUR + 30 * 60, 8),
+            30 * 60
+        );
+        assert_eq!(secs_until_hour(0, 24), SECS_PER_DAY);
+    }
+}
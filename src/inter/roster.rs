@@ -0,0 +1,207 @@
+/*!
+Fetching the SIS roster every night, and the Admin API actions for
+reviewing and applying it (see [`roster`](crate::roster)).
+
+  * `roster-preview` shows how the most recently fetched roster differs
+    from the current students.
+  * `roster-fetch` fetches the roster right away, then does the same.
+  * `roster-apply` applies the changes an Admin has reviewed.
+*/
+use std::sync::Arc;
+
+use axum::{
+    http::header::{HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use super::{respond_bad_request, retention::secs_until_hour, text_500};
+use crate::config::Glob;
+
+/// Fetch the roster and log how it differs from the current students.
+async fn fetch(glob: &Glob) {
+    match glob.fetch_roster().await {
+        Ok(d) => log::info!(
+            "Fetched roster: {} adds, {} moves, {} withdrawals, {} problems awaiting review.",
+            d.adds.len(),
+            d.moves.len(),
+            d.withdrawals.len(),
+            d.problems.len()
+        ),
+        Err(e) => log::error!("Error fetching roster: {}", &e),
+    }
+}
+
+/**
+Fetch the roster every day at `hour` o'clock UTC, forever.
+
+This is meant to be `tokio::spawn()`ed when the server starts up (if roster
+sync is configured).
+*/
+pub async fn run_nightly(glob: Arc<RwLock<Glob>>, hour: u8) {
+    loop {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let wait = secs_until_hour(now, hour);
+        log::info!("Next roster fetch in {} seconds.", &wait);
+        tokio::time::sleep(std::time::Duration::from_secs(wait)).await;
+
+        fetch(&*glob.read().await).await;
+    }
+}
+
+fn respond_preview(glob: &Glob) -> Response {
+    let state = glob.roster.lock().unwrap().clone();
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("roster-preview"),
+        )],
+        Json(json!({
+            "configured": glob.roster_sync.is_some(),
+            "state": state,
+            "diff": glob.roster_diff(),
+        })),
+    )
+        .into_response()
+}
+
+/**
+Respond with how the most recently fetched roster differs from the current
+students.
+
+Header:
+```text
+x-camp-action: roster-preview
+```
+
+Response body looks like
+```json
+{
+    "configured": true,
+    "state": {
+        "fetched": "2022-10-05 6:00:01.0 +00:00:00",
+        "last_error": null,
+        "applied": null
+    },
+    "diff": {
+        "adds": [ { "uname": "jsmith", "last": "Smith", "rest": "Jane", "teacher": "bob" } ],
+        "moves": [ { "uname": "mo", "last": "Jones", "rest": "Mo", "from": "bob", "to": "sue" } ],
+        "withdrawals": [ { "uname": "kdoe", "last": "Doe", "rest": "Kim", "teacher": "sue" } ],
+        "problems": [ "\"owl\" is assigned to \"nobody\", who is not a Teacher." ],
+        "token": "1f3a9c0e5b7d2468"
+    }
+}
+```
+`diff` is `null` if no roster has been fetched since the server started.
+*/
+pub async fn preview(glob: Arc<RwLock<Glob>>) -> Response {
+    respond_preview(&*glob.read().await)
+}
+
+/**
+Fetch the roster now, and respond as to `roster-preview`.
+
+Header:
+```text
+x-camp-action: roster-fetch
+```
+*/
+pub async fn fetch_now(glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    if glob.roster_sync.is_none() {
+        return respond_bad_request("Roster sync is not configured.".to_owned());
+    }
+    if let Err(e) = glob.fetch_roster().await {
+        log::error!("Error fetching roster: {}", &e);
+        return text_500(Some(format!("Unable to fetch roster: {}", &e)));
+    }
+
+    respond_preview(&glob)
+}
+
+/// Body of a `roster-apply` request.
+#[derive(Debug, Deserialize)]
+struct ApplyData {
+    token: String,
+    #[serde(default)]
+    withdraw: bool,
+}
+
+/**
+Apply the changes from the most recently fetched roster.
+
+Header:
+```text
+x-camp-action: roster-apply
+```
+With a JSON body like
+```json
+{ "token": "1f3a9c0e5b7d2468", "withdraw": true }
+```
+where `token` is the one from the `roster-preview` that was reviewed, and
+`withdraw` says whether to delete the students who have withdrawn (default
+`false`).
+
+Response body looks like
+```json
+{
+    "added": ["jsmith"],
+    "moved": ["mo"],
+    "withdrawn": ["kdoe"],
+    "failed": [ { "uname": "q&a", "error": "..." } ]
+}
+```
+*/
+pub async fn apply(auname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires a JSON body with a token from a roster preview.".to_owned(),
+            );
+        }
+    };
+    let adata: ApplyData = match serde_json::from_str(&body) {
+        Ok(adata) => adata,
+        Err(e) => {
+            return respond_bad_request(format!("Unable to read roster approval: {}", &e));
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .apply_roster(auname, &adata.token, adata.withdraw)
+        .await;
+    // Even a partly failed roster may have changed some users.
+    if let Err(e) = glob.write().await.refresh_users().await {
+        log::error!("Error refreshing user hash from database: {}", &e);
+        return text_500(Some("Unable to reread users from database.".to_owned()));
+    }
+    let applied = match res {
+        Ok(applied) => applied,
+        Err(e) => {
+            log::error!("Error applying roster: {}", &e);
+            return respond_bad_request(e.to_string());
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("roster-apply"),
+        )],
+        Json(applied),
+    )
+        .into_response()
+}
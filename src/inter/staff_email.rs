@@ -0,0 +1,392 @@
+/*!
+Email from the Boss to Teachers.
+
+The Boss API's `compose-teacher-email` action (see [`compose`]) sends the
+frontend a starting subject and text, rendered from the `boss_teacher_text`
+(`.txt`) template, along with a summary of each Teacher's class (see
+[`TeacherSummary`]) to choose recipients from. The `email-teachers` action
+(see [`send`]) sends the edited email to the chosen Teachers.
+
+The subject and text are themselves templates; the fields of the
+recipient's `TeacherSummary` (like `{{ name }}` or `{{ n_behind }}`) are
+filled in separately for each Teacher before the email is wrapped in an
+[`Email`](crate::email::Email) by the `boss_teacher_email` (`.json`)
+template. Replies go to the Boss.
+*/
+use std::sync::Arc;
+
+use axum::{
+    http::header::{HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use super::{
+    render_json_template, render_raw_string, render_raw_template, respond_bad_request,
+    send_rendered_email, text_500,
+};
+use crate::{
+    config::Glob,
+    user::{Role, User},
+    MiniString,
+};
+
+/// What the subject of a Teacher email starts out as.
+const DEFAULT_SUBJECT: &str = "A note about your CAMP students";
+
+/// A Teacher who might be emailed, and how their class is doing. These are
+/// also the fields that can be used in an email's subject and text.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TeacherSummary {
+    pub uname: String,
+    pub name: String,
+    pub email: String,
+    pub n_students: usize,
+    /// How many of their students are behind pace.
+    pub n_behind: usize,
+    /// The lag (see [`Pace::lag`](crate::pace::Pace::lag)) of their
+    /// furthest-behind student, or 0 if they have no students.
+    pub worst_lag: f32,
+}
+
+/// Which Teachers an email goes to.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Recipients {
+    /// Every Teacher.
+    All,
+    /// Teachers with a student at least this far behind (as a positive
+    /// fraction of the student's total weight).
+    Lag(f32),
+    /// The Teachers with these unames.
+    Unames(Vec<String>),
+}
+
+/// Body of an `email-teachers` request.
+#[derive(Debug, Deserialize)]
+struct TeacherEmail {
+    subject: String,
+    text: String,
+    recipients: Recipients,
+}
+
+/// Response to a `compose-teacher-email` request.
+#[derive(Debug, Serialize)]
+struct Draft {
+    subject: &'static str,
+    text: String,
+    teachers: Vec<TeacherSummary>,
+}
+
+/// Data for rendering the `boss_teacher_email` template.
+#[derive(Debug, Serialize)]
+struct TeacherEmailData<'a> {
+    email: &'a str,
+    name: &'a str,
+    reply_to: &'a str,
+    subject: &'a str,
+    text: &'a str,
+}
+
+/// An email that couldn't be sent.
+#[derive(Debug, PartialEq, Serialize)]
+struct Failure {
+    uname: String,
+    error: String,
+}
+
+/// Response to an `email-teachers` request.
+#[derive(Debug, Default, Serialize)]
+struct Sent {
+    sent: Vec<String>,
+    failed: Vec<Failure>,
+}
+
+/// Summarize every Teacher's class, sorted by name.
+async fn summarize(glob: &Glob) -> Result<Vec<TeacherSummary>, String> {
+    let mut teachers: Vec<TeacherSummary> = Vec::new();
+    for u in glob.users_with_role(Role::Teacher) {
+        let t = match u {
+            User::Teacher(t) => t,
+            _ => continue,
+        };
+        let paces = glob.get_paces_by_teacher(&t.base.uname).await.map_err(|e| {
+            format!("Error retrieving paces for {:?}: {}", &t.base.uname, &e)
+        })?;
+
+        teachers.push(TeacherSummary {
+            uname: t.base.uname.clone(),
+            name: t.name.clone(),
+            email: t.base.email.clone(),
+            n_students: paces.len(),
+            n_behind: paces.iter().filter(|p| p.done_weight < p.due_weight).count(),
+            worst_lag: paces.iter().map(|p| p.lag()).fold(0.0, f32::min),
+        });
+    }
+    teachers.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(teachers)
+}
+
+/// Pick out the `teachers` an email to `recipients` should go to.
+fn select<'a>(
+    recipients: &Recipients,
+    teachers: &'a [TeacherSummary],
+) -> Result<Vec<&'a TeacherSummary>, String> {
+    let chosen: Vec<&TeacherSummary> = match recipients {
+        Recipients::All => teachers.iter().collect(),
+        Recipients::Lag(threshold) => {
+            if threshold.is_nan() || *threshold < 0.0 {
+                return Err(format!(
+                    "A lag threshold must be a positive fraction, not {}.",
+                    threshold
+                ));
+            }
+            teachers
+                .iter()
+                .filter(|t| t.n_students > 0 && t.worst_lag <= -threshold)
+                .collect()
+        }
+        Recipients::Unames(unames) => unames
+            .iter()
+            .map(|uname| {
+                teachers
+                    .iter()
+                    .find(|t| &t.uname == uname)
+                    .ok_or_else(|| format!("{:?} is not the user name of a Teacher.", uname))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    if chosen.is_empty() {
+        return Err("No Teachers match; nothing was sent.".to_owned());
+    }
+    Ok(chosen)
+}
+
+/// Render the email from `reply_to` for Teacher `t`.
+fn render_one(
+    t: &TeacherSummary,
+    subject: &str,
+    text: &str,
+    reply_to: &str,
+) -> Result<String, String> {
+    let subject = render_raw_string(subject, t)?;
+    let text = render_raw_string(text, t)?;
+    let data = TeacherEmailData {
+        email: &t.email,
+        name: &t.name,
+        reply_to,
+        subject: subject.trim(),
+        text: &text,
+    };
+    render_json_template("boss_teacher_email", &data)
+}
+
+/**
+Respond with a starting point for an email to Teachers.
+
+Header:
+```text
+x-camp-action: compose-teacher-email
+```
+
+Response body looks like
+```json
+{
+    "subject": "A note about your CAMP students",
+    "text": "{{ name }},\n\n...",
+    "teachers": [
+        {
+            "uname": "bob", "name": "Mr. Bob", "email": "bob@school.org",
+            "n_students": 12, "n_behind": 3, "worst_lag": -0.18
+        },
+        ...
+    ]
+}
+```
+*/
+pub async fn compose(glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+
+    let teachers = match summarize(&glob).await {
+        Ok(teachers) => teachers,
+        Err(e) => {
+            log::error!("Error summarizing Teachers' classes: {}", &e);
+            return text_500(Some(e));
+        }
+    };
+    let data = serde_json::json!({ "service_uri": &glob.uri });
+    let text = match render_raw_template("boss_teacher_text", &data) {
+        Ok(text) => text,
+        Err(e) => {
+            log::error!("Error rendering Teacher email text: {}", &e);
+            return text_500(Some(format!("Error generating email: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("compose-teacher-email"),
+        )],
+        Json(Draft {
+            subject: DEFAULT_SUBJECT,
+            text,
+            teachers,
+        }),
+    )
+        .into_response()
+}
+
+/**
+Send an email from the Boss (whose address is `reply_to`) to some Teachers.
+
+Header:
+```text
+x-camp-action: email-teachers
+```
+With a JSON body like
+```json
+{
+    "subject": "Checking in",
+    "text": "{{ name }},\n\n{{ n_behind }} of your students are behind...",
+    "recipients": { "lag": 0.1 }
+}
+```
+where `recipients` is `"all"`, `{ "lag": x }` (every Teacher with a student
+behind by at least the fraction `x`), or `{ "unames": ["bob", ...] }`.
+
+Response body looks like
+```json
+{
+    "sent": ["bob"],
+    "failed": [ { "uname": "jane", "error": "..." } ]
+}
+```
+A problem with the subject or text (like a mistyped `{{`) keeps any email
+from being sent.
+*/
+pub async fn send(reply_to: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request must have application/json body with email details.".to_owned(),
+            );
+        }
+    };
+    let req: TeacherEmail = match serde_json::from_str(&body) {
+        Ok(req) => req,
+        Err(e) => {
+            return respond_bad_request(format!("Unable to read Teacher email: {}", &e));
+        }
+    };
+
+    let glob = glob.read().await;
+    let teachers = match summarize(&glob).await {
+        Ok(teachers) => teachers,
+        Err(e) => {
+            log::error!("Error summarizing Teachers' classes: {}", &e);
+            return text_500(Some(e));
+        }
+    };
+    let chosen = match select(&req.recipients, &teachers) {
+        Ok(chosen) => chosen,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    let mut emails: Vec<(&str, String)> = Vec::with_capacity(chosen.len());
+    for t in chosen.iter() {
+        match render_one(t, &req.subject, &req.text, reply_to) {
+            Ok(email) => emails.push((&t.uname, email)),
+            Err(e) => {
+                return respond_bad_request(format!("Unable to write email: {}", &e));
+            }
+        }
+    }
+
+    let mut sends = FuturesUnordered::new();
+    for (uname, email) in emails.into_iter() {
+        let glob = &glob;
+        sends.push(async move {
+            let res = send_rendered_email(email, glob, MiniString::from(uname)).await;
+            (uname, res)
+        });
+    }
+    let mut results = Sent::default();
+    while let Some((uname, res)) = sends.next().await {
+        match res {
+            Ok(()) => results.sent.push(uname.to_owned()),
+            Err(e) => {
+                log::error!("Error emailing Teacher {:?}: {}", uname, &e);
+                results.failed.push(Failure {
+                    uname: uname.to_owned(),
+                    error: e,
+                });
+            }
+        }
+    }
+    results.sent.sort();
+    log::info!(
+        "Emailed {} Teachers ({} failed).",
+        results.sent.len(),
+        results.failed.len()
+    );
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("email-teachers"),
+        )],
+        Json(results),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(uname: &str, n_students: usize, worst_lag: f32) -> TeacherSummary {
+        TeacherSummary {
+            uname: uname.to_owned(),
+            name: uname.to_uppercase(),
+            email: format!("{}@camelotacademy.org", uname),
+            n_students,
+            n_behind: if worst_lag < 0.0 { 1 } else { 0 },
+            worst_lag,
+        }
+    }
+
+    #[test]
+    fn teacher_email_recipients() {
+        let teachers = vec![
+            summary("adams", 8, -0.25),
+            summary("berro", 10, -0.05),
+            summary("cole", 0, 0.0),
+        ];
+        let unames = |r: &str| -> Result<Vec<String>, String> {
+            let r: Recipients = serde_json::from_str(r).unwrap();
+            select(&r, &teachers).map(|v| v.iter().map(|t| t.uname.clone()).collect())
+        };
+
+        assert_eq!(unames(r#""all""#).unwrap(), ["adams", "berro", "cole"]);
+        assert_eq!(unames(r#"{"lag": 0.1}"#).unwrap(), ["adams"]);
+        assert_eq!(unames(r#"{"lag": 0}"#).unwrap(), ["adams", "berro"]);
+        assert!(unames(r#"{"lag": 0.5}"#).is_err());
+        assert!(unames(r#"{"lag": -0.1}"#).is_err());
+        assert_eq!(unames(r#"{"unames": ["cole", "adams"]}"#).unwrap(), ["cole", "adams"]);
+        assert!(unames(r#"{"unames": ["adams", "frog"]}"#).is_err());
+        assert!(unames(r#"{"unames": []}"#).is_err());
+        assert!(serde_json::from_str::<Recipients>(r#""some""#).is_err());
+    }
+}
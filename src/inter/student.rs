@@ -1,17 +1,47 @@
 /*!
-Displaying individual student calendars.
+Displaying individual student calendars, and the compact JSON API for
+student-facing clients (like a mobile app).
+
+There's also a basic view (see [`basic`]) at `/student/basic`, for
+students whose devices can't handle much: plain HTML forms, no scripts,
+and no styles, from which students can ask their Teachers to mark goals
+done.
+
+Unlike the server-rendered view, the JSON API requires key authentication.
+A client gets a key by `POST`ing the usual login form to `/student/key`,
+then makes requests to `/student` with the `x-camp-uname`, `x-camp-key`,
+and `x-camp-action` headers. Dates in JSON responses are always
+`YYYY-MM-DD`, regardless of the configured display format.
 */
-use time::{format_description::FormatItem, macros::format_description, Date};
+use std::{borrow::Cow, collections::HashSet};
+
+use axum::{
+    extract::{Extension, Form},
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    response::{IntoResponse, Response},
+    Json,
+};
+use time::{Date, Duration};
 
 use crate::{
-    pace::{GoalDisplay, GoalStatus, PaceDisplay, RowDisplay, SummaryDisplay},
-    user::Student,
-    MiniString, SMALLSTORE,
+    anon,
+    format_date,
+    hist::HistEntry,
+    pace::{
+        AcademicCalendar, Burndown, GoalDisplay, GoalStatus, PaceDisplay, RowDisplay,
+        SummaryDisplay, WaivedDisplay,
+    },
+    report::Audience,
+    user::{Role, Student},
+    DateStyle, MiniString, SMALLSTORE,
 };
 
 use super::*;
 
-const DATE_FMT: &[FormatItem] = format_description!("[month repr:short] [day]");
+/// Number of items in a page of results if the request doesn't specify.
+const DEFAULT_PAGE_LIMIT: usize = 50;
+/// Largest page of results a request may ask for.
+const MAX_PAGE_LIMIT: usize = 200;
 
 /// The data required to render the `"student_goal_row"` template when
 /// generating the student's view.
@@ -28,6 +58,8 @@ struct GoalData<'a> {
     done_from: MiniString<SMALLSTORE>,
     tries: Option<i16>,
     score: Option<i32>,
+    /// How far along (in percent) the student is with an unfinished goal.
+    progress: Option<i16>,
     goal_class: &'a str,
 }
 
@@ -39,10 +71,15 @@ struct SummaryData<'a> {
     score: &'a str,
 }
 
-/// Write the display data for a single goal to a buffer of bytes.
+/// Gather the display data for a single goal.
 ///
-/// Used in generating the student's view.
-fn write_goal(buff: &mut Vec<u8>, g: &GoalDisplay, today: &Date) -> Result<(), String> {
+/// Used in generating the student's views.
+fn goal_data<'a>(
+    g: &'a GoalDisplay,
+    style: DateStyle,
+    cal: &AcademicCalendar,
+    today: &Date,
+) -> Result<GoalData<'a>, String> {
     let ri = match (g.rev, g.inc) {
         (false, false) => "",
         (true, false) => " R*",
@@ -56,8 +93,7 @@ fn write_goal(buff: &mut Vec<u8>, g: &GoalDisplay, today: &Date) -> Result<(), S
     let mut done_from: MiniString<SMALLSTORE> = MiniString::new();
 
     if let Some(d) = &g.due {
-        d.format_into(&mut due, &DATE_FMT)
-            .map_err(|e| format!("Failed to format date {:?}: {}", d, &e))?;
+        due = format_date(style, d)?;
         match (*d - *today).whole_days() {
             i @ 2..=i64::MAX => {
                 write!(&mut due_from, "in {} days", i).map_err(|e| e.to_string())?;
@@ -78,12 +114,11 @@ fn write_goal(buff: &mut Vec<u8>, g: &GoalDisplay, today: &Date) -> Result<(), S
     }
 
     if let Some(n) = &g.done {
-        n.format_into(&mut done, &DATE_FMT)
-            .map_err(|e| format!("Failed to format date {:?}: {}", n, &e))?;
+        done = format_date(style, n)?;
     }
 
     if let (Some(d), Some(n)) = (&g.due, &g.done) {
-        match (*d - *n).whole_days() {
+        match -cal.lateness(d, n) {
             i @ 2..=i64::MAX => {
                 write!(&mut done_from, "{} days early", &i).map_err(|e| e.to_string())?;
             }
@@ -111,10 +146,10 @@ fn write_goal(buff: &mut Vec<u8>, g: &GoalDisplay, today: &Date) -> Result<(), S
         GoalStatus::Yet => "yet",
     };
 
-    let data = GoalData {
+    Ok(GoalData {
         course: g.course,
         book: g.book,
-        chapter: g.title,
+        chapter: &g.title,
         subject: g.subject.unwrap_or(""),
         ri,
         due,
@@ -123,9 +158,22 @@ fn write_goal(buff: &mut Vec<u8>, g: &GoalDisplay, today: &Date) -> Result<(), S
         done_from,
         tries: g.tries,
         score,
+        progress: g.progress,
         goal_class,
-    };
+    })
+}
 
+/// Write the display data for a single goal to a buffer of bytes.
+///
+/// Used in generating the student's view.
+fn write_goal(
+    buff: &mut Vec<u8>,
+    g: &GoalDisplay,
+    style: DateStyle,
+    cal: &AcademicCalendar,
+    today: &Date,
+) -> Result<(), String> {
+    let data = goal_data(g, style, cal, today)?;
     write_template("student_goal_row", &data, buff)
         .map_err(|e| format!("Error writing goal {:?}: {}", g, &e))
 }
@@ -170,6 +218,9 @@ pub async fn login(s: Student, form: LoginData, glob: Arc<RwLock<Glob>>) -> Resp
         Ok(AuthResult::BadPassword) => {
             return respond_bad_password(&s.base.uname);
         }
+        Ok(AuthResult::Locked(secs)) => {
+            return respond_locked_out(&s.base.uname, secs);
+        }
         Ok(x) => {
             log::warn!(
                 "auth::Db::check_password( {:?}, {:?}, {:?} ) returned {:?}, which shouldn't happen.",
@@ -191,7 +242,7 @@ pub async fn login(s: Student, form: LoginData, glob: Arc<RwLock<Glob>>) -> Resp
         }
     };
 
-    let pd = match PaceDisplay::from(&p, &glob) {
+    let mut pd = match PaceDisplay::from(&p, &glob) {
         Ok(pd) => pd,
         Err(e) => {
             log::error!(
@@ -203,6 +254,9 @@ pub async fn login(s: Student, form: LoginData, glob: Arc<RwLock<Glob>>) -> Resp
             return html_500();
         }
     };
+    if !glob.student_scores {
+        pd.hide_scores();
+    }
 
     let today = crate::now();
 
@@ -211,11 +265,19 @@ pub async fn login(s: Student, form: LoginData, glob: Arc<RwLock<Glob>>) -> Resp
     for row_display in pd.rows.iter() {
         match row_display {
             RowDisplay::Goal(g) => {
-                if let Err(e) = write_goal(&mut goals_buff, g, &today) {
+                if let Err(e) =
+                    write_goal(&mut goals_buff, g, glob.date_style, &glob.calendar, &today)
+                {
                     log::error!("Error writing goal: {}\ndata: {:?}", &e, g);
                     return html_500();
                 }
             }
+            RowDisplay::Waived(w) => {
+                if let Err(e) = write_template("waived_row", w, &mut goals_buff) {
+                    log::error!("Error writing waived chapter: {}\ndata: {:?}", &e, w);
+                    return html_500();
+                }
+            }
             RowDisplay::Summary(s) => {
                 if let Err(e) = write_summary(&mut goals_buff, s) {
                     log::error!("Error writing summary line: {}\ndata: {:?}", &e, s);
@@ -244,18 +306,842 @@ pub async fn login(s: Student, form: LoginData, glob: Arc<RwLock<Glob>>) -> Resp
         ""
     };
 
+    // This template isn't HTML-escaped, so the messages have to be.
+    let announcements: Vec<String> = announce::active(Role::Student, &glob)
+        .await
+        .iter()
+        .map(|a| handlebars::html_escape(&a.message))
+        .collect();
+
     let data = json!({
-        "name": format!("{} {}", pd.rest, pd.last),
+        "announcements": announcements,
+        "name": format!("{} {}", anon::rest(pd.rest), anon::last(pd.last)),
         "uname": pd.uname,
-        "teacher": pd.teacher,
-        "temail":  pd.temail,
+        "teacher": anon::name(pd.teacher),
+        "temail":  anon::email(pd.temail),
         "n_done": pd.n_done,
         "n_due": pd.n_due,
         "n_total": pd.n_scheduled,
         "rows": rows,
         "rev_foot": rev_foot,
         "inc_foot": inc_foot,
+        "year": glob.current_year().map(|y| handlebars::html_escape(&y.label)),
     });
 
     serve_raw_template(StatusCode::OK, "student", &data, vec![])
 }
+
+/// Form data `POST`ed to the basic student view (see [`basic`]).
+#[derive(Debug, Deserialize)]
+pub struct BasicForm {
+    uname: String,
+    /// From the login form.
+    password: Option<String>,
+    /// From the forms in the view itself, which carry the key issued at
+    /// login.
+    key: Option<String>,
+    /// `id` of a goal the student says they've finished.
+    goal: Option<i64>,
+    /// Present if the student is logging out.
+    logout: Option<String>,
+}
+
+/// One row of the goal table in the basic student view; exactly one of
+/// `goal`, `waived`, and `summary` is set.
+#[derive(Debug, Serialize)]
+struct BasicRow<'a> {
+    goal: Option<GoalData<'a>>,
+    /// `id` of the goal, if it isn't done (and so can be requested).
+    id: Option<i64>,
+    /// Whether the student has already asked for the goal to be marked done.
+    requested: bool,
+    waived: Option<&'a WaivedDisplay<'a>>,
+    summary: Option<SummaryData<'a>>,
+}
+
+/// Respond with the basic student view's login form, along with `error`,
+/// if there is one.
+pub(super) fn basic_login_form(code: StatusCode, error: Option<&str>) -> Response {
+    serve_template(code, "student_basic", &json!({ "error": error }), vec![])
+}
+
+/// Serve the login form for the basic student view.
+pub async fn basic_login() -> Response {
+    basic_login_form(StatusCode::OK, None)
+}
+
+/**
+The basic student view.
+
+The login form `POST`s the student's `uname` and `password`; if they check
+out, the response is the student's pace table, in which each unfinished
+goal has a form for asking their Teacher to mark it done. Those forms `POST`
+back here with the `uname`, the key issued at login, and the `goal` id, and
+get the (updated) table in response. A form with a `logout` field revokes
+the key instead.
+*/
+pub async fn basic(
+    Form(form): Form<BasicForm>,
+    Extension(glob): Extension<Arc<RwLock<Glob>>>,
+) -> Response {
+    log::trace!("student::basic( {:?}, [ Glob ] ) called.", &form.uname);
+
+    let glob = glob.read().await;
+    let s = match glob.users.get(&form.uname) {
+        Some(User::Student(s)) => s,
+        _ => {
+            if let Some(password) = &form.password {
+                glob.auth()
+                    .read()
+                    .await
+                    .dummy_check(&form.uname, password)
+                    .await;
+            }
+            return basic_login_form(
+                StatusCode::UNAUTHORIZED,
+                Some("Invalid username/password combination."),
+            );
+        }
+    };
+
+    let res = {
+        let auth = glob.auth();
+        let auth = auth.read().await;
+        match (&form.password, &form.key) {
+            (Some(password), _) => {
+                auth.check_password_and_issue_key(&s.base.uname, password, &s.base.salt)
+                    .await
+            }
+            (None, Some(key)) => auth.check_key(&s.base.uname, key).await,
+            (None, None) => {
+                return basic_login_form(StatusCode::BAD_REQUEST, Some("Please log in."));
+            }
+        }
+    };
+    let key = match res {
+        Ok(AuthResult::Key(k)) => k,
+        Ok(AuthResult::Ok) => form.key.clone().unwrap_or_default(),
+        Ok(AuthResult::InvalidKey) => {
+            return basic_login_form(
+                StatusCode::UNAUTHORIZED,
+                Some("You have been logged out; please log in again."),
+            );
+        }
+        // Locked-out students are told the same thing as those with bad
+        // passwords (see `respond_locked_out`).
+        Ok(_) => {
+            return basic_login_form(
+                StatusCode::UNAUTHORIZED,
+                Some("Invalid username/password combination."),
+            );
+        }
+        Err(e) => {
+            log::error!(
+                "Error authenticating {:?} for the basic student view: {}",
+                &s.base.uname,
+                &e
+            );
+            return html_500();
+        }
+    };
+
+    if form.logout.is_some() {
+        let res = glob
+            .auth()
+            .read()
+            .await
+            .revoke_keys(&s.base.uname, Some(&key))
+            .await;
+        if let Err(e) = res {
+            log::error!("Error revoking key of {:?}: {}", &s.base.uname, &e);
+            return html_500();
+        }
+        let data = json!({ "notice": "You have logged out." });
+        return serve_template(StatusCode::OK, "student_basic", &data, vec![]);
+    }
+
+    let mut message: Option<&str> = None;
+    if let Some(id) = form.goal {
+        let res = glob
+            .data()
+            .read()
+            .await
+            .request_completion(&s.base.uname, id)
+            .await;
+        message = match res {
+            Ok(true) => Some("Your teacher has been asked to mark that goal done."),
+            Ok(false) => Some("That goal is already done, or already waiting for your teacher."),
+            Err(e) => {
+                log::error!(
+                    "Error recording completion request from {:?} for goal {}: {}",
+                    &s.base.uname,
+                    &id,
+                    &e
+                );
+                return html_500();
+            }
+        };
+    }
+
+    let p = match glob.get_pace_by_student(&s.base.uname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!(
+                "Glob::get_pace_by_student( {:?} ) error: {}",
+                &s.base.uname,
+                &e
+            );
+            return html_500();
+        }
+    };
+    let mut pd = match PaceDisplay::from(&p, &glob) {
+        Ok(pd) => pd,
+        Err(e) => {
+            log::error!(
+                "PaceDisplay::from( [ Pace {:?} ] ) error: {}",
+                &s.base.uname,
+                &e
+            );
+            return html_500();
+        }
+    };
+    if !glob.student_scores {
+        pd.hide_scores();
+    }
+
+    let requested: HashSet<i64> = p
+        .goals
+        .iter()
+        .filter(|g| g.requested)
+        .map(|g| g.id)
+        .collect();
+    let today = crate::now();
+
+    let mut rows: Vec<BasicRow> = Vec::with_capacity(pd.rows.len());
+    for row_display in pd.rows.iter() {
+        let row = match row_display {
+            RowDisplay::Goal(g) => {
+                match goal_data(g, glob.date_style, &glob.calendar, &today) {
+                    Ok(data) => BasicRow {
+                        goal: Some(data),
+                        id: if g.done.is_none() { Some(g.id) } else { None },
+                        requested: requested.contains(&g.id),
+                        waived: None,
+                        summary: None,
+                    },
+                    Err(e) => {
+                        log::error!("Error writing goal: {}\ndata: {:?}", &e, g);
+                        return html_500();
+                    }
+                }
+            }
+            RowDisplay::Waived(w) => BasicRow {
+                goal: None,
+                id: None,
+                requested: false,
+                waived: Some(w),
+                summary: None,
+            },
+            RowDisplay::Summary(sd) => BasicRow {
+                goal: None,
+                id: None,
+                requested: false,
+                waived: None,
+                summary: Some(SummaryData {
+                    text: sd.label,
+                    score: sd.value.as_str(),
+                }),
+            },
+        };
+        rows.push(row);
+    }
+
+    let data = json!({
+        "announcements": announce::active(Role::Student, &glob).await,
+        "name": format!("{} {}", pd.rest, pd.last),
+        "uname": pd.uname,
+        "key": key,
+        "teacher": pd.teacher,
+        "temail":  pd.temail,
+        "n_done": pd.n_done,
+        "n_due": pd.n_due,
+        "n_total": pd.n_scheduled,
+        "rows": rows,
+        "has_review": pd.has_review_chapters,
+        "has_incomplete": pd.has_incomplete_chapters,
+        "message": message,
+        "year": glob.current_year().map(|y| &y.label),
+    });
+
+    serve_template(StatusCode::OK, "student_basic", &data, vec![])
+}
+
+/// Optional request body for actions that return paginated lists.
+#[derive(Debug, Default, Deserialize)]
+struct PageRequest {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl PageRequest {
+    fn from_body(body: Option<String>) -> Result<PageRequest, String> {
+        match body.as_deref().map(str::trim) {
+            None | Some("") => Ok(PageRequest::default()),
+            Some(text) => serde_json::from_str(text)
+                .map_err(|e| format!("Unable to deserialize body as page request: {}", &e)),
+        }
+    }
+
+    /// Cut the appropriate page out of `items`.
+    fn apply<T>(&self, items: Vec<T>) -> Page<T> {
+        let limit = self
+            .limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .clamp(1, MAX_PAGE_LIMIT);
+        let total = items.len();
+        let items: Vec<T> = items.into_iter().skip(self.offset).take(limit).collect();
+
+        Page {
+            total,
+            offset: self.offset,
+            limit,
+            items,
+        }
+    }
+}
+
+/// A page of results from a list of `total` items.
+#[derive(Debug, Serialize)]
+struct Page<T> {
+    total: usize,
+    offset: usize,
+    limit: usize,
+    items: Vec<T>,
+}
+
+#[derive(Debug, Serialize)]
+struct TeacherJson<'a> {
+    uname: &'a str,
+    name: &'a str,
+    email: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct ProfileJson<'a> {
+    uname: &'a str,
+    email: &'a str,
+    last: &'a str,
+    rest: &'a str,
+    teacher: Option<TeacherJson<'a>>,
+}
+
+/// A single [`Goal`](crate::pace::Goal) as reported by the JSON API.
+#[derive(Debug, Serialize)]
+struct GoalJson<'a> {
+    id: i64,
+    course: &'a str,
+    book: &'a str,
+    chapter: Cow<'a, str>,
+    subject: Option<&'a str>,
+    review: bool,
+    incomplete: bool,
+    due: Option<MiniString<SMALLSTORE>>,
+    done: Option<MiniString<SMALLSTORE>>,
+    tries: Option<i16>,
+    score: Option<f32>,
+    status: &'static str,
+}
+
+impl<'a> GoalJson<'a> {
+    fn from_display(g: &GoalDisplay<'a>) -> Result<GoalJson<'a>, String> {
+        let status = match g.status {
+            GoalStatus::Done => "done",
+            GoalStatus::Late => "late",
+            GoalStatus::Overdue => "overdue",
+            GoalStatus::Yet => "yet",
+        };
+
+        Ok(GoalJson {
+            id: g.id,
+            course: g.course,
+            book: g.book,
+            chapter: g.title.clone(),
+            subject: g.subject,
+            review: g.rev,
+            incomplete: g.inc,
+            due: g.due.map(|d| format_date(DateStyle::Iso, &d)).transpose()?,
+            done: g
+                .done
+                .map(|d| format_date(DateStyle::Iso, &d))
+                .transpose()?,
+            tries: g.tries,
+            score: g.score,
+            status,
+        })
+    }
+}
+
+/// Response body for the `pace` action.
+#[derive(Debug, Serialize)]
+struct PaceJson<'a> {
+    n_due: usize,
+    n_done: usize,
+    n_scheduled: usize,
+    weight_due: f32,
+    weight_done: f32,
+    weight_scheduled: f32,
+    goals: Page<GoalJson<'a>>,
+}
+
+/// Response body for the `this-week` action.
+#[derive(Debug, Serialize)]
+struct WeekJson<'a> {
+    start: MiniString<SMALLSTORE>,
+    end: MiniString<SMALLSTORE>,
+    goals: Vec<GoalJson<'a>>,
+}
+
+/// Response body for the `burndown` action.
+#[derive(Debug, Serialize)]
+struct BurndownJson {
+    dates: Vec<MiniString<SMALLSTORE>>,
+    total_weight: f32,
+    #[serde(flatten)]
+    series: Burndown,
+}
+
+/// Wrap some serializable data in a JSON response with the appropriate
+/// `x-camp-action` header.
+fn respond_json<T: Serialize>(action: &'static str, data: &T) -> Response {
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static(action),
+        )],
+        Json(data),
+    )
+        .into_response()
+}
+
+/**
+Check a student's password and respond with a new auth key, for clients
+of the JSON API.
+
+Expects the same form data as the front-page login. Responds with
+
+```json
+{ "uname": "jsmith", "key": "..." }
+```
+*/
+pub async fn issue_key(
+    Form(form): Form<LoginData>,
+    Extension(glob): Extension<Arc<RwLock<Glob>>>,
+) -> Response {
+    log::trace!("student::issue_key( {:?}, [ Glob ] ) called.", &form.uname);
+
+    let glob = glob.read().await;
+    let s = match glob.users.get(&form.uname) {
+        Some(User::Student(s)) => s,
+        _ => {
+            glob.auth()
+                .read()
+                .await
+                .dummy_check(&form.uname, &form.password)
+                .await;
+            return ApiError::unauthorized("Invalid username/password combination.".to_owned())
+                .into_response();
+        }
+    };
+
+    let auth_response = glob
+        .auth()
+        .read()
+        .await
+        .check_password_and_issue_key(&s.base.uname, &form.password, &s.base.salt)
+        .await;
+
+    match auth_response {
+        Err(e) => {
+            log::error!(
+                "auth::Db::check_password_and_issue_key( {:?}, [ password ], {:?} ): {}",
+                &s.base.uname,
+                &s.base.salt,
+                &e
+            );
+            text_500(None)
+        }
+        Ok(AuthResult::Key(k)) => respond_json(
+            "key",
+            &json!({
+                "uname": &s.base.uname,
+                "key": &k,
+            }),
+        ),
+        // Including `AuthResult::Locked`; see `respond_locked_out`.
+        Ok(_) => ApiError::unauthorized("Invalid username/password combination.".to_owned())
+            .into_response(),
+    }
+}
+
+/**
+All requests from student JSON API clients get funneled through this
+function.
+
+A previous layer should have already ensured that the student's key
+checks out.
+
+Recognized `x-camp-action` values are
+
+  * `profile`: the student's name, email, and teacher
+  * `pace`: progress totals and a page of the student's goals
+  * `get-my-pace`: the student's whole pace calendar, in the same form
+    Teachers' clients get it (scores, rubrics, exam scores, and notices are
+    blanked out unless the `student_scores` option is on)
+  * `this-week`: goals due between this Monday and Sunday
+  * `history`: a page of the student's course completion history
+  * `burndown`: cumulative due and done weight for each day of the
+    calendar, for drawing charts
+  * `download-ical`: the student's due dates as an iCalendar file (see
+    [`ical`](super::ical))
+  * `announcements`: the Boss's current announcements for students
+  * `released-reports`: which of the student's reports the Boss has released
+  * `download-report`: the PDF of the student's report for the term in the
+    `x-camp-term` header, once the Boss has released it (see
+    [`reports`](super::reports))
+  * `change-password`: change the student's password (see
+    [`change_password`](super::change_password) for the required headers)
+
+The paginated actions take an optional body like
+
+```json
+{ "offset": 0, "limit": 50 }
+```
+*/
+pub async fn api(
+    headers: HeaderMap,
+    body: Option<String>,
+    Extension(glob): Extension<Arc<RwLock<Glob>>>,
+) -> Response {
+    let uname = match get_head("x-camp-uname", &headers) {
+        Ok(uname) => uname,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    let glob = glob.read().await;
+
+    // As with the other roles, the key has been checked, but not that the
+    // user is actually a _student_.
+    let (u, s) = match glob.users.get(uname) {
+        Some(u @ User::Student(s)) => (u, s),
+        _ => {
+            return ApiError::forbidden("This API is only available to students.".to_owned())
+                .into_response();
+        }
+    };
+
+    let action = match get_head("x-camp-action", &headers) {
+        Ok(act) => act,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    match action {
+        "profile" => profile(s, &glob),
+        "pace" => pace(s, body, &glob).await,
+        "get-my-pace" => my_pace(s, &glob).await,
+        "this-week" => this_week(s, &glob).await,
+        "history" => history(s, body, &glob).await,
+        "burndown" => burndown(s, &glob).await,
+        "download-ical" => download_ical(s, &glob).await,
+        "report-info" => report_info(s, &glob).await,
+        "released-reports" => reports::student_list(s, &glob).await,
+        "download-report" => reports::student_download(s, &headers, &glob).await,
+        "announcements" => {
+            respond_json("announcements", &announce::active(Role::Student, &glob).await)
+        }
+        "change-password" => change_password(u, &headers, &glob).await,
+        x => respond_bad_request(format!("{:?} is not a recognized x-camp-action value.", &x)),
+    }
+}
+
+fn profile(s: &Student, glob: &Glob) -> Response {
+    let teacher = match glob.users.get(&s.teacher) {
+        Some(User::Teacher(t)) => Some(TeacherJson {
+            uname: &t.base.uname,
+            name: &t.name,
+            email: &t.base.email,
+        }),
+        _ => None,
+    };
+
+    let data = ProfileJson {
+        uname: &s.base.uname,
+        email: &s.base.email,
+        last: &s.last,
+        rest: &s.rest,
+        teacher,
+    };
+
+    respond_json("profile", &data)
+}
+
+async fn pace(s: &Student, body: Option<String>, glob: &Glob) -> Response {
+    let page = match PageRequest::from_body(body) {
+        Ok(page) => page,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    let p = match glob.get_pace_by_student(&s.base.uname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!(
+                "Glob::get_pace_by_student( {:?} ) error: {}",
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+    let mut pd = match PaceDisplay::from(&p, glob) {
+        Ok(pd) => pd,
+        Err(e) => {
+            log::error!(
+                "PaceDisplay::from( [ Pace {:?} ] ) error: {}",
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+    if !glob.student_scores {
+        pd.hide_scores();
+    }
+
+    let goals: Result<Vec<GoalJson>, String> = pd
+        .rows
+        .iter()
+        .filter_map(|row| match row {
+            RowDisplay::Goal(g) => Some(GoalJson::from_display(g)),
+            RowDisplay::Waived(_) | RowDisplay::Summary(_) => None,
+        })
+        .collect();
+    let goals = match goals {
+        Ok(goals) => goals,
+        Err(e) => {
+            log::error!("Error serializing goals for {:?}: {}", &s.base.uname, &e);
+            return text_500(None);
+        }
+    };
+
+    let data = PaceJson {
+        n_due: pd.n_due,
+        n_done: pd.n_done,
+        n_scheduled: pd.n_scheduled,
+        weight_due: pd.weight_due,
+        weight_done: pd.weight_done,
+        weight_scheduled: pd.weight_scheduled,
+        goals: page.apply(goals),
+    };
+
+    respond_json("pace", &data)
+}
+
+async fn my_pace(s: &Student, glob: &Glob) -> Response {
+    let p = match glob.get_pace_by_student(&s.base.uname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!(
+                "Glob::get_pace_by_student( {:?} ) error: {}",
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+    let mut pdata = match teacher::PaceData::from_pace(&p) {
+        Ok(pdata) => pdata,
+        Err(e) => {
+            log::error!("Error serializing pace for {:?}: {}", &s.base.uname, &e);
+            return text_500(None);
+        }
+    };
+    if !glob.student_scores {
+        pdata.hide_scores();
+    }
+
+    respond_json("get-my-pace", &pdata)
+}
+
+async fn download_ical(s: &Student, glob: &Glob) -> Response {
+    let p = match glob.get_pace_by_student(&s.base.uname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!(
+                "Glob::get_pace_by_student( {:?} ) error: {}",
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+    match PaceDisplay::from(&p, glob) {
+        Ok(pd) => ical::respond(&pd),
+        Err(e) => {
+            log::error!(
+                "PaceDisplay::from( [ Pace {:?} ] ) error: {}",
+                &s.base.uname,
+                &e
+            );
+            text_500(None)
+        }
+    }
+}
+
+async fn this_week(s: &Student, glob: &Glob) -> Response {
+    let today = crate::now();
+    let start = today.saturating_sub(Duration::days(
+        today.weekday().number_days_from_monday() as i64
+    ));
+    let end = start.saturating_add(Duration::days(6));
+
+    let p = match glob.get_pace_by_student(&s.base.uname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!(
+                "Glob::get_pace_by_student( {:?} ) error: {}",
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+    let mut pd = match PaceDisplay::from(&p, glob) {
+        Ok(pd) => pd,
+        Err(e) => {
+            log::error!(
+                "PaceDisplay::from( [ Pace {:?} ] ) error: {}",
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+    if !glob.student_scores {
+        pd.hide_scores();
+    }
+
+    let goals: Result<Vec<GoalJson>, String> = pd
+        .rows
+        .iter()
+        .filter_map(|row| match row {
+            RowDisplay::Goal(g) => match g.due {
+                Some(d) if d >= start && d <= end => Some(GoalJson::from_display(g)),
+                _ => None,
+            },
+            RowDisplay::Waived(_) | RowDisplay::Summary(_) => None,
+        })
+        .collect();
+
+    let data = match (
+        goals,
+        format_date(DateStyle::Iso, &start),
+        format_date(DateStyle::Iso, &end),
+    ) {
+        (Ok(goals), Ok(start), Ok(end)) => WeekJson { start, end, goals },
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            log::error!(
+                "Error serializing this week's goals for {:?}: {}",
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+
+    respond_json("this-week", &data)
+}
+
+async fn history(s: &Student, body: Option<String>, glob: &Glob) -> Response {
+    let page = match PageRequest::from_body(body) {
+        Ok(page) => page,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    let hist: Vec<HistEntry> = match glob.get_student_completion_history(&s.base.uname).await {
+        Ok(hist) => hist,
+        Err(e) => {
+            log::error!(
+                "Error attempting to retrieve completion history for {:?}: {}",
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+
+    respond_json("history", &page.apply(hist))
+}
+
+async fn burndown(s: &Student, glob: &Glob) -> Response {
+    let p = match glob.get_pace_by_student(&s.base.uname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!(
+                "Glob::get_pace_by_student( {:?} ) error: {}",
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+
+    let dates = match iso_dates(glob.calendar.days()) {
+        Ok(dates) => dates,
+        Err(e) => {
+            log::error!("Error formatting calendar dates: {}", &e);
+            return text_500(None);
+        }
+    };
+
+    let data = BurndownJson {
+        dates,
+        total_weight: p.total_weight,
+        series: p.burndown(&glob.calendar),
+    };
+
+    respond_json("burndown", &data)
+}
+
+/// This year's report details (fact mastery, social/emotional/behavioral
+/// scores, and Teacher's notes), minus anything not meant for students.
+async fn report_info(s: &Student, glob: &Glob) -> Response {
+    let year = glob.academic_year();
+    let res = glob
+        .data()
+        .read()
+        .await
+        .get_report_sidecar(&s.base.uname, year)
+        .await;
+    let sidecar = match res {
+        Ok(sc) => sc,
+        Err(e) => {
+            log::error!(
+                "Error retrieving report sidecar for {:?}: {}",
+                &s.base.uname,
+                &e
+            );
+            return text_500(None);
+        }
+    };
+
+    respond_json("report-info", &sidecar.for_audience(Audience::Student))
+}
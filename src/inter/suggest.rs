@@ -0,0 +1,384 @@
+/*!
+Suggested schedules for assigning a whole course at once.
+
+When a Teacher is about to assign a student a course (see the Teacher API's
+`enroll-course` action), the `suggest-schedule` action (see [`respond`])
+proposes due dates for its chapters based on how long each chapter has
+actually taken students school-wide (as gathered for the Admin API's
+[`chapter-durations`](super::durations) action), rather than on the
+chapters' weights alone.
+
+Each chapter is given the median of its completed goals' durations, as long
+as there are at least [`MIN_SAMPLES`] of them. Chapters with fewer are
+given a share of time proportional to their weights, at the rate (days per
+unit of weight) at which the chapters with enough history have gone. If no
+chapter in the course has enough history, no schedule is suggested.
+
+Nothing is saved; the response is only a suggestion, and says so.
+*/
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    http::header::{HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use time::Date;
+use tokio::sync::RwLock;
+
+use super::{durations, respond_bad_request, text_500};
+use crate::{config::Glob, DATE_FMT};
+
+/// How many completed goals a chapter needs before its own history is used
+/// to suggest how long it should take.
+const MIN_SAMPLES: usize = 3;
+
+/// Body of a `suggest-schedule` request. This is the same as the body of an
+/// `enroll-course` request; the fields that don't matter here are ignored.
+#[derive(Debug, Deserialize)]
+struct SuggestRequest {
+    sym: String,
+    first: Option<i16>,
+    last: Option<i16>,
+    start: Option<String>,
+}
+
+/// What a chapter's suggested duration is based on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Basis {
+    /// The chapter's own completed goals.
+    History,
+    /// The chapter's weight, at the rate of the rest of the course.
+    Weight,
+}
+
+/// How long a chapter should take, in instructional days.
+#[derive(Debug, PartialEq, Serialize)]
+struct Estimate {
+    seq: i16,
+    basis: Basis,
+    /// Number of completed goals measured.
+    n: usize,
+    days: f32,
+    /// The 25th and 75th percentiles of the measured durations, if the
+    /// estimate is based on them.
+    low: Option<f32>,
+    high: Option<f32>,
+}
+
+/// One chapter in the response to a `suggest-schedule` request.
+#[derive(Debug, Serialize)]
+struct ChapterSuggestion {
+    #[serde(flatten)]
+    estimate: Estimate,
+    title: String,
+    /// `None` if the schedule runs past the end of the calendar.
+    due: Option<Date>,
+}
+
+/// Response to a `suggest-schedule` request.
+#[derive(Debug, Serialize)]
+struct Suggestion {
+    /// Always `true`; this is here so nobody mistakes it for a schedule
+    /// that has been saved.
+    suggested: bool,
+    sym: String,
+    start: Date,
+    /// The last suggested due date, if they all fit in the calendar.
+    end: Option<Date>,
+    chapters: Vec<ChapterSuggestion>,
+}
+
+/// The `q`th quantile (linearly interpolated) of the nonempty, sorted `xs`.
+fn quantile(xs: &[f32], q: f32) -> f32 {
+    let pos = q * (xs.len() - 1) as f32;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    xs[lo] + (xs[hi] - xs[lo]) * (pos - lo as f32)
+}
+
+/**
+Estimate how long each of the `chapters` (`(seq, weight)` pairs) should
+take, given the measured durations of each chapter's completed goals in
+`history`.
+
+Returns `None` if there isn't enough history to go on.
+*/
+fn estimate(chapters: &[(i16, f32)], history: &BTreeMap<i16, Vec<f32>>) -> Option<Vec<Estimate>> {
+    let mut ests: Vec<Option<Estimate>> = Vec::with_capacity(chapters.len());
+    let mut hist_days: f32 = 0.0;
+    let mut hist_weight: f32 = 0.0;
+    for (seq, weight) in chapters.iter() {
+        let mut xs = match history.get(seq) {
+            Some(xs) if xs.len() >= MIN_SAMPLES => xs.clone(),
+            _ => {
+                ests.push(None);
+                continue;
+            }
+        };
+        xs.sort_by(f32::total_cmp);
+        let days = quantile(&xs, 0.5);
+        hist_days += days;
+        hist_weight += weight;
+        ests.push(Some(Estimate {
+            seq: *seq,
+            basis: Basis::History,
+            n: xs.len(),
+            days,
+            low: Some(quantile(&xs, 0.25)),
+            high: Some(quantile(&xs, 0.75)),
+        }));
+    }
+
+    // This is really to prevent division by zero.
+    if hist_weight < 0.001 {
+        return None;
+    }
+    let rate = hist_days / hist_weight;
+
+    let ests = ests
+        .into_iter()
+        .zip(chapters.iter())
+        .map(|(est, (seq, weight))| match est {
+            Some(est) => est,
+            None => Estimate {
+                seq: *seq,
+                basis: Basis::Weight,
+                n: history.get(seq).map(Vec::len).unwrap_or(0),
+                days: weight * rate,
+                low: None,
+                high: None,
+            },
+        })
+        .collect();
+    Some(ests)
+}
+
+/// Lay the `estimates` end to end through the (sorted) instructional
+/// `days`, giving each chapter a due date, or `None` if it runs past the
+/// last of the `days`.
+fn schedule(estimates: &[Estimate], days: &[Date]) -> Vec<Option<Date>> {
+    let mut running: f32 = 0.0;
+    estimates
+        .iter()
+        .map(|est| {
+            running += est.days;
+            // The small subtraction keeps floating-point error from pushing
+            // a whole number of days onto the next day.
+            let idx = ((running - 0.001).ceil() as usize).max(1);
+            days.get(idx - 1).copied()
+        })
+        .collect()
+}
+
+/**
+Respond with a suggested schedule for assigning a course (or a range of its
+chapters), starting on the given date (or the beginning of the calendar).
+
+Header:
+```text
+x-camp-action: suggest-schedule
+```
+With a JSON body like that of an `enroll-course` request:
+```json
+{
+    "sym": "pha1",
+    "first": 1,             // optional
+    "last": 8,              // optional
+    "start": "2022-09-06"   // optional
+}
+```
+
+Response body looks like
+```json
+{
+    "suggested": true,
+    "sym": "pha1",
+    "start": "2022-09-06",
+    "end": "2022-11-18",    // null if it doesn't fit in the calendar
+    "chapters": [
+        {
+            "seq": 1, "title": "Kinematics", "basis": "history", "n": 14,
+            "days": 6.5, "low": 4.0, "high": 9.0, "due": "2022-09-14"
+        },
+        {
+            "seq": 2, "title": "Chapter 2", "basis": "weight", "n": 1,
+            "days": 5.2, "low": null, "high": null, "due": "2022-09-21"
+        },
+        ...
+    ]
+}
+```
+*/
+pub async fn respond(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request needs application/json body with course details.".to_owned(),
+            );
+        }
+    };
+    let req: SuggestRequest = match serde_json::from_str(&body) {
+        Ok(req) => req,
+        Err(e) => {
+            return respond_bad_request(format!(
+                "Unable to read schedule suggestion request: {}",
+                &e
+            ));
+        }
+    };
+    let start = match req.start.as_deref().map(|s| Date::parse(s, DATE_FMT)) {
+        None => None,
+        Some(Ok(d)) => Some(d),
+        Some(Err(e)) => {
+            return respond_bad_request(format!("Bad start date: {}", &e));
+        }
+    };
+
+    let glob = glob.read().await;
+    let crs = match glob.course_by_sym(&req.sym) {
+        Some(crs) => crs,
+        None => {
+            return respond_bad_request(format!("There is no course with symbol {:?}.", &req.sym));
+        }
+    };
+    let chapters: Vec<(i16, f32)> = crs
+        .all_chapters()
+        .filter(|ch| req.first.map(|n| ch.seq >= n).unwrap_or(true))
+        .filter(|ch| req.last.map(|n| ch.seq <= n).unwrap_or(true))
+        .map(|ch| (ch.seq, ch.weight))
+        .collect();
+    if chapters.is_empty() {
+        return respond_bad_request(format!(
+            "Course {:?} ({}) has no chapters in the requested range.",
+            &crs.sym, &crs.title
+        ));
+    }
+    // Suggested due dates stay out of exam windows, like autopaced ones.
+    let days = glob.calendar.schedulable(glob.calendar.window(start, None));
+    let first_day = match days.first() {
+        Some(d) => *d,
+        None => {
+            return respond_bad_request(
+                "There are no instructional days in which to schedule this course.".to_owned(),
+            );
+        }
+    };
+
+    let history: BTreeMap<i16, Vec<f32>> = match durations::gather(&glob, Some(&crs.sym)).await {
+        Ok(samples) => samples
+            .into_iter()
+            .map(|((_, seq), s)| (seq, s.actual))
+            .collect(),
+        Err(e) => {
+            return text_500(Some(e));
+        }
+    };
+    let estimates = match estimate(&chapters, &history) {
+        Some(ests) => ests,
+        None => {
+            return respond_bad_request(format!(
+                "Not enough students have completed {} to suggest a schedule.",
+                &crs.title
+            ));
+        }
+    };
+
+    let dues = schedule(&estimates, &days);
+    let end = dues.last().copied().flatten();
+    let chapters: Vec<ChapterSuggestion> = estimates
+        .into_iter()
+        .zip(dues)
+        .map(|(estimate, due)| ChapterSuggestion {
+            title: crs
+                .chapter(estimate.seq)
+                .map(|ch| ch.title.clone())
+                .unwrap_or_default(),
+            estimate,
+            due,
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("suggest-schedule"),
+        )],
+        Json(Suggestion {
+            suggested: true,
+            sym: crs.sym.clone(),
+            start: first_day,
+            end,
+            chapters,
+        }),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::date;
+
+    #[test]
+    fn suggested_schedules() {
+        let chapters = [(1, 1.0), (2, 2.0), (3, 1.0)];
+        let mut history: BTreeMap<i16, Vec<f32>> = BTreeMap::new();
+        history.insert(1, vec![6.0, 2.0, 3.0, 4.0, 5.0]);
+        history.insert(2, vec![30.0]);
+        history.insert(3, vec![2.0, 1.0, 3.0]);
+
+        let ests = estimate(&chapters, &history).unwrap();
+        assert_eq!(
+            ests,
+            vec![
+                Estimate {
+                    seq: 1,
+                    basis: Basis::History,
+                    n: 5,
+                    days: 4.0,
+                    low: Some(3.0),
+                    high: Some(5.0),
+                },
+                // Chapters 1 and 3 took 6 days for 2 units of weight.
+                Estimate {
+                    seq: 2,
+                    basis: Basis::Weight,
+                    n: 1,
+                    days: 6.0,
+                    low: None,
+                    high: None,
+                },
+                Estimate {
+                    seq: 3,
+                    basis: Basis::History,
+                    n: 3,
+                    days: 2.0,
+                    low: Some(1.5),
+                    high: Some(2.5),
+                },
+            ]
+        );
+
+        let days: Vec<Date> = (1..=14)
+            .map(|n| date!(2022 - 09 - 01) + time::Duration::days(n))
+            .collect();
+        assert_eq!(
+            schedule(&ests, &days),
+            vec![Some(days[3]), Some(days[9]), Some(days[11])]
+        );
+        assert_eq!(
+            schedule(&ests, &days[..10]),
+            vec![Some(days[3]), Some(days[9]), None]
+        );
+
+        history.remove(&1);
+        history.get_mut(&3).unwrap().pop();
+        assert!(estimate(&chapters, &history).is_none());
+    }
+}
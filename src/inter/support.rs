@@ -0,0 +1,343 @@
+/*!
+Redacted snapshots of the system's state, to attach to bug reports.
+
+The Admin API's `support-bundle` action responds with a ZIP archive
+(built by [`bundle`]) containing
+
+  * `version.json`: the version of `camp` and when the bundle was made
+  * `schema.json`: each database's `meta` table (environment tag, schema
+    version) and the number of rows in each of its tables
+  * `settings.json`: the running configuration, minus secrets
+  * `users.json`: every user's role and (masked) email address, plus
+    any unames that exist in only one of the two databases
+  * `courses.json`: the course catalog (which isn't sensitive)
+  * `failures.json`: the failed request journal, without request or
+    response bodies
+
+Unames are replaced by keyed hashes. The key is random and thrown away
+after each bundle is made, so the same uname hashes the same way
+throughout one bundle (and so can be cross-referenced between its files),
+but not between bundles, and can't be recovered by hashing guesses.
+*/
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{Cursor, Write},
+};
+
+use serde::Serialize;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{config::Glob, store::FailedRequest, user::User, DbSanity};
+
+/// Number of hex digits of each uname's hash to keep.
+const HASH_LEN: usize = 12;
+
+const README: &str = "This is a camp support bundle.
+
+Unames have been replaced with hashes, which are consistent within this
+bundle but not between bundles. Email addresses have been masked, and
+request and response bodies have been left out of failures.json.
+";
+
+/// Hides unames and email addresses.
+struct Redactor {
+    key: [u8; 32],
+}
+
+impl Redactor {
+    fn new() -> Redactor {
+        Redactor {
+            key: rand::random(),
+        }
+    }
+
+    fn uname(&self, uname: &str) -> String {
+        let hash = blake3::keyed_hash(&self.key, uname.as_bytes()).to_hex();
+        hash[..HASH_LEN].to_owned()
+    }
+}
+
+/// Mask one email address, keeping only the first letter of the local
+/// part and the domain name, and the top-level domain.
+fn mask_one_email(email: &str) -> String {
+    fn first(s: &str) -> String {
+        s.chars().take(1).chain("***".chars()).collect()
+    }
+
+    let (local, domain) = match email.split_once('@') {
+        Some(parts) => parts,
+        None => {
+            return "***".to_owned();
+        }
+    };
+    match domain.rsplit_once('.') {
+        Some((name, tld)) => format!("{}@{}.{}", first(local), first(name), tld),
+        None => format!("{}@{}", first(local), first(domain)),
+    }
+}
+
+/// Mask each address in a (possibly comma-separated) list of email
+/// addresses.
+fn mask_email(emails: &str) -> String {
+    emails
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(mask_one_email)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Serialize)]
+struct VersionInfo {
+    camp_version: &'static str,
+    generated: String,
+}
+
+#[derive(Serialize)]
+struct SchemaInfo {
+    /// The schema version this build of `camp` expects the data DB to have.
+    expected_data_schema_version: i32,
+    data: DbSanity,
+    auth: DbSanity,
+}
+
+#[derive(Serialize)]
+struct Settings<'a> {
+    uri: &'a str,
+    addr: String,
+    email_backend: &'static str,
+    email_destination: String,
+    unsubscribe_secret_set: bool,
+    auth_export_key_set: bool,
+    weekly_digest_hour: Option<u8>,
+    completion_email: String,
+    student_scores: bool,
+    date_style: crate::DateStyle,
+    failure_journal: usize,
+    min_password_length: usize,
+    archive_dir: String,
+    static_dir: String,
+    social_traits: &'a [String],
+    summary_labels: &'a crate::pace::SummaryLabels,
+    failing_threshold: f32,
+    retention: &'a crate::store::Retention,
+    /// Hour of the nightly roster fetch, if roster sync is on. (The source
+    /// may have credentials in it.)
+    roster_sync_hour: Option<u8>,
+    dates: BTreeMap<&'a str, String>,
+}
+
+#[derive(Serialize)]
+struct UserInfo {
+    uname: String,
+    role: crate::user::Role,
+    email: String,
+    /// Student's teacher.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    teacher: Option<String>,
+    /// Student's parent email(s).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent: Option<String>,
+    /// Whether the user also has an entry in the auth DB.
+    in_auth_db: bool,
+}
+
+#[derive(Serialize)]
+struct Users {
+    users: Vec<UserInfo>,
+    /// Unames that appear in the auth DB but not in the data DB.
+    auth_only: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct CourseInfo<'a> {
+    id: i64,
+    sym: &'a str,
+    title: &'a str,
+    book: &'a str,
+    chapters: usize,
+}
+
+fn settings(glob: &Glob) -> Settings<'_> {
+    Settings {
+        uri: &glob.uri,
+        addr: glob.addr.to_string(),
+        email_backend: glob.mailer.name(),
+        email_destination: glob.mailer.destination(),
+        unsubscribe_secret_set: !glob.unsubscribe_secret.is_empty(),
+        auth_export_key_set: glob.auth_export_key.is_some(),
+        weekly_digest_hour: glob.weekly_digest_hour,
+        completion_email: format!("{:?}", &glob.completion_email),
+        student_scores: glob.student_scores,
+        date_style: glob.date_style,
+        failure_journal: glob.failure_journal,
+        min_password_length: glob.min_password_length,
+        archive_dir: glob.archive_dir.display().to_string(),
+        static_dir: glob.static_dir.display().to_string(),
+        social_traits: &glob.social_traits,
+        summary_labels: &glob.summary_labels,
+        failing_threshold: glob.failing_threshold,
+        retention: &glob.retention,
+        roster_sync_hour: glob.roster_sync.as_ref().map(|r| r.hour),
+        dates: glob
+            .dates
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.to_string()))
+            .collect(),
+    }
+}
+
+fn users(glob: &Glob, auth_unames: &HashSet<String>, r: &Redactor) -> Users {
+    let mut users: Vec<UserInfo> = glob
+        .users
+        .values()
+        .map(|u| {
+            let (teacher, parent) = match u {
+                User::Student(s) => (Some(r.uname(&s.teacher)), Some(mask_email(&s.parent))),
+                _ => (None, None),
+            };
+            UserInfo {
+                uname: r.uname(u.uname()),
+                role: u.role(),
+                email: mask_email(u.email()),
+                teacher,
+                parent,
+                in_auth_db: auth_unames.contains(u.uname()),
+            }
+        })
+        .collect();
+    // Sort by hash rather than uname so the order gives nothing away.
+    users.sort_by(|a, b| a.uname.cmp(&b.uname));
+
+    let mut auth_only: Vec<String> = auth_unames
+        .iter()
+        .filter(|uname| !glob.users.contains_key(*uname))
+        .map(|uname| r.uname(uname))
+        .collect();
+    auth_only.sort();
+
+    Users { users, auth_only }
+}
+
+fn courses(glob: &Glob) -> Vec<CourseInfo<'_>> {
+    let mut courses: Vec<CourseInfo> = glob
+        .courses
+        .values()
+        .map(|c| CourseInfo {
+            id: c.id,
+            sym: &c.sym,
+            title: &c.title,
+            book: &c.book,
+            chapters: c.all_chapters().count(),
+        })
+        .collect();
+    courses.sort_by_key(|c| c.id);
+    courses
+}
+
+fn redact_failures(failures: &mut [FailedRequest], r: &Redactor) {
+    for f in failures.iter_mut() {
+        f.uname = f.uname.as_deref().map(|u| r.uname(u));
+        // Query strings can have tokens (and unames) in them.
+        if let Some((path, _)) = f.path.split_once('?') {
+            f.path = format!("{}?[redacted]", path);
+        }
+        f.body = None;
+        f.response = None;
+    }
+}
+
+/// Add the JSON serialization of `val` to `zip` as `name`.
+fn add_json<W, T>(zip: &mut ZipWriter<W>, name: &str, val: &T) -> Result<(), String>
+where
+    W: Write + std::io::Seek,
+    T: Serialize,
+{
+    let json = serde_json::to_vec_pretty(val)
+        .map_err(|e| format!("Error serializing {}: {}", name, &e))?;
+    add_file(zip, name, &json)
+}
+
+fn add_file<W>(zip: &mut ZipWriter<W>, name: &str, data: &[u8]) -> Result<(), String>
+where
+    W: Write + std::io::Seek,
+{
+    let opts = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(name, opts)
+        .map_err(|e| format!("Error starting {} in archive: {}", name, &e))?;
+    zip.write_all(data)
+        .map_err(|e| format!("Error writing {} to archive: {}", name, &e))
+}
+
+/// Gather up the contents of a support bundle and return it as the bytes
+/// of a ZIP archive.
+pub async fn bundle(glob: &Glob) -> Result<Vec<u8>, String> {
+    log::trace!("support::bundle( [ Glob ] ) called.");
+
+    let r = Redactor::new();
+
+    let (data_sanity, mut failures) = {
+        let data = glob.data();
+        let data = data.read().await;
+        tokio::try_join!(
+            data.get_sanity(),
+            data.get_failed_requests(glob.failure_journal)
+        )
+        .map_err(|e| format!("Error reading data DB: {}", &e))?
+    };
+    let (auth_sanity, auth_unames) = {
+        let auth = glob.auth();
+        let auth = auth.read().await;
+        tokio::try_join!(auth.get_sanity(), auth.get_unames())
+            .map_err(|e| format!("Error reading auth DB: {}", &e))?
+    };
+    redact_failures(&mut failures, &r);
+
+    let version = VersionInfo {
+        camp_version: env!("CARGO_PKG_VERSION"),
+        generated: time::OffsetDateTime::now_utc().to_string(),
+    };
+    let schema = SchemaInfo {
+        expected_data_schema_version: crate::store::SCHEMA_VERSION,
+        data: data_sanity,
+        auth: auth_sanity,
+    };
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    add_file(&mut zip, "README.txt", README.as_bytes())?;
+    add_json(&mut zip, "version.json", &version)?;
+    add_json(&mut zip, "schema.json", &schema)?;
+    add_json(&mut zip, "settings.json", &settings(glob))?;
+    add_json(&mut zip, "users.json", &users(glob, &auth_unames, &r))?;
+    add_json(&mut zip, "courses.json", &courses(glob))?;
+    add_json(&mut zip, "failures.json", &failures)?;
+
+    let cursor = zip
+        .finish()
+        .map_err(|e| format!("Error finishing archive: {}", &e))?;
+    Ok(cursor.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redaction() {
+        assert_eq!(mask_email("fred.frog@gmail.com"), "f***@g***.com");
+        assert_eq!(
+            mask_email("ferd.frog@gmail.com, mom@camelot.k12.us"),
+            "f***@g***.com, m***@c***.us"
+        );
+        assert_eq!(mask_email("root@localhost"), "r***@l***");
+        assert_eq!(mask_email("not an address"), "***");
+        assert_eq!(mask_email(""), "");
+
+        let (r, s) = (Redactor::new(), Redactor::new());
+        assert_eq!(r.uname("frog"), r.uname("frog"));
+        assert_ne!(r.uname("frog"), r.uname("zack"));
+        assert_ne!(r.uname("frog"), s.uname("frog"));
+        assert_eq!(r.uname("frog").len(), HASH_LEN);
+    }
+}
@@ -16,21 +16,35 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use time::Date;
+use time::{
+    format_description::FormatItem, macros::format_description, Date, PrimitiveDateTime,
+};
 use tokio::sync::RwLock;
 
+use super::extract::{CampAction, CampHeader, CampStudent, CampTerm, CampUname};
 use super::*;
 use crate::{
+    anon,
     auth::AuthResult,
     config::Glob,
-    course::Course,
-    pace::{maybe_parse_score_str, BookCh, Goal, Pace, Source, Term},
-    report, report::ReportSidecar,
-    store::Store,
+    course::{Course, Custom},
+    pace::{
+        check_rubric, maybe_parse_score_str, split_fractions, Absence, BookCh, Burndown, CustomCh,
+        Goal, Pace, PaceDisplay, RubricItem, Source, Tag, Term, TestStatus, Waiver,
+    },
+    report,
+    report::{Audience, ReportSidecar},
+    store::{GoalDeletion, GoalFilter, Store},
     user::*,
+    webhook::{Event, GoalCompleted, ReportFinalized},
     DATE_FMT,
 };
 
+/// How the timestamps used to sync with the frontend (see
+/// [`changes_since`]) are written.
+const SYNC_TIME_FMT: &[FormatItem] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond]");
+
 /// Attempt to parse a [`Date`] of the format "2021-01-27", which is the
 /// format in which the frontend encodes dates.
 fn maybe_parse_date(date_opt: Option<&str>) -> Result<Option<Date>, String> {
@@ -45,6 +59,45 @@ fn maybe_parse_date(date_opt: Option<&str>) -> Result<Option<Date>, String> {
 
 /// Check that the teacher's login credentials check out, generate them a
 /// key, then serve them the page that contains their view.
+/// Header carrying the course catalog token (see [`Glob::course_token`])
+/// in responses with course data, and in requests from clients that have
+/// some.
+const COURSES_HEADER: &str = "x-camp-courses";
+
+/// Actions that use course weights, and so are refused if the client's
+/// course data is out of date.
+const WEIGHTED_ACTIONS: &[&str] = &[
+    "add-goal",
+    "update-goal",
+    "autopace",
+    "enroll-course",
+    "split-goal",
+    "upload-goals",
+];
+
+/// Whether the request for `action` says it was made with course data
+/// other than the `current` catalog. Requests that don't say are let
+/// through.
+fn courses_stale(action: &str, headers: &HeaderMap, current: &str) -> bool {
+    if !WEIGHTED_ACTIONS.contains(&action) {
+        return false;
+    }
+    match headers.get(COURSES_HEADER) {
+        Some(v) => v.as_bytes() != current.as_bytes(),
+        None => false,
+    }
+}
+
+/// Add the course catalog token header to a response with course data.
+fn with_course_token(mut response: Response, glob: &Glob) -> Response {
+    if let Ok(v) = HeaderValue::from_str(&glob.course_token) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(COURSES_HEADER), v);
+    }
+    response
+}
+
 pub async fn login(t: Teacher, form: LoginData, glob: Arc<RwLock<Glob>>) -> Response {
     log::trace!(
         "teacher::login( {:?}, ... , [ glob ]) called.",
@@ -77,6 +130,9 @@ pub async fn login(t: Teacher, form: LoginData, glob: Arc<RwLock<Glob>>) -> Resp
         Ok(AuthResult::BadPassword) => {
             return respond_bad_password(&t.base.uname);
         }
+        Ok(AuthResult::Locked(secs)) => {
+            return respond_locked_out(&t.base.uname, secs);
+        }
         Ok(x) => {
             log::warn!(
                 "auth::Db::check_password_and_issue_key( {:?}, {:?}. {:?} returned {:?}, which shouldn't ever happen.",
@@ -87,10 +143,14 @@ pub async fn login(t: Teacher, form: LoginData, glob: Arc<RwLock<Glob>>) -> Resp
         }
     };
 
+    let glob = glob.read().await;
+    glob.set_anonymized(&auth_key, form.anonymize());
     let data = json!({
         "uname": &t.base.uname,
         "key": &auth_key,
-        "name": &t.name,
+        "name": anon::name(&t.name),
+        "training": glob.training.contains(&t.base.uname),
+        "year": glob.current_year().map(|y| &y.label),
     });
 
     serve_template(StatusCode::OK, "teacher", &data, vec![])
@@ -104,25 +164,17 @@ This will then generate the response that should be sent back to the teacher's
 front-end, regardless of whether it's an error.
 
 A previous layer should have already ensured that the teacher's key
-checks out.
+checks out. Clearing or deleting a student's goals, and force-deleting a
+completed goal, have to be confirmed first (see [`confirm`](super::confirm)).
 */
 pub async fn api(
+    CampUname(uname): CampUname,
+    CampAction(action): CampAction,
     headers: HeaderMap,
     body: Option<String>,
     Extension(glob): Extension<Arc<RwLock<Glob>>>,
 ) -> Response {
-    let uname: &str = match headers.get("x-camp-uname") {
-        Some(uname) => match uname.to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                return text_500(None);
-            }
-        },
-        None => {
-            return text_500(None);
-        }
-    };
-
+    let uname = uname.as_str();
     let u = {
         let glob = glob.read().await;
         if let Some(u) = glob.users.get(uname) {
@@ -139,43 +191,98 @@ pub async fn api(
     match u {
         User::Teacher(_) => { /* Okay, approved, you can be here. */ }
         _ => {
-            return (
-                StatusCode::FORBIDDEN,
-                "Who is this? What's you're operating number?".to_owned(),
-            )
+            return ApiError::forbidden("Who is this? What's you're operating number?".to_owned())
                 .into_response();
         }
     }
 
-    let action = match headers.get("x-camp-action") {
-        Some(act) => match act.to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                return respond_bad_request("x-camp-action header unrecognizable.".to_owned());
+    if courses_stale(action.as_str(), &headers, &glob.read().await.course_token) {
+        return ApiError::conflict(
+            "Course information has changed since it was loaded; reloading it. \
+            Please check your changes and try again."
+                .to_owned(),
+        )
+        .into_response();
+    }
+
+    // Teachers in training mode work in their sandboxes instead.
+    let training = glob.read().await.training.contains(uname);
+    let glob = if training {
+        let res = glob.read().await.sandbox(uname).await;
+        match res {
+            Ok(sandbox) => Arc::new(RwLock::new(sandbox)),
+            Err(e) => {
+                log::error!("Error opening sandbox of {:?}: {}", uname, &e);
+                return text_500(Some(format!("Unable to open your training sandbox: {}", &e)));
             }
-        },
-        None => {
-            return respond_bad_request("Request must have an x-camp-action header.".to_owned());
         }
+    } else {
+        glob
     };
 
-    match action {
+    if let Err(resp) =
+        super::confirm::interlock(action.as_str(), uname, &headers, body.as_deref(), &glob).await
+    {
+        return resp;
+    }
+
+    match action.as_str() {
         "populate-dates" => populate_dates(glob.clone()).await,
         "populate-courses" => populate_courses(glob.clone()).await,
         "populate-goals" => populate_goals(&headers, glob.clone()).await,
+        "changes-since" => changes_since(uname, body, glob.clone()).await,
         "populate-traits" => populate_traits(glob.clone()).await,
-        "add-goal" => insert_goal(body, glob.clone()).await,
-        "update-goal" => update_goal(body, glob.clone()).await,
-        "delete-goal" => delete_goal(body, glob.clone()).await,
-        "update-numbers" => update_numbers(body, glob.clone()).await,
-        "autopace" => autopace(body, glob.clone()).await,
-        "clear-goals" => clear_goals(body, glob.clone()).await,
+        "bootstrap" => bootstrap(uname, glob.clone()).await,
+        "add-goal" => insert_goal(&headers, body, glob.clone()).await,
+        "update-goal" => update_goal(&headers, body, glob.clone()).await,
+        "delete-goal" => delete_goal(&headers, body, glob.clone()).await,
+        "update-numbers" => update_numbers(&headers, body, glob.clone()).await,
+        "autopace" => autopace(&headers, body, glob.clone()).await,
+        "clear-goals" => clear_goals(&headers, body, glob.clone()).await,
+        "delete-goals" => delete_goals(uname, &headers, body, glob.clone()).await,
+        "enroll-course" => enroll_course(&headers, body, glob.clone()).await,
+        "suggest-schedule" => suggest::respond(body, glob.clone()).await,
+        "waive-chapter" => waive_chapter(uname, &headers, body, glob.clone()).await,
+        "split-goal" => split_goal(uname, &headers, body, glob.clone()).await,
+        "set-progress" => set_progress(uname, &headers, body, glob.clone()).await,
+        "dismiss-request" => dismiss_request(uname, &headers, body, glob.clone()).await,
+        "request-test" => request_test(uname, &headers, body, false, glob.clone()).await,
+        "cancel-test" => request_test(uname, &headers, body, true, glob.clone()).await,
+        "populate-absences" => populate_absences(uname, body, glob.clone()).await,
+        "add-absence" => add_absence(uname, body, glob.clone()).await,
+        "delete-absence" => delete_absence(uname, body, glob.clone()).await,
+        "recompute-weights" => recompute_weights(body, Some(uname), glob.clone()).await,
+        "bulk-complete" => bulk_complete(&headers, body, glob.clone()).await,
         "upload-goals" => upload_goals(&headers, body, glob.clone()).await,
+        "goals-template" => goals_template(uname, glob.clone()).await,
+        "download-students" => download_students(uname, glob.clone()).await,
+        "download-goals" => download_goals(&headers, glob.clone()).await,
         "show-sidecar" => show_sidecar(&headers, body, glob.clone()).await,
         "update-sidecar" => update_sidecar(&headers, body, glob.clone()).await,
         "render-report" => generate_report(&headers, body, glob.clone()).await,
+        "copy-draft" => copy_draft(&headers, body, glob.clone()).await,
         "discard-pdf" => discard_pdf(&headers, glob.clone()).await,
         "student-history" => student_history(&headers, glob.clone()).await,
+        "goal-row" => goal_row(&headers, body, glob.clone()).await,
+        "summary-rows" => summary_rows(&headers, glob.clone()).await,
+        "export-pace-markdown" => export_pace_markdown(&headers, glob.clone()).await,
+        "download-ical" => download_ical(&headers, glob.clone()).await,
+        "digest" => digest_setting(uname, body, glob.clone()).await,
+        "kiosk" => kiosk_link(uname, body, glob.clone()).await,
+        "change-password" => change_password(&u, &headers, &*glob.read().await).await,
+        "burndown" => burndown(uname, glob.clone()).await,
+        "lag-history" => lag_history(uname, body, glob.clone()).await,
+        "student-activity" => activity::respond(uname, body, &*glob.read().await).await,
+        "at-risk" => at_risk::respond(vec![uname], body, &*glob.read().await).await,
+        "populate-tags" => populate_tags(uname, glob.clone()).await,
+        "add-tag" => add_tag(uname, body, glob.clone()).await,
+        "update-tag" => update_tag(uname, body, glob.clone()).await,
+        "delete-tag" => delete_tag(uname, body, glob.clone()).await,
+        "populate-custom-chapters" => populate_custom_chapters(uname, glob.clone()).await,
+        "add-custom-chapter" => add_custom_chapter(uname, body, glob.clone()).await,
+        "update-custom-chapter" => update_custom_chapter(uname, body, glob.clone()).await,
+        "delete-custom-chapter" => delete_custom_chapter(uname, body, glob.clone()).await,
+        "tag-goals" => tag_goals(uname, body, glob.clone()).await,
         x => respond_bad_request(format!("{:?} is not a recognized x-camp-action value.", &x)),
     }
 }
@@ -201,14 +308,17 @@ Header which gets us here:
 x-camp-action: populate-dates
 ```
 */
-async fn populate_dates(glob: Arc<RwLock<Glob>>) -> Response {
-    let dates_bucket: HashMap<String, String> = glob
-        .read()
-        .await
-        .dates
+/// The named dates (like `"end-fall"`), formatted the way the frontend
+/// expects them.
+fn dates_data(glob: &Glob) -> HashMap<String, String> {
+    glob.dates
         .iter()
         .map(|(n, d)| (n.clone(), d.to_string()))
-        .collect();
+        .collect()
+}
+
+async fn populate_dates(glob: Arc<RwLock<Glob>>) -> Response {
+    let dates_bucket = dates_data(&*glob.read().await);
 
     (
         StatusCode::OK,
@@ -306,9 +416,8 @@ Header that gets us here:
 x-camp-action: populate-courses
 ```
 */
-async fn populate_courses(glob: Arc<RwLock<Glob>>) -> Response {
-    let glob = glob.read().await;
-
+/// Every course, marshaled for the frontend.
+fn courses_data(glob: &Glob) -> Vec<CourseData<'_>> {
     let mut course_data: Vec<CourseData> = Vec::with_capacity(glob.courses.len());
     for (_, crs) in glob.courses.iter() {
         match CourseData::from_course(crs) {
@@ -320,8 +429,14 @@ async fn populate_courses(glob: Arc<RwLock<Glob>>) -> Response {
             }
         }
     }
+    course_data
+}
 
-    (
+async fn populate_courses(glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let course_data = courses_data(&glob);
+
+    let response = (
         StatusCode::OK,
         [(
             HeaderName::from_static("x-camp-action"),
@@ -329,7 +444,8 @@ async fn populate_courses(glob: Arc<RwLock<Glob>>) -> Response {
         )],
         Json(&course_data),
     )
-        .into_response()
+        .into_response();
+    with_course_token(response, &glob)
 }
 
 /*
@@ -345,8 +461,14 @@ struct GoalData<'a> {
     id: i64,
     #[serde(skip_serializing)]
     uname: &'a str,
+    /// `sym` and `seq` are blank for custom goals, which have the `id` of
+    /// their custom chapter in `custom` instead.
+    #[serde(default)]
     sym: &'a str,
+    #[serde(default)]
     seq: i16,
+    #[serde(default)]
+    custom: Option<i64>,
     rev: bool,
     inc: bool,
     due: Option<String>,
@@ -354,25 +476,62 @@ struct GoalData<'a> {
     tries: Option<i16>,
     weight: f32,
     score: Option<&'a str>,
+    /// Tags are changed through their own actions, not by updating goals.
+    #[serde(skip_deserializing)]
+    tags: &'a [Tag],
+    /// Optional breakdown of the `score`.
+    #[serde(default)]
+    rubric: Vec<RubricItem>,
+    /// Which part of a split chapter this is (and of how many); goals are
+    /// split through their own action.
+    #[serde(skip_deserializing)]
+    part: Option<i16>,
+    #[serde(skip_deserializing)]
+    parts: Option<i16>,
+    /// How far along (in percent) an unfinished goal is; this is set
+    /// through its own action.
+    #[serde(skip_deserializing)]
+    progress: Option<i16>,
+    /// Whether the student has asked for the goal to be marked done.
+    #[serde(skip_deserializing)]
+    requested: bool,
+    /// Where any request for a printed test of the chapter stands.
+    #[serde(skip_deserializing)]
+    test: Option<TestStatus>,
 }
 
 impl<'a> GoalData<'a> {
     /// Called upon receipt of new or updated information about a `Goal` from
     /// the frontend to convert that data into the appropriate internal format.
     fn into_goal(self) -> Result<Goal, String> {
-        let source = BookCh {
-            sym: self.sym.to_owned(),
-            seq: self.seq,
-            // doesn't matter on insertion
-            level: 0.0,
+        let source = match self.custom {
+            Some(id) => Source::Custom(CustomCh(id)),
+            None => Source::Book(BookCh {
+                sym: self.sym.to_owned(),
+                seq: self.seq,
+                // doesn't matter on insertion
+                level: 0.0,
+            }),
         };
 
         let _ = maybe_parse_score_str(self.score)?;
+        check_rubric(&self.rubric)?;
+        if !self.rubric.is_empty() && self.score.is_none() {
+            return Err("A rubric breakdown needs a score to break down.".to_owned());
+        }
+        let rubric: Vec<RubricItem> = self
+            .rubric
+            .into_iter()
+            .map(|item| RubricItem {
+                name: item.name.trim().to_owned(),
+                ..item
+            })
+            .collect();
 
         let g = Goal {
             id: self.id,
             uname: self.uname.to_owned(),
-            source: Source::Book(source),
+            source,
             review: self.rev,
             incomplete: self.inc,
             due: maybe_parse_date(self.due.as_deref())
@@ -382,6 +541,12 @@ impl<'a> GoalData<'a> {
             tries: self.tries,
             weight: self.weight,
             score: self.score.map(|s| s.to_owned()),
+            tags: Vec::new(),
+            rubric,
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
         };
 
         Ok(g)
@@ -389,14 +554,20 @@ impl<'a> GoalData<'a> {
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-struct PaceData<'a> {
+pub(super) struct PaceData<'a> {
     uname: &'a str,
+    #[serde(serialize_with = "anon::ser::last")]
     last: &'a str,
+    #[serde(serialize_with = "anon::ser::rest")]
     rest: &'a str,
     tuname: &'a str,
     total_weight: f32,
     due_weight: f32,
     done_weight: f32,
+    /// Weight of the partial progress on unfinished goals (see
+    /// [`Pace::progress_weight`]); not part of `done_weight`.
+    #[serde(skip_deserializing)]
+    progress_weight: f32,
     goals: Vec<GoalData<'a>>,
     /// Fall/Spring exams
     fex: Option<&'a str>,
@@ -406,29 +577,29 @@ struct PaceData<'a> {
     /// Fall/Spring notices
     fnot: i16,
     snot: i16,
+    /// Chapters the student has been excused from (changed through their
+    /// own action)
+    #[serde(skip_deserializing)]
+    waived: &'a [Waiver],
 }
 
 impl<'a> PaceData<'a> {
     /// Marshal the data from a student's pace calendar in a way that can
     /// get serialized for transmission to the frontend.
-    pub fn from_pace(pcal: &'a Pace) -> Result<PaceData, String> {
+    pub fn from_pace(pcal: &'a Pace) -> Result<PaceData<'a>, String> {
         let mut goals: Vec<GoalData> = Vec::with_capacity(pcal.goals.len());
         for g in pcal.goals.iter() {
-            let src = match &g.source {
-                Source::Book(bch) => bch,
-                _ => {
-                    return Err(format!(
-                        "Student {:?} ({}, {}) has Goal w/ (unsupported) custom Source.",
-                        &pcal.student.base.uname, &pcal.student.last, &pcal.student.rest
-                    ));
-                }
+            let (sym, seq, custom) = match &g.source {
+                Source::Book(bch) => (bch.sym.as_str(), bch.seq, None),
+                Source::Custom(CustomCh(id)) => ("", 0, Some(*id)),
             };
 
             let gdat = GoalData {
                 id: g.id,
                 uname: "",
-                sym: &src.sym,
-                seq: src.seq,
+                sym,
+                seq,
+                custom,
                 rev: g.review,
                 inc: g.incomplete,
                 due: g.due.map(|d| d.to_string()),
@@ -436,6 +607,13 @@ impl<'a> PaceData<'a> {
                 tries: g.tries,
                 weight: g.weight,
                 score: g.score.as_deref(),
+                tags: &g.tags,
+                rubric: g.rubric.clone(),
+                part: g.part.map(|p| p.n),
+                parts: g.part.map(|p| p.of),
+                progress: g.progress.filter(|_| g.done.is_none()),
+                requested: g.requested,
+                test: g.test,
             };
 
             goals.push(gdat);
@@ -449,6 +627,7 @@ impl<'a> PaceData<'a> {
             total_weight: pcal.total_weight,
             due_weight: pcal.due_weight,
             done_weight: pcal.done_weight,
+            progress_weight: pcal.progress_weight(),
             goals,
             fex: pcal.student.fall_exam.as_deref(),
             sex: pcal.student.spring_exam.as_deref(),
@@ -456,10 +635,42 @@ impl<'a> PaceData<'a> {
             sex_frac: pcal.student.spring_exam_fraction,
             fnot: pcal.student.fall_notices,
             snot: pcal.student.spring_notices,
+            waived: &pcal.waived,
         };
 
         Ok(pdat)
     }
+
+    /// Blank out the scores, rubrics, exam scores, and notices, like
+    /// [`PaceDisplay::hide_scores`](crate::pace::PaceDisplay::hide_scores),
+    /// for sending to the student when they aren't supposed to see them.
+    pub(super) fn hide_scores(&mut self) {
+        for g in self.goals.iter_mut() {
+            g.score = None;
+            g.rubric.clear();
+        }
+        self.fex = None;
+        self.sex = None;
+        self.fnot = 0;
+        self.snot = 0;
+    }
+}
+
+/// Marshal a bunch of pace calendars for the frontend, skipping (and
+/// logging) any that won't go.
+fn paces_data(paces: &[Pace]) -> Vec<PaceData<'_>> {
+    let mut pace_data: Vec<PaceData> = Vec::with_capacity(paces.len());
+    for p in paces.iter() {
+        match PaceData::from_pace(p) {
+            Ok(pd) => {
+                pace_data.push(pd);
+            }
+            Err(e) => {
+                log::error!("{}", &e);
+            }
+        }
+    }
+    pace_data
 }
 
 /**
@@ -470,28 +681,153 @@ The header to get this:
 ```
 x-camp-action: populate-goals
 ```
+
+An optional `x-camp-tags` header with a comma-separated list of tag ids
+(like `3,7`) limits the response to goals with at least one of those tags
+(and students with at least one such goal).
+
+Like `populate-courses` and `bootstrap` responses, the response has an
+`x-camp-courses` header identifying the state of the course data (see
+[`Glob::course_token`]). Clients that send it back with requests that use
+course weights get a CONFLICT response if the course data has changed
+since.
 */
 async fn populate_goals(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
-    let uname = match get_head("x-camp-uname", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return text_500(Some(e)); }
+    let uname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; }
+    };
+
+    let tag_filter: Option<Vec<i64>> = match headers.get("x-camp-tags") {
+        None => None,
+        Some(_) => {
+            let tag_str = match get_head("x-camp-tags", headers) {
+                Ok(s) => s,
+                Err(e) => { return respond_bad_request(e); }
+            };
+            let ids: Result<Vec<i64>, _> = tag_str
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse::<i64>())
+                .collect();
+            match ids {
+                Ok(ids) => Some(ids),
+                Err(e) => {
+                    return respond_bad_request(format!(
+                        "Unable to parse x-camp-tags value {:?} as tag ids: {}",
+                        tag_str, &e
+                    ));
+                }
+            }
+        }
     };
 
-    let pace_cals = match glob.read().await.get_paces_by_teacher(uname).await {
+    let glob = glob.read().await;
+    let mut pace_cals = match glob.get_paces_by_teacher(&uname).await {
         Ok(goals) => goals,
         Err(e) => {
             return text_500(Some(format!("{}", &e)));
         }
     };
 
-    let mut pace_data: Vec<PaceData> = Vec::with_capacity(pace_cals.len());
-    for p in pace_cals.iter() {
-        match PaceData::from_pace(p) {
-            Ok(pd) => {
-                pace_data.push(pd);
+    if let Some(ids) = tag_filter {
+        for p in pace_cals.iter_mut() {
+            p.goals
+                .retain(|g| g.tags.iter().any(|t| ids.contains(&t.id)));
+        }
+        pace_cals.retain(|p| !p.goals.is_empty());
+    }
+
+    if fragment::requested(headers) {
+        return match fragment::pace_tables(&pace_cals, &glob) {
+            Ok(html) => with_course_token(fragment::respond("populate-goals", html), &glob),
+            Err(e) => {
+                log::error!("Error rendering pace tables for {:?}: {}", uname, &e);
+                text_500(Some(format!("Unable to render pace tables: {}", &e)))
+            }
+        };
+    }
+
+    let pace_data = paces_data(&pace_cals);
+
+    let response = (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-goals"),
+        )],
+        Json(pace_data),
+    )
+        .into_response();
+    with_course_token(response, &glob)
+}
+
+/**
+Respond with the pace data of just the Teacher's students who have changed
+(or had their goals changed) since a given time, and the unames of the
+students who have left the Teacher's class since then, so the frontend can
+keep up to date without downloading everything again.
+
+```text
+x-camp-action: changes-since
+```
+
+The body should be the `as_of` value from the `bootstrap` response (or from
+the last `changes-since` response), like `2022-10-03 14:05:09.123456`. With
+no body, all the Teacher's students count as changed. The response looks
+like
+```json
+{
+    "as_of": "2022-10-03 14:06:11.48319",
+    "goals": [ /* pace data, like from populate-goals */ ],
+    "departed": ["zack"]
+}
+```
+Replacing the paces of the students in `goals` (wholesale; some of their
+goals may have been deleted) and dropping those in `departed` brings the
+frontend up to date as of `as_of`. Changes to courses aren't covered; see
+[`store::Changes`](crate::store::Changes).
+*/
+async fn changes_since(tuname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let since = match body.as_deref().map(str::trim) {
+        None | Some("") => time::macros::datetime!(1970 - 01 - 01 0:00),
+        Some(s) => match PrimitiveDateTime::parse(s, SYNC_TIME_FMT) {
+            Ok(t) => t,
+            Err(e) => {
+                return respond_bad_request(format!(
+                    "Unable to parse {:?} as a sync timestamp: {}", s, &e
+                ));
             }
+        },
+    };
+
+    let glob = glob.read().await;
+    let res = glob.data().read().await.get_changes_since(tuname, &since).await;
+    let changes = match res {
+        Ok(changes) => changes,
+        Err(e) => {
+            log::error!("Error getting changes for {:?} since {}: {}", tuname, &since, &e);
+            return text_500(Some(format!("Unable to check for changes: {}", &e)));
+        }
+    };
+    let as_of = match changes.as_of.format(SYNC_TIME_FMT) {
+        Ok(as_of) => as_of,
+        Err(e) => {
+            log::error!("Error formatting sync timestamp {}: {}", &changes.as_of, &e);
+            return text_500(Some(format!("Unable to format sync timestamp: {}", &e)));
+        }
+    };
+
+    let mut paces: Vec<Pace> = Vec::with_capacity(changes.changed.len());
+    for uname in changes.changed.iter() {
+        match glob.get_pace_by_student(uname).await {
+            Ok(p) => { paces.push(p); },
             Err(e) => {
-                log::error!("{}", &e);
+                log::error!("Error getting Pace for student {:?}: {}", uname, &e);
+                return text_500(Some(format!(
+                    "Error retrieving Pace for {:?} from database: {}", uname, &e
+                )));
             }
         }
     }
@@ -500,9 +836,13 @@ async fn populate_goals(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Respons
         StatusCode::OK,
         [(
             HeaderName::from_static("x-camp-action"),
-            HeaderValue::from_static("populate-goals"),
+            HeaderValue::from_static("changes-since"),
         )],
-        Json(pace_data),
+        Json(json!({
+            "as_of": as_of,
+            "goals": paces_data(&paces),
+            "departed": &changes.departed,
+        })),
     )
         .into_response()
 }
@@ -521,15 +861,99 @@ async fn populate_traits(glob: Arc<RwLock<Glob>>) -> Response {
         .into_response()
 }
 
+/**
+Respond with everything the Teacher page needs when it first loads, so it
+doesn't have to make a `populate-*` request for each piece.
+
+Header:
+```text
+x-camp-action: bootstrap
+```
+
+The response body is a JSON object with the fields
+```json
+{
+    "dates": { /* as populate-dates */ },
+    "courses": [ /* as populate-courses */ ],
+    "traits": [ /* as populate-traits */ ],
+    "goals": [ /* as populate-goals */ ],
+    "tags": [ /* as populate-tags */ ],
+    "customs": [ /* as populate-custom-chapters */ ],
+    "announcements": [ /* see announce::active() */ ]
+}
+```
+The goals, tags, and announcements are fetched from the database
+concurrently.
+*/
+async fn bootstrap(uname: &str, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let data = glob.data();
+    let data = data.read().await;
+
+    let (as_of, paces, tags, announcements) = tokio::join!(
+        data.get_sync_timestamp(),
+        glob.get_paces_by_teacher(uname),
+        data.get_tags(uname),
+        announce::active(Role::Teacher, &glob)
+    );
+    let as_of = match as_of.map_err(|e| e.to_string())
+        .and_then(|t| t.format(SYNC_TIME_FMT).map_err(|e| e.to_string()))
+    {
+        Ok(as_of) => as_of,
+        Err(e) => {
+            log::error!("Error getting sync timestamp: {}", &e);
+            return text_500(Some(format!("Unable to get sync timestamp: {}", &e)));
+        }
+    };
+    let paces = match paces {
+        Ok(paces) => paces,
+        Err(e) => {
+            return text_500(Some(format!("{}", &e)));
+        }
+    };
+    let tags = match tags {
+        Ok(tags) => tags,
+        Err(e) => {
+            log::error!("Error retrieving tags for {:?}: {}", uname, &e);
+            return text_500(Some(format!("Unable to retrieve tags: {}", &e)));
+        }
+    };
+
+    let response = (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("bootstrap"),
+        )],
+        Json(json!({
+            "dates": dates_data(&glob),
+            "courses": courses_data(&glob),
+            "traits": &glob.social_traits,
+            "goals": paces_data(&paces),
+            "tags": tags,
+            "customs": customs_data(uname, &glob),
+            "announcements": announcements,
+            "as_of": as_of,
+        })),
+    )
+        .into_response();
+    with_course_token(response, &glob)
+}
+
 /**
 Send a single pace calendar's worth of data (for student `uname`) to
 the frontend.
 
 This is generally called by a handler function to generate a response when
 the teacher makes a request that alter's a student's pace calendar in some way.
+
+If the request asked for an HTML fragment (see [`fragment`]), the response
+will be the rendered pace table instead of JSON.
 */
-async fn update_pace(uname: &str, glob: Arc<RwLock<Glob>>) -> Response {
-    let p = match glob.read().await.get_pace_by_student(uname).await {
+async fn update_pace(uname: &str, headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob_lock = Arc::clone(&glob);
+    let glob = glob.read().await;
+    let p = match glob.get_pace_by_student(uname).await {
         Ok(p) => p,
         Err(e) => {
             log::error!("Error getting Pace for student {:?}: {}", uname, &e);
@@ -539,6 +963,17 @@ async fn update_pace(uname: &str, glob: Arc<RwLock<Glob>>) -> Response {
             )));
         }
     };
+    completion::notify_if_complete(&p, &glob, &glob_lock).await;
+
+    if fragment::requested(headers) {
+        return match fragment::pace_table(&p, &glob) {
+            Ok(html) => fragment::respond("update-pace", html),
+            Err(e) => {
+                log::error!("Error rendering pace table for {:?}: {}", uname, &e);
+                text_500(Some(format!("Unable to render pace table: {}", &e)))
+            }
+        };
+    }
 
     let pdata = match PaceData::from_pace(&p) {
         Ok(pdata) => pdata,
@@ -558,6 +993,99 @@ async fn update_pace(uname: &str, glob: Arc<RwLock<Glob>>) -> Response {
         .into_response()
 }
 
+/**
+Check whether giving the student `g` (a new `Goal`, or a changed version
+of one they already have) would break its course's scheduling
+[`Constraints`](crate::course::Constraints).
+
+Only violations that involve `g`'s own chapter count; a pace that was
+already out of line elsewhere shouldn't keep the Teacher from fixing it one
+`Goal` at a time. `Goal`s whose due dates aren't changing pass.
+*/
+async fn check_goal_constraints(g: &Goal, glob: &Glob) -> Result<(), Response> {
+    let (bch, due) = match (&g.source, g.due) {
+        (Source::Book(bch), Some(due)) => (bch, due),
+        _ => { return Ok(()); }
+    };
+    match glob.course_by_sym(&bch.sym) {
+        Some(crs) if !crs.constraints.is_empty() => {},
+        _ => { return Ok(()); }
+    }
+
+    let mut p = match glob.get_pace_by_student(&g.uname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("Error retrieving pace data for {:?}: {}", &g.uname, &e);
+            return Err(text_500(Some(format!(
+                "Error retrieving pace data from database: {}",
+                &e
+            ))));
+        }
+    };
+    match p.goals.iter_mut().find(|pg| pg.id == g.id) {
+        Some(pg) if pg.due == Some(due) => { return Ok(()); }
+        Some(pg) => { *pg = g.clone(); }
+        None => { p.goals.push(g.clone()); }
+    }
+
+    let messages: Vec<String> = p
+        .constraint_violations(glob)
+        .into_iter()
+        .filter(|v| v.involves(&bch.sym, bch.seq))
+        .map(|v| v.message)
+        .collect();
+    if messages.is_empty() {
+        Ok(())
+    } else {
+        Err(respond_bad_request(messages.join(" ")))
+    }
+}
+
+/// If `g` is a custom goal, ensure its custom chapter exists and was created
+/// by the student's teacher.
+#[allow(clippy::result_large_err)]
+fn check_custom_source(g: &Goal, glob: &Glob) -> Result<(), Response> {
+    let id = match &g.source {
+        Source::Custom(CustomCh(id)) => *id,
+        Source::Book(_) => { return Ok(()); }
+    };
+    let cus = match glob.customs.get(&id) {
+        Some(cus) => cus,
+        None => {
+            return Err(respond_bad_request(format!(
+                "There is no custom chapter with id {}.",
+                &id
+            )));
+        }
+    };
+    match glob.users.get(&g.uname) {
+        Some(User::Student(s)) if s.teacher == cus.uname => Ok(()),
+        Some(User::Student(_)) => Err(respond_bad_request(format!(
+            "Custom chapter {:?} belongs to another teacher.",
+            &cus.title
+        ))),
+        _ => Err(respond_bad_request(format!(
+            "{:?} is not a student user name.",
+            &g.uname
+        ))),
+    }
+}
+
+/// What the audit log says about the material the [`Goal`] `g` covers.
+fn audit_goal_detail(g: &Goal) -> String {
+    match &g.source {
+        Source::Book(b) => format!("{} {}", &b.sym, &b.seq),
+        Source::Custom(CustomCh(id)) => format!("custom {}", id),
+    }
+}
+
+/// Fire a `goal-completed` webhook for `g`, if it's done.
+async fn fire_goal_completed(g: &Goal, glob: &Glob) {
+    if let (Some(done), Some(User::Student(s))) = (g.done, glob.users.get(&g.uname)) {
+        glob.fire_webhook(Event::GoalCompleted, &GoalCompleted::new(s, g, done)).await;
+    }
+}
+
 /**
 Insert a new `Goal` into the database.
 
@@ -567,7 +1095,11 @@ x-camp-action: add-goal
 ```
 And the request body should be JSON-deserializable into a `GoalData`.
 */
-async fn insert_goal(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+async fn insert_goal(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
     let body = match body {
         Some(body) => body,
         None => {
@@ -592,20 +1124,30 @@ async fn insert_goal(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response
         }
     };
 
-    if let Err(e) = glob
-        .read()
-        .await
-        .data()
-        .read()
-        .await
-        .insert_one_goal(&g)
-        .await
-    {
+    if let Err(resp) = check_custom_source(&g, &*glob.read().await) {
+        return resp;
+    }
+    if let Err(resp) = check_goal_constraints(&g, &*glob.read().await).await {
+        return resp;
+    }
+    if let Err(resp) = check_goal_absences(&g, headers, &*glob.read().await).await {
+        return resp;
+    }
+    if let Err(resp) = check_goal_exam_windows(&g, &*glob.read().await).await {
+        return resp;
+    }
+
+    if let Err(e) = glob.read().await.store().insert_one_goal(&g).await {
         log::error!("Error inserting Goal {:?} into database: {}", &g, &e);
         return text_500(Some(format!("Error inserting Goal into database: {}", &e)));
     }
+    if let Ok(CampUname(tuname)) = CampUname::from_headers(headers) {
+        let detail = audit_goal_detail(&g);
+        glob.read().await.log_action(&tuname, "add-goal", &g.uname, Some(&detail)).await;
+    }
+    fire_goal_completed(&g, &*glob.read().await).await;
 
-    update_pace(&g.uname, glob).await
+    update_pace(&g.uname, headers, glob).await
 }
 
 /**
@@ -618,7 +1160,11 @@ x-camp-action: update-goal
 The body of the request should be JSON-deserializable into a `GoalData` with
 the `id` of the [`Goal`] to change and the updated data.
 */
-async fn update_goal(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+async fn update_goal(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
     let body = match body {
         Some(body) => body,
         None => {
@@ -643,12 +1189,52 @@ async fn update_goal(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response
         }
     };
 
-    if let Err(e) = glob.read().await.data().read().await.update_goal(&g).await {
+    if let Err(resp) = check_custom_source(&g, &*glob.read().await) {
+        return resp;
+    }
+    if let Err(resp) = check_goal_constraints(&g, &*glob.read().await).await {
+        return resp;
+    }
+    if let Err(resp) = check_goal_absences(&g, headers, &*glob.read().await).await {
+        return resp;
+    }
+    if let Err(resp) = check_goal_exam_windows(&g, &*glob.read().await).await {
+        return resp;
+    }
+
+    // Only a goal that wasn't already done fires a webhook.
+    let was_done = match g.done {
+        None => true,
+        Some(_) => match glob.read().await.store().get_goals_by_student(&g.uname).await {
+            Ok(goals) => goals.iter().any(|old| old.id == g.id && old.done.is_some()),
+            Err(e) => {
+                log::error!("Error retrieving goals of {:?}: {}", &g.uname, &e);
+                true
+            }
+        },
+    };
+
+    if let Err(e) = glob.read().await.store().update_goal(&g).await {
         log::error!("Error inserting Goal {:?} into database: {}", &g, &e);
         return text_500(Some(format!("Error inserting Goal into database: {}", &e)));
     }
+    if let Ok(CampUname(tuname)) = CampUname::from_headers(headers) {
+        let detail = format!("#{}: {}", &g.id, &audit_goal_detail(&g));
+        glob.read().await.log_action(&tuname, "update-goal", &g.uname, Some(&detail)).await;
+    }
+    if !was_done {
+        fire_goal_completed(&g, &*glob.read().await).await;
+    }
+
+    update_pace(&g.uname, headers, glob).await
+}
 
-    update_pace(&g.uname, glob).await
+/// Deserializes the body of a `delete-goal` request (unless it's just an id).
+#[derive(Debug, Deserialize)]
+struct DeleteGoalData {
+    id: i64,
+    #[serde(default)]
+    force: bool,
 }
 
 /**
@@ -658,9 +1244,16 @@ Header that gets us here:
 ```
 x-camp-action: delete-goal
 ```
-With a body parseable into the `id` of the [`Goal`] to delete.
+With a body parseable into the `id` of the [`Goal`] to delete, or that
+JSON-deserializes into a `DeleteGoalData`. Goals that have been completed or
+scored are refused (with 409 CONFLICT) unless `force` is `true`, which has
+to be confirmed (see [`confirm`](super::confirm)).
 */
-async fn delete_goal(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+async fn delete_goal(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
     let body = match body {
         Some(body) => body,
         None => {
@@ -670,23 +1263,50 @@ async fn delete_goal(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response
         }
     };
 
-    let id: i64 = match &body.parse() {
-        Ok(n) => *n,
-        Err(e) => {
-            log::error!("Error deserializing {:?} as i64: {}", &body, &e);
-            return text_500(Some("Unable to deserialize into integer.".to_owned()));
-        }
+    let ddata = match body.parse::<i64>() {
+        Ok(id) => DeleteGoalData { id, force: false },
+        Err(_) => match serde_json::from_str::<DeleteGoalData>(&body) {
+            Ok(ddata) => ddata,
+            Err(e) => {
+                log::error!("Error deserializing {:?} as DeleteGoalData: {}", &body, &e);
+                return respond_bad_request(
+                    "Body must be a Goal id or JSON with \"id\" and \"force\".".to_owned(),
+                );
+            }
+        },
+    };
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; }
     };
 
-    let uname = match glob.read().await.data().read().await.delete_goal(id).await {
-        Ok(uname) => uname,
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .delete_goal(ddata.id, ddata.force, &tuname)
+        .await;
+    let uname = match res {
+        Ok(GoalDeletion::Deleted(uname)) => uname,
+        Ok(GoalDeletion::Protected) => {
+            return ApiError::conflict(format!(
+                "Goal #{} has been completed; deleting it would remove it from the \
+                student's grades, so it must be confirmed.",
+                &ddata.id
+            ))
+            .into_response();
+        }
         Err(e) => {
-            log::error!("Error deleting Goal w/id {} from database: {}", &id, &e);
+            log::error!("Error deleting Goal w/id {} from database: {}", &ddata.id, &e);
             return text_500(Some(format!("Error deleting from database: {}", &e)));
         }
     };
+    let detail = format!("#{}", &ddata.id);
+    glob.read().await.log_action(&tuname, "delete-goal", &uname, Some(&detail)).await;
 
-    update_pace(&uname, glob).await
+    update_pace(&uname, headers, glob).await
 }
 
 /**
@@ -701,7 +1321,11 @@ x-camp-action: update-numbers
 The body should JSON-deserialize into a `PaceData` struct with the six values
 in question set appropriately.
 */
-async fn update_numbers(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+async fn update_numbers(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
     let body = match body {
         Some(body) => body,
         None => {
@@ -797,7 +1421,7 @@ async fn update_numbers(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Respon
         }
     }
 
-    update_pace(pdata.uname, glob).await
+    update_pace(pdata.uname, headers, glob).await
 }
 
 /**
@@ -807,9 +1431,20 @@ Header:
 ```
 x-camp-action: autopace
 ```
-With a body containing the `uname` of the student to autopace.
+With a body containing the `uname` of the student to autopace. Nothing is
+made due during exam windows, and if the request has an
+`x-camp-avoid-absences` header (see [`avoids_absences`]), no due dates are
+put on days the student will be absent, either.
+
+Autopacing doesn't know about courses' scheduling constraints, so if the
+new due dates break any, the response is the usual `update-pace` one, with
+the problems described in an `x-camp-warning` header.
 */
-async fn autopace(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+async fn autopace(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
     let body = match body {
         Some(body) => body,
         None => {
@@ -819,7 +1454,7 @@ async fn autopace(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
 
     let uname: &str = &body;
 
-    {
+    let violations = {
         let glob = glob.read().await;
         let mut p = match glob.get_pace_by_student(uname).await {
             Ok(p) => p,
@@ -832,7 +1467,15 @@ async fn autopace(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
             }
         };
 
-        if let Err(e) = p.autopace(&glob.calendar) {
+        let res = if avoids_absences(headers) {
+            match student_absences(uname, &glob).await {
+                Ok(absences) => p.autopace_around(&glob.calendar, &absences),
+                Err(resp) => { return resp; }
+            }
+        } else {
+            p.autopace(&glob.calendar)
+        };
+        if let Err(e) = res {
             log::error!(
                 "Error calling Pace::autopace( [ {} dates ] ) for {:?}: {}",
                 &glob.calendar.len(),
@@ -842,18 +1485,31 @@ async fn autopace(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
             return text_500(Some(format!("Error pacing due dates: {}", &e)));
         }
 
-        let data = glob.data();
-        if let Err(e) = data.read().await.update_due_dates(&p.goals).await {
+        if let Err(e) = glob.store().update_due_dates(&p.goals).await {
             log::error!("Error updating dates from {:?}: {}", &p, &e);
             return text_500(Some(format!(
                 "Error updating due dates in database: {}",
                 &e
             )));
         };
-    }
 
-    update_pace(uname, glob).await
-}
+        p.constraint_violations(&glob)
+    };
+
+    let mut resp = update_pace(uname, headers, glob).await;
+    if !violations.is_empty() {
+        let warning = violations
+            .iter()
+            .map(|v| v.message.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if let Ok(hval) = HeaderValue::from_str(&warning) {
+            resp.headers_mut()
+                .insert(HeaderName::from_static("x-camp-warning"), hval);
+        }
+    }
+    resp
+}
 
 /**
 Respond to a request to delete all of a student's goals.
@@ -864,7 +1520,11 @@ x-camp-action: clear-goals
 ```
 With a body containing the `uname` of the student in question.
 */
-async fn clear_goals(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+async fn clear_goals(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
     let body = match body {
         Some(body) => body,
         None => {
@@ -895,7 +1555,14 @@ async fn clear_goals(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response
             }
         };
 
-        if let Err(e) = data_reader.delete_goals_by_student(&t, uname).await {
+        let tuname = match CampUname::from_headers(headers) {
+            Ok(CampUname(uname)) => uname,
+            Err(resp) => { return resp; }
+        };
+        if let Err(e) = data_reader
+            .delete_goals_by_student(&t, uname, Some(&tuname))
+            .await
+        {
             log::error!("Error deleting goals for {:?}: {}", uname, &e);
             return text_500(Some(format!("Error deleting goals: {}", &e)));
         }
@@ -906,21 +1573,88 @@ async fn clear_goals(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response
         }
     }
 
-    update_pace(uname, glob).await
+    update_pace(uname, headers, glob).await
+}
+
+/// Deserializes the body of a `delete-goals` request.
+#[derive(Debug, Deserialize)]
+struct DeleteGoalsData {
+    uname: String,
+    sym: Option<String>,
+    term: Option<String>,
+    #[serde(default)]
+    unstarted: bool,
+}
+
+/// The range of due dates, as `(on or after, before)`, of goals that fall
+/// in `term`.
+fn term_due_range(term: Term, end_fall: Date, end_spring: Date) -> (Option<Date>, Option<Date>) {
+    match term {
+        Term::Fall => (None, Some(end_fall)),
+        Term::Spring => (Some(end_fall), Some(end_spring)),
+        Term::Summer => (Some(end_spring), None),
+    }
+}
+
+/// Turn the body of a `delete-goals` request into a [`GoalFilter`].
+fn goal_filter<'a>(
+    ddata: &'a DeleteGoalsData,
+    dates: &HashMap<String, Date>,
+) -> Result<GoalFilter<'a>, String> {
+    let (due_from, due_before) = match ddata.term.as_deref() {
+        None => (None, None),
+        Some(s) => {
+            let term: Term = s.parse()?;
+            match (dates.get("end-fall"), dates.get("end-spring")) {
+                (Some(f), Some(s)) => term_due_range(term, *f, *s),
+                _ => {
+                    return Err("Semester end dates have not been set by Admin.".to_owned());
+                }
+            }
+        }
+    };
+
+    let filter = GoalFilter {
+        sym: ddata.sym.as_deref(),
+        due_from,
+        due_before,
+        unstarted: ddata.unstarted,
+    };
+    if filter.is_empty() {
+        return Err(
+            "Specify a course, a term, or unstarted goals (or use \"clear all goals\").".to_owned(),
+        );
+    }
+
+    Ok(filter)
 }
 
 /**
-Respond to a request to add a collection of goals from information in CSV
-format.
+Respond to a request to delete only some of a student's goals, like those
+added by mistake in a bulk upload.
 
 Header:
+```text
+x-camp-action: delete-goals
 ```
-x-camp-action: upload-goals
+With a JSON body like
+```json
+{
+    "uname": "jsmith",
+    "sym": "pha1",      // optional; only goals from this course
+    "term": "spring",   // optional; only goals due this term
+    "unstarted": true   // optional; only goals with no done date, score, or tries
+}
 ```
-With the body being the CSV data in question. For the CSV format, see
-[`Pace::from_csv`].
+At least one of the conditions must be given. Goals that match them all are
+deleted in a single transaction (completed ones are archived first, as with
+`clear-goals`), and the response is the usual `update-pace` one, with the
+numbers of goals deleted and archived in the `x-camp-deleted` and
+`x-camp-archived` headers. Like `clear-goals`, this has to be confirmed
+first (see [`confirm`](super::confirm)).
 */
-async fn upload_goals(
+async fn delete_goals(
+    tuname: &str,
     headers: &HeaderMap,
     body: Option<String>,
     glob: Arc<RwLock<Glob>>,
@@ -928,61 +1662,133 @@ async fn upload_goals(
     let body = match body {
         Some(body) => body,
         None => {
-            return respond_bad_request("Request needs text/csv body of Goal details.".to_owned());
+            return respond_bad_request(
+                "Request needs application/json body with goals to delete.".to_owned(),
+            );
         }
     };
-
-    let tuname = match get_head("x-camp-uname", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return text_500(Some(e)); },
+    let ddata: DeleteGoalsData = match serde_json::from_str(&body) {
+        Ok(ddata) => ddata,
+        Err(e) => {
+            log::error!("Error deserializing {:?} as DeleteGoalsData: {}", &body, &e);
+            return respond_bad_request(format!("Unable to read deletion details: {}", &e));
+        }
     };
 
-    let mut others_students = String::new();
-    let mut goals: Vec<Goal> = Vec::new();
-    {
+    let counts = {
         let glob = glob.read().await;
-
-        let reader = Cursor::new(body);
-        let mut pcals = match Pace::from_csv(reader, &glob) {
-            Ok(pcals) => pcals,
+        match glob.users.get(&ddata.uname) {
+            Some(User::Student(s)) if s.teacher == tuname => {}
+            _ => {
+                return respond_bad_request(format!("{:?} is not your student.", &ddata.uname));
+            }
+        }
+        let filter = match goal_filter(&ddata, &glob.dates) {
+            Ok(filter) => filter,
             Err(e) => {
                 return respond_bad_request(e);
             }
         };
 
-        for p in pcals.iter_mut() {
-            if p.teacher.base.uname == tuname {
-                goals.append(&mut p.goals);
-            } else {
-                others_students.push('\n');
-                others_students.push_str(&p.student.base.uname);
+        let data = glob.data();
+        let data_reader = data.read().await;
+        let mut client = match data_reader.connect().await {
+            Ok(client) => client,
+            Err(e) => {
+                let estr = format!("Error connecting to database: {}", &e);
+                log::error!("{}", &estr);
+                return text_500(Some(estr));
             }
-        }
-
-        if !others_students.is_empty() {
-            let mut estr = String::from(
-                "The following students with Goals in the Goals file you just submitted are not yours:"
-            );
-            estr.extend(others_students.drain(..));
-
-            return (StatusCode::FORBIDDEN, estr).into_response();
-        }
-
-        match glob.insert_goals(&goals).await {
-            Ok(n) => {
-                log::trace!("{} inserted {} goals.", tuname, &n);
+        };
+        let t = match client.transaction().await {
+            Ok(t) => t,
+            Err(e) => {
+                let estr = format!("Error beginning transaction: {}", &e);
+                log::error!("{}", &estr);
+                return text_500(Some(estr));
             }
+        };
+
+        let counts = match data_reader
+            .delete_goals_where(&t, &ddata.uname, &filter, Some(tuname))
+            .await
+        {
+            Ok(counts) => counts,
             Err(e) => {
-                log::error!("Error inserting Goals: {}", &e);
-                return text_500(Some(format!("Error inserting Goals into database: {}", &e)));
+                log::error!("Error deleting goals for {:?} ({:?}): {}", &ddata.uname, &filter, &e);
+                return text_500(Some(format!("Error deleting goals: {}", &e)));
             }
+        };
+
+        if let Err(e) = t.commit().await {
+            log::error!("Error committing delete-goals transaction: {}", &e);
+            return text_500(Some(format!("Error committing transaction: {}", &e)));
         }
-    }
+        log::info!(
+            "{:?} deleted {} goals ({} archived) of {:?} matching {:?}.",
+            tuname,
+            &counts.deleted,
+            &counts.archived,
+            &ddata.uname,
+            &filter
+        );
+
+        counts
+    };
 
-    populate_goals(headers, glob).await
+    let mut resp = update_pace(&ddata.uname, headers, glob).await;
+    let resp_headers = resp.headers_mut();
+    resp_headers.insert(
+        HeaderName::from_static("x-camp-deleted"),
+        HeaderValue::from(counts.deleted),
+    );
+    resp_headers.insert(
+        HeaderName::from_static("x-camp-archived"),
+        HeaderValue::from(counts.archived),
+    );
+    resp
 }
 
-async fn show_sidecar(
+/// Deserializes the body of an `enroll-course` request.
+#[derive(Debug, Deserialize)]
+struct EnrollData {
+    uname: String,
+    sym: String,
+    first: Option<i16>,
+    last: Option<i16>,
+    start: Option<String>,
+    end: Option<String>,
+}
+
+/**
+Respond to a request to assign a student every chapter (or a range of
+chapters) of a course at once, autopaced between optional start and end
+dates.
+
+Header:
+```text
+x-camp-action: enroll-course
+```
+With a JSON body like
+```json
+{
+    "uname": "jsmith",
+    "sym": "pha1",
+    "first": 1,             // optional
+    "last": 8,              // optional
+    "start": "2022-09-06",  // optional
+    "end": "2023-01-20"     // optional
+}
+```
+Nothing is made due during exam windows, and if the request has an
+`x-camp-avoid-absences` header (see [`avoids_absences`]), no due dates are
+put on days the student will be absent, either.
+
+The same body sent with `x-camp-action: suggest-schedule` gets a suggested
+schedule instead, based on how long the course's chapters have taken other
+students (see [`suggest`]).
+*/
+async fn enroll_course(
     headers: &HeaderMap,
     body: Option<String>,
     glob: Arc<RwLock<Glob>>,
@@ -990,63 +1796,129 @@ async fn show_sidecar(
     let body = match body {
         Some(body) => body,
         None => {
-            return respond_bad_request("Request needs student user name in body.".to_owned());
+            return respond_bad_request(
+                "Request needs application/json body with enrollment details.".to_owned(),
+            );
         }
     };
 
-    let uname = &body;
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
 
-    let tuname = match get_head("x-camp-uname", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return text_500(Some(e)); },
+    let edata: EnrollData = match serde_json::from_str(&body) {
+        Ok(edata) => edata,
+        Err(e) => {
+            log::error!("Error deserializing {:?} as EnrollData: {}", &body, &e);
+            return respond_bad_request(format!("Unable to read enrollment details: {}", &e));
+        }
     };
 
-    let glob = glob.read().await;
+    let start = match maybe_parse_date(edata.start.as_deref()) {
+        Ok(d) => d,
+        Err(e) => { return respond_bad_request(format!("Bad start date: {}", &e)); },
+    };
+    let end = match maybe_parse_date(edata.end.as_deref()) {
+        Ok(d) => d,
+        Err(e) => { return respond_bad_request(format!("Bad end date: {}", &e)); },
+    };
+    let chapters = match (edata.first, edata.last) {
+        (None, None) => None,
+        (first, last) => Some((first.unwrap_or(i16::MIN), last.unwrap_or(i16::MAX))),
+    };
 
-    match glob.users.get(uname) {
-        Some(User::Student(s)) => {
-            if s.teacher != tuname {
-                let estr = format!("The student {:?} is not yours.", uname);
-                return (StatusCode::FORBIDDEN, estr).into_response();
+    {
+        let glob = glob.read().await;
+
+        match glob.users.get(&edata.uname) {
+            Some(User::Student(s)) => {
+                if s.teacher != tuname {
+                    let estr = format!("The student {:?} is not yours.", &edata.uname);
+                    return ApiError::forbidden(estr).into_response();
+                }
+            }
+            _ => {
+                return respond_bad_request(format!(
+                    "The uname {:?} does not belong to a student in the system.",
+                    &edata.uname
+                ));
             }
         }
-        _ => {
-            let estr = format!(
-                "The uname {:?} does not belong to a student in the system.",
-                uname
-            );
-            return respond_bad_request(estr);
+
+        let absences = if avoids_absences(headers) {
+            match student_absences(&edata.uname, &glob).await {
+                Ok(absences) => absences,
+                Err(resp) => { return resp; }
+            }
+        } else {
+            Vec::new()
+        };
+
+        match glob
+            .enroll_course(&edata.uname, &edata.sym, chapters, start, end, &absences)
+            .await
+        {
+            Ok(n) => {
+                log::trace!(
+                    "{} enrolled {:?} in {:?} ({} goals).",
+                    tuname, &edata.uname, &edata.sym, &n
+                );
+                glob.defer_course_stats([&edata.sym]);
+            }
+            Err(e) => {
+                log::error!("Error enrolling student: {:?}: {}", &edata, &e);
+                return respond_bad_request(format!("Unable to enroll student: {}", &e));
+            }
         }
     }
 
-    let this_year = glob.academic_year();
-
-    let data_guard = glob.data();
-    let data = data_guard.read().await;
+    update_pace(&edata.uname, headers, glob).await
+}
 
-    let sidecar = match data.get_report_sidecar(uname, this_year).await {
-        Ok(sc) => sc,
-        Err(e) => {
-            log::error!("Error fetching sidecar for student {:?}: {}", uname, &e);
-            return text_500(Some(format!(
-                "Error fetching extra reporting information for {:?}: {}",
-                uname, &e
-            )));
-        }
-    };
+/// Respond to a `waive-chapter` request (see [`set_waiver`]) with the
+/// student's updated pace calendar.
+async fn waive_chapter(
+    tuname: &str,
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let res = set_waiver(body, tuname, Some(tuname), &*glob.read().await).await;
+    match res {
+        Ok(uname) => update_pace(&uname, headers, glob).await,
+        Err(resp) => resp,
+    }
+}
 
-    (
-        StatusCode::OK,
-        [(
-            HeaderName::from_static("x-camp-action"),
-            HeaderValue::from_static("show-sidecar"),
-        )],
-        Json(sidecar),
-    )
-        .into_response()
+/// Deserializes the body of a `split-goal` request.
+#[derive(Debug, Deserialize)]
+struct SplitGoalData {
+    id: i64,
+    /// The due date of each part.
+    due: Vec<Option<String>>,
+    /// Each part's share of the chapter's weight; if empty, the weight is
+    /// split evenly.
+    #[serde(default)]
+    fractions: Vec<f32>,
 }
 
-async fn update_sidecar(
+/**
+Respond to a request to split one goal into several that share its chapter
+(see [`Store::split_goal`]).
+
+Header:
+```text
+x-camp-action: split-goal
+```
+Body should look like
+```json
+{ "id": 1234, "due": ["2022-10-03", "2022-10-17"], "fractions": [0.4, 0.6] }
+```
+with one due date for each part; `fractions` is optional.
+*/
+async fn split_goal(
+    tuname: &str,
     headers: &HeaderMap,
     body: Option<String>,
     glob: Arc<RwLock<Glob>>,
@@ -1055,389 +1927,3052 @@ async fn update_sidecar(
         Some(body) => body,
         None => {
             return respond_bad_request(
-                "Request needs application/json body with ReportSidecar details.".to_owned(),
+                "Request needs application/json body with split details.".to_owned(),
             );
         }
     };
-
-    let sidecar: ReportSidecar = match serde_json::from_str(&body) {
-        Ok(sc) => sc,
+    let sdata: SplitGoalData = match serde_json::from_str(&body) {
+        Ok(sdata) => sdata,
         Err(e) => {
-            log::error!(
-                "Unable to deserialize as ReportSidecar: {}; data:\n{}",
-                &e,
-                &body
-            );
-            let estr = format!(
-                "Request body did not deserialize into ReportSidecar: {}",
-                &e
-            );
-            return respond_bad_request(estr);
+            log::error!("Error deserializing {:?} as SplitGoalData: {}", &body, &e);
+            return respond_bad_request(format!("Unable to read split details: {}", &e));
         }
     };
 
-    let tuname = match get_head("x-camp-uname", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return text_500(Some(e)); },
-    };
-    let term = match get_head("x-camp-term", headers) {
-        Ok(term) => term,
-        Err(e) => { return respond_bad_request(e); }
-    };
-    let term = match term {
-        "fall" => Term::Fall,
-        "spring" => Term::Spring,
-        "summer" => Term::Summer,
-        x => {
-            log::error!(
-                "Header \"x-camp-action\" value {:?} shouldn't make it this far.", x
-            );
-            return respond_bad_request(format!(
-                "Unrecognized x-camp-term value {:?}", x
-            ));
-        },
+    let fractions = match split_fractions(sdata.due.len(), &sdata.fractions) {
+        Ok(fractions) => fractions,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
     };
-
-    let glob = glob.read().await;
-
-    match glob.users.get(&sidecar.uname) {
-        Some(User::Student(s)) => {
-            if s.teacher != tuname {
-                let estr = format!("The student {:?} is not yours.", &sidecar.uname);
-                return (StatusCode::FORBIDDEN, estr).into_response();
+    let mut parts: Vec<(Option<Date>, f32)> = Vec::with_capacity(fractions.len());
+    for (due, fraction) in sdata.due.iter().zip(fractions) {
+        match maybe_parse_date(due.as_deref()) {
+            Ok(due) => parts.push((due, fraction)),
+            Err(e) => {
+                return respond_bad_request(format!("Bad due date: {}", &e));
             }
-        },
-        _ => {
-            let estr = format!(
-                "The uname {:?} does not belong to a student in the system.",
-                &sidecar.uname
-            );
-            return respond_bad_request(estr);
         }
     }
 
-    let this_year = glob.academic_year();
-
-    let data_guard = glob.data();
-    let data = data_guard.read().await;
-
-    if let Err(e) = data.set_report_sidecar(&sidecar, this_year).await {
-        log::error!("Error setting report sidecar: {}\ndata: {:?}", &e, &sidecar);
-        let estr = format!("Error saving report sidecar info: {}", &e);
-        return text_500(Some(estr));
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .split_goal(sdata.id, &parts, Some(tuname))
+        .await;
+    match res {
+        Ok(uname) => update_pace(&uname, headers, glob).await,
+        Err(e) => {
+            log::error!("Error splitting Goal w/id {}: {}", &sdata.id, &e);
+            text_500(Some(format!("Unable to split goal: {}", &e)))
+        }
     }
+}
 
-    let text = match report::generate_report_markup(&sidecar.uname, term, &glob).await {
-        Ok(text) => text,
-        Err(e) => {
-            log::error!(
-                "Error generating {} report markdown for {:?}: {}",
-                term, &sidecar.uname, &e
-            );
-            return text_500(Some(format!(
-                "Error generating report markup: {}", &e
-            )));
-        },
-    };
+/// Deserializes the body of a `set-progress` request.
+#[derive(Debug, Deserialize)]
+struct ProgressData {
+    id: i64,
+    /// Percent done; `None` clears it.
+    progress: Option<i16>,
+}
 
-    let term = match term {
+/**
+Respond to a request to record how far along a student is with an
+unfinished goal (see [`Store::set_goal_progress`]).
+
+Header:
+```text
+x-camp-action: set-progress
+```
+Body should look like
+```json
+{ "id": 1234, "progress": 40 }
+```
+where `progress` is a percentage from 0 to 100, or `null` to clear it.
+
+Responds with the student's updated pace calendar.
+*/
+async fn set_progress(
+    tuname: &str,
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request needs application/json body with progress details.".to_owned(),
+            );
+        }
+    };
+    let pdata: ProgressData = match serde_json::from_str(&body) {
+        Ok(pdata) => pdata,
+        Err(e) => {
+            log::error!("Error deserializing {:?} as ProgressData: {}", &body, &e);
+            return respond_bad_request(format!("Unable to read progress details: {}", &e));
+        }
+    };
+    if let Some(pct) = pdata.progress {
+        if !(0..=100).contains(&pct) {
+            return respond_bad_request(format!(
+                "Progress must be a percentage from 0 to 100, not {}.",
+                &pct
+            ));
+        }
+    }
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .set_goal_progress(pdata.id, pdata.progress, Some(tuname))
+        .await;
+    match res {
+        Ok(uname) => update_pace(&uname, headers, glob).await,
+        Err(e) => {
+            log::error!("Error setting progress of Goal w/id {}: {}", &pdata.id, &e);
+            text_500(Some(format!("Unable to record progress: {}", &e)))
+        }
+    }
+}
+
+/**
+Respond to a request to dismiss a student's request that a goal be marked
+done (see [`Store::request_completion`](crate::store::Store::request_completion)),
+for when it isn't.
+
+Header:
+```text
+x-camp-action: dismiss-request
+```
+With a body parseable into the `id` of the [`Goal`].
+
+Responds with the student's updated pace calendar.
+*/
+async fn dismiss_request(
+    tuname: &str,
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let id: i64 = match body.as_deref().map(|s| s.trim().parse()) {
+        Some(Ok(id)) => id,
+        _ => {
+            return respond_bad_request("Request body must be the id of a goal.".to_owned());
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .dismiss_completion_request(tuname, id)
+        .await;
+    match res {
+        Ok(Some(uname)) => update_pace(&uname, headers, glob).await,
+        Ok(None) => respond_bad_request(format!(
+            "No student of yours has asked for goal #{} to be marked done.",
+            &id
+        )),
+        Err(e) => {
+            log::error!("Error dismissing completion request for Goal w/id {}: {}", &id, &e);
+            text_500(Some(format!("Unable to dismiss request: {}", &e)))
+        }
+    }
+}
+
+/// Deserializes the body of a `request-test` or `cancel-test` request.
+#[derive(Debug, Deserialize)]
+struct TestRequestData {
+    uname: String,
+    sym: String,
+    seq: i16,
+}
+
+/**
+Respond to a request that the office print a test of a chapter for one of
+the Teacher's students (see [`Store::request_test`]), or (if `cancel`) to
+withdraw one that hasn't been printed yet.
+
+Header:
+```text
+x-camp-action: request-test   (or cancel-test)
+```
+With a JSON body like
+```json
+{ "uname": "jsmith", "sym": "pha", "seq": 4 }
+```
+
+Responds with the student's updated pace calendar.
+*/
+async fn request_test(
+    tuname: &str,
+    headers: &HeaderMap,
+    body: Option<String>,
+    cancel: bool,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let tdata: TestRequestData = match body.as_deref().map(serde_json::from_str) {
+        Some(Ok(tdata)) => tdata,
+        Some(Err(e)) => {
+            return respond_bad_request(format!("Unable to read test request: {}", &e));
+        }
+        None => {
+            return respond_bad_request(
+                "Request needs a JSON body with the student and chapter.".to_owned(),
+            );
+        }
+    };
+
+    let res = {
+        let glob = glob.read().await;
+        match glob.users.get(&tdata.uname) {
+            Some(User::Student(s)) if s.teacher == tuname => {}
+            Some(User::Student(_)) => {
+                let estr = format!("The student {:?} is not yours.", &tdata.uname);
+                return ApiError::forbidden(estr).into_response();
+            }
+            _ => {
+                return respond_bad_request(format!(
+                    "{:?} is not a student user name.",
+                    &tdata.uname
+                ));
+            }
+        }
+        match glob.course_by_sym(&tdata.sym) {
+            Some(crs) if crs.chapter(tdata.seq).is_some() => {}
+            Some(crs) => {
+                return respond_bad_request(format!(
+                    "{} has no chapter {}.",
+                    &crs.title, &tdata.seq
+                ));
+            }
+            None => {
+                return respond_bad_request(format!("There is no course {:?}.", &tdata.sym));
+            }
+        }
+
+        let data = glob.data();
+        let data = data.read().await;
+        if cancel {
+            data.cancel_test_request(&tdata.uname, &tdata.sym, tdata.seq).await
+        } else {
+            data.request_test(tuname, &tdata.uname, &tdata.sym, tdata.seq)
+                .await
+                .map(|_| true)
+        }
+    };
+
+    match res {
+        Ok(true) => update_pace(&tdata.uname, headers, glob).await,
+        Ok(false) => ApiError::conflict(
+            "There's no unprinted request for that test to withdraw; \
+            if it's been printed, ask the office about it."
+                .to_owned(),
+        )
+        .into_response(),
+        Err(e) => {
+            log::error!("Error with test request {:?}: {}", &tdata, &e);
+            text_500(Some(format!("Unable to update test request: {}", &e)))
+        }
+    }
+}
+
+/// Header a request sets (to anything) to have due dates kept out of the
+/// student's absences; see [`avoids_absences`].
+const AVOID_ABSENCES_HEADER: &str = "x-camp-avoid-absences";
+
+/// Whether a request that sets due dates (`autopace`, `enroll-course`,
+/// `add-goal`, or `update-goal`) asks that they be kept out of the student's
+/// recorded absences (see [`Absence`]).
+fn avoids_absences(headers: &HeaderMap) -> bool {
+    headers.contains_key(AVOID_ABSENCES_HEADER)
+}
+
+/// Describe the absence `a` for an error message.
+fn describe_absence(a: &Absence) -> String {
+    let dates = if a.start == a.end {
+        format!("on {}", &a.start)
+    } else {
+        format!("from {} through {}", &a.start, &a.end)
+    };
+    match a.reason.trim() {
+        "" => format!("absent {}", &dates),
+        reason => format!("absent {} ({})", &dates, reason),
+    }
+}
+
+/// Return the absences of the student `uname`, or an error response.
+async fn student_absences(uname: &str, glob: &Glob) -> Result<Vec<Absence>, Response> {
+    let res = glob.store().get_absences_by_student(uname).await;
+    res.map_err(|e| {
+        log::error!("Error retrieving absences of {:?}: {}", uname, &e);
+        text_500(Some(format!("Error retrieving absences from database: {}", &e)))
+    })
+}
+
+/**
+If the request asks (see [`avoids_absences`]), check that `g`'s due date
+doesn't fall during one of its student's absences.
+
+`Goal`s whose due dates aren't changing pass, so Teachers can still update
+one that was scheduled before the absence was recorded.
+*/
+async fn check_goal_absences(g: &Goal, headers: &HeaderMap, glob: &Glob) -> Result<(), Response> {
+    let due = match g.due {
+        Some(due) if avoids_absences(headers) => due,
+        _ => { return Ok(()); }
+    };
+    let absences = student_absences(&g.uname, glob).await?;
+    let a = match absences.iter().find(|a| a.covers(&due)) {
+        Some(a) => a,
+        None => { return Ok(()); }
+    };
+
+    if due_date_unchanged(g, due, glob).await {
+        return Ok(());
+    }
+
+    Err(respond_bad_request(format!(
+        "The due date {} is a day this student will be {}.",
+        &due,
+        &describe_absence(a)
+    )))
+}
+
+/**
+Check that `g`'s due date doesn't fall during one of the Admin's exam
+windows (see [`ExamWindow`](crate::pace::ExamWindow)).
+
+Like with absences, `Goal`s whose due dates aren't changing pass.
+*/
+async fn check_goal_exam_windows(g: &Goal, glob: &Glob) -> Result<(), Response> {
+    let (due, w) = match g.due.and_then(|due| Some((due, glob.calendar.exam_window_on(&due)?))) {
+        Some(x) => x,
+        None => { return Ok(()); }
+    };
+    if due_date_unchanged(g, due, glob).await {
+        return Ok(());
+    }
+
+    let dates = if w.start == w.end {
+        format!("on {}", &w.start)
+    } else {
+        format!("from {} through {}", &w.start, &w.end)
+    };
+    Err(respond_bad_request(format!(
+        "The due date {} falls during {} ({}); nothing can be due then.",
+        &due, w.name.trim(), &dates
+    )))
+}
+
+/// Whether `g` is one of its student's existing `Goal`s, already due on
+/// `due`.
+async fn due_date_unchanged(g: &Goal, due: Date, glob: &Glob) -> bool {
+    if g.id == 0 {
+        return false;
+    }
+    match glob.get_pace_by_student(&g.uname).await {
+        Ok(p) => p.goals.iter().any(|pg| pg.id == g.id && pg.due == Some(due)),
+        Err(_) => false,
+    }
+}
+
+/// Ensure `uname` is one of the Teacher `tuname`'s students.
+#[allow(clippy::result_large_err)]
+fn check_own_student(tuname: &str, uname: &str, glob: &Glob) -> Result<(), Response> {
+    match glob.users.get(uname) {
+        Some(User::Student(s)) if s.teacher == tuname => Ok(()),
+        Some(User::Student(_)) => Err(ApiError::forbidden(format!(
+            "The student {:?} is not yours.",
+            uname
+        ))
+        .into_response()),
+        _ => Err(respond_bad_request(format!(
+            "{:?} is not a student user name.",
+            uname
+        ))),
+    }
+}
+
+/**
+Respond with the absences recorded for one of the Teacher's students.
+
+Header:
+```text
+x-camp-action: populate-absences
+```
+With the student's `uname` as the body. Response body looks like
+```json
+{
+    "uname": "jsmith",
+    "absences": [
+        {
+            "id": 3,
+            "uname": "jsmith",
+            "start": "2023-03-13",
+            "end": "2023-03-17",
+            "reason": "family trip"
+        }
+    ]
+}
+```
+*/
+async fn populate_absences(tuname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let uname = match body.as_deref().map(str::trim) {
+        Some(uname) if !uname.is_empty() => uname,
+        _ => {
+            return respond_bad_request("Request needs Student user name in body.".to_owned());
+        }
+    };
+
+    respond_absences(tuname, uname, &*glob.read().await).await
+}
+
+/// Respond with the absences of the student `uname` (if they're one of
+/// `tuname`'s), as for a `populate-absences` request.
+async fn respond_absences(tuname: &str, uname: &str, glob: &Glob) -> Response {
+    if let Err(resp) = check_own_student(tuname, uname, glob) {
+        return resp;
+    }
+    let absences = match student_absences(uname, glob).await {
+        Ok(absences) => absences,
+        Err(resp) => { return resp; }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-absences"),
+        )],
+        Json(json!({
+            "uname": uname,
+            "absences": absences,
+        })),
+    )
+        .into_response()
+}
+
+/// Deserializes the body of an `add-absence` request.
+#[derive(Debug, Deserialize)]
+struct AbsenceData {
+    uname: String,
+    start: String,
+    end: String,
+    #[serde(default)]
+    reason: String,
+}
+
+/**
+Record an absence for one of the Teacher's students.
+
+Header:
+```text
+x-camp-action: add-absence
+```
+With a JSON body like
+```json
+{
+    "uname": "jsmith",
+    "start": "2023-03-13",
+    "end": "2023-03-17",
+    "reason": "family trip"     // optional
+}
+```
+Responds as to `populate-absences`. Due dates already scheduled during the
+absence stay where they are.
+*/
+async fn add_absence(tuname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let adata: AbsenceData = match body.as_deref().map(serde_json::from_str) {
+        Some(Ok(adata)) => adata,
+        Some(Err(e)) => {
+            return respond_bad_request(format!("Unable to read absence: {}", &e));
+        }
+        None => {
+            return respond_bad_request(
+                "Request needs a JSON body with the student and dates.".to_owned(),
+            );
+        }
+    };
+    let (start, end) = match (
+        maybe_parse_date(Some(&adata.start)),
+        maybe_parse_date(Some(&adata.end)),
+    ) {
+        (Ok(Some(start)), Ok(Some(end))) => (start, end),
+        (Err(e), _) | (_, Err(e)) => {
+            return respond_bad_request(format!("Bad absence date: {}", &e));
+        }
+        _ => {
+            return respond_bad_request("An absence needs start and end dates.".to_owned());
+        }
+    };
+    if end < start {
+        return respond_bad_request("An absence can't end before it starts.".to_owned());
+    }
+
+    let glob = glob.read().await;
+    if let Err(resp) = check_own_student(tuname, &adata.uname, &glob) {
+        return resp;
+    }
+    let res = glob
+        .data()
+        .read()
+        .await
+        .insert_absence(&adata.uname, start, end, adata.reason.trim(), tuname)
+        .await;
+    if let Err(e) = res {
+        log::error!("Error recording absence {:?}: {}", &adata, &e);
+        return text_500(Some(format!("Unable to record absence: {}", &e)));
+    }
+
+    respond_absences(tuname, &adata.uname, &glob).await
+}
+
+/// Deserializes the body of a `delete-absence` request.
+#[derive(Debug, Deserialize)]
+struct DeleteAbsenceData {
+    uname: String,
+    id: i64,
+}
+
+/**
+Delete one of the absences of one of the Teacher's students.
+
+Header:
+```text
+x-camp-action: delete-absence
+```
+With a JSON body like
+```json
+{ "uname": "jsmith", "id": 3 }
+```
+Responds as to `populate-absences`.
+*/
+async fn delete_absence(tuname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let ddata: DeleteAbsenceData = match body.as_deref().map(serde_json::from_str) {
+        Some(Ok(ddata)) => ddata,
+        Some(Err(e)) => {
+            return respond_bad_request(format!("Unable to read absence to delete: {}", &e));
+        }
+        None => {
+            return respond_bad_request(
+                "Request needs a JSON body with the student and absence id.".to_owned(),
+            );
+        }
+    };
+
+    let glob = glob.read().await;
+    if let Err(resp) = check_own_student(tuname, &ddata.uname, &glob) {
+        return resp;
+    }
+    let res = glob
+        .data()
+        .read()
+        .await
+        .delete_absence(&ddata.uname, ddata.id)
+        .await;
+    match res {
+        Ok(true) => {}
+        Ok(false) => {
+            return respond_bad_request(format!(
+                "{:?} has no absence with id {}.",
+                &ddata.uname, &ddata.id
+            ));
+        }
+        Err(e) => {
+            log::error!("Error deleting absence {:?}: {}", &ddata, &e);
+            return text_500(Some(format!("Unable to delete absence: {}", &e)));
+        }
+    }
+
+    respond_absences(tuname, &ddata.uname, &glob).await
+}
+
+/// One goal to mark complete in a `bulk-complete` request.
+#[derive(Debug, Deserialize)]
+struct BulkTarget {
+    uname: String,
+    sym: String,
+    seq: i16,
+}
+
+/// Deserializes the body of a `bulk-complete` request.
+#[derive(Debug, Deserialize)]
+struct BulkCompleteData {
+    done: String,
+    tries: Option<i16>,
+    score: Option<String>,
+    goals: Vec<BulkTarget>,
+}
+
+/// Reports what happened to a single `BulkTarget`.
+#[derive(Debug, Serialize)]
+struct BulkResult<'a> {
+    uname: &'a str,
+    sym: &'a str,
+    seq: i16,
+    ok: bool,
+    error: Option<String>,
+}
+
+impl<'a> BulkResult<'a> {
+    fn new(tgt: &'a BulkTarget) -> BulkResult<'a> {
+        BulkResult {
+            uname: &tgt.uname,
+            sym: &tgt.sym,
+            seq: tgt.seq,
+            ok: true,
+            error: None,
+        }
+    }
+
+    fn fail(&mut self, error: String) {
+        self.ok = false;
+        self.error = Some(error);
+    }
+}
+
+/// Respond to a `bulk-complete` request that couldn't be applied, with the
+/// per-goal results explaining why (in the error's `detail`).
+fn respond_bulk_failure(results: &[BulkResult]) -> Response {
+    let e = ApiError::bad_request(
+        "No goals were marked complete; see the results for which ones couldn't be.".to_owned(),
+    )
+    .with_detail(&json!({ "results": results }));
+    (
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("bulk-complete"),
+        )],
+        e,
+    )
+        .into_response()
+}
+
+/**
+Respond to a request to mark a batch of goals (generally the same chapter
+for a whole class, after a test day) done all at once.
+
+Header:
+```text
+x-camp-action: bulk-complete
+```
+With a JSON body like
+```json
+{
+    "done": "2023-01-10",
+    "tries": 1,         // optional
+    "score": "85",      // optional
+    "goals": [
+        { "uname": "jsmith", "sym": "pha1", "seq": 4 },
+        { "uname": "mjones", "sym": "pha1", "seq": 4 }
+    ]
+}
+```
+The changes are applied atomically: if any goal doesn't exist or belongs
+to another teacher's student, none are changed. Either way the response
+contains a `results` array with the outcome for each goal, in order (in
+the `detail` of the error, on failure); on success it also contains the
+updated `paces` of the affected students.
+*/
+async fn bulk_complete(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request needs application/json body with goals to complete.".to_owned(),
+            );
+        }
+    };
+
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+
+    let bdata: BulkCompleteData = match serde_json::from_str(&body) {
+        Ok(bdata) => bdata,
+        Err(e) => {
+            log::error!("Error deserializing {:?} as BulkCompleteData: {}", &body, &e);
+            return respond_bad_request(format!("Unable to read completion details: {}", &e));
+        }
+    };
+
+    if bdata.goals.is_empty() {
+        return respond_bad_request("No goals specified.".to_owned());
+    }
+    let done = match Date::parse(&bdata.done, DATE_FMT) {
+        Ok(d) => d,
+        Err(e) => {
+            return respond_bad_request(format!("Bad done date {:?}: {}", &bdata.done, &e));
+        }
+    };
+    if let Err(e) = maybe_parse_score_str(bdata.score.as_deref()) {
+        return respond_bad_request(format!("Bad score: {}", &e));
+    }
+
+    let mut results: Vec<BulkResult> = bdata.goals.iter().map(BulkResult::new).collect();
+
+    let glob_lock = Arc::clone(&glob);
+    let glob = glob.read().await;
+
+    for res in results.iter_mut() {
+        match glob.users.get(res.uname) {
+            Some(User::Student(s)) => {
+                if s.teacher != tuname {
+                    res.fail("Not your student.".to_owned());
+                }
+            }
+            _ => {
+                res.fail("No such student.".to_owned());
+            }
+        }
+    }
+    if results.iter().any(|r| !r.ok) {
+        return respond_bulk_failure(&results);
+    }
+
+    let targets: Vec<(&str, &str, i16)> = bdata
+        .goals
+        .iter()
+        .map(|tgt| (tgt.uname.as_str(), tgt.sym.as_str(), tgt.seq))
+        .collect();
+
+    {
+        let data = glob.data();
+        let data_reader = data.read().await;
+        let mut client = match data_reader.connect().await {
+            Ok(client) => client,
+            Err(e) => {
+                let estr = format!("Error connecting to database: {}", &e);
+                log::error!("{}", &estr);
+                return text_500(Some(estr));
+            }
+        };
+        let t = match client.transaction().await {
+            Ok(t) => t,
+            Err(e) => {
+                let estr = format!("Error beginning transaction: {}", &e);
+                log::error!("{}", &estr);
+                return text_500(Some(estr));
+            }
+        };
+
+        let counts = match data_reader
+            .complete_goals(&t, &targets, &done, bdata.tries, bdata.score.as_deref())
+            .await
+        {
+            Ok(counts) => counts,
+            Err(e) => {
+                log::error!("Error completing goals {:?}: {}", &targets, &e);
+                return text_500(Some(format!("Error updating goals: {}", &e)));
+            }
+        };
+
+        for (res, n) in results.iter_mut().zip(counts.iter()) {
+            if *n == 0 {
+                res.fail("No such goal.".to_owned());
+            }
+        }
+        if results.iter().any(|r| !r.ok) {
+            // Dropping `t` without committing rolls everything back.
+            return respond_bulk_failure(&results);
+        }
+
+        if let Err(e) = t.commit().await {
+            log::error!("Error committing bulk-complete transaction: {}", &e);
+            return text_500(Some(format!("Error committing transaction: {}", &e)));
+        }
+
+        glob.defer_course_stats(targets.iter().map(|(_, sym, _)| *sym));
+    }
+
+    for (uname, sym, seq) in targets.iter() {
+        if let Some(User::Student(s)) = glob.users.get(*uname) {
+            let data = GoalCompleted {
+                student: s.into(),
+                sym: Some(sym),
+                seq: Some(*seq),
+                custom: None,
+                done,
+                score: bdata.score.as_deref(),
+            };
+            glob.fire_webhook(Event::GoalCompleted, &data).await;
+        }
+    }
+
+    let mut unames: Vec<&str> = targets.iter().map(|(uname, _, _)| *uname).collect();
+    unames.sort_unstable();
+    unames.dedup();
+
+    let mut paces: Vec<Pace> = Vec::with_capacity(unames.len());
+    for uname in unames.iter() {
+        match glob.get_pace_by_student(uname).await {
+            Ok(p) => paces.push(p),
+            Err(e) => {
+                log::error!("Error getting Pace for student {:?}: {}", uname, &e);
+                return text_500(Some(format!(
+                    "Error retrieving updated Pace from database: {}",
+                    &e
+                )));
+            }
+        }
+    }
+    for p in paces.iter() {
+        completion::notify_if_complete(p, &glob, &glob_lock).await;
+    }
+    let mut pace_data: Vec<PaceData> = Vec::with_capacity(paces.len());
+    for p in paces.iter() {
+        match PaceData::from_pace(p) {
+            Ok(pd) => pace_data.push(pd),
+            Err(e) => {
+                return text_500(Some(format!("Unable to serialize response: {}", &e)));
+            }
+        }
+    }
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("bulk-complete"),
+        )],
+        Json(json!({
+            "results": results,
+            "paces": pace_data,
+        })),
+    )
+        .into_response()
+}
+
+/**
+Respond with a CSV roster of the teacher's students, in the format Admins
+upload students in (see [`Student::csv_from_students`]).
+
+Header:
+```text
+x-camp-action: download-students
+```
+*/
+async fn download_students(tuname: &str, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let students: Vec<&Student> = glob
+        .get_students_by_teacher(tuname)
+        .into_iter()
+        .filter_map(|u| match u {
+            User::Student(s) => Some(s),
+            _ => None,
+        })
+        .collect();
+
+    respond_students_csv(&students)
+}
+
+/**
+Respond with a goals CSV file to fill in and upload, with a row for each of
+the teacher's students (see [`Pace::csv_template`]).
+
+Header:
+```text
+x-camp-action: goals-template
+```
+*/
+async fn goals_template(tuname: &str, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let students: Vec<&Student> = glob
+        .get_students_by_teacher(tuname)
+        .into_iter()
+        .filter_map(|u| match u {
+            User::Student(s) => Some(s),
+            _ => None,
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("goals-template"),
+            ),
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/csv; charset=utf-8"),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                HeaderValue::from_static("attachment; filename=\"goals.csv\""),
+            ),
+        ],
+        Pace::csv_template(&students),
+    ).into_response()
+}
+
+/**
+Respond with a student's goals as a goals CSV file (see [`Pace::to_csv`]),
+in the same format accepted by the `upload-goals` action, so they can be
+edited offline and uploaded again.
+
+Headers:
+```text
+x-camp-action: download-goals
+x-camp-student: [ uname of student ]
+```
+*/
+async fn download_goals(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let suname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+
+    let glob = glob.read().await;
+
+    match glob.users.get(&suname) {
+        Some(User::Student(s)) => {
+            if s.teacher != tuname {
+                let estr = format!("The student {:?} is not yours.", &suname);
+                return ApiError::forbidden(estr).into_response();
+            }
+        },
+        _ => {
+            return respond_bad_request(format!(
+                "The uname {:?} does not belong to a student in the system.",
+                &suname
+            ));
+        },
+    }
+
+    let p = match glob.get_pace_by_student(&suname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("Error getting Pace for student {:?}: {}", suname, &e);
+            return text_500(Some(format!("Error retrieving Pace from database: {}", &e)));
+        }
+    };
+
+    let uname = match HeaderValue::from_str(&suname) {
+        Ok(uname) => uname,
+        Err(e) => {
+            let estr = format!(
+                "Error converting student uname {:?} into header value: {}",
+                suname, &e
+            );
+            log::error!("{}", &estr);
+            return text_500(Some(estr));
+        },
+    };
+
+    let disposition = format!("attachment; filename=\"goals-{}.csv\"", &suname);
+    let disposition = match HeaderValue::from_str(&disposition) {
+        Ok(v) => v,
+        Err(_) => HeaderValue::from_static("attachment; filename=\"goals.csv\""),
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("download-goals"),
+            ),
+            (
+                HeaderName::from_static("x-camp-student"),
+                uname,
+            ),
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/csv; charset=utf-8"),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        p.to_csv(),
+    ).into_response()
+}
+
+/**
+Respond to a request to add a collection of goals from information in CSV
+format.
+
+Header:
+```
+x-camp-action: upload-goals
+```
+With the body being the CSV data in question. For the CSV format, see
+[`Pace::from_csv`].
+*/
+async fn upload_goals(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request("Request needs text/csv body of Goal details.".to_owned());
+        }
+    };
+
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+
+    let mut others_students = String::new();
+    let mut goals: Vec<Goal> = Vec::new();
+    {
+        let glob = glob.read().await;
+
+        let reader = Cursor::new(body);
+        let mut pcals = match Pace::from_csv(reader, &glob) {
+            Ok(pcals) => pcals,
+            Err(e) => {
+                return respond_bad_request(e);
+            }
+        };
+
+        for p in pcals.iter_mut() {
+            if p.teacher.base.uname == tuname {
+                goals.append(&mut p.goals);
+            } else {
+                others_students.push('\n');
+                others_students.push_str(&p.student.base.uname);
+            }
+        }
+
+        if !others_students.is_empty() {
+            let mut estr = String::from(
+                "The following students with Goals in the Goals file you just submitted are not yours:"
+            );
+            estr.extend(others_students.drain(..));
+
+            return ApiError::forbidden(estr).into_response();
+        }
+
+        match glob.insert_goals(&goals).await {
+            Ok(n) => {
+                log::trace!("{} inserted {} goals.", tuname, &n);
+                glob.defer_course_stats(goals.iter().filter_map(|g| match &g.source {
+                    Source::Book(bch) => Some(bch.sym.as_str()),
+                    Source::Custom(_) => None,
+                }));
+            }
+            Err(e) => {
+                log::error!("Error inserting Goals: {}", &e);
+                return text_500(Some(format!("Error inserting Goals into database: {}", &e)));
+            }
+        }
+    }
+
+    populate_goals(headers, glob).await
+}
+
+async fn show_sidecar(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request("Request needs student user name in body.".to_owned());
+        }
+    };
+
+    let uname = &body;
+
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+
+    let glob = glob.read().await;
+
+    match glob.users.get(uname) {
+        Some(User::Student(s)) => {
+            if s.teacher != tuname {
+                let estr = format!("The student {:?} is not yours.", uname);
+                return ApiError::forbidden(estr).into_response();
+            }
+        }
+        _ => {
+            let estr = format!(
+                "The uname {:?} does not belong to a student in the system.",
+                uname
+            );
+            return respond_bad_request(estr);
+        }
+    }
+
+    let this_year = glob.academic_year();
+
+    let data_guard = glob.data();
+    let data = data_guard.read().await;
+
+    let sidecar = match data.get_report_sidecar(uname, this_year).await {
+        Ok(sc) => sc,
+        Err(e) => {
+            log::error!("Error fetching sidecar for student {:?}: {}", uname, &e);
+            return text_500(Some(format!(
+                "Error fetching extra reporting information for {:?}: {}",
+                uname, &e
+            )));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("show-sidecar"),
+        )],
+        Json(sidecar),
+    )
+        .into_response()
+}
+
+async fn update_sidecar(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request needs application/json body with ReportSidecar details.".to_owned(),
+            );
+        }
+    };
+
+    let sidecar: ReportSidecar = match serde_json::from_str(&body) {
+        Ok(sc) => sc,
+        Err(e) => {
+            log::error!(
+                "Unable to deserialize as ReportSidecar: {}; data:\n{}",
+                &e,
+                &body
+            );
+            let estr = format!(
+                "Request body did not deserialize into ReportSidecar: {}",
+                &e
+            );
+            return respond_bad_request(estr);
+        }
+    };
+
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let term = match CampTerm::from_headers(headers) {
+        Ok(CampTerm(term)) => term,
+        Err(resp) => { return resp; }
+    };
+    // Reports are for parents unless otherwise specified.
+    let audience = match headers.get("x-camp-audience") {
+        None => Audience::Parent,
+        Some(val) => match val.to_str().map(Audience::from_str) {
+            Ok(Ok(audience)) => audience,
+            _ => {
+                return respond_bad_request(format!(
+                    "Unrecognized x-camp-audience value {:?}", val
+                ));
+            },
+        },
+    };
+
+    let glob = glob.read().await;
+
+    match glob.users.get(&sidecar.uname) {
+        Some(User::Student(s)) => {
+            if s.teacher != tuname {
+                let estr = format!("The student {:?} is not yours.", &sidecar.uname);
+                return ApiError::forbidden(estr).into_response();
+            }
+        },
+        _ => {
+            let estr = format!(
+                "The uname {:?} does not belong to a student in the system.",
+                &sidecar.uname
+            );
+            return respond_bad_request(estr);
+        }
+    }
+
+    let this_year = glob.academic_year();
+
+    let data_guard = glob.data();
+    let data = data_guard.read().await;
+
+    if let Err(e) = data.set_report_sidecar(&sidecar, this_year).await {
+        log::error!("Error setting report sidecar: {}\ndata: {:?}", &e, &sidecar);
+        let estr = format!("Error saving report sidecar info: {}", &e);
+        return text_500(Some(estr));
+    }
+
+    let text = match report::generate_report_markup(&sidecar.uname, term, audience, &glob).await {
+        Ok(text) => text,
+        Err(e) => {
+            log::error!(
+                "Error generating {} report markdown for {:?}: {}",
+                term, &sidecar.uname, &e
+            );
+            return text_500(Some(format!(
+                "Error generating report markup: {}", &e
+            )));
+        },
+    };
+
+    let term = match term {
         Term::Fall => HeaderValue::from_static("fall"),
         Term::Spring => HeaderValue::from_static("spring"),
         Term::Summer => HeaderValue::from_static("summer"),
     };
 
-    let suname = match HeaderValue::from_str(&sidecar.uname) {
-        Ok(uname) => uname,
-        Err(e) => { 
-            let estr = format!(
-                "Error turning student uname {:?} into a header value: {}",
-                &sidecar.uname, &e
-            );
-            log::error!("Error generating report markdown response: {}", &estr);
-            return text_500(Some(estr));
+    let suname = match HeaderValue::from_str(&sidecar.uname) {
+        Ok(uname) => uname,
+        Err(e) => { 
+            let estr = format!(
+                "Error turning student uname {:?} into a header value: {}",
+                &sidecar.uname, &e
+            );
+            log::error!("Error generating report markdown response: {}", &estr);
+            return text_500(Some(estr));
+        },
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("edit-markdown"),
+            ),
+            (
+                HeaderName::from_static("x-camp-student"),
+                suname,
+            ),
+            (
+                HeaderName::from_static("x-camp-term"),
+                term,
+            ),
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/markdown"),
+            ),
+        ],
+        text
+    )
+        .into_response()
+}
+
+async fn generate_report(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>
+) -> Response {
+    let suname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let term = match CampTerm::from_headers(headers) {
+        Ok(CampTerm(term)) => term,
+        Err(resp) => { return resp; },
+    };
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request needs application/json body with ReportSidecar details.".to_owned(),
+            );
+        }
+    };
+
+    let glob = glob.read().await;
+
+    match glob.users.get(&suname) {
+        Some(User::Student(s)) => {
+            if s.teacher != tuname {
+                let estr = format!("The student {:?} is not yours.", &suname);
+                return ApiError::forbidden(estr).into_response();
+            }
+        },
+        _ => {
+            let estr = format!(
+                "The uname {:?} does not belong to a student in the system.",
+                &suname
+            );
+            return respond_bad_request(estr);
+        }
+    }
+
+    {
+        let data_guard = glob.data();
+        let data = data_guard.read().await;
+        let mut client = match data.connect().await {
+            Ok(client) => client,
+            Err(e) => { return text_500(Some(e.to_string())); },
+        };
+        let t = match client.transaction().await {
+            Ok(trans) => trans,
+            Err(e) => { return text_500(Some(e.to_string())); },
+        };
+        if let Err(e) = Store::set_draft(&t, &suname, term, &body).await {
+            log::error!(
+                "Error attempting to store {} report draft for {:?}: {}",
+                &term, &suname, &e
+            );
+            return text_500(Some(format!(
+                "Error attempting to store report draft in database: {}", &e
+            )));
+        }
+        if let Err(e) = t.commit().await {
+            log::error!(
+                "Error committing transaction for storing {} report draft for {:?}: {}",
+                &term, &suname, &e
+            );
+            return text_500(Some(format!(
+                "Error committing report draft to database: {}", &e
+            )));
+        }
+    }
+
+    let pdf_data = match report::render_markdown(body, &glob).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!(
+                "Error attempting to render {} report PDF for {:?}: {}",
+                &term, suname, &e
+            );
+            return text_500(Some(format!(
+                "Error generating PDF file: {}", &e
+            )));
+        },
+    };
+
+    {
+        let data_guard = glob.data();
+        let data = data_guard.read().await;
+        let mut client = match data.connect().await {
+            Ok(client) => client,
+            Err(e) => { return text_500(Some(e.to_string())); },
+        };
+        let t = match client.transaction().await {
+            Ok(trans) => trans,
+            Err(e) => { return text_500(Some(e.to_string())); },
+        };
+        if let Err(e) = Store::set_final(&t, &suname, term, &pdf_data).await {
+            log::error!(
+                "Error attempting to store final {} report PDF for {:?}: {}",
+                &term, &suname, &e
+            );
+            return text_500(Some(format!(
+                "Error attempting to store report PDF in database: {}", &e
+            )));
+        }
+        if let Err(e) = t.commit().await {
+            log::error!(
+                "Error committing transaction for storing {} report PDF for {:?}: {}",
+                &term, &suname, &e
+            );
+            return text_500(Some(format!(
+                "Error committing final report PDF to database: {}", &e
+            )));
+        }
+    }
+    glob.log_action(&tuname, "finalize-report", &suname, Some(&term.to_string())).await;
+    if let Some(User::Student(s)) = glob.users.get(&suname) {
+        glob.fire_webhook(Event::ReportFinalized, &ReportFinalized::new(s, term)).await;
+    }
+
+    let uname = match HeaderValue::from_str(&suname) {
+        Ok(uname) => uname,
+        Err(e) => {
+            let estr = format!(
+                "Error converting student uname {:?} into header value: {}",
+                suname, &e
+            );
+            log::error!("{}", &estr);
+            return text_500(Some(estr));
+        },
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/pdf"),
+            ),
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("display-pdf"),
+            ),
+            (
+                HeaderName::from_static("x-camp-student"),
+                uname,
+            ),
+            (
+                HeaderName::from_static("x-camp-term"),
+                headers.get("x-camp-term").unwrap().clone(),
+            ),
+        ],
+        pdf_data
+    ).into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CopyDraftData {
+    #[serde(default)]
+    force: bool,
+}
+
+/**
+Respond to a request to start a student's Spring report from their Fall
+report draft.
+
+```text
+x-camp-action: copy-draft
+x-camp-student: <uname>
+```
+
+The Fall draft (the markdown the student's Fall report was last rendered
+from) is copied into the Spring slot, with the term's name and the
+summary table changed to suit (see [`report::carry_over_draft`]), and
+sent back for editing, just like the response to `update-sidecar`.
+
+If the student already has a Spring draft, the response is 409 CONFLICT
+(with this action in the `x-camp-action` header, and the student in
+`x-camp-student`) unless the body is JSON with `"force": true`.
+*/
+async fn copy_draft(
+    headers: &HeaderMap,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>
+) -> Response {
+    let suname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let cdata = match body.as_deref().map(str::trim) {
+        None | Some("") => CopyDraftData::default(),
+        Some(body) => match serde_json::from_str::<CopyDraftData>(body) {
+            Ok(cdata) => cdata,
+            Err(e) => {
+                log::error!("Error deserializing {:?} as CopyDraftData: {}", body, &e);
+                return respond_bad_request(
+                    "Body must be empty or JSON with \"force\".".to_owned(),
+                );
+            }
+        },
+    };
+
+    let glob = glob.read().await;
+
+    match glob.users.get(&suname) {
+        Some(User::Student(s)) => {
+            if s.teacher != tuname {
+                let estr = format!("The student {:?} is not yours.", &suname);
+                return ApiError::forbidden(estr).into_response();
+            }
+        },
+        _ => {
+            let estr = format!(
+                "The uname {:?} does not belong to a student in the system.",
+                &suname
+            );
+            return respond_bad_request(estr);
+        }
+    }
+
+    let uname = match HeaderValue::from_str(&suname) {
+        Ok(uname) => uname,
+        Err(e) => {
+            let estr = format!(
+                "Error converting student uname {:?} into header value: {}",
+                suname, &e
+            );
+            log::error!("{}", &estr);
+            return text_500(Some(estr));
+        },
+    };
+
+    let fall_draft = {
+        let data_guard = glob.data();
+        let data = data_guard.read().await;
+        let mut client = match data.connect().await {
+            Ok(client) => client,
+            Err(e) => { return text_500(Some(e.to_string())); },
+        };
+        let t = match client.transaction().await {
+            Ok(trans) => trans,
+            Err(e) => { return text_500(Some(e.to_string())); },
+        };
+        match Store::get_draft(&t, &suname, Term::Fall).await {
+            Ok(Some(text)) => text,
+            Ok(None) => {
+                return ApiError::not_found(format!(
+                    "{:?} has no Fall report draft to start from.", &suname
+                )).into_response();
+            },
+            Err(e) => {
+                log::error!("Error reading Fall report draft for {:?}: {}", &suname, &e);
+                return text_500(Some(format!(
+                    "Error reading Fall report draft from database: {}", &e
+                )));
+            },
+        }
+    };
+
+    let text = match report::carry_over_draft(
+        &suname, &fall_draft, Term::Fall, Term::Spring, &glob
+    ).await {
+        Ok(text) => text,
+        Err(e) => {
+            log::error!("Error carrying Fall report draft over for {:?}: {}", &suname, &e);
+            return text_500(Some(format!("Error copying Fall report draft: {}", &e)));
+        },
+    };
+
+    {
+        let data_guard = glob.data();
+        let data = data_guard.read().await;
+        let mut client = match data.connect().await {
+            Ok(client) => client,
+            Err(e) => { return text_500(Some(e.to_string())); },
+        };
+        let t = match client.transaction().await {
+            Ok(trans) => trans,
+            Err(e) => { return text_500(Some(e.to_string())); },
+        };
+        // Checked here, in the same transaction as the write, so a draft
+        // saved in the meantime doesn't get clobbered either.
+        match Store::get_draft(&t, &suname, Term::Spring).await {
+            Ok(Some(_)) if !cdata.force => {
+                let mut resp = ApiError::conflict(format!(
+                    "{:?} already has a Spring report draft; copying the Fall draft \
+                    will replace it.",
+                    &suname
+                )).into_response();
+                let resp_headers = resp.headers_mut();
+                resp_headers.insert(
+                    HeaderName::from_static("x-camp-action"),
+                    HeaderValue::from_static("copy-draft"),
+                );
+                resp_headers.insert(HeaderName::from_static("x-camp-student"), uname);
+                return resp;
+            },
+            Ok(_) => {},
+            Err(e) => {
+                log::error!("Error reading Spring report draft for {:?}: {}", &suname, &e);
+                return text_500(Some(format!(
+                    "Error reading Spring report draft from database: {}", &e
+                )));
+            },
+        }
+        if let Err(e) = Store::set_draft(&t, &suname, Term::Spring, &text).await {
+            log::error!("Error storing Spring report draft for {:?}: {}", &suname, &e);
+            return text_500(Some(format!(
+                "Error attempting to store report draft in database: {}", &e
+            )));
+        }
+        if let Err(e) = t.commit().await {
+            log::error!(
+                "Error committing transaction for copying report draft for {:?}: {}",
+                &suname, &e
+            );
+            return text_500(Some(format!(
+                "Error committing report draft to database: {}", &e
+            )));
+        }
+    }
+    glob.log_action(&tuname, "copy-draft", &suname, None).await;
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("edit-markdown"),
+            ),
+            (
+                HeaderName::from_static("x-camp-student"),
+                uname,
+            ),
+            (
+                HeaderName::from_static("x-camp-term"),
+                HeaderValue::from_static("spring"),
+            ),
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/markdown"),
+            ),
+        ],
+        text
+    )
+        .into_response()
+}
+
+async fn discard_pdf(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let suname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let term = match CampTerm::from_headers(headers) {
+        Ok(CampTerm(term)) => term,
+        Err(resp) => { return resp; },
+    };
+
+    let glob = glob.read().await;
+    match glob.users.get(&suname) {
+        Some(User::Student(s)) => {
+            if s.teacher != tuname {
+                let estr = format!("The student {:?} is not yours.", &suname);
+                return ApiError::forbidden(estr).into_response();
+            }
+        },
+        _ => {
+            let estr = format!(
+                "The uname {:?} does not belong to a student in the system.",
+                &suname
+            );
+            return respond_bad_request(estr);
+        },
+    }
+
+    match glob.data().read().await.clear_final(&suname, term).await {
+        Ok(()) => respond_ok(),
+        Err(e) => {
+            log::error!(
+                "Error attempting to discard {} report PDF for {:?}: {}",
+                &term, suname, &e
+            );
+            text_500(Some(format!(
+                "Error attempting to discard report PDF: {}", &e
+            )))
+        }
+    }
+}
+
+async fn student_history(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let suname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+
+    let hist = match glob.read().await.get_student_completion_history(&suname).await {
+        Ok(hist) => hist,
+        Err(e) => {
+            log::error!(
+                "Error attempting to retrieve completion history for {:?}: {}",
+                suname, &e
+            );
+            return text_500(Some(format!(
+                "Error reading from database: {}", &e
+            )));
+        },
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("student-history"),
+            ),
+            (
+                HeaderName::from_static("x-camp-student"),
+                headers.get("x-camp-student").unwrap().clone(),
+            ),
+        ],
+        Json(&hist)
+    ).into_response()
+}
+
+/**
+Respond with the rendered HTML table row for a single goal.
+
+Headers:
+```text
+x-camp-action: goal-row
+x-camp-student: [ uname of student whose goal it is ]
+```
+With a body parseable into the `id` of the [`Goal`] in question.
+*/
+async fn goal_row(headers: &HeaderMap, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let suname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+
+    let id: i64 = match body.as_deref().map(|b| b.trim().parse::<i64>()) {
+        Some(Ok(n)) => n,
+        Some(Err(e)) => {
+            return respond_bad_request(format!("Unable to parse body as goal id: {}", &e));
+        }
+        None => {
+            return respond_bad_request("Request must have the goal id as a body.".to_owned());
+        }
+    };
+
+    let glob = glob.read().await;
+
+    match glob.users.get(&suname) {
+        Some(User::Student(s)) => {
+            if s.teacher != tuname {
+                let estr = format!("The student {:?} is not yours.", &suname);
+                return ApiError::forbidden(estr).into_response();
+            }
+        },
+        _ => {
+            return respond_bad_request(format!(
+                "The uname {:?} does not belong to a student in the system.",
+                &suname
+            ));
+        },
+    }
+
+    let p = match glob.get_pace_by_student(&suname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("Error getting Pace for student {:?}: {}", suname, &e);
+            return text_500(Some(format!("Error retrieving Pace from database: {}", &e)));
+        }
+    };
+
+    match fragment::goal_row(&p, id, &glob) {
+        Ok(html) => fragment::respond("goal-row", html),
+        Err(e) => respond_bad_request(e),
+    }
+}
+
+/**
+Respond with the rendered HTML semester summary rows for a single student.
+
+Headers:
+```text
+x-camp-action: summary-rows
+x-camp-student: [ uname of student ]
+```
+*/
+async fn summary_rows(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let suname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+
+    let glob = glob.read().await;
+
+    match glob.users.get(&suname) {
+        Some(User::Student(s)) => {
+            if s.teacher != tuname {
+                let estr = format!("The student {:?} is not yours.", &suname);
+                return ApiError::forbidden(estr).into_response();
+            }
+        },
+        _ => {
+            return respond_bad_request(format!(
+                "The uname {:?} does not belong to a student in the system.",
+                &suname
+            ));
+        },
+    }
+
+    let p = match glob.get_pace_by_student(&suname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("Error getting Pace for student {:?}: {}", suname, &e);
+            return text_500(Some(format!("Error retrieving Pace from database: {}", &e)));
+        }
+    };
+
+    match fragment::summary_rows(&p, &glob) {
+        Ok(html) => fragment::respond("summary-rows", html),
+        Err(e) => {
+            log::error!("Error rendering summary rows for {:?}: {}", suname, &e);
+            text_500(Some(format!("Unable to render summary rows: {}", &e)))
+        }
+    }
+}
+
+/**
+Respond with a student's pace calendar rendered as Markdown (see
+[`report::pace_markdown`]), for pasting into newsletters and the like.
+
+Headers:
+```text
+x-camp-action: export-pace-markdown
+x-camp-student: [ uname of student ]
+```
+*/
+async fn export_pace_markdown(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let suname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+
+    let glob = glob.read().await;
+
+    match glob.users.get(&suname) {
+        Some(User::Student(s)) => {
+            if s.teacher != tuname {
+                let estr = format!("The student {:?} is not yours.", &suname);
+                return ApiError::forbidden(estr).into_response();
+            }
+        },
+        _ => {
+            return respond_bad_request(format!(
+                "The uname {:?} does not belong to a student in the system.",
+                &suname
+            ));
+        },
+    }
+
+    let p = match glob.get_pace_by_student(&suname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("Error getting Pace for student {:?}: {}", suname, &e);
+            return text_500(Some(format!("Error retrieving Pace from database: {}", &e)));
+        }
+    };
+    let pd = match PaceDisplay::from(&p, &glob) {
+        Ok(pd) => pd,
+        Err(e) => {
+            log::error!("Error generating PaceDisplay for {:?}: {}", suname, &e);
+            return text_500(Some(format!("Unable to display pace: {}", &e)));
+        }
+    };
+
+    let text = match report::pace_markdown(pd, &glob) {
+        Ok(text) => text,
+        Err(e) => {
+            log::error!("Error rendering pace Markdown for {:?}: {}", suname, &e);
+            return text_500(Some(format!("Unable to render pace as Markdown: {}", &e)));
+        }
+    };
+
+    let uname = match HeaderValue::from_str(&suname) {
+        Ok(uname) => uname,
+        Err(e) => {
+            let estr = format!(
+                "Error converting student uname {:?} into header value: {}",
+                suname, &e
+            );
+            log::error!("{}", &estr);
+            return text_500(Some(estr));
+        },
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (
+                HeaderName::from_static("x-camp-action"),
+                HeaderValue::from_static("export-pace-markdown"),
+            ),
+            (
+                HeaderName::from_static("x-camp-student"),
+                uname,
+            ),
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/markdown; charset=utf-8"),
+            ),
+        ],
+        text,
+    ).into_response()
+}
+
+/**
+Respond with a student's due dates as an iCalendar file (see
+[`ical`](super::ical)), for the student's family to import.
+
+Headers:
+```text
+x-camp-action: download-ical
+x-camp-student: [ uname of student ]
+```
+*/
+async fn download_ical(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
+    let suname = match CampStudent::from_headers(headers) {
+        Ok(CampStudent(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+    let tuname = match CampUname::from_headers(headers) {
+        Ok(CampUname(uname)) => uname,
+        Err(resp) => { return resp; },
+    };
+
+    let glob = glob.read().await;
+
+    match glob.users.get(&suname) {
+        Some(User::Student(s)) => {
+            if s.teacher != tuname {
+                let estr = format!("The student {:?} is not yours.", &suname);
+                return ApiError::forbidden(estr).into_response();
+            }
+        },
+        _ => {
+            return respond_bad_request(format!(
+                "The uname {:?} does not belong to a student in the system.",
+                &suname
+            ));
+        },
+    }
+
+    let p = match glob.get_pace_by_student(&suname).await {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("Error getting Pace for student {:?}: {}", suname, &e);
+            return text_500(Some(format!("Error retrieving Pace from database: {}", &e)));
+        }
+    };
+
+    let uname = match HeaderValue::from_str(&suname) {
+        Ok(uname) => uname,
+        Err(e) => {
+            let estr = format!(
+                "Error converting student uname {:?} into header value: {}",
+                suname, &e
+            );
+            log::error!("{}", &estr);
+            return text_500(Some(estr));
+        },
+    };
+
+    match PaceDisplay::from(&p, &glob) {
+        Ok(pd) => {
+            let mut resp = super::ical::respond(&pd);
+            resp.headers_mut().insert(HeaderName::from_static("x-camp-student"), uname);
+            resp
+        },
+        Err(e) => {
+            log::error!("Error generating PaceDisplay for {:?}: {}", suname, &e);
+            text_500(Some(format!("Unable to display pace: {}", &e)))
+        }
+    }
+}
+
+/**
+Report (and optionally change) whether the teacher receives the weekly
+summary email (see [`digest`](super::digest)).
+
+Header:
+```text
+x-camp-action: digest
+```
+With an optional body of `true` (to receive the email) or `false` (to opt
+out). The response is a JSON `true` or `false` reflecting the (possibly
+new) setting.
+*/
+async fn digest_setting(tuname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let data = glob.data();
+    let data = data.read().await;
+
+    if let Some(body) = body {
+        let receive: bool = match serde_json::from_str(&body) {
+            Ok(b) => b,
+            Err(e) => {
+                return respond_bad_request(format!(
+                    "Body should be either true or false, not {:?}: {}",
+                    &body, &e
+                ));
+            }
+        };
+        if let Err(e) = data.set_digest_optout(tuname, !receive).await {
+            log::error!("Error setting digest opt-out for {:?}: {}", tuname, &e);
+            return text_500(Some(format!("Unable to change setting: {}", &e)));
+        }
+    }
+
+    let receiving = match data.get_digest_optouts().await {
+        Ok(optouts) => !optouts.contains(tuname),
+        Err(e) => {
+            log::error!("Error retrieving digest opt-outs: {}", &e);
+            return text_500(Some(format!("Unable to retrieve setting: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("digest"),
+        )],
+        Json(receiving),
+    )
+        .into_response()
+}
+
+/**
+Report (and optionally change) the teacher's secret link to their read-only
+classroom progress board (see [`kiosk`](super::kiosk)).
+
+Header:
+```text
+x-camp-action: kiosk
+```
+With an optional body of `new` (to generate a new link, invalidating any
+old one) or `revoke` (to invalidate the link without replacing it). The
+response is a JSON string with the (possibly new) full link, or `null` if
+there isn't one.
+*/
+async fn kiosk_link(tuname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+    let data = glob.data();
+    let data = data.read().await;
+
+    let res = match body.as_deref().map(str::trim) {
+        None => data.get_kiosk_token(tuname).await,
+        Some("new") => data.new_kiosk_token(tuname).await.map(Some),
+        Some("revoke") => data.delete_kiosk_token(tuname).await.map(|_| None),
+        Some(x) => {
+            return respond_bad_request(format!(
+                "Body should be either \"new\" or \"revoke\", not {:?}.",
+                x
+            ));
+        }
+    };
+
+    let token = match res {
+        Ok(token) => token,
+        Err(e) => {
+            log::error!("Error managing kiosk link for {:?}: {}", tuname, &e);
+            return text_500(Some(format!("Unable to manage classroom display link: {}", &e)));
+        }
+    };
+    let link = token.map(|t| format!("{}/kiosk/{}", glob.uri.trim_end_matches('/'), &t));
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("kiosk"),
+        )],
+        Json(link),
+    )
+        .into_response()
+}
+
+/// One student's series in the response to a `burndown` request.
+#[derive(Debug, Serialize)]
+struct StudentBurndown<'a> {
+    uname: &'a str,
+    last: &'a str,
+    rest: &'a str,
+    total_weight: f32,
+    #[serde(flatten)]
+    series: Burndown,
+}
+
+/// The sum of all the teacher's students' series.
+#[derive(Debug, Serialize)]
+struct TotalBurndown {
+    total_weight: f32,
+    #[serde(flatten)]
+    series: Burndown,
+}
+
+/**
+Respond with data for drawing burn-down charts: the cumulative due and done
+weights of each of the teacher's students for every day of the calendar
+(see [`Burndown`]), along with their totals.
+
+Header:
+```text
+x-camp-action: burndown
+```
+
+Response body looks like
+```json
+{
+    "dates": [ "2022-08-29", "2022-08-30", ... ],
+    "students": [
+        {
+            "uname": "jsmith", "last": "Smith", "rest": "Jane",
+            "total_weight": 1.0,
+            "due": [ 0.0, 0.0, 0.05, ... ],
+            "done": [ 0.0, 0.04, 0.04, ... ]
+        },
+        ...
+    ],
+    "total": { "total_weight": 12.0, "due": [ ... ], "done": [ ... ] }
+}
+```
+*/
+async fn burndown(tuname: &str, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+
+    let paces = match glob.get_paces_by_teacher(tuname).await {
+        Ok(paces) => paces,
+        Err(e) => {
+            log::error!("Error retrieving Paces for teacher {:?}: {}", tuname, &e);
+            return text_500(Some(format!("Unable to retrieve pace data: {}", &e)));
+        }
+    };
+
+    let dates = match iso_dates(glob.calendar.days()) {
+        Ok(dates) => dates,
+        Err(e) => {
+            log::error!("Error formatting calendar dates: {}", &e);
+            return text_500(Some(format!("Unable to format calendar: {}", &e)));
+        }
+    };
+
+    let n_days = glob.calendar.len();
+    let mut total = TotalBurndown {
+        total_weight: 0.0,
+        series: Burndown {
+            due: vec![0.0; n_days],
+            done: vec![0.0; n_days],
         },
     };
+    let students: Vec<StudentBurndown> = paces
+        .iter()
+        .map(|p| {
+            let series = p.burndown(&glob.calendar);
+            total.total_weight += p.total_weight;
+            total.series.accumulate(&series);
+            StudentBurndown {
+                uname: &p.student.base.uname,
+                last: &p.student.last,
+                rest: &p.student.rest,
+                total_weight: p.total_weight,
+                series,
+            }
+        })
+        .collect();
 
     (
         StatusCode::OK,
-        [
-            (
-                HeaderName::from_static("x-camp-action"),
-                HeaderValue::from_static("edit-markdown"),
-            ),
-            (
-                HeaderName::from_static("x-camp-student"),
-                suname,
-            ),
-            (
-                HeaderName::from_static("x-camp-term"),
-                term,
-            ),
-            (
-                header::CONTENT_TYPE,
-                HeaderValue::from_static("text/markdown"),
-            ),
-        ],
-        text
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("burndown"),
+        )],
+        Json(json!({
+            "dates": dates,
+            "students": students,
+            "total": total,
+        })),
     )
         .into_response()
 }
 
-async fn generate_report(
-    headers: &HeaderMap,
+/// Longest allowable tag name, in characters.
+const MAX_TAG_LEN: usize = 64;
+
+/// Trim a requested tag name and ensure it's reasonable.
+fn check_tag_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Tag name cannot be blank.".to_owned());
+    }
+    if name.chars().count() > MAX_TAG_LEN {
+        return Err(format!(
+            "Tag names can be at most {} characters long.",
+            &MAX_TAG_LEN
+        ));
+    }
+    Ok(name)
+}
+
+/**
+Respond with the list of the teacher's goal tags.
+
+Header:
+```text
+x-camp-action: populate-tags
+```
+
+Response body is a JSON array of `{ "id": 3, "name": "test retake" }`
+objects, ordered by name. The `add-tag`, `update-tag`, and `delete-tag`
+actions respond the same way.
+*/
+async fn populate_tags(tuname: &str, glob: Arc<RwLock<Glob>>) -> Response {
+    let res = glob.read().await.data().read().await.get_tags(tuname).await;
+    let tags = match res {
+        Ok(tags) => tags,
+        Err(e) => {
+            log::error!("Error retrieving tags for {:?}: {}", tuname, &e);
+            return text_500(Some(format!("Unable to retrieve tags: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-tags"),
+        )],
+        Json(tags),
+    )
+        .into_response()
+}
+
+/**
+Create a new tag.
+
+Header:
+```text
+x-camp-action: add-tag
+```
+Body should be the (plain text) name of the tag.
+*/
+async fn add_tag(tuname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires the name of the tag in the body.".to_owned(),
+            );
+        }
+    };
+    let name = match check_tag_name(&body) {
+        Ok(name) => name,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .insert_tag(tuname, name)
+        .await;
+    if let Err(e) = res {
+        return respond_bad_request(format!("Unable to add tag: {}", &e));
+    }
+
+    populate_tags(tuname, glob).await
+}
+
+/**
+Rename a tag.
+
+Header:
+```text
+x-camp-action: update-tag
+```
+Body should look like `{ "id": 3, "name": "new name" }`.
+*/
+async fn update_tag(tuname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires application/json body with tag details.".to_owned(),
+            );
+        }
+    };
+    let tag: Tag = match serde_json::from_str(&body) {
+        Ok(tag) => tag,
+        Err(e) => {
+            return respond_bad_request(format!("Unable to deserialize tag: {}", &e));
+        }
+    };
+    let name = match check_tag_name(&tag.name) {
+        Ok(name) => name,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .rename_tag(tuname, tag.id, name)
+        .await;
+    if let Err(e) = res {
+        return respond_bad_request(format!("Unable to rename tag: {}", &e));
+    }
+
+    populate_tags(tuname, glob).await
+}
+
+/**
+Delete a tag (removing it from any goals that have it).
+
+Header:
+```text
+x-camp-action: delete-tag
+```
+Body should be the id of the tag.
+*/
+async fn delete_tag(tuname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request("Request requires id of tag in body.".to_owned());
+        }
+    };
+    let id: i64 = match body.trim().parse() {
+        Ok(n) => n,
+        Err(e) => {
+            return respond_bad_request(format!(
+                "Unable to parse body of request {:?} as tag id: {}",
+                &body, &e
+            ));
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .delete_tag(tuname, id)
+        .await;
+    if let Err(e) = res {
+        return respond_bad_request(format!("Unable to delete tag: {}", &e));
+    }
+
+    populate_tags(tuname, glob).await
+}
+
+/// Deserializes the body of a `tag-goals` request.
+#[derive(Debug, Deserialize)]
+struct TagGoals {
+    tag: i64,
+    goals: Vec<i64>,
+    #[serde(default)]
+    remove: bool,
+}
+
+/**
+Attach a tag to (or remove it from) a number of goals.
+
+Header:
+```text
+x-camp-action: tag-goals
+```
+Body should look like
+```json
+{ "tag": 3, "goals": [ 1021, 1022, 1043 ], "remove": false }
+```
+where `"remove"` is optional (and `false` by default). The response body is
+the number of goals whose tags changed.
+*/
+async fn tag_goals(tuname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires application/json body with tag and goal ids.".to_owned(),
+            );
+        }
+    };
+    let req: TagGoals = match serde_json::from_str(&body) {
+        Ok(req) => req,
+        Err(e) => {
+            return respond_bad_request(format!("Unable to deserialize tag-goals request: {}", &e));
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .tag_goals(tuname, req.tag, &req.goals, !req.remove)
+        .await;
+    let n = match res {
+        Ok(n) => n,
+        Err(e) => {
+            return respond_bad_request(format!("Unable to tag goals: {}", &e));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("tag-goals"),
+        )],
+        Json(n),
+    )
+        .into_response()
+}
+
+/// The longest (in characters) a custom chapter's title may be.
+const MAX_CUSTOM_TITLE_LEN: usize = 128;
+
+/// Deserializes the body of an `add-custom-chapter` or
+/// `update-custom-chapter` request.
+#[derive(Debug, Deserialize)]
+struct CustomData {
+    /// Ignored when adding a custom chapter.
+    #[serde(default)]
+    id: i64,
+    title: String,
+    weight: f32,
+}
+
+impl CustomData {
+    /// Trim the requested title and ensure it and the weight are reasonable.
+    fn check(&self) -> Result<&str, String> {
+        let title = self.title.trim();
+        if title.is_empty() {
+            return Err("Custom chapter title cannot be blank.".to_owned());
+        }
+        if title.chars().count() > MAX_CUSTOM_TITLE_LEN {
+            return Err(format!(
+                "Custom chapter titles can be at most {} characters long.",
+                &MAX_CUSTOM_TITLE_LEN
+            ));
+        }
+        if !(self.weight > 0.0 && self.weight <= 1.0) {
+            return Err(
+                "A custom chapter's weight must be more than 0 and at most 1 (a whole course)."
+                    .to_owned(),
+            );
+        }
+        Ok(title)
+    }
+}
+
+/// The custom chapters created by the teacher `tuname`, in the order they
+/// were created.
+fn customs_data<'a>(tuname: &str, glob: &'a Glob) -> Vec<&'a Custom> {
+    let mut customs: Vec<&Custom> = glob
+        .customs
+        .values()
+        .filter(|cus| cus.uname == tuname)
+        .collect();
+    customs.sort_by_key(|cus| cus.id);
+    customs
+}
+
+/// Ensure the custom chapter with the given `id` exists and was created by
+/// the teacher `tuname`.
+#[allow(clippy::result_large_err)]
+fn check_custom_owner(tuname: &str, id: i64, glob: &Glob) -> Result<(), Response> {
+    match glob.customs.get(&id) {
+        Some(cus) if cus.uname == tuname => Ok(()),
+        Some(_) => Err(respond_bad_request(format!(
+            "Custom chapter {} isn't one of yours.",
+            &id
+        ))),
+        None => Err(respond_bad_request(format!(
+            "There is no custom chapter with id {}.",
+            &id
+        ))),
+    }
+}
+
+/**
+Respond with the list of the teacher's custom chapters.
+
+Header:
+```text
+x-camp-action: populate-custom-chapters
+```
+Response body looks like
+```json
+[ { "id": 4, "uname": "berro", "title": "Science Fair Project", "weight": 0.1 } ]
+```
+*/
+async fn populate_custom_chapters(tuname: &str, glob: Arc<RwLock<Glob>>) -> Response {
+    let glob = glob.read().await;
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-custom-chapters"),
+        )],
+        Json(customs_data(tuname, &glob)),
+    )
+        .into_response()
+}
+
+/**
+Create a new custom chapter, for assigning goals that aren't part of any
+course. Its weight is a fraction of a whole course.
+
+Header:
+```text
+x-camp-action: add-custom-chapter
+```
+Body should look like `{ "title": "Science Fair Project", "weight": 0.1 }`.
+*/
+async fn add_custom_chapter(
+    tuname: &str,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires application/json body with custom chapter details.".to_owned(),
+            );
+        }
+    };
+    let cdata: CustomData = match serde_json::from_str(&body) {
+        Ok(cdata) => cdata,
+        Err(e) => {
+            return respond_bad_request(format!("Unable to deserialize custom chapter: {}", &e));
+        }
+    };
+    let title = match cdata.check() {
+        Ok(title) => title,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .insert_custom_chapter(tuname, title, cdata.weight)
+        .await;
+    match res {
+        Ok(cus) => {
+            log::info!("{:?} added custom chapter {:?}.", tuname, &cus);
+            glob.write().await.customs.insert(cus.id, cus);
+        }
+        Err(e) => {
+            log::error!("Error inserting custom chapter for {:?}: {}", tuname, &e);
+            return text_500(Some(format!("Unable to add custom chapter: {}", &e)));
+        }
+    }
+
+    populate_custom_chapters(tuname, glob).await
+}
+
+/**
+Change the title or weight of one of the teacher's custom chapters. Goals
+that use it get its new weight.
+
+Header:
+```text
+x-camp-action: update-custom-chapter
+```
+Body should look like `{ "id": 4, "title": "Science Fair Poster", "weight": 0.05 }`.
+*/
+async fn update_custom_chapter(
+    tuname: &str,
     body: Option<String>,
-    glob: Arc<RwLock<Glob>>
+    glob: Arc<RwLock<Glob>>,
 ) -> Response {
-    let suname = match get_head("x-camp-student", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return respond_bad_request(e); },
-    };
-    let tuname = match get_head("x-camp-uname", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return text_500(Some(e)); },
-    };
-    let term = match get_head("x-camp-term", headers) {
-        Ok(term) => term,
-        Err(e) => { return respond_bad_request(e); },
-    };
-    let term = match Term::from_str(term) {
-        Ok(term) => term,
-        Err(e) => {
-            log::warn!(
-                "Invalid x-camp-term value ({:?}) in attempt to generate report for {:?}: {}",
-                term, suname, &e
-            );
-            return respond_bad_request(format!(
-                "Invalid x-camp-term value {:?}: {}", term, &e
-            ));
-        },
-    };
     let body = match body {
         Some(body) => body,
         None => {
             return respond_bad_request(
-                "Request needs application/json body with ReportSidecar details.".to_owned(),
+                "Request requires application/json body with custom chapter details.".to_owned(),
             );
         }
     };
-
-    let glob = glob.read().await;
-
-    match glob.users.get(suname) {
-        Some(User::Student(s)) => {
-            if s.teacher != tuname {
-                let estr = format!("The student {:?} is not yours.", &suname);
-                return (StatusCode::FORBIDDEN, estr).into_response();
-            }
-        },
-        _ => {
-            let estr = format!(
-                "The uname {:?} does not belong to a student in the system.",
-                &suname
-            );
-            return respond_bad_request(estr);
+    let cdata: CustomData = match serde_json::from_str(&body) {
+        Ok(cdata) => cdata,
+        Err(e) => {
+            return respond_bad_request(format!("Unable to deserialize custom chapter: {}", &e));
         }
-    }
+    };
+    let title = match cdata.check() {
+        Ok(title) => title,
+        Err(e) => {
+            return respond_bad_request(e);
+        }
+    };
 
+    let cus = Custom {
+        id: cdata.id,
+        uname: tuname.to_owned(),
+        title: title.to_owned(),
+        weight: cdata.weight,
+    };
     {
-        let data_guard = glob.data();
-        let data = data_guard.read().await;
-        let mut client = match data.connect().await {
-            Ok(client) => client,
-            Err(e) => { return text_500(Some(e.to_string())); },
-        };
-        let t = match client.transaction().await {
-            Ok(trans) => trans,
-            Err(e) => { return text_500(Some(e.to_string())); },
-        };
-        if let Err(e) = Store::set_draft(&t, suname, term, &body).await {
-            log::error!(
-                "Error attempting to store {} report draft for {:?}: {}",
-                &term, &suname, &e
-            );
-            return text_500(Some(format!(
-                "Error attempting to store report draft in database: {}", &e
-            )));
+        let glob = glob.read().await;
+        if let Err(resp) = check_custom_owner(tuname, cus.id, &glob) {
+            return resp;
         }
-        if let Err(e) = t.commit().await {
-            log::error!(
-                "Error committing transaction for storing {} report draft for {:?}: {}",
-                &term, &suname, &e
-            );
-            return text_500(Some(format!(
-                "Error committing report draft to database: {}", &e
-            )));
+        let res = glob.data().read().await.update_custom_chapter(&cus).await;
+        if let Err(e) = res {
+            log::error!("Error updating custom chapter {:?}: {}", &cus, &e);
+            return text_500(Some(format!("Unable to update custom chapter: {}", &e)));
         }
     }
+    glob.write().await.customs.insert(cus.id, cus);
 
-    let pdf_data = match report::render_markdown(body, &glob).await {
-        Ok(data) => data,
-        Err(e) => {
-            log::error!(
-                "Error attempting to render {} report PDF for {:?}: {}",
-                &term, suname, &e
+    populate_custom_chapters(tuname, glob).await
+}
+
+/**
+Delete one of the teacher's custom chapters. This is refused as long as any
+goals use it.
+
+Header:
+```text
+x-camp-action: delete-custom-chapter
+```
+Body should be the id of the custom chapter.
+*/
+async fn delete_custom_chapter(
+    tuname: &str,
+    body: Option<String>,
+    glob: Arc<RwLock<Glob>>,
+) -> Response {
+    let body = match body {
+        Some(body) => body,
+        None => {
+            return respond_bad_request(
+                "Request requires id of custom chapter in body.".to_owned(),
             );
-            return text_500(Some(format!(
-                "Error generating PDF file: {}", &e
-            )));
-        },
+        }
+    };
+    let id: i64 = match body.trim().parse() {
+        Ok(n) => n,
+        Err(e) => {
+            return respond_bad_request(format!(
+                "Unable to parse body of request {:?} as custom chapter id: {}",
+                &body, &e
+            ));
+        }
     };
 
     {
-        let data_guard = glob.data();
-        let data = data_guard.read().await;
-        let mut client = match data.connect().await {
-            Ok(client) => client,
-            Err(e) => { return text_500(Some(e.to_string())); },
-        };
-        let t = match client.transaction().await {
-            Ok(trans) => trans,
-            Err(e) => { return text_500(Some(e.to_string())); },
-        };
-        if let Err(e) = Store::set_final(&t, suname, term, &pdf_data).await {
-            log::error!(
-                "Error attempting to store final {} report PDF for {:?}: {}",
-                &term, &suname, &e
-            );
-            return text_500(Some(format!(
-                "Error attempting to store report PDF in database: {}", &e
-            )));
+        let glob = glob.read().await;
+        if let Err(resp) = check_custom_owner(tuname, id, &glob) {
+            return resp;
         }
-        if let Err(e) = t.commit().await {
-            log::error!(
-                "Error committing transaction for storing {} report PDF for {:?}: {}",
-                &term, &suname, &e
-            );
-            return text_500(Some(format!(
-                "Error committing final report PDF to database: {}", &e
-            )));
+        let res = glob.data().read().await.delete_custom_chapter(id).await;
+        if let Err(e) = res {
+            return respond_bad_request(format!("Unable to delete custom chapter: {}", &e));
         }
     }
+    glob.write().await.customs.remove(&id);
 
-    let uname = match HeaderValue::from_str(suname) {
-        Ok(uname) => uname,
-        Err(e) => {
-            let estr = format!(
-                "Error converting student uname {:?} into header value: {}",
-                suname, &e
-            );
-            log::error!("{}", &estr);
-            return text_500(Some(estr));
-        },
-    };
-
-    (
-        StatusCode::OK,
-        [
-            (
-                header::CONTENT_TYPE,
-                HeaderValue::from_static("application/pdf"),
-            ),
-            (
-                HeaderName::from_static("x-camp-action"),
-                HeaderValue::from_static("display-pdf"),
-            ),
-            (
-                HeaderName::from_static("x-camp-student"),
-                uname,
-            ),
-            (
-                HeaderName::from_static("x-camp-term"),
-                headers.get("x-camp-term").unwrap().clone(),
-            ),
-        ],
-        pdf_data
-    ).into_response()
+    populate_custom_chapters(tuname, glob).await
 }
 
-async fn discard_pdf(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
-    let suname = match get_head("x-camp-student", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return respond_bad_request(e); },
-    };
-    let tuname = match get_head("x-camp-uname", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return text_500(Some(e)); },
-    };
-    let term = match get_head("x-camp-term", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return respond_bad_request(e); },
-    };
-    let term = match Term::from_str(term) {
-        Ok(t) => t,
-        Err(e) => {
-            log::warn!(
-                "Invalid x-camp-term value ({:?}) in attempt to generate report for {:?}: {}",
-                term, suname, &e
-            );
-            return respond_bad_request(format!(
-                "Invalid x-camp-term value {:?}: {}", term, &e
-            ));
-        },
-    };
+/**
+Respond with the weekly lag history of the student whose uname is the body
+of the request, or of all of the teacher's students if there's no body; see
+[`lag_history::respond`].
 
+Header:
+```text
+x-camp-action: lag-history
+```
+*/
+async fn lag_history(tuname: &str, body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
     let glob = glob.read().await;
-    match glob.users.get(suname) {
-        Some(User::Student(s)) => {
-            if s.teacher != tuname {
-                let estr = format!("The student {:?} is not yours.", &suname);
-                return (StatusCode::FORBIDDEN, estr).into_response();
+
+    let students: Vec<&Student> = match body.as_deref().map(str::trim) {
+        Some(uname) if !uname.is_empty() => match glob.users.get(uname) {
+            Some(User::Student(s)) if s.teacher == tuname => vec![s],
+            _ => {
+                return respond_bad_request(format!("{:?} is not your student.", uname));
             }
         },
-        _ => {
-            let estr = format!(
-                "The uname {:?} does not belong to a student in the system.",
-                &suname
-            );
-            return respond_bad_request(estr);
-        },
+        _ => glob
+            .get_students_by_teacher(tuname)
+            .into_iter()
+            .filter_map(|u| match u {
+                User::Student(s) => Some(s),
+                _ => None,
+            })
+            .collect(),
+    };
+
+    lag_history::respond(students, &glob).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use time::macros::date;
+
+    use crate::store::MemStore;
+
+    #[test]
+    fn stale_course_tokens() {
+        let mut headers = HeaderMap::new();
+        assert!(!courses_stale("update-goal", &headers, "1f3a9c0e5b7d2468"));
+
+        headers.insert(COURSES_HEADER, HeaderValue::from_static("1f3a9c0e5b7d2468"));
+        assert!(!courses_stale("update-goal", &headers, "1f3a9c0e5b7d2468"));
+        assert!(courses_stale("update-goal", &headers, "0000000000000000"));
+        assert!(courses_stale("autopace", &headers, "0000000000000000"));
+        assert!(!courses_stale("delete-goal", &headers, "0000000000000000"));
     }
 
-    match glob.data().read().await.clear_final(suname, term).await {
-        Ok(()) => { return respond_ok(); },
-        Err(e) => {
-            log::error!(
-                "Error attempting to discard {} report PDF for {:?}: {}",
-                &term, suname, &e
-            );
-            return text_500(Some(format!(
-                "Error attempting to discard report PDF: {}", &e
-            )));
+    #[test]
+    fn sync_timestamps() {
+        let t = time::macros::datetime!(2022 - 10 - 03 14:05:09.123456);
+        let s = t.format(SYNC_TIME_FMT).unwrap();
+        assert_eq!(&s, "2022-10-03 14:05:09.123456");
+        assert_eq!(PrimitiveDateTime::parse(&s, SYNC_TIME_FMT).unwrap(), t);
+        assert!(PrimitiveDateTime::parse("2022-10-03", SYNC_TIME_FMT).is_err());
+    }
+
+    #[test]
+    fn delete_goal_filters() {
+        let dates: HashMap<String, Date> = [
+            ("end-fall".to_owned(), date!(2023 - 01 - 13)),
+            ("end-spring".to_owned(), date!(2023 - 05 - 26)),
+        ]
+        .into_iter()
+        .collect();
+        let ddata = |body: &str| -> DeleteGoalsData { serde_json::from_str(body).unwrap() };
+
+        let d = ddata(r#"{"uname": "frog", "sym": "pc", "term": "spring"}"#);
+        let f = goal_filter(&d, &dates).unwrap();
+        assert_eq!(f.sym, Some("pc"));
+        assert_eq!(f.due_from, Some(date!(2023 - 01 - 13)));
+        assert_eq!(f.due_before, Some(date!(2023 - 05 - 26)));
+        assert!(!f.unstarted);
+
+        let d = ddata(r#"{"uname": "frog", "term": "Fall", "unstarted": true}"#);
+        let f = goal_filter(&d, &dates).unwrap();
+        assert_eq!((f.due_from, f.due_before), (None, Some(date!(2023 - 01 - 13))));
+        assert!(f.unstarted);
+
+        let d = ddata(r#"{"uname": "frog", "term": "summer"}"#);
+        let f = goal_filter(&d, &dates).unwrap();
+        assert_eq!((f.due_from, f.due_before), (Some(date!(2023 - 05 - 26)), None));
+
+        assert!(goal_filter(&ddata(r#"{"uname": "frog"}"#), &dates).is_err());
+        assert!(goal_filter(&ddata(r#"{"uname": "frog", "unstarted": false}"#), &dates).is_err());
+        assert!(goal_filter(&ddata(r#"{"uname": "frog", "term": "winter"}"#), &dates).is_err());
+        let no_dates: HashMap<String, Date> = HashMap::new();
+        assert!(goal_filter(&ddata(r#"{"uname": "frog", "term": "fall"}"#), &no_dates).is_err());
+    }
+
+    fn goal(seq: i16, due: Date) -> Goal {
+        Goal {
+            id: 0,
+            uname: "frog".to_owned(),
+            source: Source::Book(BookCh {
+                sym: "pc".to_owned(),
+                seq,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due: Some(due),
+            done: None,
+            tries: None,
+            weight: 0.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
         }
     }
-}
 
-async fn student_history(headers: &HeaderMap, glob: Arc<RwLock<Glob>>) -> Response {
-    let suname = match get_head("x-camp-student", headers) {
-        Ok(uname) => uname,
-        Err(e) => { return respond_bad_request(e); },
-    };
+    /// Teacher "berro" and student "frog", who is in berro's class.
+    fn users() -> [User; 2] {
+        let teacher = BaseUser {
+            uname: "berro".to_owned(),
+            role: Role::Teacher,
+            salt: String::new(),
+            email: "berro@camelotacademy.org".to_owned(),
+        }
+        .into_teacher("Mr Berro".to_owned());
+        let student = BaseUser {
+            uname: "frog".to_owned(),
+            role: Role::Student,
+            salt: String::new(),
+            email: "fred.frog@gmail.com".to_owned(),
+        }
+        .into_student(
+            "Frog".to_owned(),
+            "Frederick".to_owned(),
+            "berro".to_owned(),
+            "ferd.frog@gmail.com".to_owned(),
+            None,
+            None,
+            0.2,
+            0.2,
+            0,
+            0,
+        );
+        [teacher, student]
+    }
 
-    let hist = match glob.read().await.get_student_completion_history(suname).await {
-        Ok(hist) => hist,
-        Err(e) => {
-            log::error!(
-                "Error attempting to retrieve completion history for {:?}: {}",
-                suname, &e
-            );
-            return text_500(Some(format!(
-                "Error reading from database: {}", &e
-            )));
-        },
-    };
+    #[tokio::test]
+    async fn constrained_due_dates() {
+        // Course "pc" requires 3 instructional days between chapters.
+        let crs = Course::from_reader(std::fs::File::open("test/good_course_1.mix").unwrap())
+            .unwrap();
+        // The week of Monday 10/3.
+        let days: Vec<Date> = (3..=7)
+            .map(|d| Date::from_calendar_date(2022, time::Month::October, d).unwrap())
+            .collect();
+        let first = days[0];
+        let store = MemStore::new()
+            .with_users(users())
+            .with_courses([crs])
+            .with_calendar(days)
+            .with_goals(&[goal(1, first), goal(2, first), goal(3, first)])
+            .unwrap();
+        let glob = Arc::new(RwLock::new(
+            crate::config::in_memory(store).await.unwrap(),
+        ));
+
+        // Weights 8, 9, and 8 put the chapters too close together.
+        let resp = autopace(&HeaderMap::new(), Some("frog".to_owned()), glob.clone()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().contains_key("x-camp-warning"));
 
-    (
-        StatusCode::OK,
-        [
-            (
-                HeaderName::from_static("x-camp-action"),
-                HeaderValue::from_static("student-history"),
-            ),
-            (
-                HeaderName::from_static("x-camp-student"),
-                headers.get("x-camp-student").unwrap().clone(),
-            ),
-        ],
-        Json(&hist)
-    ).into_response()
-}
\ No newline at end of file
+        let glob = glob.read().await;
+        let goals = glob.store().get_goals_by_student("frog").await.unwrap();
+        let due: Vec<Option<Date>> = goals.iter().map(|g| g.due).collect();
+        assert_eq!(
+            due,
+            vec![
+                Some(date!(2022 - 10 - 4)),
+                Some(date!(2022 - 10 - 6)),
+                Some(date!(2022 - 10 - 7)),
+            ]
+        );
+
+        // Moving chapter 2 even closer to chapter 1 is refused...
+        let mut g = goals[1].clone();
+        g.due = Some(date!(2022 - 10 - 5));
+        assert!(check_goal_constraints(&g, &glob).await.is_err());
+        // ...but moving chapter 1 far enough away is fine, and so is leaving
+        // chapter 3 where it is, even though it's already too close.
+        let mut g = goals[0].clone();
+        g.due = Some(date!(2022 - 10 - 3));
+        assert!(check_goal_constraints(&g, &glob).await.is_ok());
+        assert!(check_goal_constraints(&goals[2], &glob).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn absent_due_dates() {
+        let crs = Course::from_reader(std::fs::File::open("test/good_course_0.mix").unwrap())
+            .unwrap();
+        // The week of Monday 10/3, with frog out Tuesday and Wednesday.
+        let days: Vec<Date> = (3..=7)
+            .map(|d| Date::from_calendar_date(2022, time::Month::October, d).unwrap())
+            .collect();
+        let first = days[0];
+        let away = Absence {
+            id: 1,
+            uname: "frog".to_owned(),
+            start: date!(2022 - 10 - 4),
+            end: date!(2022 - 10 - 5),
+            reason: "dentist".to_owned(),
+        };
+        let store = MemStore::new()
+            .with_users(users())
+            .with_courses([crs])
+            .with_calendar(days)
+            .with_absences("frog", vec![away])
+            .with_goals(&[goal(1, first), goal(2, first), goal(3, first)])
+            .unwrap();
+        let glob = Arc::new(RwLock::new(
+            crate::config::in_memory(store).await.unwrap(),
+        ));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AVOID_ABSENCES_HEADER, HeaderValue::from_static("true"));
+        let resp = autopace(&headers, Some("frog".to_owned()), glob.clone()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let glob = glob.read().await;
+        let goals = glob.store().get_goals_by_student("frog").await.unwrap();
+        let due: Vec<Option<Date>> = goals.iter().map(|g| g.due).collect();
+        assert_eq!(
+            due,
+            vec![
+                Some(date!(2022 - 10 - 3)),
+                Some(date!(2022 - 10 - 7)),
+                Some(date!(2022 - 10 - 7)),
+            ]
+        );
+
+        // Moving a goal into the absence is only refused when asked.
+        let mut g = goals[0].clone();
+        g.due = Some(date!(2022 - 10 - 5));
+        assert!(check_goal_absences(&g, &headers, &glob).await.is_err());
+        assert!(check_goal_absences(&g, &HeaderMap::new(), &glob).await.is_ok());
+        // A goal that's already there can still be changed otherwise.
+        glob.store().update_goal(&g).await.unwrap();
+        g.review = true;
+        assert!(check_goal_absences(&g, &headers, &glob).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn fragments_of_own_students() {
+        let crs = Course::from_reader(std::fs::File::open("test/good_course_0.mix").unwrap())
+            .unwrap();
+        let first = date!(2022 - 10 - 03);
+        let store = MemStore::new()
+            .with_users(users())
+            .with_courses([crs])
+            .with_calendar(vec![first])
+            .with_goals(&[goal(1, first)])
+            .unwrap();
+        let mut glob = crate::config::in_memory(store).await.unwrap();
+        glob.dates.insert("end-fall".to_owned(), date!(2023 - 01 - 13));
+        glob.dates.insert("end-spring".to_owned(), date!(2023 - 05 - 26));
+        let glob = Arc::new(RwLock::new(glob));
+        crate::inter::init("camp-docker/camp/templates").unwrap();
+        let id = glob.read().await.store().get_goals_by_student("frog").await.unwrap()[0].id;
+
+        let headers = |tuname: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-camp-uname", HeaderValue::from_static(tuname));
+            headers.insert("x-camp-student", HeaderValue::from_static("frog"));
+            headers
+        };
+
+        let resp = goal_row(&headers("berro"), Some(id.to_string()), glob.clone()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = summary_rows(&headers("berro"), glob.clone()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Other Teachers can't see frog's calendar.
+        let resp = goal_row(&headers("jones"), Some(id.to_string()), glob.clone()).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = summary_rows(&headers("jones"), glob.clone()).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn custom_goals() {
+        let crs = Course::from_reader(std::fs::File::open("test/good_course_0.mix").unwrap())
+            .unwrap();
+        let custom = |uname: &str, title: &str| Custom {
+            id: 0,
+            uname: uname.to_owned(),
+            title: title.to_owned(),
+            weight: 0.25,
+        };
+        let first = date!(2022 - 10 - 03);
+        let sci_fair = Goal {
+            source: Source::Custom(CustomCh(1)),
+            ..goal(1, first)
+        };
+        let store = MemStore::new()
+            .with_users(users())
+            .with_courses([crs])
+            .with_calendar(vec![first])
+            .with_custom_chapters([
+                custom("berro", "Science Fair Project"),
+                custom("somebody_else", "Book Report"),
+            ])
+            .with_goals(&[goal(1, first), sci_fair.clone()])
+            .unwrap();
+        let glob = crate::config::in_memory(store).await.unwrap();
+
+        let p = glob.get_pace_by_student("frog").await.unwrap();
+        let g = p.goals.iter().find(|g| g.source == sci_fair.source).unwrap();
+        assert!((g.weight - 0.25).abs() < 0.0001);
+        let pdata = PaceData::from_pace(&p).unwrap();
+        let gdata = pdata.goals.iter().find(|g| g.custom == Some(1)).unwrap();
+        assert_eq!((gdata.sym, gdata.seq), ("", 0));
+
+        // Students who aren't supposed to see scores don't get them.
+        let mut scored = p;
+        scored.goals[0].score = Some("93".to_owned());
+        scored.goals[0].rubric = vec![RubricItem {
+            name: "Setup".to_owned(),
+            points: 3.0,
+            max: 4.0,
+        }];
+        let mut pdata = PaceData::from_pace(&scored).unwrap();
+        assert_eq!(pdata.goals[0].score, Some("93"));
+        pdata.hide_scores();
+        assert!(pdata.goals.iter().all(|g| g.score.is_none() && g.rubric.is_empty()));
+
+        // Custom goals come from the frontend without a sym or seq.
+        let json = r#"{
+            "id": 0, "uname": "frog", "custom": 1, "rev": false, "inc": false,
+            "due": "2022-10-03", "done": null, "tries": null, "weight": 0.0,
+            "score": null
+        }"#;
+        let g = serde_json::from_str::<GoalData>(json)
+            .unwrap()
+            .into_goal()
+            .unwrap();
+        assert_eq!(g.source, sci_fair.source);
+        assert!(check_custom_source(&g, &glob).is_ok());
+
+        // Teachers can only assign their own custom chapters.
+        let mut g = g.clone();
+        g.source = Source::Custom(CustomCh(2));
+        assert!(check_custom_source(&g, &glob).is_err());
+        g.source = Source::Custom(CustomCh(3));
+        assert!(check_custom_source(&g, &glob).is_err());
+
+        let customs = customs_data("berro", &glob);
+        assert_eq!(customs.len(), 1);
+        assert_eq!(&customs[0].title, "Science Fair Project");
+        assert!(check_custom_owner("berro", 1, &glob).is_ok());
+        assert!(check_custom_owner("berro", 2, &glob).is_err());
+
+        let cdata = |title: &str, weight: f32| CustomData {
+            id: 0,
+            title: title.to_owned(),
+            weight,
+        };
+        assert_eq!(cdata("  Poster ", 0.1).check(), Ok("Poster"));
+        assert!(cdata(" ", 0.1).check().is_err());
+        assert!(cdata("Poster", 0.0).check().is_err());
+        assert!(cdata("Poster", 1.5).check().is_err());
+        assert!(cdata("Poster", f32::NAN).check().is_err());
+    }
+}
@@ -0,0 +1,211 @@
+/*!
+Unsubscribe links for parent progress emails.
+
+Every progress email includes a link to "/unsubscribe" with the parent's
+address and a token signing it (so nobody can unsubscribe anyone else).
+Following the link only shows a page asking whether to unsubscribe, because
+mail scanners fetch the links in messages to check them out; the page's
+button `POST`s back to the same place, which records the opt-out (see
+[`Store::set_email_optout`](crate::store::Store::set_email_optout)), and
+the Boss's `email-all` action skips that address from then on.
+*/
+use std::{fmt::Write, sync::Arc};
+
+use axum::{
+    extract::{Form, Query},
+    http::StatusCode,
+    response::Response,
+    Extension,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use super::{html_500, serve_template};
+use crate::config::Glob;
+
+/// Return the token that authorizes unsubscribing `email`, given the
+/// signing `secret`.
+pub fn token(secret: &str, email: &str) -> blake3::Hash {
+    let key = blake3::hash(secret.as_bytes());
+    blake3::keyed_hash(key.as_bytes(), email.to_lowercase().as_bytes())
+}
+
+/// Percent-encode everything but unreserved characters, for use in a query
+/// string.
+fn url_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char);
+            }
+            _ => {
+                // Writing to a `String` can't fail.
+                write!(&mut encoded, "%{:02X}", b).unwrap();
+            }
+        }
+    }
+    encoded
+}
+
+/// Return the full unsubscribe link for `email`.
+pub fn link(glob: &Glob, email: &str) -> String {
+    format!(
+        "{}/unsubscribe?email={}&token={}",
+        glob.uri.trim_end_matches('/'),
+        url_encode(email),
+        token(&glob.unsubscribe_secret, email).to_hex()
+    )
+}
+
+/// The query string of an unsubscribe link, which is also what the
+/// confirmation page's form `POST`s.
+#[derive(Deserialize)]
+pub struct UnsubscribeQuery {
+    email: String,
+    token: String,
+}
+
+fn respond(code: StatusCode, message: &str) -> Response {
+    serve_template(code, "unsubscribed", &json!({ "message": message }), vec![])
+}
+
+/// Check that `q` has the right token for its email address; if not, return
+/// the page saying so.
+#[allow(clippy::result_large_err)]
+fn check(q: &UnsubscribeQuery, glob: &Glob) -> Result<(), Response> {
+    let valid = match blake3::Hash::from_hex(q.token.trim()) {
+        // `blake3::Hash` equality is constant-time.
+        Ok(t) => t == token(&glob.unsubscribe_secret, &q.email),
+        Err(_) => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        log::warn!("Invalid unsubscribe token for {:?}.", &q.email);
+        Err(respond(
+            StatusCode::BAD_REQUEST,
+            "This unsubscribe link is invalid. Please make sure you copied the entire link.",
+        ))
+    }
+}
+
+/// Handler for GET requests to "/unsubscribe", which are made by following
+/// the link in a progress email. Doesn't change anything; just asks whether
+/// to unsubscribe.
+pub async fn confirm_page(
+    Query(q): Query<UnsubscribeQuery>,
+    Extension(glob): Extension<Arc<RwLock<Glob>>>,
+) -> Response {
+    log::trace!("unsubscribe::confirm_page( {:?} ) called.", &q.email);
+
+    if let Err(resp) = check(&q, &*glob.read().await) {
+        return resp;
+    }
+
+    let data = json!({
+        "message": format!("Stop sending automatic progress emails to {}?", &q.email),
+        "email": &q.email,
+        "token": &q.token,
+    });
+    serve_template(StatusCode::OK, "unsubscribed", &data, vec![])
+}
+
+/// Handler for POST requests to "/unsubscribe", made from the page served by
+/// [`confirm_page`].
+pub async fn unsubscribe(
+    Form(q): Form<UnsubscribeQuery>,
+    Extension(glob): Extension<Arc<RwLock<Glob>>>,
+) -> Response {
+    log::trace!("unsubscribe( {:?} ) called.", &q.email);
+
+    let glob = glob.read().await;
+    if let Err(resp) = check(&q, &glob) {
+        return resp;
+    }
+
+    let res = glob
+        .data()
+        .read()
+        .await
+        .set_email_optout(&q.email, true)
+        .await;
+    if let Err(e) = res {
+        log::error!("Error recording email opt-out for {:?}: {}", &q.email, &e);
+        return html_500();
+    }
+
+    log::info!("{:?} unsubscribed from progress emails.", &q.email);
+    respond(
+        StatusCode::OK,
+        &format!(
+            "{} will no longer receive automatic progress emails. \
+            If this was a mistake, please contact your student's teacher.",
+            &q.email
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::tests::ensure_logging;
+
+    #[test]
+    fn tokens_and_encoding() {
+        assert_eq!(
+            token("secret", "Ferd.Frog@gmail.com"),
+            token("secret", "ferd.frog@gmail.com")
+        );
+        assert_ne!(
+            token("secret", "ferd.frog@gmail.com"),
+            token("other", "ferd.frog@gmail.com")
+        );
+        assert_eq!(url_encode("a.b+c@d-e.org"), "a.b%2Bc%40d-e.org");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn only_post_unsubscribes() {
+        ensure_logging();
+        crate::inter::init("camp-docker/camp/templates").unwrap();
+
+        let mut glob = crate::config::in_memory(crate::store::MemStore::new())
+            .await
+            .unwrap();
+        glob.unsubscribe_secret = "secret".to_owned();
+        glob.data().read().await.ensure_db_schema().await.unwrap();
+        let glob = Arc::new(RwLock::new(glob));
+        let email = "ferd.frog@gmail.com";
+        let q = || UnsubscribeQuery {
+            email: email.to_owned(),
+            token: token("secret", email).to_hex().to_string(),
+        };
+        let optouts = || async {
+            glob.read().await.data().read().await.get_email_optouts().await.unwrap()
+        };
+
+        // Following the link (or a mail scanner following it) just asks.
+        let resp = confirm_page(Query(q()), Extension(glob.clone())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(optouts().await.is_empty());
+
+        let bad = UnsubscribeQuery {
+            token: token("other", email).to_hex().to_string(),
+            ..q()
+        };
+        let resp = unsubscribe(Form(bad), Extension(glob.clone())).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(optouts().await.is_empty());
+
+        let resp = unsubscribe(Form(q()), Extension(glob.clone())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(optouts().await.contains(email));
+
+        glob.read().await.data().read().await.nuke_database().await.unwrap();
+    }
+}
@@ -0,0 +1,283 @@
+/*!
+Admin API actions for managing outbound webhooks (see
+[`webhook`](crate::webhook)).
+
+  * `populate-webhooks` lists the registered webhooks, secrets and all.
+  * `add-webhook`, `update-webhook`, and `delete-webhook` change them, and
+    respond like `populate-webhooks`.
+  * `webhook-deliveries` shows the most recent deliveries and how they went.
+
+These are only for unrestricted Admins, because the secrets are in the
+responses.
+*/
+use std::sync::Arc;
+
+use axum::{
+    http::header::{HeaderName, HeaderValue},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+use super::{respond_bad_request, text_500, ApiError};
+use crate::{
+    config::Glob,
+    webhook::{check_url, Event},
+};
+
+/// Number of deliveries shown by `webhook-deliveries`.
+const LOG_LENGTH: i64 = 200;
+
+/// Deserializes the body of an `add-webhook` or `update-webhook` request.
+#[derive(Debug, Deserialize)]
+struct WebhookData {
+    /// Only for `update-webhook`.
+    id: Option<i64>,
+    url: String,
+    events: Vec<Event>,
+    #[serde(default = "active_default")]
+    active: bool,
+}
+
+fn active_default() -> bool {
+    true
+}
+
+/// Read and check the body of an `add-webhook` or `update-webhook` request.
+#[allow(clippy::result_large_err)]
+fn read_webhook_data(body: Option<String>) -> Result<WebhookData, Response> {
+    let mut wdata: WebhookData = match body.as_deref().map(serde_json::from_str) {
+        Some(Ok(wdata)) => wdata,
+        Some(Err(e)) => {
+            return Err(respond_bad_request(format!(
+                "Unable to read webhook details: {}",
+                &e
+            )));
+        }
+        None => {
+            return Err(respond_bad_request(
+                "Request needs a JSON body with the webhook's details.".to_owned(),
+            ));
+        }
+    };
+
+    wdata.url = wdata.url.trim().to_owned();
+    if let Err(e) = check_url(&wdata.url) {
+        return Err(respond_bad_request(e));
+    }
+    if wdata.events.is_empty() {
+        return Err(respond_bad_request(
+            "A webhook needs at least one event.".to_owned(),
+        ));
+    }
+    // In a standard order, without duplicates.
+    wdata.events = Event::ALL
+        .iter()
+        .filter(|e| wdata.events.contains(e))
+        .copied()
+        .collect();
+
+    Ok(wdata)
+}
+
+/**
+Respond with all the registered webhooks.
+
+```text
+x-camp-action: populate-webhooks
+```
+
+Response body looks like
+```json
+[
+    {
+        "id": 1,
+        "url": "https://tutoring.example.org/camp",
+        "secret": "q3Vx...",
+        "events": ["goal-completed", "student-lagging"],
+        "active": true
+    }
+]
+```
+*/
+pub async fn populate(glob: Arc<RwLock<Glob>>) -> Response {
+    let hooks = match glob.read().await.data().read().await.get_webhooks().await {
+        Ok(hooks) => hooks,
+        Err(e) => {
+            log::error!("Error retrieving webhooks: {}", &e);
+            return text_500(Some(format!("Unable to retrieve webhooks: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("populate-webhooks"),
+        )],
+        Json(hooks),
+    )
+        .into_response()
+}
+
+/**
+Register a new webhook; its secret is generated.
+
+```text
+x-camp-action: add-webhook
+```
+With a JSON body like
+```json
+{ "url": "https://tutoring.example.org/camp", "events": ["goal-completed"] }
+```
+*/
+pub async fn add(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let wdata = match read_webhook_data(body) {
+        Ok(wdata) => wdata,
+        Err(resp) => {
+            return resp;
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .insert_webhook(&wdata.url, &wdata.events)
+        .await;
+    if let Err(e) = res {
+        log::error!("Error inserting webhook {:?}: {}", &wdata, &e);
+        return text_500(Some(format!("Unable to add webhook: {}", &e)));
+    }
+
+    populate(glob).await
+}
+
+/**
+Change a webhook's URL, events, or whether it's active.
+
+```text
+x-camp-action: update-webhook
+```
+With a JSON body like
+```json
+{
+    "id": 1,
+    "url": "https://tutoring.example.org/camp",
+    "events": ["goal-completed", "report-finalized"],
+    "active": false
+}
+```
+*/
+pub async fn update(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let wdata = match read_webhook_data(body) {
+        Ok(wdata) => wdata,
+        Err(resp) => {
+            return resp;
+        }
+    };
+    let id = match wdata.id {
+        Some(id) => id,
+        None => {
+            return respond_bad_request("Request needs the id of the webhook.".to_owned());
+        }
+    };
+
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .update_webhook(id, &wdata.url, &wdata.events, wdata.active)
+        .await;
+    match res {
+        Ok(true) => {}
+        Ok(false) => {
+            return ApiError::not_found(format!("There is no webhook #{}.", &id))
+                .into_response();
+        }
+        Err(e) => {
+            log::error!("Error updating webhook {:?}: {}", &wdata, &e);
+            return text_500(Some(format!("Unable to update webhook: {}", &e)));
+        }
+    }
+
+    populate(glob).await
+}
+
+/**
+Delete a webhook, along with the log of its deliveries.
+
+```text
+x-camp-action: delete-webhook
+```
+The body should be the webhook's `id`.
+*/
+pub async fn delete(body: Option<String>, glob: Arc<RwLock<Glob>>) -> Response {
+    let id: i64 = match body.as_deref().map(|s| s.trim().parse()) {
+        Some(Ok(id)) => id,
+        Some(Err(e)) => {
+            return respond_bad_request(format!("Unable to read webhook id: {}", &e));
+        }
+        None => {
+            return respond_bad_request("Request requires id of webhook in body.".to_owned());
+        }
+    };
+
+    match glob.read().await.data().read().await.delete_webhook(id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ApiError::not_found(format!("There is no webhook #{}.", &id))
+                .into_response();
+        }
+        Err(e) => {
+            log::error!("Error deleting webhook {}: {}", &id, &e);
+            return text_500(Some(format!("Unable to delete webhook: {}", &e)));
+        }
+    }
+
+    populate(glob).await
+}
+
+/**
+Respond with the most recent webhook deliveries, newest first.
+
+```text
+x-camp-action: webhook-deliveries
+```
+
+The body of the response is a JSON array of
+[`Delivery`](crate::store::Delivery)s.
+*/
+pub async fn deliveries(glob: Arc<RwLock<Glob>>) -> Response {
+    let res = glob
+        .read()
+        .await
+        .data()
+        .read()
+        .await
+        .get_webhook_deliveries(LOG_LENGTH)
+        .await;
+    let log = match res {
+        Ok(log) => log,
+        Err(e) => {
+            log::error!("Error retrieving webhook deliveries: {}", &e);
+            return text_500(Some(format!("Unable to retrieve deliveries: {}", &e)));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [(
+            HeaderName::from_static("x-camp-action"),
+            HeaderValue::from_static("webhook-deliveries"),
+        )],
+        Json(log),
+    )
+        .into_response()
+}
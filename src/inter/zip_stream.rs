@@ -0,0 +1,175 @@
+/*!
+Writing ZIP archives a file at a time, for streaming them to the client.
+
+The `zip` crate's writer needs to seek back and patch each file's header
+once it knows the file's size and checksum, so it has to hold the whole
+archive until it's done. Here, every file is added whole, so its header
+can be written correctly the first time, and the bytes of each file can be
+sent as soon as it's added. Only the central directory (a few dozen bytes
+per file) is kept until the end.
+
+Files are stored uncompressed (as the `zip` crate was used before; PDFs
+don't compress much anyway), and there's no Zip64 support, so an archive
+(or any file in it) can't be larger than 4 GiB.
+*/
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR_SIG: u32 = 0x0605_4b50;
+/// Version 2.0 of the format, which is all we need.
+const VERSION: u16 = 20;
+/// General purpose flag bit 11: file names are UTF-8.
+const UTF8_NAMES: u16 = 0x0800;
+/// Compression method 0: stored.
+const STORED: u16 = 0;
+/// 1980-01-01 00:00, the earliest MS-DOS date (what the `zip` crate uses
+/// by default).
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Encodes a ZIP archive one file at a time; see the module-level docs.
+#[derive(Debug, Default)]
+pub struct ZipEncoder {
+    /// Bytes of archive produced so far.
+    offset: u64,
+    central_dir: Vec<u8>,
+    n_files: usize,
+}
+
+fn put_u16(buff: &mut Vec<u8>, n: u16) {
+    buff.extend_from_slice(&n.to_le_bytes());
+}
+
+fn put_u32(buff: &mut Vec<u8>, n: u32) {
+    buff.extend_from_slice(&n.to_le_bytes());
+}
+
+impl ZipEncoder {
+    pub fn new() -> ZipEncoder {
+        ZipEncoder::default()
+    }
+
+    /// Number of files added so far.
+    pub fn len(&self) -> usize {
+        self.n_files
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n_files == 0
+    }
+
+    /**
+    Add the file `name` with contents `data` to the archive.
+
+    Returns the next chunk of the archive: the file's header followed by
+    its contents.
+    */
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+        let name_len = u16::try_from(name.len())
+            .map_err(|_| format!("File name {:?} is too long for a ZIP archive.", name))?;
+        let size = u32::try_from(data.len())
+            .map_err(|_| format!("{} is too large for a ZIP archive.", name))?;
+        let offset = u32::try_from(self.offset)
+            .map_err(|_| format!("Archive is too large to add {}.", name))?;
+        if self.n_files >= u16::MAX as usize {
+            return Err("Too many files for a ZIP archive.".to_owned());
+        }
+        let crc = crc32fast::hash(data);
+
+        let mut chunk: Vec<u8> = Vec::with_capacity(30 + name.len() + data.len());
+        put_u32(&mut chunk, LOCAL_HEADER_SIG);
+        put_u16(&mut chunk, VERSION);
+        put_u16(&mut chunk, UTF8_NAMES);
+        put_u16(&mut chunk, STORED);
+        put_u16(&mut chunk, DOS_TIME);
+        put_u16(&mut chunk, DOS_DATE);
+        put_u32(&mut chunk, crc);
+        put_u32(&mut chunk, size); // compressed size
+        put_u32(&mut chunk, size);
+        put_u16(&mut chunk, name_len);
+        put_u16(&mut chunk, 0); // extra field length
+        chunk.extend_from_slice(name.as_bytes());
+        chunk.extend_from_slice(data);
+
+        let cd = &mut self.central_dir;
+        put_u32(cd, CENTRAL_HEADER_SIG);
+        put_u16(cd, VERSION); // version made by
+        put_u16(cd, VERSION);
+        put_u16(cd, UTF8_NAMES);
+        put_u16(cd, STORED);
+        put_u16(cd, DOS_TIME);
+        put_u16(cd, DOS_DATE);
+        put_u32(cd, crc);
+        put_u32(cd, size);
+        put_u32(cd, size);
+        put_u16(cd, name_len);
+        put_u16(cd, 0); // extra field length
+        put_u16(cd, 0); // comment length
+        put_u16(cd, 0); // disk number
+        put_u16(cd, 0); // internal attributes
+        put_u32(cd, 0); // external attributes
+        put_u32(cd, offset);
+        cd.extend_from_slice(name.as_bytes());
+
+        self.offset += chunk.len() as u64;
+        self.n_files += 1;
+        Ok(chunk)
+    }
+
+    /// Return the last chunk of the archive: the central directory.
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        let cd_offset = u32::try_from(self.offset)
+            .map_err(|_| "Archive is too large to finish.".to_owned())?;
+        let cd_size = self.central_dir.len() as u32;
+        let n_files = self.n_files as u16;
+
+        let mut chunk = self.central_dir;
+        put_u32(&mut chunk, END_OF_CENTRAL_DIR_SIG);
+        put_u16(&mut chunk, 0); // this disk
+        put_u16(&mut chunk, 0); // disk with the central directory
+        put_u16(&mut chunk, n_files); // on this disk
+        put_u16(&mut chunk, n_files);
+        put_u32(&mut chunk, cd_size);
+        put_u32(&mut chunk, cd_offset);
+        put_u16(&mut chunk, 0); // comment length
+
+        Ok(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+
+    #[test]
+    fn readable_archive() {
+        let files: &[(&str, &[u8])] = &[
+            ("frog.pdf", b"%PDF-1.3 Fred's report"),
+            ("zack.pdf", b"%PDF-1.3 Zack's report, which is somewhat longer"),
+            ("émile.pdf", b""),
+        ];
+
+        let mut enc = ZipEncoder::new();
+        assert!(enc.is_empty());
+        let mut archive: Vec<u8> = Vec::new();
+        for (name, data) in files.iter() {
+            archive.extend(enc.add(name, data).unwrap());
+        }
+        assert_eq!(enc.len(), 3);
+        archive.extend(enc.finish().unwrap());
+
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+        assert_eq!(zip.len(), files.len());
+        for (name, data) in files.iter() {
+            let mut f = zip.by_name(name).unwrap();
+            let mut contents: Vec<u8> = Vec::new();
+            f.read_to_end(&mut contents).unwrap();
+            assert_eq!(&contents, data);
+        }
+
+        let empty = ZipEncoder::new().finish().unwrap();
+        assert_eq!(zip::ZipArchive::new(std::io::Cursor::new(empty)).unwrap().len(), 0);
+    }
+}
@@ -1,6 +1,8 @@
 use std::{
+    collections::BTreeMap,
     fmt::{Display, Write},
     ops::{Deref, DerefMut},
+    str::FromStr,
 };
 
 use once_cell::sync::Lazy;
@@ -8,15 +10,24 @@ use serde::Serialize;
 use smallstr::SmallString;
 use time::{format_description::FormatItem, macros::format_description, Date};
 
+pub mod anon;
 pub mod auth;
+pub mod auth_export;
 pub mod config;
 pub mod course;
+pub mod downloads;
+pub mod email;
 pub mod hist;
 pub mod inter;
 pub mod pace;
+pub mod pool;
+pub mod render;
 pub mod report;
+pub mod roster;
+pub mod startup;
 pub mod store;
 pub mod user;
+pub mod webhook;
 
 #[allow(clippy::upper_case_acronyms)]
 /// 16-byte backing store for `SmallString`s or `MiniString`s.
@@ -30,6 +41,18 @@ type MEDSTORE = [u8; 32];
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/**
+Environment tag each database gets stamped with on startup, unless one is
+specified in the configuration file. A running instance will refuse to
+start (or to destroy any data) if the tag it finds in either database
+doesn't match its own; this keeps a test build from wiping out production
+data just because it was pointed at the wrong connection string.
+*/
+#[cfg(not(test))]
+pub const DEFAULT_ENVIRONMENT: &str = "production";
+#[cfg(test)]
+pub const DEFAULT_ENVIRONMENT: &str = "test";
+
 /// Format for `time::Date`s used for server-client interchange and also as a
 /// display format in the Admin and Teacher views.
 pub const DATE_FMT: &[FormatItem] = format_description!("[year]-[month]-[day]");
@@ -86,6 +109,21 @@ impl Display for UnifiedError {
 
 impl std::error::Error for UnifiedError {}
 
+/**
+A database's metadata and the size of each of its tables, for sanity
+checks; see [`support`](crate::inter::support).
+
+Both [`auth::Db::get_sanity`] and
+[`store::Store::get_sanity`](crate::store::Store::get_sanity) return these.
+*/
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct DbSanity {
+    /// The contents of the `meta` table (environment, schema version).
+    pub meta: BTreeMap<String, String>,
+    /// The number of rows in each table.
+    pub row_counts: BTreeMap<String, i64>,
+}
+
 /**
 This function is used for reading data from CSV files (and sometimes SQL
 query results) where a blank value is better represented internally as
@@ -219,7 +257,7 @@ impl<A: smallvec::Array<Item = u8>> std::io::Write for MiniString<A> {
 
         match self.0.write_str(str_buff) {
             Ok(()) => Ok(buff.len()),
-            Err(_) => Err(Error::new(ErrorKind::Other, "formatting failed")),
+            Err(_) => Err(Error::other("formatting failed")),
         }
     }
 
@@ -241,19 +279,84 @@ impl<A: smallvec::Array<Item = u8>> From<&str> for MiniString<A> {
     }
 }
 
-pub fn format_date(format: &[FormatItem], date: &Date) -> Result<MiniString<SMALLSTORE>, String> {
+/**
+How dates are displayed to humans (in calendars, emails, and reports).
+
+This is set by the `date_format` configuration option, and doesn't affect
+[`DATE_FMT`], which is used for server-client interchange.
+
+| style   | also accepted | example              |
+|---------|---------------|----------------------|
+| `short` |               | `Sep 05`             |
+| `iso`   |               | `2023-09-05`         |
+| `us`    | `en-US`       | `09/05/2023`         |
+| `eu`    | `en-GB`       | `05/09/2023`         |
+| `long`  |               | `September 5, 2023`  |
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateStyle {
+    #[default]
+    Short,
+    Iso,
+    Us,
+    Eu,
+    Long,
+}
+
+impl DateStyle {
+    /// The [`time`] format description for this style.
+    pub fn items(&self) -> &'static [FormatItem<'static>] {
+        match self {
+            DateStyle::Short => format_description!("[month repr:short] [day]"),
+            DateStyle::Iso => DATE_FMT,
+            DateStyle::Us => format_description!("[month]/[day]/[year]"),
+            DateStyle::Eu => format_description!("[day]/[month]/[year]"),
+            DateStyle::Long => {
+                format_description!("[month repr:long] [day padding:none], [year]")
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DateStyle::Short => "short",
+            DateStyle::Iso => "iso",
+            DateStyle::Us => "us",
+            DateStyle::Eu => "eu",
+            DateStyle::Long => "long",
+        }
+    }
+}
+
+impl FromStr for DateStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "short" => Ok(DateStyle::Short),
+            "iso" => Ok(DateStyle::Iso),
+            "us" | "en-US" => Ok(DateStyle::Us),
+            "eu" | "en-GB" => Ok(DateStyle::Eu),
+            "long" => Ok(DateStyle::Long),
+            _ => Err(format!("Unrecognized date format: {:?}", s)),
+        }
+    }
+}
+
+pub fn format_date(style: DateStyle, date: &Date) -> Result<MiniString<SMALLSTORE>, String> {
     let mut s: MiniString<SMALLSTORE> = MiniString::new();
-    date.format_into(&mut s, format)
+    date.format_into(&mut s, style.items())
         .map_err(|e| format!("Failed to format date {:?}: {}", date, &e))?;
     Ok(s)
 }
 
 pub fn format_maybe_date(
-    format: &[FormatItem],
+    style: DateStyle,
     maybe_date: &Option<Date>,
 ) -> Result<MiniString<SMALLSTORE>, String> {
     match maybe_date {
-        Some(d) => format_date(format, d),
+        Some(d) => format_date(style, d),
         None => Ok(MiniString::new()),
     }
 }
@@ -296,4 +399,27 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn date_styles() {
+        let d = Date::from_calendar_date(2023, time::Month::September, 5).unwrap();
+        let cases = [
+            (DateStyle::Short, "Sep 05"),
+            (DateStyle::Iso, "2023-09-05"),
+            (DateStyle::Us, "09/05/2023"),
+            (DateStyle::Eu, "05/09/2023"),
+            (DateStyle::Long, "September 5, 2023"),
+        ];
+        for (style, expected) in cases.iter() {
+            assert_eq!(format_date(*style, &d).unwrap().as_str(), *expected);
+            assert_eq!(DateStyle::from_str(style.as_str()), Ok(*style));
+        }
+
+        assert_eq!(DateStyle::default(), DateStyle::Short);
+        assert_eq!(DateStyle::from_str("en-US"), Ok(DateStyle::Us));
+        assert_eq!(DateStyle::from_str("en-GB"), Ok(DateStyle::Eu));
+        assert!(DateStyle::from_str("klingon").is_err());
+
+        assert_eq!(format_maybe_date(DateStyle::Iso, &None).unwrap().as_str(), "");
+    }
 }
@@ -1,9 +1,10 @@
 /*!
 Here we go!
 */
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use axum::{
+    handler::Handler,
     http::StatusCode,
     middleware,
     response::{IntoResponse, Response},
@@ -18,7 +19,14 @@ use tower_http::{
     set_header::response::SetResponseHeaderLayer,
 };
 
-use camp::{config, config::Glob, inter, user::User};
+use camp::{
+    anon,
+    config,
+    config::{Cfg, Glob, RuntimeCfg},
+    inter,
+    startup::SharedStatus,
+    user::User,
+};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -40,21 +48,30 @@ async fn handle_login(
         match glob.users.get(&form.uname) {
             Some(u) => u.clone(),
             None => {
+                glob.auth()
+                    .read()
+                    .await
+                    .dummy_check(&form.uname, &form.password)
+                    .await;
                 return inter::respond_bad_password(&form.uname);
             }
         }
     };
 
-    match user {
-        User::Admin(a) => inter::admin::login(a, form, glob.clone()).await,
-        User::Boss(b) => inter::boss::login(b, form, glob.clone()).await,
-        User::Teacher(t) => inter::teacher::login(t, form, glob.clone()).await,
-        User::Student(s) => inter::student::login(s, form, glob.clone()).await,
-    }
+    // The page served on login is anonymized along with the session.
+    let anonymize = form.anonymize();
+    let login = async move {
+        match user {
+            User::Admin(a) => inter::admin::login(a, form, glob.clone()).await,
+            User::Boss(b) => inter::boss::login(b, form, glob.clone()).await,
+            User::Teacher(t) => inter::teacher::login(t, form, glob.clone()).await,
+            User::Student(s) => inter::student::login(s, form, glob.clone()).await,
+        }
+    };
+    anon::scope(anonymize, login).await
 }
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() {
+fn main() {
     let log_cfg = simplelog::ConfigBuilder::new()
         .add_filter_allow_str("camp")
         .build();
@@ -74,35 +91,111 @@ async fn main() {
         Some(path) => path.as_str(),
         None => "config.toml",
     };
-    let glob = config::load_configuration(config_path).await.unwrap();
+    let rt_cfg = RuntimeCfg::from_file(config_path).unwrap();
+    log::info!("Runtime configuration: {:?}", &rt_cfg);
+    rt_cfg.build().unwrap().block_on(serve(config_path));
+}
+
+async fn serve(config_path: &str) {
+    // Until the configuration is loaded (which may mean waiting for the
+    // databases), serve a maintenance page.
+    let addr = Cfg::from_file(config_path).unwrap().addr;
+    let status = SharedStatus::default();
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let maintenance = tokio::spawn(
+        axum::Server::bind(&addr)
+            .serve(inter::maintenance::router(status.clone()).into_make_service())
+            .with_graceful_shutdown(async {
+                stop_rx.await.ok();
+            }),
+    );
+
+    let glob = config::load_configuration_waiting(config_path, &status)
+        .await
+        .unwrap();
+
+    stop_tx.send(()).ok();
+    if let Err(e) = maintenance.await {
+        log::error!("Error shutting down maintenance server: {}", &e);
+    }
 
     let glob = Arc::new(RwLock::new(glob));
 
+    {
+        let glob = glob.read().await;
+        tokio::spawn(glob.deferred.clone().run(glob.data()));
+        if let Some(outbox) = glob.webhooks.as_ref() {
+            tokio::spawn(outbox.clone().run(glob.data()));
+        }
+    }
+    if let Some(outbox) = glob.read().await.outbox.clone() {
+        tokio::spawn(outbox.run(glob.clone()));
+    }
+
+    if let Some(hour) = glob.read().await.weekly_digest_hour {
+        tokio::spawn(inter::digest::run_weekly(glob.clone(), hour));
+    }
+    tokio::spawn(inter::lag_history::run_weekly(glob.clone()));
+    tokio::spawn(inter::retention::run_daily(glob.clone()));
+    if let Some(hour) = glob.read().await.roster_sync.as_ref().map(|r| r.hour) {
+        tokio::spawn(inter::roster::run_nightly(glob.clone(), hour));
+    }
+
     let serve_root =
         get_service(ServeFile::new("data/index.html")).handle_error(catchall_error_handler);
 
-    let serve_static = get_service(ServeDir::new("static")).handle_error(catchall_error_handler);
+    let static_dir = glob.read().await.static_dir.clone();
+    let serve_static = get_service(ServeDir::new(static_dir))
+        .handle_error(catchall_error_handler)
+        .layer(middleware::from_fn(inter::assets::cache_control));
 
-    let addr = glob.read().await.addr;
     let app = Router::new()
         .route("/boss", post(inter::boss::api))
         .route("/admin", post(inter::admin::api))
         .route("/teacher", post(inter::teacher::api))
+        .route("/student", post(inter::student::api))
+        .route("/logout", post(inter::logout))
         .layer(middleware::from_fn(inter::key_authenticate))
         .layer(middleware::from_fn(inter::request_identity))
-        .route("/pwd", get(inter::password_reset))
-        .route("/login", post(handle_login))
+        .layer(middleware::from_fn(inter::journal::capture_failures))
+        .route(
+            "/pwd",
+            get(inter::password_reset).layer(middleware::from_fn(inter::rate_limit::limit)),
+        )
+        .route(
+            "/unsubscribe",
+            get(inter::unsubscribe::confirm_page).post(inter::unsubscribe::unsubscribe),
+        )
+        .route("/kiosk/:token", get(inter::kiosk::dashboard))
+        .route("/report/:token", get(inter::reports::download))
+        .route("/archive/:token", get(inter::boss::serve_archive))
+        .route(
+            "/login",
+            post(handle_login).layer(middleware::from_fn(inter::rate_limit::limit)),
+        )
+        .route(
+            "/student/key",
+            post(inter::student::issue_key).layer(middleware::from_fn(inter::rate_limit::limit)),
+        )
+        .route(
+            "/student/basic",
+            get(inter::student::basic_login).post(
+                inter::student::basic.layer(middleware::from_fn(inter::rate_limit::limit)),
+            ),
+        )
+        .layer(middleware::from_fn(inter::error_page::request_id))
         .layer(Extension(glob.clone()))
         .nest("/static", serve_static)
         //.layer(middleware::from_fn(inter::log_request))
         .route("/", serve_root)
+        .fallback(inter::error_page::not_found.into_service())
         .layer(SetResponseHeaderLayer::appending(
             HeaderName::from_static("x-camp-version"),
             HeaderValue::from_static(VERSION)
         ));
 
     axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }
@@ -3,6 +3,7 @@ The `Goal` struct and `Pace` calendars, for internally represending pace
 calendar information.
 */
 use std::{
+    borrow::Cow,
     cmp::{Ord, Ordering, PartialOrd},
     collections::HashMap,
     io::{Read, Write},
@@ -14,10 +15,37 @@ use time::{Date, Month};
 
 use crate::{
     config::Glob,
+    course::Constraints,
     user::{Student, Teacher, User},
-    MiniString, MEDSTORE,
+    MiniString, DATE_FMT, MEDSTORE,
 };
 
+/// Comments at the top of a [`Pace::csv_template`], explaining the columns.
+const CSV_TEMPLATE_HEADER: &str = "\
+# Goals for upload. Lines starting with # are ignored.
+#
+# Columns:
+#   uname: the student's user name
+#   sym:   course symbol
+#   seq:   chapter number
+#   y,m,d: due date (year, month number, day)
+#   rev:   anything here marks the goal as a review goal
+#   inc:   anything here marks the goal as an incomplete from last year
+#   done:  (optional) date the goal was completed, like 2022-09-08
+#   tries: (optional) number of tries it took, if done
+#   score: (optional) score, like 85 or 17/20, if done
+#
+# uname, sym, y, m, and d carry forward from the line above when left blank,
+# so each goal after a student's first usually only needs seq and d. Rows
+# with only a uname are skipped, so leave them for students getting no goals.
+#
+# Example:
+#   jsmith, pha1,  3, 2022, 09, 10,  x,  , 2022-09-08, 1, 17/20
+#         ,     ,  9,     ,   , 28,   , x
+#
+#uname, sym, seq, y, m, d, rev, inc, done, tries, score
+";
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 pub enum Term {
     Fall,
@@ -93,9 +121,7 @@ pub fn parse_score_str(score_str: &str) -> Result<f32, String> {
     let chunks: SmallVec<[f32; 2]> = score_str
         .split('/')
         .take(2)
-        .map(|s| s.trim().parse::<f32>())
-        .filter(|res| res.is_ok())
-        .map(|ok| ok.unwrap())
+        .flat_map(|s| s.trim().parse::<f32>())
         .collect();
 
     match chunks[..] {
@@ -149,16 +175,102 @@ impl PartialEq for BookCh {
 }
 impl Eq for BookCh {}
 
+/// A chapter a particular student has been excused from (say, because they
+/// tested out of it). Waived chapters don't count toward the student's
+/// required weight, even if the student has a `Goal` for one.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Waiver {
+    /// Symbol of the [`Course`](crate::course::Course) the chapter is in.
+    pub sym: String,
+    /// The chapter's number in the course.
+    pub seq: i16,
+}
+
+/// A stretch of days a student's family has said they'll be away. Due dates
+/// can be kept out of absences when autopacing (see
+/// [`Pace::autopace_around`]) and when setting them by hand.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Absence {
+    pub id: i64,
+    pub uname: String,
+    /// First day of the absence.
+    pub start: Date,
+    /// Last day of the absence (which may be the same as `start`).
+    pub end: Date,
+    pub reason: String,
+}
+
+impl Absence {
+    /// Whether the student is away on `d`.
+    pub fn covers(&self, d: &Date) -> bool {
+        self.start <= *d && *d <= self.end
+    }
+}
+
+/**
+A stretch of days (like exam week) set aside by the Admin, during which
+nothing should be due. Exam windows are still instructional days, so they
+count toward how far behind a student is; they just aren't used for due
+dates (see [`AcademicCalendar::schedulable`]).
+*/
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ExamWindow {
+    /// Assigned by the database; ignored when adding one.
+    #[serde(default)]
+    pub id: i64,
+    /// Like "Fall Exams".
+    pub name: String,
+    /// First day of the window.
+    pub start: Date,
+    /// Last day of the window (which may be the same as `start`).
+    pub end: Date,
+}
+
+impl ExamWindow {
+    /// Whether `d` falls during this window.
+    pub fn covers(&self, d: &Date) -> bool {
+        self.start <= *d && *d <= self.end
+    }
+
+    /// Return an error explaining why this can't be saved, if it can't.
+    pub fn check(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("An exam window needs a name.".to_owned());
+        }
+        if self.end < self.start {
+            return Err(format!(
+                "Exam window {:?} ends ({}) before it starts ({}).",
+                name, &self.end, &self.start
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The `dates` that don't fall during any of the `absences`.
+pub fn outside_absences(dates: &[Date], absences: &[Absence]) -> Vec<Date> {
+    dates
+        .iter()
+        .filter(|d| !absences.iter().any(|a| a.covers(d)))
+        .copied()
+        .collect()
+}
+
 /// Represents material for a "custom" goal (not from an extant Course in
-/// the database.) This is currently not supported.
+/// the database.)
 ///
-/// `id` would be the value of the database's primary key from the table of
-/// custom goals (if it existed).
+/// The `i64` is the `id` of the [`Custom`](crate::course::Custom) chapter in
+/// the database's `custom_chapters` table.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct CustomCh(i64);
+pub struct CustomCh(pub i64);
+
+/// What's shown in place of a course title for custom goals in pace
+/// calendars and reports.
+pub const CUSTOM_COURSE: &str = "Custom";
 
-/// Currently, only `Source::Book` values are supported, and trying to do
-/// anything wtih a `Source::Custom` will yield you an error.
+/// The material a [`Goal`] covers: either a chapter of one of the Courses in
+/// the database, or one of a Teacher's custom chapters.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Source {
     Book(BookCh),
@@ -199,6 +311,201 @@ pub struct Goal {
     /// Score string of a completed Goal (see [`parse_score_str`]).
     /// As-of-yet unfinished `Goal`s will have scores of `None`.
     pub score: Option<String>,
+    /// The Teacher-defined [`Tag`]s attached to this `Goal`. These are
+    /// managed separately from the rest of the `Goal`'s data; they get
+    /// filled in when `Goal`s are read from the database, and are ignored
+    /// when `Goal`s are inserted or updated.
+    pub tags: Vec<Tag>,
+    /// Optional breakdown of the `score` into rubric components (see
+    /// [`check_rubric`]). Empty for most `Goal`s.
+    pub rubric: Vec<RubricItem>,
+    /// Which piece of its chapter this `Goal` covers, if the chapter has
+    /// been split into several `Goal`s. `None` for most `Goal`s.
+    pub part: Option<Part>,
+    /// How far along (in percent) the student is with this `Goal`, if it's
+    /// unfinished and the Teacher has said (see
+    /// [`Store::set_goal_progress`]). This is only informational; it never
+    /// counts toward the `Goal` being done, or toward any grade. Like
+    /// `part`, it's ignored when `Goal`s are inserted or updated.
+    ///
+    /// [`Store::set_goal_progress`]: crate::store::Store::set_goal_progress
+    pub progress: Option<i16>,
+    /// Whether the student has asked for this (unfinished) `Goal` to be
+    /// marked done (see [`Store::request_completion`]). Like `tags`, this
+    /// is filled in when `Goal`s are read from the database, and ignored
+    /// when they're inserted or updated.
+    ///
+    /// [`Store::request_completion`]: crate::store::Store::request_completion
+    pub requested: bool,
+    /// Where the Teacher's request for a printed test of this `Goal`'s
+    /// chapter stands, if there is one. Like `requested`, this is only
+    /// filled in when `Goal`s are read from the database.
+    pub test: Option<TestStatus>,
+}
+
+/**
+Which piece a [`Goal`] covers of a chapter that has been split into
+several checkpoints (see
+[`Store::split_goal`](crate::store::Store::split_goal)).
+
+Each piece gets `fraction` of the weight the whole chapter would have.
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Part {
+    /// Which piece this is, starting from 1.
+    pub n: i16,
+    /// How many pieces the chapter was split into.
+    pub of: i16,
+    pub fraction: f32,
+}
+
+/// The most pieces a single [`Goal`] can be split into.
+pub const MAX_SPLIT_PARTS: usize = 8;
+
+/// How far along a Teacher's request for a printed chapter test is (see
+/// [`Store::request_test`](crate::store::Store::request_test)).
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    /// The office hasn't gotten to it yet.
+    Requested,
+    Printed,
+    /// The Teacher has the test.
+    Delivered,
+}
+
+impl TestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TestStatus::Requested => "requested",
+            TestStatus::Printed => "printed",
+            TestStatus::Delivered => "delivered",
+        }
+    }
+}
+
+impl std::str::FromStr for TestStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "requested" => Ok(TestStatus::Requested),
+            "printed" => Ok(TestStatus::Printed),
+            "delivered" => Ok(TestStatus::Delivered),
+            _ => Err(format!("{:?} is not a valid test request status.", s)),
+        }
+    }
+}
+
+/// A label a Teacher can attach to any of their students' [`Goal`]s (like
+/// "needs parent signature" or "test retake").
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Tag {
+    /// Database table primary key.
+    pub id: i64,
+    pub name: String,
+}
+
+/// The most components a single [`Goal`]'s rubric may have.
+pub const MAX_RUBRIC_ITEMS: usize = 12;
+/// The longest (in characters) a rubric component's name may be.
+pub const MAX_RUBRIC_NAME: usize = 40;
+
+/// One component of the rubric breakdown of a [`Goal`]'s score (like
+/// "Setup: 3/4").
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RubricItem {
+    pub name: String,
+    pub points: f32,
+    pub max: f32,
+}
+
+/**
+Ensure a rubric breakdown is fit to store and show to parents.
+
+Each component needs a name (which, because it ends up in report tables,
+can't contain `|` or line breaks), and `0 <= points <= max`. There can be
+at most [`MAX_RUBRIC_ITEMS`] components, and their names must be distinct.
+*/
+pub fn check_rubric(items: &[RubricItem]) -> Result<(), String> {
+    if items.len() > MAX_RUBRIC_ITEMS {
+        return Err(format!(
+            "A rubric can have at most {} components.",
+            &MAX_RUBRIC_ITEMS
+        ));
+    }
+
+    for (n, item) in items.iter().enumerate() {
+        let name = item.name.trim();
+        if name.is_empty() {
+            return Err(format!("Rubric component {} has no name.", n + 1));
+        }
+        if name.chars().count() > MAX_RUBRIC_NAME {
+            return Err(format!(
+                "Rubric component name {:?} is longer than {} characters.",
+                name, &MAX_RUBRIC_NAME
+            ));
+        }
+        if name.contains(['|', '\n', '\r']) {
+            return Err(format!(
+                "Rubric component name {:?} can't contain '|' or line breaks.",
+                name
+            ));
+        }
+        if !(item.max.is_finite() && item.max > 0.0) {
+            return Err(format!(
+                "Rubric component {:?} must be out of a positive number of points.",
+                name
+            ));
+        }
+        if !(item.points.is_finite() && item.points >= 0.0 && item.points <= item.max) {
+            return Err(format!(
+                "Rubric component {:?} must have between 0 and {} points.",
+                name, &item.max
+            ));
+        }
+        if items[..n].iter().any(|i| i.name.trim() == name) {
+            return Err(format!("Rubric component {:?} appears twice.", name));
+        }
+    }
+
+    Ok(())
+}
+
+/**
+Work out the fractions of a chapter's weight each of `n` pieces of it
+should get.
+
+If `fractions` is empty, the weight is split evenly. Otherwise there must be
+one (positive) fraction for each piece; they're scaled to sum to 1, but
+shouldn't be far off to start with.
+*/
+pub fn split_fractions(n: usize, fractions: &[f32]) -> Result<Vec<f32>, String> {
+    if !(2..=MAX_SPLIT_PARTS).contains(&n) {
+        return Err(format!(
+            "A goal can be split into between 2 and {} parts.",
+            &MAX_SPLIT_PARTS
+        ));
+    }
+    if fractions.is_empty() {
+        return Ok(vec![1.0 / n as f32; n]);
+    }
+    if fractions.len() != n {
+        return Err(format!(
+            "{} fractions were given for {} parts.",
+            &fractions.len(),
+            &n
+        ));
+    }
+    if fractions.iter().any(|f| !(f.is_finite() && *f > 0.0)) {
+        return Err("Each part's fraction must be positive.".to_owned());
+    }
+    let total: f32 = fractions.iter().sum();
+    if (total - 1.0).abs() > 0.01 {
+        return Err(format!("Fractions must add up to 1 (not {}).", &total));
+    }
+
+    Ok(fractions.iter().map(|f| f / total).collect())
 }
 
 impl PartialEq for Goal {
@@ -212,6 +519,8 @@ impl PartialEq for Goal {
             && self.done == other.done
             && self.tries == other.tries
             && self.score == other.score
+            && self.rubric == other.rubric
+            && self.part == other.part
     }
 }
 
@@ -244,6 +553,12 @@ impl Goal {
 
     Columns `rev` and `inc` are considered `true` if they have any text
     whatsoever.
+
+    The last three columns, `done` (`YYYY-MM-DD`), `tries`, and `score`, are
+    optional, and don't carry forward; they're for goals already completed
+    before the school started using camp. A `tries` or `score` needs a
+    `done` date, and a `score` has to be something
+    [`parse_score_str`] understands.
      */
     pub fn from_csv_line(row: &csv::StringRecord, prev: Option<&Goal>) -> Result<Goal, String> {
         log::trace!("Goal::from_csv_line( {:?} ) called.", row);
@@ -368,6 +683,35 @@ impl Goal {
         let review = blank_means_none(row.get(6)).is_some();
         let incomplete = blank_means_none(row.get(7)).is_some();
 
+        let done = match blank_means_none(row.get(8)) {
+            Some(s) => match Date::parse(s, DATE_FMT) {
+                Ok(d) => Some(d),
+                Err(_) => {
+                    return Err(format!("Unable to parse {:?} as done date (YYYY-MM-DD).", s));
+                }
+            },
+            None => None,
+        };
+        let tries: Option<i16> = match blank_means_none(row.get(9)) {
+            Some(s) => match s.parse() {
+                Ok(n) if n > 0 => Some(n),
+                _ => {
+                    return Err(format!("Unable to parse {:?} as number of tries.", s));
+                }
+            },
+            None => None,
+        };
+        let score = match blank_means_none(row.get(10)) {
+            Some(s) => {
+                parse_score_str(s)?;
+                Some(s.to_owned())
+            }
+            None => None,
+        };
+        if done.is_none() && (tries.is_some() || score.is_some()) {
+            return Err("A goal with tries or a score needs a done date.".into());
+        }
+
         let g = Goal {
             // This doesn't matter; it will be set upon database insertion.
             id: 0,
@@ -376,14 +720,17 @@ impl Goal {
             review,
             incomplete,
             due: Some(due),
-            // No goals read from .csv files can possibly be done.
-            done: None,
-            // Will get set once it's done.
-            tries: None,
+            done,
+            tries,
             // Will get set in the `Pace` calendar constructror.
             weight: 0.0,
-            // Goals read from .csv files should have no score yet.
-            score: None,
+            score,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
         };
 
         Ok(g)
@@ -442,12 +789,21 @@ impl Ord for Goal {
                     } else if slev > olev {
                         Greater
                     } else {
-                        n.cmp(m)
+                        let part_n = |g: &Goal| g.part.map(|p| p.n).unwrap_or(0);
+                        n.cmp(m).then_with(|| part_n(self).cmp(&part_n(other)))
                     }
                 }
-                _ => Equal,
+                Source::Custom(_) => Less,
+            },
+            // Undated custom goals go after the book goals, in the order
+            // their custom chapters were created.
+            Source::Custom(CustomCh(a)) => match &other.source {
+                Source::Book(_) => Greater,
+                Source::Custom(CustomCh(b)) => {
+                    let part_n = |g: &Goal| g.part.map(|p| p.n).unwrap_or(0);
+                    a.cmp(b).then_with(|| part_n(self).cmp(&part_n(other)))
+                }
             },
-            _ => Equal,
         }
     }
 }
@@ -458,6 +814,39 @@ impl PartialOrd for Goal {
     }
 }
 
+/**
+Count how many of a student's `goals` would end up in a different place in
+their pace if course levels went from `old` to `new` (both maps of course
+symbol to level).
+
+Goals are ordered by due date first, so re-leveling courses only moves
+goals that share a due date (or don't have one).
+*/
+pub fn count_reordered(
+    goals: &[Goal],
+    old: &HashMap<String, f32>,
+    new: &HashMap<String, f32>,
+) -> usize {
+    let order = |levels: &HashMap<String, f32>| -> Vec<i64> {
+        let mut goals = goals.to_vec();
+        for g in goals.iter_mut() {
+            if let Source::Book(ref mut b) = g.source {
+                if let Some(level) = levels.get(&b.sym) {
+                    b.level = *level;
+                }
+            }
+        }
+        goals.sort();
+        goals.iter().map(|g| g.id).collect()
+    };
+
+    order(old)
+        .iter()
+        .zip(order(new).iter())
+        .filter(|(a, b)| a != b)
+        .count()
+}
+
 /// Represents a student's entire assigned pace for one year.
 #[derive(Debug)]
 pub struct Pace {
@@ -475,6 +864,23 @@ pub struct Pace {
     /// Some of the weights of the so-far-completed `Goal`s (that is, those
     /// with `Some` done dates.)
     pub done_weight: f32,
+    /// Chapters the student has been excused from. `Goal`s for these
+    /// chapters have zero weight.
+    pub waived: Vec<Waiver>,
+}
+
+/// A change in a `Goal`'s weight, as reported by
+/// [`Glob::recompute_weights`](crate::config::Glob::recompute_weights).
+#[derive(Debug, Serialize)]
+pub struct WeightDelta {
+    pub id: i64,
+    pub uname: String,
+    pub sym: String,
+    pub seq: i16,
+    /// `None` if the student's [`Pace`] couldn't be built from the old
+    /// course data at all.
+    pub old: Option<f32>,
+    pub new: f32,
 }
 
 fn affirm_goal(mut g: Goal, glob: &Glob) -> Result<Goal, String> {
@@ -514,22 +920,35 @@ fn affirm_goal(mut g: Goal, glob: &Glob) -> Result<Goal, String> {
             };
             g.weight = chp.weight / crs_wgt;
         }
-        Source::Custom(_) => {
-            return Err("Custom Goals not yet supported.".to_owned());
-        }
+        Source::Custom(CustomCh(id)) => match glob.customs.get(&id) {
+            Some(cus) => {
+                g.weight = cus.weight;
+            }
+            None => {
+                return Err(format!("There is no custom chapter with id {}.", &id));
+            }
+        },
     }
 
     Ok(g)
 }
 
 impl Pace {
-    /// Instantiate a new `Pace` calendar.
-    pub fn new(s: Student, t: Teacher, mut goals: Vec<Goal>, glob: &Glob) -> Result<Pace, String> {
+    /// Instantiate a new `Pace` calendar. `Goal`s for any of the `waived`
+    /// chapters are given zero weight.
+    pub fn new(
+        s: Student,
+        t: Teacher,
+        mut goals: Vec<Goal>,
+        waived: Vec<Waiver>,
+        glob: &Glob,
+    ) -> Result<Pace, String> {
         log::trace!(
-            "Pace::new( [ Student {:?} ], [ Teacher {:?} ], [ {} Goals ] ) called.",
+            "Pace::new( [ Student {:?} ], [ Teacher {:?} ], [ {} Goals ], {:?} ) called.",
             &s.base.uname,
             &t.base.uname,
-            &goals.len()
+            &goals.len(),
+            &waived
         );
 
         goals.sort();
@@ -539,39 +958,57 @@ impl Pace {
         let mut due_weight: f32 = 0.0;
         let mut done_weight: f32 = 0.0;
         for g in goals.iter_mut() {
-            let source = match &mut g.source {
-                Source::Book(bch) => bch,
-                _ => {
-                    return Err("Custom chapters not supported.".into());
-                }
-            };
-            let crs = match glob.course_by_sym(&source.sym) {
-                Some(crs) => crs,
-                None => {
-                    return Err(format!("Unknown course symbol {:?}", &source.sym));
-                }
-            };
-            let chp = match crs.chapter(source.seq) {
-                Some(chp) => chp,
-                None => {
-                    return Err(format!(
-                        "Course {:?} ({}) doesn't have a chapter {}.",
-                        &source.sym, &crs.title, &source.seq
-                    ));
+            let weight = match &mut g.source {
+                Source::Book(source) => {
+                    let crs = match glob.course_by_sym(&source.sym) {
+                        Some(crs) => crs,
+                        None => {
+                            return Err(format!("Unknown course symbol {:?}", &source.sym));
+                        }
+                    };
+                    let chp = match crs.chapter(source.seq) {
+                        Some(chp) => chp,
+                        None => {
+                            return Err(format!(
+                                "Course {:?} ({}) doesn't have a chapter {}.",
+                                &source.sym, &crs.title, &source.seq
+                            ));
+                        }
+                    };
+                    source.level = crs.level;
+
+                    let weight = match crs.weight {
+                        Some(w) => chp.weight / w,
+                        None => {
+                            return Err(format!(
+                                "Course {:?} ({}) has not had its weights set.",
+                                &source.sym, &crs.title
+                            ));
+                        }
+                    };
+                    if waived
+                        .iter()
+                        .any(|w| w.sym == source.sym && w.seq == source.seq)
+                    {
+                        0.0
+                    } else {
+                        weight
+                    }
                 }
+                // A custom chapter's weight is already a fraction of a
+                // (hypothetical) course, and custom chapters can't be waived.
+                Source::Custom(CustomCh(id)) => match glob.customs.get(id) {
+                    Some(cus) => cus.weight,
+                    None => {
+                        return Err(format!("Unknown custom chapter id {}", id));
+                    }
+                },
             };
-
-            let weight = match crs.weight {
-                Some(w) => chp.weight / w,
-                None => {
-                    return Err(format!(
-                        "Course {:?} ({}) has not had its weights set.",
-                        &source.sym, &crs.title
-                    ));
-                }
+            let weight = match &g.part {
+                Some(part) => weight * part.fraction,
+                None => weight,
             };
 
-            source.level = crs.level;
             g.weight = weight;
             if let Some(due_date) = &g.due {
                 total_weight += weight;
@@ -591,6 +1028,7 @@ impl Pace {
             total_weight,
             due_weight,
             done_weight,
+            waived,
         };
 
         log::debug!("{:#?}", &p);
@@ -598,6 +1036,81 @@ impl Pace {
         Ok(p)
     }
 
+    /**
+    Generate a goals .csv file, ready to be filled in and uploaded (see
+    [`Pace::from_csv`]), with a row for each of the given `students` (in
+    order by name). Each row has just the student's `uname`, under a
+    comment with the student's name; the header comments describe the
+    columns.
+    */
+    pub fn csv_template(students: &[&Student]) -> String {
+        let mut students = students.to_vec();
+        students.sort_by(|a, b| {
+            (&a.last, &a.rest, &a.base.uname).cmp(&(&b.last, &b.rest, &b.base.uname))
+        });
+
+        let mut csv = String::from(CSV_TEMPLATE_HEADER);
+        for s in students.iter() {
+            csv.push_str("\n# ");
+            csv.push_str(&s.last);
+            csv.push_str(", ");
+            csv.push_str(&s.rest);
+            csv.push('\n');
+            csv.push_str(&s.base.uname);
+            csv.push_str(",,,,,,,\n");
+        }
+
+        csv
+    }
+
+    /**
+    Write this `Pace`'s goals as a goals .csv file in the format read by
+    [`Pace::from_csv`], so they can be edited and uploaded again.
+
+    Every column of every row is filled in. Only goals from book chapters
+    with due dates can be written this way, and completion dates and scores
+    aren't written; how many goals were left out is noted in a comment.
+    */
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(CSV_TEMPLATE_HEADER);
+        csv.push_str("\n# ");
+        csv.push_str(&self.student.last);
+        csv.push_str(", ");
+        csv.push_str(&self.student.rest);
+        csv.push('\n');
+
+        let mut n_left_out: usize = 0;
+        for g in self.goals.iter() {
+            let (bch, due) = match (&g.source, g.due) {
+                (Source::Book(bch), Some(due)) => (bch, due),
+                _ => {
+                    n_left_out += 1;
+                    continue;
+                }
+            };
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                &g.uname,
+                &bch.sym,
+                &bch.seq,
+                due.year(),
+                due.month() as u8,
+                due.day(),
+                if g.review { "x" } else { "" },
+                if g.incomplete { "x" } else { "" },
+            ));
+        }
+
+        if n_left_out > 0 {
+            csv.push_str(&format!(
+                "# {} goal(s) from custom chapters or without due dates left out.\n",
+                &n_left_out
+            ));
+        }
+
+        csv
+    }
+
     /**
     Read a series of goals from data in CSV format and return them as a `Vec`
     of `Pace`s.
@@ -615,7 +1128,13 @@ impl Pace {
     the same as the previous line.
 
     Columns `rev` and `inc` are considered `true` if they have any text
-    whatsoever.
+    whatsoever. Goals that were completed before the school started using
+    camp can have optional `done`, `tries`, and `score` columns after those
+    (see [`Goal::from_csv_line`]).
+
+    Rows with nothing but a `uname` are skipped, so the rows of a
+    [`Pace::csv_template`] can be left alone for students who aren't getting
+    any goals.
      */
     pub fn from_csv<R: Read>(r: R, glob: &Glob) -> Result<Vec<Pace>, String> {
         log::trace!("Pace::from_csv(...) called.");
@@ -644,6 +1163,10 @@ impl Pace {
                     if is_blank {
                         continue;
                     }
+                    // So is a template row that hasn't been filled in.
+                    if record.iter().skip(1).all(|r| r.is_empty()) {
+                        continue;
+                    }
 
                     let res = Goal::from_csv_line(&record, prev_goal.as_ref());
                     match res {
@@ -712,6 +1235,7 @@ impl Pace {
                 total_weight,
                 due_weight: 0.0,
                 done_weight: 0.0,
+                waived: Vec::new(),
             };
 
             cals.push(p);
@@ -720,10 +1244,26 @@ impl Pace {
         Ok(cals)
     }
 
-    /// Given an academic calendar represented by a (sorted, duh) slice of
-    /// [`Date`]s, distribute this `Pace`'s due dates throughout the year,
+    /// Distribute this `Pace`'s due dates throughout the instructional days
+    /// of the academic year (except those during exam windows),
     /// proportionally according to the weights of the `Goal`s.
-    pub fn autopace(&mut self, dates: &[Date]) -> Result<(), String> {
+    pub fn autopace(&mut self, cal: &AcademicCalendar) -> Result<(), String> {
+        self.autopace_on(&cal.schedulable(cal.days()))
+    }
+
+    /// Like [`Pace::autopace`], but without putting any due dates on days
+    /// during the student's `absences`, either.
+    pub fn autopace_around(
+        &mut self,
+        cal: &AcademicCalendar,
+        absences: &[Absence],
+    ) -> Result<(), String> {
+        self.autopace_on(&outside_absences(&cal.schedulable(cal.days()), absences))
+    }
+
+    /// Distribute this `Pace`'s due dates throughout the given (sorted)
+    /// `dates`.
+    fn autopace_on(&mut self, dates: &[Date]) -> Result<(), String> {
         log::trace!(
             "Pace[ {:?} ]::autopace( [ {} dates ] ) called.",
             &self.student.base.uname,
@@ -759,87 +1299,629 @@ impl Pace {
 
         Ok(())
     }
-}
 
-/**
-Represents the state of the `Goal` on the current day:
-  * `Done`: completed before the due date
-  * `Late`: completed after the due date
-  * `Overdue`: The due date has passed, but the goal is still uncompleted.
-  * `Yet`: The goal is uncompleted, but the due date is also still in the future.
-*/
-#[derive(Debug)]
-pub enum GoalStatus {
-    Done,
-    Late,
-    Overdue,
-    Yet,
-}
+    /// The weight done minus the weight due, as a fraction of the total
+    /// weight (or 0 if there's no weight at all).
+    pub fn lag(&self) -> f32 {
+        if self.total_weight.abs() < 0.001 {
+            0.0
+        } else {
+            (self.done_weight - self.due_weight) / self.total_weight
+        }
+    }
 
-/**
-All the calculated data necessary to display a [`Goal`] and its current
-status.
+    /// Sum of the weights of the unfinished, scheduled `Goal`s, each scaled
+    /// by how far along the student is with it (see [`Goal::progress`]).
+    /// This is kept apart from `done_weight` (and so from lag and grades).
+    pub fn progress_weight(&self) -> f32 {
+        self.goals
+            .iter()
+            .filter(|g| g.done.is_none() && g.due.is_some())
+            .filter_map(|g| g.progress.map(|pct| g.weight * pct as f32 / 100.0))
+            .sum()
+    }
 
-This is an abstraction meant to be used in several different contexts which
-each display the `Goal`'s data slightly differently.
-*/
-#[derive(Debug)]
-pub struct GoalDisplay<'a> {
-    /// The ID of the goal.
-    pub id: i64,
-    /// Title of the [`Course`](crate::course::Course) to which this `Goal` belongs.
-    pub course: &'a str,
-    /// Title of the textbook (or other source) form which this material
-    /// is drawn.
-    pub book: &'a str,
-    /// Title of the Chapter or section containing the material covered
-    /// by this `Goal` (probably "Chapter N").
-    pub title: &'a str,
-    /// Material covered (if this information is available).
-    pub subject: Option<&'a str>,
-    /// Whether the `Goal` in question is a review of previously-covered material.
-    pub rev: bool,
-    /// Whether this `Goal` represents material incomplete from a prior academic year.
-    pub inc: bool,
-    /// When the `Goal` is due (if it's due).
-    pub due: Option<Date>,
-    /// When the `Goal` was completed (if it's complete).
-    pub done: Option<Date>,
-    /// How many attempts it took to display mastery (if it's complete).
-    pub tries: Option<i16>,
-    /// The string of characters the teacher has used to represent the score
-    /// achieved on this `Goal` (if it's complete).
-    pub mark: MiniString<MEDSTORE>,
-    /// The value of that string of characters (if it's complete).
-    pub score: Option<f32>,
-    /// The status of this `Goal` on the current date.
-    pub status: GoalStatus,
-}
+    /// Whether `g` is for a chapter this `Pace`'s student has been excused
+    /// from.
+    pub fn is_waived(&self, g: &Goal) -> bool {
+        match &g.source {
+            Source::Book(bch) => self
+                .waived
+                .iter()
+                .any(|w| w.sym == bch.sym && w.seq == bch.seq),
+            Source::Custom(_) => false,
+        }
+    }
 
-impl<'a> GoalDisplay<'a> {
-    /// Generate all the information necessary to display the given [`Goal`].
-    fn from_goal(g: &'a Goal, glob: &'a Glob, today: &Date) -> Result<GoalDisplay<'a>, String> {
-        let bch = match &g.source {
-            Source::Book(bch) => bch,
-            _ => {
-                return Err(format!("Goal {}: custom sources unsupported.", &g.id));
-            }
-        };
+    /// Whether every scheduled (that is, due) `Goal` in this `Pace` is done.
+    /// A `Pace` with nothing scheduled isn't complete. Waived `Goal`s don't
+    /// count.
+    pub fn is_complete(&self) -> bool {
+        let mut scheduled = self
+            .goals
+            .iter()
+            .filter(|g| g.due.is_some() && !self.is_waived(g))
+            .peekable();
+        scheduled.peek().is_some() && scheduled.all(|g| g.done.is_some())
+    }
 
-        let crs = glob
-            .course_by_sym(&bch.sym)
-            .ok_or_else(|| format!("Goal {}: no course with symbol {:?}.", &g.id, &bch.sym))?;
-        let chp = crs.chapter(bch.seq).ok_or_else(|| {
-            format!(
-                "Goal {}: Course {:?} has no Chapter {}",
-                &g.id, &bch.sym, &bch.seq
-            )
-        })?;
+    /// How many instructional days before `today` this `Pace`'s oldest
+    /// overdue (and still incomplete) `Goal` was due, or 0 if nothing is
+    /// overdue.
+    pub fn days_behind(&self, cal: &AcademicCalendar, today: &Date) -> i64 {
+        self.goals
+            .iter()
+            .filter(|g| g.done.is_none())
+            .filter_map(|g| g.due)
+            .filter(|d| d < today)
+            .min()
+            .map(|d| cal.lateness(&d, today))
+            .unwrap_or(0)
+    }
 
-        let mut mark: MiniString<MEDSTORE> = MiniString::new();
-        if let Some(s) = g.score.as_deref() {
-            write!(&mut mark, "{}", s)
-                .map_err(|e| format!("Error writing goal mark {:?}: {}", s, &e))?;
+    /// Cumulative due and done weights of this `Pace` on each instructional
+    /// day of `cal`; see [`Burndown`].
+    pub fn burndown(&self, cal: &AcademicCalendar) -> Burndown {
+        let mut due: Vec<(Date, f32)> = Vec::with_capacity(self.goals.len());
+        let mut done: Vec<(Date, f32)> = Vec::with_capacity(self.goals.len());
+        for g in self.goals.iter() {
+            if let Some(d) = g.due {
+                due.push((d, g.weight));
+            }
+            if let Some(d) = g.done {
+                done.push((d, g.weight));
+            }
+        }
+
+        Burndown {
+            due: cumulative_weights(due, cal),
+            done: cumulative_weights(done, cal),
+        }
+    }
+
+    /**
+    How many instructional days each of this `Pace`'s completed, scheduled,
+    unwaived `Goal`s took, compared with how many its weight allots it; see
+    [`GoalDuration`].
+
+    A `Goal` is taken to have been started when the `Goal` done before it
+    was finished (or when the calendar starts, for the first one). `Goal`s
+    done on the same day share the days since the last completion evenly.
+    Unscheduled `Goal`s count as the previous completion for the ones after
+    them, but aren't included themselves.
+    */
+    pub fn durations(&self, cal: &AcademicCalendar) -> Vec<GoalDuration<'_>> {
+        if cal.is_empty() || self.total_weight < 0.001 {
+            return Vec::new();
+        }
+
+        let mut done: Vec<(&Date, &Goal)> = self
+            .goals
+            .iter()
+            .filter(|g| !self.is_waived(g))
+            .filter_map(|g| g.done.as_ref().map(|d| (d, g)))
+            .collect();
+        done.sort_by_key(|(d, _)| *d);
+
+        let days_per_weight = cal.len() as f32 / self.total_weight;
+        let mut durations: Vec<GoalDuration> = Vec::new();
+        let mut prev_rank: usize = 0;
+        for same_day in done.chunk_by(|a, b| a.0 == b.0) {
+            let rank = cal.rank(same_day[0].0);
+            let actual = rank.saturating_sub(prev_rank) as f32 / same_day.len() as f32;
+            prev_rank = rank;
+
+            for (_, g) in same_day.iter() {
+                if g.due.is_some() && g.weight > 0.0 {
+                    durations.push(GoalDuration {
+                        goal: g,
+                        actual,
+                        expected: days_per_weight * g.weight,
+                    });
+                }
+            }
+        }
+
+        durations
+    }
+
+    /**
+    Check the due dates of this `Pace`'s unfinished, unwaived textbook
+    `Goal`s against their courses' [`Constraints`].
+
+    Finished `Goal`s are left out, because there's nothing to be done about
+    when they were due.
+    */
+    pub fn constraint_violations(&self, glob: &Glob) -> Vec<ConstraintViolation> {
+        let mut by_course: HashMap<&str, Vec<(i16, Date)>> = HashMap::new();
+        for g in self.goals.iter() {
+            if g.done.is_some() || self.is_waived(g) {
+                continue;
+            }
+            if let (Source::Book(bch), Some(due)) = (&g.source, g.due) {
+                by_course
+                    .entry(bch.sym.as_str())
+                    .or_default()
+                    .push((bch.seq, due));
+            }
+        }
+
+        let mut violations: Vec<ConstraintViolation> = Vec::new();
+        for (sym, mut due) in by_course.into_iter() {
+            if let Some(crs) = glob.course_by_sym(sym) {
+                if !crs.constraints.is_empty() {
+                    due.sort_by_key(|(seq, d)| (*d, *seq));
+                    violations.extend(check_constraints(
+                        sym,
+                        &crs.constraints,
+                        &due,
+                        &glob.calendar,
+                    ));
+                }
+            }
+        }
+        violations.sort_by(|a, b| a.sym.cmp(&b.sym).then(a.seqs.cmp(&b.seqs)));
+        violations
+    }
+}
+
+/**
+Series of cumulative weights for drawing burn-down charts.
+
+Element `n` of each series corresponds to day `n` of the calendar the
+`Burndown` was generated from: `due[n]` is the total weight of the
+`Goal`s due on or before that day, and `done[n]` the total weight of the
+`Goal`s completed on or before it.
+*/
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Burndown {
+    pub due: Vec<f32>,
+    pub done: Vec<f32>,
+}
+
+impl Burndown {
+    /// Add `other`'s series to these, element by element (for aggregating
+    /// several students' `Burndown`s).
+    pub fn accumulate(&mut self, other: &Burndown) {
+        if self.due.len() < other.due.len() {
+            self.due.resize(other.due.len(), 0.0);
+        }
+        if self.done.len() < other.done.len() {
+            self.done.resize(other.done.len(), 0.0);
+        }
+        for (x, y) in self.due.iter_mut().zip(other.due.iter()) {
+            *x += y;
+        }
+        for (x, y) in self.done.iter_mut().zip(other.done.iter()) {
+            *x += y;
+        }
+    }
+}
+
+/// How long a completed [`Goal`] took, in instructional days; see
+/// [`Pace::durations`].
+#[derive(Clone, Debug)]
+pub struct GoalDuration<'a> {
+    pub goal: &'a Goal,
+    /// Days from the previous completion to this `Goal`'s.
+    pub actual: f32,
+    /// The share of the calendar this `Goal`'s weight allots it (which is
+    /// how [`Pace::autopace`] spaces due dates).
+    pub expected: f32,
+}
+
+/// Running total of the weights of `events` that fall on or before each
+/// instructional day of `cal`.
+fn cumulative_weights(events: Vec<(Date, f32)>, cal: &AcademicCalendar) -> Vec<f32> {
+    let mut totals: Vec<f32> = vec![0.0; cal.len()];
+    for (d, w) in events.iter() {
+        if let Some(x) = totals.get_mut(cal.first_on_or_after(d)) {
+            *x += w;
+        }
+    }
+
+    let mut running: f32 = 0.0;
+    for x in totals.iter_mut() {
+        running += *x;
+        *x = running;
+    }
+
+    totals
+}
+
+/**
+The academic year's instructional days, indexed for fast date arithmetic.
+
+Day `n` of the calendar is the `n`th instructional day (counting from 0).
+Arithmetic here counts only instructional days, so the Friday before a
+three-day weekend and the Tuesday after it are one day apart.
+
+Dates that aren't instructional days (weekends, holidays, days outside
+the academic year) are fine to pass to any method; they count as falling
+just after the last instructional day before them.
+
+Days during [`ExamWindow`]s are instructional days like any other as far
+as the arithmetic goes; they're only left out of the days due dates are
+put on (see [`AcademicCalendar::schedulable`]).
+*/
+#[derive(Clone, Debug, Default)]
+pub struct AcademicCalendar {
+    days: Vec<Date>,
+    index: HashMap<Date, usize>,
+    exam_windows: Vec<ExamWindow>,
+}
+
+impl AcademicCalendar {
+    /// Build a calendar from the given instructional `days` (in any order;
+    /// duplicates are ignored).
+    pub fn new(mut days: Vec<Date>) -> AcademicCalendar {
+        days.sort_unstable();
+        days.dedup();
+        let index = days.iter().enumerate().map(|(n, d)| (*d, n)).collect();
+        AcademicCalendar {
+            days,
+            index,
+            exam_windows: Vec::new(),
+        }
+    }
+
+    /// Set aside the given `windows`, during which nothing should be due.
+    pub fn with_exam_windows(mut self, mut windows: Vec<ExamWindow>) -> AcademicCalendar {
+        windows.sort_by_key(|w| (w.start, w.end));
+        self.exam_windows = windows;
+        self
+    }
+
+    /// All the instructional days, in order.
+    pub fn days(&self) -> &[Date] {
+        &self.days
+    }
+
+    /// The exam windows, in order.
+    pub fn exam_windows(&self) -> &[ExamWindow] {
+        &self.exam_windows
+    }
+
+    /// The exam window `d` falls in, if any.
+    pub fn exam_window_on(&self, d: &Date) -> Option<&ExamWindow> {
+        self.exam_windows.iter().find(|w| w.covers(d))
+    }
+
+    /// The `dates` that don't fall during an exam window, and so may have
+    /// things due on them.
+    pub fn schedulable(&self, dates: &[Date]) -> Vec<Date> {
+        dates
+            .iter()
+            .filter(|d| self.exam_window_on(d).is_none())
+            .copied()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.days.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.days.is_empty()
+    }
+
+    pub fn first(&self) -> Option<&Date> {
+        self.days.first()
+    }
+
+    /// The index of `d`, if it's an instructional day.
+    pub fn index_of(&self, d: &Date) -> Option<usize> {
+        self.index.get(d).copied()
+    }
+
+    /// The index of the first instructional day on or after `d` (which is
+    /// the length of the calendar if there isn't one).
+    fn first_on_or_after(&self, d: &Date) -> usize {
+        match self.index.get(d) {
+            Some(n) => *n,
+            None => self.days.partition_point(|x| x < d),
+        }
+    }
+
+    /// The number of instructional days on or before `d`.
+    fn rank(&self, d: &Date) -> usize {
+        match self.index.get(d) {
+            Some(n) => n + 1,
+            None => self.days.partition_point(|x| x < d),
+        }
+    }
+
+    /**
+    The number of instructional days after `from`, up to and including `to`.
+
+    This is negative if `to` is before `from`, and is additive:
+    `days_between(a, b) + days_between(b, c) == days_between(a, c)`.
+    */
+    pub fn days_between(&self, from: &Date, to: &Date) -> i64 {
+        self.rank(to) as i64 - self.rank(from) as i64
+    }
+
+    /**
+    How many instructional days late (or, if negative, early) something
+    due on `due` and done on `done` was.
+
+    Something done after its due date is always at least one day late, even
+    if it was done over the following weekend (and likewise for early).
+    */
+    pub fn lateness(&self, due: &Date, done: &Date) -> i64 {
+        match self.days_between(due, done) {
+            0 => (*done - *due).whole_days().signum(),
+            n => n,
+        }
+    }
+
+    /**
+    The instructional day `n` instructional days after `d` (or before, if
+    `n` is negative), if the calendar extends that far.
+
+    Adding 0 days to a date that isn't an instructional day gives the last
+    instructional day before it.
+    */
+    pub fn add_days(&self, d: &Date, n: i64) -> Option<Date> {
+        let idx = self.rank(d) as i64 + n - 1;
+        if idx < 0 {
+            return None;
+        }
+        self.days.get(idx as usize).copied()
+    }
+
+    /// The instructional days between `start` and `end` (inclusive); either
+    /// end may be left open.
+    pub fn window(&self, start: Option<Date>, end: Option<Date>) -> &[Date] {
+        let lo = match start {
+            Some(d) => self.first_on_or_after(&d),
+            None => 0,
+        };
+        let hi = match end {
+            Some(d) => self.rank(&d),
+            None => self.days.len(),
+        };
+        if lo >= hi {
+            return &[];
+        }
+        &self.days[lo..hi]
+    }
+}
+
+/**
+Distribute due dates for the given `Goal`s (in the order given) throughout
+the given (sorted) slice of [`Date`]s, proportionally according to their
+weights.
+
+This is like [`Pace::autopace`], but for an arbitrary run of `Goal`s
+(say, a single course being assigned all at once) rather than an entire
+`Pace`. Every `Goal` passed will end up with a due date.
+*/
+pub fn distribute_due_dates(goals: &mut [Goal], dates: &[Date]) -> Result<(), String> {
+    log::trace!(
+        "distribute_due_dates( [ {} Goals ], [ {} dates ] ) called.",
+        &goals.len(),
+        &dates.len()
+    );
+
+    if dates.is_empty() {
+        return Err("There are no instructional days in which to schedule these goals.".into());
+    }
+    let total_weight: f32 = goals.iter().map(|g| g.weight).sum();
+    // This is really to prevent division by zero.
+    if total_weight < 0.001 {
+        return Err("These goals don't have enough weight to schedule.".into());
+    }
+
+    let mut running_weight: f32 = 0.0;
+    let n_dates: f32 = dates.len() as f32;
+    for g in goals.iter_mut() {
+        running_weight += g.weight;
+        let frac = running_weight / total_weight;
+        // Clamping guards against floating-point error pushing `idx` out of
+        // range at either end.
+        let idx = ((n_dates * frac).ceil() as usize).clamp(1, dates.len());
+        g.due = Some(dates[idx - 1]);
+    }
+
+    Ok(())
+}
+
+/// A way in which some of a student's due dates break their course's
+/// [`Constraints`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstraintViolation {
+    /// The course's symbol.
+    pub sym: String,
+    /// The chapters whose due dates are in violation.
+    pub seqs: Vec<i16>,
+    /// An explanation fit for showing to a Teacher.
+    pub message: String,
+}
+
+impl std::fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", &self.message)
+    }
+}
+
+impl ConstraintViolation {
+    /// Whether the violation involves chapter `seq` of course `sym`.
+    pub fn involves(&self, sym: &str, seq: i16) -> bool {
+        self.sym == sym && self.seqs.contains(&seq)
+    }
+}
+
+/**
+Check the `(chapter, due date)` pairs in `due` (sorted by date) for course
+`sym` against its `constraints`.
+
+Parts of the same chapter (see [`Goal::part`]) don't count as separate
+chapters: they can be due as close together as you like, and only count
+once per week.
+*/
+fn check_constraints(
+    sym: &str,
+    constraints: &Constraints,
+    due: &[(i16, Date)],
+    cal: &AcademicCalendar,
+) -> Vec<ConstraintViolation> {
+    let mut violations: Vec<ConstraintViolation> = Vec::new();
+
+    if let Some(min_gap) = constraints.min_gap {
+        for pair in due.windows(2) {
+            let ((a_seq, a_due), (b_seq, b_due)) = (pair[0], pair[1]);
+            if a_seq == b_seq {
+                continue;
+            }
+            let gap = cal.days_between(&a_due, &b_due);
+            if gap < min_gap as i64 {
+                violations.push(ConstraintViolation {
+                    sym: sym.to_owned(),
+                    seqs: vec![a_seq, b_seq],
+                    message: format!(
+                        "{} chapters {} and {} are due {} instructional day(s) apart, but need at least {}.",
+                        sym, a_seq, b_seq, gap, min_gap
+                    ),
+                });
+            }
+        }
+    }
+
+    if let Some(max) = constraints.max_per_week {
+        let mut weeks: Vec<(Date, Vec<i16>)> = Vec::new();
+        for (seq, d) in due.iter() {
+            let monday = d.saturating_sub(time::Duration::days(
+                d.weekday().number_days_from_monday() as i64,
+            ));
+            match weeks.last_mut() {
+                Some((m, seqs)) if *m == monday => {
+                    if !seqs.contains(seq) {
+                        seqs.push(*seq);
+                    }
+                }
+                _ => weeks.push((monday, vec![*seq])),
+            }
+        }
+        for (monday, seqs) in weeks.into_iter() {
+            if seqs.len() > max as usize {
+                violations.push(ConstraintViolation {
+                    sym: sym.to_owned(),
+                    message: format!(
+                        "{} chapters {} are all due the week of {}, but at most {} can be.",
+                        sym,
+                        seqs.iter()
+                            .map(|n| n.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        &monday,
+                        max
+                    ),
+                    seqs,
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+/**
+Represents the state of the `Goal` on the current day:
+  * `Done`: completed before the due date
+  * `Late`: completed after the due date
+  * `Overdue`: The due date has passed, but the goal is still uncompleted.
+  * `Yet`: The goal is uncompleted, but the due date is also still in the future.
+*/
+#[derive(Debug)]
+pub enum GoalStatus {
+    Done,
+    Late,
+    Overdue,
+    Yet,
+}
+
+/**
+All the calculated data necessary to display a [`Goal`] and its current
+status.
+
+This is an abstraction meant to be used in several different contexts which
+each display the `Goal`'s data slightly differently.
+*/
+#[derive(Debug)]
+pub struct GoalDisplay<'a> {
+    /// The ID of the goal.
+    pub id: i64,
+    /// Title of the [`Course`](crate::course::Course) to which this `Goal` belongs.
+    pub course: &'a str,
+    /// Title of the textbook (or other source) form which this material
+    /// is drawn.
+    pub book: &'a str,
+    /// Title of the Chapter or section containing the material covered
+    /// by this `Goal` (probably "Chapter N"), noting which part of it this
+    /// is if it's been split.
+    pub title: Cow<'a, str>,
+    /// Material covered (if this information is available).
+    pub subject: Option<&'a str>,
+    /// Whether the `Goal` in question is a review of previously-covered material.
+    pub rev: bool,
+    /// Whether this `Goal` represents material incomplete from a prior academic year.
+    pub inc: bool,
+    /// When the `Goal` is due (if it's due).
+    pub due: Option<Date>,
+    /// When the `Goal` was completed (if it's complete).
+    pub done: Option<Date>,
+    /// How many attempts it took to display mastery (if it's complete).
+    pub tries: Option<i16>,
+    /// The string of characters the teacher has used to represent the score
+    /// achieved on this `Goal` (if it's complete).
+    pub mark: MiniString<MEDSTORE>,
+    /// The value of that string of characters (if it's complete).
+    pub score: Option<f32>,
+    /// The status of this `Goal` on the current date.
+    pub status: GoalStatus,
+    /// The Teacher's [`Tag`]s attached to this `Goal`.
+    pub tags: &'a [Tag],
+    /// Rubric breakdown of the score (if the Teacher entered one).
+    pub rubric: &'a [RubricItem],
+    /// How far along (in percent) the student is, if it isn't complete.
+    pub progress: Option<i16>,
+}
+
+impl<'a> GoalDisplay<'a> {
+    /// Generate all the information necessary to display the given [`Goal`].
+    fn from_goal(g: &'a Goal, glob: &'a Glob, today: &Date) -> Result<GoalDisplay<'a>, String> {
+        let (course, book, title, subject) = match &g.source {
+            Source::Book(bch) => {
+                let crs = glob.course_by_sym(&bch.sym).ok_or_else(|| {
+                    format!("Goal {}: no course with symbol {:?}.", &g.id, &bch.sym)
+                })?;
+                let chp = crs.chapter(bch.seq).ok_or_else(|| {
+                    format!(
+                        "Goal {}: Course {:?} has no Chapter {}",
+                        &g.id, &bch.sym, &bch.seq
+                    )
+                })?;
+                (
+                    crs.title.as_str(),
+                    crs.book.as_str(),
+                    chp.title.as_str(),
+                    chp.subject.as_deref(),
+                )
+            }
+            Source::Custom(CustomCh(id)) => {
+                let cus = glob
+                    .customs
+                    .get(id)
+                    .ok_or_else(|| format!("Goal {}: no custom chapter with id {}.", &g.id, id))?;
+                (CUSTOM_COURSE, "", cus.title.as_str(), None)
+            }
+        };
+
+        let mut mark: MiniString<MEDSTORE> = MiniString::new();
+        if let Some(s) = g.score.as_deref() {
+            write!(&mut mark, "{}", s)
+                .map_err(|e| format!("Error writing goal mark {:?}: {}", s, &e))?;
         }
 
         let score = maybe_parse_score_str(g.score.as_deref())?;
@@ -864,10 +1946,13 @@ impl<'a> GoalDisplay<'a> {
 
         let gd = GoalDisplay {
             id: g.id,
-            course: crs.title.as_str(),
-            book: crs.book.as_str(),
-            title: chp.title.as_str(),
-            subject: chp.subject.as_deref(),
+            course,
+            book,
+            title: match &g.part {
+                Some(p) => Cow::Owned(format!("{} (part {} of {})", title, p.n, p.of)),
+                None => Cow::Borrowed(title),
+            },
+            subject,
             rev: g.review,
             inc: g.incomplete,
             due: g.due,
@@ -876,27 +1961,102 @@ impl<'a> GoalDisplay<'a> {
             mark,
             score,
             status,
+            tags: &g.tags,
+            rubric: &g.rubric,
+            progress: g.progress.filter(|_| g.done.is_none()),
         };
 
         Ok(gd)
     }
 }
 
+/**
+The labels used for grading periods and their summary lines in pace
+calendars and reports.
+
+Schools don't all call these things the same thing, so these can be set in
+the `summary_labels` table of the configuration file; any that aren't
+specified there keep their default values (shown in parentheses).
+*/
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct SummaryLabels {
+    /// Name of the Fall grading period ("Fall Semester").
+    pub fall: String,
+    /// Name of the Spring grading period ("Spring Semester").
+    pub spring: String,
+    /// Name of the Summer grading period ("Summer").
+    pub summer: String,
+    /// Fall test average line in pace calendars ("Fall Test Average").
+    pub fall_tests: String,
+    /// Spring test average line in pace calendars ("Spring Test Average").
+    pub spring_tests: String,
+    /// Exam score line in pace calendars ("Exam Score").
+    pub exam: String,
+    /// Homework notices line in pace calendars ("Notices").
+    pub notices: String,
+    /// Fall grade line in pace calendars ("Fall Semester Grade").
+    pub fall_grade: String,
+    /// Spring grade line in pace calendars ("Spring Semester Grade").
+    pub spring_grade: String,
+    /// Test average row of report summary tables ("Test Average").
+    pub tests_row: String,
+    /// Exam row of report summary tables ("Final Exam").
+    pub exam_row: String,
+    /// Grade row of report summary tables ("Semester Grade").
+    pub grade_row: String,
+}
+
+impl Default for SummaryLabels {
+    fn default() -> Self {
+        Self {
+            fall: "Fall Semester".to_owned(),
+            spring: "Spring Semester".to_owned(),
+            summer: "Summer".to_owned(),
+            fall_tests: "Fall Test Average".to_owned(),
+            spring_tests: "Spring Test Average".to_owned(),
+            exam: "Exam Score".to_owned(),
+            notices: "Notices".to_owned(),
+            fall_grade: "Fall Semester Grade".to_owned(),
+            spring_grade: "Spring Semester Grade".to_owned(),
+            tests_row: "Test Average".to_owned(),
+            exam_row: "Final Exam".to_owned(),
+            grade_row: "Semester Grade".to_owned(),
+        }
+    }
+}
+
 /// A single line (of possibly several) in a semester summary of a student's
 /// progress.
 #[derive(Debug, Serialize)]
-pub struct SummaryDisplay {
-    pub label: &'static str,
+pub struct SummaryDisplay<'a> {
+    pub label: &'a str,
     pub value: MiniString<MEDSTORE>,
 }
 
+/// A chapter the student has been excused from (see [`Waiver`]), as shown
+/// in a `Pace` calendar display.
+#[derive(Debug, Serialize)]
+pub struct WaivedDisplay<'a> {
+    /// Title of the [`Course`](crate::course::Course) the chapter is in.
+    pub course: &'a str,
+    /// Title of the textbook.
+    pub book: &'a str,
+    /// Title of the chapter (probably "Chapter N").
+    pub title: &'a str,
+    /// Material covered (if this information is available).
+    pub subject: Option<&'a str>,
+}
+
 /// Represents a single row of data to display in a `Pace` calendar display.
 ///
-/// This could either be `Goal` information, or a line of semester summary info.
+/// This could either be `Goal` information, a waived chapter, or a line of
+/// semester summary info.
 #[derive(Debug)]
 pub enum RowDisplay<'a> {
     Goal(GoalDisplay<'a>),
-    Summary(SummaryDisplay),
+    Waived(WaivedDisplay<'a>),
+    Summary(SummaryDisplay<'a>),
 }
 
 /**
@@ -946,6 +2106,64 @@ pub struct PaceDisplay<'a> {
     pub rows: Vec<RowDisplay<'a>>,
 }
 
+/**
+Calculate a semester grade (as a fraction, like scores) from a student's
+test average, exam score, and number of notices; each notice costs one
+percentage point.
+
+`exam_frac` is the fraction of the grade that the exam is worth.
+*/
+pub fn semester_grade(tests: f32, exam_frac: f32, exam: f32, n_notices: i16) -> f32 {
+    (exam_frac * exam) + ((1.0 - exam_frac) * tests) - (0.01 * n_notices as f32)
+}
+
+/**
+The inverse of [`semester_grade`]: the exam score a student with the given
+test average and number of notices needs to earn a semester grade of
+`target` (all as fractions).
+
+The result may be above 1.0 (if `target` is out of reach without extra
+credit) or below 0.0 (if it's assured no matter what). Returns `None` if
+the exam isn't worth anything, in which case it can't make a difference.
+*/
+pub fn required_exam_score(tests: f32, exam_frac: f32, n_notices: i16, target: f32) -> Option<f32> {
+    if exam_frac <= 0.0 {
+        return None;
+    }
+    let without_exam = semester_grade(tests, exam_frac, 0.0, n_notices);
+    Some((target - without_exam) / exam_frac)
+}
+
+/**
+Project a student's semester grade from their test average so far, the
+weight of the semester's goals they've done and that remain, and their exam
+score (if they've taken it). All grades and scores are fractions.
+
+Returns `(projected, best)`: `projected` assumes the rest of the semester
+(including the exam, if it hasn't been taken) goes the way it has so far;
+`best` assumes perfect scores on everything that's left.
+*/
+pub fn project_semester_grade(
+    tests: f32,
+    done_weight: f32,
+    remaining_weight: f32,
+    exam_frac: f32,
+    exam: Option<f32>,
+    n_notices: i16,
+) -> (f32, f32) {
+    let projected = semester_grade(tests, exam_frac, exam.unwrap_or(tests), n_notices);
+
+    let total_weight = done_weight + remaining_weight;
+    let best_tests = if total_weight > 0.0 {
+        ((tests * done_weight) + remaining_weight) / total_weight
+    } else {
+        tests
+    };
+    let best = semester_grade(best_tests, exam_frac, exam.unwrap_or(1.0), n_notices);
+
+    (projected, best)
+}
+
 /// Generate semester summary lines (if necessary).
 ///
 /// Produces 0-4 lines, depending on what the student has done (or at
@@ -959,7 +2177,8 @@ fn generate_summary(
     exam_frac: f32,
     exam_score: Option<f32>,
     sem_inc: bool,
-) -> Result<SmallVec<[SummaryDisplay; 4]>, String> {
+    labels: &SummaryLabels,
+) -> Result<SmallVec<[SummaryDisplay<'_>; 4]>, String> {
     log::trace!(
         "generate_summary( {:?}, {}, {}, {}, {:?}) called.",
         &term,
@@ -969,12 +2188,12 @@ fn generate_summary(
         &exam_score
     );
 
-    let mut lines: SmallVec<[SummaryDisplay; 4]> = SmallVec::new();
+    let mut lines: SmallVec<[SummaryDisplay<'_>; 4]> = SmallVec::new();
 
     let int_score = (sem_frac * 100.0).round() as i32;
     let label = match term {
-        Term::Fall => "Fall Test Average",
-        Term::Spring => "Spring Test Average",
+        Term::Fall => labels.fall_tests.as_str(),
+        Term::Spring => labels.spring_tests.as_str(),
         // This shouldn't be called for the Summer term, so just return an
         // empty Vec of rows.
         Term::Summer => {
@@ -989,31 +2208,28 @@ fn generate_summary(
 
     if let Some(f) = exam_score {
         let int_score = (100.0 * f).round() as i32;
-        let label = "Exam Score";
+        let label = labels.exam.as_str();
         let mut value: MiniString<MEDSTORE> = MiniString::new();
         write!(&mut value, "{}", &int_score)
             .map_err(|e| format!("Error writing exam score {:?}: {}", &int_score, &e))?;
         let line = SummaryDisplay { label, value };
         lines.push(line);
 
-        let sem_final = (exam_frac * f) + ((1.0 - exam_frac) * sem_frac);
-        let mut sem_pct = 100.0 * sem_final;
+        let sem_pct = 100.0 * semester_grade(sem_frac, exam_frac, f, n_notices);
 
         if n_notices > 0 {
-            let label = "Notices";
+            let label = labels.notices.as_str();
             let mut value: MiniString<MEDSTORE> = MiniString::new();
             write!(&mut value, "-{}", &n_notices)
                 .map_err(|e| format!("Error writing # notices {:?}: {}", &n_notices, &e))?;
             let line = SummaryDisplay { label, value };
             lines.push(line);
-
-            sem_pct -= n_notices as f32;
         }
 
         let int_pct = sem_pct.round() as i32;
         let label = match term {
-            Term::Fall => "Fall Semester Grade",
-            Term::Spring => "Spring Semester Grade",
+            Term::Fall => labels.fall_grade.as_str(),
+            Term::Spring => labels.spring_grade.as_str(),
             _ => unreachable!(),
         };
         let mut value: MiniString<MEDSTORE> = MiniString::new();
@@ -1077,7 +2293,7 @@ impl<'a> PaceDisplay<'a> {
         let mut sems_last_id: Option<i64> = None;
         let mut last_completed_goal: Option<usize> = None;
 
-        for g in p.goals.iter() {
+        for g in p.goals.iter().filter(|g| !p.is_waived(g)) {
             if let Some(d) = &g.due {
                 if d < &today {
                     n_due += 1;
@@ -1165,25 +2381,23 @@ impl<'a> PaceDisplay<'a> {
             )
         })?;
 
-        let fall_total: Option<f32> = match fall_exam {
-            Some(f) => {
-                let exam = f * p.student.fall_exam_fraction;
-                let tests = fall_tests * (1.0 - p.student.fall_exam_fraction);
-                let notices = (p.student.fall_notices as f32) * 0.01;
-                Some(exam + tests - notices)
-            }
-            None => None,
-        };
-
-        let spring_total: Option<f32> = match spring_exam {
-            Some(f) => {
-                let exam = f * p.student.spring_exam_fraction;
-                let tests = spring_tests * (1.0 - p.student.spring_exam_fraction);
-                let notices = (p.student.spring_notices as f32) * 0.01;
-                Some(exam + tests - notices)
-            }
-            None => None,
-        };
+        let fall_total: Option<f32> = fall_exam.map(|f| {
+            semester_grade(
+                fall_tests,
+                p.student.fall_exam_fraction,
+                f,
+                p.student.fall_notices,
+            )
+        });
+
+        let spring_total: Option<f32> = spring_exam.map(|f| {
+            semester_grade(
+                spring_tests,
+                p.student.spring_exam_fraction,
+                f,
+                p.student.spring_notices,
+            )
+        });
 
         let mut fall_summary: SmallVec<[SummaryDisplay; 4]> = if semf_last_id.is_some() {
             if semf_done > 0 {
@@ -1194,6 +2408,7 @@ impl<'a> PaceDisplay<'a> {
                     p.student.fall_exam_fraction,
                     fall_exam,
                     semf_inc,
+                    &glob.summary_labels,
                 )?
             } else {
                 SmallVec::new()
@@ -1211,6 +2426,7 @@ impl<'a> PaceDisplay<'a> {
                     p.student.spring_exam_fraction,
                     spring_exam,
                     sems_inc,
+                    &glob.summary_labels,
                 )?
             } else {
                 SmallVec::new()
@@ -1220,9 +2436,35 @@ impl<'a> PaceDisplay<'a> {
         };
 
         let n_sum_rows = fall_summary.len() + spring_summary.len();
-        let mut rows: Vec<RowDisplay> = Vec::with_capacity(p.goals.len() + n_sum_rows);
+        let mut rows: Vec<RowDisplay> =
+            Vec::with_capacity(p.waived.len() + p.goals.len() + n_sum_rows);
+
+        // Waived chapters are listed first, in place of any `Goal`s for them.
+        for w in p.waived.iter() {
+            let (crs, chp) = match glob
+                .course_by_sym(&w.sym)
+                .and_then(|crs| crs.chapter(w.seq).map(|chp| (crs, chp)))
+            {
+                Some(x) => x,
+                None => {
+                    log::warn!(
+                        "{:?} has a waiver for nonexistent chapter {:?} {}.",
+                        &p.student.base.uname,
+                        &w.sym,
+                        &w.seq
+                    );
+                    continue;
+                }
+            };
+            rows.push(RowDisplay::Waived(WaivedDisplay {
+                course: crs.title.as_str(),
+                book: crs.book.as_str(),
+                title: chp.title.as_str(),
+                subject: chp.subject.as_deref(),
+            }));
+        }
 
-        for g in p.goals.iter() {
+        for g in p.goals.iter().filter(|g| !p.is_waived(g)) {
             let gd = GoalDisplay::from_goal(g, glob, &today).map_err(|e| {
                 format!(
                     "Unable to generate display info from Goal {}: {}",
@@ -1282,6 +2524,41 @@ impl<'a> PaceDisplay<'a> {
 
         Ok(pd)
     }
+
+    /**
+    Blank out everything that reveals a score: each goal's score, mark, and
+    rubric, the exam and semester grades, and the summary rows (test
+    averages, notices, and grades) entirely.
+
+    This is for deployments that only show students their completion status
+    (see the `student_scores` configuration option); it's applied to the
+    student's own views, never to the Teacher's or Boss's.
+    */
+    pub fn hide_scores(&mut self) {
+        let last_completed = self.last_completed_goal.take();
+        for (n, mut row) in std::mem::take(&mut self.rows).into_iter().enumerate() {
+            match &mut row {
+                RowDisplay::Summary(_) => {
+                    continue;
+                }
+                RowDisplay::Goal(g) => {
+                    g.score = None;
+                    g.mark = MiniString::new();
+                    g.rubric = &[];
+                }
+                RowDisplay::Waived(_) => {}
+            }
+            // Removing the summary rows shifts the goal rows' indices.
+            if last_completed == Some(n) {
+                self.last_completed_goal = Some(self.rows.len());
+            }
+            self.rows.push(row);
+        }
+        self.fall_exam = None;
+        self.spring_exam = None;
+        self.fall_total = None;
+        self.spring_total = None;
+    }
 }
 
 #[cfg(test)]
@@ -1297,11 +2574,6 @@ mod tests {
 
     use serial_test::serial;
 
-    static AUTH_CONN: &str =
-        "host=localhost user=camp_test password='camp_test' dbname=camp_auth_test";
-    static DATA_CONN: &str =
-        "host=localhost user=camp_test password='camp_test' dbname=camp_store_test";
-
     static COURSE_FILES: &[&str] = &[
         "test/env/course_0.mix",
         "test/env/course_1.mix",
@@ -1360,9 +2632,9 @@ mod tests {
             data.read().await.insert_courses(&courses).await?;
         }
 
-        g.insert_user(&boss).await.unwrap();
+        g.insert_user("root", &boss).await.unwrap();
         for u in teachers.iter() {
-            g.insert_user(u).await.unwrap();
+            g.insert_user("root", u).await.unwrap();
         }
         g.refresh_users().await.unwrap();
         g.upload_students(&student_csv).await.unwrap();
@@ -1464,4 +2736,687 @@ mod tests {
 
         teardown_env(g).await.unwrap();
     }
+    #[test]
+    fn distribute_dates() {
+        ensure_logging();
+
+        let dates: Vec<Date> = (1..=10)
+            .map(|d| Date::from_calendar_date(2022, Month::September, d).unwrap())
+            .collect();
+        let mut goals: Vec<Goal> = [1.0_f32, 1.0, 2.0, 1.0]
+            .iter()
+            .enumerate()
+            .map(|(n, w)| Goal {
+                id: n as i64,
+                uname: "jsmith".to_owned(),
+                source: Source::Book(BookCh {
+                    sym: "pc".to_owned(),
+                    seq: n as i16,
+                    level: 0.0,
+                }),
+                review: false,
+                incomplete: false,
+                due: None,
+                done: None,
+                tries: None,
+                weight: *w,
+                score: None,
+                tags: Vec::new(),
+                rubric: Vec::new(),
+                part: None,
+                progress: None,
+                requested: false,
+                test: None,
+            })
+            .collect();
+
+        distribute_due_dates(&mut goals, &dates).unwrap();
+        let days: Vec<u8> = goals.iter().map(|g| g.due.unwrap().day()).collect();
+        assert_eq!(days, vec![2, 4, 8, 10]);
+
+        distribute_due_dates(&mut goals[..1], &dates[..3]).unwrap();
+        assert_eq!(goals[0].due.unwrap().day(), 3);
+
+        assert!(distribute_due_dates(&mut goals, &[]).is_err());
+    }
+
+    #[test]
+    fn reordered_goals() {
+        let day = |d: u8| Date::from_calendar_date(2022, Month::September, d).unwrap();
+        let goal = |id: i64, sym: &str, seq: i16, due: Option<u8>| Goal {
+            id,
+            uname: "jsmith".to_owned(),
+            source: Source::Book(BookCh {
+                sym: sym.to_owned(),
+                seq,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due: due.map(day),
+            done: None,
+            tries: None,
+            weight: 1.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        };
+        let levels = |pairs: &[(&str, f32)]| -> HashMap<String, f32> {
+            pairs.iter().map(|(s, x)| (s.to_string(), *x)).collect()
+        };
+        let old = levels(&[("alg", 9.0), ("geo", 10.0)]);
+
+        // Dated goals stay put.
+        let dated = vec![goal(1, "geo", 1, Some(5)), goal(2, "alg", 1, Some(9))];
+        let swapped = levels(&[("alg", 11.0), ("geo", 10.0)]);
+        assert_eq!(count_reordered(&dated, &old, &swapped), 0);
+
+        let undated = vec![
+            goal(1, "alg", 1, None),
+            goal(2, "alg", 2, None),
+            goal(3, "geo", 1, None),
+            goal(4, "geo", 2, Some(3)),
+        ];
+        assert_eq!(count_reordered(&undated, &old, &old), 0);
+        assert_eq!(count_reordered(&undated, &old, &levels(&[("alg", 9.5), ("geo", 10.0)])), 0);
+        // geo 1 goes first, alg 1 and 2 each move down one.
+        assert_eq!(count_reordered(&undated, &old, &swapped), 3);
+    }
+
+    #[test]
+    fn absent_dates() {
+        let day = |d: u8| Date::from_calendar_date(2022, Month::September, d).unwrap();
+        let absence = |start: u8, end: u8| Absence {
+            id: 0,
+            uname: "jsmith".to_owned(),
+            start: day(start),
+            end: day(end),
+            reason: String::new(),
+        };
+        let dates: Vec<Date> = (1..=10).map(day).collect();
+
+        assert!(absence(3, 3).covers(&day(3)));
+        assert!(!absence(3, 5).covers(&day(6)));
+        assert_eq!(outside_absences(&dates, &[]), dates);
+
+        let left = outside_absences(&dates, &[absence(2, 4), absence(9, 9), absence(20, 25)]);
+        let days: Vec<u8> = left.iter().map(|d| d.day()).collect();
+        assert_eq!(days, vec![1, 5, 6, 7, 8, 10]);
+
+        assert!(outside_absences(&dates, &[absence(1, 30)]).is_empty());
+    }
+
+    #[test]
+    fn calendar_arithmetic() {
+        let day = |d: u8| Date::from_calendar_date(2022, Month::September, d).unwrap();
+        // A four-day week after Labor Day, then a full week.
+        let cal = AcademicCalendar::new([13, 6, 7, 8, 9, 12, 9].into_iter().map(day).collect());
+
+        assert_eq!(cal.len(), 6);
+        assert_eq!(cal.index_of(&day(9)), Some(3));
+        assert_eq!(cal.index_of(&day(10)), None);
+        assert_eq!(cal.days_between(&day(9), &day(12)), 1);
+        assert_eq!(cal.days_between(&day(10), &day(12)), 1);
+        assert_eq!(cal.days_between(&day(13), &day(6)), -5);
+        assert_eq!(cal.days_between(&day(1), &day(30)), 6);
+        assert_eq!(cal.lateness(&day(9), &day(10)), 1);
+        assert_eq!(cal.lateness(&day(12), &day(8)), -2);
+        assert_eq!(cal.add_days(&day(9), 1), Some(day(12)));
+        assert_eq!(cal.add_days(&day(10), 0), Some(day(9)));
+        assert_eq!(cal.add_days(&day(6), -1), None);
+        assert_eq!(cal.add_days(&day(6), 6), None);
+        assert_eq!(cal.window(Some(day(8)), Some(day(11))), &[day(8), day(9)]);
+        assert_eq!(cal.window(None, Some(day(7))), &[day(6), day(7)]);
+        assert!(cal.window(Some(day(10)), Some(day(11))).is_empty());
+    }
+
+    #[test]
+    fn exam_windows() {
+        let day = |d: u8| Date::from_calendar_date(2022, Month::September, d).unwrap();
+        let window = |name: &str, start: u8, end: u8| ExamWindow {
+            id: 0,
+            name: name.to_owned(),
+            start: day(start),
+            end: day(end),
+        };
+        let cal = AcademicCalendar::new([6, 7, 8, 9, 12, 13].into_iter().map(day).collect())
+            .with_exam_windows(vec![window("Quizzes", 12, 12), window("Midterms", 7, 9)]);
+
+        assert_eq!(cal.exam_windows()[0].name, "Midterms");
+        assert_eq!(cal.exam_window_on(&day(8)).unwrap().name, "Midterms");
+        assert!(cal.exam_window_on(&day(13)).is_none());
+        assert_eq!(cal.schedulable(cal.days()), vec![day(6), day(13)]);
+        assert_eq!(cal.schedulable(cal.window(Some(day(9)), None)), vec![day(13)]);
+        // Exam days still count as instructional days.
+        assert_eq!(cal.len(), 6);
+        assert_eq!(cal.days_between(&day(6), &day(13)), 5);
+
+        assert!(window("Finals", 9, 8).check().is_err());
+        assert!(window(" ", 8, 9).check().is_err());
+        assert!(window("Finals", 9, 9).check().is_ok());
+    }
+
+    /// Check `AcademicCalendar`'s arithmetic against naive scans of the
+    /// sorted days, over a bunch of random calendars and dates.
+    #[test]
+    fn calendar_properties() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(0x5ca1ab1e);
+        let start = Date::from_calendar_date(2022, Month::August, 22).unwrap();
+        let date = |n: i64| start + time::Duration::days(n);
+
+        for _ in 0..50 {
+            let days: Vec<Date> = (0..300).filter(|_| rng.gen_bool(0.6)).map(date).collect();
+            let cal = AcademicCalendar::new(days.clone());
+            let rank = |d: &Date| days.iter().filter(|x| *x <= d).count() as i64;
+
+            for (n, d) in days.iter().enumerate() {
+                assert_eq!(cal.index_of(d), Some(n));
+            }
+
+            for _ in 0..50 {
+                let a = date(rng.gen_range(-10..310));
+                let b = date(rng.gen_range(-10..310));
+                let c = date(rng.gen_range(-10..310));
+
+                let ab = cal.days_between(&a, &b);
+                assert_eq!(ab, rank(&b) - rank(&a));
+                assert_eq!(ab, -cal.days_between(&b, &a));
+                assert_eq!(ab + cal.days_between(&b, &c), cal.days_between(&a, &c));
+
+                let late = cal.lateness(&a, &b);
+                assert_eq!(late.signum(), (b - a).whole_days().signum());
+
+                let window: Vec<Date> = days
+                    .iter()
+                    .filter(|d| **d >= a && **d <= b)
+                    .copied()
+                    .collect();
+                assert_eq!(cal.window(Some(a), Some(b)), &window[..]);
+
+                let n = rng.gen_range(-20..20);
+                match cal.add_days(&a, n) {
+                    Some(d) => {
+                        assert!(cal.index_of(&d).is_some());
+                        if n != 0 || cal.index_of(&a).is_some() {
+                            assert_eq!(cal.days_between(&a, &d), n);
+                        }
+                    }
+                    None => {
+                        let idx = rank(&a) + n - 1;
+                        assert!(idx < 0 || idx >= days.len() as i64);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn burndown_series() {
+        ensure_logging();
+
+        let day = |d: u8| Date::from_calendar_date(2022, Month::September, d).unwrap();
+        let cal = AcademicCalendar::new([5, 6, 7, 8, 9].into_iter().map(day).collect());
+        let due = [day(6), day(6), day(9), day(12)];
+        let done = [Some(day(2)), Some(day(8)), None, None];
+
+        let events: Vec<(Date, f32)> = due.iter().map(|d| (*d, 0.25)).collect();
+        assert_eq!(
+            cumulative_weights(events, &cal),
+            vec![0.0, 0.5, 0.5, 0.5, 0.75]
+        );
+        let events: Vec<(Date, f32)> = done.iter().flatten().map(|d| (*d, 0.25)).collect();
+        let done_series = cumulative_weights(events, &cal);
+        assert_eq!(done_series, vec![0.25, 0.25, 0.25, 0.5, 0.5]);
+
+        let mut total = Burndown::default();
+        let one = Burndown {
+            due: vec![0.0, 0.5, 0.5, 0.5, 0.75],
+            done: done_series,
+        };
+        total.accumulate(&one);
+        total.accumulate(&one);
+        assert_eq!(total.due, vec![0.0, 1.0, 1.0, 1.0, 1.5]);
+        assert_eq!(total.done, vec![0.5, 0.5, 0.5, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn goal_durations() {
+        let day = |d: u8| Date::from_calendar_date(2022, Month::September, d).unwrap();
+        let cal = AcademicCalendar::new(
+            [5, 6, 7, 8, 9, 12, 13, 14, 15, 16]
+                .into_iter()
+                .map(day)
+                .collect(),
+        );
+        let base = |uname: &str, role: Role| BaseUser {
+            uname: uname.to_owned(),
+            role,
+            salt: String::new(),
+            email: format!("{}@camelotacademy.org", uname),
+        };
+        let teacher = match base("berro", Role::Teacher).into_teacher("Mr Berro".to_owned()) {
+            User::Teacher(t) => t,
+            _ => unreachable!(),
+        };
+        let student = match base("frog", Role::Student).into_student(
+            "Frog".to_owned(),
+            "Fred".to_owned(),
+            "berro".to_owned(),
+            String::new(),
+            None,
+            None,
+            0.2,
+            0.2,
+            0,
+            0,
+        ) {
+            User::Student(s) => s,
+            _ => unreachable!(),
+        };
+
+        // The last one is extra credit, done between the third and fourth.
+        let goals: Vec<Goal> = [
+            (Some(day(12)), Some(day(7))),
+            (Some(day(14)), Some(day(13))),
+            (Some(day(15)), Some(day(13))),
+            (Some(day(16)), Some(day(16))),
+            (Some(day(16)), None),
+            (None, Some(day(14))),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(n, (due, done))| Goal {
+            id: n as i64,
+            uname: "frog".to_owned(),
+            source: Source::Book(BookCh {
+                sym: "pc".to_owned(),
+                seq: n as i16,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due,
+            done,
+            tries: None,
+            weight: 0.2,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        })
+        .collect();
+
+        let mut p = Pace {
+            student,
+            teacher,
+            goals,
+            total_weight: 1.0,
+            due_weight: 0.0,
+            done_weight: 0.8,
+            waived: Vec::new(),
+        };
+        // Progress only counts on unfinished goals, and never as done.
+        assert_eq!(p.progress_weight(), 0.0);
+        p.goals[4].progress = Some(50);
+        p.goals[0].progress = Some(30);
+        assert!((p.progress_weight() - 0.1).abs() < 0.0001);
+        assert_eq!(p.lag(), 0.8);
+        let durations: Vec<(i64, f32, f32)> = p
+            .durations(&cal)
+            .iter()
+            .map(|d| (d.goal.id, d.actual, d.expected))
+            .collect();
+        assert_eq!(
+            durations,
+            vec![(0, 3.0, 2.0), (1, 2.0, 2.0), (2, 2.0, 2.0), (3, 2.0, 2.0)]
+        );
+
+        assert!(p.durations(&AcademicCalendar::default()).is_empty());
+    }
+
+    #[test]
+    fn summary_labels() {
+        let labels: SummaryLabels = toml::from_str(
+            r#"fall = "Trimester 1"
+            fall_grade = "T1 Grade""#,
+        )
+        .unwrap();
+        assert_eq!(labels.fall, "Trimester 1");
+        assert_eq!(labels.spring, SummaryLabels::default().spring);
+
+        let lines = generate_summary(Term::Fall, 0.9, 2, 0.2, Some(0.8), false, &labels).unwrap();
+        let labels: Vec<&str> = lines.iter().map(|sd| sd.label).collect();
+        assert_eq!(
+            labels,
+            ["Fall Test Average", "Exam Score", "Notices", "T1 Grade"]
+        );
+        assert_eq!(lines[3].value.as_str(), "86");
+
+        let defaults = SummaryLabels::default();
+        let lines = generate_summary(Term::Spring, 0.9, 0, 0.2, None, false, &defaults).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].label, "Spring Test Average");
+    }
+
+    #[test]
+    fn rubric_checks() {
+        fn item(name: &str, points: f32, max: f32) -> RubricItem {
+            RubricItem {
+                name: name.to_owned(),
+                points,
+                max,
+            }
+        }
+
+        assert!(check_rubric(&[]).is_ok());
+        assert!(check_rubric(&[item("Setup", 3.0, 4.0), item("Accuracy", 0.0, 6.0)]).is_ok());
+        assert!(check_rubric(&[item("  ", 3.0, 4.0)]).is_err());
+        assert!(check_rubric(&[item("Set|up", 3.0, 4.0)]).is_err());
+        assert!(check_rubric(&[item("Setup", 5.0, 4.0)]).is_err());
+        assert!(check_rubric(&[item("Setup", -1.0, 4.0)]).is_err());
+        assert!(check_rubric(&[item("Setup", 0.0, 0.0)]).is_err());
+        assert!(check_rubric(&[item("Setup", f32::NAN, 4.0)]).is_err());
+        assert!(check_rubric(&[item("Setup", 1.0, 4.0), item("Setup ", 2.0, 4.0)]).is_err());
+        let many: Vec<RubricItem> = (0..=MAX_RUBRIC_ITEMS)
+            .map(|n| item(&format!("Part {}", n), 1.0, 1.0))
+            .collect();
+        assert!(check_rubric(&many).is_err());
+    }
+
+    #[test]
+    fn course_constraints() {
+        use time::macros::date;
+
+        // Mon 10/3 through Fri 10/14, skipping Mon 10/10.
+        let cal = AcademicCalendar::new(vec![
+            date!(2022 - 10 - 3),
+            date!(2022 - 10 - 4),
+            date!(2022 - 10 - 5),
+            date!(2022 - 10 - 6),
+            date!(2022 - 10 - 7),
+            date!(2022 - 10 - 11),
+            date!(2022 - 10 - 12),
+            date!(2022 - 10 - 13),
+            date!(2022 - 10 - 14),
+        ]);
+        let due = [
+            (1, date!(2022 - 10 - 3)),
+            (2, date!(2022 - 10 - 5)),
+            (2, date!(2022 - 10 - 6)),
+            (3, date!(2022 - 10 - 7)),
+            (4, date!(2022 - 10 - 12)),
+        ];
+
+        let none = Constraints::default();
+        assert!(check_constraints("pc", &none, &due, &cal).is_empty());
+
+        let gap = Constraints {
+            min_gap: Some(2),
+            max_per_week: None,
+        };
+        // 2 -> 3 is one day (parts of 2 don't count), and 3 -> 4 is two,
+        // because the 10th isn't an instructional day.
+        let v = check_constraints("pc", &gap, &due, &cal);
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0].seqs, vec![2, 3]);
+        assert!(v[0].involves("pc", 3));
+        assert!(!v[0].involves("pc", 1));
+
+        let weekly = Constraints {
+            min_gap: None,
+            max_per_week: Some(2),
+        };
+        let v = check_constraints("pc", &weekly, &due, &cal);
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0].seqs, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn split_goal_fractions() {
+        assert_eq!(split_fractions(2, &[]).unwrap(), vec![0.5, 0.5]);
+        assert_eq!(split_fractions(4, &[]).unwrap().len(), 4);
+        let fracs = split_fractions(2, &[0.25, 0.75]).unwrap();
+        assert!((fracs[0] - 0.25).abs() < 0.0001);
+        assert!((fracs.iter().sum::<f32>() - 1.0).abs() < 0.0001);
+
+        assert!(split_fractions(1, &[]).is_err());
+        assert!(split_fractions(MAX_SPLIT_PARTS + 1, &[]).is_err());
+        assert!(split_fractions(3, &[0.5, 0.5]).is_err());
+        assert!(split_fractions(2, &[0.5, 0.6]).is_err());
+        assert!(split_fractions(2, &[1.5, -0.5]).is_err());
+    }
+
+    #[test]
+    fn what_if_exam() {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.0001;
+
+        // 0.8 * 0.75 + 0.2 * 0.9 - 0.02
+        assert!(close(semester_grade(0.75, 0.2, 0.9, 2), 0.76));
+        let needed = required_exam_score(0.75, 0.2, 2, 0.76).unwrap();
+        assert!(close(needed, 0.9));
+        assert!(close(semester_grade(0.75, 0.2, needed, 2), 0.76));
+
+        assert!(required_exam_score(0.75, 0.2, 0, 0.95).unwrap() > 1.0);
+        assert!(required_exam_score(0.98, 0.2, 0, 0.7).unwrap() < 0.0);
+        assert_eq!(required_exam_score(0.75, 0.0, 0, 0.85), None);
+    }
+
+    #[test]
+    fn projected_grades() {
+        let close = |(a, b): (f32, f32), (x, y): (f32, f32)| {
+            (a - x).abs() < 0.0001 && (b - y).abs() < 0.0001
+        };
+
+        // Best case tests are (0.6 * 0.5 + 0.5) / 1.0 = 0.8.
+        assert!(close(
+            project_semester_grade(0.6, 0.5, 0.5, 0.2, None, 0),
+            (0.6, 0.84)
+        ));
+        assert!(close(
+            project_semester_grade(0.6, 0.5, 0.5, 0.2, Some(0.5), 0),
+            (0.58, 0.74)
+        ));
+        assert!(close(
+            project_semester_grade(0.6, 0.0, 0.0, 0.2, None, 1),
+            (0.59, 0.67)
+        ));
+    }
+
+    #[test]
+    fn csv_templates() {
+        let student = |uname: &str, last: &str, rest: &str| {
+            let base = BaseUser {
+                uname: uname.to_owned(),
+                role: Role::Student,
+                salt: String::new(),
+                email: format!("{}@camelotacademy.org", uname),
+            };
+            match base.into_student(
+                last.to_owned(),
+                rest.to_owned(),
+                "berro".to_owned(),
+                String::new(),
+                None,
+                None,
+                0.2,
+                0.2,
+                0,
+                0,
+            ) {
+                User::Student(s) => s,
+                _ => unreachable!(),
+            }
+        };
+        let frog = student("frog", "Frog", "Fred");
+        let ape = student("ape", "Ape", "Amy");
+
+        let csv = Pace::csv_template(&[&frog, &ape]);
+        assert!(csv.contains("# Ape, Amy\nape,"));
+        let mut reader = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .has_headers(false)
+            .from_reader(csv.as_bytes());
+        let records: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        let unames: Vec<&str> = records.iter().map(|r| &r[0]).collect();
+        assert_eq!(unames, vec!["ape", "frog"]);
+        assert!(records
+            .iter()
+            .all(|r| r.len() == 8 && r.iter().skip(1).all(|f| f.is_empty())));
+
+        // Filled in, the rows read as goals.
+        let filled = csv.replace("frog,,,,,,,", "frog, pc, 3, 2022, 9, 12,,\n,, 4,,, 19,, x");
+        let mut reader = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .has_headers(false)
+            .from_reader(filled.as_bytes());
+        let mut prev: Option<Goal> = None;
+        for rec in reader.records().skip(1) {
+            let g = Goal::from_csv_line(&rec.unwrap(), prev.as_ref()).unwrap();
+            assert_eq!(g.uname, "frog");
+            prev = Some(g);
+        }
+        let g = prev.unwrap();
+        assert!(g.incomplete);
+        assert_eq!(g.due, Some(Date::from_calendar_date(2022, Month::September, 19).unwrap()));
+    }
+
+    #[test]
+    fn csv_completions() {
+        let read = |line: &str| {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .flexible(true)
+                .has_headers(false)
+                .from_reader(line.as_bytes());
+            let rec = reader.records().next().unwrap().unwrap();
+            Goal::from_csv_line(&rec, None)
+        };
+
+        let g = read("frog, pc, 3, 2022, 9, 12,,, 2022-09-08, 2, 17/20").unwrap();
+        assert_eq!(g.done, Some(Date::from_calendar_date(2022, Month::September, 8).unwrap()));
+        assert_eq!(g.tries, Some(2));
+        assert_eq!(g.score.as_deref(), Some("17/20"));
+
+        let g = read("frog, pc, 3, 2022, 9, 12,,, 2022-09-08").unwrap();
+        assert!(g.done.is_some() && g.tries.is_none() && g.score.is_none());
+        let g = read("frog, pc, 3, 2022, 9, 12,,,,,").unwrap();
+        assert!(g.done.is_none() && g.score.is_none());
+
+        assert!(read("frog, pc, 3, 2022, 9, 12,,, 9/8/2022").is_err());
+        assert!(read("frog, pc, 3, 2022, 9, 12,,, 2022-09-08, 0").is_err());
+        assert!(read("frog, pc, 3, 2022, 9, 12,,, 2022-09-08, 1, great").is_err());
+        assert!(read("frog, pc, 3, 2022, 9, 12,,, 2022-09-08, 1, 5/0").is_err());
+        assert!(read("frog, pc, 3, 2022, 9, 12,,,, 1, 85").is_err());
+    }
+
+    #[test]
+    fn goals_to_csv() {
+        let base = |uname: &str, role: Role| BaseUser {
+            uname: uname.to_owned(),
+            role,
+            salt: String::new(),
+            email: format!("{}@camelotacademy.org", uname),
+        };
+        let student = match base("frog", Role::Student).into_student(
+            "Frog".to_owned(),
+            "Fred".to_owned(),
+            "berro".to_owned(),
+            String::new(),
+            None,
+            None,
+            0.2,
+            0.2,
+            0,
+            0,
+        ) {
+            User::Student(s) => s,
+            _ => unreachable!(),
+        };
+        let teacher = match base("berro", Role::Teacher).into_teacher("Mr Berro".to_owned()) {
+            User::Teacher(t) => t,
+            _ => unreachable!(),
+        };
+        let goal = |source: Source, due: Option<Date>, review: bool, incomplete: bool| Goal {
+            id: 0,
+            uname: "frog".to_owned(),
+            source,
+            review,
+            incomplete,
+            due,
+            done: None,
+            tries: None,
+            weight: 0.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        };
+        let book = |sym: &str, seq: i16| {
+            Source::Book(BookCh {
+                sym: sym.to_owned(),
+                seq,
+                level: 0.0,
+            })
+        };
+        let sept = |d: u8| Some(Date::from_calendar_date(2022, Month::September, d).unwrap());
+
+        let p = Pace {
+            student,
+            teacher,
+            goals: vec![
+                goal(book("pc", 3), sept(12), true, false),
+                goal(Source::Custom(CustomCh(7)), sept(14), false, false),
+                goal(book("pc", 4), sept(19), false, true),
+                goal(book("pc", 5), None, false, false),
+            ],
+            total_weight: 0.0,
+            due_weight: 0.0,
+            done_weight: 0.0,
+            waived: Vec::new(),
+        };
+
+        let csv = p.to_csv();
+        assert!(csv.contains("# Frog, Fred\nfrog,pc,3,2022,9,12,x,\n"));
+        assert!(csv.contains("# 2 goal(s)"));
+
+        let mut reader = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .has_headers(false)
+            .from_reader(csv.as_bytes());
+        let mut prev: Option<Goal> = None;
+        let mut read: Vec<Goal> = Vec::new();
+        for rec in reader.records() {
+            let g = Goal::from_csv_line(&rec.unwrap(), prev.as_ref()).unwrap();
+            read.push(g.clone());
+            prev = Some(g);
+        }
+
+        let expected = [&p.goals[0], &p.goals[2]];
+        assert_eq!(read.len(), expected.len());
+        for (a, b) in read.iter().zip(expected.iter()) {
+            assert_eq!(a.uname, b.uname);
+            assert_eq!(a.source, b.source);
+            assert_eq!(a.due, b.due);
+            assert_eq!(a.review, b.review);
+            assert_eq!(a.incomplete, b.incomplete);
+        }
+    }
 }
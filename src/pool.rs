@@ -0,0 +1,73 @@
+/*!
+Optional connection pools for the auth DB and the data DB.
+
+By default, [`auth::Db::connect`](crate::auth::Db::connect) and
+[`Store::connect`](crate::store::Store::connect) open a new connection for
+every interaction with the database. If `db_pool_size` is set in the config
+file, each of them instead keeps a pool of up to that many connections (see
+[`new_pool`]), and hands them out as [`PgClient`]s, which go back into the
+pool when dropped.
+
+Either way, a [`PgClient`] derefs to a [`tokio_postgres::Client`], so code
+that uses a connection doesn't have to care where it came from.
+*/
+use std::{
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+
+use deadpool_postgres::{Manager, ManagerConfig, Object, Pool, RecyclingMethod, Runtime};
+use tokio_postgres::{Client, NoTls};
+
+/// How long to wait for a connection from a pool whose connections are all
+/// in use before giving up.
+const POOL_WAIT: Duration = Duration::from_secs(30);
+
+/// A connection to one of the databases, either opened just for the caller
+/// or checked out of a pool.
+pub enum PgClient {
+    Direct(Client),
+    Pooled(Object),
+}
+
+impl Deref for PgClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        match self {
+            PgClient::Direct(client) => client,
+            PgClient::Pooled(obj) => obj,
+        }
+    }
+}
+
+impl DerefMut for PgClient {
+    fn deref_mut(&mut self) -> &mut Client {
+        match self {
+            PgClient::Direct(client) => client,
+            PgClient::Pooled(obj) => obj,
+        }
+    }
+}
+
+/// Create a pool of up to `size` connections to the database at
+/// `connection_string`. Connections are only opened as they're needed.
+pub fn new_pool(connection_string: &str, size: usize) -> Result<Pool, String> {
+    let pg_config: tokio_postgres::Config = connection_string
+        .parse()
+        .map_err(|e| format!("Error parsing connection string: {}", &e))?;
+    let mgr = Manager::from_config(
+        pg_config,
+        NoTls,
+        ManagerConfig {
+            recycling_method: RecyclingMethod::Fast,
+        },
+    );
+
+    Pool::builder(mgr)
+        .max_size(size)
+        .runtime(Runtime::Tokio1)
+        .wait_timeout(Some(POOL_WAIT))
+        .build()
+        .map_err(|e| format!("Error building connection pool: {}", &e))
+}
@@ -0,0 +1,468 @@
+/*!
+Turning report markdown into PDF documents.
+
+Reports are generated as (Pandoc-flavored) markdown by
+[`report::generate_report_markup`](crate::report::generate_report_markup);
+a [`ReportRenderer`] then turns that text into the bytes of a PDF file.
+Which renderer gets used is selected by the `report_renderer` option in
+the configuration file:
+
+  * `"pandoc"` (the default) sends the markdown off to a Pandoc rendering
+    service (see `src/bin/pandocker.rs`). This produces the nicest output.
+  * `"builtin"` lays out the text itself with [`printpdf`]. The result is
+    a lot plainer, but it doesn't require the external service.
+*/
+use std::{future::Future, pin::Pin};
+
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+};
+
+/// Value of the `report_renderer` config option that selects [`PandocRenderer`].
+pub const PANDOC: &str = "pandoc";
+/// Value of the `report_renderer` config option that selects [`BuiltinRenderer`].
+pub const BUILTIN: &str = "builtin";
+
+/// Boxed future returned by [`ReportRenderer::render`].
+pub type RenderFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<u8>, String>> + Send + 'a>>;
+
+/// Something that can turn report markdown into a PDF.
+pub trait ReportRenderer: Send + Sync {
+    /// The name by which this renderer is selected in the config file.
+    fn name(&self) -> &'static str;
+
+    /// Render the markdown `text` into the bytes of a PDF document.
+    fn render(&self, text: String) -> RenderFuture<'_>;
+}
+
+/// Renders reports by POSTing them to a Pandoc service.
+pub struct PandocRenderer {
+    uri: hyper::Uri,
+    auth: String,
+    format: Option<String>,
+}
+
+impl PandocRenderer {
+    /// `format` is passed along to Pandoc as the "from" format; if `None`,
+    /// it defaults to `markdown+smart+raw_attribute`.
+    pub fn new(uri: hyper::Uri, auth: String, format: Option<String>) -> Self {
+        Self { uri, auth, format }
+    }
+
+    async fn request(&self, text: String) -> Result<Vec<u8>, String> {
+        use hyper::{body, Body, Client, Method, Request};
+
+        log::trace!(
+            "PandocRenderer::request( [ {} bytes of text ] ) called.",
+            &text.len()
+        );
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_only()
+            .enable_http1()
+            .build();
+        let client: Client<_, hyper::Body> = Client::builder().build(https);
+
+        let format: &str = match self.format.as_ref() {
+            Some(fmt) => fmt,
+            None => "markdown+smart+raw_attribute",
+        };
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(&self.uri)
+            .header("Authorization", &self.auth)
+            .header("Content-Type", "text/markdown")
+            .header("x-camp-from", format)
+            .body(Body::from(text))
+            .map_err(|e| format!("Error building report PDF rendering request: {}", &e))?;
+
+        let resp = client
+            .request(req)
+            .await
+            .map_err(|e| format!("Error sending PDF rendering request: {}", &e))?;
+
+        if resp.status() != 200 {
+            let reason = match resp.status().canonical_reason() {
+                Some(s) => format!("{} ({})", s, resp.status().as_u16()),
+                None => format!("{}", resp.status().as_u16()),
+            };
+            return Err(format!(
+                "PDF rendering service returned {} (expected 200) while attempting to render report into PDF.",
+                &reason
+            ));
+        }
+
+        let bytes = body::to_bytes(resp.into_body())
+            .await
+            .map_err(|e| format!("Error reading response from PDF rendering service: {}", &e))?;
+        Ok(bytes.to_vec())
+    }
+}
+
+impl ReportRenderer for PandocRenderer {
+    fn name(&self) -> &'static str {
+        PANDOC
+    }
+
+    fn render(&self, text: String) -> RenderFuture<'_> {
+        Box::pin(self.request(text))
+    }
+}
+
+/**
+Renders reports in-process with [`printpdf`] and its built-in fonts.
+
+This understands just enough of the markdown the report templates produce
+to lay it out legibly: headings, paragraphs (with `\` hard line breaks),
+tables (set in a monospace font, as they come out of the template already
+aligned), and `\pagebreak`. Front matter, raw HTML, and fenced raw blocks
+are skipped, and inline markup is stripped.
+*/
+pub struct BuiltinRenderer;
+
+impl ReportRenderer for BuiltinRenderer {
+    fn name(&self) -> &'static str {
+        BUILTIN
+    }
+
+    fn render(&self, text: String) -> RenderFuture<'_> {
+        // printpdf documents aren't `Send`, so all the work happens
+        // synchronously, before the future ever yields.
+        Box::pin(async move { layout(&text) })
+    }
+}
+
+// US Letter, in millimeters.
+const PAGE_WIDTH: f32 = 215.9;
+const PAGE_HEIGHT: f32 = 279.4;
+const MARGIN: f32 = 25.4;
+
+const BODY_SIZE: f32 = 11.0;
+const TABLE_SIZE: f32 = 9.0;
+const HEADING_SIZES: &[f32] = &[18.0, 14.0, 12.0];
+
+const MM_PER_PT: f32 = 25.4 / 72.0;
+const LEADING: f32 = 1.3;
+// Rough average character widths, as fractions of the font size; these are
+// only used for deciding where to wrap lines.
+const HELVETICA_WIDTH: f32 = 0.5;
+const COURIER_WIDTH: f32 = 0.6;
+
+/// A chunk of the document, as far as [`BuiltinRenderer`] is concerned.
+#[derive(Debug, PartialEq)]
+enum Block {
+    Heading(usize, String),
+    /// Lines separated by hard breaks.
+    Paragraph(Vec<String>),
+    Table(Vec<String>),
+    PageBreak,
+}
+
+/// Strip out the inline markup the report templates use.
+fn plain(line: &str) -> String {
+    let mut s = line
+        .replace("&ast;", "*")
+        .replace("**", "")
+        .replace("``", "\"")
+        .replace("''", "\"")
+        .replace("---", "-");
+
+    // [text]{.class} spans
+    while let Some(close) = s.find("]{") {
+        let open = match s[..close].rfind('[') {
+            Some(n) => n,
+            None => break,
+        };
+        let end = match s[close..].find('}') {
+            Some(n) => close + n,
+            None => break,
+        };
+        s = format!("{}{}{}", &s[..open], &s[(open + 1)..close], &s[(end + 1)..]);
+    }
+
+    s
+}
+
+/// Break the markdown `text` into [`Block`]s.
+fn parse_blocks(text: &str) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut para: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut table: Vec<String> = Vec::new();
+
+    let mut lines = text.lines().peekable();
+
+    // YAML front matter
+    if lines.peek().map(|line| line.trim()) == Some("---") {
+        lines.next();
+        for line in lines.by_ref() {
+            if line.trim() == "---" {
+                break;
+            }
+        }
+    }
+
+    fn flush(
+        blocks: &mut Vec<Block>,
+        para: &mut Vec<String>,
+        current: &mut String,
+        table: &mut Vec<String>,
+    ) {
+        if !current.is_empty() {
+            para.push(std::mem::take(current));
+        }
+        if !para.is_empty() {
+            blocks.push(Block::Paragraph(std::mem::take(para)));
+        }
+        if !table.is_empty() {
+            blocks.push(Block::Table(std::mem::take(table)));
+        }
+    }
+
+    let mut fenced = false;
+    for line in lines {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("```") {
+            flush(&mut blocks, &mut para, &mut current, &mut table);
+            fenced = !fenced;
+            continue;
+        }
+        if fenced || trimmed.starts_with('<') {
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            flush(&mut blocks, &mut para, &mut current, &mut table);
+        } else if trimmed == "\\pagebreak" {
+            flush(&mut blocks, &mut para, &mut current, &mut table);
+            blocks.push(Block::PageBreak);
+        } else if trimmed.starts_with('#') {
+            flush(&mut blocks, &mut para, &mut current, &mut table);
+            let level = trimmed.chars().take_while(|c| *c == '#').count();
+            blocks.push(Block::Heading(level, plain(trimmed[level..].trim())));
+        } else if trimmed.starts_with('|') {
+            if !current.is_empty() || !para.is_empty() {
+                flush(&mut blocks, &mut para, &mut current, &mut table);
+            }
+            // Skip the alignment row.
+            if !trimmed.chars().all(|c| "|:- \t".contains(c)) {
+                table.push(plain(trimmed));
+            }
+        } else {
+            if !table.is_empty() {
+                flush(&mut blocks, &mut para, &mut current, &mut table);
+            }
+            let (content, hard_break) = match trimmed.strip_suffix('\\') {
+                Some(content) => (content.trim_end(), true),
+                None => (trimmed, false),
+            };
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(&plain(content));
+            if hard_break {
+                para.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    flush(&mut blocks, &mut para, &mut current, &mut table);
+
+    blocks
+}
+
+/// Break `text` into lines of no more than (about) `width` characters.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// How many characters of the given average width (as a fraction of the
+/// font size) fit across the page at the given font `size`.
+fn chars_per_line(size: f32, char_width: f32) -> usize {
+    ((PAGE_WIDTH - 2.0 * MARGIN) / (size * char_width * MM_PER_PT)) as usize
+}
+
+enum Face {
+    Regular,
+    Bold,
+    Mono,
+}
+
+/// Keeps track of where on which page the next line goes.
+struct Layout {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    mono: IndirectFontRef,
+    y: f32,
+}
+
+impl Layout {
+    fn new() -> Result<Layout, String> {
+        let (doc, page, layer) =
+            PdfDocument::new("Report", Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "text");
+        let font = |f: BuiltinFont| {
+            doc.add_builtin_font(f)
+                .map_err(|e| format!("Unable to load built-in font: {}", &e))
+        };
+        let regular = font(BuiltinFont::Helvetica)?;
+        let bold = font(BuiltinFont::HelveticaBold)?;
+        let mono = font(BuiltinFont::Courier)?;
+        let layer = doc.get_page(page).get_layer(layer);
+
+        Ok(Layout {
+            doc,
+            layer,
+            regular,
+            bold,
+            mono,
+            y: PAGE_HEIGHT - MARGIN,
+        })
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "text");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn line(&mut self, text: &str, size: f32, face: Face) {
+        let height = size * MM_PER_PT * LEADING;
+        if self.y - height < MARGIN {
+            self.new_page();
+        }
+        self.y -= height;
+        let font = match face {
+            Face::Regular => &self.regular,
+            Face::Bold => &self.bold,
+            Face::Mono => &self.mono,
+        };
+        self.layer
+            .use_text(text, size, Mm(MARGIN), Mm(self.y), font);
+    }
+
+    fn skip(&mut self, size: f32) {
+        self.y -= size * MM_PER_PT * LEADING;
+    }
+
+    fn finish(self) -> Result<Vec<u8>, String> {
+        self.doc
+            .save_to_bytes()
+            .map_err(|e| format!("Error writing PDF: {}", &e))
+    }
+}
+
+/// Lay out the markdown `text` as a PDF.
+fn layout(text: &str) -> Result<Vec<u8>, String> {
+    log::trace!(
+        "render::layout( [ {} bytes of text ] ) called.",
+        &text.len()
+    );
+
+    let mut out = Layout::new()?;
+    let body_width = chars_per_line(BODY_SIZE, HELVETICA_WIDTH);
+    let table_width = chars_per_line(TABLE_SIZE, COURIER_WIDTH);
+
+    for block in parse_blocks(text).iter() {
+        match block {
+            Block::Heading(level, text) => {
+                let size = HEADING_SIZES[(level - 1).min(HEADING_SIZES.len() - 1)];
+                out.skip(BODY_SIZE / 2.0);
+                for line in wrap(text, chars_per_line(size, HELVETICA_WIDTH)).iter() {
+                    out.line(line, size, Face::Bold);
+                }
+            }
+            Block::Paragraph(lines) => {
+                for line in lines.iter().flat_map(|l| wrap(l, body_width)) {
+                    out.line(&line, BODY_SIZE, Face::Regular);
+                }
+                out.skip(BODY_SIZE / 2.0);
+            }
+            Block::Table(rows) => {
+                for row in rows.iter() {
+                    let row: String = row.chars().take(table_width).collect();
+                    out.line(&row, TABLE_SIZE, Face::Mono);
+                }
+                out.skip(BODY_SIZE / 2.0);
+            }
+            Block::PageBreak => out.new_page(),
+        }
+    }
+
+    out.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SAMPLE: &str = "---
+documentclass: article
+---
+
+# Mathematics Semester Report
+Camelot Academy &ast; Durham, NC \\
+phone: 555 555-5555
+
+Student: [Zeke Milk]{.underline} \\
+Teacher: Mr Bob
+
+| Course | Chapter |
+| :----- | :-----: |
+| **pha1** | 4 |
+
+<div style=\"page-break-after: always;\">
+\\pagebreak
+</div>
+
+```{=latex}
+\\vspace*{\\fill}
+```
+";
+
+    #[test]
+    fn blocks() {
+        let blocks = parse_blocks(SAMPLE);
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Heading(1, "Mathematics Semester Report".to_owned()),
+                Block::Paragraph(vec![
+                    "Camelot Academy * Durham, NC".to_owned(),
+                    "phone: 555 555-5555".to_owned(),
+                ]),
+                Block::Paragraph(vec![
+                    "Student: Zeke Milk".to_owned(),
+                    "Teacher: Mr Bob".to_owned(),
+                ]),
+                Block::Table(vec![
+                    "| Course | Chapter |".to_owned(),
+                    "| pha1 | 4 |".to_owned(),
+                ]),
+                Block::PageBreak,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn builtin_pdf() {
+        let bytes = BuiltinRenderer.render(SAMPLE.to_owned()).await.unwrap();
+        assert!(bytes.starts_with(b"%PDF"));
+    }
+}
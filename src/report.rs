@@ -3,6 +3,7 @@ Internal representations, containers, and functions for data only necessary
 for report-writing season.
 */
 use std::{
+    borrow::Cow,
     collections::{BTreeMap, HashMap},
     fmt::Write as FmtWrite,
     io::Write as IoWrite,
@@ -15,12 +16,12 @@ use crate::{
     blank_string_means_none,
     config::Glob,
     format_maybe_date,
-    inter::{render_raw_template, write_raw_template},
-    pace::{GoalDisplay, PaceDisplay, RowDisplay, Term},
+    DateStyle,
+    inter::{render_raw_string, render_raw_template, write_raw_template},
+    pace::{GoalDisplay, Pace, PaceDisplay, RowDisplay, SummaryLabels, Term},
     MiniString, UnifiedError, SMALLSTORE, MEDSTORE,
 };
 
-const DATE_FMT: &[FormatItem] = format_description!("[month repr:short] [day]");
 const TIMESTAMP_FMT: &[FormatItem] = format_description!(
     "[year]-[month]-[day] [hour]:[minute]:[second] UTC"
 );
@@ -205,6 +206,71 @@ impl std::default::Default for FactSet {
     }
 }
 
+/**
+Who a report (or a part of one) is for.
+
+These are ordered from least to most privileged: anything visible to
+`Student`s is also visible to `Parent`s, and the `Boss` sees everything.
+*/
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Audience {
+    #[default]
+    Student,
+    Parent,
+    Boss,
+}
+
+impl Audience {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Audience::Student => "student",
+            Audience::Parent => "parent",
+            Audience::Boss => "boss",
+        }
+    }
+
+    /// Whether this audience may see something with the given visibility
+    /// level.
+    pub fn can_see(&self, visibility: Audience) -> bool {
+        *self >= visibility
+    }
+}
+
+impl std::str::FromStr for Audience {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "student" | "Student" => Ok(Audience::Student),
+            "parent" | "Parent" => Ok(Audience::Parent),
+            "boss" | "Boss" => Ok(Audience::Boss),
+            _ => Err(format!("{:?} is not a valid Audience.", s)),
+        }
+    }
+}
+
+/// The least-privileged [`Audience`] allowed to see each section of a
+/// [`ReportSidecar`]. By default, everything is visible to everyone.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct SidecarVisibility {
+    pub facts: Audience,
+    pub social: Audience,
+    pub mastery: Audience,
+}
+
+/// A Teacher's free-form note to appear in one term's report.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ReportNote {
+    pub term: Term,
+    pub text: String,
+    /// The least-privileged [`Audience`] allowed to see this note.
+    pub visibility: Audience,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ReportSidecar {
     pub uname: String,
@@ -215,6 +281,28 @@ pub struct ReportSidecar {
     pub spring_complete: Vec<String>,
     pub summer_complete: Vec<String>,
     pub mastery: Vec<Mastery>,
+    #[serde(default)]
+    pub visibility: SidecarVisibility,
+    #[serde(default)]
+    pub notes: Vec<ReportNote>,
+}
+
+impl ReportSidecar {
+    /// Remove everything the given `audience` isn't allowed to see.
+    pub fn for_audience(mut self, audience: Audience) -> ReportSidecar {
+        if !audience.can_see(self.visibility.facts) {
+            self.facts = None;
+        }
+        if !audience.can_see(self.visibility.social) {
+            self.fall_social.clear();
+            self.spring_social.clear();
+        }
+        if !audience.can_see(self.visibility.mastery) {
+            self.mastery.clear();
+        }
+        self.notes.retain(|n| audience.can_see(n.visibility));
+        self
+    }
 }
 
 fn fact_status_display(factstatus: FactStatus) -> &'static str {
@@ -258,11 +346,13 @@ impl From<FactSet> for FactSetDisplay {
 #[derive(Debug, Serialize)]
 pub struct ReportGoalData<'a> {
     course: &'a str,
-    title: &'a str,
+    title: Cow<'a, str>,
     due: MiniString<SMALLSTORE>,
     done: MiniString<SMALLSTORE>,
     tries: Option<i16>,
     score: MiniString<SMALLSTORE>,
+    /// Rubric breakdown of the score, like "Setup 3/4, Accuracy 5/6".
+    rubric: String,
     mastery: &'static str,
 }
 
@@ -270,10 +360,19 @@ impl<'a> ReportGoalData<'a> {
     fn new(
         gd: GoalDisplay<'a>,
         mastery: Option<MasteryStatus>,
+        style: DateStyle,
     ) -> Result<ReportGoalData<'a>, String> {
-        let due = format_maybe_date(DATE_FMT, &gd.due)?;
-        let done = format_maybe_date(DATE_FMT, &gd.done)?;
+        let due = format_maybe_date(style, &gd.due)?;
+        let done = format_maybe_date(style, &gd.done)?;
         let score = write_maybe_percent(gd.score)?;
+        let mut rubric = String::new();
+        for (n, item) in gd.rubric.iter().enumerate() {
+            if n > 0 {
+                rubric.push_str(", ");
+            }
+            write!(&mut rubric, "{} {}/{}", &item.name, &item.points, &item.max)
+                .map_err(|e| format!("Error writing rubric component: {}", &e))?;
+        }
         let mastery = match mastery {
             Some(ms) => ms.as_str(),
             None => "",
@@ -286,6 +385,7 @@ impl<'a> ReportGoalData<'a> {
             due,
             done,
             score,
+            rubric,
             mastery,
         };
 
@@ -358,6 +458,10 @@ pub struct ReportData<'a> {
     spring_letter: &'a str,
     summary_lines: String,
     timestamp: MiniString<MEDSTORE>,
+    labels: &'a SummaryLabels,
+    show_facts: bool,
+    show_social: bool,
+    notes: String,
 }
 
 fn reqs_complete(is_incomplete: bool) -> &'static str {
@@ -426,14 +530,18 @@ where S: AsRef<str> + std::fmt::Debug
     Ok(list)
 }
 
-impl<'a, 'b> ReportData<'a> {
+impl<'a> ReportData<'a> {
     fn assemble(
         mut pd: PaceDisplay<'a>,
         sc: ReportSidecar,
         term: Term,
-        glob: &Glob,
+        audience: Audience,
+        glob: &'a Glob,
     ) -> Result<ReportData<'a>, String> {
         let academic_year = glob.academic_year_string();
+        let show_facts = audience.can_see(sc.visibility.facts);
+        let show_social = audience.can_see(sc.visibility.social);
+        let sc = sc.for_audience(audience);
 
         let facts_status = match sc.facts {
             None => FactSetDisplay::default(),
@@ -452,8 +560,11 @@ impl<'a, 'b> ReportData<'a> {
 
         let academic_year_end = match glob.dates.get("end-spring") {
             Some(d) => d,
-            None => {
-                return Err("Admin has not set \"end-spring\" date.".to_owned());
+            None => match glob.current_year() {
+                Some(y) => &y.ends,
+                None => {
+                    return Err("Admin has not set \"end-spring\" date.".to_owned());
+                },
             },
         };
 
@@ -498,7 +609,7 @@ impl<'a, 'b> ReportData<'a> {
                         } else {
                             None
                         };
-                        let line = ReportGoalData::new(gd, mast)?;
+                        let line = ReportGoalData::new(gd, mast, glob.date_style)?;
 
                         crate::inter::write_raw_template("report_goal", &line, &mut lines)?;
                     },
@@ -511,7 +622,7 @@ impl<'a, 'b> ReportData<'a> {
                                 continue;
                             }
                         }
-                        let line = ReportGoalData::new(gd, None)?;
+                        let line = ReportGoalData::new(gd, None, glob.date_style)?;
 
                         crate::inter::write_raw_template("report_summer_goal", &line, &mut lines)?;
                     },
@@ -611,6 +722,15 @@ They have {} chapter{} left before their {} academic year is complete.",
                 "error writing list of courses completed during Summer: {}", &e
             ))?;
         
+        let mut notes = String::new();
+        for note in sc.notes.iter().filter(|n| n.term == term) {
+            let text = note.text.trim();
+            if !text.is_empty() {
+                notes.push_str(text);
+                notes.push_str("\n\n");
+            }
+        }
+
         let mut timestamp: MiniString<MEDSTORE> = MiniString::new();
         time::OffsetDateTime::now_utc().format_into(&mut timestamp, &TIMESTAMP_FMT)
             .map_err(|e| format!(
@@ -649,6 +769,10 @@ They have {} chapter{} left before their {} academic year is complete.",
             spring_letter,
             summary_lines: String::new(),
             timestamp,
+            labels: &glob.summary_labels,
+            show_facts,
+            show_social,
+            notes,
         };
 
         log::debug!("{:#?}", &rd);
@@ -657,98 +781,340 @@ They have {} chapter{} left before their {} academic year is complete.",
     }
 }
 
+/// One table of goals in a Markdown pace export, followed by whatever
+/// semester summary lines came after it in the pace calendar.
+#[derive(Debug, Default, Serialize)]
+struct PaceMarkdownSection {
+    table: String,
+    summary: Vec<SummaryLine>,
+}
+
+#[derive(Debug, Serialize)]
+struct SummaryLine {
+    label: String,
+    value: String,
+}
+
+/// For serializing the `pace_markdown` template.
+#[derive(Debug, Serialize)]
+struct PaceMarkdownData<'a> {
+    name: String,
+    teacher: &'a str,
+    n_done: usize,
+    n_due: usize,
+    n_scheduled: usize,
+    sections: Vec<PaceMarkdownSection>,
+}
+
+/**
+Render a student's pace calendar as a standalone Markdown document, suitable
+for pasting into a newsletter (or feeding to [`render_markdown`]).
+
+Goals are laid out in tables with the same columns as the Summer report;
+each semester's summary lines follow the table of goals that precedes them.
+*/
+pub fn pace_markdown(mut pd: PaceDisplay<'_>, glob: &Glob) -> Result<String, String> {
+    log::trace!("pace_markdown( [ PaceDisplay {:?} ], [ &Glob ] ) called.", pd.uname);
+
+    let head = std::fs::read("data/report_pace_head_summer.md").map_err(|e| format!(
+        "Unable to read file \"data/report_pace_head_summer.md\": {}", &e
+    ))?;
+
+    let mut sections: Vec<PaceMarkdownSection> = Vec::new();
+    let mut lines: Vec<u8> = Vec::new();
+    let mut n_lines: usize = 0;
+    let mut current = PaceMarkdownSection::default();
+
+    for row in pd.rows.drain(..) {
+        match row {
+            RowDisplay::Goal(gd) => {
+                if !current.summary.is_empty() {
+                    sections.push(std::mem::take(&mut current));
+                }
+                if n_lines == 0 {
+                    lines.extend_from_slice(&head);
+                }
+                let line = ReportGoalData::new(gd, None, glob.date_style)?;
+                write_raw_template("report_summer_goal", &line, &mut lines)?;
+                n_lines += 1;
+            },
+            RowDisplay::Waived(wd) => {
+                if !current.summary.is_empty() {
+                    sections.push(std::mem::take(&mut current));
+                }
+                if n_lines == 0 {
+                    lines.extend_from_slice(&head);
+                }
+                write_raw_template("report_waived_goal", &wd, &mut lines)?;
+                n_lines += 1;
+            },
+            RowDisplay::Summary(sd) => {
+                if n_lines > 0 {
+                    let table = String::from_utf8(std::mem::take(&mut lines))
+                        .map_err(|e| format!("Pace goal lines are not UTF-8: {}", &e))?;
+                    current.table = format_markdown_table(table)?;
+                    n_lines = 0;
+                }
+                current.summary.push(SummaryLine {
+                    label: sd.label.to_owned(),
+                    value: sd.value.to_string(),
+                });
+            },
+        }
+    }
+    if n_lines > 0 {
+        let table = String::from_utf8(lines)
+            .map_err(|e| format!("Pace goal lines are not UTF-8: {}", &e))?;
+        current.table = format_markdown_table(table)?;
+    }
+    if !(current.table.is_empty() && current.summary.is_empty()) {
+        sections.push(current);
+    }
+
+    let data = PaceMarkdownData {
+        name: format!("{} {}", pd.rest, pd.last),
+        teacher: pd.teacher,
+        n_done: pd.n_done,
+        n_due: pd.n_due,
+        n_scheduled: pd.n_scheduled,
+        sections,
+    };
+
+    render_raw_template("pace_markdown", &data)
+}
+
+/// Names of the built-in report templates, which schools can replace with
+/// their own (see [`Store::activate_report_template`]).
+///
+/// [`Store::activate_report_template`]: crate::store::Store::activate_report_template
+pub const TEMPLATE_KINDS: &[&str] = &["report", "report_summer"];
+
+/// Name of the built-in template for `term`'s reports (one of
+/// [`TEMPLATE_KINDS`]).
+pub fn template_kind(term: Term) -> &'static str {
+    match term {
+        Term::Fall | Term::Spring => "report",
+        Term::Summer => "report_summer",
+    }
+}
+
+/// Gather everything the report templates need to render `uname`'s `term`
+/// report for `audience`.
+async fn report_data<'a>(
+    uname: &str,
+    term: Term,
+    audience: Audience,
+    p: &'a Pace,
+    glob: &'a Glob,
+) -> Result<ReportData<'a>, UnifiedError> {
+    let this_year = glob.academic_year();
+
+    let pd = PaceDisplay::from(p, glob)?;
+    let sc = glob.data().read().await.get_report_sidecar(uname, this_year).await?;
+
+    let mut rd = ReportData::assemble(pd, sc, term, audience, glob)?;
+    rd.summary_lines = render_summary(&rd, term)?;
+    Ok(rd)
+}
+
+/**
+Generate the Markdown text of `uname`'s `term` report, including only
+what the given `audience` is allowed to see.
+
+If the school has activated its own version of the template for `term`
+(see [`TEMPLATE_KINDS`]), that's used; otherwise (or if it fails to
+render) the built-in one is.
+*/
 pub async fn generate_report_markup(
     uname: &str,
     term: Term,
+    audience: Audience,
     glob: &Glob,
 ) -> Result<String, UnifiedError> {
     log::trace!(
-        "generate_report_markup( {:?}, {:?}, [ &Glob ]) called.",
+        "generate_report_markup( {:?}, {:?}, {:?}, [ &Glob ]) called.",
         uname,
-        &term
+        &term,
+        &audience
     );
 
-    let this_year = glob.academic_year();
+    let p = glob.get_pace_by_student(uname).await?;
+    let rd = report_data(uname, term, audience, &p, glob).await?;
+
+    let template_name = template_kind(term);
+    let custom = glob.data().read().await.get_active_report_template(template_name).await?;
+    if let Some(body) = custom {
+        match render_raw_string(&body, &rd) {
+            Ok(text) => {
+                return Ok(text);
+            }
+            Err(e) => {
+                log::error!(
+                    "Error rendering active {:?} template for {:?}; using built-in: {}",
+                    template_name,
+                    uname,
+                    &e
+                );
+            }
+        }
+    }
+
+    let text = render_raw_template(template_name, &rd)
+        .map_err(|e| format!("Error rendering template {:?}: {}", template_name, &e))?;
+
+    Ok(text)
+}
+
+/**
+Render `uname`'s `term` report (as a parent would see it) with the template
+`body` instead of the active one, so an Admin can check a template before
+activating it.
+*/
+pub async fn preview_report_markup(
+    uname: &str,
+    term: Term,
+    body: &str,
+    glob: &Glob,
+) -> Result<String, UnifiedError> {
+    log::trace!(
+        "preview_report_markup( {:?}, {:?}, [ {} bytes ], [ &Glob ]) called.",
+        uname,
+        &term,
+        body.len()
+    );
 
     let p = glob.get_pace_by_student(uname).await?;
-    let pd = PaceDisplay::from(&p, glob)?;
-    let sc = glob.data().read().await.get_report_sidecar(uname, this_year).await?;
+    let rd = report_data(uname, term, Audience::Parent, &p, glob).await?;
+    let text = render_raw_string(body, &rd)?;
 
-    let mut rd = ReportData::assemble(pd, sc, term, glob)?;
+    Ok(text)
+}
 
+/// Check that `body` is a usable Handlebars template, so a broken one
+/// can't be uploaded.
+pub fn check_template(body: &str) -> Result<(), String> {
+    handlebars::Template::compile(body)
+        .map(|_| ())
+        .map_err(|e| format!("Template doesn't parse: {}", &e))
+}
+
+/// Render the summary table at the bottom of a `term` report.
+fn render_summary(rd: &ReportData, term: Term) -> Result<String, String> {
     let summary_name = match term {
         Term::Fall => "fall_summary",
         Term::Spring => "spring_summary",
         Term::Summer => "summer_summary",
     };
 
-    let summary_lines = render_raw_template(summary_name, &rd)
+    let summary_lines = render_raw_template(summary_name, rd)
         .map_err(|e| format!("Error rendering template {:?}: {}", &summary_name, &e))?;
-    let summary_lines = format_markdown_table(summary_lines).map_err(|e| format!(
+    format_markdown_table(summary_lines).map_err(|e| format!(
         "Unable to format {:?} table: {}", summary_name, &e
-    ))?;
-    rd.summary_lines = summary_lines;
+    ))
+}
 
-    let template_name = match term {
-        Term::Fall | Term::Spring => "report",
-        Term::Summer => "report_summer",
-    };
+/// Replace every whole-word occurrence of `from` in `text` with `to`.
+fn replace_word(text: &str, from: &str, to: &str) -> String {
+    let is_word = |c: Option<char>| c.map(char::is_alphanumeric).unwrap_or(false);
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(n) = rest.find(from) {
+        out.push_str(&rest[..n]);
+        let after = &rest[n + from.len()..];
+        if is_word(out.chars().next_back()) || is_word(after.chars().next()) {
+            out.push_str(from);
+        } else {
+            out.push_str(to);
+        }
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
 
-    let text = render_raw_template(template_name, &rd)
-        .map_err(|e| format!("Error rendering template {:?}: {}", summary_name, &e))?;
+/**
+Turn `draft`, the markup of a `from` report, into the start of a `to`
+report: the term's name is changed wherever it's capitalized as a name
+(lowercase "fall" is left alone, as it's as likely to be a verb), and the
+first table under the "Summary" heading is swapped for `summary`.
+*/
+fn carry_over_markup(draft: &str, from: Term, to: Term, summary: &str) -> String {
+    let from_upper = from.as_str().to_uppercase();
+    let to_upper = to.as_str().to_uppercase();
+
+    let mut out = String::with_capacity(draft.len() + summary.len());
+    let mut in_summary = false;
+    let mut swapping = false;
+    let mut swapped = false;
+    for line in draft.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') {
+            in_summary = trimmed.trim_start_matches('#').trim() == "Summary";
+        }
+        if in_summary && !swapped && trimmed.starts_with('|') {
+            if !swapping {
+                out.push_str(summary.trim_end());
+                out.push('\n');
+                swapping = true;
+            }
+            continue;
+        } else if swapping {
+            swapping = false;
+            swapped = true;
+        }
 
-    Ok(text)
+        let line = replace_word(line, from.as_str(), to.as_str());
+        out.push_str(&replace_word(&line, &from_upper, &to_upper));
+    }
+
+    out
 }
 
-pub async fn render_markdown(text: String, glob: &Glob) -> Result<Vec<u8>, UnifiedError> {
-    use hyper::{body, Body, Client, Method, Request};
+/**
+Start `uname`'s `to` report from `draft`, the markup of their `from`
+report; see [`carry_over_markup`]. The summary table is regenerated from
+the student's current data.
+*/
+pub async fn carry_over_draft(
+    uname: &str,
+    draft: &str,
+    from: Term,
+    to: Term,
+    glob: &Glob,
+) -> Result<String, UnifiedError> {
+    log::trace!(
+        "carry_over_draft( {:?}, [ {} bytes of text ], {:?}, {:?}, [ &Glob ] ) called.",
+        uname,
+        draft.len(),
+        &from,
+        &to
+    );
+
+    let this_year = glob.academic_year();
 
+    let p = glob.get_pace_by_student(uname).await?;
+    let pd = PaceDisplay::from(&p, glob)?;
+    let sc = glob.data().read().await.get_report_sidecar(uname, this_year).await?;
+    // The summary table is the same whoever the report is for.
+    let rd = ReportData::assemble(pd, sc, to, Audience::Parent, glob)?;
+    let summary = render_summary(&rd, to)?;
+
+    Ok(carry_over_markup(draft, from, to, &summary))
+}
+
+/// Render report markdown `text` into a PDF with whichever
+/// [`ReportRenderer`](crate::render::ReportRenderer) is configured.
+pub async fn render_markdown(text: String, glob: &Glob) -> Result<Vec<u8>, UnifiedError> {
     log::trace!(
         "render_markdown( [ {} bytes of text ], [ &G ] ) called.",
         &text.len()
     );
-    let https = hyper_rustls::HttpsConnectorBuilder::new()
-        .with_native_roots()
-        .https_only()
-        .enable_http1()
-        .build();
-    let client: Client<_, hyper::Body> = Client::builder().build(https);
-
-    let format: &str = match glob.pandoc_format.as_ref() {
-        Some(fmt) => fmt,
-        None => "markdown+smart+raw_attribute",
-    };
 
-    let req = Request::builder()
-        .method(Method::POST)
-        .uri(&glob.pandoc_uri)
-        .header("Authorization", &glob.pandoc_auth)
-        .header("Content-Type", "text/markdown")
-        .header("x-camp-from", format)
-        .body(Body::from(text))
-        .map_err(|e| format!("Error building report PDF rendering request: {}", &e))?;
-
-    let resp = client
-        .request(req)
-        .await
-        .map_err(|e| format!("Error from PDF rendering request: {}", &e))
-        .map_err(|e| format!("Error sending PDF rendering request: {}", &e))?;
-
-    if resp.status() != 200 {
-        let reason = match resp.status().canonical_reason() {
-            Some(s) => format!("{} ({})", s, resp.status().as_u16()),
-            None => format!("{}", resp.status().as_u16()),
-        };
-        return Err(format!(
-            "PDF rendering service returned {} (expected 200) while attempting to render report into PDF.",
-            &reason
-        ).into());
-    }
-
-    let bytes = body::to_bytes(resp.into_body())
-        .await
-        .map_err(|e| format!("Error reading response from PDF rendering service: {}", &e))?;
-    Ok(bytes.to_vec())
+    let bytes = glob.renderer.render(text).await.map_err(|e| {
+        format!("Error rendering report with {:?}: {}", glob.renderer.name(), &e)
+    })?;
+    Ok(bytes)
 }
 
 #[cfg(test)]
@@ -763,16 +1129,106 @@ mod tests {
     static UNAME: &str = "zmilk";
     static OUTDIR: &str = "scratch/";
 
+
+    #[test]
+    fn carry_over() {
+        let fall = "# Semester Report
+
+   Term: 2022--2023, Fall
+
+Grades fall off when Fallon misses FALL EXAMS.
+
+## Summary
+
+|         | Fall | Spring |
+| :------ | :--: | :----: |
+| Grade   | 90%  |        |
+
+Keep up the good work this Fall!
+";
+        let summary = "|       | Fall | Spring |
+| :---- | :--: | :----: |
+| Grade | 90%  | 85%    |
+";
+        let spring = carry_over_markup(fall, Term::Fall, Term::Spring, summary);
+        assert_eq!(
+            spring,
+            "# Semester Report
+
+   Term: 2022--2023, Spring
+
+Grades fall off when Fallon misses SPRING EXAMS.
+
+## Summary
+
+|       | Fall | Spring |
+| :---- | :--: | :----: |
+| Grade | 90%  | 85%    |
+
+Keep up the good work this Spring!
+"
+        );
+
+        assert_eq!(replace_word("Fall,Fall", "Fall", "Spring"), "Spring,Spring");
+        assert_eq!(replace_word("Fallow", "Fall", "Spring"), "Fallow");
+    }
+
+    #[test]
+    fn sidecar_audiences() {
+        let note = |text: &str, visibility: Audience| ReportNote {
+            term: Term::Fall,
+            text: text.to_owned(),
+            visibility,
+        };
+        let sc = ReportSidecar {
+            uname: UNAME.to_owned(),
+            facts: Some(FactSet::default()),
+            fall_social: [("Leadership".to_owned(), "3".to_owned())].into(),
+            spring_social: HashMap::new(),
+            fall_complete: vec![],
+            spring_complete: vec![],
+            summer_complete: vec![],
+            mastery: vec![Mastery {
+                id: 1,
+                status: MasteryStatus::Mastered,
+            }],
+            visibility: SidecarVisibility {
+                facts: Audience::Student,
+                social: Audience::Parent,
+                mastery: Audience::Boss,
+            },
+            notes: vec![
+                note("Great work!", Audience::Student),
+                note("Struggling at home.", Audience::Parent),
+            ],
+        };
+
+        let boss = sc.clone().for_audience(Audience::Boss);
+        assert_eq!(boss.mastery.len(), 1);
+        assert_eq!(boss.notes.len(), 2);
+
+        let parent = sc.clone().for_audience(Audience::Parent);
+        assert!(parent.facts.is_some());
+        assert_eq!(parent.fall_social.len(), 1);
+        assert!(parent.mastery.is_empty());
+        assert_eq!(parent.notes.len(), 2);
+
+        let student = sc.for_audience(Audience::Student);
+        assert!(student.facts.is_some());
+        assert!(student.fall_social.is_empty());
+        assert!(student.mastery.is_empty());
+        assert_eq!(student.notes, [note("Great work!", Audience::Student)]);
+    }
     #[tokio::test]
     #[serial]
     async fn fall_markdown() -> Result<(), Box<dyn std::error::Error>> {
         ensure_logging();
         let glob = config::load_configuration(CONFIG).await?;
-        let text = generate_report_markup(UNAME, Term::Fall, &glob).await?;
+        let text = generate_report_markup(UNAME, Term::Fall, Audience::Parent, &glob).await?;
         let mut fname = String::from(OUTDIR);
         fname.push_str(UNAME);
         fname.push_str("_fall.md");
-        std::fs::write(&fname, &text.as_bytes())?;
+        std::fs::write(&fname, text.as_bytes())?;
         Ok(())
     }
 
@@ -781,11 +1237,11 @@ mod tests {
     async fn spring_markdown() -> Result<(), Box<dyn std::error::Error>> {
         ensure_logging();
         let glob = config::load_configuration(CONFIG).await?;
-        let text = generate_report_markup(UNAME, Term::Spring, &glob).await?;
+        let text = generate_report_markup(UNAME, Term::Spring, Audience::Parent, &glob).await?;
         let mut fname = String::from(OUTDIR);
         fname.push_str(UNAME);
         fname.push_str("_spring.md");
-        std::fs::write(&fname, &text.as_bytes())?;
+        std::fs::write(&fname, text.as_bytes())?;
         Ok(())
     }
 
@@ -794,7 +1250,7 @@ mod tests {
     async fn render_report() -> Result<(), Box<dyn std::error::Error>> {
         ensure_logging();
         let glob = config::load_configuration(CONFIG).await?;
-        let text = generate_report_markup(UNAME, Term::Spring, &glob).await?;
+        let text = generate_report_markup(UNAME, Term::Spring, Audience::Parent, &glob).await?;
         let pdf_bytes = render_markdown(text, &glob).await?;
         let mut fname = String::from(OUTDIR);
         fname.push_str(UNAME);
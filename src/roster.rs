@@ -0,0 +1,370 @@
+/*!
+Keeping the student roster in step with the school information system.
+
+The SIS exports its roster every night, in the same CSV format Admins use
+to upload students (see [`Student::vec_from_csv_reader`]). If the
+`[roster_sync]` table of the config file says where to find it (see
+[`RosterSync`]), a background task (see
+[`roster`](crate::inter::roster)) fetches it every night and keeps it, and
+Admins can see how it differs from the students camp already has (see
+[`diff`]): students to add, students who have changed Teachers, and
+students who have withdrawn.
+
+Nothing changes until an Admin has reviewed the differences and approved
+them (see [`Glob::apply_roster`](crate::config::Glob::apply_roster)), and
+then only through the usual `Glob` user methods.
+*/
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::user::{Student, User};
+
+fn default_hour() -> u8 {
+    6
+}
+
+/// Where and when to fetch the SIS roster; the `[roster_sync]` table of the
+/// config file. Without one, the roster isn't synced at all.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RosterSync {
+    /// An `https://` (or `http://`) URL, or the path of a local file. There's
+    /// no SFTP client; have the SFTP job drop the file somewhere the server
+    /// can read it, and give that path.
+    pub source: String,
+    /// Value of the `Authorization` header to send when fetching over HTTP,
+    /// if the SIS needs one.
+    #[serde(default)]
+    pub auth: Option<String>,
+    /// Hour of the day (UTC) at which to fetch the roster. Default is 6.
+    #[serde(default = "default_hour")]
+    pub hour: u8,
+}
+
+/// A student on the roster but not in camp (to be added), or the other way
+/// around (to be withdrawn).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Listed {
+    pub uname: String,
+    pub last: String,
+    pub rest: String,
+    /// uname of the student's Teacher (on the roster, for students to be
+    /// added; in camp, for students to be withdrawn)
+    pub teacher: String,
+}
+
+impl From<&Student> for Listed {
+    fn from(s: &Student) -> Self {
+        Listed {
+            uname: s.base.uname.clone(),
+            last: s.last.clone(),
+            rest: s.rest.clone(),
+            teacher: s.teacher.clone(),
+        }
+    }
+}
+
+/// A student the roster has with a different Teacher than camp does.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Move {
+    pub uname: String,
+    pub last: String,
+    pub rest: String,
+    /// uname of the student's Teacher in camp
+    pub from: String,
+    /// uname of the student's Teacher on the roster
+    pub to: String,
+}
+
+/// How a roster differs from the students camp has; see [`diff`]. This is
+/// what Admins review before applying a roster.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct RosterDiff {
+    /// Students on the roster who aren't in camp.
+    pub adds: Vec<Listed>,
+    /// Students whose Teacher is different on the roster.
+    pub moves: Vec<Move>,
+    /// Students in camp who aren't on the roster anymore.
+    pub withdrawals: Vec<Listed>,
+    /// Roster rows that can't be applied, and why. They're left out of the
+    /// changes above.
+    pub problems: Vec<String>,
+    /// Must be passed back to apply the changes, to show they're the ones
+    /// that were reviewed.
+    pub token: String,
+}
+
+impl RosterDiff {
+    /// Whether applying the roster would change anything. (`problems` don't
+    /// count, because they aren't applied.)
+    pub fn is_empty(&self) -> bool {
+        self.adds.is_empty() && self.moves.is_empty() && self.withdrawals.is_empty()
+    }
+}
+
+/// The most recently fetched roster, and how fetching has gone. Only kept in
+/// memory, so after a restart there's nothing to review until the next
+/// fetch.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RosterState {
+    /// When the roster was last fetched.
+    pub fetched: Option<String>,
+    /// The error from the last attempt to fetch it, if that failed.
+    pub last_error: Option<String>,
+    /// When changes from it were last applied.
+    pub applied: Option<String>,
+    /// The students on the roster (as of `fetched`).
+    #[serde(skip)]
+    pub students: Vec<Student>,
+}
+
+/// A change from the roster that couldn't be applied.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Failure {
+    /// The student the change was for.
+    pub uname: String,
+    /// Why it didn't work.
+    pub error: String,
+}
+
+/// What applying a roster's changes did. Each change is tried on its own,
+/// so some can fail without stopping the rest.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct RosterApplied {
+    /// unames of students added
+    pub added: Vec<String>,
+    /// unames of students whose Teachers were changed
+    pub moved: Vec<String>,
+    /// unames of students deleted for having withdrawn
+    pub withdrawn: Vec<String>,
+    /// changes that couldn't be made
+    pub failed: Vec<Failure>,
+}
+
+/**
+Work out how `roster` differs from the students among `users`.
+
+Roster rows that can't be applied (duplicate unames, Teachers who don't
+exist, unames that belong to staff) are reported as `problems` rather than
+changes. The `token` is a hash of the changes, so an apply can be refused
+if the roster (or camp's students) has changed since it was reviewed.
+*/
+pub fn diff(roster: &[Student], users: &HashMap<String, User>) -> RosterDiff {
+    let mut d = RosterDiff::default();
+    let mut seen: HashSet<&str> = HashSet::with_capacity(roster.len());
+
+    for s in roster.iter() {
+        let uname = s.base.uname.as_str();
+        if !seen.insert(uname) {
+            d.problems
+                .push(format!("{:?} is on the roster more than once.", uname));
+            continue;
+        }
+        match users.get(&s.teacher) {
+            Some(User::Teacher(_)) => {}
+            _ => {
+                d.problems.push(format!(
+                    "{:?} is assigned to {:?}, who is not a Teacher.",
+                    uname, &s.teacher
+                ));
+                continue;
+            }
+        }
+
+        match users.get(uname) {
+            None => d.adds.push(Listed::from(s)),
+            Some(User::Student(current)) if current.teacher != s.teacher => {
+                d.moves.push(Move {
+                    uname: uname.to_owned(),
+                    last: current.last.clone(),
+                    rest: current.rest.clone(),
+                    from: current.teacher.clone(),
+                    to: s.teacher.clone(),
+                });
+            }
+            Some(User::Student(_)) => {}
+            Some(u) => d
+                .problems
+                .push(format!("{:?} is already in use by a {}.", uname, u.role())),
+        }
+    }
+
+    for u in users.values() {
+        if let User::Student(s) = u {
+            if !seen.contains(s.base.uname.as_str()) {
+                d.withdrawals.push(Listed::from(s));
+            }
+        }
+    }
+
+    d.adds.sort_by(|a, b| a.uname.cmp(&b.uname));
+    d.moves.sort_by(|a, b| a.uname.cmp(&b.uname));
+    d.withdrawals.sort_by(|a, b| a.uname.cmp(&b.uname));
+
+    let mut hasher = blake3::Hasher::new();
+    for a in d.adds.iter() {
+        hasher.update(format!("+{}>{};", &a.uname, &a.teacher).as_bytes());
+    }
+    for m in d.moves.iter() {
+        hasher.update(format!("{}:{}>{};", &m.uname, &m.from, &m.to).as_bytes());
+    }
+    for w in d.withdrawals.iter() {
+        hasher.update(format!("-{};", &w.uname).as_bytes());
+    }
+    d.token = hasher.finalize().to_hex()[..16].to_owned();
+
+    d
+}
+
+/// Read the roster's CSV text from wherever `cfg` says it is.
+pub async fn fetch(cfg: &RosterSync) -> Result<String, String> {
+    let source = cfg.source.trim();
+    if source.starts_with("https://") || source.starts_with("http://") {
+        return fetch_http(source, cfg.auth.as_deref()).await;
+    }
+    if source.starts_with("sftp://") {
+        return Err(
+            "Fetching over SFTP isn't supported; give the path where the file is dropped off."
+                .to_owned(),
+        );
+    }
+
+    let path = source.strip_prefix("file://").unwrap_or(source);
+    std::fs::read_to_string(path).map_err(|e| format!("Error reading roster {:?}: {}", path, &e))
+}
+
+async fn fetch_http(uri: &str, auth: Option<&str>) -> Result<String, String> {
+    use hyper::{Body, Client, Method, Request};
+
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client: Client<_, hyper::Body> = Client::builder().build(https);
+
+    let mut req = Request::builder().method(Method::GET).uri(uri);
+    if let Some(auth) = auth {
+        req = req.header("Authorization", auth);
+    }
+    let req = req
+        .body(Body::empty())
+        .map_err(|e| format!("Error building roster request: {}", &e))?;
+
+    let resp = client
+        .request(req)
+        .await
+        .map_err(|e| format!("Error requesting roster: {}", &e))?;
+    if !resp.status().is_success() {
+        return Err(format!(
+            "Roster source returned {} response.",
+            &resp.status()
+        ));
+    }
+
+    let body = hyper::body::to_bytes(resp.into_body())
+        .await
+        .map_err(|e| format!("Error reading roster response: {}", &e))?;
+    String::from_utf8(body.to_vec()).map_err(|e| format!("Roster isn't valid UTF-8: {}", &e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::user::{BaseUser, Role};
+
+    static ROSTER: &str = "#uname, last, rest, email, parent, teacher
+    frog, Frog, Frederick, fred.frog@gmail.com, ferd.frog@gmail.com, berro
+    zack, Milk, Zachary, milktruck@gmail.com, handsome.dave@gmail.com, jenny
+    newt, Newt, Ned, ned@gmail.com, ned.sr@gmail.com, berro
+    jenny, Jenny, Jen, jj@gmail.com, jj.sr@gmail.com, berro
+    owl, Owl, Olive, olive@gmail.com, owls@gmail.com, nobody
+    newt, Newt, Ned, ned@gmail.com, ned.sr@gmail.com, berro";
+
+    fn users() -> HashMap<String, User> {
+        let base = |uname: &str| BaseUser {
+            uname: uname.to_owned(),
+            role: Role::Student,
+            salt: String::new(),
+            email: format!("{}@camelotacademy.org", uname),
+        };
+        let student = |uname: &str, teacher: &str| {
+            base(uname).into_student(
+                uname.to_owned(),
+                uname.to_owned(),
+                teacher.to_owned(),
+                String::new(),
+                None,
+                None,
+                0.2,
+                0.2,
+                0,
+                0,
+            )
+        };
+
+        [
+            base("berro").into_teacher("Mr Berro".to_owned()),
+            base("jenny").into_teacher("Ms Jenny".to_owned()),
+            student("frog", "berro"),
+            student("zack", "berro"),
+            student("gone", "jenny"),
+        ]
+        .into_iter()
+        .map(|u| (u.uname().to_owned(), u))
+        .collect()
+    }
+
+    #[test]
+    fn roster_diffs() {
+        let roster = Student::vec_from_csv_reader(ROSTER.as_bytes()).unwrap();
+        let users = users();
+        let d = diff(&roster, &users);
+
+        let unames = |v: &[Listed]| v.iter().map(|l| l.uname.clone()).collect::<Vec<_>>();
+        assert_eq!(unames(&d.adds), vec!["newt".to_owned()]);
+        assert_eq!(
+            d.moves,
+            vec![Move {
+                uname: "zack".to_owned(),
+                last: "zack".to_owned(),
+                rest: "zack".to_owned(),
+                from: "berro".to_owned(),
+                to: "jenny".to_owned(),
+            }]
+        );
+        assert_eq!(unames(&d.withdrawals), vec!["gone".to_owned()]);
+        // "jenny" is a Teacher, "owl"'s teacher doesn't exist, and "newt"
+        // is listed twice.
+        assert_eq!(d.problems.len(), 3);
+        assert!(!d.is_empty());
+
+        // The token only depends on the changes.
+        assert_eq!(diff(&roster, &users).token, d.token);
+        let fewer: Vec<Student> = roster
+            .iter()
+            .filter(|s| s.base.uname != "newt")
+            .cloned()
+            .collect();
+        assert_ne!(diff(&fewer, &users).token, d.token);
+
+        let current: Vec<Student> = users
+            .values()
+            .filter_map(|u| match u {
+                User::Student(s) => Some(s.clone()),
+                _ => None,
+            })
+            .collect();
+        let d = diff(&current, &users);
+        assert!(d.is_empty());
+        assert!(d.problems.is_empty());
+    }
+
+    #[test]
+    fn roster_config() {
+        let r: RosterSync = toml::from_str(r#"source = "/srv/sis/roster.csv""#).unwrap();
+        assert_eq!(r.hour, 6);
+        assert_eq!(r.auth, None);
+    }
+}
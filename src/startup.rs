@@ -0,0 +1,189 @@
+/*!
+Waiting for the databases to come up when the server starts.
+
+In a container, camp may well start before Postgres is ready to take
+connections. Rather than failing right away (and crash-looping until
+Postgres is up), [`load_configuration`](crate::config::load_configuration)
+waits for each database (see [`wait_for`]), retrying with exponential
+backoff as set in the `[startup_retry]` table of the config file (see
+[`StartupRetry`]).
+
+Meanwhile, the server answers every request with a maintenance page (see
+[`maintenance`](crate::inter::maintenance)), which reports what it's
+waiting on from the shared [`StartupStatus`].
+*/
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// How long to keep trying to connect to each database at startup.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct StartupRetry {
+    /// Number of attempts to make before giving up; 0 means never give up.
+    /// Default is 0.
+    pub attempts: u32,
+    /// Seconds to wait after the first failed attempt. Default is 1.
+    pub initial_secs: u64,
+    /// The wait doubles after each failed attempt, up to this many
+    /// seconds. Default is 60.
+    pub max_secs: u64,
+}
+
+impl Default for StartupRetry {
+    fn default() -> Self {
+        Self {
+            attempts: 0,
+            initial_secs: 1,
+            max_secs: 60,
+        }
+    }
+}
+
+impl StartupRetry {
+    /// How long to wait after failed attempt number `attempt` (counting
+    /// from 1).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        Duration::from_secs(self.initial_secs.saturating_mul(factor).min(self.max_secs))
+    }
+}
+
+/// What startup is waiting on.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct StartupStatus {
+    /// The database being waited for, if any.
+    pub waiting_for: Option<String>,
+    /// Number of failed attempts to connect to it so far.
+    pub attempts: u32,
+    /// The error from the most recent failed attempt.
+    pub last_error: Option<String>,
+}
+
+/// Startup status shared between the startup sequence and the maintenance
+/// page.
+pub type SharedStatus = Arc<Mutex<StartupStatus>>;
+
+/**
+Call `connect` until it succeeds, waiting between attempts as `retry`
+says, and keeping `status` up to date. `name` is the name of the database,
+for logs and the maintenance page.
+
+Returns the last error if `retry.attempts` attempts all fail.
+*/
+pub async fn wait_for<F, Fut>(
+    name: &str,
+    retry: &StartupRetry,
+    status: &SharedStatus,
+    mut connect: F,
+) -> Result<(), String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let start = Instant::now();
+    let mut attempt: u32 = 0;
+    loop {
+        attempt += 1;
+        let e = match connect().await {
+            Ok(()) => {
+                if attempt > 1 {
+                    log::info!(
+                        "{} available after {} attempts ({} seconds).",
+                        name,
+                        &attempt,
+                        start.elapsed().as_secs()
+                    );
+                }
+                *status.lock().unwrap() = StartupStatus::default();
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+
+        if retry.attempts > 0 && attempt >= retry.attempts {
+            log::error!(
+                "{} still unavailable after {} attempts; giving up: {}",
+                name,
+                &attempt,
+                &e
+            );
+            return Err(format!("{} unavailable: {}", name, &e));
+        }
+
+        let wait = retry.backoff(attempt);
+        log::warn!(
+            "{} unavailable (attempt {}, {} seconds waiting so far); retrying in {} seconds: {}",
+            name,
+            &attempt,
+            start.elapsed().as_secs(),
+            wait.as_secs(),
+            &e
+        );
+        *status.lock().unwrap() = StartupStatus {
+            waiting_for: Some(name.to_owned()),
+            attempts: attempt,
+            last_error: Some(e),
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let r = StartupRetry::default();
+        let secs: Vec<u64> = [1, 2, 3, 6, 7, 8, 100]
+            .into_iter()
+            .map(|n| r.backoff(n).as_secs())
+            .collect();
+        assert_eq!(secs, vec![1, 2, 4, 32, 60, 60, 60]);
+
+        let r: StartupRetry = toml::from_str("attempts = 5").unwrap();
+        assert_eq!(r.initial_secs, 1);
+        assert_eq!(r.max_secs, 60);
+    }
+
+    #[tokio::test]
+    async fn retry_until_up() {
+        let retry = StartupRetry {
+            attempts: 4,
+            initial_secs: 0,
+            max_secs: 0,
+        };
+        let status: SharedStatus = Arc::default();
+
+        let mut n = 0;
+        let res = wait_for("data DB", &retry, &status, || {
+            n += 1;
+            let res = if n < 3 {
+                Err("connection refused".to_owned())
+            } else {
+                Ok(())
+            };
+            async move { res }
+        })
+        .await;
+        assert_eq!(res, Ok(()));
+        assert_eq!(n, 3);
+        assert_eq!(*status.lock().unwrap(), StartupStatus::default());
+
+        let res = wait_for("auth DB", &retry, &status, || async {
+            Err("connection refused".to_owned())
+        })
+        .await;
+        assert!(res.is_err());
+        let status = status.lock().unwrap().clone();
+        assert_eq!(status.waiting_for.as_deref(), Some("auth DB"));
+        assert_eq!(status.attempts, 3);
+    }
+}
@@ -0,0 +1,189 @@
+/*!
+`Store` methods for the stretches of days students' families have said
+they'll be away (see [`Absence`]).
+
+```sql
+CREATE TABLE absences (
+    id          BIGSERIAL PRIMARY KEY,
+    uname       TEXT NOT NULL REFERENCES students(uname) ON DELETE CASCADE,
+    start_date  DATE NOT NULL,
+    end_date    DATE NOT NULL,
+    reason      TEXT NOT NULL DEFAULT '',
+    recorded_by TEXT NOT NULL,  /* uname of the Teacher */
+    CHECK (start_date <= end_date)
+);
+```
+
+Absences are cleared by the yearly nuke along with the goals.
+*/
+use time::Date;
+use tokio_postgres::{Row, Transaction};
+
+use super::{DbError, Store};
+use crate::pace::Absence;
+
+fn absence_from_row(row: &Row) -> Result<Absence, DbError> {
+    Ok(Absence {
+        id: row.try_get("id")?,
+        uname: row.try_get("uname")?,
+        start: row.try_get("start_date")?,
+        end: row.try_get("end_date")?,
+        reason: row.try_get("reason")?,
+    })
+}
+
+impl Store {
+    /// Return the absences of the student `uname`, in order.
+    pub async fn get_absences_by_student(&self, uname: &str) -> Result<Vec<Absence>, DbError> {
+        log::trace!("Store::get_absences_by_student( {:?} ) called.", uname);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT id, uname, start_date, end_date, reason FROM absences
+                WHERE uname = $1
+                ORDER BY start_date, end_date",
+                &[&uname],
+            )
+            .await?;
+
+        rows.iter().map(absence_from_row).collect()
+    }
+
+    /**
+    Record that the student `uname` will be away from `start` through `end`
+    (inclusive). `by` is the uname of the Teacher recording it.
+
+    Returns the new [`Absence`].
+    */
+    pub async fn insert_absence(
+        &self,
+        uname: &str,
+        start: Date,
+        end: Date,
+        reason: &str,
+        by: &str,
+    ) -> Result<Absence, DbError> {
+        log::trace!(
+            "Store::insert_absence( {:?}, {}, {}, {:?}, {:?} ) called.",
+            uname,
+            &start,
+            &end,
+            reason,
+            by
+        );
+
+        if end < start {
+            return Err(DbError(format!(
+                "An absence can't end ({}) before it starts ({}).",
+                &end, &start
+            )));
+        }
+
+        let client = self.connect().await?;
+        let row = client
+            .query_one(
+                "INSERT INTO absences (uname, start_date, end_date, reason, recorded_by)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING id, uname, start_date, end_date, reason",
+                &[&uname, &start, &end, &reason, &by],
+            )
+            .await
+            .map_err(|e| {
+                DbError::from(e).annotate(&format!("Error recording absence for {:?}", uname))
+            })?;
+
+        absence_from_row(&row)
+    }
+
+    /// Delete the absence `id` of the student `uname`. Returns whether there
+    /// was one to delete.
+    pub async fn delete_absence(&self, uname: &str, id: i64) -> Result<bool, DbError> {
+        log::trace!("Store::delete_absence( {:?}, {} ) called.", uname, &id);
+
+        let client = self.connect().await?;
+        let n = client
+            .execute(
+                "DELETE FROM absences WHERE id = $1 AND uname = $2",
+                &[&id, &uname],
+            )
+            .await?;
+
+        Ok(n > 0)
+    }
+
+    /// Clear the year's absences.
+    pub async fn yearly_clear_absences(t: &Transaction<'_>) -> Result<(), DbError> {
+        log::trace!("Store::yearly_clear_absences( [ T ] ) called.");
+
+        t.execute("DELETE FROM absences", &[]).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+    use time::Month;
+
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    fn day(d: u8) -> Date {
+        Date::from_calendar_date(2023, Month::March, d).unwrap()
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn absences() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        insert_users(&db, &["berro"], &[("frog", "berro"), ("zack", "berro")]).await;
+
+        let trip = db
+            .insert_absence("frog", day(13), day(17), "family trip", "berro")
+            .await
+            .unwrap();
+        assert_eq!((trip.start, trip.end), (day(13), day(17)));
+        let dentist = db
+            .insert_absence("frog", day(2), day(2), "dentist", "berro")
+            .await
+            .unwrap();
+        db.insert_absence("zack", day(6), day(7), "", "berro")
+            .await
+            .unwrap();
+        assert!(db
+            .insert_absence("frog", day(9), day(8), "backwards", "berro")
+            .await
+            .is_err());
+
+        assert_eq!(
+            db.get_absences_by_student("frog").await.unwrap(),
+            vec![dentist.clone(), trip.clone()]
+        );
+
+        // An absence can only be deleted by way of its own student.
+        assert!(!db.delete_absence("zack", trip.id).await.unwrap());
+        assert!(db.delete_absence("frog", trip.id).await.unwrap());
+        assert!(!db.delete_absence("frog", trip.id).await.unwrap());
+        assert_eq!(
+            db.get_absences_by_student("frog").await.unwrap(),
+            vec![dentist]
+        );
+
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            Store::yearly_clear_absences(&t).await.unwrap();
+            t.commit().await.unwrap();
+        }
+        assert!(db.get_absences_by_student("zack").await.unwrap().is_empty());
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -0,0 +1,207 @@
+/*!
+`Store` methods for announcements the Boss posts to the top of Teachers'
+and/or students' pages.
+
+```sql
+CREATE TABLE announcements (
+    id      BIGSERIAL PRIMARY KEY,
+    message TEXT NOT NULL,
+    roles   TEXT[] NOT NULL,    /* the Roles that see it, like '{Teacher}' */
+    starts  DATE NOT NULL,      /* first day it's shown */
+    ends    DATE NOT NULL       /* last day it's shown */
+);
+```
+*/
+use serde::{Deserialize, Serialize};
+use time::{Date, Duration};
+use tokio_postgres::Row;
+
+use super::{DbError, Store};
+use crate::user::Role;
+
+/// A message shown to every user with one of the given roles, from
+/// `starts` through `ends` (inclusive).
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Announcement {
+    /// Assigned by the database; ignored on insertion.
+    #[serde(default)]
+    pub id: i64,
+    pub message: String,
+    pub roles: Vec<Role>,
+    pub starts: Date,
+    pub ends: Date,
+}
+
+fn announcement_from_row(row: &Row) -> Result<Announcement, DbError> {
+    let role_strs: Vec<String> = row.try_get("roles")?;
+    let roles = role_strs
+        .iter()
+        .map(|s| s.parse::<Role>())
+        .collect::<Result<Vec<Role>, String>>()
+        .map_err(DbError)?;
+
+    Ok(Announcement {
+        id: row.try_get("id")?,
+        message: row.try_get("message")?,
+        roles,
+        starts: row.try_get("starts")?,
+        ends: row.try_get("ends")?,
+    })
+}
+
+impl Store {
+    /// Insert a new announcement and return its id.
+    pub async fn insert_announcement(&self, a: &Announcement) -> Result<i64, DbError> {
+        log::trace!("Store::insert_announcement( {:?} ) called.", a);
+
+        let roles: Vec<String> = a.roles.iter().map(|r| r.to_string()).collect();
+
+        let client = self.connect().await?;
+        let row = client
+            .query_one(
+                "INSERT INTO announcements (message, roles, starts, ends)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id",
+                &[&a.message, &roles, &a.starts, &a.ends],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error inserting announcement"))?;
+
+        Ok(row.try_get("id")?)
+    }
+
+    /// Return all announcements, most recently started first.
+    pub async fn get_announcements(&self) -> Result<Vec<Announcement>, DbError> {
+        log::trace!("Store::get_announcements() called.");
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT id, message, roles, starts, ends FROM announcements
+                ORDER BY starts DESC, id DESC",
+                &[],
+            )
+            .await?;
+
+        rows.iter().map(announcement_from_row).collect()
+    }
+
+    /// Return the announcements users with the given `role` should see on
+    /// `day`, oldest first.
+    pub async fn get_active_announcements(
+        &self,
+        role: Role,
+        day: &Date,
+    ) -> Result<Vec<Announcement>, DbError> {
+        log::trace!(
+            "Store::get_active_announcements( {:?}, {} ) called.",
+            &role,
+            day
+        );
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT id, message, roles, starts, ends FROM announcements
+                WHERE starts <= $1 AND ends >= $1 AND $2 = ANY(roles)
+                ORDER BY starts, id",
+                &[day, &role.to_string()],
+            )
+            .await?;
+
+        rows.iter().map(announcement_from_row).collect()
+    }
+
+    /**
+    Stop showing the announcement with the given `id`, by ending it the day
+    before `day`.
+
+    Returns whether there was an announcement to expire (one that hadn't
+    already ended).
+    */
+    pub async fn expire_announcement(&self, id: i64, day: &Date) -> Result<bool, DbError> {
+        log::trace!("Store::expire_announcement( {}, {} ) called.", id, day);
+
+        let yesterday = day.saturating_sub(Duration::days(1));
+
+        let client = self.connect().await?;
+        let n = client
+            .execute(
+                "UPDATE announcements SET ends = $2 WHERE id = $1 AND ends >= $3",
+                &[&id, &yesterday, day],
+            )
+            .await?;
+
+        Ok(n > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+    use time::macros::date;
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn announcements() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+
+        let mut both = Announcement {
+            id: 0,
+            message: "No school Monday.".to_owned(),
+            roles: vec![Role::Teacher, Role::Student],
+            starts: date!(2022 - 10 - 3),
+            ends: date!(2022 - 10 - 10),
+        };
+        let mut teachers = Announcement {
+            id: 0,
+            message: "Reports due Friday.".to_owned(),
+            roles: vec![Role::Teacher],
+            starts: date!(2022 - 10 - 5),
+            ends: date!(2022 - 10 - 14),
+        };
+        both.id = db.insert_announcement(&both).await.unwrap();
+        teachers.id = db.insert_announcement(&teachers).await.unwrap();
+
+        let all = db.get_announcements().await.unwrap();
+        assert_eq!(all, vec![teachers.clone(), both.clone()]);
+
+        let day = date!(2022 - 10 - 6);
+        assert_eq!(
+            db.get_active_announcements(Role::Teacher, &day)
+                .await
+                .unwrap(),
+            vec![both.clone(), teachers.clone()]
+        );
+        assert_eq!(
+            db.get_active_announcements(Role::Student, &day)
+                .await
+                .unwrap(),
+            vec![both.clone()]
+        );
+        assert!(db
+            .get_active_announcements(Role::Student, &date!(2022 - 10 - 11))
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert!(db.expire_announcement(both.id, &day).await.unwrap());
+        assert!(!db.expire_announcement(both.id, &day).await.unwrap());
+        assert_eq!(
+            db.get_active_announcements(Role::Teacher, &day)
+                .await
+                .unwrap(),
+            vec![teachers.clone()]
+        );
+
+        db.nuke_database().await.unwrap();
+    }
+}
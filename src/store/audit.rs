@@ -0,0 +1,194 @@
+/*!
+`Store` methods for the audit log: a record of who changed users, goals,
+and final reports, and when.
+
+```sql
+CREATE TABLE audit (
+    id      BIGSERIAL PRIMARY KEY,
+    at      TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    actor   TEXT NOT NULL,  /* uname of whoever did it */
+    action  TEXT NOT NULL,  /* like 'update-user' or 'finalize-report' */
+    target  TEXT NOT NULL,  /* uname (or goal id) acted on */
+    detail  TEXT
+);
+```
+
+Unlike the other tables, this one isn't cleared at the end of the year,
+and there's no foreign key on `actor` or `target`, so entries outlive the
+users they mention.
+*/
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+
+use super::{DbError, Store};
+
+/// How many entries [`Store::get_audit_log`] returns if not told otherwise.
+const DEFAULT_AUDIT_LIMIT: i64 = 200;
+
+/// A single entry in the audit log.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// When it happened (UTC), as `YYYY-MM-DD HH:MM:SS`.
+    pub at: String,
+    pub actor: String,
+    pub action: String,
+    pub target: String,
+    pub detail: Option<String>,
+}
+
+/// Which entries of the audit log to fetch; `None` fields match anything.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    /// Only entries whose action is one of these.
+    pub actions: Option<Vec<String>>,
+    pub target: Option<String>,
+    /// Return at most this many entries (newest first).
+    pub limit: Option<i64>,
+    /// Skip this many of the newest matching entries first.
+    pub offset: Option<i64>,
+}
+
+fn entry_from_row(row: &Row) -> Result<AuditEntry, DbError> {
+    Ok(AuditEntry {
+        id: row.try_get("id")?,
+        at: row.try_get("at")?,
+        actor: row.try_get("actor")?,
+        action: row.try_get("action")?,
+        target: row.try_get("target")?,
+        detail: row.try_get("detail")?,
+    })
+}
+
+impl Store {
+    /// Record that `actor` did `action` to `target`.
+    pub async fn log_action(
+        &self,
+        actor: &str,
+        action: &str,
+        target: &str,
+        detail: Option<&str>,
+    ) -> Result<(), DbError> {
+        log::trace!(
+            "Store::log_action( {:?}, {:?}, {:?}, {:?} ) called.",
+            actor,
+            action,
+            target,
+            &detail
+        );
+
+        let client = self.connect().await?;
+        client
+            .execute(
+                "INSERT INTO audit (actor, action, target, detail)
+                VALUES ($1, $2, $3, $4)",
+                &[&actor, &action, &target, &detail],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error writing to audit log"))?;
+
+        Ok(())
+    }
+
+    /// Return the most recent entries of the audit log that match `filter`,
+    /// newest first.
+    pub async fn get_audit_log(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, DbError> {
+        log::trace!("Store::get_audit_log( {:?} ) called.", filter);
+
+        let limit = filter.limit.unwrap_or(DEFAULT_AUDIT_LIMIT).max(0);
+        let offset = filter.offset.unwrap_or(0).max(0);
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT id, to_char(at, 'YYYY-MM-DD HH24:MI:SS') AS at,
+                    actor, action, target, detail
+                FROM audit
+                WHERE ($1::TEXT IS NULL OR actor = $1)
+                    AND ($2::TEXT IS NULL OR action = $2)
+                    AND ($3::TEXT IS NULL OR target = $3)
+                    AND ($4::TEXT[] IS NULL OR action = ANY($4))
+                ORDER BY id DESC LIMIT $5 OFFSET $6",
+                &[
+                    &filter.actor,
+                    &filter.action,
+                    &filter.target,
+                    &filter.actions,
+                    &limit,
+                    &offset,
+                ],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error reading audit log"))?;
+
+        rows.iter().map(entry_from_row).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn audit_log() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        db.nuke_database().await.unwrap();
+        db.ensure_db_schema().await.unwrap();
+
+        db.log_action("root", "insert-user", "berro", None).await.unwrap();
+        db.log_action("berro", "add-goal", "frog", Some("pha 3"))
+            .await
+            .unwrap();
+        db.log_action("berro", "finalize-report", "frog", Some("fall"))
+            .await
+            .unwrap();
+
+        let all = db.get_audit_log(&AuditFilter::default()).await.unwrap();
+        let actions: Vec<&str> = all.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, ["finalize-report", "add-goal", "insert-user"]);
+        assert_eq!(all[1].detail.as_deref(), Some("pha 3"));
+
+        let filter = AuditFilter {
+            actor: Some("berro".to_owned()),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let some = db.get_audit_log(&filter).await.unwrap();
+        assert_eq!(some.len(), 1);
+        assert_eq!(&some[0].action, "finalize-report");
+
+        let filter = AuditFilter {
+            target: Some("berro".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(db.get_audit_log(&filter).await.unwrap()[0].actor, "root");
+
+        let filter = AuditFilter {
+            target: Some("frog".to_owned()),
+            actions: Some(vec!["add-goal".to_owned(), "finalize-report".to_owned()]),
+            limit: Some(1),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let page = db.get_audit_log(&filter).await.unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(&page[0].action, "add-goal");
+        let filter = AuditFilter {
+            actions: Some(vec!["delete-user".to_owned()]),
+            ..Default::default()
+        };
+        assert!(db.get_audit_log(&filter).await.unwrap().is_empty());
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -0,0 +1,428 @@
+/*!
+The parts of the data DB the [`Glob`](crate::config::Glob) needs to build
+students' [`Pace`](crate::pace::Pace)s, behind a trait, so the code that
+uses them can run against something other than Postgres.
+
+The [`Glob`](crate::config::Glob) keeps its [`Store`] in a `RwLock`, and
+it's the `RwLock<Store>` that implements [`DataStore`]; the `Glob` hands out
+the same `Store` through both
+[`Glob::data`](crate::config::Glob::data) and
+[`Glob::store`](crate::config::Glob::store). [`MemStore`] is a (very)
+simple in-memory implementation for testing.
+
+This covers loading the `Glob`'s users, courses (and custom chapters),
+calendar (and exam windows), and academic years, reading and writing `Goal`s and `Waiver`s, and
+reading students' `Absence`s. Everything else
+still goes through `Glob::data` to the `Store` itself; methods should move
+into the trait as the handlers that use them get tests.
+*/
+use std::{collections::HashMap, future::Future, pin::Pin};
+
+use time::Date;
+use tokio::sync::RwLock;
+
+use super::{AcademicYear, DbError, Store};
+use crate::{
+    course::{Course, Custom},
+    pace::{Absence, CustomCh, ExamWindow, Goal, Waiver},
+    user::User,
+};
+
+/// Boxed future returned by [`DataStore`] methods.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, DbError>> + Send + 'a>>;
+
+/// Somewhere to keep users, courses, the calendar, and students' goals.
+///
+/// Each method does what the [`Store`] method of the same name does.
+pub trait DataStore: Send + Sync {
+    fn get_users(&self) -> StoreFuture<'_, HashMap<String, User>>;
+    fn get_courses(&self) -> StoreFuture<'_, HashMap<i64, Course>>;
+    fn get_custom_chapters(&self) -> StoreFuture<'_, HashMap<i64, Custom>>;
+    fn get_calendar(&self) -> StoreFuture<'_, Vec<Date>>;
+    fn get_exam_windows(&self) -> StoreFuture<'_, Vec<ExamWindow>>;
+    fn get_dates(&self) -> StoreFuture<'_, HashMap<String, Date>>;
+    fn get_academic_years(&self) -> StoreFuture<'_, Vec<AcademicYear>>;
+
+    fn get_goals_by_student<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, Vec<Goal>>;
+    fn get_pace_bundle<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, (Vec<Goal>, Vec<Waiver>)>;
+    fn get_goals_by_teacher<'a>(&'a self, tuname: &'a str) -> StoreFuture<'a, Vec<Goal>>;
+    fn insert_goals<'a>(&'a self, goals: &'a [Goal]) -> StoreFuture<'a, usize>;
+    fn insert_one_goal<'a>(&'a self, g: &'a Goal) -> StoreFuture<'a, ()>;
+    fn update_goal<'a>(&'a self, g: &'a Goal) -> StoreFuture<'a, ()>;
+    fn update_due_dates<'a>(&'a self, goals: &'a [Goal]) -> StoreFuture<'a, usize>;
+
+    fn get_waivers_by_student<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, Vec<Waiver>>;
+    fn get_waivers_by_teacher<'a>(
+        &'a self,
+        tuname: &'a str,
+    ) -> StoreFuture<'a, HashMap<String, Vec<Waiver>>>;
+
+    fn get_absences_by_student<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, Vec<Absence>>;
+}
+
+impl DataStore for RwLock<Store> {
+    fn get_users(&self) -> StoreFuture<'_, HashMap<String, User>> {
+        Box::pin(async move { self.read().await.get_users().await })
+    }
+
+    fn get_courses(&self) -> StoreFuture<'_, HashMap<i64, Course>> {
+        Box::pin(async move { self.read().await.get_courses().await })
+    }
+
+    fn get_custom_chapters(&self) -> StoreFuture<'_, HashMap<i64, Custom>> {
+        Box::pin(async move { self.read().await.get_custom_chapters().await })
+    }
+
+    fn get_calendar(&self) -> StoreFuture<'_, Vec<Date>> {
+        Box::pin(async move { self.read().await.get_calendar().await })
+    }
+
+    fn get_exam_windows(&self) -> StoreFuture<'_, Vec<ExamWindow>> {
+        Box::pin(async move { self.read().await.get_exam_windows().await })
+    }
+
+    fn get_dates(&self) -> StoreFuture<'_, HashMap<String, Date>> {
+        Box::pin(async move { self.read().await.get_dates().await })
+    }
+
+    fn get_academic_years(&self) -> StoreFuture<'_, Vec<AcademicYear>> {
+        Box::pin(async move { self.read().await.get_academic_years().await })
+    }
+
+    fn get_goals_by_student<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, Vec<Goal>> {
+        Box::pin(async move { self.read().await.get_goals_by_student(uname).await })
+    }
+
+    fn get_pace_bundle<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, (Vec<Goal>, Vec<Waiver>)> {
+        Box::pin(async move { self.read().await.get_pace_bundle(uname).await })
+    }
+
+    fn get_goals_by_teacher<'a>(&'a self, tuname: &'a str) -> StoreFuture<'a, Vec<Goal>> {
+        Box::pin(async move { self.read().await.get_goals_by_teacher(tuname).await })
+    }
+
+    fn insert_goals<'a>(&'a self, goals: &'a [Goal]) -> StoreFuture<'a, usize> {
+        Box::pin(async move { self.read().await.insert_goals(goals).await })
+    }
+
+    fn insert_one_goal<'a>(&'a self, g: &'a Goal) -> StoreFuture<'a, ()> {
+        Box::pin(async move { self.read().await.insert_one_goal(g).await })
+    }
+
+    fn update_goal<'a>(&'a self, g: &'a Goal) -> StoreFuture<'a, ()> {
+        Box::pin(async move { self.read().await.update_goal(g).await })
+    }
+
+    fn update_due_dates<'a>(&'a self, goals: &'a [Goal]) -> StoreFuture<'a, usize> {
+        Box::pin(async move { self.read().await.update_due_dates(goals).await })
+    }
+
+    fn get_waivers_by_student<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, Vec<Waiver>> {
+        Box::pin(async move { self.read().await.get_waivers_by_student(uname).await })
+    }
+
+    fn get_waivers_by_teacher<'a>(
+        &'a self,
+        tuname: &'a str,
+    ) -> StoreFuture<'a, HashMap<String, Vec<Waiver>>> {
+        Box::pin(async move { self.read().await.get_waivers_by_teacher(tuname).await })
+    }
+
+    fn get_absences_by_student<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, Vec<Absence>> {
+        Box::pin(async move { self.read().await.get_absences_by_student(uname).await })
+    }
+}
+
+#[derive(Default)]
+struct MemData {
+    users: HashMap<String, User>,
+    courses: HashMap<i64, Course>,
+    customs: HashMap<i64, Custom>,
+    calendar: Vec<Date>,
+    exam_windows: Vec<ExamWindow>,
+    dates: HashMap<String, Date>,
+    years: Vec<AcademicYear>,
+    /// In order of `id`, like they'd come out of the database.
+    goals: Vec<Goal>,
+    waivers: HashMap<String, Vec<Waiver>>,
+    absences: HashMap<String, Vec<Absence>>,
+    next_goal_id: i64,
+}
+
+impl MemData {
+    fn insert_goal(&mut self, g: &Goal) -> Result<(), DbError> {
+        if let crate::pace::Source::Custom(CustomCh(id)) = &g.source {
+            if !self.customs.contains_key(id) {
+                return Err(DbError(format!("No custom chapter with id {}.", id)));
+            }
+        }
+        self.next_goal_id += 1;
+        let mut g = g.clone();
+        g.id = self.next_goal_id;
+        self.goals.push(g);
+        Ok(())
+    }
+
+    fn teacher_of(&self, uname: &str) -> Option<&str> {
+        match self.users.get(uname) {
+            Some(User::Student(s)) => Some(&s.teacher),
+            _ => None,
+        }
+    }
+}
+
+/**
+A [`DataStore`] that keeps everything in memory, for testing code that
+needs a [`Glob`](crate::config::Glob) without needing a database.
+
+Build one up with the `with_xxx()` methods. Like the database, it assigns
+`Course`s, custom chapters, and `Goal`s their `id`s as they're added.
+*/
+#[derive(Default)]
+pub struct MemStore {
+    data: std::sync::Mutex<MemData>,
+}
+
+impl MemStore {
+    pub fn new() -> MemStore {
+        MemStore::default()
+    }
+
+    pub fn with_users<I: IntoIterator<Item = User>>(self, users: I) -> Self {
+        {
+            let mut data = self.data.lock().unwrap();
+            for u in users {
+                data.users.insert(u.uname().to_owned(), u);
+            }
+        }
+        self
+    }
+
+    pub fn with_courses<I: IntoIterator<Item = Course>>(self, courses: I) -> Self {
+        {
+            let mut data = self.data.lock().unwrap();
+            for crs in courses {
+                let id = data.courses.len() as i64 + 1;
+                let chapters: Vec<_> = crs
+                    .all_chapters()
+                    .map(|ch| {
+                        let mut ch = ch.clone();
+                        ch.course_id = id;
+                        ch
+                    })
+                    .collect();
+                let mut crs = crs.with_chapters(chapters);
+                crs.id = id;
+                data.courses.insert(id, crs);
+            }
+        }
+        self
+    }
+
+    pub fn with_custom_chapters<I: IntoIterator<Item = Custom>>(self, customs: I) -> Self {
+        {
+            let mut data = self.data.lock().unwrap();
+            for mut cus in customs {
+                cus.id = data.customs.len() as i64 + 1;
+                data.customs.insert(cus.id, cus);
+            }
+        }
+        self
+    }
+
+    pub fn with_calendar(self, days: Vec<Date>) -> Self {
+        self.data.lock().unwrap().calendar = days;
+        self
+    }
+
+    pub fn with_exam_windows(self, windows: Vec<ExamWindow>) -> Self {
+        self.data.lock().unwrap().exam_windows = windows;
+        self
+    }
+
+    pub fn with_academic_years(self, years: Vec<AcademicYear>) -> Self {
+        self.data.lock().unwrap().years = years;
+        self
+    }
+
+    pub fn with_goals(self, goals: &[Goal]) -> Result<Self, DbError> {
+        {
+            let mut data = self.data.lock().unwrap();
+            for g in goals.iter() {
+                data.insert_goal(g)?;
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn with_waivers(self, uname: &str, waivers: Vec<Waiver>) -> Self {
+        self.data
+            .lock()
+            .unwrap()
+            .waivers
+            .insert(uname.to_owned(), waivers);
+        self
+    }
+
+    pub fn with_absences(self, uname: &str, absences: Vec<Absence>) -> Self {
+        self.data
+            .lock()
+            .unwrap()
+            .absences
+            .insert(uname.to_owned(), absences);
+        self
+    }
+}
+
+impl DataStore for MemStore {
+    fn get_users(&self) -> StoreFuture<'_, HashMap<String, User>> {
+        let users = self.data.lock().unwrap().users.clone();
+        Box::pin(std::future::ready(Ok(users)))
+    }
+
+    fn get_courses(&self) -> StoreFuture<'_, HashMap<i64, Course>> {
+        let courses = self.data.lock().unwrap().courses.clone();
+        Box::pin(std::future::ready(Ok(courses)))
+    }
+
+    fn get_custom_chapters(&self) -> StoreFuture<'_, HashMap<i64, Custom>> {
+        let customs = self.data.lock().unwrap().customs.clone();
+        Box::pin(std::future::ready(Ok(customs)))
+    }
+
+    fn get_calendar(&self) -> StoreFuture<'_, Vec<Date>> {
+        let days = self.data.lock().unwrap().calendar.clone();
+        Box::pin(std::future::ready(Ok(days)))
+    }
+
+    fn get_exam_windows(&self) -> StoreFuture<'_, Vec<ExamWindow>> {
+        let windows = self.data.lock().unwrap().exam_windows.clone();
+        Box::pin(std::future::ready(Ok(windows)))
+    }
+
+    fn get_dates(&self) -> StoreFuture<'_, HashMap<String, Date>> {
+        let dates = self.data.lock().unwrap().dates.clone();
+        Box::pin(std::future::ready(Ok(dates)))
+    }
+
+    fn get_academic_years(&self) -> StoreFuture<'_, Vec<AcademicYear>> {
+        let years = self.data.lock().unwrap().years.clone();
+        Box::pin(std::future::ready(Ok(years)))
+    }
+
+    fn get_goals_by_student<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, Vec<Goal>> {
+        let goals: Vec<Goal> = self
+            .data
+            .lock()
+            .unwrap()
+            .goals
+            .iter()
+            .filter(|g| g.uname == uname)
+            .cloned()
+            .collect();
+        Box::pin(std::future::ready(Ok(goals)))
+    }
+
+    fn get_pace_bundle<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, (Vec<Goal>, Vec<Waiver>)> {
+        let data = self.data.lock().unwrap();
+        let goals: Vec<Goal> = data
+            .goals
+            .iter()
+            .filter(|g| g.uname == uname)
+            .cloned()
+            .collect();
+        let waivers = data.waivers.get(uname).cloned().unwrap_or_default();
+        Box::pin(std::future::ready(Ok((goals, waivers))))
+    }
+
+    fn get_goals_by_teacher<'a>(&'a self, tuname: &'a str) -> StoreFuture<'a, Vec<Goal>> {
+        let data = self.data.lock().unwrap();
+        let goals: Vec<Goal> = data
+            .goals
+            .iter()
+            .filter(|g| data.teacher_of(&g.uname) == Some(tuname))
+            .cloned()
+            .collect();
+        Box::pin(std::future::ready(Ok(goals)))
+    }
+
+    fn insert_goals<'a>(&'a self, goals: &'a [Goal]) -> StoreFuture<'a, usize> {
+        let mut data = self.data.lock().unwrap();
+        let res = goals
+            .iter()
+            .try_for_each(|g| data.insert_goal(g))
+            .map(|_| goals.len());
+        Box::pin(std::future::ready(res))
+    }
+
+    fn insert_one_goal<'a>(&'a self, g: &'a Goal) -> StoreFuture<'a, ()> {
+        let res = self.data.lock().unwrap().insert_goal(g);
+        Box::pin(std::future::ready(res))
+    }
+
+    fn update_goal<'a>(&'a self, g: &'a Goal) -> StoreFuture<'a, ()> {
+        let mut data = self.data.lock().unwrap();
+        // Only the columns `Store::update_goal()` writes get changed.
+        if let Some(old) = data.goals.iter_mut().find(|old| old.id == g.id) {
+            old.source = g.source.clone();
+            old.review = g.review;
+            old.incomplete = g.incomplete;
+            old.due = g.due;
+            old.done = g.done;
+            old.tries = g.tries;
+            old.score = g.score.clone();
+            old.rubric = g.rubric.clone();
+        }
+        Box::pin(std::future::ready(Ok(())))
+    }
+
+    fn update_due_dates<'a>(&'a self, goals: &'a [Goal]) -> StoreFuture<'a, usize> {
+        let mut data = self.data.lock().unwrap();
+        let mut n_changed: usize = 0;
+        for g in goals.iter() {
+            if let Some(old) = data.goals.iter_mut().find(|old| old.id == g.id) {
+                old.due = g.due;
+                n_changed += 1;
+            }
+        }
+        Box::pin(std::future::ready(Ok(n_changed)))
+    }
+
+    fn get_waivers_by_student<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, Vec<Waiver>> {
+        let waivers = self
+            .data
+            .lock()
+            .unwrap()
+            .waivers
+            .get(uname)
+            .cloned()
+            .unwrap_or_default();
+        Box::pin(std::future::ready(Ok(waivers)))
+    }
+
+    fn get_waivers_by_teacher<'a>(
+        &'a self,
+        tuname: &'a str,
+    ) -> StoreFuture<'a, HashMap<String, Vec<Waiver>>> {
+        let data = self.data.lock().unwrap();
+        let waivers: HashMap<String, Vec<Waiver>> = data
+            .waivers
+            .iter()
+            .filter(|(uname, _)| data.teacher_of(uname) == Some(tuname))
+            .map(|(uname, v)| (uname.clone(), v.clone()))
+            .collect();
+        Box::pin(std::future::ready(Ok(waivers)))
+    }
+
+    fn get_absences_by_student<'a>(&'a self, uname: &'a str) -> StoreFuture<'a, Vec<Absence>> {
+        let absences = self
+            .data
+            .lock()
+            .unwrap()
+            .absences
+            .get(uname)
+            .cloned()
+            .unwrap_or_default();
+        Box::pin(std::future::ready(Ok(absences)))
+    }
+}
@@ -0,0 +1,245 @@
+/*!
+Connections that hang around long enough to reuse their prepared statements.
+
+Most `Store` methods open a fresh connection, use it once, and throw it
+away, which means every statement they run gets parsed and planned from
+scratch every time. That's fine for most things, but a few queries (like
+fetching a Teacher's goals, or updating a single goal) get run constantly.
+
+Methods on those hot paths call [`Store::cached_connect`] instead of
+[`Store::connect`]. The [`CachedClient`] that returns keeps a map of the
+statements prepared on its connection (keyed by SQL text), and when it's
+dropped, the connection (and its statements) goes back into a small idle
+list for the next caller, instead of being closed.
+
+If the `Store` has a connection pool (see [`Store::set_pool_size`]), the
+pool does this instead: connections go back into the pool rather than the
+idle list, and statements are cached by the pool's connections.
+
+Because prepared statements can go stale if the tables they refer to are
+dropped and recreated, [`Store::ensure_db_schema`] and
+[`Store::nuke_database`] forget them all (see [`Store::forget_statements`]).
+*/
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio_postgres::{Client, Statement};
+
+use crate::pool::PgClient;
+
+use super::{DbError, Store};
+
+/// The most connections to keep lying around idle.
+const MAX_IDLE: usize = 4;
+
+/// A connection, and the statements that have been prepared on it.
+struct Conn {
+    client: PgClient,
+    statements: HashMap<String, Statement>,
+}
+
+/// Idle connections waiting to be reused.
+#[derive(Clone, Default)]
+pub(super) struct IdleConns(Arc<Mutex<Vec<Conn>>>);
+
+impl IdleConns {
+    fn pop(&self) -> Option<Conn> {
+        let mut idle = self.0.lock().unwrap();
+        while let Some(conn) = idle.pop() {
+            if !conn.client.is_closed() {
+                return Some(conn);
+            }
+        }
+        None
+    }
+
+    fn push(&self, conn: Conn) {
+        // Pooled connections go back to the pool (when dropped).
+        if let PgClient::Pooled(_) = conn.client {
+            return;
+        }
+        let mut idle = self.0.lock().unwrap();
+        if idle.len() < MAX_IDLE && !conn.client.is_closed() {
+            idle.push(conn);
+        }
+    }
+
+    /// Close all the idle connections (and so forget their statements).
+    pub(super) fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+
+    /// Number of idle connections.
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+}
+
+/**
+A connection checked out from a [`Store`]'s idle list (or freshly opened),
+that remembers the statements prepared on it. It goes back on the idle list
+when dropped.
+*/
+pub struct CachedClient {
+    conn: Option<Conn>,
+    idle: IdleConns,
+}
+
+impl CachedClient {
+    /**
+    Return a prepared statement for `sql`, preparing it only if it hasn't
+    already been prepared on this connection.
+
+    The returned [`Statement`] can be used with any
+    [`Transaction`](tokio_postgres::Transaction) started from
+    [`CachedClient::client`].
+    */
+    pub async fn prepare(&mut self, sql: &str) -> Result<Statement, DbError> {
+        let conn = self.conn.as_mut().unwrap();
+        if let PgClient::Pooled(obj) = &conn.client {
+            return obj
+                .prepare_cached(sql)
+                .await
+                .map_err(|e| DbError::from(e).annotate("Error preparing statement"));
+        }
+        if let Some(stmt) = conn.statements.get(sql) {
+            return Ok(stmt.clone());
+        }
+
+        log::trace!("Preparing statement: {:?}", sql);
+        let stmt = conn
+            .client
+            .prepare(sql)
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error preparing statement"))?;
+        conn.statements.insert(sql.to_owned(), stmt.clone());
+        Ok(stmt)
+    }
+
+    /// The underlying connection, for starting transactions.
+    pub fn client(&mut self) -> &mut Client {
+        &mut self.conn.as_mut().unwrap().client
+    }
+}
+
+impl Drop for CachedClient {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.idle.push(conn);
+        }
+    }
+}
+
+impl Store {
+    /// Forget all prepared statements: close the idle connections, and clear
+    /// the pool's statement caches.
+    pub(super) fn forget_statements(&self) {
+        self.idle.clear();
+        if let Some(pool) = &self.pool {
+            pool.manager().statement_caches.clear();
+        }
+    }
+
+    /// Return a connection from the idle list (or a new one, if there are
+    /// none), which caches its prepared statements.
+    pub async fn cached_connect(&self) -> Result<CachedClient, DbError> {
+        let conn = match self.idle.pop() {
+            Some(conn) => conn,
+            None => Conn {
+                client: self.connect().await?,
+                statements: HashMap::new(),
+            },
+        };
+
+        Ok(CachedClient {
+            conn: Some(conn),
+            idle: self.idle.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn statement_cache() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        assert_eq!(db.idle.len(), 0);
+
+        let sql = "SELECT COUNT(*) FROM students";
+        {
+            let mut client = db.cached_connect().await.unwrap();
+            let stmt = client.prepare(sql).await.unwrap();
+            client.prepare(sql).await.unwrap();
+            // The second call should reuse the first statement.
+            assert_eq!(client.conn.as_ref().unwrap().statements.len(), 1);
+            client.client().query_one(&stmt, &[]).await.unwrap();
+        }
+        assert_eq!(db.idle.len(), 1);
+
+        {
+            let mut client = db.cached_connect().await.unwrap();
+            assert_eq!(db.idle.len(), 0);
+            assert!(client.conn.as_ref().unwrap().statements.contains_key(sql));
+
+            // Statements prepared on the client work in its transactions.
+            let stmt = client.prepare(sql).await.unwrap();
+            let t = client.client().transaction().await.unwrap();
+            t.query_one(&stmt, &[]).await.unwrap();
+            t.commit().await.unwrap();
+
+            let _other = db.cached_connect().await.unwrap();
+        }
+        assert_eq!(db.idle.len(), 2);
+
+        db.nuke_database().await.unwrap();
+        assert_eq!(db.idle.len(), 0);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn pooled_connections() {
+        ensure_logging();
+
+        let mut db = Store::new(TEST_CONNECTION.to_owned());
+        db.set_pool_size(2).unwrap();
+        db.ensure_db_schema().await.unwrap();
+        let pool = db.pool.clone().unwrap();
+
+        let sql = "SELECT COUNT(*) FROM students";
+        {
+            let mut client = db.cached_connect().await.unwrap();
+            let stmt = client.prepare(sql).await.unwrap();
+            let t = client.client().transaction().await.unwrap();
+            t.query_one(&stmt, &[]).await.unwrap();
+            t.commit().await.unwrap();
+        }
+        // The connection went back to the pool, not the idle list.
+        assert_eq!(db.idle.len(), 0);
+        assert_eq!(pool.status().size, 1);
+        assert_eq!(pool.status().available, 1);
+
+        {
+            let _a = db.connect().await.unwrap();
+            let _b = db.connect().await.unwrap();
+            assert_eq!(pool.status().size, 2);
+            assert_eq!(pool.status().available, 0);
+        }
+        assert_eq!(pool.status().available, 2);
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -15,14 +15,37 @@ CREATE TABLE dates (
     day DATE NOT NULL
 );
 ```
+
+```sql
+CREATE TABLE exam_windows (
+    id          BIGSERIAL PRIMARY KEY,
+    name        TEXT NOT NULL,
+    start_date  DATE NOT NULL,
+    end_date    DATE NOT NULL,
+    CHECK (start_date <= end_date)
+);
+```
 */
 use std::collections::HashMap;
 
 use futures::stream::{FuturesUnordered, StreamExt};
 use time::Date;
-use tokio_postgres::types::{ToSql, Type};
+use tokio_postgres::{
+    types::{ToSql, Type},
+    Row,
+};
 
 use super::{DbError, Store};
+use crate::pace::ExamWindow;
+
+fn exam_window_from_row(row: &Row) -> Result<ExamWindow, DbError> {
+    Ok(ExamWindow {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        start: row.try_get("start_date")?,
+        end: row.try_get("end_date")?,
+    })
+}
 
 impl Store {
     /// Store this collection of dates as making up the "working days" of the
@@ -74,6 +97,28 @@ impl Store {
         Ok((n_deleted as usize, n_inserted as usize))
     }
 
+    /// Add this collection of dates to the "working days" of the current
+    /// academic year, leaving the days already there alone. Returns the
+    /// number of days that weren't there already.
+    pub async fn add_to_calendar(&self, dates: &[Date]) -> Result<usize, DbError> {
+        log::trace!("Store::add_to_calendar( {:?} ) called.", &dates);
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        let n_inserted = t
+            .execute(
+                "INSERT INTO calendar (day) SELECT UNNEST($1::DATE[])
+                ON CONFLICT (day) DO NOTHING",
+                &[&dates],
+            )
+            .await
+            .map_err(|e| format!("Error adding dates to calendar: {}", &e))?;
+
+        t.commit().await?;
+        Ok(n_inserted as usize)
+    }
+
     /// Retrieve the collection of "working dates" from the current academic
     /// year as stored. They should be in chronological order.
     pub async fn get_calendar(&self) -> Result<Vec<Date>, DbError> {
@@ -174,13 +219,72 @@ impl Store {
 
         Ok(map)
     }
+
+    /// Return all the [`ExamWindow`]s, in order.
+    pub async fn get_exam_windows(&self) -> Result<Vec<ExamWindow>, DbError> {
+        log::trace!("Store::get_exam_windows() called.");
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT id, name, start_date, end_date FROM exam_windows
+                ORDER BY start_date, end_date",
+                &[],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error fetching exam windows"))?;
+
+        rows.iter().map(exam_window_from_row).collect()
+    }
+
+    /// Save a new [`ExamWindow`] (its `id` is ignored), and return it as
+    /// saved.
+    pub async fn insert_exam_window(&self, window: &ExamWindow) -> Result<ExamWindow, DbError> {
+        log::trace!("Store::insert_exam_window( {:?} ) called.", window);
+
+        window.check().map_err(DbError)?;
+
+        let client = self.connect().await?;
+        let row = client
+            .query_one(
+                "INSERT INTO exam_windows (name, start_date, end_date)
+                VALUES ($1, $2, $3)
+                RETURNING id, name, start_date, end_date",
+                &[&window.name.trim(), &window.start, &window.end],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error saving exam window"))?;
+
+        exam_window_from_row(&row)
+    }
+
+    /// Delete the [`ExamWindow`] with the given `id`; returns whether there
+    /// was one.
+    pub async fn delete_exam_window(&self, id: i64) -> Result<bool, DbError> {
+        log::trace!("Store::delete_exam_window( {} ) called.", &id);
+
+        let client = self.connect().await?;
+        let n = client
+            .execute("DELETE FROM exam_windows WHERE id = $1", &[&id])
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error deleting exam window"))?;
+
+        Ok(n > 0)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use time::{macros::format_description, Month};
+    use serial_test::serial;
+    use time::{
+        macros::{date, format_description},
+        Month,
+    };
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
 
     #[test]
     fn date_format() {
@@ -192,4 +296,61 @@ mod tests {
         let hween = Date::parse("2021-10-31", &dfmtr).unwrap();
         println!("{:?}, {}", &hween, &hween);
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn merged_calendars() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+
+        let first = [date!(2022 - 09 - 06), date!(2022 - 09 - 07)];
+        db.set_calendar(&first).await.unwrap();
+        let more = [date!(2022 - 09 - 07), date!(2022 - 09 - 08)];
+        assert_eq!(db.add_to_calendar(&more).await.unwrap(), 1);
+        assert_eq!(
+            db.get_calendar().await.unwrap(),
+            vec![
+                date!(2022 - 09 - 06),
+                date!(2022 - 09 - 07),
+                date!(2022 - 09 - 08)
+            ]
+        );
+
+        db.nuke_database().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn exam_windows() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+
+        let window = |name: &str, start: Date, end: Date| ExamWindow {
+            id: 0,
+            name: name.to_owned(),
+            start,
+            end,
+        };
+        let spring = window(" Spring Exams ", date!(2023 - 05 - 22), date!(2023 - 05 - 26));
+        let spring = db.insert_exam_window(&spring).await.unwrap();
+        assert_eq!(spring.name, "Spring Exams");
+        let fall = window("Fall Exams", date!(2022 - 12 - 12), date!(2022 - 12 - 16));
+        let fall = db.insert_exam_window(&fall).await.unwrap();
+        let backwards = window("Backwards", date!(2023 - 01 - 10), date!(2023 - 01 - 09));
+        assert!(db.insert_exam_window(&backwards).await.is_err());
+        assert_eq!(
+            db.get_exam_windows().await.unwrap(),
+            vec![fall.clone(), spring.clone()]
+        );
+
+        assert!(db.delete_exam_window(spring.id).await.unwrap());
+        assert!(!db.delete_exam_window(-1).await.unwrap());
+        assert_eq!(db.get_exam_windows().await.unwrap(), vec![fall]);
+
+        db.nuke_database().await.unwrap();
+    }
 }
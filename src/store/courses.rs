@@ -9,7 +9,9 @@ CREATE TABLE courses (
     sym   TEXT UNIQUE NOT NULL,
     book  TEXT,
     title TEXT NOT NULL,
-    level REAL
+    level REAL,
+    min_gap         SMALLINT,   /* NULL means no constraint */
+    max_per_week    SMALLINT    /* NULL means no constraint */
 );
 
 CREATE TABLE chapters (
@@ -29,13 +31,13 @@ CREATE TABLE custom_chapters (
 );
 ```
 */
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
 
 use tokio_postgres::{types::Type, Row, Transaction};
 
 use super::{DbError, Store};
-use crate::course::{Chapter, Course};
+use crate::course::{Chapter, Constraints, Course, Custom};
 
 fn chapter_from_row(row: &Row) -> Result<Chapter, DbError> {
     Ok(Chapter {
@@ -43,22 +45,33 @@ fn chapter_from_row(row: &Row) -> Result<Chapter, DbError> {
         course_id: row.try_get("course")?,
         seq: row.try_get("sequence")?,
         title: row.try_get("title")?,
-        subject: match row.try_get("subject") {
-            Ok(x) => Some(x),
-            Err(_) => None,
-        },
+        subject: row.try_get("subject").ok(),
         weight: row.try_get("weight")?,
     })
 }
 
 fn course_from_row(row: &Row) -> Result<Course, DbError> {
+    let constraints = Constraints {
+        min_gap: row.try_get("min_gap")?,
+        max_per_week: row.try_get("max_per_week")?,
+    };
     Ok(Course::new(
         row.try_get("id")?,
         row.try_get("sym")?,
         row.try_get("book")?,
         row.try_get("title")?,
         row.try_get("level")?,
-    ))
+    )
+    .with_constraints(constraints))
+}
+
+fn custom_from_row(row: &Row) -> Result<Custom, DbError> {
+    Ok(Custom {
+        id: row.try_get("id")?,
+        uname: row.try_get("uname")?,
+        title: row.try_get("title")?,
+        weight: row.try_get::<_, Option<f32>>("weight")?.unwrap_or(1.0),
+    })
 }
 
 impl Store {
@@ -103,10 +116,17 @@ impl Store {
 
         let insert_course_query = t
             .prepare_typed(
-                "INSERT INTO courses (sym, book, title, level)
-                VALUES ($1, $2, $3, $4)
+                "INSERT INTO courses (sym, book, title, level, min_gap, max_per_week)
+                VALUES ($1, $2, $3, $4, $5, $6)
                 RETURNING id",
-                &[Type::TEXT, Type::TEXT, Type::TEXT, Type::FLOAT4],
+                &[
+                    Type::TEXT,
+                    Type::TEXT,
+                    Type::TEXT,
+                    Type::FLOAT4,
+                    Type::INT2,
+                    Type::INT2,
+                ],
             )
             .await?;
         let insert_chapter_query = t
@@ -127,7 +147,14 @@ impl Store {
             let row = t
                 .query_one(
                     &insert_course_query,
-                    &[&crs.sym, &crs.book, &crs.title, &crs.level],
+                    &[
+                        &crs.sym,
+                        &crs.book,
+                        &crs.title,
+                        &crs.level,
+                        &crs.constraints.min_gap,
+                        &crs.constraints.max_per_week,
+                    ],
                 )
                 .await?;
             let id: i64 = row.try_get("id")?;
@@ -159,15 +186,45 @@ impl Store {
         client
             .execute(
                 "UPDATE courses SET
-                book = $1, title = $2, level = $3
-                WHERE sym = $4",
-                &[&c.book, &c.title, &c.level, &c.sym],
+                book = $1, title = $2, level = $3, min_gap = $4, max_per_week = $5
+                WHERE sym = $6",
+                &[
+                    &c.book,
+                    &c.title,
+                    &c.level,
+                    &c.constraints.min_gap,
+                    &c.constraints.max_per_week,
+                    &c.sym,
+                ],
             )
             .await?;
 
         Ok(())
     }
 
+    /// Set the levels of the courses with the given symbols, all at once.
+    /// Returns the number of courses changed.
+    pub async fn set_course_levels(&self, levels: &[(String, f32)]) -> Result<usize, DbError> {
+        log::trace!("Store::set_course_levels( {:?} ) called.", levels);
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+        let stmt = t
+            .prepare_typed(
+                "UPDATE courses SET level = $1 WHERE sym = $2",
+                &[Type::FLOAT4, Type::TEXT],
+            )
+            .await?;
+
+        let mut n: u64 = 0;
+        for (sym, level) in levels.iter() {
+            n += t.execute(&stmt, &[level, sym]).await?;
+        }
+        t.commit().await?;
+
+        Ok(n as usize)
+    }
+
     /// Insert the given collection of chapters into the database.
     pub async fn insert_chapters(&self, chapters: &[Chapter]) -> Result<usize, DbError> {
         log::trace!(
@@ -298,6 +355,147 @@ impl Store {
         Ok(())
     }
 
+    /**
+    Reorder the chapters of the course with the given `sym`bol.
+
+    `ids` must contain the id of every one of the course's chapters exactly
+    once, in the desired order. The course's existing sequence numbers are
+    handed out in that order (so the first id gets the lowest one, etc.),
+    and every [`Goal`](crate::pace::Goal) (as well as every waiver, test
+    request, and deleted goal) that refers to a moved chapter has its `seq`
+    changed to follow it. This all happens in a single transaction, so
+    either everything moves or nothing does.
+
+    Returns the number of goals whose `seq` was changed.
+    */
+    pub async fn reorder_chapters(&self, sym: &str, ids: &[i64]) -> Result<u64, DbError> {
+        log::trace!("Store::reorder_chapters( {:?}, {:?} ) called.", sym, ids);
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        let course_id: i64 = match t
+            .query_opt("SELECT id FROM courses WHERE sym = $1", &[&sym])
+            .await?
+        {
+            Some(row) => row.try_get("id")?,
+            None => {
+                return Err(DbError(format!("No course with symbol {:?}.", sym)));
+            }
+        };
+
+        let rows = t
+            .query(
+                "SELECT id, sequence FROM chapters WHERE course = $1",
+                &[&course_id],
+            )
+            .await?;
+        let mut current: HashMap<i64, i16> = HashMap::with_capacity(rows.len());
+        for row in rows.iter() {
+            current.insert(row.try_get("id")?, row.try_get("sequence")?);
+        }
+
+        if ids.len() != current.len() {
+            return Err(DbError(format!(
+                "Course {:?} has {} chapters, but {} were given.",
+                sym,
+                &current.len(),
+                &ids.len()
+            )));
+        }
+        let mut seen: HashSet<i64> = HashSet::with_capacity(ids.len());
+        for id in ids.iter() {
+            if !current.contains_key(id) {
+                return Err(DbError(format!(
+                    "Course {:?} has no chapter with id {}.",
+                    sym, id
+                )));
+            }
+            if !seen.insert(*id) {
+                return Err(DbError(format!("Chapter id {} given more than once.", id)));
+            }
+        }
+
+        let mut seqs: Vec<i16> = current.values().copied().collect();
+        seqs.sort_unstable();
+        if seqs.windows(2).any(|w| w[0] == w[1]) {
+            return Err(DbError(format!(
+                "Course {:?} has chapters with duplicate sequence numbers; \
+                these must be fixed before reordering.",
+                sym
+            )));
+        }
+
+        let mut moved_ids: Vec<i64> = Vec::new();
+        let mut old_seqs: Vec<i16> = Vec::new();
+        let mut new_seqs: Vec<i16> = Vec::new();
+        for (id, new_seq) in ids.iter().zip(seqs.iter()) {
+            let old_seq = current[id];
+            if old_seq != *new_seq {
+                moved_ids.push(*id);
+                old_seqs.push(old_seq);
+                new_seqs.push(*new_seq);
+            }
+        }
+
+        if moved_ids.is_empty() {
+            log::trace!("Chapter order of {:?} unchanged.", sym);
+            return Ok(0);
+        }
+
+        // Each of these updates is done in a single statement, so values
+        // being swapped between rows never collide with each other.
+        t.execute(
+            "UPDATE chapters SET sequence = m.new
+            FROM (SELECT UNNEST($1::BIGINT[]) AS id, UNNEST($2::SMALLINT[]) AS new) m
+            WHERE chapters.id = m.id",
+            &[&moved_ids, &new_seqs],
+        )
+        .await
+        .map_err(|e| DbError::from(e).annotate("Error updating chapter sequence numbers"))?;
+
+        // Waivers and test requests are unique by (uname, sym, seq), and
+        // Postgres checks that row by row, so those tables are remapped in
+        // two steps: first to (negative) placeholders that can't collide
+        // with anything, and then to the new sequence numbers.
+        let mut n_goals = 0;
+        for table in ["goals", "waivers", "test_requests", "deleted_goals"] {
+            let annotation = format!("Error remapping {} chapters", table);
+            let placeholders = format!(
+                "UPDATE {0} SET seq = -1 - m.new
+                FROM (SELECT UNNEST($2::SMALLINT[]) AS old, UNNEST($3::SMALLINT[]) AS new) m
+                WHERE {0}.sym = $1 AND {0}.seq = m.old",
+                table
+            );
+            let n = t
+                .execute(&placeholders, &[&sym, &old_seqs, &new_seqs])
+                .await
+                .map_err(|e| DbError::from(e).annotate(&annotation))?;
+            let finals = format!(
+                "UPDATE {} SET seq = -1 - seq WHERE sym = $1 AND seq < 0",
+                table
+            );
+            t.execute(&finals, &[&sym])
+                .await
+                .map_err(|e| DbError::from(e).annotate(&annotation))?;
+            if table == "goals" {
+                n_goals = n;
+            }
+        }
+
+        t.commit()
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error committing transaction"))?;
+
+        log::trace!(
+            "Moved {} chapters of {:?}; remapped {} goals.",
+            &moved_ids.len(),
+            sym,
+            &n_goals
+        );
+        Ok(n_goals)
+    }
+
     /// Retrieve the course with the given `sym`bol and wrap it up
     /// in a [`Course`] struct.
     pub async fn get_course_by_sym(&self, sym: &str) -> Result<Option<Course>, DbError> {
@@ -315,13 +513,7 @@ impl Store {
             Some(row) => row,
         };
 
-        let crs = Course::new(
-            row.try_get("id")?,
-            row.try_get("sym")?,
-            row.try_get("book")?,
-            row.try_get("title")?,
-            row.try_get("level")?,
-        );
+        let crs = course_from_row(&row)?;
 
         let rows = client
             .query(
@@ -378,6 +570,96 @@ impl Store {
 
         Ok(course_map)
     }
+
+    /// Return a HashMap of all custom chapters in the database.
+    pub async fn get_custom_chapters(&self) -> Result<HashMap<i64, Custom>, DbError> {
+        let client = self.connect().await?;
+        let rows = client.query("SELECT * FROM custom_chapters", &[]).await?;
+        let mut customs: HashMap<i64, Custom> = HashMap::with_capacity(rows.len());
+        for row in rows.iter() {
+            let cus = custom_from_row(row)?;
+            customs.insert(cus.id, cus);
+        }
+
+        Ok(customs)
+    }
+
+    /// Insert a new custom chapter created by the teacher `uname`, returning
+    /// it with its newly-assigned `id`.
+    pub async fn insert_custom_chapter(
+        &self,
+        uname: &str,
+        title: &str,
+        weight: f32,
+    ) -> Result<Custom, DbError> {
+        log::trace!(
+            "Store::insert_custom_chapter( {:?}, {:?}, {} ) called.",
+            uname,
+            title,
+            &weight
+        );
+
+        let client = self.connect().await?;
+        let row = client
+            .query_one(
+                "INSERT INTO custom_chapters (uname, title, weight)
+                VALUES ($1, $2, $3)
+                RETURNING *",
+                &[&uname, &title, &weight],
+            )
+            .await?;
+
+        custom_from_row(&row)
+    }
+
+    /// Update the `title` and `weight` of the custom chapter with the id of
+    /// `cus.id`. (Its creator can't be changed.)
+    pub async fn update_custom_chapter(&self, cus: &Custom) -> Result<(), DbError> {
+        log::trace!("Store::update_custom_chapter( {:?} ) called.", cus);
+
+        let client = self.connect().await?;
+        let n = client
+            .execute(
+                "UPDATE custom_chapters SET title = $1, weight = $2 WHERE id = $3",
+                &[&cus.title, &cus.weight, &cus.id],
+            )
+            .await?;
+        if n == 0 {
+            return Err(DbError(format!("No custom chapter with id {}.", &cus.id)));
+        }
+
+        Ok(())
+    }
+
+    /// Delete the custom chapter with the given `id`. This fails if any
+    /// [`Goal`](crate::pace::Goal)s still use it.
+    pub async fn delete_custom_chapter(&self, id: i64) -> Result<(), DbError> {
+        log::trace!("Store::delete_custom_chapter( {} ) called.", &id);
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        let row = t
+            .query_one("SELECT COUNT(*) AS n FROM goals WHERE custom = $1", &[&id])
+            .await?;
+        let n_goals: i64 = row.try_get("n")?;
+        if n_goals > 0 {
+            return Err(DbError(format!(
+                "Custom chapter {} is still used by {} goal(s).",
+                &id, &n_goals
+            )));
+        }
+
+        let n = t
+            .execute("DELETE FROM custom_chapters WHERE id = $1", &[&id])
+            .await?;
+        if n == 0 {
+            return Err(DbError(format!("No custom chapter with id {}.", &id)));
+        }
+
+        t.commit().await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -388,8 +670,10 @@ mod tests {
 
     use float_cmp::approx_eq;
     use serial_test::serial;
+    use time::macros::date;
 
-    use crate::store::tests::TEST_CONNECTION;
+    use crate::pace::{BookCh, Goal, Source, Waiver};
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
     use crate::tests::ensure_logging;
 
     fn same_chapters(a: &Chapter, b: &Chapter) -> bool {
@@ -399,10 +683,10 @@ mod tests {
         if !approx_eq!(f32, a.weight, b.weight) {
             return false;
         }
-        if &a.title != &b.title {
+        if a.title != b.title {
             return false;
         }
-        if &a.subject != &b.subject {
+        if a.subject != b.subject {
             return false;
         }
         true
@@ -412,13 +696,16 @@ mod tests {
         if !approx_eq!(f32, a.level, b.level) {
             return false;
         }
-        if &a.sym != &b.sym {
+        if a.sym != b.sym {
+            return false;
+        }
+        if a.title != b.title {
             return false;
         }
-        if &a.title != &b.title {
+        if a.book != b.book {
             return false;
         }
-        if &a.book != &b.book {
+        if a.constraints != b.constraints {
             return false;
         }
 
@@ -454,6 +741,111 @@ mod tests {
         assert!(same_courses(&course_vec[0], &new_cpc));
         assert!(!same_courses(&course_vec[1], &new_cpc));
 
+        let constraints = Constraints {
+            min_gap: Some(3),
+            max_per_week: None,
+        };
+        db.update_course(&new_cpc.with_constraints(constraints))
+            .await
+            .unwrap();
+        let new_cpc = db.get_course_by_sym("pc").await.unwrap().unwrap();
+        assert_eq!(new_cpc.constraints, constraints);
+
+        let levels = vec![("pc".to_owned(), 12.7), ("nope".to_owned(), 1.0)];
+        assert_eq!(db.set_course_levels(&levels).await.unwrap(), 1);
+        let new_cpc = db.get_course_by_sym("pc").await.unwrap().unwrap();
+        assert!(approx_eq!(f32, new_cpc.level, 12.7));
+
+        db.nuke_database().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn reorder_chapters() {
+        ensure_logging();
+
+        let cpc = Course::from_reader(File::open("test/good_course_0.mix").unwrap()).unwrap();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        db.insert_courses(&[cpc]).await.unwrap();
+
+        let before = db.get_course_by_sym("pc").await.unwrap().unwrap();
+        let seqs: Vec<i16> = before.all_chapters().map(|ch| ch.seq).collect();
+        let mut ids: Vec<i64> = before.all_chapters().map(|ch| ch.id).collect();
+        ids.reverse();
+
+        assert!(db.reorder_chapters("pc", &ids[1..]).await.is_err());
+        let mut dupes = ids.clone();
+        dupes[0] = dupes[1];
+        assert!(db.reorder_chapters("pc", &dupes).await.is_err());
+        assert!(db.reorder_chapters("nope", &ids).await.is_err());
+
+        insert_users(&db, &["berro"], &[("frog", "berro")]).await;
+        let goal = |seq: i16| Goal {
+            id: 0,
+            uname: "frog".to_owned(),
+            source: Source::Book(BookCh {
+                sym: "pc".to_owned(),
+                seq,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due: Some(date!(2022 - 10 - 3)),
+            done: None,
+            tries: None,
+            weight: 0.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        };
+        let waiver = |seq: i16| Waiver {
+            sym: "pc".to_owned(),
+            seq,
+        };
+        // Reversed, the first chapter gets the last sequence number, etc.
+        let n = seqs.len();
+        let (first, second, next_to_last, last) = (seqs[0], seqs[1], seqs[n - 2], seqs[n - 1]);
+        db.insert_goals(&[goal(first), goal(second)]).await.unwrap();
+        // The first and last trade places, so they'd collide if moved one
+        // at a time.
+        for seq in [first, second, last] {
+            db.set_waiver("frog", &waiver(seq), true, "berro").await.unwrap();
+        }
+
+        assert_eq!(db.reorder_chapters("pc", &ids).await.unwrap(), 2);
+
+        let mut goal_seqs: Vec<i16> = db
+            .get_goals_by_student("frog")
+            .await
+            .unwrap()
+            .iter()
+            .map(|g| match &g.source {
+                Source::Book(bch) => bch.seq,
+                _ => panic!("Not a book goal: {:?}", g),
+            })
+            .collect();
+        goal_seqs.sort_unstable();
+        assert_eq!(goal_seqs, [next_to_last, last]);
+        assert_eq!(
+            db.get_waivers_by_student("frog").await.unwrap(),
+            vec![waiver(first), waiver(next_to_last), waiver(last)]
+        );
+
+        let after = db.get_course_by_sym("pc").await.unwrap().unwrap();
+        let after_seqs: Vec<i16> = after.all_chapters().map(|ch| ch.seq).collect();
+        assert_eq!(seqs, after_seqs);
+        for (ch, id) in after.all_chapters().zip(ids.iter()) {
+            assert_eq!(ch.id, *id);
+            let old = before.all_chapters().find(|old| old.id == *id).unwrap();
+            assert_eq!(ch.title, old.title);
+        }
+
         db.nuke_database().await.unwrap();
     }
 
@@ -0,0 +1,170 @@
+/*!
+A queue of deferred work to be done in the background.
+
+Some operations (like uploading a large file of goals) invalidate cached
+aggregate data that is too slow to recompute before responding to the
+request. Instead, the handler [`schedule`](Queue::schedule)s the
+recomputation, and a single background task (started with [`Queue::run`])
+works through the queue, pausing between jobs so as not to monopolize the
+database. Scheduling a job that's already pending does nothing, so a burst
+of uploads touching the same course only recomputes it once.
+*/
+use std::{
+    collections::BTreeSet,
+    fmt::{Display, Formatter},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::sync::{Notify, RwLock};
+
+use super::{DbError, Store};
+
+/// How long the background task waits before starting each job.
+const THROTTLE: Duration = Duration::from_millis(500);
+
+/// A unit of deferred work.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Job {
+    /// Recompute the [`CourseStats`](crate::store::CourseStats) for the
+    /// course with the given `sym`.
+    CourseStats(String),
+}
+
+impl Job {
+    async fn run(&self, store: &Store) -> Result<(), DbError> {
+        match self {
+            Job::CourseStats(sym) => store.recompute_course_stats(sym).await,
+        }
+    }
+}
+
+impl Display for Job {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Job::CourseStats(sym) => write!(f, "course stats for {:?}", sym),
+        }
+    }
+}
+
+/// A snapshot of the state of the [`Queue`], for reporting to Admins.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Progress {
+    /// Number of jobs waiting to be run.
+    pub pending: usize,
+    /// Description of the job currently running (if any).
+    pub running: Option<String>,
+    pub completed: u64,
+    pub failed: u64,
+    /// The most recent error (if any jobs have failed).
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct State {
+    pending: BTreeSet<Job>,
+    progress: Progress,
+}
+
+#[derive(Default)]
+pub struct Queue {
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+impl Queue {
+    pub fn new() -> Queue {
+        Queue::default()
+    }
+
+    /// Add `jobs` to the queue (unless they're already pending).
+    pub fn schedule<I: IntoIterator<Item = Job>>(&self, jobs: I) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.pending.extend(jobs);
+            state.progress.pending = state.pending.len();
+        }
+        self.notify.notify_one();
+    }
+
+    /// Report the current state of the queue.
+    pub fn progress(&self) -> Progress {
+        self.state.lock().unwrap().progress.clone()
+    }
+
+    /// Remove the next job from the queue and mark it as running.
+    fn take_next(&self) -> Option<Job> {
+        let mut state = self.state.lock().unwrap();
+        let job = state.pending.pop_first()?;
+        state.progress.pending = state.pending.len();
+        state.progress.running = Some(job.to_string());
+        Some(job)
+    }
+
+    /// Record the result of the job most recently taken.
+    fn finish(&self, job: &Job, res: Result<(), DbError>) {
+        let mut state = self.state.lock().unwrap();
+        state.progress.running = None;
+        match res {
+            Ok(()) => {
+                state.progress.completed += 1;
+            }
+            Err(e) => {
+                log::error!("Deferred job ({}) failed: {}", job, &e);
+                state.progress.failed += 1;
+                state.progress.last_error = Some(format!("{}: {}", job, &e));
+            }
+        }
+    }
+
+    /**
+    Run scheduled jobs against `store` as they come in, forever.
+
+    This is meant to be `tokio::spawn()`ed when the server starts up.
+    */
+    pub async fn run(self: Arc<Self>, store: Arc<RwLock<Store>>) {
+        loop {
+            match self.take_next() {
+                None => self.notify.notified().await,
+                Some(job) => {
+                    tokio::time::sleep(THROTTLE).await;
+                    let res = job.run(&*store.read().await).await;
+                    self.finish(&job, res);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_and_dedupe() {
+        let q = Queue::new();
+        q.schedule(["pha", "alg"].map(|s| Job::CourseStats(s.to_owned())));
+        q.schedule([Job::CourseStats("pha".to_owned())]);
+        assert_eq!(q.progress().pending, 2);
+
+        let job = q.take_next().unwrap();
+        assert_eq!(job, Job::CourseStats("alg".to_owned()));
+        let p = q.progress();
+        assert_eq!(p.pending, 1);
+        assert_eq!(p.running.as_deref(), Some("course stats for \"alg\""));
+
+        q.finish(&job, Ok(()));
+        let job = q.take_next().unwrap();
+        q.finish(&job, Err(DbError::from("oops")));
+        assert!(q.take_next().is_none());
+
+        let p = q.progress();
+        assert_eq!((p.pending, p.completed, p.failed), (0, 1, 1));
+        assert!(p.running.is_none());
+        assert_eq!(
+            p.last_error.as_deref(),
+            Some("course stats for \"pha\": oops")
+        );
+    }
+}
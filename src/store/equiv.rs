@@ -0,0 +1,220 @@
+/*!
+`Store` methods for mapping other schools' course identifiers to our own
+courses, so transfer students can be credited with work done elsewhere.
+
+```sql
+CREATE TABLE course_equivalents (
+    ext     TEXT PRIMARY KEY,   /* the other school's identifier */
+    sym     TEXT NOT NULL REFERENCES courses(sym)
+                ON UPDATE CASCADE ON DELETE CASCADE
+);
+```
+*/
+use std::collections::BTreeMap;
+
+use tokio_postgres::Transaction;
+
+use super::{DbError, Store};
+use crate::hist::TransferRecord;
+
+impl Store {
+    /// Return the map of external course identifiers to course `sym`s.
+    pub async fn get_course_equivalents(&self) -> Result<BTreeMap<String, String>, DbError> {
+        log::trace!("Store::get_course_equivalents() called.");
+
+        let client = self.connect().await?;
+        let rows = client
+            .query("SELECT ext, sym FROM course_equivalents", &[])
+            .await?;
+
+        let mut map: BTreeMap<String, String> = BTreeMap::new();
+        for row in rows.iter() {
+            map.insert(row.try_get("ext")?, row.try_get("sym")?);
+        }
+
+        Ok(map)
+    }
+
+    /// Record that the external course `ext` is equivalent to our course
+    /// `sym`, replacing any equivalence `ext` already had.
+    pub async fn set_course_equivalent(&self, ext: &str, sym: &str) -> Result<(), DbError> {
+        log::trace!("Store::set_course_equivalent( {:?}, {:?} ) called.", ext, sym);
+
+        let client = self.connect().await?;
+        client
+            .execute(
+                "INSERT INTO course_equivalents (ext, sym) VALUES ($1, $2)
+                ON CONFLICT (ext) DO UPDATE SET sym = EXCLUDED.sym",
+                &[&ext, &sym],
+            )
+            .await
+            .map_err(|e| {
+                DbError::from(e).annotate(&format!(
+                    "Error making {:?} equivalent to course {:?}",
+                    ext, sym
+                ))
+            })?;
+
+        Ok(())
+    }
+
+    /// Forget the equivalence for the external course `ext`. Returns
+    /// whether there was one.
+    pub async fn delete_course_equivalent(&self, ext: &str) -> Result<bool, DbError> {
+        log::trace!("Store::delete_course_equivalent( {:?} ) called.", ext);
+
+        let client = self.connect().await?;
+        let n = client
+            .execute("DELETE FROM course_equivalents WHERE ext = $1", &[&ext])
+            .await?;
+
+        Ok(n > 0)
+    }
+
+    /// Return the `sym` of the course `ident` refers to: either `ident`
+    /// itself, if it's one of our course symbols, or the course it's
+    /// recorded as equivalent to. Returns `None` if it's neither.
+    pub async fn resolve_course(
+        t: &Transaction<'_>,
+        ident: &str,
+    ) -> Result<Option<String>, DbError> {
+        log::trace!("Store::resolve_course( [ T ], {:?} ) called.", ident);
+
+        let row = t
+            .query_opt(
+                "SELECT sym FROM (
+                    SELECT sym, 0 AS pri FROM courses WHERE sym = $1
+                    UNION ALL
+                    SELECT sym, 1 AS pri FROM course_equivalents WHERE ext = $1
+                ) AS matches
+                ORDER BY pri LIMIT 1",
+                &[&ident],
+            )
+            .await?;
+
+        match row {
+            Some(row) => Ok(Some(row.try_get("sym")?)),
+            None => Ok(None),
+        }
+    }
+
+    /**
+    Add the courses in `records` to their students' completion histories,
+    translating external course identifiers to our courses.
+
+    This is all or nothing: if any record's course can't be resolved, nothing
+    is added. Records for courses already in the student's history are
+    skipped. Returns the number of records added and the number skipped.
+    */
+    pub async fn import_transfer_credit(
+        &self,
+        records: &[TransferRecord],
+    ) -> Result<(usize, usize), DbError> {
+        log::trace!(
+            "Store::import_transfer_credit( [ {} records ] ) called.",
+            records.len()
+        );
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        let (mut added, mut skipped) = (0usize, 0usize);
+        for rec in records.iter() {
+            let sym = match Store::resolve_course(&t, &rec.course).await? {
+                Some(sym) => sym,
+                None => {
+                    return Err(DbError(format!(
+                        "{:?} (for {:?}) is neither a course nor equivalent to one.",
+                        &rec.course, &rec.uname
+                    )));
+                }
+            };
+
+            let existing = t
+                .query_opt(
+                    "SELECT FROM completion WHERE uname = $1 AND courses = $2",
+                    &[&rec.uname, &sym],
+                )
+                .await?;
+            if existing.is_some() {
+                skipped += 1;
+                continue;
+            }
+
+            Store::add_completion(&t, &rec.uname, rec.year, rec.term, &sym).await?;
+            added += 1;
+        }
+
+        t.commit().await?;
+        Ok((added, skipped))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::course::Course;
+    use crate::pace::Term;
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    fn record(course: &str, year: i32, term: Term) -> TransferRecord {
+        TransferRecord {
+            uname: "frog".to_owned(),
+            course: course.to_owned(),
+            year,
+            term,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn course_equivalents() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        insert_users(&db, &["berro"], &[("frog", "berro")]).await;
+        let crs = Course::from_reader(std::fs::File::open("test/good_course_0.mix").unwrap())
+            .unwrap();
+        let sym = crs.sym.clone();
+        db.insert_courses(&[crs]).await.unwrap();
+
+        db.set_course_equivalent("ALG-1A", "nonexistent")
+            .await
+            .unwrap_err();
+        db.set_course_equivalent("ALG-1A", &sym).await.unwrap();
+        db.set_course_equivalent("ALGEBRA", &sym).await.unwrap();
+        assert!(db.delete_course_equivalent("ALGEBRA").await.unwrap());
+        assert!(!db.delete_course_equivalent("ALGEBRA").await.unwrap());
+        let equivs = db.get_course_equivalents().await.unwrap();
+        assert_eq!(equivs.len(), 1);
+        assert_eq!(equivs["ALG-1A"], sym);
+
+        // An unresolvable course means nothing gets added.
+        db.import_transfer_credit(&[
+            record("ALG-1A", 2021, Term::Fall),
+            record("GEOM", 2021, Term::Spring),
+        ])
+        .await
+        .unwrap_err();
+        assert!(db.get_completion_history("frog").await.unwrap().is_empty());
+
+        let (added, skipped) = db
+            .import_transfer_credit(&[
+                record("ALG-1A", 2021, Term::Fall),
+                record(&sym, 2021, Term::Fall),
+            ])
+            .await
+            .unwrap();
+        assert_eq!((added, skipped), (1, 1));
+        let hist = db.get_completion_history("frog").await.unwrap();
+        assert_eq!(hist.len(), 1);
+        assert_eq!(hist[0].sym, sym);
+
+        db.nuke_database().await.unwrap();
+    }
+}
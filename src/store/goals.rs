@@ -14,28 +14,124 @@ CREATE TABLE goals (
     due         DATE,
     done        DATE,
     tries       SMALLINT,
-    score   TEXT
+    score   TEXT,
+    part        SMALLINT,   /* these three are NULL unless the goal is */
+    parts       SMALLINT,   /* one piece of a split chapter; see       */
+    fraction    REAL,       /* `Store::split_goal()`                   */
+    progress    SMALLINT,   /* percent; see `Store::set_goal_progress()` */
+    updated_at  TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+                            /* kept current by triggers; see `sync` */
+);
+```
+
+Some goals' scores are broken down into rubric components, which are kept
+(as a JSON array of [`RubricItem`]s) in a separate table:
+
+```sql
+CREATE TABLE goal_rubrics (
+    goal        BIGINT PRIMARY KEY REFERENCES goals(id) ON DELETE CASCADE,
+    components  JSONB NOT NULL
+);
+```
+
+Deleting a goal that has been completed (or scored) would lose part of a
+student's grade history, so such goals are copied into `deleted_goals`
+first (without their tags, but with their rubric breakdowns):
+
+```sql
+CREATE TABLE deleted_goals (
+    id          BIGINT,
+    uname       TEXT,
+    sym         TEXT,
+    seq         SMALLINT,
+    custom      BIGINT,
+    review      BOOL,
+    incomplete  BOOL,
+    due         DATE,
+    done        DATE,
+    tries       SMALLINT,
+    score       TEXT,
+    rubric      JSONB,
+    part        SMALLINT,
+    parts       SMALLINT,
+    fraction    REAL,
+    deleted     TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    deleted_by  TEXT
 );
 ```
 */
 use futures::stream::{FuturesUnordered, StreamExt};
-use tokio_postgres::{types::ToSql, types::Type, Row, Transaction};
+use time::{Date, PrimitiveDateTime};
+use tokio_postgres::{types::ToSql, types::Type, GenericClient, Row, Transaction};
+
+use super::{waivers::STUDENT_WAIVERS, DbError, Store};
+use crate::pace::{BookCh, CustomCh, Goal, Part, RubricItem, Source, Tag, Waiver};
 
-use super::{DbError, Store};
-use crate::pace::{BookCh, Goal, Source};
+/// Columns to select (in addition to those of the `goals` table) so that
+/// [`goal_from_row`] can fill in each `Goal`'s [`Tag`]s, rubric, whether
+/// it has been requested (see [`requests`](super::requests); requests for
+/// goals that are done don't count), and the status of any request for a
+/// printed test of its chapter (see [`test_requests`](super::test_requests)).
+const TAG_COLUMNS: &str = "ARRAY(
+        SELECT tags.id FROM goal_tags INNER JOIN tags ON goal_tags.tag = tags.id
+        WHERE goal_tags.goal = goals.id ORDER BY tags.name
+    ) AS tag_ids,
+    ARRAY(
+        SELECT tags.name FROM goal_tags INNER JOIN tags ON goal_tags.tag = tags.id
+        WHERE goal_tags.goal = goals.id ORDER BY tags.name
+    ) AS tag_names,
+    (
+        SELECT components::TEXT FROM goal_rubrics WHERE goal_rubrics.goal = goals.id
+    ) AS rubric,
+    (
+        goals.done IS NULL
+        AND EXISTS (SELECT FROM completion_requests WHERE completion_requests.goal = goals.id)
+    ) AS requested,
+    (
+        SELECT status FROM test_requests
+        WHERE test_requests.uname = goals.uname
+            AND test_requests.sym = goals.sym
+            AND test_requests.seq = goals.seq
+    ) AS test";
 
 fn goal_from_row(row: &Row) -> Result<Goal, DbError> {
-    let bkch = BookCh {
-        sym: row.try_get("sym")?,
-        seq: row.try_get("seq")?,
-        // Gets set in the `Pace` constructor.
-        level: 0.0,
+    let source = match row.try_get::<_, Option<i64>>("custom")? {
+        Some(id) => Source::Custom(CustomCh(id)),
+        None => Source::Book(BookCh {
+            sym: row.try_get("sym")?,
+            seq: row.try_get("seq")?,
+            // Gets set in the `Pace` constructor.
+            level: 0.0,
+        }),
+    };
+
+    let tag_ids: Vec<i64> = row.try_get("tag_ids")?;
+    let tag_names: Vec<String> = row.try_get("tag_names")?;
+    let tags: Vec<Tag> = tag_ids
+        .into_iter()
+        .zip(tag_names)
+        .map(|(id, name)| Tag { id, name })
+        .collect();
+
+    let rubric: Vec<RubricItem> = match row.try_get::<_, Option<String>>("rubric")? {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| DbError(format!("Error reading rubric {:?}: {}", &json, &e)))?,
+        None => Vec::new(),
+    };
+
+    let part = match (
+        row.try_get("part")?,
+        row.try_get("parts")?,
+        row.try_get("fraction")?,
+    ) {
+        (Some(n), Some(of), Some(fraction)) => Some(Part { n, of, fraction }),
+        _ => None,
     };
 
     Ok(Goal {
         id: row.try_get("id")?,
         uname: row.try_get("uname")?,
-        source: Source::Book(bkch),
+        source,
         review: row.try_get("review")?,
         incomplete: row.try_get("incomplete")?,
         due: row.try_get("due")?,
@@ -44,9 +140,148 @@ fn goal_from_row(row: &Row) -> Result<Goal, DbError> {
         // Gets set in the `Pace` constructor.
         weight: 0.0,
         score: row.try_get("score")?,
+        tags,
+        rubric,
+        part,
+        progress: row.try_get("progress")?,
+        requested: row.try_get("requested")?,
+        test: match row.try_get::<_, Option<&str>>("test")? {
+            Some(s) => Some(s.parse().map_err(DbError)?),
+            None => None,
+        },
     })
 }
 
+/// The values of the `sym`, `seq`, and `custom` columns for a goal from the
+/// given `Source`; book goals have no `custom`, and custom goals have
+/// neither `sym` nor `seq`.
+fn source_columns(src: &Source) -> (Option<&str>, Option<i16>, Option<i64>) {
+    match src {
+        Source::Book(bch) => (Some(&bch.sym), Some(bch.seq), None),
+        Source::Custom(CustomCh(id)) => (None, None, Some(*id)),
+    }
+}
+
+/// What happened to a goal someone tried to delete with
+/// [`Store::delete_goal`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum GoalDeletion {
+    /// The goal was deleted; this is the uname of its student.
+    Deleted(String),
+    /// The goal has a done date or a score, so deleting it requires `force`.
+    Protected,
+}
+
+/// Which of a student's goals [`Store::delete_goals_where`] should delete.
+/// Goals must match every condition that's set.
+#[derive(Debug, Default)]
+pub struct GoalFilter<'a> {
+    /// Only goals from the course with this symbol.
+    pub sym: Option<&'a str>,
+    /// Only goals due on or after this date.
+    pub due_from: Option<Date>,
+    /// Only goals due before this date.
+    pub due_before: Option<Date>,
+    /// Only goals that haven't been started: no done date, score, or tries.
+    pub unstarted: bool,
+}
+
+impl GoalFilter<'_> {
+    /// Whether this would match every one of a student's goals.
+    pub fn is_empty(&self) -> bool {
+        self.sym.is_none()
+            && self.due_from.is_none()
+            && self.due_before.is_none()
+            && !self.unstarted
+    }
+}
+
+/// What [`Store::delete_goals_where`] did.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GoalsDeleted {
+    pub deleted: usize,
+    /// How many of the deleted goals were completed (or scored), and so
+    /// were archived.
+    pub archived: usize,
+}
+
+/// A completed goal that has been deleted (see [`Store::get_deleted_goals`]).
+#[derive(Debug)]
+pub struct GoalTombstone {
+    pub goal: Goal,
+    pub deleted: PrimitiveDateTime,
+    pub deleted_by: Option<String>,
+}
+
+/// Copy the completed (or scored) goals matching `condition` (in which `$1`
+/// is `param`) into the `deleted_goals` table, and clear their mastery
+/// statuses, in preparation for deleting them.
+async fn tombstone_goals(
+    t: &Transaction<'_>,
+    condition: &str,
+    param: &(dyn ToSql + Sync),
+    deleted_by: Option<&str>,
+) -> Result<u64, DbError> {
+    let n = t
+        .execute(
+            &format!(
+                "INSERT INTO deleted_goals (
+                    id, uname, sym, seq, custom, review, incomplete,
+                    due, done, tries, score, rubric, part, parts, fraction, deleted_by
+                )
+                SELECT
+                    id, uname, sym, seq, custom, review, incomplete,
+                    due, done, tries, score,
+                    (SELECT components FROM goal_rubrics WHERE goal = goals.id),
+                    part, parts, fraction, $2
+                FROM goals
+                WHERE ({}) AND (done IS NOT NULL OR score IS NOT NULL)",
+                condition
+            ),
+            &[param, &deleted_by],
+        )
+        .await
+        .map_err(|e| DbError::from(e).annotate("Error archiving deleted goals"))?;
+
+    t.execute(
+        &format!(
+            "DELETE FROM nmr WHERE id IN (SELECT id FROM goals WHERE {})",
+            condition
+        ),
+        &[param],
+    )
+    .await?;
+
+    Ok(n)
+}
+
+/// Store the rubric breakdown of the score of the goal with the given `id`
+/// (or remove it, if `rubric` is empty).
+async fn set_rubric<C: GenericClient>(
+    client: &C,
+    id: i64,
+    rubric: &[RubricItem],
+) -> Result<(), DbError> {
+    if rubric.is_empty() {
+        client
+            .execute("DELETE FROM goal_rubrics WHERE goal = $1", &[&id])
+            .await?;
+    } else {
+        let json = serde_json::to_string(rubric)
+            .map_err(|e| DbError(format!("Error serializing rubric: {}", &e)))?;
+        client
+            .execute(
+                "INSERT INTO goal_rubrics (goal, components) VALUES ($1, $2::TEXT::JSONB)
+                ON CONFLICT (goal) DO UPDATE SET components = EXCLUDED.components",
+                &[&id, &json],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error storing rubric"))?;
+    }
+
+    Ok(())
+}
+
 impl Store {
     /**
     Insert the supplied [`Goal`]s into the database.
@@ -57,20 +292,8 @@ impl Store {
     pub async fn insert_goals(&self, goals: &[Goal]) -> Result<usize, DbError> {
         log::trace!("Store::insert_goals( [ {} goals ] ) called.", &goals.len());
 
-        // Make copies of all the book `Source`s, and throw an error on custom
-        // ones because we don't support those yet.
-        for g in goals.iter() {
-            if let Source::Custom(_) = &g.source {
-                return Err(DbError("Custom Sources are unsupported.".to_owned()));
-            }
-        }
-        let sources: Vec<BookCh> = goals
-            .iter()
-            .map(|g| match g.source {
-                Source::Book(ref bch) => bch.clone(),
-                _ => panic!("We just checked, and there shouldn't be any Custom Sources."),
-            })
-            .collect();
+        let sources: Vec<(Option<&str>, Option<i16>, Option<i64>)> =
+            goals.iter().map(|g| source_columns(&g.source)).collect();
 
         let mut client = self.connect().await?;
         let t = client.transaction().await?;
@@ -78,37 +301,43 @@ impl Store {
         let insert_stmt = t
             .prepare_typed(
                 "INSERT INTO goals (
-                uname, sym, seq, review, incomplete,
-                due, done
+                uname, sym, seq, custom, review, incomplete,
+                due, done, tries, score
             )
             VALUES (
-                $1, $2, $3, $4, $5,
-                $6, $7
+                $1, $2, $3, $4, $5, $6,
+                $7, $8, $9, $10
             )",
                 &[
                     Type::TEXT,
                     Type::TEXT,
                     Type::INT2,
+                    Type::INT8,
                     Type::BOOL,
                     Type::BOOL,
                     Type::DATE,
                     Type::DATE,
+                    Type::INT2,
+                    Type::TEXT,
                 ],
             )
             .await?;
 
-        let pvec: Vec<[&(dyn ToSql + Sync); 7]> = goals
+        let pvec: Vec<[&(dyn ToSql + Sync); 10]> = goals
             .iter()
             .zip(sources.iter())
-            .map(|(g, src)| {
-                let p: [&(dyn ToSql + Sync); 7] = [
+            .map(|(g, (sym, seq, custom))| {
+                let p: [&(dyn ToSql + Sync); 10] = [
                     &g.uname,
-                    &src.sym,
-                    &src.seq,
+                    sym,
+                    seq,
+                    custom,
                     &g.review,
                     &g.incomplete,
                     &g.due,
                     &g.done,
+                    &g.tries,
+                    &g.score,
                 ];
                 p
             })
@@ -143,29 +372,27 @@ impl Store {
     pub async fn insert_one_goal(&self, g: &Goal) -> Result<(), DbError> {
         log::trace!("Store::insert_one_goal( {:?} ) called.", g);
 
-        let src = match &g.source {
-            Source::Book(bch) => bch,
-            _ => {
-                return Err(DbError("Custom sources not yet supported.".to_owned()));
-            }
-        };
+        let (sym, seq, custom) = source_columns(&g.source);
 
-        let client = self.connect().await?;
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
 
-        client
-            .execute(
+        let row = t
+            .query_one(
                 "INSERT INTO goals (
-                uname, sym, seq, review, incomplete,
+                uname, sym, seq, custom, review, incomplete,
                 due, done
             )
             VALUES (
-                $1, $2, $3, $4, $5,
-                $6, $7
-            )",
+                $1, $2, $3, $4, $5, $6,
+                $7, $8
+            )
+            RETURNING id",
                 &[
                     &g.uname,
-                    &src.sym,
-                    &src.seq,
+                    &sym,
+                    &seq,
+                    &custom,
                     &g.review,
                     &g.incomplete,
                     &g.due,
@@ -173,6 +400,9 @@ impl Store {
                 ],
             )
             .await?;
+        let id: i64 = row.try_get("id")?;
+        set_rubric(&t, id, &g.rubric).await?;
+        t.commit().await?;
 
         Ok(())
     }
@@ -182,36 +412,96 @@ impl Store {
     pub async fn update_goal(&self, g: &Goal) -> Result<(), DbError> {
         log::trace!("Store_update_goal( {:?} ) called.", g);
 
-        let src = match &g.source {
-            Source::Book(bch) => bch,
-            _ => {
-                return Err(DbError("Custom sources not yet supported.".to_owned()));
-            }
-        };
-
-        let client = self.connect().await?;
+        let (sym, seq, custom) = source_columns(&g.source);
 
-        client
-            .execute(
+        let mut client = self.cached_connect().await?;
+        let stmt = client
+            .prepare(
                 "UPDATE goals SET
-                sym = $1, seq = $2, review = $3, incomplete = $4,
-                due = $5, done = $6, tries = $7, score = $8
-            WHERE id = $9",
+                    sym = $1, seq = $2, custom = $3, review = $4, incomplete = $5,
+                    due = $6, done = $7, tries = $8, score = $9,
+                    progress = CASE WHEN $7::DATE IS NULL THEN progress END
+                WHERE id = $10",
+            )
+            .await?;
+        let t = client.client().transaction().await?;
+
+        t.execute(
+            &stmt,
+            &[
+                &sym,
+                &seq,
+                &custom,
+                &g.review,
+                &g.incomplete,
+                &g.due,
+                &g.done,
+                &g.tries,
+                &g.score,
+                &g.id,
+            ],
+        )
+        .await?;
+        set_rubric(&t, g.id, &g.rubric).await?;
+        t.commit().await?;
+
+        Ok(())
+    }
+
+    /**
+    Mark the goals identified by each `(uname, sym, seq)` in `targets` as done
+    on the date `done`, with the given `tries` and `score`.
+
+    Returns the number of goals matched by each target, in order. Targets
+    are updated one after another on the supplied [`Transaction`]; it's up to
+    the caller to decide whether the results warrant committing it.
+    */
+    pub async fn complete_goals(
+        &self,
+        t: &Transaction<'_>,
+        targets: &[(&str, &str, i16)],
+        done: &Date,
+        tries: Option<i16>,
+        score: Option<&str>,
+    ) -> Result<Vec<u64>, DbError> {
+        log::trace!(
+            "Store::complete_goals( [ T ], [ {} targets ], {}, {:?}, {:?} ) called.",
+            &targets.len(),
+            done,
+            &tries,
+            &score
+        );
+
+        let update_stmt = t
+            .prepare_typed(
+                "UPDATE goals SET done = $1, tries = $2, score = $3, progress = NULL
+                WHERE uname = $4 AND sym = $5 AND seq = $6",
                 &[
-                    &src.sym,
-                    &src.seq,
-                    &g.review,
-                    &g.incomplete,
-                    &g.due,
-                    &g.done,
-                    &g.tries,
-                    &g.score,
-                    &g.id,
+                    Type::DATE,
+                    Type::INT2,
+                    Type::TEXT,
+                    Type::TEXT,
+                    Type::TEXT,
+                    Type::INT2,
                 ],
             )
             .await?;
 
-        Ok(())
+        let mut n_matched: Vec<u64> = Vec::with_capacity(targets.len());
+        for (uname, sym, seq) in targets.iter() {
+            let n = t
+                .execute(&update_stmt, &[done, &tries, &score, uname, sym, seq])
+                .await
+                .map_err(|e| {
+                    DbError::from(e).annotate(&format!(
+                        "Error completing goal ({:?}, {:?}, {})",
+                        uname, sym, seq
+                    ))
+                })?;
+            n_matched.push(n);
+        }
+
+        Ok(n_matched)
     }
 
     /**
@@ -224,15 +514,11 @@ impl Store {
     pub async fn update_due_dates(&self, goals: &[Goal]) -> Result<usize, DbError> {
         log::trace!("Store::update_goals( [ {} goals] ) called.", &goals.len());
 
-        let mut client = self.connect().await?;
-        let t = client.transaction().await?;
-
-        let update_stmt = t
-            .prepare_typed(
-                "UPDATE goals SET due = $1 WHERE id = $2",
-                &[Type::DATE, Type::INT8],
-            )
+        let mut client = self.cached_connect().await?;
+        let update_stmt = client
+            .prepare("UPDATE goals SET due = $1 WHERE id = $2")
             .await?;
+        let t = client.client().transaction().await?;
 
         let pvec: Vec<[&(dyn ToSql + Sync); 2]> = goals
             .iter()
@@ -266,30 +552,332 @@ impl Store {
         Ok(n_changed as usize)
     }
 
-    /// Delete the goal with the given `id` from the database.
-    pub async fn delete_goal(&self, id: i64) -> Result<String, DbError> {
-        log::trace!("Store::delete_goal( {} ) called.", &id);
+    /**
+    Split the goal with the given `id` into one goal for each
+    `(due, fraction)` in `parts`, all for the same chapter. The original
+    goal becomes the first part (keeping its tags); the rest are new. If
+    the goal has already been completed, all of its parts are, too, with
+    the same score.
 
-        let client = self.connect().await?;
+    If `tuname` is supplied, the goal must belong to one of that Teacher's
+    students. Returns the uname of the goal's student.
+    */
+    pub async fn split_goal(
+        &self,
+        id: i64,
+        parts: &[(Option<Date>, f32)],
+        tuname: Option<&str>,
+    ) -> Result<String, DbError> {
+        log::trace!(
+            "Store::split_goal( {}, {:?}, {:?} ) called.",
+            &id,
+            parts,
+            &tuname
+        );
 
-        let row = client
-            .query_one("DELETE FROM goals WHERE id = $1 RETURNING uname", &[&id])
+        if parts.len() < 2 {
+            return Err(DbError(
+                "A goal must be split into at least two parts.".to_owned(),
+            ));
+        }
+        let n_parts = parts.len() as i16;
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        let row = t
+            .query_opt(
+                "SELECT goals.uname, students.teacher, goals.part
+                FROM goals INNER JOIN students ON goals.uname = students.uname
+                WHERE goals.id = $1 FOR UPDATE OF goals",
+                &[&id],
+            )
+            .await?
+            .ok_or_else(|| DbError(format!("There is no Goal with id {}.", &id)))?;
+        let uname: String = row.try_get("uname")?;
+        if let Some(tuname) = tuname {
+            let teacher: String = row.try_get("teacher")?;
+            if teacher != tuname {
+                return Err(DbError(format!(
+                    "Goal {} doesn't belong to one of your students.",
+                    &id
+                )));
+            }
+        }
+        if row.try_get::<_, Option<i16>>("part")?.is_some() {
+            return Err(DbError(format!("Goal {} has already been split.", &id)));
+        }
+
+        let (due, fraction) = &parts[0];
+        t.execute(
+            "UPDATE goals SET due = $1, part = 1, parts = $2, fraction = $3 WHERE id = $4",
+            &[due, &n_parts, fraction, &id],
+        )
+        .await
+        .map_err(|e| DbError::from(e).annotate("Error updating first part"))?;
+
+        let insert_stmt = t
+            .prepare_typed(
+                "INSERT INTO goals (
+                    uname, sym, seq, custom, review, incomplete, due, done, tries, score,
+                    part, parts, fraction
+                )
+                SELECT
+                    uname, sym, seq, custom, review, incomplete, $1, done, tries, score,
+                    $2, $3, $4
+                FROM goals WHERE id = $5
+                RETURNING id",
+                &[Type::DATE, Type::INT2, Type::INT2, Type::FLOAT4, Type::INT8],
+            )
+            .await?;
+        for (n, (due, fraction)) in parts.iter().enumerate().skip(1) {
+            let n = (n + 1) as i16;
+            let row = t
+                .query_one(&insert_stmt, &[due, &n, &n_parts, fraction, &id])
+                .await
+                .map_err(|e| DbError::from(e).annotate(&format!("Error inserting part {}", n)))?;
+            let part_id: i64 = row.try_get("id")?;
+            t.execute(
+                "INSERT INTO goal_rubrics (goal, components)
+                SELECT $1, components FROM goal_rubrics WHERE goal = $2",
+                &[&part_id, &id],
+            )
             .await?;
+        }
+
+        t.commit().await?;
+        Ok(uname)
+    }
+
+    /**
+    Record that the unfinished goal with the given `id` is `progress`
+    percent done (or clear its progress, if `None`).
 
+    This is only for display; it doesn't count toward the goal being done,
+    and it's cleared when the goal is completed. If `tuname` is supplied,
+    the goal must belong to one of that Teacher's students. Returns the
+    uname of the goal's student.
+    */
+    pub async fn set_goal_progress(
+        &self,
+        id: i64,
+        progress: Option<i16>,
+        tuname: Option<&str>,
+    ) -> Result<String, DbError> {
+        log::trace!(
+            "Store::set_goal_progress( {}, {:?}, {:?} ) called.",
+            &id,
+            &progress,
+            &tuname
+        );
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        let row = t
+            .query_opt(
+                "SELECT goals.uname, students.teacher, goals.done
+                FROM goals INNER JOIN students ON goals.uname = students.uname
+                WHERE goals.id = $1 FOR UPDATE OF goals",
+                &[&id],
+            )
+            .await?
+            .ok_or_else(|| DbError(format!("There is no Goal with id {}.", &id)))?;
         let uname: String = row.try_get("uname")?;
+        if let Some(tuname) = tuname {
+            let teacher: String = row.try_get("teacher")?;
+            if teacher != tuname {
+                return Err(DbError(format!(
+                    "Goal {} doesn't belong to one of your students.",
+                    &id
+                )));
+            }
+        }
+        if row.try_get::<_, Option<Date>>("done")?.is_some() {
+            return Err(DbError(format!("Goal {} has already been completed.", &id)));
+        }
+
+        t.execute(
+            "UPDATE goals SET progress = $1 WHERE id = $2",
+            &[&progress, &id],
+        )
+        .await
+        .map_err(|e| DbError::from(e).annotate("Error recording progress"))?;
 
+        t.commit().await?;
         Ok(uname)
     }
 
+    /**
+    Delete the goal with the given `id` from the database.
+
+    A goal that has been completed or scored is only deleted if `force` is
+    `true`, and is archived (as deleted by `deleted_by`) first.
+    */
+    pub async fn delete_goal(
+        &self,
+        id: i64,
+        force: bool,
+        deleted_by: &str,
+    ) -> Result<GoalDeletion, DbError> {
+        log::trace!(
+            "Store::delete_goal( {}, {}, {:?} ) called.",
+            &id,
+            &force,
+            deleted_by
+        );
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        let row = t
+            .query_opt(
+                "SELECT done, score FROM goals WHERE id = $1 FOR UPDATE",
+                &[&id],
+            )
+            .await?
+            .ok_or_else(|| DbError(format!("There is no Goal with id {}.", &id)))?;
+        let done: Option<Date> = row.try_get("done")?;
+        let score: Option<String> = row.try_get("score")?;
+        if (done.is_some() || score.is_some()) && !force {
+            return Ok(GoalDeletion::Protected);
+        }
+
+        tombstone_goals(&t, "id = $1", &id, Some(deleted_by)).await?;
+        let row = t
+            .query_one("DELETE FROM goals WHERE id = $1 RETURNING uname", &[&id])
+            .await?;
+        let uname: String = row.try_get("uname")?;
+
+        t.commit().await?;
+        Ok(GoalDeletion::Deleted(uname))
+    }
+
+    /// Return the archived copies of the given student's deleted goals, in
+    /// the order they were deleted.
+    pub async fn get_deleted_goals(&self, uname: &str) -> Result<Vec<GoalTombstone>, DbError> {
+        log::trace!("Store::get_deleted_goals( {:?} ) called.", uname);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT
+                    id, uname, sym, seq, custom, review, incomplete, due, done, tries, score,
+                    part, parts, fraction, NULL::SMALLINT AS progress,
+                    ARRAY[]::BIGINT[] AS tag_ids, ARRAY[]::TEXT[] AS tag_names,
+                    rubric::TEXT AS rubric, FALSE AS requested, NULL::TEXT AS test,
+                    deleted, deleted_by
+                FROM deleted_goals WHERE uname = $1
+                ORDER BY deleted, id",
+                &[&uname],
+            )
+            .await?;
+
+        let mut tombstones: Vec<GoalTombstone> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            tombstones.push(GoalTombstone {
+                goal: goal_from_row(row)?,
+                deleted: row.try_get("deleted")?,
+                deleted_by: row.try_get("deleted_by")?,
+            });
+        }
+
+        Ok(tombstones)
+    }
+
     /// Fetch all of a student's pace goals and wrap them in a vector of
     /// [`Goal`]s.
     pub async fn get_goals_by_student(&self, uname: &str) -> Result<Vec<Goal>, DbError> {
         log::trace!("Store::get_goals_by_student( {:?} ) called.", uname);
 
+        let mut client = self.cached_connect().await?;
+        let stmt = client
+            .prepare(&format!(
+                "SELECT *, {} FROM goals WHERE uname = $1",
+                TAG_COLUMNS
+            ))
+            .await?;
+
+        let rows = client.client().query(&stmt, &[&uname]).await?;
+
+        let mut goals: Vec<Goal> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            match goal_from_row(row) {
+                Ok(g) => {
+                    goals.push(g);
+                }
+                Err(e) => {
+                    return Err(DbError(format!(
+                        "Unable to read Goal from database: {}",
+                        &e
+                    )));
+                }
+            }
+        }
+
+        Ok(goals)
+    }
+
+    /**
+    Fetch what the database has of the student `uname`'s
+    [`Pace`](crate::pace::Pace): their goals and their waived chapters.
+
+    This is [`Store::get_goals_by_student`] and
+    [`Store::get_waivers_by_student`] together. It is still two queries, not
+    a join, but they are pipelined on one cached connection (whose prepared
+    statements are reused), so there's only one round trip to wait on. No
+    course or chapter metadata comes back; the Pace gets that, along with the
+    student and their Teacher, from what the [`Glob`](crate::config::Glob)
+    already has in memory.
+    */
+    pub async fn get_pace_bundle(&self, uname: &str) -> Result<(Vec<Goal>, Vec<Waiver>), DbError> {
+        log::trace!("Store::get_pace_bundle( {:?} ) called.", uname);
+
+        let mut client = self.cached_connect().await?;
+        let goals_stmt = client
+            .prepare(&format!(
+                "SELECT *, {} FROM goals WHERE uname = $1",
+                TAG_COLUMNS
+            ))
+            .await?;
+        let waivers_stmt = client.prepare(STUDENT_WAIVERS).await?;
+
+        let client = client.client();
+        let params: [&(dyn ToSql + Sync); 1] = [&uname];
+        let (goal_rows, waiver_rows) = tokio::try_join!(
+            client.query(&goals_stmt, &params),
+            client.query(&waivers_stmt, &params),
+        )?;
+
+        let mut goals: Vec<Goal> = Vec::with_capacity(goal_rows.len());
+        for row in goal_rows.iter() {
+            goals.push(goal_from_row(row).map_err(|e| {
+                DbError(format!("Unable to read Goal from database: {}", &e))
+            })?);
+        }
+        let mut waivers: Vec<Waiver> = Vec::with_capacity(waiver_rows.len());
+        for row in waiver_rows.iter() {
+            waivers.push(Waiver {
+                sym: row.try_get("sym")?,
+                seq: row.try_get("seq")?,
+            });
+        }
+
+        Ok((goals, waivers))
+    }
+
+    /// Fetch all goals (of every student) from the course with the given
+    /// symbol.
+    pub async fn get_goals_by_course(&self, sym: &str) -> Result<Vec<Goal>, DbError> {
+        log::trace!("Store::get_goals_by_course( {:?} ) called.", sym);
+
         let client = self.connect().await?;
 
         let rows = client
-            .query("SELECT * FROM goals WHERE uname = $1", &[&uname])
+            .query(
+                &format!("SELECT *, {} FROM goals WHERE sym = $1", TAG_COLUMNS),
+                &[&sym],
+            )
             .await?;
 
         let mut goals: Vec<Goal> = Vec::with_capacity(rows.len());
@@ -310,14 +898,21 @@ impl Store {
         Ok(goals)
     }
 
-    /// Delete all of a student's pace goals.
+    /// Delete all of a student's pace goals (archiving any that have been
+    /// completed, as deleted by `deleted_by`).
     pub async fn delete_goals_by_student(
         &self,
         t: &Transaction<'_>,
         uname: &str,
+        deleted_by: Option<&str>,
     ) -> Result<usize, DbError> {
-        log::trace!("Store::delete_goals_by_student( {:?} ) called.", uname);
+        log::trace!(
+            "Store::delete_goals_by_student( {:?}, {:?} ) called.",
+            uname,
+            &deleted_by
+        );
 
+        tombstone_goals(t, "uname = $1", &uname, deleted_by).await?;
         let n_goals = t
             .execute("DELETE FROM goals WHERE uname = $1", &[&uname])
             .await?;
@@ -325,6 +920,66 @@ impl Store {
         Ok(n_goals as usize)
     }
 
+    /**
+    Delete those of a student's pace goals that match `filter` (archiving
+    any that have been completed, as deleted by `deleted_by`).
+
+    Goals with no due date never match a filter on due dates.
+    */
+    pub async fn delete_goals_where(
+        &self,
+        t: &Transaction<'_>,
+        uname: &str,
+        filter: &GoalFilter<'_>,
+        deleted_by: Option<&str>,
+    ) -> Result<GoalsDeleted, DbError> {
+        log::trace!(
+            "Store::delete_goals_where( {:?}, {:?}, {:?} ) called.",
+            uname,
+            filter,
+            &deleted_by
+        );
+
+        let rows = t
+            .query(
+                "SELECT id FROM goals
+                WHERE uname = $1
+                    AND ($2::TEXT IS NULL OR sym = $2)
+                    AND ($3::DATE IS NULL OR due >= $3)
+                    AND ($4::DATE IS NULL OR due < $4)
+                    AND (NOT $5 OR (
+                        done IS NULL AND score IS NULL AND COALESCE(tries, 0) = 0
+                    ))
+                FOR UPDATE",
+                &[
+                    &uname,
+                    &filter.sym,
+                    &filter.due_from,
+                    &filter.due_before,
+                    &filter.unstarted,
+                ],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error selecting goals to delete"))?;
+        let ids = rows
+            .iter()
+            .map(|row| row.try_get("id"))
+            .collect::<Result<Vec<i64>, _>>()?;
+        if ids.is_empty() {
+            return Ok(GoalsDeleted::default());
+        }
+
+        let archived = tombstone_goals(t, "id = ANY($1)", &ids, deleted_by).await?;
+        let deleted = t
+            .execute("DELETE FROM goals WHERE id = ANY($1)", &[&ids])
+            .await?;
+
+        Ok(GoalsDeleted {
+            deleted: deleted as usize,
+            archived: archived as usize,
+        })
+    }
+
     /// Retrieve all of the goals of students who have the given teacher.
     ///
     /// This is used, among other things, to fetch data for the teacher's
@@ -332,21 +987,22 @@ impl Store {
     pub async fn get_goals_by_teacher(&self, tuname: &str) -> Result<Vec<Goal>, DbError> {
         log::trace!("Store::get_goals_by_teacher( {:?} ) called.", tuname);
 
-        let client = self.connect().await?;
-
-        let rows = client
-            .query(
+        let mut client = self.cached_connect().await?;
+        let stmt = client
+            .prepare(&format!(
                 "SELECT
-                id, goals.uname, sym, seq, custom, review, incomplete,
-                due, done, tries, score
-            FROM
-                goals INNER JOIN students ON goals.uname = students.uname
-            WHERE
-                students.teacher = $1",
-                &[&tuname],
-            )
+                    id, goals.uname, sym, seq, custom, review, incomplete,
+                    due, done, tries, score, part, parts, fraction, progress, {}
+                FROM
+                    goals INNER JOIN students ON goals.uname = students.uname
+                WHERE
+                    students.teacher = $1",
+                TAG_COLUMNS
+            ))
             .await?;
 
+        let rows = client.client().query(&stmt, &[&tuname]).await?;
+
         let mut goals: Vec<Goal> = Vec::with_capacity(rows.len());
         for row in rows.iter() {
             match goal_from_row(row) {
@@ -367,7 +1023,546 @@ impl Store {
         log::trace!("Store::yearly_clear_goals( [ T ] ) called.");
 
         let _ = t.execute("DELETE FROM goals", &[]).await?;
+        let _ = t.execute("DELETE FROM deleted_goals", &[]).await?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+
+    use serial_test::serial;
+
+    use crate::course::{Course, Custom};
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    /// Set up a clean database with one course, teacher, and student.
+    async fn populate(db: &Store) {
+        db.ensure_db_schema().await.unwrap();
+        // Start clean, in case a failed test left courses behind.
+        db.nuke_database().await.unwrap();
+        db.ensure_db_schema().await.unwrap();
+
+        let crs = Course::from_reader(File::open("test/good_course_0.mix").unwrap()).unwrap();
+        db.insert_courses(&[crs]).await.unwrap();
+        insert_users(db, &["berro"], &[("frog", "berro")]).await;
+    }
+
+    fn goal(seq: i16) -> Goal {
+        Goal {
+            id: 0,
+            uname: "frog".to_owned(),
+            source: Source::Book(BookCh {
+                sym: "pc".to_owned(),
+                seq,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due: None,
+            done: None,
+            tries: None,
+            weight: 0.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn goal_rubrics() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        populate(&db).await;
+
+        let rubric = vec![
+            RubricItem {
+                name: "Setup".to_owned(),
+                points: 3.0,
+                max: 4.0,
+            },
+            RubricItem {
+                name: "Accuracy".to_owned(),
+                points: 5.5,
+                max: 6.0,
+            },
+        ];
+        let g = Goal {
+            rubric: rubric.clone(),
+            ..goal(1)
+        };
+        db.insert_one_goal(&g).await.unwrap();
+
+        let mut g = db.get_goals_by_student("frog").await.unwrap().remove(0);
+        assert_eq!(g.rubric, rubric);
+        assert_eq!(
+            db.get_goals_by_teacher("berro").await.unwrap()[0].rubric,
+            rubric
+        );
+
+        g.rubric.truncate(1);
+        db.update_goal(&g).await.unwrap();
+        assert_eq!(
+            db.get_goals_by_student("frog").await.unwrap()[0].rubric,
+            rubric[..1]
+        );
+
+        g.rubric.clear();
+        db.update_goal(&g).await.unwrap();
+        assert!(db.get_goals_by_student("frog").await.unwrap()[0]
+            .rubric
+            .is_empty());
+
+        db.nuke_database().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn pace_bundle() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        populate(&db).await;
+
+        let (goals, waivers) = db.get_pace_bundle("frog").await.unwrap();
+        assert!(goals.is_empty() && waivers.is_empty());
+
+        db.insert_goals(&[goal(1), goal(2)]).await.unwrap();
+        let w = Waiver {
+            sym: "pc".to_owned(),
+            seq: 2,
+        };
+        db.set_waiver("frog", &w, true, "berro").await.unwrap();
+
+        let ids = |goals: &[Goal]| {
+            let mut ids: Vec<i64> = goals.iter().map(|g| g.id).collect();
+            ids.sort_unstable();
+            ids
+        };
+        let (goals, waivers) = db.get_pace_bundle("frog").await.unwrap();
+        assert_eq!(
+            ids(&goals),
+            ids(&db.get_goals_by_student("frog").await.unwrap())
+        );
+        assert_eq!(goals.len(), 2);
+        assert_eq!(waivers, vec![w]);
+        // Again, with the statements already prepared.
+        let (again, _) = db.get_pace_bundle("frog").await.unwrap();
+        assert_eq!(ids(&again), ids(&goals));
+
+        db.nuke_database().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn deletion_protection() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        populate(&db).await;
+
+        let done = Goal {
+            done: Some(time::macros::date!(2022 - 10 - 04)),
+            score: Some("17/20".to_owned()),
+            rubric: vec![RubricItem {
+                name: "Work".to_owned(),
+                points: 17.0,
+                max: 20.0,
+            }],
+            ..goal(2)
+        };
+        let id_of = |goals: &[Goal], seq: i16| {
+            goals
+                .iter()
+                .find(|g| matches!(&g.source, Source::Book(b) if b.seq == seq))
+                .unwrap()
+                .id
+        };
+        db.insert_goals(&[goal(1), goal(2)]).await.unwrap();
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        let (pending_id, done_id) = (id_of(&goals, 1), id_of(&goals, 2));
+        db.update_goal(&Goal {
+            id: done_id,
+            ..done.clone()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            db.delete_goal(pending_id, false, "berro").await.unwrap(),
+            GoalDeletion::Deleted("frog".to_owned())
+        );
+        assert_eq!(
+            db.delete_goal(done_id, false, "berro").await.unwrap(),
+            GoalDeletion::Protected
+        );
+        assert_eq!(db.get_goals_by_student("frog").await.unwrap().len(), 1);
+        assert!(db.get_deleted_goals("frog").await.unwrap().is_empty());
+
+        assert_eq!(
+            db.delete_goal(done_id, true, "berro").await.unwrap(),
+            GoalDeletion::Deleted("frog".to_owned())
+        );
+        assert!(db.get_goals_by_student("frog").await.unwrap().is_empty());
+        let tombs = db.get_deleted_goals("frog").await.unwrap();
+        assert_eq!(tombs.len(), 1);
+        assert_eq!(tombs[0].goal.id, done_id);
+        assert_eq!(tombs[0].goal.done, done.done);
+        assert_eq!(tombs[0].goal.score, done.score);
+        assert_eq!(tombs[0].goal.rubric, done.rubric);
+        assert_eq!(tombs[0].deleted_by.as_deref(), Some("berro"));
+        assert!(db.delete_goal(done_id, true, "berro").await.is_err());
+
+        db.insert_goals(&[goal(1), goal(2)]).await.unwrap();
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        db.update_goal(&Goal {
+            id: id_of(&goals, 2),
+            ..done.clone()
+        })
+        .await
+        .unwrap();
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            assert_eq!(
+                db.delete_goals_by_student(&t, "frog", None).await.unwrap(),
+                2
+            );
+            t.commit().await.unwrap();
+        }
+        let tombs = db.get_deleted_goals("frog").await.unwrap();
+        assert_eq!(tombs.len(), 2);
+        assert_eq!(tombs[1].deleted_by, None);
+
+        db.nuke_database().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn filtered_deletion() {
+        use time::macros::date;
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        populate(&db).await;
+
+        let due = |seq: i16, d: Date| Goal {
+            due: Some(d),
+            ..goal(seq)
+        };
+        db.insert_goals(&[
+            due(1, date!(2022 - 10 - 03)),
+            due(2, date!(2022 - 10 - 10)),
+            due(3, date!(2022 - 11 - 07)),
+            due(4, date!(2023 - 02 - 06)),
+            goal(5),
+        ])
+        .await
+        .unwrap();
+        // Goal 2 is done, and goal 3 has been attempted.
+        for mut g in db.get_goals_by_student("frog").await.unwrap() {
+            match &g.source {
+                Source::Book(b) if b.seq == 2 => {
+                    g.done = Some(date!(2022 - 10 - 05));
+                    g.tries = Some(1);
+                    g.score = Some("9/10".to_owned());
+                }
+                Source::Book(b) if b.seq == 3 => {
+                    g.tries = Some(1);
+                }
+                _ => continue,
+            }
+            db.update_goal(&g).await.unwrap();
+        }
+
+        let delete = |filter: GoalFilter<'static>| {
+            let db = &db;
+            async move {
+                let mut client = db.connect().await.unwrap();
+                let t = client.transaction().await.unwrap();
+                let res = db
+                    .delete_goals_where(&t, "frog", &filter, Some("berro"))
+                    .await
+                    .unwrap();
+                t.commit().await.unwrap();
+                res
+            }
+        };
+        let seqs = || async {
+            let mut seqs: Vec<i16> = db
+                .get_goals_by_student("frog")
+                .await
+                .unwrap()
+                .iter()
+                .map(|g| match &g.source {
+                    Source::Book(b) => b.seq,
+                    x => panic!("Unexpected goal source {:?}", x),
+                })
+                .collect();
+            seqs.sort_unstable();
+            seqs
+        };
+
+        assert!(GoalFilter::default().is_empty());
+        assert_eq!(
+            delete(GoalFilter {
+                sym: Some("nope"),
+                ..Default::default()
+            })
+            .await,
+            GoalsDeleted::default()
+        );
+
+        // Only the fall goals that haven't been started.
+        assert_eq!(
+            delete(GoalFilter {
+                due_before: Some(date!(2023 - 01 - 13)),
+                unstarted: true,
+                ..Default::default()
+            })
+            .await,
+            GoalsDeleted {
+                deleted: 1,
+                archived: 0
+            }
+        );
+        assert_eq!(seqs().await, vec![2, 3, 4, 5]);
+
+        // Everything in the fall, including completed work, which gets
+        // archived; the unscheduled goal isn't in any term.
+        assert_eq!(
+            delete(GoalFilter {
+                sym: Some("pc"),
+                due_before: Some(date!(2023 - 01 - 13)),
+                ..Default::default()
+            })
+            .await,
+            GoalsDeleted {
+                deleted: 2,
+                archived: 1
+            }
+        );
+        assert_eq!(seqs().await, vec![4, 5]);
+        let tombs = db.get_deleted_goals("frog").await.unwrap();
+        assert_eq!(tombs.len(), 1);
+        assert_eq!(tombs[0].deleted_by.as_deref(), Some("berro"));
+
+        assert_eq!(
+            delete(GoalFilter {
+                due_from: Some(date!(2023 - 01 - 13)),
+                ..Default::default()
+            })
+            .await,
+            GoalsDeleted {
+                deleted: 1,
+                archived: 0
+            }
+        );
+        assert_eq!(seqs().await, vec![5]);
+
+        db.nuke_database().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn split_goal() {
+        use time::macros::date;
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        populate(&db).await;
+
+        let done = Goal {
+            done: Some(date!(2022 - 10 - 04)),
+            tries: Some(2),
+            score: Some("17/20".to_owned()),
+            ..goal(2)
+        };
+        db.insert_goals(&[goal(1), goal(2)]).await.unwrap();
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        let id_of = |seq: i16| {
+            goals
+                .iter()
+                .find(|g| matches!(&g.source, Source::Book(b) if b.seq == seq))
+                .unwrap()
+                .id
+        };
+        db.update_goal(&Goal {
+            id: id_of(2),
+            ..done.clone()
+        })
+        .await
+        .unwrap();
+
+        let parts = [
+            (Some(date!(2022 - 10 - 10)), 0.25),
+            (Some(date!(2022 - 10 - 17)), 0.25),
+            (None, 0.5),
+        ];
+        assert!(db.split_goal(id_of(1), &parts[..1], None).await.is_err());
+        assert!(db
+            .split_goal(id_of(1), &parts, Some("not_berro"))
+            .await
+            .is_err());
+        assert_eq!(
+            db.split_goal(id_of(1), &parts, Some("berro"))
+                .await
+                .unwrap(),
+            "frog"
+        );
+        assert!(db.split_goal(id_of(1), &parts, None).await.is_err());
+        db.split_goal(id_of(2), &parts[1..], None).await.unwrap();
+
+        let mut goals = db.get_goals_by_teacher("berro").await.unwrap();
+        goals.sort_by_key(|g| g.id);
+        assert_eq!(goals.len(), 5);
+        let split: Vec<&Goal> = goals
+            .iter()
+            .filter(|g| matches!(&g.source, Source::Book(b) if b.seq == 1))
+            .collect();
+        assert_eq!(split[0].id, id_of(1));
+        for (n, (g, (due, fraction))) in split.iter().zip(parts.iter()).enumerate() {
+            assert_eq!(&g.due, due);
+            assert_eq!(
+                g.part,
+                Some(Part {
+                    n: n as i16 + 1,
+                    of: 3,
+                    fraction: *fraction
+                })
+            );
+        }
+        // Completion is preserved in every part.
+        for g in goals
+            .iter()
+            .filter(|g| matches!(&g.source, Source::Book(b) if b.seq == 2))
+        {
+            assert_eq!(g.done, done.done);
+            assert_eq!(g.tries, done.tries);
+            assert_eq!(g.score, done.score);
+            assert_eq!(g.part.unwrap().of, 2);
+        }
+
+        db.nuke_database().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn goal_progress() {
+        use time::macros::date;
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        populate(&db).await;
+
+        db.insert_goals(&[goal(1), goal(2)]).await.unwrap();
+        let mut goals = db.get_goals_by_student("frog").await.unwrap();
+        goals.sort_by_key(|g| g.id);
+        assert!(goals.iter().all(|g| g.progress.is_none()));
+        let (id1, id2) = (goals[0].id, goals[1].id);
+
+        assert!(db.set_goal_progress(id1, Some(40), Some("not_berro")).await.is_err());
+        assert_eq!(
+            db.set_goal_progress(id1, Some(40), Some("berro")).await.unwrap(),
+            "frog"
+        );
+        db.set_goal_progress(id2, Some(75), None).await.unwrap();
+        db.set_goal_progress(id2, None, None).await.unwrap();
+        let goals = db.get_goals_by_teacher("berro").await.unwrap();
+        let progress_of = |id: i64| goals.iter().find(|g| g.id == id).unwrap().progress;
+        assert_eq!((progress_of(id1), progress_of(id2)), (Some(40), None));
+
+        // Updating an unfinished goal keeps its progress; completing it
+        // clears it, and then it can't be set.
+        let progress_now = || async {
+            let goals = db.get_goals_by_student("frog").await.unwrap();
+            goals.iter().find(|g| g.id == id1).unwrap().progress
+        };
+        let mut g = goals.iter().find(|g| g.id == id1).unwrap().clone();
+        g.due = Some(date!(2022 - 10 - 10));
+        db.update_goal(&g).await.unwrap();
+        assert_eq!(progress_now().await, Some(40));
+        g.done = Some(date!(2022 - 10 - 11));
+        db.update_goal(&g).await.unwrap();
+        assert_eq!(progress_now().await, None);
+        assert!(db.set_goal_progress(id1, Some(50), None).await.is_err());
+
+        db.nuke_database().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn custom_goals() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        populate(&db).await;
+
+        let cus = db
+            .insert_custom_chapter("berro", "Science Fair Project", 0.1)
+            .await
+            .unwrap();
+        let g = Goal {
+            source: Source::Custom(CustomCh(cus.id)),
+            ..goal(1)
+        };
+        db.insert_goals(&[g.clone(), goal(2)]).await.unwrap();
+        db.insert_one_goal(&g).await.unwrap();
+
+        let mut goals = db.get_goals_by_student("frog").await.unwrap();
+        goals.sort_by_key(|g| g.id);
+        let customs: Vec<&Goal> = goals
+            .iter()
+            .filter(|g| g.source == Source::Custom(CustomCh(cus.id)))
+            .collect();
+        assert_eq!(customs.len(), 2);
+
+        // A goal can be switched between book and custom material.
+        let mut g = goals[0].clone();
+        g.source = match &g.source {
+            Source::Book(_) => Source::Custom(CustomCh(cus.id)),
+            Source::Custom(_) => goal(3).source,
+        };
+        db.update_goal(&g).await.unwrap();
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        assert_eq!(
+            goals.iter().find(|x| x.id == g.id).unwrap().source,
+            g.source
+        );
+
+        let renamed = Custom {
+            title: "Science Fair Poster".to_owned(),
+            weight: 0.05,
+            ..cus.clone()
+        };
+        db.update_custom_chapter(&renamed).await.unwrap();
+        assert_eq!(
+            db.get_custom_chapters().await.unwrap().get(&cus.id),
+            Some(&renamed)
+        );
+
+        // Can't delete a custom chapter that's still in use.
+        assert!(db.delete_custom_chapter(cus.id).await.is_err());
+        for g in goals.iter() {
+            if let Source::Custom(_) = &g.source {
+                db.delete_goal(g.id, false, "berro").await.unwrap();
+            }
+        }
+        db.delete_custom_chapter(cus.id).await.unwrap();
+        assert!(db.get_custom_chapters().await.unwrap().is_empty());
+        assert!(db.delete_custom_chapter(cus.id).await.is_err());
+
+        db.nuke_database().await.unwrap();
+    }
+}
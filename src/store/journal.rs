@@ -0,0 +1,208 @@
+/*!
+A bounded journal of failed API requests, for debugging.
+
+When the `failure_journal` configuration option is nonzero, the
+[`journal`](crate::inter::journal) middleware records every API request
+that gets a 4xx or 5xx response here, and the table is trimmed to keep
+only that many of the most recent entries.
+
+```sql
+CREATE TABLE failed_requests (
+    id          BIGSERIAL PRIMARY KEY,
+    at          TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    method      TEXT NOT NULL,
+    path        TEXT NOT NULL,
+    uname       TEXT,
+    action      TEXT,
+    status      SMALLINT NOT NULL,
+    body        TEXT,
+    response    TEXT
+);
+```
+*/
+use serde::Serialize;
+use tokio_postgres::Row;
+
+use super::{DbError, Store};
+
+/// A single journaled request and the response it got.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct FailedRequest {
+    pub id: i64,
+    /// When the request was made (UTC), as `YYYY-MM-DD HH:MM:SS`.
+    pub at: String,
+    pub method: String,
+    pub path: String,
+    /// Value of the `x-camp-uname` header, if any.
+    pub uname: Option<String>,
+    /// Value of the `x-camp-action` header, if any.
+    pub action: Option<String>,
+    pub status: i16,
+    /// The (sanitized, possibly truncated) request body.
+    pub body: Option<String>,
+    /// The (possibly truncated) response body.
+    pub response: Option<String>,
+}
+
+fn failure_from_row(row: &Row, with_bodies: bool) -> Result<FailedRequest, DbError> {
+    let (body, response) = if with_bodies {
+        (row.try_get("body")?, row.try_get("response")?)
+    } else {
+        (None, None)
+    };
+
+    Ok(FailedRequest {
+        id: row.try_get("id")?,
+        at: row.try_get("at")?,
+        method: row.try_get("method")?,
+        path: row.try_get("path")?,
+        uname: row.try_get("uname")?,
+        action: row.try_get("action")?,
+        status: row.try_get("status")?,
+        body,
+        response,
+    })
+}
+
+impl Store {
+    /**
+    Add `req` to the journal (its `id` and `at` fields are ignored and
+    assigned by the database), then discard all but the `keep` most recent
+    entries.
+    */
+    pub async fn journal_failed_request(
+        &self,
+        req: &FailedRequest,
+        keep: usize,
+    ) -> Result<(), DbError> {
+        log::trace!(
+            "Store::journal_failed_request( {} {} -> {}, {} ) called.",
+            &req.method,
+            &req.path,
+            &req.status,
+            &keep
+        );
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        t.execute(
+            "INSERT INTO failed_requests
+                (method, path, uname, action, status, body, response)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            &[
+                &req.method,
+                &req.path,
+                &req.uname,
+                &req.action,
+                &req.status,
+                &req.body,
+                &req.response,
+            ],
+        )
+        .await
+        .map_err(|e| DbError::from(e).annotate("Error inserting failed request"))?;
+
+        let keep = keep as i64;
+        t.execute(
+            "DELETE FROM failed_requests WHERE id <= (
+                SELECT id FROM failed_requests
+                ORDER BY id DESC OFFSET $1 LIMIT 1
+            )",
+            &[&keep],
+        )
+        .await
+        .map_err(|e| DbError::from(e).annotate("Error trimming failed request journal"))?;
+
+        t.commit()
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error committing transaction"))
+    }
+
+    /// Return the most recent `n` journaled failures, newest first, without
+    /// their request or response bodies.
+    pub async fn get_failed_requests(&self, n: usize) -> Result<Vec<FailedRequest>, DbError> {
+        log::trace!("Store::get_failed_requests( {} ) called.", &n);
+
+        let n = n as i64;
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT id, to_char(at, 'YYYY-MM-DD HH24:MI:SS') AS at,
+                    method, path, uname, action, status
+                FROM failed_requests
+                ORDER BY id DESC LIMIT $1",
+                &[&n],
+            )
+            .await?;
+
+        rows.iter()
+            .map(|row| failure_from_row(row, false))
+            .collect()
+    }
+
+    /// Return the journaled failure with the given `id` (bodies and all).
+    pub async fn get_failed_request(&self, id: i64) -> Result<Option<FailedRequest>, DbError> {
+        log::trace!("Store::get_failed_request( {} ) called.", &id);
+
+        let client = self.connect().await?;
+        let opt_row = client
+            .query_opt(
+                "SELECT id, to_char(at, 'YYYY-MM-DD HH24:MI:SS') AS at,
+                    method, path, uname, action, status, body, response
+                FROM failed_requests WHERE id = $1",
+                &[&id],
+            )
+            .await?;
+
+        match opt_row {
+            Some(row) => Ok(Some(failure_from_row(&row, true)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn journal_is_bounded() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+
+        for n in 0..5 {
+            let req = FailedRequest {
+                method: "POST".to_owned(),
+                path: "/teacher".to_owned(),
+                uname: Some("teacher".to_owned()),
+                action: Some(format!("action-{}", n)),
+                status: 400,
+                body: Some(format!("body {}", n)),
+                response: Some("nope".to_owned()),
+                ..Default::default()
+            };
+            db.journal_failed_request(&req, 3).await.unwrap();
+        }
+
+        let list = db.get_failed_requests(10).await.unwrap();
+        let actions: Vec<&str> = list.iter().map(|f| f.action.as_deref().unwrap()).collect();
+        assert_eq!(actions, ["action-4", "action-3", "action-2"]);
+        assert!(list.iter().all(|f| f.body.is_none()));
+
+        let one = db.get_failed_request(list[1].id).await.unwrap().unwrap();
+        assert_eq!(one.body.as_deref(), Some("body 3"));
+        assert_eq!(one.status, 400);
+        assert!(db.get_failed_request(-1).await.unwrap().is_none());
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+/*!
+`Store` methods for Teachers' classroom dashboard ("kiosk") links.
+
+Each Teacher may have (at most) one secret token, which grants read-only
+access to their class's dashboard (see [`kiosk`](crate::inter::kiosk))
+without logging in, so it can be left up on a classroom projector.
+
+```sql
+CREATE TABLE kiosk_tokens (
+    uname   TEXT PRIMARY KEY REFERENCES teachers(uname) ON DELETE CASCADE,
+    token   TEXT UNIQUE NOT NULL,
+    created TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+);
+```
+*/
+use rand::{distributions::Alphanumeric, Rng};
+
+use super::{DbError, Store};
+
+/// Length of a newly-generated kiosk token.
+const TOKEN_LENGTH: usize = 24;
+
+impl Store {
+    /// Return the Teacher `tuname`'s current kiosk token, if they have one.
+    pub async fn get_kiosk_token(&self, tuname: &str) -> Result<Option<String>, DbError> {
+        log::trace!("Store::get_kiosk_token( {:?} ) called.", tuname);
+
+        let client = self.connect().await?;
+        let opt_row = client
+            .query_opt(
+                "SELECT token FROM kiosk_tokens WHERE uname = $1",
+                &[&tuname],
+            )
+            .await?;
+
+        match opt_row {
+            Some(row) => Ok(Some(row.try_get("token")?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Generate a new kiosk token for the Teacher `tuname` (replacing any
+    /// they already had) and return it.
+    pub async fn new_kiosk_token(&self, tuname: &str) -> Result<String, DbError> {
+        log::trace!("Store::new_kiosk_token( {:?} ) called.", tuname);
+
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+
+        let client = self.connect().await?;
+        client
+            .execute(
+                "INSERT INTO kiosk_tokens (uname, token) VALUES ($1, $2)
+                ON CONFLICT (uname) DO UPDATE
+                SET token = EXCLUDED.token, created = EXCLUDED.created",
+                &[&tuname, &token],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error storing kiosk token"))?;
+
+        Ok(token)
+    }
+
+    /// Revoke the Teacher `tuname`'s kiosk token (if they have one).
+    pub async fn delete_kiosk_token(&self, tuname: &str) -> Result<(), DbError> {
+        log::trace!("Store::delete_kiosk_token( {:?} ) called.", tuname);
+
+        let client = self.connect().await?;
+        client
+            .execute("DELETE FROM kiosk_tokens WHERE uname = $1", &[&tuname])
+            .await?;
+
+        Ok(())
+    }
+
+    /// Return the uname of the Teacher to whom the kiosk `token` belongs.
+    pub async fn get_kiosk_teacher(&self, token: &str) -> Result<Option<String>, DbError> {
+        log::trace!("Store::get_kiosk_teacher( [ token ] ) called.");
+
+        let client = self.connect().await?;
+        let opt_row = client
+            .query_opt("SELECT uname FROM kiosk_tokens WHERE token = $1", &[&token])
+            .await?;
+
+        match opt_row {
+            Some(row) => Ok(Some(row.try_get("uname")?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn kiosk_tokens() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        insert_users(&db, &["berro"], &[]).await;
+
+        assert!(db.get_kiosk_token("berro").await.unwrap().is_none());
+        let old = db.new_kiosk_token("berro").await.unwrap();
+        let new = db.new_kiosk_token("berro").await.unwrap();
+        assert_ne!(old, new);
+        assert_eq!(
+            db.get_kiosk_token("berro").await.unwrap(),
+            Some(new.clone())
+        );
+        assert!(db.get_kiosk_teacher(&old).await.unwrap().is_none());
+        assert_eq!(
+            db.get_kiosk_teacher(&new).await.unwrap().as_deref(),
+            Some("berro")
+        );
+
+        db.delete_kiosk_token("berro").await.unwrap();
+        assert!(db.get_kiosk_teacher(&new).await.unwrap().is_none());
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+/*!
+`Store` methods for the weekly history of students' lag.
+
+Once a week (see [`lag_history`](crate::inter::lag_history)), every
+student's lag (the weight they've done minus the weight that's due, as a
+fraction of their total weight) is recorded here, so Bosses and Teachers
+can see whether they've been catching up. Partial progress on unfinished
+goals (see [`Pace::progress_weight`](crate::pace::Pace::progress_weight))
+is recorded alongside, but doesn't count toward the lag.
+
+```sql
+CREATE TABLE lag_history (
+    uname           TEXT REFERENCES students(uname) ON DELETE CASCADE,
+    week            DATE,   /* the Monday of the week */
+    lag             REAL NOT NULL,
+    done_weight     REAL NOT NULL,
+    due_weight      REAL NOT NULL,
+    total_weight    REAL NOT NULL,
+    progress_weight REAL NOT NULL DEFAULT 0,
+    PRIMARY KEY (uname, week)
+);
+```
+*/
+use std::collections::HashMap;
+
+use serde::Serialize;
+use time::Date;
+use tokio_postgres::{types::Type, Transaction};
+
+use super::{DbError, Store};
+
+/// One student's lag as of the start of one week.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LagSnapshot {
+    /// The Monday of the week the snapshot was taken.
+    pub week: Date,
+    pub lag: f32,
+    pub done_weight: f32,
+    pub due_weight: f32,
+    pub total_weight: f32,
+    /// Weight of the partial progress on unfinished goals.
+    pub progress_weight: f32,
+}
+
+impl Store {
+    /**
+    Record each `(uname, snapshot)` pair in `snaps`, unless there's already
+    a snapshot for that student and week.
+
+    Returns the number of snapshots recorded.
+    */
+    pub async fn insert_lag_snapshots(
+        &self,
+        snaps: &[(&str, LagSnapshot)],
+    ) -> Result<usize, DbError> {
+        log::trace!(
+            "Store::insert_lag_snapshots( [ {} snapshots ] ) called.",
+            snaps.len()
+        );
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+        let stmt = t
+            .prepare_typed(
+                "INSERT INTO lag_history
+                    (uname, week, lag, done_weight, due_weight, total_weight, progress_weight)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT DO NOTHING",
+                &[
+                    Type::TEXT,
+                    Type::DATE,
+                    Type::FLOAT4,
+                    Type::FLOAT4,
+                    Type::FLOAT4,
+                    Type::FLOAT4,
+                    Type::FLOAT4,
+                ],
+            )
+            .await?;
+
+        let mut n_inserted: usize = 0;
+        for (uname, s) in snaps.iter() {
+            n_inserted += t
+                .execute(
+                    &stmt,
+                    &[
+                        uname,
+                        &s.week,
+                        &s.lag,
+                        &s.done_weight,
+                        &s.due_weight,
+                        &s.total_weight,
+                        &s.progress_weight,
+                    ],
+                )
+                .await
+                .map_err(|e| {
+                    DbError::from(e).annotate(&format!("Error recording lag for {:?}", uname))
+                })? as usize;
+        }
+        t.commit().await?;
+
+        Ok(n_inserted)
+    }
+
+    /// Return the lag histories (oldest first) of each of the students in
+    /// `unames` who have any.
+    pub async fn get_lag_history(
+        &self,
+        unames: &[&str],
+    ) -> Result<HashMap<String, Vec<LagSnapshot>>, DbError> {
+        log::trace!("Store::get_lag_history( {:?} ) called.", unames);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT uname, week, lag, done_weight, due_weight, total_weight, progress_weight
+                FROM lag_history WHERE uname = ANY($1)
+                ORDER BY week",
+                &[&unames],
+            )
+            .await?;
+
+        let mut history: HashMap<String, Vec<LagSnapshot>> = HashMap::new();
+        for row in rows.iter() {
+            let snap = LagSnapshot {
+                week: row.try_get("week")?,
+                lag: row.try_get("lag")?,
+                done_weight: row.try_get("done_weight")?,
+                due_weight: row.try_get("due_weight")?,
+                total_weight: row.try_get("total_weight")?,
+                progress_weight: row.try_get("progress_weight")?,
+            };
+            history.entry(row.try_get("uname")?).or_default().push(snap);
+        }
+
+        Ok(history)
+    }
+
+    /// Clear the year's lag history.
+    pub async fn yearly_clear_lag_history(t: &Transaction<'_>) -> Result<(), DbError> {
+        log::trace!("Store::yearly_clear_lag_history( [ T ] ) called.");
+
+        t.execute("DELETE FROM lag_history", &[]).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+    use time::macros::date;
+
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    fn snap(week: Date, done: f32) -> LagSnapshot {
+        LagSnapshot {
+            week,
+            lag: done - 0.5,
+            done_weight: done,
+            due_weight: 0.5,
+            total_weight: 1.0,
+            progress_weight: 0.125,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn lag_history() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        insert_users(&db, &["berro"], &[("frog", "berro"), ("zack", "berro")]).await;
+
+        let (w1, w2) = (date!(2022 - 09 - 05), date!(2022 - 09 - 12));
+        let n = db
+            .insert_lag_snapshots(&[
+                ("frog", snap(w2, 0.5)),
+                ("frog", snap(w1, 0.25)),
+                ("zack", snap(w1, 0.75)),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(n, 3);
+        // A second snapshot in the same week doesn't replace the first.
+        let n = db
+            .insert_lag_snapshots(&[("frog", snap(w2, 0.0))])
+            .await
+            .unwrap();
+        assert_eq!(n, 0);
+
+        let history = db.get_lag_history(&["frog"]).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history["frog"], vec![snap(w1, 0.25), snap(w2, 0.5)]);
+
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            Store::yearly_clear_lag_history(&t).await.unwrap();
+            t.commit().await.unwrap();
+        }
+        assert!(db
+            .get_lag_history(&["frog", "zack"])
+            .await
+            .unwrap()
+            .is_empty());
+
+        db.nuke_database().await.unwrap();
+    }
+}
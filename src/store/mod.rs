@@ -7,16 +7,73 @@ TODO:
 */
 use std::fmt::Write;
 
+use deadpool_postgres::Pool;
 use rand::{distributions, Rng};
-use tokio_postgres::{Client, NoTls};
+use tokio_postgres::{Client, NoTls, Transaction};
 
+use crate::{
+    pool::{new_pool, PgClient},
+    DbSanity,
+};
+
+mod absences;
+mod announce;
+mod audit;
+mod backend;
+mod cached;
 mod cal;
 mod courses;
+pub mod deferred;
+mod equiv;
 mod goals;
+mod journal;
+mod kiosk;
+mod lag;
+mod notices;
+mod optout;
+mod outbox;
+mod perms;
+mod releases;
+mod report_templates;
 mod reports;
+mod requests;
+mod retention;
+mod sandbox;
+mod stats;
+mod sync;
+mod tags;
+mod test_requests;
 mod users;
+mod waivers;
+mod webhooks;
+mod yearly;
+mod years;
+
+pub use announce::Announcement;
+pub use audit::{AuditEntry, AuditFilter};
+pub use backend::{DataStore, MemStore, StoreFuture};
+pub use cached::CachedClient;
+pub use goals::{GoalDeletion, GoalFilter, GoalTombstone, GoalsDeleted};
+pub use journal::FailedRequest;
+pub use lag::LagSnapshot;
+pub use outbox::{FailedEmail, QueuedEmail};
+pub use releases::ReportRelease;
+pub use report_templates::ReportTemplate;
+pub use reports::{FinalStream, ReportMeta};
+pub use retention::{PruneStats, Pruned, Retention};
+pub use stats::CourseStats;
+pub use sync::Changes;
+pub use test_requests::TestRequest;
+pub use webhooks::{Delivery, PendingDelivery, Webhook};
+pub use yearly::{NukePreview, YearArchive};
+pub use years::AcademicYear;
 
 const DEFAULT_SALT_LENGTH: usize = 4;
+/// Default number of rows per statement for bulk inserts; see
+/// [`Store::set_bulk_insert`].
+const DEFAULT_INSERT_CHUNK: usize = 500;
+/// Default number of bulk insert statements in flight at once.
+const DEFAULT_INSERT_CONCURRENCY: usize = 4;
 const DEFAULT_SALT_CHARS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
 
 static SCHEMA: &[(&str, &str, &str)] = &[
@@ -28,7 +85,9 @@ static SCHEMA: &[(&str, &str, &str)] = &[
             sym   TEXT UNIQUE NOT NULL,
             title TEXT NOT NULL,
             book  TEXT,
-            level REAL
+            level REAL,
+            min_gap         SMALLINT,   /* see course::Constraints */
+            max_per_week    SMALLINT
         )",
         "DROP TABLE courses",
     ),
@@ -91,16 +150,18 @@ static SCHEMA: &[(&str, &str, &str)] = &[
             fall_exam_fraction REAL,
             spring_exam_fraction REAL,
             fall_notices SMALLINT,
-            spring_notices SMALLINT
+            spring_notices SMALLINT,
+            updated_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
         )",
         "DROP TABLE students",
     ),
     /*
-    Two tables of calendar info.
+    Three tables of calendar info.
 
     calendar holds just a list of "working days" as used by the autopacer.
     dates holds special dates (so far this is just the ends of the Spring
-    and Fall Semesters).
+    and Fall Semesters). exam_windows holds stretches of working days on
+    which nothing should be due; see [`cal`].
     */
     (
         "SELECT FROM information_schema.tables WHERE table_name = 'calendar'",
@@ -115,6 +176,28 @@ static SCHEMA: &[(&str, &str, &str)] = &[
         )",
         "DROP TABLE dates",
     ),
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'exam_windows'",
+        "CREATE TABLE exam_windows (
+            id          BIGSERIAL PRIMARY KEY,
+            name        TEXT NOT NULL,
+            start_date  DATE NOT NULL,
+            end_date    DATE NOT NULL,
+            CHECK (start_date <= end_date)
+        )",
+        "DROP TABLE exam_windows",
+    ),
+    // Names and bounds of academic years; see [`years`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'academic_years'",
+        "CREATE TABLE academic_years (
+            label   TEXT PRIMARY KEY,
+            starts  DATE NOT NULL,
+            ends    DATE NOT NULL,
+            active  BOOLEAN NOT NULL DEFAULT FALSE
+        )",
+        "DROP TABLE academic_years",
+    ),
     // Student pace goals.
     (
         "SELECT FROM information_schema.tables WHERE table_name = 'goals'",
@@ -129,7 +212,12 @@ static SCHEMA: &[(&str, &str, &str)] = &[
             due         DATE,
             done        DATE,
             tries       SMALLINT,
-            score       TEXT
+            score       TEXT,
+            part        SMALLINT,
+            parts       SMALLINT,
+            fraction    REAL,
+            progress    SMALLINT,
+            updated_at  TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
         )",
         "DROP TABLE goals",
     ),
@@ -173,12 +261,24 @@ static SCHEMA: &[(&str, &str, &str)] = &[
         )",
         "DROP TABLE completion",
     ),
+    // Other schools' course identifiers, and which of our courses they
+    // correspond to (for crediting transfer students).
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'course_equivalents'",
+        "CREATE TABLE course_equivalents (
+            ext     TEXT PRIMARY KEY,
+            sym     TEXT NOT NULL REFERENCES courses(sym)
+                        ON UPDATE CASCADE ON DELETE CASCADE
+        )",
+        "DROP TABLE course_equivalents",
+    ),
     (
         "SELECT FROM information_schema.tables WHERE table_name = 'drafts'",
         "CREATE TABLE drafts (
             uname   TEXT REFERENCES students(uname),
             term    TEXT,
-            draft   TEXT
+            draft   TEXT,
+            saved   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
         )",
         "DROP TABLE drafts",
     ),
@@ -191,6 +291,442 @@ static SCHEMA: &[(&str, &str, &str)] = &[
         )",
         "DROP TABLE reports",
     ),
+    // When each report in the `reports` table was finalized, and how long
+    // it is.
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'report_meta'",
+        "CREATE TABLE report_meta (
+            uname       TEXT REFERENCES students(uname),
+            term        TEXT,
+            finalized   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            n_pages     INT,
+            PRIMARY KEY (uname, term)
+        )",
+        "DROP TABLE report_meta",
+    ),
+    // Who gets to see which parts of a report, and Teachers' notes for them.
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'report_visibility'",
+        "CREATE TABLE report_visibility (
+            uname   TEXT PRIMARY KEY REFERENCES students(uname),
+            facts   TEXT NOT NULL,
+            social  TEXT NOT NULL,
+            mastery TEXT NOT NULL
+        )",
+        "DROP TABLE report_visibility",
+    ),
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'report_notes'",
+        "CREATE TABLE report_notes (
+            uname       TEXT REFERENCES students(uname),
+            term        TEXT,
+            note        TEXT,
+            visibility  TEXT
+        )",
+        "DROP TABLE report_notes",
+    ),
+    // Finalized reports the Boss has released to students and parents, and
+    // who has downloaded them; see [`releases`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'report_releases'",
+        "CREATE TABLE report_releases (
+            uname       TEXT REFERENCES students(uname) ON DELETE CASCADE,
+            term        TEXT,
+            token       TEXT UNIQUE NOT NULL,
+            released_by TEXT NOT NULL,
+            released    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            PRIMARY KEY (uname, term)
+        )",
+        "DROP TABLE report_releases",
+    ),
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'report_downloads'",
+        "CREATE TABLE report_downloads (
+            uname       TEXT REFERENCES students(uname) ON DELETE CASCADE,
+            term        TEXT NOT NULL,
+            via         TEXT NOT NULL,
+            downloaded  TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+        )",
+        "DROP TABLE report_downloads",
+    ),
+    // Teachers who don't want the weekly summary email.
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'digest_optout'",
+        "CREATE TABLE digest_optout (
+            uname   TEXT PRIMARY KEY REFERENCES teachers(uname)
+        )",
+        "DROP TABLE digest_optout",
+    ),
+    // Aggregate per-course data, recomputed in the background; see
+    // [`deferred`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'course_stats'",
+        "CREATE TABLE course_stats (
+            sym         TEXT PRIMARY KEY,
+            n_students  BIGINT NOT NULL,
+            n_goals     BIGINT NOT NULL,
+            n_done      BIGINT NOT NULL
+        )",
+        "DROP TABLE course_stats",
+    ),
+    // Recent API requests that failed, kept for debugging; see [`journal`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'failed_requests'",
+        "CREATE TABLE failed_requests (
+            id          BIGSERIAL PRIMARY KEY,
+            at          TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            method      TEXT NOT NULL,
+            path        TEXT NOT NULL,
+            uname       TEXT,
+            action      TEXT,
+            status      SMALLINT NOT NULL,
+            body        TEXT,
+            response    TEXT
+        )",
+        "DROP TABLE failed_requests",
+    ),
+    // Who changed users, goals, and final reports; see [`audit`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'audit'",
+        "CREATE TABLE audit (
+            id      BIGSERIAL PRIMARY KEY,
+            at      TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            actor   TEXT NOT NULL,
+            action  TEXT NOT NULL,
+            target  TEXT NOT NULL,
+            detail  TEXT
+        )",
+        "DROP TABLE audit",
+    ),
+    // Teacher-defined labels for goals; see [`tags`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'tags'",
+        "CREATE TABLE tags (
+            id      BIGSERIAL PRIMARY KEY,
+            uname   TEXT NOT NULL REFERENCES teachers(uname) ON DELETE CASCADE,
+            name    TEXT NOT NULL,
+            UNIQUE (uname, name)
+        )",
+        "DROP TABLE tags",
+    ),
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'goal_tags'",
+        "CREATE TABLE goal_tags (
+            goal    BIGINT REFERENCES goals(id) ON DELETE CASCADE,
+            tag     BIGINT REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (goal, tag)
+        )",
+        "DROP TABLE goal_tags",
+    ),
+    // Rubric breakdowns of goal scores; see [`goals`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'goal_rubrics'",
+        "CREATE TABLE goal_rubrics (
+            goal        BIGINT PRIMARY KEY REFERENCES goals(id) ON DELETE CASCADE,
+            components  JSONB NOT NULL
+        )",
+        "DROP TABLE goal_rubrics",
+    ),
+    // Archived copies of completed goals that have been deleted.
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'deleted_goals'",
+        "CREATE TABLE deleted_goals (
+            id          BIGINT,
+            uname       TEXT,
+            sym         TEXT,
+            seq         SMALLINT,
+            custom      BIGINT,
+            review      BOOL,
+            incomplete  BOOL,
+            due         DATE,
+            done        DATE,
+            tries       SMALLINT,
+            score       TEXT,
+            rubric      JSONB,
+            part        SMALLINT,
+            parts       SMALLINT,
+            fraction    REAL,
+            deleted     TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            deleted_by  TEXT
+        )",
+        "DROP TABLE deleted_goals",
+    ),
+    // Parents who don't want progress emails; see [`optout`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'email_optout'",
+        "CREATE TABLE email_optout (
+            email   TEXT PRIMARY KEY,
+            since   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+        )",
+        "DROP TABLE email_optout",
+    ),
+    // Secret links to Teachers' read-only classroom dashboards; see
+    // [`kiosk`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'kiosk_tokens'",
+        "CREATE TABLE kiosk_tokens (
+            uname   TEXT PRIMARY KEY REFERENCES teachers(uname) ON DELETE CASCADE,
+            token   TEXT UNIQUE NOT NULL,
+            created TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+        )",
+        "DROP TABLE kiosk_tokens",
+    ),
+    // Students whose Teachers have been congratulated on their finishing
+    // all their goals this year; see [`notices`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'completion_notices'",
+        "CREATE TABLE completion_notices (
+            uname   TEXT PRIMARY KEY REFERENCES students(uname) ON DELETE CASCADE,
+            sent    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+        )",
+        "DROP TABLE completion_notices",
+    ),
+    // Students' requests that their goals be marked done; see
+    // [`requests`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'completion_requests'",
+        "CREATE TABLE completion_requests (
+            goal        BIGINT PRIMARY KEY REFERENCES goals(id) ON DELETE CASCADE,
+            requested   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+        )",
+        "DROP TABLE completion_requests",
+    ),
+    // Teachers' requests for printed chapter tests; see [`test_requests`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'test_requests'",
+        "CREATE TABLE test_requests (
+            id          BIGSERIAL PRIMARY KEY,
+            uname       TEXT NOT NULL REFERENCES students(uname) ON DELETE CASCADE,
+            sym         TEXT NOT NULL REFERENCES courses(sym) ON DELETE CASCADE,
+            seq         SMALLINT NOT NULL,
+            teacher     TEXT NOT NULL,
+            status      TEXT NOT NULL DEFAULT 'requested',
+            requested   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            updated     TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            UNIQUE (uname, sym, seq)
+        )",
+        "DROP TABLE test_requests",
+    ),
+    // Each student's lag at the start of each week; see [`lag`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'lag_history'",
+        "CREATE TABLE lag_history (
+            uname           TEXT REFERENCES students(uname) ON DELETE CASCADE,
+            week            DATE,
+            lag             REAL NOT NULL,
+            done_weight     REAL NOT NULL,
+            due_weight      REAL NOT NULL,
+            total_weight    REAL NOT NULL,
+            progress_weight REAL NOT NULL DEFAULT 0,
+            PRIMARY KEY (uname, week)
+        )",
+        "DROP TABLE lag_history",
+    ),
+    // Chapters individual students have been excused from; see [`waivers`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'waivers'",
+        "CREATE TABLE waivers (
+            uname       TEXT REFERENCES students(uname) ON DELETE CASCADE,
+            sym         TEXT,
+            seq         SMALLINT,
+            waived_by   TEXT NOT NULL,
+            PRIMARY KEY (uname, sym, seq)
+        )",
+        "DROP TABLE waivers",
+    ),
+    // Days students' families have said they'll be away; see [`absences`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'absences'",
+        "CREATE TABLE absences (
+            id          BIGSERIAL PRIMARY KEY,
+            uname       TEXT NOT NULL REFERENCES students(uname) ON DELETE CASCADE,
+            start_date  DATE NOT NULL,
+            end_date    DATE NOT NULL,
+            reason      TEXT NOT NULL DEFAULT '',
+            recorded_by TEXT NOT NULL,
+            CHECK (start_date <= end_date)
+        )",
+        "DROP TABLE absences",
+    ),
+    // What restricted Admins are allowed to do; see [`perms`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'admin_permissions'",
+        "CREATE TABLE admin_permissions (
+            uname   TEXT PRIMARY KEY REFERENCES users(uname)
+                        ON UPDATE CASCADE ON DELETE CASCADE,
+            perms   TEXT[] NOT NULL
+        )",
+        "DROP TABLE admin_permissions",
+    ),
+    // Messages the Boss has posted to Teachers' and students' pages; see
+    // [`announce`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'announcements'",
+        "CREATE TABLE announcements (
+            id      BIGSERIAL PRIMARY KEY,
+            message TEXT NOT NULL,
+            roles   TEXT[] NOT NULL,
+            starts  DATE NOT NULL,
+            ends    DATE NOT NULL
+        )",
+        "DROP TABLE announcements",
+    ),
+    // Students who have left Teachers' classes, for clients that only want
+    // to fetch what's changed; see [`sync`] (and [`sync::TRIGGERS`], which
+    // keep it filled).
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'student_departures'",
+        "CREATE TABLE student_departures (
+            uname       TEXT NOT NULL,
+            teacher     TEXT,
+            departed    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+        )",
+        "DROP TABLE student_departures",
+    ),
+    // Teachers practicing in sandboxes instead of on live data; see
+    // [`sandbox`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'training_mode'",
+        "CREATE TABLE training_mode (
+            uname   TEXT PRIMARY KEY REFERENCES teachers(uname) ON DELETE CASCADE,
+            since   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+        )",
+        "DROP TABLE training_mode",
+    ),
+    // Everything the yearly nuke deleted, one dump per nuke; see [`yearly`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'year_archives'",
+        "CREATE TABLE year_archives (
+            id          BIGSERIAL PRIMARY KEY,
+            year        TEXT NOT NULL,
+            archived    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            dump        TEXT NOT NULL
+        )",
+        "DROP TABLE year_archives",
+    ),
+    // Outbound webhooks and their deliveries; see [`webhooks`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'webhooks'",
+        "CREATE TABLE webhooks (
+            id      BIGSERIAL PRIMARY KEY,
+            url     TEXT NOT NULL,
+            secret  TEXT NOT NULL,
+            events  TEXT[] NOT NULL,
+            active  BOOL NOT NULL DEFAULT true
+        )",
+        "DROP TABLE webhooks",
+    ),
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'webhook_deliveries'",
+        "CREATE TABLE webhook_deliveries (
+            id              BIGSERIAL PRIMARY KEY,
+            hook            BIGINT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+            event           TEXT NOT NULL,
+            payload         TEXT NOT NULL,
+            created         TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            status          TEXT NOT NULL DEFAULT 'pending',
+            attempts        SMALLINT NOT NULL DEFAULT 0,
+            next_attempt    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            response        SMALLINT,
+            error           TEXT
+        )",
+        "DROP TABLE webhook_deliveries",
+    ),
+    // Email waiting to be sent (or that couldn't be); see [`outbox`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'outbox'",
+        "CREATE TABLE outbox (
+            id              BIGSERIAL PRIMARY KEY,
+            about           TEXT NOT NULL,
+            email           TEXT NOT NULL,
+            created         TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            status          TEXT NOT NULL DEFAULT 'pending',
+            attempts        SMALLINT NOT NULL DEFAULT 0,
+            next_attempt    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            error           TEXT
+        )",
+        "DROP TABLE outbox",
+    ),
+    // Schools' own versions of the report templates; see
+    // [`report_templates`].
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'report_templates'",
+        "CREATE TABLE report_templates (
+            id          BIGSERIAL PRIMARY KEY,
+            kind        TEXT NOT NULL,
+            label       TEXT NOT NULL,
+            body        TEXT NOT NULL,
+            uploaded    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+            uploaded_by TEXT NOT NULL,
+            active      BOOL NOT NULL DEFAULT false
+        )",
+        "DROP TABLE report_templates",
+    ),
+    // Key-value metadata about the database itself (like the environment
+    // tag; see [`Store::claim_environment`]).
+    (
+        "SELECT FROM information_schema.tables WHERE table_name = 'meta'",
+        "CREATE TABLE meta (
+            key     TEXT PRIMARY KEY,
+            value   TEXT NOT NULL
+        )",
+        "DROP TABLE meta",
+    ),
+];
+
+/**
+Version of the database layout this binary expects; recorded in the `meta`
+table under the key `schema_version`.
+
+Adding a table to [`SCHEMA`] doesn't need a new version, because
+[`Store::ensure_db_schema`] creates missing tables. Changing an existing
+table does: bump this, and add the statements that make the change to
+[`MIGRATIONS`].
+*/
+pub const SCHEMA_VERSION: i32 = 6;
+
+/**
+Statements to upgrade the database from each schema version to the next:
+`MIGRATIONS[n]` takes version `n` to version `n + 1`. (Missing tables are
+created before these run.)
+
+Databases from before schema versions were recorded are version 0.
+*/
+static MIGRATIONS: &[&[&str]] = &[
+    // 0 -> 1: Only new tables.
+    &[],
+    // 1 -> 2: Goals can be split into parts.
+    &[
+        "ALTER TABLE goals
+            ADD COLUMN IF NOT EXISTS part SMALLINT,
+            ADD COLUMN IF NOT EXISTS parts SMALLINT,
+            ADD COLUMN IF NOT EXISTS fraction REAL",
+        "ALTER TABLE deleted_goals
+            ADD COLUMN IF NOT EXISTS part SMALLINT,
+            ADD COLUMN IF NOT EXISTS parts SMALLINT,
+            ADD COLUMN IF NOT EXISTS fraction REAL",
+    ],
+    // 2 -> 3: Courses can have scheduling constraints.
+    &["ALTER TABLE courses
+        ADD COLUMN IF NOT EXISTS min_gap SMALLINT,
+        ADD COLUMN IF NOT EXISTS max_per_week SMALLINT"],
+    // 3 -> 4: Goals and students know when they were last changed.
+    &[
+        "ALTER TABLE goals ADD COLUMN IF NOT EXISTS
+            updated_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')",
+        "ALTER TABLE students ADD COLUMN IF NOT EXISTS
+            updated_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')",
+    ],
+    // 4 -> 5: Drafts know when they were saved, so they can expire.
+    &["ALTER TABLE drafts ADD COLUMN IF NOT EXISTS
+        saved TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')"],
+    // 5 -> 6: Unfinished goals can record partial progress, which the lag
+    // history keeps track of.
+    &[
+        "ALTER TABLE goals ADD COLUMN IF NOT EXISTS progress SMALLINT",
+        "ALTER TABLE lag_history ADD COLUMN IF NOT EXISTS
+            progress_weight REAL NOT NULL DEFAULT 0",
+    ],
 ];
 
 /**
@@ -256,6 +792,12 @@ connections or using connection pools, but it is _way simpler_ to implement,
 so unless and until there's a burning need to change it, it stays
 inefficient but simple.
 
+The exception is a handful of frequently-run queries, which use
+[`Store::cached_connect`] to reuse connections (and the statements already
+prepared on them); see [`CachedClient`]. Busier deployments can instead have
+every interaction take its connection from a pool (see
+[`Store::set_pool_size`]).
+
 Some methods take as one of their arguments an [`&Transaction`](Transaction). These are
 meant to be used in operations that may require multiple queries or
 intermediate steps. For example, adding a new user to the system requires
@@ -273,6 +815,17 @@ pub struct Store {
     connection_string: String,
     salt_chars: Vec<char>,
     salt_length: usize,
+    environment: String,
+    idle: cached::IdleConns,
+    /// `None` unless connections are pooled; see [`Store::set_pool_size`].
+    pool: Option<Pool>,
+    /// Where connections look for tables, if not just `public`; see
+    /// [`Store::sandboxed`].
+    search_path: Option<String>,
+    /// Rows per statement when inserting many students.
+    insert_chunk: usize,
+    /// Statements in flight at once when inserting many students.
+    insert_concurrency: usize,
 }
 
 impl Store {
@@ -286,6 +839,42 @@ impl Store {
             connection_string,
             salt_chars,
             salt_length,
+            environment: crate::DEFAULT_ENVIRONMENT.to_owned(),
+            idle: cached::IdleConns::default(),
+            pool: None,
+            search_path: None,
+            insert_chunk: DEFAULT_INSERT_CHUNK,
+            insert_concurrency: DEFAULT_INSERT_CONCURRENCY,
+        }
+    }
+
+    /// Set the environment tag this `Store` expects to find in its database.
+    pub fn set_environment(&mut self, environment: &str) {
+        self.environment = environment.to_owned();
+    }
+
+    /// Keep a pool of up to `size` connections, instead of opening a new one
+    /// for each interaction (see [`pool`](crate::pool)). Zero turns pooling
+    /// off.
+    pub fn set_pool_size(&mut self, size: usize) -> Result<(), DbError> {
+        self.pool = match size {
+            0 => None,
+            n => Some(new_pool(&self.connection_string, n)?),
+        };
+        Ok(())
+    }
+
+    /// Insert many students at once (see [`Store::insert_students`]) in
+    /// statements of `chunk` rows each, with at most `concurrency` of them
+    /// in flight at a time.
+    ///
+    /// Zeroes are quietly ignored.
+    pub fn set_bulk_insert(&mut self, chunk: usize, concurrency: usize) {
+        if chunk > 0 {
+            self.insert_chunk = chunk;
+        }
+        if concurrency > 0 {
+            self.insert_concurrency = concurrency;
         }
     }
 
@@ -318,7 +907,8 @@ impl Store {
     }
 
     /**
-    Return a connection to the underlying Postgres store.
+    Return a connection to the underlying Postgres store (from the pool, if
+    there is one).
 
     This connection should only ever be used to instantiate a
     [`Transaction`] for use in one of the `Store` methods that requires one:
@@ -330,12 +920,20 @@ impl Store {
     ```
 
     */
-    pub async fn connect(&self) -> Result<Client, DbError> {
+    pub async fn connect(&self) -> Result<PgClient, DbError> {
         log::trace!(
             "Store::connect() called w/connection string {:?}",
             &self.connection_string
         );
 
+        if let Some(pool) = &self.pool {
+            return pool
+                .get()
+                .await
+                .map(PgClient::Pooled)
+                .map_err(|e| DbError::from(format!("Data DB: {}", &e)).annotate("Unable to connect"));
+        }
+
         match tokio_postgres::connect(&self.connection_string, NoTls).await {
             Ok((client, connection)) => {
                 log::trace!("    ...connection successful.");
@@ -346,7 +944,13 @@ impl Store {
                         log::trace!("tokio connection runtime drops.");
                     }
                 });
-                Ok(client)
+                if let Some(path) = &self.search_path {
+                    client
+                        .batch_execute(&format!("SET search_path TO {}", path))
+                        .await
+                        .map_err(|e| DbError::from(e).annotate("Unable to set search path"))?;
+                }
+                Ok(PgClient::Direct(client))
             }
             Err(e) => {
                 let dberr = DbError::from(e);
@@ -366,14 +970,35 @@ impl Store {
     pub async fn ensure_db_schema(&self) -> Result<(), DbError> {
         log::trace!("Store::ensure_db_schema() called.");
 
+        self.forget_statements();
         let mut client = self.connect().await?;
         let t = client
             .transaction()
             .await
             .map_err(|e| DbError::from(e).annotate("Data DB unable to begin transaction"))?;
 
+        Store::create_missing_tables(&t).await?;
+        // A brand new database is already current. (Older ones should have
+        // been brought up to date by `Store::migrate_schema` before this,
+        // and so already have a version.)
+        t.execute(
+            "INSERT INTO meta (key, value) VALUES ('schema_version', $1)
+            ON CONFLICT DO NOTHING",
+            &[&SCHEMA_VERSION.to_string()],
+        )
+        .await
+        .map_err(|e| DbError::from(e).annotate("Unable to record schema version"))?;
+
+        t.commit()
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error committing transaction"))
+    }
+
+    async fn create_missing_tables(t: &Transaction<'_>) -> Result<(), DbError> {
         for (test_stmt, create_stmt, _) in SCHEMA.iter() {
-            if t.query_opt(test_stmt.to_owned(), &[]).await?.is_none() {
+            // Sandboxes have tables with the same names in other schemas.
+            let test_stmt = format!("{} AND table_schema = current_schema()", test_stmt);
+            if t.query_opt(&test_stmt, &[]).await?.is_none() {
                 log::info!(
                     "{:?} returned no results; attempting to insert table.",
                     test_stmt
@@ -381,10 +1006,218 @@ impl Store {
                 t.execute(create_stmt.to_owned(), &[]).await?;
             }
         }
+        for (test_stmt, create_stmt, _) in sync::TRIGGERS.iter() {
+            if t.query_opt(test_stmt.to_owned(), &[]).await?.is_none() {
+                log::info!(
+                    "{:?} returned no results; attempting to create trigger.",
+                    test_stmt
+                );
+                t.execute(create_stmt.to_owned(), &[]).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+    Return the schema version recorded in the database, `None` if the
+    database is empty, or 0 if it predates recording schema versions.
+    */
+    pub async fn read_schema_version(&self) -> Result<Option<i32>, DbError> {
+        log::trace!("Store::read_schema_version() called.");
+
+        let client = self.connect().await?;
+        if client
+            .query_opt(
+                "SELECT FROM information_schema.tables WHERE table_name = 'meta'",
+                &[],
+            )
+            .await?
+            .is_none()
+        {
+            let existing = client
+                .query_opt(
+                    "SELECT FROM information_schema.tables WHERE table_name = 'users'",
+                    &[],
+                )
+                .await?;
+            return Ok(existing.map(|_| 0));
+        }
+
+        let opt_row = client
+            .query_opt("SELECT value FROM meta WHERE key = 'schema_version'", &[])
+            .await?;
+        match opt_row {
+            None => Ok(Some(0)),
+            Some(row) => {
+                let value: String = row.try_get("value")?;
+                let version = value.parse::<i32>().map_err(|e| {
+                    DbError(format!("Bad schema version {:?} in Data DB: {}", &value, &e))
+                })?;
+                Ok(Some(version))
+            }
+        }
+    }
+
+    /**
+    Ensure the database's schema is the one this binary expects
+    ([`SCHEMA_VERSION`]) before anything else touches it.
+
+    A database with a newer schema is always an error. So is an older one,
+    unless `auto_migrate` is `true`, in which case it gets upgraded (see
+    [`MIGRATIONS`]). This should be called at startup, before
+    [`Store::ensure_db_schema`].
+    */
+    pub async fn migrate_schema(&self, auto_migrate: bool) -> Result<(), DbError> {
+        log::trace!("Store::migrate_schema( {} ) called.", &auto_migrate);
+
+        let version = match self.read_schema_version().await? {
+            // It'll be created from scratch.
+            None => return Ok(()),
+            Some(n) if n == SCHEMA_VERSION => return Ok(()),
+            Some(n) if n > SCHEMA_VERSION => {
+                return Err(DbError(format!(
+                    "Data DB schema is version {}, but this binary only understands \
+                    version {}. It has probably been used by a newer version of this \
+                    software; deploy that instead.",
+                    &n, &SCHEMA_VERSION
+                )));
+            }
+            Some(n) if n < 0 => {
+                return Err(DbError(format!("Nonsensical Data DB schema version {}.", &n)));
+            }
+            Some(n) => n,
+        };
+
+        if !auto_migrate {
+            return Err(DbError(format!(
+                "Data DB schema is version {}, but this binary expects version {}. Back up \
+                the database, then set auto_migrate = true in the configuration file to \
+                upgrade it.",
+                &version, &SCHEMA_VERSION
+            )));
+        }
+
+        self.check_environment().await?;
+        let mut client = self.connect().await?;
+        let t = client
+            .transaction()
+            .await
+            .map_err(|e| DbError::from(e).annotate("Data DB unable to begin transaction"))?;
+
+        Store::create_missing_tables(&t).await?;
+        for (n, stmts) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            log::info!("Migrating Data DB schema from version {} to {}.", n, n + 1);
+            for stmt in stmts.iter() {
+                t.execute(stmt.to_owned(), &[]).await.map_err(|e| {
+                    DbError::from(e).annotate(&format!("Error migrating to version {}", n + 1))
+                })?;
+            }
+        }
+        t.execute(
+            "INSERT INTO meta (key, value) VALUES ('schema_version', $1)
+            ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value",
+            &[&SCHEMA_VERSION.to_string()],
+        )
+        .await
+        .map_err(|e| DbError::from(e).annotate("Unable to record schema version"))?;
 
         t.commit()
             .await
-            .map_err(|e| DbError::from(e).annotate("Error committing transaction"))
+            .map_err(|e| DbError::from(e).annotate("Error committing migration"))?;
+        log::info!("Data DB schema is now version {}.", &SCHEMA_VERSION);
+        Ok(())
+    }
+
+    /// Read the environment tag stored in the database, if there is one.
+    async fn read_environment(&self, client: &Client) -> Result<Option<String>, DbError> {
+        if client
+            .query_opt(
+                "SELECT FROM information_schema.tables WHERE table_name = 'meta'",
+                &[],
+            )
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+
+        let opt_row = client
+            .query_opt("SELECT value FROM meta WHERE key = 'environment'", &[])
+            .await?;
+        Ok(opt_row.map(|row| row.get("value")))
+    }
+
+    /**
+    Ensure the database is tagged as belonging to this `Store`'s environment.
+
+    An untagged database will be tagged; a database tagged with a different
+    environment is an error. This should be called at startup, after
+    [`Store::ensure_db_schema`].
+    */
+    pub async fn claim_environment(&self) -> Result<(), DbError> {
+        log::trace!("Store::claim_environment() called.");
+
+        let client = self.connect().await?;
+        match self.read_environment(&client).await? {
+            Some(env) if env == self.environment => Ok(()),
+            Some(env) => Err(DbError(format!(
+                "Data DB belongs to environment {:?}, but this is {:?}.",
+                &env, &self.environment
+            ))),
+            None => {
+                client
+                    .execute(
+                        "INSERT INTO meta (key, value) VALUES ('environment', $1)",
+                        &[&self.environment],
+                    )
+                    .await
+                    .map_err(|e| DbError::from(e).annotate("Unable to tag environment"))?;
+                log::info!("Data DB tagged as {:?} environment.", &self.environment);
+                Ok(())
+            }
+        }
+    }
+
+    /**
+    Return an error if the database is tagged with an environment other than
+    this `Store`'s. An untagged database passes.
+
+    Anything that destroys data wholesale should call this first.
+    */
+    pub async fn check_environment(&self) -> Result<(), DbError> {
+        log::trace!("Store::check_environment() called.");
+
+        let client = self.connect().await?;
+        match self.read_environment(&client).await? {
+            Some(env) if env != self.environment => Err(DbError(format!(
+                "Refusing to touch Data DB belonging to environment {:?} from {:?}.",
+                &env, &self.environment
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Return the contents of the `meta` table and the number of rows in each
+    /// table.
+    pub async fn get_sanity(&self) -> Result<DbSanity, DbError> {
+        log::trace!("Store::get_sanity() called.");
+
+        let client = self.connect().await?;
+        let mut sanity = DbSanity::default();
+        for row in client.query("SELECT key, value FROM meta", &[]).await?.iter() {
+            sanity.meta.insert(row.try_get("key")?, row.try_get("value")?);
+        }
+        for (_, _, drop_stmt) in SCHEMA.iter() {
+            let table = drop_stmt.trim_start_matches("DROP TABLE ");
+            let row = client
+                .query_one(&format!("SELECT COUNT(*) FROM {}", table), &[])
+                .await
+                .map_err(|e| DbError::from(e).annotate(&format!("Error counting {}", table)))?;
+            sanity.row_counts.insert(table.to_owned(), row.try_get(0)?);
+        }
+
+        Ok(sanity)
     }
 
     /**
@@ -397,9 +1230,15 @@ impl Store {
     pub async fn nuke_database(&self) -> Result<(), DbError> {
         log::trace!("Store::nuke_database() called.");
 
+        self.check_environment().await?;
+        self.forget_statements();
+        if let Err(e) = self.drop_sandboxes().await {
+            log::error!("Error dropping sandboxes: {}", &e.display());
+        }
         let client = self.connect().await?;
 
-        for (_, _, drop_stmt) in SCHEMA.iter().rev() {
+        let drop_stmts = sync::TRIGGERS.iter().rev().chain(SCHEMA.iter().rev());
+        for (_, _, drop_stmt) in drop_stmts {
             if let Err(e) = client.execute(drop_stmt.to_owned(), &[]).await {
                 let err = DbError::from(e);
                 log::error!("Error dropping: {:?}: {}", &drop_stmt, &err.display());
@@ -429,12 +1268,60 @@ mod tests {
     */
     use super::*;
     use crate::tests::ensure_logging;
+    use crate::user::Student;
 
     use serial_test::serial;
 
     pub static TEST_CONNECTION: &str =
         "host=localhost user=camp_test password='camp_test' dbname=camp_store_test";
 
+    /// Teachers other modules' tests can add with [`insert_users`].
+    static TEACHERS: &[(&str, &str, &str)] = &[
+        ("berro", "berro@camelotacademy.org", "Mr Berro"),
+        ("jenny", "jenny@camelotacademy.org", "Ms Jenny"),
+        ("adams", "adams@camelotacademy.org", "Ms Adams"),
+    ];
+
+    /// Students other modules' tests can add with [`insert_users`]. (Their
+    /// teachers here get replaced.)
+    static STUDENTS_CSV: &str = "#uname, last, rest, email, parent, teacher
+    frog, Frog, Frederick, fred.frog@gmail.com, ferd.frog@gmail.com, berro
+    zack, Milk, Zachary, milktruck@gmail.com, handsome.dave@gmail.com, berro
+    toad, Toad, Theodore, ted.toad@gmail.com, tad.toad@gmail.com, berro";
+
+    /**
+    Insert the named `teachers` (from [`TEACHERS`]) and `students` (from
+    [`STUDENTS_CSV`]), for tests that need some users around. Each student
+    is given as `(uname, teacher)`.
+    */
+    pub async fn insert_users(db: &Store, teachers: &[&str], students: &[(&str, &str)]) {
+        let mut studs: Vec<Student> = Vec::with_capacity(students.len());
+        for s in Student::vec_from_csv_reader(std::io::Cursor::new(STUDENTS_CSV.as_bytes()))
+            .unwrap()
+            .into_iter()
+        {
+            if let Some((_, teacher)) = students.iter().find(|(u, _)| *u == s.base.uname) {
+                studs.push(Student {
+                    teacher: teacher.to_string(),
+                    ..s
+                });
+            }
+        }
+        assert_eq!(studs.len(), students.len(), "unknown test student");
+
+        let mut client = db.connect().await.unwrap();
+        let t = client.transaction().await.unwrap();
+        for uname in teachers.iter() {
+            let (uname, email, name) = TEACHERS
+                .iter()
+                .find(|(u, _, _)| u == uname)
+                .expect("unknown test teacher");
+            db.insert_teacher(&t, uname, email, name).await.unwrap();
+        }
+        db.insert_students(&t, &mut studs).await.unwrap();
+        t.commit().await.unwrap();
+    }
+
     /**
     This function is for getting the database back in a blank slate state if
     a test panics partway through and leaves it munged.
@@ -461,4 +1348,105 @@ mod tests {
         db.ensure_db_schema().await.unwrap();
         db.nuke_database().await.unwrap();
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn environment_markers() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        db.claim_environment().await.unwrap();
+        // Claiming twice is fine.
+        db.claim_environment().await.unwrap();
+        db.check_environment().await.unwrap();
+
+        let mut prod = Store::new(TEST_CONNECTION.to_owned());
+        prod.set_environment("production");
+        assert!(prod.claim_environment().await.is_err());
+        assert!(prod.check_environment().await.is_err());
+        assert!(prod.nuke_database().await.is_err());
+
+        db.nuke_database().await.unwrap();
+        // With the tag gone, nothing is stopping anyone.
+        prod.check_environment().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn sanity() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        db.claim_environment().await.unwrap();
+
+        let sanity = db.get_sanity().await.unwrap();
+        assert_eq!(
+            sanity.meta.get("schema_version"),
+            Some(&SCHEMA_VERSION.to_string())
+        );
+        assert_eq!(
+            sanity.meta.get("environment").map(|s| s.as_str()),
+            Some(crate::DEFAULT_ENVIRONMENT)
+        );
+        assert_eq!(sanity.row_counts.len(), SCHEMA.len());
+        assert_eq!(sanity.row_counts["meta"], 2);
+
+        db.nuke_database().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn schema_versions() {
+        ensure_logging();
+
+        assert_eq!(MIGRATIONS.len(), SCHEMA_VERSION as usize);
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.nuke_database().await.unwrap();
+        assert_eq!(db.read_schema_version().await.unwrap(), None);
+        db.migrate_schema(false).await.unwrap();
+        db.ensure_db_schema().await.unwrap();
+        assert_eq!(
+            db.read_schema_version().await.unwrap(),
+            Some(SCHEMA_VERSION)
+        );
+        db.migrate_schema(false).await.unwrap();
+
+        let client = db.connect().await.unwrap();
+        client
+            .execute(
+                "UPDATE meta SET value = $1 WHERE key = 'schema_version'",
+                &[&(SCHEMA_VERSION + 1).to_string()],
+            )
+            .await
+            .unwrap();
+        assert!(db.migrate_schema(true).await.is_err());
+
+        // Pretend the database predates schema versions.
+        client
+            .execute("DELETE FROM meta WHERE key = 'schema_version'", &[])
+            .await
+            .unwrap();
+        client.execute("DROP TABLE kiosk_tokens", &[]).await.unwrap();
+        assert_eq!(db.read_schema_version().await.unwrap(), Some(0));
+        assert!(db.migrate_schema(false).await.is_err());
+        db.migrate_schema(true).await.unwrap();
+        assert_eq!(
+            db.read_schema_version().await.unwrap(),
+            Some(SCHEMA_VERSION)
+        );
+        // The missing table is back.
+        assert!(client
+            .query_opt(
+                "SELECT FROM information_schema.tables WHERE table_name = 'kiosk_tokens'",
+                &[],
+            )
+            .await
+            .unwrap()
+            .is_some());
+
+        db.nuke_database().await.unwrap();
+    }
 }
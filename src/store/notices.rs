@@ -0,0 +1,136 @@
+/*!
+`Store` methods for keeping track of which students' Teachers have been
+congratulated on their finishing the year's goals (see
+[`completion`](crate::inter::completion)).
+
+```sql
+CREATE TABLE completion_notices (
+    uname   TEXT PRIMARY KEY REFERENCES students(uname) ON DELETE CASCADE,
+    sent    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+);
+```
+
+A notice is "claimed" before it's sent, so that two goal updates racing
+each other can't both send one. When email goes through the outbox, the
+notice is claimed and queued in one go (see
+[`Store::queue_completion_notice`]), so a claimed notice always gets sent.
+The table is cleared by the yearly nuke, so each student can only be
+celebrated once per year.
+*/
+use tokio_postgres::Transaction;
+
+use super::{DbError, Store};
+
+impl Store {
+    /// Record that the completion notice for the student `uname` is being
+    /// sent. Returns `false` if one already has been (this year).
+    pub async fn claim_completion_notice(&self, uname: &str) -> Result<bool, DbError> {
+        log::trace!("Store::claim_completion_notice( {:?} ) called.", uname);
+
+        let client = self.connect().await?;
+        let n = client
+            .execute(
+                "INSERT INTO completion_notices (uname) VALUES ($1)
+                ON CONFLICT DO NOTHING",
+                &[&uname],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error claiming completion notice"))?;
+
+        Ok(n == 1)
+    }
+
+    /// Claim the completion notice for the student `uname` and queue the
+    /// rendered JSON `email` to send it, in one transaction. Returns `false`
+    /// (and queues nothing) if it had already been claimed.
+    pub async fn queue_completion_notice(&self, uname: &str, email: &str) -> Result<bool, DbError> {
+        log::trace!(
+            "Store::queue_completion_notice( {:?}, [ {} bytes ] ) called.",
+            uname,
+            email.len()
+        );
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+        let n = t
+            .execute(
+                "INSERT INTO completion_notices (uname) VALUES ($1)
+                ON CONFLICT DO NOTHING",
+                &[&uname],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error claiming completion notice"))?;
+        if n != 1 {
+            return Ok(false);
+        }
+        Store::queue_email_in(&t, uname, email).await?;
+        t.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Forget that the completion notice for the student `uname` was sent
+    /// (because sending it failed), so it can be claimed again.
+    pub async fn release_completion_notice(&self, uname: &str) -> Result<(), DbError> {
+        log::trace!("Store::release_completion_notice( {:?} ) called.", uname);
+
+        let client = self.connect().await?;
+        client
+            .execute("DELETE FROM completion_notices WHERE uname = $1", &[&uname])
+            .await?;
+
+        Ok(())
+    }
+
+    /// Forget all the completion notices sent this year.
+    pub async fn yearly_clear_completion_notices(t: &Transaction<'_>) -> Result<(), DbError> {
+        log::trace!("Store::yearly_clear_completion_notices( [ T ] ) called.");
+
+        t.execute("DELETE FROM completion_notices", &[]).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn claim_once() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        insert_users(&db, &["berro"], &[("frog", "berro")]).await;
+
+        assert!(db.claim_completion_notice("frog").await.unwrap());
+        assert!(!db.claim_completion_notice("frog").await.unwrap());
+        db.release_completion_notice("frog").await.unwrap();
+        assert!(db.claim_completion_notice("frog").await.unwrap());
+
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            Store::yearly_clear_completion_notices(&t).await.unwrap();
+            t.commit().await.unwrap();
+        }
+        assert!(db.claim_completion_notice("frog").await.unwrap());
+
+        // Claiming and queueing go together.
+        assert!(!db.queue_completion_notice("frog", "{}").await.unwrap());
+        assert!(db.get_due_emails(10).await.unwrap().is_empty());
+        db.release_completion_notice("frog").await.unwrap();
+        assert!(db.queue_completion_notice("frog", "{}").await.unwrap());
+        assert_eq!(db.get_due_emails(10).await.unwrap().len(), 1);
+        assert!(!db.claim_completion_notice("frog").await.unwrap());
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+/*!
+`Store` methods for parents' email communication preferences.
+
+Parents who follow the unsubscribe link in a progress email and confirm
+(see [`unsubscribe`](crate::inter::unsubscribe)) get recorded here, by email
+address, and don't get any more progress emails.
+
+```sql
+CREATE TABLE email_optout (
+    email   TEXT PRIMARY KEY,
+    since   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+);
+```
+
+Addresses are stored lowercased, because that's how people type them.
+
+The secret used to sign unsubscribe links is also kept here (in the `meta`
+table), so links remain valid across restarts.
+*/
+use std::collections::HashSet;
+
+use rand::{distributions::Alphanumeric, Rng};
+
+use super::{DbError, Store};
+
+/// Length of a newly-generated unsubscribe link secret.
+const SECRET_LENGTH: usize = 32;
+
+impl Store {
+    /// Return the (lowercased) email addresses of all parents who have
+    /// unsubscribed from progress emails.
+    pub async fn get_email_optouts(&self) -> Result<HashSet<String>, DbError> {
+        log::trace!("Store::get_email_optouts() called.");
+
+        let client = self.connect().await?;
+        let rows = client.query("SELECT email FROM email_optout", &[]).await?;
+
+        let mut emails: HashSet<String> = HashSet::with_capacity(rows.len());
+        for row in rows.iter() {
+            emails.insert(row.try_get("email")?);
+        }
+
+        Ok(emails)
+    }
+
+    /// Unsubscribe the given address from (if `opt_out` is `true`) or
+    /// resubscribe it to progress emails.
+    pub async fn set_email_optout(&self, email: &str, opt_out: bool) -> Result<(), DbError> {
+        log::trace!(
+            "Store::set_email_optout( {:?}, {} ) called.",
+            email,
+            &opt_out
+        );
+
+        let email = email.to_lowercase();
+        let client = self.connect().await?;
+        if opt_out {
+            client
+                .execute(
+                    "INSERT INTO email_optout (email) VALUES ($1)
+                    ON CONFLICT DO NOTHING",
+                    &[&email],
+                )
+                .await?;
+        } else {
+            client
+                .execute("DELETE FROM email_optout WHERE email = $1", &[&email])
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Return the secret used to sign unsubscribe links, generating (and
+    /// storing) one if it doesn't exist yet.
+    pub async fn get_unsubscribe_secret(&self) -> Result<String, DbError> {
+        log::trace!("Store::get_unsubscribe_secret() called.");
+
+        let client = self.connect().await?;
+        let secret: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SECRET_LENGTH)
+            .map(char::from)
+            .collect();
+
+        client
+            .execute(
+                "INSERT INTO meta (key, value) VALUES ('unsubscribe_secret', $1)
+                ON CONFLICT DO NOTHING",
+                &[&secret],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Unable to store unsubscribe secret"))?;
+
+        let row = client
+            .query_one(
+                "SELECT value FROM meta WHERE key = 'unsubscribe_secret'",
+                &[],
+            )
+            .await?;
+        Ok(row.try_get("value")?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn optouts() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+
+        let secret = db.get_unsubscribe_secret().await.unwrap();
+        assert_eq!(secret.len(), SECRET_LENGTH);
+        assert_eq!(db.get_unsubscribe_secret().await.unwrap(), secret);
+
+        db.set_email_optout("Ferd.Frog@gmail.com", true)
+            .await
+            .unwrap();
+        db.set_email_optout("ferd.frog@gmail.com", true)
+            .await
+            .unwrap();
+        db.set_email_optout("handsome.dave@gmail.com", true)
+            .await
+            .unwrap();
+        db.set_email_optout("handsome.dave@gmail.com", false)
+            .await
+            .unwrap();
+
+        let optouts = db.get_email_optouts().await.unwrap();
+        assert_eq!(optouts.len(), 1);
+        assert!(optouts.contains("ferd.frog@gmail.com"));
+
+        db.nuke_database().await.unwrap();
+    }
+}
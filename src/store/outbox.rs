@@ -0,0 +1,249 @@
+/*!
+`Store` methods for the queue of outgoing email (see
+[`email::Outbox`](crate::email::Outbox)).
+
+```sql
+CREATE TABLE outbox (
+    id              BIGSERIAL PRIMARY KEY,
+    about           TEXT NOT NULL,  /* who it's about, for error messages */
+    email           TEXT NOT NULL,  /* a rendered JSON email template */
+    created         TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    status          TEXT NOT NULL DEFAULT 'pending',    /* or 'failed' */
+    attempts        SMALLINT NOT NULL DEFAULT 0,
+    next_attempt    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    error           TEXT    /* what went wrong with the last attempt */
+);
+```
+
+An email is queued in the same transaction as whatever it's about (when
+there is such a transaction; see [`Store::queue_email_in`]), so it can't
+be lost between the two. Emails are deleted once they're sent, so what's
+left is what's waiting and what has failed for good.
+*/
+use serde::Serialize;
+use tokio_postgres::{Row, Transaction};
+
+use super::{DbError, Store};
+
+/// An email due to be sent.
+#[derive(Debug)]
+pub struct QueuedEmail {
+    pub id: i64,
+    pub about: String,
+    /// The rendered JSON email template; see [`Email`](crate::email::Email).
+    pub email: String,
+    /// Number of times sending it has already failed.
+    pub attempts: i16,
+}
+
+/// An email that couldn't be sent, and has been given up on.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FailedEmail {
+    pub id: i64,
+    pub about: String,
+    /// The rendered JSON email template; see [`Email`](crate::email::Email).
+    pub email: String,
+    /// When it was queued (UTC), as `YYYY-MM-DD HH:MM:SS`.
+    pub created: String,
+    pub attempts: i16,
+    /// What went wrong with the last attempt.
+    pub error: Option<String>,
+}
+
+fn failed_from_row(row: &Row) -> Result<FailedEmail, DbError> {
+    Ok(FailedEmail {
+        id: row.try_get("id")?,
+        about: row.try_get("about")?,
+        email: row.try_get("email")?,
+        created: row.try_get("created")?,
+        attempts: row.try_get("attempts")?,
+        error: row.try_get("error")?,
+    })
+}
+
+impl Store {
+    /// Queue the rendered JSON `email` (about `about`) to be sent, as part
+    /// of the transaction `t`.
+    pub async fn queue_email_in(
+        t: &Transaction<'_>,
+        about: &str,
+        email: &str,
+    ) -> Result<(), DbError> {
+        log::trace!(
+            "Store::queue_email_in( [ T ], {:?}, [ {} bytes ] ) called.",
+            about,
+            email.len()
+        );
+
+        t.execute(
+            "INSERT INTO outbox (about, email) VALUES ($1, $2)",
+            &[&about, &email],
+        )
+        .await
+        .map_err(|e| DbError::from(e).annotate("Error queueing email"))?;
+
+        Ok(())
+    }
+
+    /// Queue the rendered JSON `email` (about `about`) to be sent.
+    pub async fn queue_email(&self, about: &str, email: &str) -> Result<(), DbError> {
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+        Store::queue_email_in(&t, about, email).await?;
+        t.commit().await?;
+        Ok(())
+    }
+
+    /// Return up to `limit` emails that are due to be sent, longest-waiting
+    /// first.
+    pub async fn get_due_emails(&self, limit: i64) -> Result<Vec<QueuedEmail>, DbError> {
+        log::trace!("Store::get_due_emails( {} ) called.", &limit);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT id, about, email, attempts FROM outbox
+                WHERE status = 'pending' AND next_attempt <= (now() AT TIME ZONE 'UTC')
+                ORDER BY next_attempt, id
+                LIMIT $1",
+                &[&limit],
+            )
+            .await?;
+
+        let mut due: Vec<QueuedEmail> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            due.push(QueuedEmail {
+                id: row.try_get("id")?,
+                about: row.try_get("about")?,
+                email: row.try_get("email")?,
+                attempts: row.try_get("attempts")?,
+            });
+        }
+
+        Ok(due)
+    }
+
+    /// Take the email `id`, which has been sent, out of the outbox.
+    pub async fn record_email_sent(&self, id: i64) -> Result<(), DbError> {
+        log::trace!("Store::record_email_sent( {} ) called.", &id);
+
+        let client = self.connect().await?;
+        client
+            .execute("DELETE FROM outbox WHERE id = $1", &[&id])
+            .await?;
+
+        Ok(())
+    }
+
+    /**
+    Record that an attempt to send the email `id` failed with `error`.
+
+    It stays pending, due again in `retry_in` seconds, or fails for good if
+    `retry_in` is `None`.
+    */
+    pub async fn record_email_failure(
+        &self,
+        id: i64,
+        error: &str,
+        retry_in: Option<u64>,
+    ) -> Result<(), DbError> {
+        log::trace!(
+            "Store::record_email_failure( {}, {:?}, {:?} ) called.",
+            &id,
+            error,
+            &retry_in
+        );
+
+        let status = match retry_in {
+            Some(_) => "pending",
+            None => "failed",
+        };
+        let retry_secs = retry_in.unwrap_or(0) as f64;
+
+        let client = self.connect().await?;
+        client
+            .execute(
+                "UPDATE outbox SET
+                    status = $1, attempts = attempts + 1, error = $2,
+                    next_attempt = (now() AT TIME ZONE 'UTC') + make_interval(secs => $3)
+                WHERE id = $4",
+                &[&status, &error, &retry_secs, &id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Return the `limit` most recently queued emails that have failed for
+    /// good, newest first.
+    pub async fn get_failed_emails(&self, limit: i64) -> Result<Vec<FailedEmail>, DbError> {
+        log::trace!("Store::get_failed_emails( {} ) called.", &limit);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT id, about, email, attempts, error,
+                    to_char(created, 'YYYY-MM-DD HH24:MI:SS') AS created
+                FROM outbox WHERE status = 'failed'
+                ORDER BY id DESC
+                LIMIT $1",
+                &[&limit],
+            )
+            .await?;
+
+        rows.iter().map(failed_from_row).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn outbox() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+
+        db.queue_email("Frederick Frog", r#"{"to": []}"#).await.unwrap();
+        {
+            // Nothing's queued if the transaction doesn't go through.
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            Store::queue_email_in(&t, "Ronald Rabbit", "{}").await.unwrap();
+            t.rollback().await.unwrap();
+        }
+        db.queue_email("Sally Salamander", "{}").await.unwrap();
+
+        let due = db.get_due_emails(10).await.unwrap();
+        assert_eq!(due.len(), 2);
+        assert_eq!(&due[0].about, "Frederick Frog");
+        assert_eq!((due[0].email.as_str(), due[0].attempts), (r#"{"to": []}"#, 0));
+
+        db.record_email_failure(due[0].id, "Sendgrid returned 503.", Some(60))
+            .await
+            .unwrap();
+        db.record_email_sent(due[1].id).await.unwrap();
+        // The retry isn't due yet, and the sent one is gone.
+        assert!(db.get_due_emails(10).await.unwrap().is_empty());
+        assert!(db.get_failed_emails(10).await.unwrap().is_empty());
+
+        db.record_email_failure(due[0].id, "Sendgrid returned 503.", None)
+            .await
+            .unwrap();
+        let failed = db.get_failed_emails(10).await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!((failed[0].id, failed[0].attempts), (due[0].id, 2));
+        assert_eq!(failed[0].error.as_deref(), Some("Sendgrid returned 503."));
+        assert!(db.get_due_emails(10).await.unwrap().is_empty());
+
+        db.nuke_database().await.unwrap();
+    }
+}
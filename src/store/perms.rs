@@ -0,0 +1,164 @@
+/*!
+`Store` methods for restricting what individual Admins can do.
+
+```sql
+CREATE TABLE admin_permissions (
+    uname   TEXT PRIMARY KEY REFERENCES users(uname)
+                ON UPDATE CASCADE ON DELETE CASCADE,
+    perms   TEXT[] NOT NULL     /* like '{Users,Calendar}' */
+);
+```
+
+Admins without a row here are unrestricted.
+*/
+use std::collections::BTreeMap;
+
+use tokio_postgres::Row;
+
+use super::{DbError, Store};
+use crate::user::Permission;
+
+fn perms_from_row(row: &Row) -> Result<Vec<Permission>, DbError> {
+    let perm_strs: Vec<String> = row.try_get("perms")?;
+    let mut perms = perm_strs
+        .iter()
+        .map(|s| s.parse::<Permission>())
+        .collect::<Result<Vec<Permission>, String>>()
+        .map_err(DbError)?;
+    perms.sort();
+    Ok(perms)
+}
+
+impl Store {
+    /// Return the permissions the Admin `uname` is restricted to, or `None`
+    /// if they're unrestricted.
+    pub async fn get_admin_permissions(
+        &self,
+        uname: &str,
+    ) -> Result<Option<Vec<Permission>>, DbError> {
+        log::trace!("Store::get_admin_permissions( {:?} ) called.", uname);
+
+        let client = self.connect().await?;
+        let row = client
+            .query_opt(
+                "SELECT perms FROM admin_permissions WHERE uname = $1",
+                &[&uname],
+            )
+            .await?;
+
+        match row {
+            Some(row) => Ok(Some(perms_from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Return the permissions of every restricted Admin.
+    pub async fn get_all_admin_permissions(
+        &self,
+    ) -> Result<BTreeMap<String, Vec<Permission>>, DbError> {
+        log::trace!("Store::get_all_admin_permissions() called.");
+
+        let client = self.connect().await?;
+        let rows = client
+            .query("SELECT uname, perms FROM admin_permissions", &[])
+            .await?;
+
+        let mut map: BTreeMap<String, Vec<Permission>> = BTreeMap::new();
+        for row in rows.iter() {
+            map.insert(row.try_get("uname")?, perms_from_row(row)?);
+        }
+
+        Ok(map)
+    }
+
+    /// Restrict the Admin `uname` to the given permissions, or, if `perms` is
+    /// `None`, lift all their restrictions.
+    pub async fn set_admin_permissions(
+        &self,
+        uname: &str,
+        perms: Option<&[Permission]>,
+    ) -> Result<(), DbError> {
+        log::trace!(
+            "Store::set_admin_permissions( {:?}, {:?} ) called.",
+            uname,
+            &perms
+        );
+
+        let client = self.connect().await?;
+        match perms {
+            Some(perms) => {
+                let perm_strs: Vec<String> = perms.iter().map(|p| p.to_string()).collect();
+                client
+                    .execute(
+                        "INSERT INTO admin_permissions (uname, perms) VALUES ($1, $2)
+                        ON CONFLICT (uname) DO UPDATE SET perms = EXCLUDED.perms",
+                        &[&uname, &perm_strs],
+                    )
+                    .await
+                    .map_err(|e| {
+                        DbError::from(e)
+                            .annotate(&format!("Error setting permissions for {:?}", uname))
+                    })?;
+            }
+            None => {
+                client
+                    .execute("DELETE FROM admin_permissions WHERE uname = $1", &[&uname])
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn admin_permissions() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            db.insert_admin(&t, "office", "office@camelotacademy.org")
+                .await
+                .unwrap();
+            t.commit().await.unwrap();
+        }
+
+        assert_eq!(db.get_admin_permissions("office").await.unwrap(), None);
+        db.set_admin_permissions("nobody", Some(&[Permission::Users]))
+            .await
+            .unwrap_err();
+
+        db.set_admin_permissions("office", Some(&[Permission::Calendar, Permission::Users]))
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_admin_permissions("office").await.unwrap(),
+            Some(vec![Permission::Users, Permission::Calendar])
+        );
+        db.set_admin_permissions("office", Some(&[])).await.unwrap();
+        assert_eq!(
+            db.get_admin_permissions("office").await.unwrap(),
+            Some(vec![])
+        );
+        assert_eq!(db.get_all_admin_permissions().await.unwrap().len(), 1);
+
+        db.set_admin_permissions("office", None).await.unwrap();
+        assert_eq!(db.get_admin_permissions("office").await.unwrap(), None);
+        assert!(db.get_all_admin_permissions().await.unwrap().is_empty());
+
+        db.nuke_database().await.unwrap();
+    }
+}
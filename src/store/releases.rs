@@ -0,0 +1,384 @@
+/*!
+`Store` methods for releasing finalized reports to students and parents,
+and keeping track of who has downloaded them.
+
+A finalized report (see [`Store::set_final`]) isn't available to anyone
+but the Boss until the Boss releases it. Releasing it gives it a secret
+token for a link parents can download it from without logging in (see
+[`reports`](crate::inter::reports)). Finalizing the report again (or
+clearing it) withdraws the release, so only reports the Boss has seen get
+out.
+
+Every download by a student or through a link is recorded.
+
+```sql
+CREATE TABLE report_releases (
+    uname       TEXT REFERENCES students(uname) ON DELETE CASCADE,
+    term        TEXT,
+    token       TEXT UNIQUE NOT NULL,
+    released_by TEXT NOT NULL,
+    released    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    PRIMARY KEY (uname, term)
+);
+
+CREATE TABLE report_downloads (
+    uname       TEXT REFERENCES students(uname) ON DELETE CASCADE,
+    term        TEXT NOT NULL,
+    via         TEXT NOT NULL,  /* one of { 'student', 'link' } */
+    downloaded  TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+);
+```
+*/
+use std::str::FromStr;
+
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Serialize;
+use tokio_postgres::Transaction;
+
+use super::{DbError, Store};
+use crate::pace::Term;
+
+/// Length of a newly-generated report link token.
+const TOKEN_LENGTH: usize = 32;
+
+/// A released report, and how often it has been downloaded.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ReportRelease {
+    pub uname: String,
+    pub term: Term,
+    /// Secret part of the link parents can download the report from.
+    pub token: String,
+    pub released_by: String,
+    /// When the report was released (UTC), as `YYYY-MM-DD HH:MM:SS`.
+    pub released: String,
+    pub downloads: i64,
+    /// When the report was last downloaded (UTC), if ever.
+    pub last_downloaded: Option<String>,
+}
+
+fn new_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+impl Store {
+    /**
+    Release the finalized `term` reports of the students with the given
+    `unames` (or of every student with one, if `unames` is `None`) that
+    haven't been released already. Returns the unames of the students whose
+    reports were released.
+    */
+    pub async fn release_reports(
+        &self,
+        term: Term,
+        unames: Option<&[String]>,
+        released_by: &str,
+    ) -> Result<Vec<String>, DbError> {
+        log::trace!(
+            "Store::release_reports( {:?}, {:?}, {:?} ) called.",
+            &term,
+            &unames,
+            released_by
+        );
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+        let rows = t
+            .query(
+                "SELECT reports.uname FROM reports
+                    LEFT JOIN report_releases
+                        ON reports.uname = report_releases.uname
+                        AND reports.term = report_releases.term
+                WHERE reports.term = $1
+                    AND octet_length(reports.doc) > 0
+                    AND report_releases.uname IS NULL
+                    AND ($2::TEXT[] IS NULL OR reports.uname = ANY($2))
+                ORDER BY reports.uname",
+                &[&term.as_str(), &unames],
+            )
+            .await?;
+
+        let mut released: Vec<String> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            let uname: String = row.try_get("uname")?;
+            t.execute(
+                "INSERT INTO report_releases (uname, term, token, released_by)
+                    VALUES ($1, $2, $3, $4)",
+                &[&uname, &term.as_str(), &new_token(), &released_by],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error releasing report"))?;
+            released.push(uname);
+        }
+        t.commit().await?;
+
+        Ok(released)
+    }
+
+    /// Withdraw the release of `uname`'s `term` report. Returns whether it
+    /// had been released.
+    pub async fn withdraw_report(&self, uname: &str, term: Term) -> Result<bool, DbError> {
+        log::trace!("Store::withdraw_report( {:?}, {:?} ) called.", uname, &term);
+
+        let client = self.connect().await?;
+        let n = client
+            .execute(
+                "DELETE FROM report_releases WHERE uname = $1 AND term = $2",
+                &[&uname, &term.as_str()],
+            )
+            .await?;
+
+        Ok(n > 0)
+    }
+
+    /// Withdraw the release of `uname`'s `term` report as part of
+    /// transaction `t` (for when the report itself changes).
+    pub(super) async fn withdraw_report_in(
+        t: &Transaction<'_>,
+        uname: &str,
+        term: Term,
+    ) -> Result<(), DbError> {
+        t.execute(
+            "DELETE FROM report_releases WHERE uname = $1 AND term = $2",
+            &[&uname, &term.as_str()],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /**
+    Return the released reports (with their download counts) of the
+    student with the given `uname`, or of all students if `uname` is
+    `None`, ordered by student and term.
+    */
+    pub async fn get_releases(&self, uname: Option<&str>) -> Result<Vec<ReportRelease>, DbError> {
+        log::trace!("Store::get_releases( {:?} ) called.", &uname);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT r.uname, r.term, r.token, r.released_by,
+                    to_char(r.released, 'YYYY-MM-DD HH24:MI:SS') AS released,
+                    COUNT(d.uname) AS downloads,
+                    to_char(MAX(d.downloaded), 'YYYY-MM-DD HH24:MI:SS') AS last_downloaded
+                FROM report_releases r LEFT JOIN report_downloads d
+                    ON r.uname = d.uname AND r.term = d.term
+                        AND d.downloaded >= r.released
+                WHERE $1::TEXT IS NULL OR r.uname = $1
+                GROUP BY r.uname, r.term, r.token, r.released_by, r.released
+                ORDER BY r.uname, r.term",
+                &[&uname],
+            )
+            .await?;
+
+        let mut releases: Vec<ReportRelease> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            let term: &str = row.try_get("term")?;
+            releases.push(ReportRelease {
+                uname: row.try_get("uname")?,
+                term: Term::from_str(term)?,
+                token: row.try_get("token")?,
+                released_by: row.try_get("released_by")?,
+                released: row.try_get("released")?,
+                downloads: row.try_get("downloads")?,
+                last_downloaded: row.try_get("last_downloaded")?,
+            });
+        }
+
+        Ok(releases)
+    }
+
+    /// Return `uname`'s `term` report, if it has been released.
+    pub async fn get_released_report(
+        &self,
+        uname: &str,
+        term: Term,
+    ) -> Result<Option<Vec<u8>>, DbError> {
+        log::trace!(
+            "Store::get_released_report( {:?}, {:?} ) called.",
+            uname,
+            &term
+        );
+
+        let client = self.connect().await?;
+        let opt_row = client
+            .query_opt(
+                "SELECT reports.doc FROM reports JOIN report_releases
+                    ON reports.uname = report_releases.uname
+                    AND reports.term = report_releases.term
+                WHERE reports.uname = $1 AND reports.term = $2",
+                &[&uname, &term.as_str()],
+            )
+            .await?;
+
+        match opt_row {
+            Some(row) => {
+                let bytes: Option<Vec<u8>> = row.try_get("doc")?;
+                Ok(bytes.filter(|b| !b.is_empty()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Return the uname and term of the released report the link `token`
+    /// is for, along with the report itself.
+    pub async fn get_report_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<(String, Term, Vec<u8>)>, DbError> {
+        log::trace!("Store::get_report_by_token( [ token ] ) called.");
+
+        let client = self.connect().await?;
+        let opt_row = client
+            .query_opt(
+                "SELECT reports.uname, reports.term, reports.doc
+                FROM reports JOIN report_releases
+                    ON reports.uname = report_releases.uname
+                    AND reports.term = report_releases.term
+                WHERE report_releases.token = $1",
+                &[&token],
+            )
+            .await?;
+
+        let row = match opt_row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let doc: Option<Vec<u8>> = row.try_get("doc")?;
+        let doc = match doc.filter(|b| !b.is_empty()) {
+            Some(doc) => doc,
+            None => return Ok(None),
+        };
+        let term: &str = row.try_get("term")?;
+
+        Ok(Some((row.try_get("uname")?, Term::from_str(term)?, doc)))
+    }
+
+    /// Record that `uname`'s `term` report was downloaded `via` either
+    /// `"student"` (the student's own API) or `"link"`.
+    pub async fn record_report_download(
+        &self,
+        uname: &str,
+        term: Term,
+        via: &str,
+    ) -> Result<(), DbError> {
+        log::trace!(
+            "Store::record_report_download( {:?}, {:?}, {:?} ) called.",
+            uname,
+            &term,
+            via
+        );
+
+        let client = self.connect().await?;
+        client
+            .execute(
+                "INSERT INTO report_downloads (uname, term, via) VALUES ($1, $2, $3)",
+                &[&uname, &term.as_str(), &via],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error recording report download"))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn report_releases() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        insert_users(&db, &["berro"], &[("frog", "berro"), ("toad", "berro")]).await;
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            Store::set_final(&t, "frog", Term::Fall, b"frog's report")
+                .await
+                .unwrap();
+            Store::set_final(&t, "toad", Term::Fall, b"toad's report")
+                .await
+                .unwrap();
+            t.commit().await.unwrap();
+        }
+
+        // Nothing is available until it's released.
+        assert!(db
+            .get_released_report("frog", Term::Fall)
+            .await
+            .unwrap()
+            .is_none());
+
+        let only_frog = ["frog".to_owned()];
+        assert_eq!(
+            db.release_reports(Term::Fall, Some(&only_frog), "boss")
+                .await
+                .unwrap(),
+            vec!["frog".to_owned()]
+        );
+        assert_eq!(
+            db.release_reports(Term::Fall, None, "boss").await.unwrap(),
+            vec!["toad".to_owned()]
+        );
+        assert!(db
+            .release_reports(Term::Spring, None, "boss")
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(
+            db.get_released_report("frog", Term::Fall)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some(&b"frog's report"[..])
+        );
+        let releases = db.get_releases(Some("frog")).await.unwrap();
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].downloads, 0);
+        let token = releases[0].token.clone();
+        let (uname, term, doc) = db.get_report_by_token(&token).await.unwrap().unwrap();
+        assert_eq!((uname.as_str(), term), ("frog", Term::Fall));
+        assert_eq!(doc, b"frog's report");
+        assert!(db.get_report_by_token("nope").await.unwrap().is_none());
+
+        db.record_report_download("frog", Term::Fall, "student")
+            .await
+            .unwrap();
+        db.record_report_download("frog", Term::Fall, "link")
+            .await
+            .unwrap();
+        let releases = db.get_releases(None).await.unwrap();
+        assert_eq!(releases.len(), 2);
+        assert_eq!(releases[0].downloads, 2);
+        assert!(releases[0].last_downloaded.is_some());
+
+        // Finalizing the report again withdraws the release.
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            Store::set_final(&t, "frog", Term::Fall, b"frog's new report")
+                .await
+                .unwrap();
+            t.commit().await.unwrap();
+        }
+        assert!(db.get_report_by_token(&token).await.unwrap().is_none());
+        assert!(db.withdraw_report("toad", Term::Fall).await.unwrap());
+        assert!(!db.withdraw_report("toad", Term::Fall).await.unwrap());
+        assert!(db.get_releases(None).await.unwrap().is_empty());
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -0,0 +1,251 @@
+/*!
+`Store` methods for schools' own versions of the report templates (see
+[`report::generate_report_markup`](crate::report::generate_report_markup)).
+
+```sql
+CREATE TABLE report_templates (
+    id          BIGSERIAL PRIMARY KEY,
+    kind        TEXT NOT NULL,  /* the built-in template it replaces */
+    label       TEXT NOT NULL,
+    body        TEXT NOT NULL,  /* Handlebars Markdown */
+    uploaded    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    uploaded_by TEXT NOT NULL,
+    active      BOOL NOT NULL DEFAULT false
+);
+```
+
+Every upload is kept as a new version. At most one version of each kind
+is active at a time; when none is, the built-in template is used.
+*/
+use serde::Serialize;
+use tokio_postgres::Row;
+
+use super::{DbError, Store};
+
+/// One uploaded version of a report template.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ReportTemplate {
+    pub id: i64,
+    /// Name of the built-in template this replaces (like `"report"`).
+    pub kind: String,
+    pub label: String,
+    pub body: String,
+    /// When it was uploaded (UTC), as `YYYY-MM-DD HH:MM:SS`.
+    pub uploaded: String,
+    pub uploaded_by: String,
+    pub active: bool,
+}
+
+fn template_from_row(row: &Row) -> Result<ReportTemplate, DbError> {
+    Ok(ReportTemplate {
+        id: row.try_get("id")?,
+        kind: row.try_get("kind")?,
+        label: row.try_get("label")?,
+        body: row.try_get("body")?,
+        uploaded: row.try_get("uploaded")?,
+        uploaded_by: row.try_get("uploaded_by")?,
+        active: row.try_get("active")?,
+    })
+}
+
+const TEMPLATE_COLUMNS: &str = "id, kind, label, body, uploaded_by, active,
+    to_char(uploaded, 'YYYY-MM-DD HH24:MI:SS') AS uploaded";
+
+impl Store {
+    /// Save a new (inactive) version of the `kind` report template, and
+    /// return its id.
+    pub async fn insert_report_template(
+        &self,
+        kind: &str,
+        label: &str,
+        body: &str,
+        uploaded_by: &str,
+    ) -> Result<i64, DbError> {
+        log::trace!(
+            "Store::insert_report_template( {:?}, {:?}, [ {} bytes ], {:?} ) called.",
+            kind,
+            label,
+            body.len(),
+            uploaded_by
+        );
+
+        let client = self.connect().await?;
+        let row = client
+            .query_one(
+                "INSERT INTO report_templates (kind, label, body, uploaded_by)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id",
+                &[&kind, &label, &body, &uploaded_by],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error saving report template"))?;
+
+        Ok(row.try_get("id")?)
+    }
+
+    /// Return every version of every report template, by kind, newest
+    /// first.
+    pub async fn get_report_templates(&self) -> Result<Vec<ReportTemplate>, DbError> {
+        log::trace!("Store::get_report_templates() called.");
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                &format!(
+                    "SELECT {} FROM report_templates ORDER BY kind, id DESC",
+                    TEMPLATE_COLUMNS
+                ),
+                &[],
+            )
+            .await?;
+
+        rows.iter().map(template_from_row).collect()
+    }
+
+    /// Return the version of a report template with the given `id`, if
+    /// there is one.
+    pub async fn get_report_template(&self, id: i64) -> Result<Option<ReportTemplate>, DbError> {
+        log::trace!("Store::get_report_template( {} ) called.", &id);
+
+        let client = self.connect().await?;
+        let opt_row = client
+            .query_opt(
+                &format!("SELECT {} FROM report_templates WHERE id = $1", TEMPLATE_COLUMNS),
+                &[&id],
+            )
+            .await?;
+
+        opt_row.as_ref().map(template_from_row).transpose()
+    }
+
+    /// Return the body of the active version of the `kind` report
+    /// template, if one is active.
+    pub async fn get_active_report_template(&self, kind: &str) -> Result<Option<String>, DbError> {
+        log::trace!("Store::get_active_report_template( {:?} ) called.", kind);
+
+        let client = self.connect().await?;
+        let opt_row = client
+            .query_opt(
+                "SELECT body FROM report_templates WHERE kind = $1 AND active",
+                &[&kind],
+            )
+            .await?;
+
+        match opt_row {
+            Some(row) => Ok(Some(row.try_get("body")?)),
+            None => Ok(None),
+        }
+    }
+
+    /**
+    Make the version with the given `id` the active `kind` report template,
+    deactivating any other; with `None`, deactivate them all, so the
+    built-in template gets used again.
+    */
+    pub async fn activate_report_template(
+        &self,
+        kind: &str,
+        id: Option<i64>,
+    ) -> Result<(), DbError> {
+        log::trace!(
+            "Store::activate_report_template( {:?}, {:?} ) called.",
+            kind,
+            &id
+        );
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        if let Some(id) = id {
+            let row = t
+                .query_opt("SELECT kind FROM report_templates WHERE id = $1", &[&id])
+                .await?
+                .ok_or_else(|| DbError(format!("There is no report template with id {}.", &id)))?;
+            let its_kind: String = row.try_get("kind")?;
+            if its_kind != kind {
+                return Err(DbError(format!(
+                    "Report template {} is a {:?} template, not a {:?} one.",
+                    &id, &its_kind, kind
+                )));
+            }
+        }
+
+        t.execute(
+            "UPDATE report_templates SET active = (id IS NOT DISTINCT FROM $2)
+            WHERE kind = $1",
+            &[&kind, &id],
+        )
+        .await
+        .map_err(|e| DbError::from(e).annotate("Error activating report template"))?;
+
+        t.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn report_templates() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+
+        let v1 = db
+            .insert_report_template("report", "crest", "# {{rest}}", "admin")
+            .await
+            .unwrap();
+        let v2 = db
+            .insert_report_template("report", "crest, bigger", "## {{rest}}", "admin")
+            .await
+            .unwrap();
+        let summer = db
+            .insert_report_template("report_summer", "summer", "{{last}}", "admin")
+            .await
+            .unwrap();
+        assert_eq!(db.get_active_report_template("report").await.unwrap(), None);
+
+        db.activate_report_template("report", Some(v1)).await.unwrap();
+        db.activate_report_template("report_summer", Some(summer))
+            .await
+            .unwrap();
+        db.activate_report_template("report", Some(v2)).await.unwrap();
+        assert_eq!(
+            db.get_active_report_template("report").await.unwrap().as_deref(),
+            Some("## {{rest}}")
+        );
+        // A version can only be activated as its own kind.
+        assert!(db
+            .activate_report_template("report_summer", Some(v1))
+            .await
+            .is_err());
+        assert!(db.activate_report_template("report", Some(-1)).await.is_err());
+
+        let all = db.get_report_templates().await.unwrap();
+        let ids: Vec<(i64, bool)> = all.iter().map(|t| (t.id, t.active)).collect();
+        assert_eq!(ids, vec![(v2, true), (v1, false), (summer, true)]);
+        assert_eq!(db.get_report_template(v1).await.unwrap().as_ref(), Some(&all[1]));
+        assert_eq!(db.get_report_template(-1).await.unwrap(), None);
+
+        // Deactivating them all goes back to the built-in template, but
+        // leaves the other kind alone.
+        db.activate_report_template("report", None).await.unwrap();
+        assert_eq!(db.get_active_report_template("report").await.unwrap(), None);
+        assert!(db
+            .get_active_report_template("report_summer")
+            .await
+            .unwrap()
+            .is_some());
+
+        db.nuke_database().await.unwrap();
+    }
+}
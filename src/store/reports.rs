@@ -31,7 +31,8 @@ CREATE TABLE completion (
 CREATE TABLE drafts (
     uname   TEXT REFERENCES students(uname),
     term    TEXT,
-    draft   TEXT
+    draft   TEXT,
+    saved   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
 );
 
 CREATE TABLE reports (
@@ -39,6 +40,31 @@ CREATE TABLE reports (
     term    TEXT,
     doc     bytea
 );
+
+CREATE TABLE report_meta (
+    uname       TEXT REFERENCES students(uname),
+    term        TEXT,
+    finalized   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    n_pages     INT,
+    PRIMARY KEY (uname, term)
+);
+
+CREATE TABLE report_visibility (
+    uname   TEXT PRIMARY KEY REFERENCES students(uname),
+    facts   TEXT NOT NULL,  /* one of { 'student', 'parent', 'boss' } */
+    social  TEXT NOT NULL,
+    mastery TEXT NOT NULL
+);
+
+CREATE TABLE report_notes (
+    uname       TEXT REFERENCES students(uname),
+    term        TEXT,
+    note        TEXT,
+    visibility  TEXT    /* one of { 'student', 'parent', 'boss' } */
+);
+
+Students without a row in `report_visibility` get the default
+[`SidecarVisibility`] (everything visible to everyone).
 */
 use std::{
     collections::HashMap,
@@ -47,9 +73,10 @@ use std::{
 };
 
 use futures::{
-    stream::{FuturesUnordered, StreamExt},
+    stream::{self, BoxStream, FuturesUnordered, StreamExt},
     try_join,
 };
+use serde::Serialize;
 use tokio_postgres::{
     types::{ToSql, Type},
     Row, Transaction,
@@ -62,6 +89,58 @@ use crate::{
     pace::Term, report::*,
 };
 
+/// Information about a finalized report PDF, so the Boss can tell what's
+/// there before downloading it.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ReportMeta {
+    pub uname: String,
+    pub term: Term,
+    /// When the report was finalized (UTC), as `YYYY-MM-DD HH:MM:SS`.
+    /// Reports finalized before this was recorded won't have one.
+    pub finalized: Option<String>,
+    pub n_pages: Option<i32>,
+    /// Size of the PDF in bytes.
+    pub size: i32,
+}
+
+/**
+Count the pages of a PDF document by counting its `/Type /Page` objects.
+
+This doesn't actually parse the PDF, so it only works on documents whose
+page objects aren't compressed (like the ones we generate). Returns `None`
+if it finds no pages at all.
+*/
+fn count_pdf_pages(pdf: &[u8]) -> Option<i32> {
+    const TYPE: &[u8] = b"/Type";
+    const PAGE: &[u8] = b"/Page";
+
+    let mut n: i32 = 0;
+    for (i, _) in pdf
+        .windows(TYPE.len())
+        .enumerate()
+        .filter(|(_, w)| *w == TYPE)
+    {
+        let rest = &pdf[(i + TYPE.len())..];
+        let start = rest
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let rest = &rest[start..];
+        // Don't count the `/Type /Pages` node(s) of the page tree.
+        if rest.starts_with(PAGE) && !rest[PAGE.len()..].starts_with(b"s") {
+            n += 1;
+        }
+    }
+
+    match n {
+        0 => None,
+        n => Some(n),
+    }
+}
+
+/// Finalized report PDFs, as `(uname, PDF bytes)`; see [`Store::stream_finals`].
+pub type FinalStream = BoxStream<'static, Result<(String, Vec<u8>), DbError>>;
+
 fn row2mastery(row: &Row) -> Result<Mastery, DbError> {
     let status: Option<&str> = row.try_get("status")?;
 
@@ -526,6 +605,134 @@ impl Store {
         Ok(map)
     }
 
+    pub async fn set_report_visibility(
+        t: &Transaction<'_>,
+        uname: &str,
+        vis: &SidecarVisibility,
+    ) -> Result<(), DbError> {
+        log::trace!(
+            "Store::set_report_visibility( [ &T ], {:?}, {:?} ) called.",
+            uname,
+            vis
+        );
+
+        t.execute(
+            "INSERT INTO report_visibility (uname, facts, social, mastery)
+                VALUES ($1, $2, $3, $4)
+            ON CONFLICT (uname) DO UPDATE
+                SET facts = EXCLUDED.facts,
+                    social = EXCLUDED.social,
+                    mastery = EXCLUDED.mastery",
+            &[
+                &uname,
+                &vis.facts.as_str(),
+                &vis.social.as_str(),
+                &vis.mastery.as_str(),
+            ],
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_report_visibility(
+        t: &Transaction<'_>,
+        uname: &str,
+    ) -> Result<SidecarVisibility, DbError> {
+        log::trace!("Store::get_report_visibility( [ &T ], {:?} ) called.", uname);
+
+        let row = match t
+            .query_opt(
+                "SELECT facts, social, mastery FROM report_visibility
+                WHERE uname = $1",
+                &[&uname],
+            )
+            .await?
+        {
+            Some(row) => row,
+            None => {
+                return Ok(SidecarVisibility::default());
+            }
+        };
+
+        let facts: &str = row.try_get("facts")?;
+        let social: &str = row.try_get("social")?;
+        let mastery: &str = row.try_get("mastery")?;
+
+        Ok(SidecarVisibility {
+            facts: Audience::from_str(facts)?,
+            social: Audience::from_str(social)?,
+            mastery: Audience::from_str(mastery)?,
+        })
+    }
+
+    pub async fn set_report_notes(
+        t: &Transaction<'_>,
+        uname: &str,
+        notes: &[ReportNote],
+    ) -> Result<(), DbError> {
+        log::trace!(
+            "Store::set_report_notes( [ &T ], {:?}, [ {} notes ] ) called.",
+            uname,
+            &notes.len()
+        );
+
+        t.execute("DELETE FROM report_notes WHERE uname = $1", &[&uname])
+            .await?;
+
+        let insert_stmt = t
+            .prepare_typed(
+                "INSERT INTO report_notes (uname, term, note, visibility)
+                VALUES ($1, $2, $3, $4)",
+                &[Type::TEXT, Type::TEXT, Type::TEXT, Type::TEXT],
+            )
+            .await?;
+
+        for note in notes.iter() {
+            t.execute(
+                &insert_stmt,
+                &[
+                    &uname,
+                    &note.term.as_str(),
+                    &note.text,
+                    &note.visibility.as_str(),
+                ],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error writing report note to DB"))?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_report_notes(
+        t: &Transaction<'_>,
+        uname: &str,
+    ) -> Result<Vec<ReportNote>, DbError> {
+        log::trace!("Store::get_report_notes( [ &T ], {:?} ) called.", uname);
+
+        let rows = t
+            .query(
+                "SELECT term, note, visibility FROM report_notes
+                WHERE uname = $1",
+                &[&uname],
+            )
+            .await?;
+
+        let mut notes: Vec<ReportNote> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            let term: &str = row.try_get("term")?;
+            let visibility: &str = row.try_get("visibility")?;
+            notes.push(ReportNote {
+                term: Term::from_str(term)?,
+                text: row.try_get("note")?,
+                visibility: Audience::from_str(visibility)?,
+            });
+        }
+
+        Ok(notes)
+    }
+
     pub async fn set_report_sidecar(
             &self,
             sidecar: &ReportSidecar,
@@ -551,6 +758,8 @@ impl Store {
             Store::set_completion(&t, uname, year, Term::Spring, &sidecar.spring_complete),
             Store::set_completion(&t, uname, year, Term::Summer, &sidecar.summer_complete),
             Store::set_mastery(&t, &sidecar.mastery),
+            Store::set_report_visibility(&t, uname, &sidecar.visibility),
+            Store::set_report_notes(&t, uname, &sidecar.notes),
         ) {
             return Err(format!("Unable to write sidecar data to database: {}", &e).into());
         }
@@ -576,6 +785,8 @@ impl Store {
             spring_complete,
             summer_complete,
             mastery,
+            visibility,
+            notes,
         ) = tokio::try_join!(
             Store::get_facts(&t, uname),
             Store::get_social(&t, uname, Term::Fall),
@@ -584,6 +795,8 @@ impl Store {
             Store::get_completion(&t, uname, year, Term::Spring),
             Store::get_completion(&t, uname, year, Term::Summer),
             Store::get_mastery(&t, uname),
+            Store::get_report_visibility(&t, uname),
+            Store::get_report_notes(&t, uname),
         )?;
 
         t.commit().await?;
@@ -597,6 +810,8 @@ impl Store {
             fall_complete,
             spring_complete,
             summer_complete,
+            visibility,
+            notes,
         };
 
         Ok(car)
@@ -690,6 +905,17 @@ impl Store {
         ).await?;
         t.execute(&insert_stmt, &params[..]).await?;
 
+        let n_pages = count_pdf_pages(pdf_bytes);
+        t.execute(
+            "INSERT INTO report_meta (uname, term, n_pages)
+                VALUES ($1, $2, $3)
+            ON CONFLICT (uname, term) DO UPDATE SET
+                finalized = DEFAULT, n_pages = EXCLUDED.n_pages",
+            &[&uname, &term.as_str(), &n_pages],
+        ).await?;
+        // The Boss has to release the new version.
+        Store::withdraw_report_in(t, uname, term).await?;
+
         Ok(())
     }
 
@@ -713,16 +939,7 @@ impl Store {
         {
             Some(row) => {
                 let bytes: Option<Vec<u8>> = row.try_get("doc")?;
-                match bytes {
-                    Some(bytez) => {
-                        if bytez.is_empty() {
-                            None
-                        } else {
-                            Some(bytez)
-                        }
-                    }
-                    None => None,
-                }
+                bytes.filter(|bytez| !bytez.is_empty())
             }
             None => None,
         };
@@ -730,6 +947,59 @@ impl Store {
         Ok(opt)
     }
 
+    /**
+    Stream the finalized `term` reports of the students with the given
+    `unames`, one at a time, in that order. Students without one are
+    skipped. The stream ends after the first error.
+
+    The stream has its own connection, so it doesn't borrow the `Store`,
+    and only one PDF is held at a time.
+    */
+    pub async fn stream_finals(
+        &self,
+        unames: Vec<String>,
+        term: Term,
+    ) -> Result<FinalStream, DbError> {
+        log::trace!(
+            "Store::stream_finals( [ {} unames ], {:?} ) called.",
+            unames.len(),
+            &term
+        );
+
+        let client = self.connect().await?;
+        let stmt = client
+            .prepare_typed(
+                "SELECT doc FROM reports WHERE uname = $1 AND term = $2",
+                &[Type::TEXT, Type::TEXT],
+            )
+            .await?;
+
+        let state = (client, stmt, unames.into_iter());
+        let finals = stream::unfold(state, move |(client, stmt, mut unames)| async move {
+            while let Some(uname) = unames.next() {
+                let res = match client.query_opt(&stmt, &[&uname, &term.as_str()]).await {
+                    Ok(Some(row)) => row.try_get::<_, Option<Vec<u8>>>("doc"),
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e),
+                };
+                match res {
+                    Ok(Some(doc)) if !doc.is_empty() => {
+                        return Some((Ok((uname, doc)), (client, stmt, unames)));
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        let e = DbError::from(e)
+                            .annotate(&format!("Error reading {} report of {:?}", &term, &uname));
+                        return Some((Err(e), (client, stmt, Vec::new().into_iter())));
+                    }
+                }
+            }
+            None
+        });
+
+        Ok(finals.boxed())
+    }
+
     pub async fn clear_final(
         &self,
         uname: &str,
@@ -740,15 +1010,62 @@ impl Store {
             uname, &term.as_str()
         );
 
-        let client = self.connect().await?;
-        client.execute(
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+        let params: [&(dyn ToSql + Sync); 2] = [&uname, &term.as_str()];
+        t.execute(
             "DELETE FROM reports WHERE uname = $1 AND term = $2",
-            &[&uname, &term.as_str()],
+            &params[..],
+        ).await?;
+        t.execute(
+            "DELETE FROM report_meta WHERE uname = $1 AND term = $2",
+            &params[..],
         ).await?;
+        Store::withdraw_report_in(&t, uname, term).await?;
+        t.commit().await?;
 
         Ok(())
     }
 
+    /**
+    Return information about the finalized reports of the student with the
+    given `uname`, or of all students if `uname` is `None`, ordered by
+    student and term.
+    */
+    pub async fn get_report_meta(&self, uname: Option<&str>) -> Result<Vec<ReportMeta>, DbError> {
+        log::trace!("Store::get_report_meta( {:?} ) called.", &uname);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT reports.uname, reports.term,
+                    to_char(report_meta.finalized, 'YYYY-MM-DD HH24:MI:SS') AS finalized,
+                    report_meta.n_pages, octet_length(reports.doc) AS size
+                FROM reports LEFT JOIN report_meta
+                    ON reports.uname = report_meta.uname
+                    AND reports.term = report_meta.term
+                WHERE octet_length(reports.doc) > 0
+                    AND ($1::TEXT IS NULL OR reports.uname = $1)
+                ORDER BY reports.uname, reports.term",
+                &[&uname],
+            )
+            .await?;
+
+        let mut metas: Vec<ReportMeta> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            let term: &str = row.try_get("term")?;
+            metas.push(ReportMeta {
+                uname: row.try_get("uname")?,
+                term: Term::from_str(term)?,
+                finalized: row.try_get("finalized")?,
+                n_pages: row.try_get("n_pages")?,
+                size: row.try_get("size")?,
+            });
+        }
+
+        Ok(metas)
+    }
+
     /**
     Clear all sidecar student data for the year.
 
@@ -763,6 +1080,11 @@ impl Store {
             t.execute("DELETE FROM social", &[]),
             t.execute("DELETE FROM drafts", &[]),
             t.execute("DELETE FROM reports", &[]),
+            t.execute("DELETE FROM report_meta", &[]),
+            t.execute("DELETE FROM report_releases", &[]),
+            t.execute("DELETE FROM report_downloads", &[]),
+            t.execute("DELETE FROM report_visibility", &[]),
+            t.execute("DELETE FROM report_notes", &[]),
         )?;
 
         Ok(())
@@ -797,7 +1119,7 @@ mod tests {
     fn social_map() -> HashMap<String, String> {
         SOCIAL_CATS
             .iter()
-            .map(|cat| (String::from(*cat), format!("2")))
+            .map(|cat| (String::from(*cat), "2".to_string()))
             .collect()
     }
 
@@ -862,10 +1184,80 @@ mod tests {
             spring_complete: vec![],
             summer_complete: vec![],
             mastery,
+            visibility: SidecarVisibility::default(),
+            notes: vec![ReportNote {
+                term: Term::Fall,
+                text: "Needs to show more work.".to_owned(),
+                visibility: Audience::Parent,
+            }],
         };
 
         db.set_report_sidecar(&sc, YEAR).await?;
 
         Ok(())
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn stream_finals() {
+        use crate::store::tests::{insert_users, TEST_CONNECTION};
+
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        insert_users(
+            &db,
+            &["berro"],
+            &[("frog", "berro"), ("toad", "berro"), ("zack", "berro")],
+        )
+        .await;
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            Store::set_final(&t, "zack", Term::Fall, b"%PDF zack fall").await.unwrap();
+            Store::set_final(&t, "frog", Term::Fall, b"%PDF frog fall").await.unwrap();
+            Store::set_final(&t, "frog", Term::Spring, b"%PDF frog spring").await.unwrap();
+            t.commit().await.unwrap();
+        }
+
+        let unames: Vec<String> = ["frog", "toad", "zack", "nobody"]
+            .iter()
+            .map(|u| u.to_string())
+            .collect();
+        let finals: Vec<(String, Vec<u8>)> = db
+            .stream_finals(unames, Term::Fall)
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(
+            finals,
+            vec![
+                ("frog".to_owned(), b"%PDF frog fall".to_vec()),
+                ("zack".to_owned(), b"%PDF zack fall".to_vec()),
+            ]
+        );
+
+        let none = db
+            .stream_finals(vec!["toad".to_owned()], Term::Spring)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+        assert!(none.is_empty());
+
+        db.nuke_database().await.unwrap();
+    }
+
+    #[test]
+    fn count_pages() {
+        let pdf = b"%PDF-1.3\n1 0 obj << /Type /Pages /Kids [2 0 R 3 0 R] /Count 2 >>\n\
+            2 0 obj << /Type /Page /Parent 1 0 R >>\n\
+            3 0 obj << /Type/Page /Parent 1 0 R >>\n\
+            4 0 obj << /Type /Catalog /Pages 1 0 R >>\n%%EOF";
+        assert_eq!(count_pdf_pages(pdf), Some(2));
+        assert_eq!(count_pdf_pages(b"not a pdf"), None);
+    }
 }
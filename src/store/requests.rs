@@ -0,0 +1,173 @@
+/*!
+`Store` methods for students' requests that their Teachers mark goals done
+(made from the basic student view; see
+[`student::basic`](crate::inter::student::basic)).
+
+```sql
+CREATE TABLE completion_requests (
+    goal        BIGINT PRIMARY KEY REFERENCES goals(id) ON DELETE CASCADE,
+    requested   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+);
+```
+
+A request only counts while its goal isn't done (see
+[`Goal::requested`](crate::pace::Goal::requested)), so marking the goal
+done is all it takes to answer one; a Teacher who doesn't agree that the
+goal is done can dismiss it instead.
+*/
+use super::{DbError, Store};
+
+impl Store {
+    /**
+    Record that the student `uname` says they've finished the goal with the
+    given `id`.
+
+    Returns `false` (and records nothing) if the goal isn't one of the
+    student's unfinished goals, or has already been requested.
+    */
+    pub async fn request_completion(&self, uname: &str, id: i64) -> Result<bool, DbError> {
+        log::trace!("Store::request_completion( {:?}, {} ) called.", uname, &id);
+
+        let client = self.connect().await?;
+        let n = client
+            .execute(
+                "INSERT INTO completion_requests (goal)
+                SELECT id FROM goals WHERE id = $1 AND uname = $2 AND done IS NULL
+                ON CONFLICT DO NOTHING",
+                &[&id, &uname],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error recording completion request"))?;
+
+        Ok(n == 1)
+    }
+
+    /**
+    Forget the request that the goal with the given `id` be marked done, if
+    the goal belongs to one of Teacher `tuname`'s students.
+
+    Returns the `uname` of the goal's student, or `None` if there was no
+    such request.
+    */
+    pub async fn dismiss_completion_request(
+        &self,
+        tuname: &str,
+        id: i64,
+    ) -> Result<Option<String>, DbError> {
+        log::trace!(
+            "Store::dismiss_completion_request( {:?}, {} ) called.",
+            tuname,
+            &id
+        );
+
+        let client = self.connect().await?;
+        let row = client
+            .query_opt(
+                "DELETE FROM completion_requests USING goals, students
+                WHERE completion_requests.goal = $1
+                    AND goals.id = completion_requests.goal
+                    AND students.uname = goals.uname
+                    AND students.teacher = $2
+                RETURNING goals.uname",
+                &[&id, &tuname],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error dismissing completion request"))?;
+
+        match row {
+            Some(row) => Ok(Some(row.try_get("uname")?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+    use time::macros::date;
+
+    use crate::course::Course;
+    use crate::pace::{BookCh, Goal, Source};
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    fn goal(uname: &str, seq: i16) -> Goal {
+        Goal {
+            id: 0,
+            uname: uname.to_owned(),
+            source: Source::Book(BookCh {
+                sym: "pc".to_owned(),
+                seq,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due: Some(date!(2022 - 10 - 3)),
+            done: None,
+            tries: None,
+            weight: 0.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn completion_requests() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        db.nuke_database().await.unwrap();
+        db.ensure_db_schema().await.unwrap();
+
+        let crs =
+            Course::from_reader(std::fs::File::open("test/good_course_0.mix").unwrap()).unwrap();
+        db.insert_courses(&[crs]).await.unwrap();
+        insert_users(&db, &["berro"], &[("frog", "berro"), ("toad", "berro")]).await;
+        db.insert_goals(&[goal("frog", 1), goal("frog", 2), goal("toad", 1)])
+            .await
+            .unwrap();
+
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        assert!(goals.iter().all(|g| !g.requested));
+        let (a, mut b) = (goals[0].id, goals[1].clone());
+        let toads = db.get_goals_by_student("toad").await.unwrap();
+
+        assert!(db.request_completion("frog", a).await.unwrap());
+        assert!(!db.request_completion("frog", a).await.unwrap());
+        // Not frog's goal.
+        assert!(!db.request_completion("frog", toads[0].id).await.unwrap());
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        assert!(goals.iter().find(|g| g.id == a).unwrap().requested);
+
+        // Completed goals can't be requested, and requests for goals that
+        // get completed lapse.
+        b.done = Some(date!(2022 - 10 - 1));
+        db.update_goal(&b).await.unwrap();
+        assert!(!db.request_completion("frog", b.id).await.unwrap());
+        let mut a_goal = goals.into_iter().find(|g| g.id == a).unwrap();
+        a_goal.done = Some(date!(2022 - 10 - 2));
+        db.update_goal(&a_goal).await.unwrap();
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        assert!(goals.iter().all(|g| !g.requested));
+
+        a_goal.done = None;
+        db.update_goal(&a_goal).await.unwrap();
+        assert_eq!(db.dismiss_completion_request("adams", a).await.unwrap(), None);
+        assert_eq!(
+            db.dismiss_completion_request("berro", a).await.unwrap(),
+            Some("frog".to_owned())
+        );
+        assert_eq!(db.dismiss_completion_request("berro", a).await.unwrap(), None);
+
+        db.nuke_database().await.unwrap();
+    }
+}
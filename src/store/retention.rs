@@ -0,0 +1,306 @@
+/*!
+Deleting data that has been kept long enough.
+
+How long is set per kind of data by a [`Retention`] (the `[retention]`
+table of the config file); a background task (see
+[`retention`](crate::inter::retention)) calls [`Store::prune`] once a day.
+Rows are deleted [`PRUNE_BATCH`] at a time, so a first run against years of
+accumulated data doesn't hold locks on a whole table while it works.
+
+Report drafts are aged by when they were last saved:
+
+```sql
+CREATE TABLE drafts (
+    uname   TEXT REFERENCES students(uname),
+    term    TEXT,
+    draft   TEXT,
+    saved   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+);
+```
+
+The other tables already record when their rows were written.
+*/
+use serde::{Deserialize, Serialize};
+
+use super::{DbError, Store};
+
+/// Most rows deleted from a table by a single statement.
+pub const PRUNE_BATCH: i64 = 1000;
+
+/**
+How many days to keep each kind of data before [`Store::prune`] deletes
+it. Zero means keep it forever.
+
+Auth keys aren't here; they expire after the auth DB's key life.
+*/
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Retention {
+    /// Report drafts, by when they were last saved. Default is 0.
+    pub drafts: u32,
+    /// Journaled failed API requests. Default is 0 (though the
+    /// `failure_journal` setting still limits how many are kept).
+    pub failed_requests: u32,
+    /// Archived copies of deleted goals. Default is 0.
+    pub deleted_goals: u32,
+    /// Records of students leaving Teachers' classes, which clients syncing
+    /// changes need only until they next sync. Default is 30.
+    pub departures: u32,
+    /// The log of webhook deliveries, by when they were queued. Default
+    /// is 30.
+    pub webhook_deliveries: u32,
+    /// Hour of the day (UTC) at which the daily cleanup runs. Default is 8.
+    pub hour: u8,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            drafts: 0,
+            failed_requests: 0,
+            deleted_goals: 0,
+            departures: 30,
+            webhook_deliveries: 30,
+            hour: 8,
+        }
+    }
+}
+
+/// Numbers of rows deleted from each table by a cleanup.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Pruned {
+    pub drafts: u64,
+    pub failed_requests: u64,
+    pub deleted_goals: u64,
+    pub departures: u64,
+    pub webhook_deliveries: u64,
+    /// Expired auth keys, filled in by [`Glob::prune`](crate::config::Glob::prune).
+    pub keys: u64,
+}
+
+impl Pruned {
+    pub fn total(&self) -> u64 {
+        self.drafts
+            + self.failed_requests
+            + self.deleted_goals
+            + self.departures
+            + self.webhook_deliveries
+            + self.keys
+    }
+
+    fn add(&mut self, other: &Pruned) {
+        self.drafts += other.drafts;
+        self.failed_requests += other.failed_requests;
+        self.deleted_goals += other.deleted_goals;
+        self.departures += other.departures;
+        self.webhook_deliveries += other.webhook_deliveries;
+        self.keys += other.keys;
+    }
+}
+
+/// Running totals of cleanups since the server started, for reporting to
+/// Admins.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PruneStats {
+    pub runs: u64,
+    /// When the most recent cleanup finished.
+    pub last_run: Option<String>,
+    /// What the most recent successful cleanup deleted.
+    pub last: Pruned,
+    /// What every cleanup has deleted.
+    pub total: Pruned,
+    pub failed: u64,
+    /// The most recent error (if any cleanups have failed).
+    pub last_error: Option<String>,
+}
+
+impl PruneStats {
+    /// Record the outcome of a cleanup that finished at `when`.
+    pub fn record(&mut self, res: &Result<Pruned, String>, when: String) {
+        self.runs += 1;
+        self.last_run = Some(when);
+        match res {
+            Ok(pruned) => {
+                self.total.add(pruned);
+                self.last = pruned.clone();
+            }
+            Err(e) => {
+                self.failed += 1;
+                self.last_error = Some(e.clone());
+            }
+        }
+    }
+}
+
+impl Store {
+    /// Delete rows from `table` whose `column` timestamp is more than `days`
+    /// days old, in batches, returning how many were deleted.
+    async fn prune_table(&self, table: &str, column: &str, days: u32) -> Result<u64, DbError> {
+        if days == 0 {
+            return Ok(0);
+        }
+        let days = days.min(i32::MAX as u32) as i32;
+        let stmt = format!(
+            "DELETE FROM {table} WHERE ctid = ANY(ARRAY(
+                SELECT ctid FROM {table}
+                WHERE {column} < (now() AT TIME ZONE 'UTC') - make_interval(days => $1)
+                LIMIT $2
+            ))",
+            table = table,
+            column = column,
+        );
+
+        let client = self.connect().await?;
+        let mut n_deleted: u64 = 0;
+        loop {
+            let n = client
+                .execute(&stmt, &[&days, &PRUNE_BATCH])
+                .await
+                .map_err(|e| DbError::from(e).annotate(&format!("Error pruning {}", table)))?;
+            n_deleted += n;
+            if n < PRUNE_BATCH as u64 {
+                break;
+            }
+        }
+        log::trace!("Pruned {} rows from {}.", &n_deleted, table);
+
+        Ok(n_deleted)
+    }
+
+    /// Delete everything that has been kept longer than `r` says to.
+    pub async fn prune(&self, r: &Retention) -> Result<Pruned, DbError> {
+        log::trace!("Store::prune( {:?} ) called.", r);
+
+        Ok(Pruned {
+            drafts: self.prune_table("drafts", "saved", r.drafts).await?,
+            failed_requests: self
+                .prune_table("failed_requests", "at", r.failed_requests)
+                .await?,
+            deleted_goals: self
+                .prune_table("deleted_goals", "deleted", r.deleted_goals)
+                .await?,
+            departures: self
+                .prune_table("student_departures", "departed", r.departures)
+                .await?,
+            webhook_deliveries: self
+                .prune_table("webhook_deliveries", "created", r.webhook_deliveries)
+                .await?,
+            keys: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
+
+    #[test]
+    fn retention_config() {
+        let r: Retention = toml::from_str("drafts = 90").unwrap();
+        assert_eq!(
+            r,
+            Retention {
+                drafts: 90,
+                ..Default::default()
+            }
+        );
+        let r: Retention = toml::from_str("").unwrap();
+        assert_eq!(r, Retention::default());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn prune_old_rows() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        {
+            let client = db.connect().await.unwrap();
+            for age in [1, 45, 100] {
+                let when = format!("(now() AT TIME ZONE 'UTC') - INTERVAL '{} days'", age);
+                client
+                    .execute(
+                        &format!(
+                            "INSERT INTO drafts (term, draft, saved) VALUES ('Fall', '', {})",
+                            &when
+                        ),
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                client
+                    .execute(
+                        &format!(
+                            "INSERT INTO failed_requests (at, method, path, status)
+                            VALUES ({}, 'POST', '/admin', 400)",
+                            &when
+                        ),
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                client
+                    .execute(
+                        &format!(
+                            "INSERT INTO student_departures (uname, departed) VALUES ('jj', {})",
+                            &when
+                        ),
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let r = Retention {
+            drafts: 30,
+            failed_requests: 60,
+            deleted_goals: 0,
+            departures: 0,
+            webhook_deliveries: 0,
+            hour: 0,
+        };
+        let pruned = db.prune(&r).await.unwrap();
+        assert_eq!(
+            pruned,
+            Pruned {
+                drafts: 2,
+                failed_requests: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(pruned.total(), 3);
+        assert_eq!(db.prune(&r).await.unwrap().total(), 0);
+
+        let client = db.connect().await.unwrap();
+        for (table, n) in [
+            ("drafts", 1i64),
+            ("failed_requests", 2),
+            ("student_departures", 3),
+        ] {
+            let row = client
+                .query_one(&format!("SELECT COUNT(*) AS n FROM {}", table), &[])
+                .await
+                .unwrap();
+            assert_eq!(row.get::<_, i64>("n"), n, "{}", table);
+        }
+
+        let mut stats = PruneStats::default();
+        stats.record(&Ok(pruned.clone()), "then".to_owned());
+        stats.record(&Err("oops".to_owned()), "now".to_owned());
+        stats.record(&Ok(pruned), "later".to_owned());
+        assert_eq!(stats.runs, 3);
+        assert_eq!(stats.failed, 1);
+        assert_eq!(stats.total.drafts, 4);
+        assert_eq!(stats.last.total(), 3);
+        assert_eq!(stats.last_run.as_deref(), Some("later"));
+
+        db.nuke_database().await.unwrap();
+    }
+}
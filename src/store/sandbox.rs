@@ -0,0 +1,367 @@
+/*!
+`Store` methods for Teachers' training sandboxes.
+
+A Teacher in training mode practices on a copy of their class's data, kept
+in a Postgres schema of their own (see [`sandbox_schema`]). The schema has
+its own copy of each of the [`SANDBOX_TABLES`]; a `Store` from
+[`Store::sandboxed`] looks for tables in that schema before `public`, so
+whatever the Teacher does to their students' goals, reports, and so on
+lands in the copies, while everything else (users, courses, the calendar)
+is read from the live tables.
+
+```sql
+CREATE TABLE training_mode (
+    uname   TEXT PRIMARY KEY REFERENCES teachers(uname) ON DELETE CASCADE,
+    since   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+);
+```
+
+A sandbox is filled when its Teacher is put into training mode (or it's
+reset), and dropped when they're taken out.
+*/
+use std::collections::HashSet;
+
+use tokio_postgres::Transaction;
+
+use super::{cached::IdleConns, DbError, Store};
+
+/// Which rows of a table get copied into a new sandbox.
+enum Rows {
+    /// All of them.
+    All,
+    /// Those whose `uname` is one of the Teacher's students.
+    Class,
+    /// Those whose `uname` is the Teacher's.
+    Own,
+    /// Those whose given column is the `id` of one of the copied goals.
+    Goals(&'static str),
+    /// None; the copy starts out empty.
+    None,
+}
+
+/**
+The tables a sandbox has its own copies of: everything a Teacher can
+change through the Teacher API. The copies are made in this order.
+
+Every student is copied, not just the Teacher's, because reading users
+takes a student row for every user with the Student role. The audit log
+is copied empty, so practice doesn't show up in the real one.
+*/
+static SANDBOX_TABLES: &[(&str, Rows)] = &[
+    ("students", Rows::All),
+    ("custom_chapters", Rows::Own),
+    ("goals", Rows::Class),
+    ("nmr", Rows::Goals("id")),
+    ("goal_rubrics", Rows::Goals("goal")),
+    ("tags", Rows::Own),
+    ("goal_tags", Rows::Goals("goal")),
+    ("completion_requests", Rows::Goals("goal")),
+    ("test_requests", Rows::Class),
+    ("deleted_goals", Rows::Class),
+    ("waivers", Rows::Class),
+    ("absences", Rows::Class),
+    ("facts", Rows::Class),
+    ("social", Rows::Class),
+    ("completion", Rows::Class),
+    ("drafts", Rows::Class),
+    ("reports", Rows::Class),
+    ("report_meta", Rows::Class),
+    ("report_visibility", Rows::Class),
+    ("report_notes", Rows::Class),
+    ("completion_notices", Rows::Class),
+    ("lag_history", Rows::Class),
+    ("digest_optout", Rows::Own),
+    ("kiosk_tokens", Rows::Own),
+    ("audit", Rows::None),
+];
+
+/// Name of the schema that holds the sandbox of the Teacher `tuname`.
+/// (Unames can be longer than Postgres allows names to be, so it's made
+/// from a hash.)
+pub fn sandbox_schema(tuname: &str) -> String {
+    let hash = blake3::hash(tuname.as_bytes());
+    format!("sandbox_{}", &hash.to_hex()[..16])
+}
+
+impl Store {
+    /**
+    Return a `Store` on the same database as this one whose connections
+    use the sandbox of the Teacher `tuname` (which should exist; see
+    [`Store::set_training`]).
+
+    It never pools its connections, even if this `Store` does.
+    */
+    pub fn sandboxed(&self, tuname: &str) -> Store {
+        Store {
+            connection_string: self.connection_string.clone(),
+            salt_chars: self.salt_chars.clone(),
+            salt_length: self.salt_length,
+            environment: self.environment.clone(),
+            idle: IdleConns::default(),
+            pool: None,
+            search_path: Some(format!("{}, public", &sandbox_schema(tuname))),
+            insert_chunk: self.insert_chunk,
+            insert_concurrency: self.insert_concurrency,
+        }
+    }
+
+    /// Return the unames of the Teachers in training mode.
+    pub async fn get_training(&self) -> Result<HashSet<String>, DbError> {
+        log::trace!("Store::get_training() called.");
+
+        let client = self.connect().await?;
+        let rows = client.query("SELECT uname FROM training_mode", &[]).await?;
+
+        let mut unames: HashSet<String> = HashSet::with_capacity(rows.len());
+        for row in rows.iter() {
+            unames.insert(row.try_get("uname")?);
+        }
+        Ok(unames)
+    }
+
+    /// (Re)create the sandbox of the Teacher `tuname`, with copies of the
+    /// live data.
+    async fn fill_sandbox(t: &Transaction<'_>, tuname: &str) -> Result<(), DbError> {
+        let schema = sandbox_schema(tuname);
+        t.batch_execute(&format!(
+            "DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}",
+            &schema
+        ))
+        .await
+        .map_err(|e| DbError::from(e).annotate("Unable to create sandbox schema"))?;
+
+        for (table, rows) in SANDBOX_TABLES.iter() {
+            t.execute(
+                &format!(
+                    "CREATE TABLE {0}.{1} (LIKE public.{1} INCLUDING ALL)",
+                    &schema, table
+                ),
+                &[],
+            )
+            .await
+            .map_err(|e| {
+                DbError::from(e).annotate(&format!("Unable to create sandbox {}", table))
+            })?;
+
+            let copy = format!("INSERT INTO {0}.{1} SELECT * FROM public.{1}", &schema, table);
+            let res = match rows {
+                Rows::All => t.execute(&copy, &[]).await,
+                Rows::Class => {
+                    let stmt = format!(
+                        "{} WHERE uname IN (SELECT uname FROM public.students WHERE teacher = $1)",
+                        &copy
+                    );
+                    t.execute(&stmt, &[&tuname]).await
+                }
+                Rows::Own => t.execute(&format!("{} WHERE uname = $1", &copy), &[&tuname]).await,
+                Rows::Goals(col) => {
+                    let stmt = format!(
+                        "{} WHERE {} IN (SELECT id FROM {}.goals)",
+                        &copy, col, &schema
+                    );
+                    t.execute(&stmt, &[]).await
+                }
+                Rows::None => Ok(0),
+            };
+            res.map_err(|e| DbError::from(e).annotate(&format!("Unable to copy {}", table)))?;
+        }
+
+        Ok(())
+    }
+
+    /**
+    Put the Teacher `tuname` into training mode, with a fresh sandbox, or
+    (if `training` is false) take them out of it, dropping their sandbox.
+
+    Putting a Teacher who is already in training mode into it again leaves
+    their sandbox alone.
+    */
+    pub async fn set_training(&self, tuname: &str, training: bool) -> Result<(), DbError> {
+        log::trace!("Store::set_training( {:?}, {} ) called.", tuname, &training);
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+        if training {
+            let n = t
+                .execute(
+                    "INSERT INTO training_mode (uname) VALUES ($1)
+                    ON CONFLICT DO NOTHING",
+                    &[&tuname],
+                )
+                .await?;
+            if n > 0 {
+                Store::fill_sandbox(&t, tuname).await?;
+            }
+        } else {
+            t.execute("DELETE FROM training_mode WHERE uname = $1", &[&tuname])
+                .await?;
+            t.batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {} CASCADE",
+                &sandbox_schema(tuname)
+            ))
+            .await?;
+        }
+        t.commit().await?;
+
+        Ok(())
+    }
+
+    /// Throw away everything the Teacher `tuname` has done in their sandbox,
+    /// replacing it with fresh copies of the live data. Returns `false` if
+    /// they aren't in training mode.
+    pub async fn reset_sandbox(&self, tuname: &str) -> Result<bool, DbError> {
+        log::trace!("Store::reset_sandbox( {:?} ) called.", tuname);
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+        if t
+            .query_opt("SELECT FROM training_mode WHERE uname = $1", &[&tuname])
+            .await?
+            .is_none()
+        {
+            return Ok(false);
+        }
+        Store::fill_sandbox(&t, tuname).await?;
+        t.commit().await?;
+
+        Ok(true)
+    }
+
+    /**
+    Bring the students in the sandbox of the Teacher `tuname` into line
+    with the live ones: students added since the sandbox was filled are
+    copied in, and students who have since been deleted are removed.
+    Students already in the sandbox are otherwise left as they are.
+    */
+    pub async fn sync_sandbox_students(&self, tuname: &str) -> Result<(), DbError> {
+        log::trace!("Store::sync_sandbox_students( {:?} ) called.", tuname);
+
+        let schema = sandbox_schema(tuname);
+        let client = self.connect().await?;
+        client
+            .batch_execute(&format!(
+                "DELETE FROM {0}.students s WHERE NOT EXISTS (
+                    SELECT FROM public.students p WHERE p.uname = s.uname
+                );
+                INSERT INTO {0}.students SELECT * FROM public.students p
+                    WHERE NOT EXISTS (
+                        SELECT FROM {0}.students s WHERE s.uname = p.uname
+                    )",
+                &schema
+            ))
+            .await
+            .map_err(|e| DbError::from(e).annotate("Unable to update sandbox students"))?;
+
+        Ok(())
+    }
+
+    /// Drop every sandbox.
+    #[cfg(any(test, feature = "fake"))]
+    pub(super) async fn drop_sandboxes(&self) -> Result<(), DbError> {
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT nspname FROM pg_namespace WHERE nspname LIKE 'sandbox\\_%'",
+                &[],
+            )
+            .await?;
+        for row in rows.iter() {
+            let schema: &str = row.try_get("nspname")?;
+            client
+                .batch_execute(&format!("DROP SCHEMA {} CASCADE", schema))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::pace::Waiver;
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+    use crate::user::Student;
+
+    fn waiver(seq: i16) -> Waiver {
+        Waiver {
+            sym: "pha".to_owned(),
+            seq,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn sandboxes() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        insert_users(
+            &db,
+            &["berro", "adams"],
+            &[("frog", "berro"), ("zack", "berro"), ("toad", "adams")],
+        )
+        .await;
+        db.set_waiver("frog", &waiver(1), true, "berro").await.unwrap();
+        db.set_waiver("toad", &waiver(1), true, "adams").await.unwrap();
+
+        assert!(!db.reset_sandbox("berro").await.unwrap());
+        db.set_training("berro", true).await.unwrap();
+        assert_eq!(
+            db.get_training().await.unwrap(),
+            HashSet::from(["berro".to_owned()])
+        );
+
+        // The sandbox starts out with berro's students' data, and what's
+        // done in it doesn't show up outside it.
+        let sandbox = db.sandboxed("berro");
+        assert_eq!(sandbox.get_users().await.unwrap().len(), 5);
+        assert_eq!(
+            sandbox.get_waivers_by_student("frog").await.unwrap(),
+            vec![waiver(1)]
+        );
+        assert!(sandbox.get_waivers_by_student("toad").await.unwrap().is_empty());
+        sandbox.set_waiver("frog", &waiver(2), true, "berro").await.unwrap();
+        assert_eq!(
+            sandbox.get_waivers_by_student("frog").await.unwrap(),
+            vec![waiver(1), waiver(2)]
+        );
+        assert_eq!(db.get_waivers_by_student("frog").await.unwrap(), vec![waiver(1)]);
+
+        // Putting berro into training mode again doesn't reset anything.
+        db.set_training("berro", true).await.unwrap();
+        assert_eq!(sandbox.get_waivers_by_student("frog").await.unwrap().len(), 2);
+        assert!(db.reset_sandbox("berro").await.unwrap());
+        assert_eq!(
+            sandbox.get_waivers_by_student("frog").await.unwrap(),
+            vec![waiver(1)]
+        );
+
+        // Students added after the sandbox was made get copied in.
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            let mut studs = Student::vec_from_csv_reader(std::io::Cursor::new(
+                "newt, Newt, Nathan, newt@gmail.com, newt.parent@gmail.com, berro".as_bytes(),
+            ))
+            .unwrap();
+            db.insert_students(&t, &mut studs).await.unwrap();
+            t.commit().await.unwrap();
+        }
+        assert!(sandbox.get_users().await.is_err());
+        db.sync_sandbox_students("berro").await.unwrap();
+        assert!(sandbox.get_users().await.unwrap().contains_key("newt"));
+
+        db.set_training("berro", false).await.unwrap();
+        assert!(db.get_training().await.unwrap().is_empty());
+        // A sandbox that's gone can't be synced, which is what keeps
+        // requests from using the live tables through it.
+        assert!(db.sync_sandbox_students("berro").await.is_err());
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+/*!
+Cached per-course aggregate statistics.
+
+These involve counting over every goal in a course, so rather than being
+computed on demand, they're stored in the `course_stats` table and
+recomputed in the background (see [`deferred`](crate::store::deferred))
+after bulk changes to goals.
+
+```sql
+CREATE TABLE course_stats (
+    sym         TEXT PRIMARY KEY,
+    n_students  BIGINT NOT NULL,
+    n_goals     BIGINT NOT NULL,
+    n_done      BIGINT NOT NULL
+);
+```
+*/
+use serde::Serialize;
+
+use super::{DbError, Store};
+
+/// Aggregate goal counts for a single course.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CourseStats {
+    pub sym: String,
+    /// Number of students with at least one goal in the course.
+    pub n_students: i64,
+    pub n_goals: i64,
+    pub n_done: i64,
+}
+
+impl Store {
+    /**
+    Recount the goals in the course with the given `sym` and update its
+    row in the `course_stats` table. A course with no goals has its row
+    removed.
+    */
+    pub async fn recompute_course_stats(&self, sym: &str) -> Result<(), DbError> {
+        log::trace!("Store::recompute_course_stats( {:?} ) called.", sym);
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        let row = t
+            .query_one(
+                "SELECT COUNT(DISTINCT uname) AS n_students,
+                    COUNT(*) AS n_goals, COUNT(done) AS n_done
+                FROM goals WHERE sym = $1",
+                &[&sym],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error counting goals"))?;
+        let n_students: i64 = row.try_get("n_students")?;
+        let n_goals: i64 = row.try_get("n_goals")?;
+        let n_done: i64 = row.try_get("n_done")?;
+
+        if n_goals == 0 {
+            t.execute("DELETE FROM course_stats WHERE sym = $1", &[&sym])
+                .await?;
+        } else {
+            t.execute(
+                "INSERT INTO course_stats (sym, n_students, n_goals, n_done)
+                    VALUES ($1, $2, $3, $4)
+                ON CONFLICT (sym) DO UPDATE SET
+                    n_students = EXCLUDED.n_students,
+                    n_goals = EXCLUDED.n_goals,
+                    n_done = EXCLUDED.n_done",
+                &[&sym, &n_students, &n_goals, &n_done],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error updating course stats"))?;
+        }
+
+        t.commit()
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error committing transaction"))
+    }
+
+    /// Return the cached statistics for every course that has any goals.
+    pub async fn get_course_stats(&self) -> Result<Vec<CourseStats>, DbError> {
+        log::trace!("Store::get_course_stats() called.");
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT sym, n_students, n_goals, n_done FROM course_stats
+                ORDER BY sym",
+                &[],
+            )
+            .await?;
+
+        let mut stats: Vec<CourseStats> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            stats.push(CourseStats {
+                sym: row.try_get("sym")?,
+                n_students: row.try_get("n_students")?,
+                n_goals: row.try_get("n_goals")?,
+                n_done: row.try_get("n_done")?,
+            });
+        }
+
+        Ok(stats)
+    }
+}
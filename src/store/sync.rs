@@ -0,0 +1,382 @@
+/*!
+`Store` methods for letting clients fetch only what's changed since they
+last looked.
+
+The `goals` and `students` tables both have an `updated_at` column:
+
+```sql
+updated_at  TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+```
+
+Rather than every `Store` method that changes them having to remember to
+keep it current, triggers do:
+
+  * Updating a row of either table sets its `updated_at`.
+  * Adding, changing, or removing a goal's tags (`goal_tags`), rubric
+    breakdown (`goal_rubrics`), or completion request
+    (`completion_requests`) counts as updating the goal.
+  * Deleting a goal, or adding, changing, or removing a waiver, counts as
+    updating the student.
+  * Deleting a student, or moving them to a different Teacher, records
+    their departure from their (old) Teacher's class:
+
+```sql
+CREATE TABLE student_departures (
+    uname       TEXT NOT NULL,
+    teacher     TEXT,
+    departed    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+);
+```
+
+Changes to courses (chapter titles or weights, say) aren't tracked; clients
+should reload everything when they change.
+*/
+use time::{Duration, PrimitiveDateTime};
+
+use super::{DbError, Store};
+
+/**
+How far before the requested time [`Store::get_changes_since`] looks.
+
+A row's `updated_at` is the time its transaction _started_, so a change
+that was in progress (but not yet visible) when a client last looked can
+have an `updated_at` before the time the client was told. Looking back a
+little further catches those, at the cost of sometimes sending the same
+change twice.
+*/
+const SYNC_SLACK: Duration = Duration::seconds(30);
+
+/**
+The functions and triggers that keep track of changes, as `(test, create,
+drop)` statements like those in [`SCHEMA`](super::SCHEMA). They're created
+after all the tables are, and dropped before.
+*/
+pub(super) static TRIGGERS: &[(&str, &str, &str)] = &[
+    (
+        "SELECT FROM pg_proc WHERE proname = 'sync_set_updated_at'",
+        "CREATE FUNCTION sync_set_updated_at() RETURNS trigger AS $$
+        BEGIN
+            NEW.updated_at := now() AT TIME ZONE 'UTC';
+            RETURN NEW;
+        END
+        $$ LANGUAGE plpgsql",
+        "DROP FUNCTION sync_set_updated_at",
+    ),
+    (
+        "SELECT FROM pg_proc WHERE proname = 'sync_touch_goal'",
+        "CREATE FUNCTION sync_touch_goal() RETURNS trigger AS $$
+        BEGIN
+            IF TG_OP = 'DELETE' THEN
+                UPDATE goals SET updated_at = now() AT TIME ZONE 'UTC' WHERE id = OLD.goal;
+            ELSE
+                UPDATE goals SET updated_at = now() AT TIME ZONE 'UTC' WHERE id = NEW.goal;
+            END IF;
+            RETURN NULL;
+        END
+        $$ LANGUAGE plpgsql",
+        "DROP FUNCTION sync_touch_goal",
+    ),
+    (
+        "SELECT FROM pg_proc WHERE proname = 'sync_touch_student'",
+        "CREATE FUNCTION sync_touch_student() RETURNS trigger AS $$
+        BEGIN
+            IF TG_OP = 'DELETE' THEN
+                UPDATE students SET updated_at = now() AT TIME ZONE 'UTC' WHERE uname = OLD.uname;
+            ELSE
+                UPDATE students SET updated_at = now() AT TIME ZONE 'UTC' WHERE uname = NEW.uname;
+            END IF;
+            RETURN NULL;
+        END
+        $$ LANGUAGE plpgsql",
+        "DROP FUNCTION sync_touch_student",
+    ),
+    (
+        "SELECT FROM pg_proc WHERE proname = 'sync_student_departs'",
+        "CREATE FUNCTION sync_student_departs() RETURNS trigger AS $$
+        BEGIN
+            IF TG_OP = 'DELETE' OR OLD.teacher IS DISTINCT FROM NEW.teacher THEN
+                INSERT INTO student_departures (uname, teacher)
+                    VALUES (OLD.uname, OLD.teacher);
+            END IF;
+            RETURN NULL;
+        END
+        $$ LANGUAGE plpgsql",
+        "DROP FUNCTION sync_student_departs",
+    ),
+    (
+        "SELECT FROM pg_trigger WHERE tgname = 'goals_set_updated_at'",
+        "CREATE TRIGGER goals_set_updated_at BEFORE UPDATE ON goals
+            FOR EACH ROW EXECUTE FUNCTION sync_set_updated_at()",
+        "DROP TRIGGER goals_set_updated_at ON goals",
+    ),
+    (
+        "SELECT FROM pg_trigger WHERE tgname = 'students_set_updated_at'",
+        "CREATE TRIGGER students_set_updated_at BEFORE UPDATE ON students
+            FOR EACH ROW EXECUTE FUNCTION sync_set_updated_at()",
+        "DROP TRIGGER students_set_updated_at ON students",
+    ),
+    (
+        "SELECT FROM pg_trigger WHERE tgname = 'goal_tags_touch_goal'",
+        "CREATE TRIGGER goal_tags_touch_goal AFTER INSERT OR UPDATE OR DELETE ON goal_tags
+            FOR EACH ROW EXECUTE FUNCTION sync_touch_goal()",
+        "DROP TRIGGER goal_tags_touch_goal ON goal_tags",
+    ),
+    (
+        "SELECT FROM pg_trigger WHERE tgname = 'goal_rubrics_touch_goal'",
+        "CREATE TRIGGER goal_rubrics_touch_goal AFTER INSERT OR UPDATE OR DELETE ON goal_rubrics
+            FOR EACH ROW EXECUTE FUNCTION sync_touch_goal()",
+        "DROP TRIGGER goal_rubrics_touch_goal ON goal_rubrics",
+    ),
+    (
+        "SELECT FROM pg_trigger WHERE tgname = 'completion_requests_touch_goal'",
+        "CREATE TRIGGER completion_requests_touch_goal
+            AFTER INSERT OR UPDATE OR DELETE ON completion_requests
+            FOR EACH ROW EXECUTE FUNCTION sync_touch_goal()",
+        "DROP TRIGGER completion_requests_touch_goal ON completion_requests",
+    ),
+    (
+        "SELECT FROM pg_trigger WHERE tgname = 'goals_touch_student'",
+        "CREATE TRIGGER goals_touch_student AFTER DELETE ON goals
+            FOR EACH ROW EXECUTE FUNCTION sync_touch_student()",
+        "DROP TRIGGER goals_touch_student ON goals",
+    ),
+    (
+        "SELECT FROM pg_trigger WHERE tgname = 'waivers_touch_student'",
+        "CREATE TRIGGER waivers_touch_student AFTER INSERT OR UPDATE OR DELETE ON waivers
+            FOR EACH ROW EXECUTE FUNCTION sync_touch_student()",
+        "DROP TRIGGER waivers_touch_student ON waivers",
+    ),
+    (
+        "SELECT FROM pg_trigger WHERE tgname = 'students_depart'",
+        "CREATE TRIGGER students_depart AFTER UPDATE OR DELETE ON students
+            FOR EACH ROW EXECUTE FUNCTION sync_student_departs()",
+        "DROP TRIGGER students_depart ON students",
+    ),
+];
+
+/// Which of a Teacher's students have changed since some time.
+#[derive(Debug, PartialEq)]
+pub struct Changes {
+    /// The database's clock when the changes were checked; the time to ask
+    /// for changes since next time.
+    pub as_of: PrimitiveDateTime,
+    /// Students of the Teacher who have been changed (or had any of their
+    /// goals or waivers changed).
+    pub changed: Vec<String>,
+    /// Students who have been deleted from the Teacher's class, or moved to
+    /// another one.
+    pub departed: Vec<String>,
+}
+
+impl Store {
+    /// Return the database's clock (in UTC), for use as a starting point for
+    /// [`Store::get_changes_since`].
+    pub async fn get_sync_timestamp(&self) -> Result<PrimitiveDateTime, DbError> {
+        log::trace!("Store::get_sync_timestamp() called.");
+
+        let client = self.connect().await?;
+        let row = client
+            .query_one("SELECT now() AT TIME ZONE 'UTC' AS now", &[])
+            .await?;
+
+        Ok(row.try_get("now")?)
+    }
+
+    /// Return which of Teacher `tuname`'s students have changed (or left)
+    /// since `since`.
+    pub async fn get_changes_since(
+        &self,
+        tuname: &str,
+        since: &PrimitiveDateTime,
+    ) -> Result<Changes, DbError> {
+        log::trace!(
+            "Store::get_changes_since( {:?}, {} ) called.",
+            tuname,
+            since
+        );
+
+        let since = since.saturating_sub(SYNC_SLACK);
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        let as_of: PrimitiveDateTime = t
+            .query_one("SELECT now() AT TIME ZONE 'UTC' AS now", &[])
+            .await?
+            .try_get("now")?;
+
+        let changed_rows = t
+            .query(
+                "SELECT uname FROM students
+                WHERE teacher = $1 AND (
+                    updated_at > $2
+                    OR EXISTS (
+                        SELECT 1 FROM goals
+                        WHERE goals.uname = students.uname AND goals.updated_at > $2
+                    )
+                )
+                ORDER BY uname",
+                &[&tuname, &since],
+            )
+            .await?;
+        let departed_rows = t
+            .query(
+                "SELECT DISTINCT uname FROM student_departures
+                WHERE teacher = $1 AND departed > $2
+                AND uname NOT IN (SELECT uname FROM students WHERE teacher = $1)
+                ORDER BY uname",
+                &[&tuname, &since],
+            )
+            .await?;
+        t.commit().await?;
+
+        let changed = changed_rows
+            .iter()
+            .map(|row| row.try_get("uname"))
+            .collect::<Result<Vec<String>, _>>()?;
+        let departed = departed_rows
+            .iter()
+            .map(|row| row.try_get("uname"))
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(Changes {
+            as_of,
+            changed,
+            departed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+
+    use serial_test::serial;
+    use time::macros::date;
+
+    use crate::course::Course;
+    use crate::pace::{BookCh, Goal, Source};
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+    use crate::user::User;
+
+    static COURSE: &str = "test/good_course_0.mix";
+
+    fn goal(uname: &str, sym: &str, seq: i16) -> Goal {
+        Goal {
+            id: 0,
+            uname: uname.to_owned(),
+            source: Source::Book(BookCh {
+                sym: sym.to_owned(),
+                seq,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due: Some(date!(2022 - 10 - 3)),
+            done: None,
+            tries: None,
+            weight: 0.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        }
+    }
+
+    /// Make everything look like it was last changed on 6/1/2022 (which
+    /// means getting the triggers out of the way).
+    async fn backdate(db: &Store) {
+        let client = db.connect().await.unwrap();
+        client
+            .batch_execute(
+                "ALTER TABLE goals DISABLE TRIGGER goals_set_updated_at;
+                ALTER TABLE students DISABLE TRIGGER students_set_updated_at;
+                UPDATE goals SET updated_at = '2022-06-01';
+                UPDATE students SET updated_at = '2022-06-01';
+                ALTER TABLE goals ENABLE TRIGGER goals_set_updated_at;
+                ALTER TABLE students ENABLE TRIGGER students_set_updated_at;",
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn changes_since() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        // Start clean, in case a failed test left courses behind.
+        db.nuke_database().await.unwrap();
+        db.ensure_db_schema().await.unwrap();
+
+        let crs = Course::from_reader(File::open(COURSE).unwrap()).unwrap();
+        let sym = crs.sym.clone();
+        db.insert_courses(&[crs]).await.unwrap();
+        insert_users(&db, &["berro", "jenny"], &[("frog", "berro"), ("zack", "berro")]).await;
+        db.insert_goals(&[goal("frog", &sym, 1), goal("zack", &sym, 1)])
+            .await
+            .unwrap();
+
+        // Everything has changed since a while ago.
+        let long_ago = date!(2022 - 1 - 1).midnight();
+        let ch = db.get_changes_since("berro", &long_ago).await.unwrap();
+        assert_eq!(ch.changed, vec!["frog".to_owned(), "zack".to_owned()]);
+        assert!(ch.departed.is_empty());
+
+        // Nothing has changed since later than now; look from a little into
+        // the future to get past the slack.
+        let later = ch.as_of + SYNC_SLACK + Duration::seconds(1);
+        let ch = db.get_changes_since("berro", &later).await.unwrap();
+        assert!(ch.changed.is_empty());
+
+        // Backdate everything, so changes made from here on stand out.
+        backdate(&db).await;
+        let since = date!(2022 - 7 - 1).midnight();
+        let ch = db.get_changes_since("berro", &since).await.unwrap();
+        assert!(ch.changed.is_empty());
+
+        // Changing a goal changes its student.
+        let mut g = db.get_goals_by_student("frog").await.unwrap().pop().unwrap();
+        g.due = Some(date!(2022 - 10 - 4));
+        db.update_goal(&g).await.unwrap();
+        let ch = db.get_changes_since("berro", &since).await.unwrap();
+        assert_eq!(ch.changed, vec!["frog".to_owned()]);
+
+        // So does deleting one (after backdating again).
+        backdate(&db).await;
+        let g = db.get_goals_by_student("zack").await.unwrap().pop().unwrap();
+        db.delete_goal(g.id, false, "berro").await.unwrap();
+        let ch = db.get_changes_since("berro", &since).await.unwrap();
+        assert_eq!(ch.changed, vec!["zack".to_owned()]);
+
+        // Moving a student to another Teacher is a departure for the old
+        // one and a change for the new one.
+        let mut zack = match db.get_user_by_uname("zack").await.unwrap() {
+            Some(User::Student(s)) => s,
+            x => panic!("Expected Student zack, got {:?}", &x),
+        };
+        zack.teacher = "jenny".to_owned();
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            db.update_student(&t, &zack).await.unwrap();
+            t.commit().await.unwrap();
+        }
+        let ch = db.get_changes_since("berro", &since).await.unwrap();
+        assert_eq!(ch.departed, vec!["zack".to_owned()]);
+        assert!(!ch.changed.contains(&"zack".to_owned()));
+        let ch = db.get_changes_since("jenny", &since).await.unwrap();
+        assert_eq!(ch.changed, vec!["zack".to_owned()]);
+        assert!(ch.departed.is_empty());
+
+        db.nuke_database().await.unwrap();
+    }
+}
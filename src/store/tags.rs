@@ -0,0 +1,336 @@
+/*!
+`Store` methods for Teacher-defined goal [`Tag`]s.
+
+Each Teacher has their own set of tags, which they can attach to any of
+their students' goals.
+
+```sql
+CREATE TABLE tags (
+    id      BIGSERIAL PRIMARY KEY,
+    uname   TEXT NOT NULL REFERENCES teachers(uname) ON DELETE CASCADE,
+    name    TEXT NOT NULL,
+    UNIQUE (uname, name)
+);
+
+CREATE TABLE goal_tags (
+    goal    BIGINT REFERENCES goals(id) ON DELETE CASCADE,
+    tag     BIGINT REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (goal, tag)
+);
+```
+
+The cascading deletes mean deleting goals, tags, or Teachers never has to
+worry about tags.
+*/
+use super::{DbError, Store};
+use crate::pace::Tag;
+
+impl Store {
+    /// Return all of the Teacher `tuname`'s tags, ordered by name.
+    pub async fn get_tags(&self, tuname: &str) -> Result<Vec<Tag>, DbError> {
+        log::trace!("Store::get_tags( {:?} ) called.", tuname);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT id, name FROM tags WHERE uname = $1 ORDER BY name",
+                &[&tuname],
+            )
+            .await?;
+
+        let mut tags: Vec<Tag> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            tags.push(Tag {
+                id: row.try_get("id")?,
+                name: row.try_get("name")?,
+            });
+        }
+
+        Ok(tags)
+    }
+
+    /// Create a new tag called `name` for the Teacher `tuname`.
+    pub async fn insert_tag(&self, tuname: &str, name: &str) -> Result<Tag, DbError> {
+        log::trace!("Store::insert_tag( {:?}, {:?} ) called.", tuname, name);
+
+        let client = self.connect().await?;
+        let row = client
+            .query_opt(
+                "INSERT INTO tags (uname, name) VALUES ($1, $2)
+                ON CONFLICT (uname, name) DO NOTHING
+                RETURNING id",
+                &[&tuname, &name],
+            )
+            .await?;
+
+        match row {
+            Some(row) => Ok(Tag {
+                id: row.try_get("id")?,
+                name: name.to_owned(),
+            }),
+            None => Err(DbError(format!(
+                "You already have a tag called {:?}.",
+                name
+            ))),
+        }
+    }
+
+    /// Rename the Teacher `tuname`'s tag with the given `id`.
+    pub async fn rename_tag(&self, tuname: &str, id: i64, name: &str) -> Result<(), DbError> {
+        log::trace!(
+            "Store::rename_tag( {:?}, {}, {:?} ) called.",
+            tuname,
+            &id,
+            name
+        );
+
+        let client = self.connect().await?;
+        if client
+            .query_opt(
+                "SELECT FROM tags WHERE uname = $1 AND name = $2 AND id <> $3",
+                &[&tuname, &name, &id],
+            )
+            .await?
+            .is_some()
+        {
+            return Err(DbError(format!(
+                "You already have a tag called {:?}.",
+                name
+            )));
+        }
+
+        match client
+            .execute(
+                "UPDATE tags SET name = $1 WHERE id = $2 AND uname = $3",
+                &[&name, &id, &tuname],
+            )
+            .await?
+        {
+            0 => Err(DbError(format!("You have no tag with id {}.", &id))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Delete the Teacher `tuname`'s tag with the given `id` (which also
+    /// removes it from any goals).
+    pub async fn delete_tag(&self, tuname: &str, id: i64) -> Result<(), DbError> {
+        log::trace!("Store::delete_tag( {:?}, {} ) called.", tuname, &id);
+
+        let client = self.connect().await?;
+        match client
+            .execute(
+                "DELETE FROM tags WHERE id = $1 AND uname = $2",
+                &[&id, &tuname],
+            )
+            .await?
+        {
+            0 => Err(DbError(format!("You have no tag with id {}.", &id))),
+            _ => Ok(()),
+        }
+    }
+
+    /**
+    Attach (or, if `attach` is `false`, detach) the Teacher `tuname`'s tag
+    `tag_id` to (from) each of the goals with the given `goal_ids`.
+
+    Every goal must belong to one of the Teacher's students. Returns the
+    number of goals whose tags actually changed.
+    */
+    pub async fn tag_goals(
+        &self,
+        tuname: &str,
+        tag_id: i64,
+        goal_ids: &[i64],
+        attach: bool,
+    ) -> Result<u64, DbError> {
+        log::trace!(
+            "Store::tag_goals( {:?}, {}, {:?}, {} ) called.",
+            tuname,
+            &tag_id,
+            goal_ids,
+            &attach
+        );
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        if t.query_opt(
+            "SELECT FROM tags WHERE id = $1 AND uname = $2",
+            &[&tag_id, &tuname],
+        )
+        .await?
+        .is_none()
+        {
+            return Err(DbError(format!("You have no tag with id {}.", &tag_id)));
+        }
+
+        let row = t
+            .query_one(
+                "SELECT COUNT(*) AS n FROM goals
+                    INNER JOIN students ON goals.uname = students.uname
+                WHERE students.teacher = $1 AND goals.id = ANY($2)",
+                &[&tuname, &goal_ids],
+            )
+            .await?;
+        let n_yours: i64 = row.try_get("n")?;
+        let mut unique_ids = goal_ids.to_vec();
+        unique_ids.sort_unstable();
+        unique_ids.dedup();
+        if n_yours as usize != unique_ids.len() {
+            return Err(DbError(
+                "Not all of those goals belong to your students.".to_owned(),
+            ));
+        }
+
+        let n = if attach {
+            t.execute(
+                "INSERT INTO goal_tags (goal, tag)
+                    SELECT UNNEST($1::BIGINT[]), $2
+                ON CONFLICT DO NOTHING",
+                &[&unique_ids, &tag_id],
+            )
+            .await?
+        } else {
+            t.execute(
+                "DELETE FROM goal_tags WHERE tag = $1 AND goal = ANY($2)",
+                &[&tag_id, &unique_ids],
+            )
+            .await?
+        };
+
+        t.commit()
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error committing transaction"))?;
+
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+
+    use serial_test::serial;
+
+    use crate::course::Course;
+    use crate::pace::{BookCh, Goal, Source};
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    fn goal(uname: &str, seq: i16) -> Goal {
+        Goal {
+            id: 0,
+            uname: uname.to_owned(),
+            source: Source::Book(BookCh {
+                sym: "pc".to_owned(),
+                seq,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due: None,
+            done: None,
+            tries: None,
+            weight: 0.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn tag_goals() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+
+        let crs = Course::from_reader(File::open("test/good_course_0.mix").unwrap()).unwrap();
+        db.insert_courses(&[crs]).await.unwrap();
+        insert_users(&db, &["berro", "jenny"], &[("frog", "berro"), ("zack", "jenny")]).await;
+        db.insert_goals(&[goal("frog", 1), goal("frog", 2), goal("zack", 1)])
+            .await
+            .unwrap();
+        let frog_ids: Vec<i64> = db
+            .get_goals_by_student("frog")
+            .await
+            .unwrap()
+            .iter()
+            .map(|g| g.id)
+            .collect();
+        let zack_id = db.get_goals_by_student("zack").await.unwrap()[0].id;
+
+        let retake = db.insert_tag("berro", "test retake").await.unwrap();
+        let sig = db.insert_tag("berro", "needs signature").await.unwrap();
+        assert!(db.insert_tag("berro", "test retake").await.is_err());
+        let names: Vec<String> = db
+            .get_tags("berro")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, ["needs signature", "test retake"]);
+        assert!(db.get_tags("jenny").await.unwrap().is_empty());
+
+        assert_eq!(
+            db.tag_goals("berro", retake.id, &frog_ids, true)
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            db.tag_goals("berro", sig.id, &frog_ids[..1], true)
+                .await
+                .unwrap(),
+            1
+        );
+        // Not berro's student, and not jenny's tag.
+        assert!(db
+            .tag_goals("berro", sig.id, &[zack_id], true)
+            .await
+            .is_err());
+        assert!(db
+            .tag_goals("jenny", sig.id, &[zack_id], true)
+            .await
+            .is_err());
+
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        let first = goals.iter().find(|g| g.id == frog_ids[0]).unwrap();
+        assert_eq!(first.tags, vec![sig.clone(), retake.clone()]);
+
+        db.rename_tag("berro", retake.id, "retake").await.unwrap();
+        assert!(db
+            .rename_tag("berro", retake.id, "needs signature")
+            .await
+            .is_err());
+        db.delete_tag("berro", sig.id).await.unwrap();
+        assert_eq!(
+            db.tag_goals("berro", retake.id, &frog_ids[1..], false)
+                .await
+                .unwrap(),
+            1
+        );
+
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        let first = goals.iter().find(|g| g.id == frog_ids[0]).unwrap();
+        let second = goals.iter().find(|g| g.id == frog_ids[1]).unwrap();
+        assert_eq!(
+            first.tags,
+            vec![Tag {
+                id: retake.id,
+                name: "retake".to_owned()
+            }]
+        );
+        assert!(second.tags.is_empty());
+
+        db.nuke_database().await.unwrap();
+    }
+}
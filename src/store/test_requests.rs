@@ -0,0 +1,288 @@
+/*!
+`Store` methods for Teachers' requests that the office print chapter tests.
+
+A Teacher requests a test of a chapter for one of their students; the
+request then waits in a queue for an Admin to print it and deliver it to
+the Teacher. Its status shows up on the student's goal for that chapter
+(see [`Goal::test`](crate::pace::Goal::test)).
+
+```sql
+CREATE TABLE test_requests (
+    id          BIGSERIAL PRIMARY KEY,
+    uname       TEXT NOT NULL REFERENCES students(uname) ON DELETE CASCADE,
+    sym         TEXT NOT NULL REFERENCES courses(sym) ON DELETE CASCADE,
+    seq         SMALLINT NOT NULL,
+    teacher     TEXT NOT NULL,  /* who asked */
+    status      TEXT NOT NULL DEFAULT 'requested',
+    requested   TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    updated     TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    UNIQUE (uname, sym, seq)
+);
+```
+
+There's at most one request per student per chapter; asking again (say,
+for a retake) sends the existing request back to the queue.
+*/
+use serde::Serialize;
+use tokio_postgres::{Row, Transaction};
+
+use super::{DbError, Store};
+use crate::{anon, pace::TestStatus};
+
+/// A request for a printed chapter test, as shown in the office's queue.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TestRequest {
+    pub id: i64,
+    pub uname: String,
+    #[serde(serialize_with = "anon::ser::last")]
+    pub last: String,
+    #[serde(serialize_with = "anon::ser::rest")]
+    pub rest: String,
+    pub teacher: String,
+    pub sym: String,
+    pub seq: i16,
+    pub status: TestStatus,
+    /// When the test was (last) requested (UTC), as `YYYY-MM-DD HH:MM:SS`.
+    pub requested: String,
+    /// When the status last changed, likewise.
+    pub updated: String,
+}
+
+fn request_from_row(row: &Row) -> Result<TestRequest, DbError> {
+    let status: &str = row.try_get("status")?;
+    Ok(TestRequest {
+        id: row.try_get("id")?,
+        uname: row.try_get("uname")?,
+        last: row.try_get("last")?,
+        rest: row.try_get("rest")?,
+        teacher: row.try_get("teacher")?,
+        sym: row.try_get("sym")?,
+        seq: row.try_get("seq")?,
+        status: status.parse().map_err(DbError)?,
+        requested: row.try_get("requested")?,
+        updated: row.try_get("updated")?,
+    })
+}
+
+impl Store {
+    /// Record that Teacher `tuname` wants a printed test of chapter `seq` of
+    /// course `sym` for the student `uname`.
+    pub async fn request_test(
+        &self,
+        tuname: &str,
+        uname: &str,
+        sym: &str,
+        seq: i16,
+    ) -> Result<(), DbError> {
+        log::trace!(
+            "Store::request_test( {:?}, {:?}, {:?}, {} ) called.",
+            tuname,
+            uname,
+            sym,
+            &seq
+        );
+
+        let client = self.connect().await?;
+        client
+            .execute(
+                "INSERT INTO test_requests (uname, sym, seq, teacher)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (uname, sym, seq) DO UPDATE
+                SET teacher = $4, status = 'requested',
+                    requested = (now() AT TIME ZONE 'UTC'),
+                    updated = (now() AT TIME ZONE 'UTC')",
+                &[&uname, &sym, &seq, &tuname],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error recording test request"))?;
+
+        Ok(())
+    }
+
+    /**
+    Withdraw the request for a test of chapter `seq` of course `sym` for
+    the student `uname`.
+
+    Only requests the office hasn't printed yet can be withdrawn; returns
+    whether there was one.
+    */
+    pub async fn cancel_test_request(
+        &self,
+        uname: &str,
+        sym: &str,
+        seq: i16,
+    ) -> Result<bool, DbError> {
+        log::trace!(
+            "Store::cancel_test_request( {:?}, {:?}, {} ) called.",
+            uname,
+            sym,
+            &seq
+        );
+
+        let client = self.connect().await?;
+        let n = client
+            .execute(
+                "DELETE FROM test_requests
+                WHERE uname = $1 AND sym = $2 AND seq = $3 AND status = 'requested'",
+                &[&uname, &sym, &seq],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error withdrawing test request"))?;
+
+        Ok(n > 0)
+    }
+
+    /// Clear the year's test requests, so their statuses don't show up on
+    /// next year's goals for the same chapters.
+    pub async fn yearly_clear_test_requests(t: &Transaction<'_>) -> Result<(), DbError> {
+        log::trace!("Store::yearly_clear_test_requests( [ T ] ) called.");
+
+        t.execute("DELETE FROM test_requests", &[]).await?;
+
+        Ok(())
+    }
+
+    /// Return the test requests that haven't been delivered yet (or, if
+    /// `all`, every one), oldest first.
+    pub async fn get_test_requests(&self, all: bool) -> Result<Vec<TestRequest>, DbError> {
+        log::trace!("Store::get_test_requests( {} ) called.", &all);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT
+                    test_requests.id, test_requests.uname, students.last, students.rest,
+                    test_requests.teacher, test_requests.sym, test_requests.seq,
+                    test_requests.status,
+                    to_char(test_requests.requested, 'YYYY-MM-DD HH24:MI:SS') AS requested,
+                    to_char(test_requests.updated, 'YYYY-MM-DD HH24:MI:SS') AS updated
+                FROM test_requests INNER JOIN students
+                    ON students.uname = test_requests.uname
+                WHERE $1 OR test_requests.status <> 'delivered'
+                ORDER BY test_requests.requested, test_requests.id",
+                &[&all],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error fetching test requests"))?;
+
+        rows.iter().map(request_from_row).collect()
+    }
+
+    /// Set the status of the test request with the given `id`; returns
+    /// whether there is one.
+    pub async fn set_test_status(&self, id: i64, status: TestStatus) -> Result<bool, DbError> {
+        log::trace!("Store::set_test_status( {}, {:?} ) called.", &id, &status);
+
+        let client = self.connect().await?;
+        let n = client
+            .execute(
+                "UPDATE test_requests
+                SET status = $1, updated = (now() AT TIME ZONE 'UTC')
+                WHERE id = $2",
+                &[&status.as_str(), &id],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error updating test request"))?;
+
+        Ok(n > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+    use time::macros::date;
+
+    use crate::course::Course;
+    use crate::pace::{BookCh, Goal, Source};
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    fn goal(uname: &str, seq: i16) -> Goal {
+        Goal {
+            id: 0,
+            uname: uname.to_owned(),
+            source: Source::Book(BookCh {
+                sym: "pc".to_owned(),
+                seq,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due: Some(date!(2022 - 10 - 3)),
+            done: None,
+            tries: None,
+            weight: 0.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_requests() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        db.nuke_database().await.unwrap();
+        db.ensure_db_schema().await.unwrap();
+
+        let crs =
+            Course::from_reader(std::fs::File::open("test/good_course_0.mix").unwrap()).unwrap();
+        db.insert_courses(&[crs]).await.unwrap();
+        insert_users(&db, &["berro"], &[("frog", "berro"), ("toad", "berro")]).await;
+        db.insert_goals(&[goal("frog", 1), goal("frog", 2), goal("toad", 1)])
+            .await
+            .unwrap();
+
+        db.request_test("berro", "frog", "pc", 1).await.unwrap();
+        db.request_test("berro", "toad", "pc", 1).await.unwrap();
+        let queue = db.get_test_requests(false).await.unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!((queue[0].last.as_str(), queue[0].seq), ("Frog", 1));
+        assert!(queue.iter().all(|r| r.status == TestStatus::Requested));
+
+        // The status shows up on the matching goal, and only that one.
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        let tests: Vec<Option<TestStatus>> = goals.iter().map(|g| g.test).collect();
+        assert_eq!(tests, [Some(TestStatus::Requested), None]);
+
+        db.set_test_status(queue[0].id, TestStatus::Printed).await.unwrap();
+        assert!(!db.cancel_test_request("frog", "pc", 1).await.unwrap());
+        assert!(db.cancel_test_request("toad", "pc", 1).await.unwrap());
+        assert!(!db.set_test_status(queue[1].id, TestStatus::Printed).await.unwrap());
+
+        db.set_test_status(queue[0].id, TestStatus::Delivered).await.unwrap();
+        assert!(db.get_test_requests(false).await.unwrap().is_empty());
+        assert_eq!(db.get_test_requests(true).await.unwrap().len(), 1);
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        assert_eq!(goals[0].test, Some(TestStatus::Delivered));
+
+        // Asking again puts it back in the queue.
+        db.request_test("berro", "frog", "pc", 1).await.unwrap();
+        let queue = db.get_test_requests(false).await.unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].status, TestStatus::Requested);
+
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            Store::yearly_clear_test_requests(&t).await.unwrap();
+            t.commit().await.unwrap();
+        }
+        assert!(db.get_test_requests(true).await.unwrap().is_empty());
+        // Old statuses don't linger on goals for the same chapters.
+        let goals = db.get_goals_by_student("frog").await.unwrap();
+        assert!(goals.iter().all(|g| g.test.is_none()));
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -25,12 +25,18 @@ CREATE TABLE students (
     fall_exam_fraction  REAL,
     spring_exam_fraction REAL,
     fall_notices   SMALLINT,
-    spring_notices SMALLINT
+    spring_notices SMALLINT,
+    updated_at  TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+                /* kept current by triggers; see `sync` */
+);
+
+CREATE TABLE digest_optout (
+    uname TEXT PRIMARY KEY REFERENCES teachers(uname)
 );
 
 ```
 */
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
 
 use futures::stream::{FuturesUnordered, StreamExt};
@@ -74,6 +80,68 @@ struct StudentSidecar {
     spring_notices: i16,
 }
 
+/**
+A chunk of `Student`s pulled apart into columns, to be inserted with a
+single statement that `UNNEST`s them; see `Store::insert_students()`.
+*/
+struct StudentColumns<'a> {
+    uname: Vec<&'a str>,
+    email: Vec<&'a str>,
+    salt: &'a [String],
+    last: Vec<&'a str>,
+    rest: Vec<&'a str>,
+    teacher: Vec<&'a str>,
+    parent: Vec<&'a str>,
+    fall_exam: Vec<Option<&'a str>>,
+    spring_exam: Vec<Option<&'a str>>,
+    fall_exam_fraction: Vec<f32>,
+    spring_exam_fraction: Vec<f32>,
+    fall_notices: Vec<i16>,
+    spring_notices: Vec<i16>,
+}
+
+impl<'a> StudentColumns<'a> {
+    fn new(studs: &'a [Student], salt: &'a [String]) -> StudentColumns<'a> {
+        StudentColumns {
+            uname: studs.iter().map(|s| s.base.uname.as_str()).collect(),
+            email: studs.iter().map(|s| s.base.email.as_str()).collect(),
+            salt,
+            last: studs.iter().map(|s| s.last.as_str()).collect(),
+            rest: studs.iter().map(|s| s.rest.as_str()).collect(),
+            teacher: studs.iter().map(|s| s.teacher.as_str()).collect(),
+            parent: studs.iter().map(|s| s.parent.as_str()).collect(),
+            fall_exam: studs.iter().map(|s| s.fall_exam.as_deref()).collect(),
+            spring_exam: studs.iter().map(|s| s.spring_exam.as_deref()).collect(),
+            fall_exam_fraction: studs.iter().map(|s| s.fall_exam_fraction).collect(),
+            spring_exam_fraction: studs.iter().map(|s| s.spring_exam_fraction).collect(),
+            fall_notices: studs.iter().map(|s| s.fall_notices).collect(),
+            spring_notices: studs.iter().map(|s| s.spring_notices).collect(),
+        }
+    }
+
+    /// Parameters for the 'users' table insert statement.
+    fn base_params<'b>(&'b self, role: &'b String) -> [&'b (dyn ToSql + Sync); 4] {
+        [&self.uname, role, &self.salt, &self.email]
+    }
+
+    /// Parameters for the 'students' table insert statement.
+    fn student_params(&self) -> [&(dyn ToSql + Sync); 11] {
+        [
+            &self.uname,
+            &self.last,
+            &self.rest,
+            &self.teacher,
+            &self.parent,
+            &self.fall_exam,
+            &self.spring_exam,
+            &self.fall_exam_fraction,
+            &self.spring_exam_fraction,
+            &self.fall_notices,
+            &self.spring_notices,
+        ]
+    }
+}
+
 /// Turn a row queried from the 'users' table in to a `BaseUser.
 fn base_user_from_row(row: &Row) -> Result<BaseUser, DbError> {
     log::trace!("base_user_from_row( {:?} ) called.", row);
@@ -120,10 +188,7 @@ fn student_from_row(row: &Row) -> Result<StudentSidecar, DbError> {
     log::trace!("student_from_row( {:?} ) called.", row);
 
     let teacher: Option<String> = row.try_get("teacher")?;
-    let teacher = match teacher {
-        Some(uname) => uname,
-        None => String::new(),
-    };
+    let teacher = teacher.unwrap_or_default();
 
     let s = StudentSidecar {
         uname: row.try_get("uname")?,
@@ -212,10 +277,16 @@ impl Store {
                 &params[..]
             ),
             t.execute("DELETE FROM reports WHERE uname = $1", &params[..]),
+            t.execute("DELETE FROM report_meta WHERE uname = $1", &params[..]),
+            t.execute("DELETE FROM report_releases WHERE uname = $1", &params[..]),
+            t.execute("DELETE FROM report_downloads WHERE uname = $1", &params[..]),
+            t.execute("DELETE FROM report_visibility WHERE uname = $1", &params[..]),
+            t.execute("DELETE FROM report_notes WHERE uname = $1", &params[..]),
             t.execute("DELETE FROM social WHERE uname = $1", &params[..]),
+            t.execute("DELETE FROM digest_optout WHERE uname = $1", &params[..]),
         )?;
 
-        let n_goals = self.delete_goals_by_student(t, uname).await?;
+        let n_goals = self.delete_goals_by_student(t, uname, None).await?;
         log::trace!("Deleted {} Goals.", &n_goals);
 
         let (s_del_res, t_del_res) = tokio::join!(
@@ -455,6 +526,8 @@ impl Store {
 
     /// Insert the slice of supplied students into the database. On success,
     /// the Student objects salts are set.
+    ///
+    /// Large slices are inserted in chunks; see [`Store::set_bulk_insert`].
     pub async fn insert_students(
         &self,
         t: &Transaction<'_>,
@@ -500,8 +573,14 @@ impl Store {
         let (buiq, stiq) = tokio::join!(
             t.prepare_typed(
                 "INSERT INTO users (uname, role, salt, email)
-                    VALUES ($1, $2, $3, $4)",
-                &[Type::TEXT, Type::TEXT, Type::TEXT, Type::TEXT]
+                    SELECT u, $2, s, e
+                    FROM UNNEST($1::TEXT[], $3::TEXT[], $4::TEXT[]) AS x(u, s, e)",
+                &[
+                    Type::TEXT_ARRAY,
+                    Type::TEXT,
+                    Type::TEXT_ARRAY,
+                    Type::TEXT_ARRAY
+                ]
             ),
             t.prepare_typed(
                 "INSERT INTO students (
@@ -510,131 +589,101 @@ impl Store {
                     fall_exam_fraction, spring_exam_fraction,
                     fall_notices, spring_notices
                 )
-                    VALUES (
-                        $1, $2, $3, $4, $5,
-                        $6, $7, $8, $9, $10, $11
+                    SELECT * FROM UNNEST(
+                        $1::TEXT[], $2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[],
+                        $6::TEXT[], $7::TEXT[], $8::REAL[], $9::REAL[],
+                        $10::SMALLINT[], $11::SMALLINT[]
                     )",
                 &[
-                    Type::TEXT,
-                    Type::TEXT,
-                    Type::TEXT,
-                    Type::TEXT,
-                    Type::TEXT,
-                    Type::TEXT,
-                    Type::TEXT,
-                    Type::FLOAT4,
-                    Type::FLOAT4,
-                    Type::INT2,
-                    Type::INT2
+                    Type::TEXT_ARRAY,
+                    Type::TEXT_ARRAY,
+                    Type::TEXT_ARRAY,
+                    Type::TEXT_ARRAY,
+                    Type::TEXT_ARRAY,
+                    Type::TEXT_ARRAY,
+                    Type::TEXT_ARRAY,
+                    Type::FLOAT4_ARRAY,
+                    Type::FLOAT4_ARRAY,
+                    Type::INT2_ARRAY,
+                    Type::INT2_ARRAY
                 ]
             ),
         );
         let (base_user_insert_query, student_table_insert_query) = (buiq?, stiq?);
 
         /*
-        This next block is terrible and confusing.
-
-        I want to run a bunch of database inserts concurrently. The
-        parameters referenced in the insert statements, though, must
-        be in a slice of references. These slices need to be bound
-        _oustide_ the async function call that's being passed into
-        `FuturesUnordered`.
-
-        The `Student`s all exist in a slice that's been passed to
-        this function, so we can refer to those unames and emails.
-
-        We create a `String` holding the role (`"Student"`) each of
-        these students will be assigned.
-
-        We create a vector of salt strings we can reference.
-
-        Finally we create a vector of four-element arrays (`pvec`).
-        Each array holds references to the four parameters we are
-        passing to the insert function to insert the corresponding
-        student:
-          * a reference to the `Student.base.uname`
-          * a reference to the String holding the text "role"
-          * a reference to one of the salts
-          * a reference to the `Student.base.email`
-
-        A reference to this array (making it a slice), will then be
-        passed as the "parameters" to the insert statement.
-
-        Phew.
-
-        We are also putting it in its own scope, so `inserts` will drop.
+        Students go in `self.insert_chunk` at a time, one statement per
+        chunk (with the columns passed as arrays and `UNNEST`ed), and with
+        at most `self.insert_concurrency` statements in flight at once, so
+        a file of a few thousand students doesn't queue up a few thousand
+        statements on the connection.
+
+        As with the single inserts this replaced, the parameters have to be
+        bound outside the futures, so we pull every chunk apart into
+        columns first, then keep a window of inserts going, pushing the
+        next chunk's insert whenever one finishes.
+
+        All the base users have to be in before any of the 'students' table
+        rows that refer to them.
         */
-        let mut n_base_inserted: u64 = 0;
-        let mut salts: Vec<String> = std::iter::repeat(())
-            .take(students.len())
+        let total = students.len();
+        let chunk_size = self.insert_chunk;
+        let concurrency = self.insert_concurrency;
+        let student_role = Role::Student.to_string();
+        let mut salts: Vec<String> = std::iter::repeat_n((), total)
             .map(|_| self.generate_salt())
             .collect();
+
+        let mut n_base_inserted: u64 = 0;
+        let mut n_stud_inserted: u64 = 0;
         {
-            let student_role = Role::Student.to_string();
-
-            let pvec: Vec<[&(dyn ToSql + Sync); 4]> = students
-                .iter()
-                .enumerate()
-                .map(|(n, s)| {
-                    let p: [&(dyn ToSql + Sync); 4] =
-                        [&s.base.uname, &student_role, &salts[n], &s.base.email];
-                    p
-                })
+            let columns: Vec<StudentColumns> = students
+                .chunks(chunk_size)
+                .zip(salts.chunks(chunk_size))
+                .map(|(studs, salt)| StudentColumns::new(studs, salt))
                 .collect();
 
+            let pvec: Vec<[&(dyn ToSql + Sync); 4]> =
+                columns.iter().map(|c| c.base_params(&student_role)).collect();
+            let mut pending = pvec.iter();
             let mut inserts = FuturesUnordered::new();
-            for params in pvec.iter() {
+            for params in pending.by_ref().take(concurrency) {
                 inserts.push(t.execute(&base_user_insert_query, params));
             }
 
             while let Some(res) = inserts.next().await {
                 match res {
-                    Ok(_) => {
-                        n_base_inserted += 1;
+                    Ok(n) => {
+                        n_base_inserted += n;
+                        log::debug!("Inserted {} of {} base users.", &n_base_inserted, &total);
                     }
                     Err(e) => {
-                        let estr = format!("Error inserting base user into database: {}", &e);
+                        let estr = format!("Error inserting base users into database: {}", &e);
                         return Err(DbError(estr));
                     }
                 }
+                if let Some(params) = pending.next() {
+                    inserts.push(t.execute(&base_user_insert_query, params));
+                }
             }
-        }
-
-        /*
-        We're about to do a similar thing here. See the previous massive
-        comment block if you're confused.
-        */
-        let mut n_stud_inserted: u64 = 0;
-        {
-            let pvec: Vec<[&(dyn ToSql + Sync); 11]> = students
-                .iter()
-                .map(|s| {
-                    let p: [&(dyn ToSql + Sync); 11] = [
-                        &s.base.uname,
-                        &s.last,
-                        &s.rest,
-                        &s.teacher,
-                        &s.parent,
-                        &s.fall_exam,
-                        &s.spring_exam,
-                        &s.fall_exam_fraction,
-                        &s.spring_exam_fraction,
-                        &s.fall_notices,
-                        &s.spring_notices,
-                    ];
-                    p
-                })
-                .collect();
 
+            let pvec: Vec<[&(dyn ToSql + Sync); 11]> =
+                columns.iter().map(|c| c.student_params()).collect();
+            let mut pending = pvec.iter();
             let mut inserts = FuturesUnordered::new();
-            for params in pvec.iter() {
+            for params in pending.by_ref().take(concurrency) {
                 inserts.push(t.execute(&student_table_insert_query, params));
             }
 
             while let Some(res) = inserts.next().await {
                 match res {
-                    Ok(_) => {
-                        n_stud_inserted += 1;
+                    Ok(n) => {
+                        n_stud_inserted += n;
+                        log::debug!(
+                            "Inserted {} of {} students table rows.",
+                            &n_stud_inserted,
+                            &total
+                        );
                     }
                     Err(e) => {
                         let estr =
@@ -642,6 +691,18 @@ impl Store {
                         return Err(DbError(estr));
                     }
                 }
+                if let Some(params) = pending.next() {
+                    inserts.push(t.execute(&student_table_insert_query, params));
+                }
+            }
+
+            if columns.len() > 1 {
+                log::info!(
+                    "Inserted {} students in {} chunks of up to {}.",
+                    &n_stud_inserted,
+                    columns.len(),
+                    &chunk_size
+                );
             }
         }
 
@@ -708,6 +769,55 @@ impl Store {
         Ok(())
     }
 
+    /**
+    Move all of Teacher `from_t`'s students to Teacher `to_t`, all in one
+    transaction, and return the unames of the students moved.
+    */
+    pub async fn reassign_students(
+        &self,
+        from_t: &str,
+        to_t: &str,
+    ) -> Result<Vec<String>, DbError> {
+        log::trace!("Store::reassign_students( {:?}, {:?} ) called.", from_t, to_t);
+
+        if from_t == to_t {
+            return Err(DbError(format!(
+                "Students can't be moved from Teacher {:?} to the same Teacher.",
+                from_t
+            )));
+        }
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        for uname in [from_t, to_t] {
+            if t
+                .query_opt("SELECT FROM teachers WHERE uname = $1", &[&uname])
+                .await?
+                .is_none()
+            {
+                return Err(DbError(format!("There is no Teacher {:?}.", uname)));
+            }
+        }
+
+        let rows = t
+            .query(
+                "UPDATE students SET teacher = $1 WHERE teacher = $2
+                RETURNING uname",
+                &[&to_t, &from_t],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error reassigning students"))?;
+        let mut unames: Vec<String> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            unames.push(row.try_get("uname")?);
+        }
+
+        t.commit().await?;
+        unames.sort();
+        Ok(unames)
+    }
+
     async fn get_base_users(t: &Transaction<'_>) -> Result<HashMap<String, BaseUser>, DbError> {
         log::trace!("Store::get_base_users( &T ) called.");
 
@@ -949,6 +1059,86 @@ This absolutely shouldn't be able to happen, but here we are.",
         Ok(Some(u))
     }
 
+    /// Return the role of user `uname`, if there is such a user.
+    pub async fn get_user_role(&self, uname: &str) -> Result<Option<Role>, DbError> {
+        log::trace!("Store::get_user_role( {:?} ) called.", uname);
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+        let role = check_existing_user_role(&t, uname).await?;
+        t.commit().await?;
+
+        Ok(role)
+    }
+
+    /// Return the unames and roles of all users with the given email
+    /// address (ignoring case), in uname order.
+    pub async fn get_users_by_email(&self, email: &str) -> Result<Vec<(String, Role)>, DbError> {
+        log::trace!("Store::get_users_by_email( {:?} ) called.", email);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT uname, role FROM users
+                WHERE lower(email) = lower($1)
+                ORDER BY uname",
+                &[&email],
+            )
+            .await?;
+
+        let mut users: Vec<(String, Role)> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            let role_str: &str = row.try_get("role")?;
+            let role: Role = role_str.parse().map_err(DbError)?;
+            users.push((row.try_get("uname")?, role));
+        }
+
+        Ok(users)
+    }
+
+    /// Return the unames of all Teachers who have opted out of the weekly
+    /// summary email.
+    pub async fn get_digest_optouts(&self) -> Result<HashSet<String>, DbError> {
+        log::trace!("Store::get_digest_optouts() called.");
+
+        let client = self.connect().await?;
+        let rows = client.query("SELECT uname FROM digest_optout", &[]).await?;
+
+        let mut unames: HashSet<String> = HashSet::with_capacity(rows.len());
+        for row in rows.iter() {
+            unames.insert(row.try_get("uname")?);
+        }
+
+        Ok(unames)
+    }
+
+    /// Opt the given Teacher out of (if `opt_out` is `true`) or back into
+    /// the weekly summary email.
+    pub async fn set_digest_optout(&self, uname: &str, opt_out: bool) -> Result<(), DbError> {
+        log::trace!(
+            "Store::set_digest_optout( {:?}, {} ) called.",
+            uname,
+            &opt_out
+        );
+
+        let client = self.connect().await?;
+        if opt_out {
+            client
+                .execute(
+                    "INSERT INTO digest_optout (uname) VALUES ($1)
+                    ON CONFLICT DO NOTHING",
+                    &[&uname],
+                )
+                .await?;
+        } else {
+            client
+                .execute("DELETE FROM digest_optout WHERE uname = $1", &[&uname])
+                .await?;
+        }
+
+        Ok(())
+    }
+
     /**
     Delete all Student-oriented data: everything from the `goals` table, all
     the `students` sidecar data, all the `users` with role `student`.
@@ -966,9 +1156,15 @@ This absolutely shouldn't be able to happen, but here we are.",
             t.execute("DELETE FROM facts", &[]),
             t.execute("DELETE FROM nmr", &[]),
             t.execute("DELETE FROM reports", &[]),
+            t.execute("DELETE FROM report_meta", &[]),
+            t.execute("DELETE FROM report_releases", &[]),
+            t.execute("DELETE FROM report_downloads", &[]),
+            t.execute("DELETE FROM report_visibility", &[]),
+            t.execute("DELETE FROM report_notes", &[]),
             t.execute("DELETE FROM social", &[]),
         )?;
             t.execute("DELETE FROM goals", &[]).await?;
+            t.execute("DELETE FROM deleted_goals", &[]).await?;
             t.execute("DELETE FROM students", &[]).await?;
         let uname_rows = t
             .query(
@@ -998,43 +1194,43 @@ mod tests {
     use crate::UnifiedError;
 
     fn same_students(a: &Student, b: &Student) -> bool {
-        if &a.base.uname != &b.base.uname {
+        if a.base.uname != b.base.uname {
             return false;
         }
-        if &a.base.role != &b.base.role {
+        if a.base.role != b.base.role {
             return false;
         }
-        if &a.base.email != &b.base.email {
+        if a.base.email != b.base.email {
             return false;
         }
-        if &a.last != &b.last {
+        if a.last != b.last {
             return false;
         }
-        if &a.rest != &b.rest {
+        if a.rest != b.rest {
             return false;
         }
-        if &a.teacher != &b.teacher {
+        if a.teacher != b.teacher {
             return false;
         }
-        if &a.parent != &b.parent {
+        if a.parent != b.parent {
             return false;
         }
-        if &a.fall_exam != &b.fall_exam {
+        if a.fall_exam != b.fall_exam {
             return false;
         }
-        if &a.spring_exam != &b.spring_exam {
+        if a.spring_exam != b.spring_exam {
             return false;
         }
-        if &a.fall_exam_fraction != &b.spring_exam_fraction {
+        if a.fall_exam_fraction != b.spring_exam_fraction {
             return false;
         }
-        if &a.spring_exam_fraction != &b.spring_exam_fraction {
+        if a.spring_exam_fraction != b.spring_exam_fraction {
             return false;
         }
-        if &a.fall_notices != &b.fall_notices {
+        if a.fall_notices != b.fall_notices {
             return false;
         }
-        if &a.spring_notices != &b.spring_notices {
+        if a.spring_notices != b.spring_notices {
             return false;
         }
         true
@@ -1120,11 +1316,30 @@ mod tests {
                 (u.uname(), u.email(), u.role())
             );
         }
+        t.commit().await?;
+
+        // berro leaves; irfan takes over both of their students.
+        assert_eq!(
+            db.reassign_students("berro", "irfan").await.unwrap(),
+            vec!["frog".to_owned(), "ghill".to_owned()]
+        );
+        assert!(db.reassign_students("berro", "irfan").await.unwrap().is_empty());
+        assert!(db.reassign_students("irfan", "irfan").await.is_err());
+        assert!(db.reassign_students("irfan", "nobody").await.is_err());
+        let moved = db.get_users().await.unwrap();
+        for (uname, teacher) in [("frog", "irfan"), ("ghill", "irfan"), ("zack", "jenny")] {
+            match moved.get(uname) {
+                Some(User::Student(s)) => assert_eq!(&s.teacher, teacher),
+                x => panic!("Expected User::Student, got {:?}", &x),
+            }
+        }
+
+        let t = client.transaction().await?;
 
         for stud in studs.drain(..) {
             let s = match umap.remove(&stud.base.uname).unwrap() {
                 User::Student(s) => s,
-                x @ _ => panic!("Expected User::Student, got {:?}", &x),
+                x => panic!("Expected User::Student, got {:?}", &x),
             };
             assert!(same_students(&stud, &s));
             db.delete_user(&t, &stud.base.uname).await.unwrap();
@@ -1140,6 +1355,109 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn insert_students_in_chunks() -> Result<(), UnifiedError> {
+        ensure_logging();
+
+        let mut db = Store::new(TEST_CONNECTION.to_owned());
+        // More chunks than can be in flight at once, and a short last one.
+        db.set_bulk_insert(3, 2);
+        db.ensure_db_schema().await?;
+
+        let mut client = db.connect().await?;
+        let t = client.transaction().await?;
+        for (uname, email, name) in TEACHERS.iter() {
+            db.insert_teacher(&t, uname, email, name).await.unwrap();
+        }
+
+        let mut studs: Vec<Student> = (0..11)
+            .map(|n| {
+                let base = BaseUser {
+                    uname: format!("stud{}", n),
+                    role: Role::Student,
+                    salt: String::new(),
+                    email: format!("stud{}@gmail.com", n),
+                };
+                let u = base.into_student(
+                    format!("Last{}", n),
+                    format!("Rest{}", n),
+                    TEACHERS[n % TEACHERS.len()].0.to_owned(),
+                    format!("parent{}@gmail.com", n),
+                    (n % 2 == 0).then(|| "B+".to_owned()),
+                    None,
+                    0.2,
+                    0.2,
+                    n as i16,
+                    0,
+                );
+                match u {
+                    User::Student(s) => s,
+                    x => panic!("Expected User::Student, got {:?}", &x),
+                }
+            })
+            .collect();
+        assert_eq!(
+            db.insert_students(&t, &mut studs).await.unwrap(),
+            studs.len()
+        );
+        assert!(studs.iter().all(|s| !s.base.salt.is_empty()));
+        t.commit().await?;
+
+        let mut umap = db.get_users().await?;
+        for stud in studs.iter() {
+            let s = match umap.remove(&stud.base.uname).unwrap() {
+                User::Student(s) => s,
+                x => panic!("Expected User::Student, got {:?}", &x),
+            };
+            assert!(same_students(stud, &s));
+            assert_eq!(stud.base.salt, s.base.salt);
+        }
+
+        db.nuke_database().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn existing_users() -> Result<(), UnifiedError> {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await?;
+
+        {
+            let mut client = db.connect().await?;
+            let t = client.transaction().await?;
+            for (uname, email) in ADMINS.iter() {
+                db.insert_admin(&t, uname, email).await?;
+            }
+            for (uname, email) in BOSSES.iter() {
+                db.insert_boss(&t, uname, email).await?;
+            }
+            t.commit().await?;
+        }
+
+        assert_eq!(db.get_user_role("dan").await?, Some(Role::Admin));
+        assert_eq!(db.get_user_role("tdg").await?, Some(Role::Boss));
+        assert_eq!(db.get_user_role("nobody").await?, None);
+
+        assert_eq!(
+            db.get_users_by_email("Thelma@CamelotAcademy.org").await?,
+            vec![
+                ("admin".to_owned(), Role::Admin),
+                ("tdg".to_owned(), Role::Boss)
+            ]
+        );
+        assert!(db
+            .get_users_by_email("nobody@camelotacademy.org")
+            .await?
+            .is_empty());
+
+        db.nuke_database().await?;
+        Ok(())
+    }
+
     #[tokio::test]
     #[serial]
     async fn alter_users() -> Result<(), UnifiedError> {
@@ -0,0 +1,209 @@
+/*!
+`Store` methods for chapters individual students have been excused from
+(see [`Waiver`]).
+
+```sql
+CREATE TABLE waivers (
+    uname       TEXT REFERENCES students(uname) ON DELETE CASCADE,
+    sym         TEXT,
+    seq         SMALLINT,
+    waived_by   TEXT NOT NULL,  /* uname of the Teacher or Admin */
+    PRIMARY KEY (uname, sym, seq)
+);
+```
+
+Waivers aren't tied to the `courses` table, so deleting a course doesn't
+have to deal with them; waivers for chapters that no longer exist are just
+ignored when building [`Pace`](crate::pace::Pace)s. They're cleared by the
+yearly nuke along with the goals.
+*/
+use std::collections::HashMap;
+
+use tokio_postgres::Transaction;
+
+use super::{DbError, Store};
+use crate::pace::Waiver;
+
+/// Selects the chapters the student `$1` has been excused from.
+pub(super) const STUDENT_WAIVERS: &str =
+    "SELECT sym, seq FROM waivers WHERE uname = $1 ORDER BY sym, seq";
+
+impl Store {
+    /// Return the chapters the student `uname` has been excused from.
+    pub async fn get_waivers_by_student(&self, uname: &str) -> Result<Vec<Waiver>, DbError> {
+        log::trace!("Store::get_waivers_by_student( {:?} ) called.", uname);
+
+        let client = self.connect().await?;
+        let rows = client.query(STUDENT_WAIVERS, &[&uname]).await?;
+
+        let mut waivers: Vec<Waiver> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            waivers.push(Waiver {
+                sym: row.try_get("sym")?,
+                seq: row.try_get("seq")?,
+            });
+        }
+
+        Ok(waivers)
+    }
+
+    /// Return the waived chapters of each of the students of the Teacher
+    /// `tuname` who have any.
+    pub async fn get_waivers_by_teacher(
+        &self,
+        tuname: &str,
+    ) -> Result<HashMap<String, Vec<Waiver>>, DbError> {
+        log::trace!("Store::get_waivers_by_teacher( {:?} ) called.", tuname);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT waivers.uname, sym, seq
+                FROM waivers INNER JOIN students ON waivers.uname = students.uname
+                WHERE students.teacher = $1
+                ORDER BY sym, seq",
+                &[&tuname],
+            )
+            .await?;
+
+        let mut waivers: HashMap<String, Vec<Waiver>> = HashMap::new();
+        for row in rows.iter() {
+            let w = Waiver {
+                sym: row.try_get("sym")?,
+                seq: row.try_get("seq")?,
+            };
+            waivers.entry(row.try_get("uname")?).or_default().push(w);
+        }
+
+        Ok(waivers)
+    }
+
+    /**
+    Waive (if `waived` is `true`) or un-waive the chapter `w` for the student
+    `uname`. `by` is the uname of the user doing the waiving.
+
+    Returns whether anything changed.
+    */
+    pub async fn set_waiver(
+        &self,
+        uname: &str,
+        w: &Waiver,
+        waived: bool,
+        by: &str,
+    ) -> Result<bool, DbError> {
+        log::trace!(
+            "Store::set_waiver( {:?}, {:?}, {}, {:?} ) called.",
+            uname,
+            w,
+            &waived,
+            by
+        );
+
+        let client = self.connect().await?;
+        let n = if waived {
+            client
+                .execute(
+                    "INSERT INTO waivers (uname, sym, seq, waived_by)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT DO NOTHING",
+                    &[&uname, &w.sym, &w.seq, &by],
+                )
+                .await
+                .map_err(|e| {
+                    DbError::from(e).annotate(&format!("Error waiving chapter for {:?}", uname))
+                })?
+        } else {
+            client
+                .execute(
+                    "DELETE FROM waivers WHERE uname = $1 AND sym = $2 AND seq = $3",
+                    &[&uname, &w.sym, &w.seq],
+                )
+                .await?
+        };
+
+        Ok(n > 0)
+    }
+
+    /// Clear the year's waivers.
+    pub async fn yearly_clear_waivers(t: &Transaction<'_>) -> Result<(), DbError> {
+        log::trace!("Store::yearly_clear_waivers( [ T ] ) called.");
+
+        t.execute("DELETE FROM waivers", &[]).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    fn waiver(sym: &str, seq: i16) -> Waiver {
+        Waiver {
+            sym: sym.to_owned(),
+            seq,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn waivers() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        insert_users(&db, &["berro"], &[("frog", "berro"), ("zack", "berro")]).await;
+
+        assert!(db
+            .set_waiver("frog", &waiver("pha", 2), true, "berro")
+            .await
+            .unwrap());
+        assert!(db
+            .set_waiver("frog", &waiver("pha", 1), true, "admin")
+            .await
+            .unwrap());
+        // Waiving the same chapter twice doesn't change anything.
+        assert!(!db
+            .set_waiver("frog", &waiver("pha", 1), true, "berro")
+            .await
+            .unwrap());
+        assert!(db
+            .set_waiver("zack", &waiver("pha", 1), true, "berro")
+            .await
+            .unwrap());
+
+        assert_eq!(
+            db.get_waivers_by_student("frog").await.unwrap(),
+            vec![waiver("pha", 1), waiver("pha", 2)]
+        );
+
+        assert!(db
+            .set_waiver("frog", &waiver("pha", 1), false, "berro")
+            .await
+            .unwrap());
+        assert!(!db
+            .set_waiver("frog", &waiver("pha", 1), false, "berro")
+            .await
+            .unwrap());
+
+        let by_teacher = db.get_waivers_by_teacher("berro").await.unwrap();
+        assert_eq!(by_teacher.len(), 2);
+        assert_eq!(by_teacher["frog"], vec![waiver("pha", 2)]);
+        assert_eq!(by_teacher["zack"], vec![waiver("pha", 1)]);
+
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            Store::yearly_clear_waivers(&t).await.unwrap();
+            t.commit().await.unwrap();
+        }
+        assert!(db.get_waivers_by_teacher("berro").await.unwrap().is_empty());
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -0,0 +1,425 @@
+/*!
+`Store` methods for outbound webhooks (see [`webhook`](crate::webhook)):
+the endpoints Admins have registered, and the queue and log of deliveries
+to them.
+
+```sql
+CREATE TABLE webhooks (
+    id      BIGSERIAL PRIMARY KEY,
+    url     TEXT NOT NULL,
+    secret  TEXT NOT NULL,
+    events  TEXT[] NOT NULL,    /* see `webhook::Event` */
+    active  BOOL NOT NULL DEFAULT true
+);
+
+CREATE TABLE webhook_deliveries (
+    id              BIGSERIAL PRIMARY KEY,
+    hook            BIGINT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+    event           TEXT NOT NULL,
+    payload         TEXT NOT NULL,
+    created         TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    status          TEXT NOT NULL DEFAULT 'pending',
+    attempts        SMALLINT NOT NULL DEFAULT 0,
+    next_attempt    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    response        SMALLINT,   /* HTTP status of the last attempt */
+    error           TEXT        /* what went wrong with the last attempt */
+);
+```
+
+Deleting a webhook deletes its deliveries, too. Otherwise, the log is kept
+as long as the `[retention]` table of the config file says.
+*/
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Serialize;
+use tokio_postgres::Row;
+
+use super::{DbError, Store};
+use crate::webhook::{Attempt, DeliveryStatus, Event};
+
+/// Length of the secrets generated for new webhooks.
+const SECRET_LENGTH: usize = 32;
+
+/// An endpoint that gets POSTed to when the [`Event`]s it wants happen.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    /// Signs deliveries; see [`sign`](crate::webhook::sign).
+    pub secret: String,
+    pub events: Vec<Event>,
+    /// Inactive webhooks don't get any deliveries queued.
+    pub active: bool,
+}
+
+/// A delivery due to be sent, with everything needed to send it.
+#[derive(Debug)]
+pub struct PendingDelivery {
+    pub id: i64,
+    pub url: String,
+    pub secret: String,
+    pub event: Event,
+    pub payload: String,
+    /// Number of times sending it has already failed.
+    pub attempts: i16,
+}
+
+/// A delivery, as shown in the log.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Delivery {
+    pub id: i64,
+    /// `id` of the [`Webhook`].
+    pub hook: i64,
+    pub url: String,
+    pub event: Event,
+    /// When the event happened (UTC), as `YYYY-MM-DD HH:MM:SS`.
+    pub created: String,
+    pub status: DeliveryStatus,
+    pub attempts: i16,
+    /// When it's next due to be sent, likewise, if it's still pending.
+    pub next_attempt: Option<String>,
+    /// HTTP status of the response to the last attempt, if there was one.
+    pub response: Option<i16>,
+    /// What went wrong with the last attempt, if anything.
+    pub error: Option<String>,
+}
+
+fn parse_events(names: Vec<String>) -> Result<Vec<Event>, DbError> {
+    names
+        .iter()
+        .map(|s| s.parse::<Event>().map_err(DbError::from))
+        .collect()
+}
+
+fn webhook_from_row(row: &Row) -> Result<Webhook, DbError> {
+    Ok(Webhook {
+        id: row.try_get("id")?,
+        url: row.try_get("url")?,
+        secret: row.try_get("secret")?,
+        events: parse_events(row.try_get("events")?)?,
+        active: row.try_get("active")?,
+    })
+}
+
+fn delivery_from_row(row: &Row) -> Result<Delivery, DbError> {
+    let event: &str = row.try_get("event")?;
+    let status: &str = row.try_get("status")?;
+    Ok(Delivery {
+        id: row.try_get("id")?,
+        hook: row.try_get("hook")?,
+        url: row.try_get("url")?,
+        event: event.parse()?,
+        created: row.try_get("created")?,
+        status: status.parse()?,
+        attempts: row.try_get("attempts")?,
+        next_attempt: row.try_get("next_attempt")?,
+        response: row.try_get("response")?,
+        error: row.try_get("error")?,
+    })
+}
+
+impl Store {
+    /// Return all the registered webhooks, in the order they were added.
+    pub async fn get_webhooks(&self) -> Result<Vec<Webhook>, DbError> {
+        log::trace!("Store::get_webhooks() called.");
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT id, url, secret, events, active FROM webhooks ORDER BY id",
+                &[],
+            )
+            .await?;
+
+        rows.iter().map(webhook_from_row).collect()
+    }
+
+    /// Register a new (active) webhook with a freshly generated secret, and
+    /// return it.
+    pub async fn insert_webhook(&self, url: &str, events: &[Event]) -> Result<Webhook, DbError> {
+        log::trace!("Store::insert_webhook( {:?}, {:?} ) called.", url, events);
+
+        let secret: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SECRET_LENGTH)
+            .map(char::from)
+            .collect();
+        let names: Vec<&str> = events.iter().map(Event::as_str).collect();
+
+        let client = self.connect().await?;
+        let row = client
+            .query_one(
+                "INSERT INTO webhooks (url, secret, events) VALUES ($1, $2, $3)
+                RETURNING id, url, secret, events, active",
+                &[&url, &secret, &names],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error registering webhook"))?;
+
+        webhook_from_row(&row)
+    }
+
+    /// Change the `url`, `events`, and whether the webhook `id` is `active`.
+    /// Returns whether there was such a webhook.
+    pub async fn update_webhook(
+        &self,
+        id: i64,
+        url: &str,
+        events: &[Event],
+        active: bool,
+    ) -> Result<bool, DbError> {
+        log::trace!(
+            "Store::update_webhook( {}, {:?}, {:?}, {} ) called.",
+            &id,
+            url,
+            events,
+            &active
+        );
+
+        let names: Vec<&str> = events.iter().map(Event::as_str).collect();
+        let client = self.connect().await?;
+        let n = client
+            .execute(
+                "UPDATE webhooks SET url = $1, events = $2, active = $3 WHERE id = $4",
+                &[&url, &names, &active, &id],
+            )
+            .await?;
+
+        Ok(n > 0)
+    }
+
+    /// Delete the webhook `id` (and its deliveries). Returns whether there
+    /// was one to delete.
+    pub async fn delete_webhook(&self, id: i64) -> Result<bool, DbError> {
+        log::trace!("Store::delete_webhook( {} ) called.", &id);
+
+        let client = self.connect().await?;
+        let n = client
+            .execute("DELETE FROM webhooks WHERE id = $1", &[&id])
+            .await?;
+
+        Ok(n > 0)
+    }
+
+    /// Queue a delivery of `payload` to every active webhook that wants
+    /// `event`. Returns the number queued.
+    pub async fn queue_webhook_deliveries(
+        &self,
+        event: Event,
+        payload: &str,
+    ) -> Result<usize, DbError> {
+        log::trace!(
+            "Store::queue_webhook_deliveries( {}, [ {} bytes ] ) called.",
+            &event,
+            payload.len()
+        );
+
+        let client = self.connect().await?;
+        let n = client
+            .execute(
+                "INSERT INTO webhook_deliveries (hook, event, payload)
+                SELECT id, $1, $2 FROM webhooks
+                WHERE active AND $1 = ANY(events)",
+                &[&event.as_str(), &payload],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error queueing webhook deliveries"))?;
+
+        Ok(n as usize)
+    }
+
+    /// Return up to `limit` pending deliveries that are due to be sent,
+    /// longest-waiting first.
+    pub async fn get_due_webhook_deliveries(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<PendingDelivery>, DbError> {
+        log::trace!("Store::get_due_webhook_deliveries( {} ) called.", &limit);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT webhook_deliveries.id, webhooks.url, webhooks.secret,
+                    webhook_deliveries.event, webhook_deliveries.payload,
+                    webhook_deliveries.attempts
+                FROM webhook_deliveries JOIN webhooks ON webhooks.id = webhook_deliveries.hook
+                WHERE webhook_deliveries.status = 'pending'
+                    AND webhook_deliveries.next_attempt <= (now() AT TIME ZONE 'UTC')
+                ORDER BY webhook_deliveries.next_attempt, webhook_deliveries.id
+                LIMIT $1",
+                &[&limit],
+            )
+            .await?;
+
+        let mut due: Vec<PendingDelivery> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            let event: &str = row.try_get("event")?;
+            due.push(PendingDelivery {
+                id: row.try_get("id")?,
+                url: row.try_get("url")?,
+                secret: row.try_get("secret")?,
+                event: event.parse()?,
+                payload: row.try_get("payload")?,
+                attempts: row.try_get("attempts")?,
+            });
+        }
+
+        Ok(due)
+    }
+
+    /**
+    Record an `attempt` to send the delivery `id`.
+
+    If it didn't go through, the delivery stays pending, due again in
+    `retry_in` seconds, or fails for good if `retry_in` is `None`.
+    */
+    pub async fn record_webhook_attempt(
+        &self,
+        id: i64,
+        attempt: &Attempt,
+        retry_in: Option<u64>,
+    ) -> Result<(), DbError> {
+        log::trace!(
+            "Store::record_webhook_attempt( {}, {:?}, {:?} ) called.",
+            &id,
+            attempt,
+            &retry_in
+        );
+
+        let status = match (&attempt.error, retry_in) {
+            (None, _) => DeliveryStatus::Delivered,
+            (Some(_), Some(_)) => DeliveryStatus::Pending,
+            (Some(_), None) => DeliveryStatus::Failed,
+        };
+        let response = attempt.response.map(|n| n as i16);
+        let retry_secs = retry_in.unwrap_or(0) as f64;
+
+        let client = self.connect().await?;
+        client
+            .execute(
+                "UPDATE webhook_deliveries SET
+                    status = $1, attempts = attempts + 1, response = $2, error = $3,
+                    next_attempt = (now() AT TIME ZONE 'UTC') + make_interval(secs => $4)
+                WHERE id = $5",
+                &[&status.as_str(), &response, &attempt.error, &retry_secs, &id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Return the `limit` most recently queued deliveries, newest first.
+    pub async fn get_webhook_deliveries(&self, limit: i64) -> Result<Vec<Delivery>, DbError> {
+        log::trace!("Store::get_webhook_deliveries( {} ) called.", &limit);
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT webhook_deliveries.id, hook, webhooks.url, event,
+                    to_char(created, 'YYYY-MM-DD HH24:MI:SS') AS created,
+                    status, attempts, response, error,
+                    CASE WHEN status = 'pending'
+                        THEN to_char(next_attempt, 'YYYY-MM-DD HH24:MI:SS')
+                    END AS next_attempt
+                FROM webhook_deliveries JOIN webhooks ON webhooks.id = webhook_deliveries.hook
+                ORDER BY webhook_deliveries.id DESC
+                LIMIT $1",
+                &[&limit],
+            )
+            .await?;
+
+        rows.iter().map(delivery_from_row).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
+
+    #[tokio::test]
+    #[serial]
+    async fn webhooks() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+
+        let tutoring = db
+            .insert_webhook(
+                "https://tutoring.example.org/camp",
+                &[Event::StudentLagging, Event::GoalCompleted],
+            )
+            .await
+            .unwrap();
+        assert!(tutoring.active);
+        assert_eq!(tutoring.secret.len(), SECRET_LENGTH);
+        let office = db
+            .insert_webhook("https://office.example.org/camp", &[Event::ReportFinalized])
+            .await
+            .unwrap();
+        assert_eq!(db.get_webhooks().await.unwrap(), vec![tutoring.clone(), office.clone()]);
+
+        assert_eq!(
+            db.queue_webhook_deliveries(Event::GoalCompleted, "{}").await.unwrap(),
+            1
+        );
+        assert_eq!(
+            db.queue_webhook_deliveries(Event::ReportFinalized, "{}").await.unwrap(),
+            1
+        );
+        // Inactive webhooks get nothing.
+        assert!(db
+            .update_webhook(office.id, &office.url, &office.events, false)
+            .await
+            .unwrap());
+        assert_eq!(
+            db.queue_webhook_deliveries(Event::ReportFinalized, "{}").await.unwrap(),
+            0
+        );
+        assert!(!db.update_webhook(-1, &office.url, &[], true).await.unwrap());
+
+        let due = db.get_due_webhook_deliveries(10).await.unwrap();
+        assert_eq!(due.len(), 2);
+        assert_eq!(due[0].event, Event::GoalCompleted);
+        assert_eq!(&due[0].secret, &tutoring.secret);
+
+        let failed = Attempt {
+            response: Some(503),
+            error: Some("Endpoint returned 503 Service Unavailable.".to_owned()),
+        };
+        db.record_webhook_attempt(due[0].id, &failed, Some(60))
+            .await
+            .unwrap();
+        let ok = Attempt {
+            response: Some(200),
+            error: None,
+        };
+        db.record_webhook_attempt(due[1].id, &ok, None).await.unwrap();
+        // The retry isn't due yet.
+        assert!(db.get_due_webhook_deliveries(10).await.unwrap().is_empty());
+
+        let log = db.get_webhook_deliveries(10).await.unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].status, DeliveryStatus::Delivered);
+        assert_eq!(log[0].next_attempt, None);
+        assert_eq!(log[1].status, DeliveryStatus::Pending);
+        assert_eq!((log[1].attempts, log[1].response), (1, Some(503)));
+        assert!(log[1].next_attempt.is_some());
+
+        db.record_webhook_attempt(due[0].id, &failed, None)
+            .await
+            .unwrap();
+        let log = db.get_webhook_deliveries(10).await.unwrap();
+        assert_eq!((log[1].status, log[1].attempts), (DeliveryStatus::Failed, 2));
+
+        assert!(db.delete_webhook(tutoring.id).await.unwrap());
+        assert!(!db.delete_webhook(tutoring.id).await.unwrap());
+        assert_eq!(db.get_webhook_deliveries(10).await.unwrap().len(), 1);
+
+        db.nuke_database().await.unwrap();
+    }
+}
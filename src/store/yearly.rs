@@ -0,0 +1,338 @@
+/*!
+Previewing and archiving the data removed by the yearly nuke (see
+[`Glob::yearly_data_nuke`](crate::config::Glob::yearly_data_nuke)).
+
+Because the nuke is irreversible, it has to be previewed first. The
+[`NukePreview`] lists exactly what would be deleted, along with a `token`
+that depends on all of it; the nuke itself is refused unless it's passed
+the token from a preview of the data as it still stands. Before anything
+is deleted, all of it gets dumped (as JSON) by [`Store::yearly_archive`].
+
+The dump is kept in the database, labeled with the academic year it's
+from, by [`Store::archive_academic_year`], so Admins can look back at
+previous years without digging through the server's archive directory:
+
+```sql
+CREATE TABLE year_archives (
+    id          BIGSERIAL PRIMARY KEY,
+    year        TEXT NOT NULL,  /* academic year label, like '2022--2023' */
+    archived    TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+    dump        TEXT NOT NULL   /* JSON from Store::yearly_archive() */
+);
+```
+*/
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use time::PrimitiveDateTime;
+use tokio_postgres::{Row, Transaction};
+
+use super::{DbError, Store};
+
+/// Every table the yearly nuke clears. (`goal_tags`, `goal_rubrics`, and
+/// `completion_requests` are cleared by cascading from `goals`, but are
+/// listed so they show up in previews and archives.)
+const YEARLY_TABLES: &[&str] = &[
+    "goals",
+    "goal_tags",
+    "goal_rubrics",
+    "completion_requests",
+    "test_requests",
+    "deleted_goals",
+    "nmr",
+    "facts",
+    "social",
+    "drafts",
+    "reports",
+    "report_meta",
+    "report_releases",
+    "report_downloads",
+    "report_visibility",
+    "report_notes",
+    "completion_notices",
+    "lag_history",
+    "waivers",
+    "absences",
+];
+
+/// Tables that aren't cleared by the yearly nuke, but are archived with it
+/// anyway, so each year's archive says who its students were.
+const ALSO_ARCHIVED: &[&str] = &["students", "completion"];
+
+/// A year's archived data (without the data itself); see
+/// [`Store::archive_academic_year`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct YearArchive {
+    pub id: i64,
+    /// Label of the academic year the data is from.
+    pub year: String,
+    pub archived: PrimitiveDateTime,
+    /// Length of the dump, in bytes.
+    pub size: i64,
+}
+
+fn year_archive_from_row(row: &Row) -> Result<YearArchive, DbError> {
+    Ok(YearArchive {
+        id: row.try_get("id")?,
+        year: row.try_get("year")?,
+        archived: row.try_get("archived")?,
+        size: row.try_get("size")?,
+    })
+}
+
+/// What the yearly nuke would delete.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct NukePreview {
+    /// Number of rows that would be deleted from each table.
+    pub counts: BTreeMap<String, i64>,
+    /// Students who have any data that would be deleted.
+    pub unames: Vec<String>,
+    /// Must be passed to the nuke to confirm it.
+    pub token: String,
+}
+
+impl Store {
+    /// Report what the yearly nuke would delete if run in transaction `t`.
+    pub async fn yearly_nuke_preview_in(t: &Transaction<'_>) -> Result<NukePreview, DbError> {
+        log::trace!("Store::yearly_nuke_preview_in( [ T ] ) called.");
+
+        let mut counts: BTreeMap<String, i64> = BTreeMap::new();
+        for table in YEARLY_TABLES.iter() {
+            let row = t
+                .query_one(&format!("SELECT COUNT(*) AS n FROM {}", table), &[])
+                .await?;
+            counts.insert(table.to_string(), row.try_get("n")?);
+        }
+
+        let rows = t
+            .query(
+                "SELECT uname FROM goals
+                UNION SELECT uname FROM facts
+                UNION SELECT uname FROM social
+                UNION SELECT uname FROM drafts
+                UNION SELECT uname FROM reports
+                ORDER BY uname",
+                &[],
+            )
+            .await?;
+        let mut unames: Vec<String> = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            unames.push(row.try_get("uname")?);
+        }
+
+        let mut hasher = blake3::Hasher::new();
+        for (table, n) in counts.iter() {
+            hasher.update(format!("{}={};", table, n).as_bytes());
+        }
+        for uname in unames.iter() {
+            hasher.update(uname.as_bytes());
+            hasher.update(b";");
+        }
+        let token = hasher.finalize().to_hex()[..16].to_owned();
+
+        Ok(NukePreview {
+            counts,
+            unames,
+            token,
+        })
+    }
+
+    /// Report what the yearly nuke would delete right now.
+    pub async fn yearly_nuke_preview(&self) -> Result<NukePreview, DbError> {
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+        Store::yearly_nuke_preview_in(&t).await
+    }
+
+    /**
+    Return the entire contents of every table the yearly nuke clears (and
+    the [`ALSO_ARCHIVED`] ones), as a JSON object whose keys are table names
+    and whose values are arrays of rows (as JSON objects).
+    */
+    pub async fn yearly_archive(t: &Transaction<'_>) -> Result<String, DbError> {
+        log::trace!("Store::yearly_archive( [ T ] ) called.");
+
+        let mut tables = serde_json::Map::new();
+        for table in YEARLY_TABLES.iter().chain(ALSO_ARCHIVED.iter()) {
+            let row = t
+                .query_one(
+                    &format!(
+                        "SELECT COALESCE(json_agg(t), '[]'::json)::TEXT AS rows FROM {} t",
+                        table
+                    ),
+                    &[],
+                )
+                .await
+                .map_err(|e| DbError::from(e).annotate(&format!("Error archiving {}", table)))?;
+            let text: String = row.try_get("rows")?;
+            let rows: serde_json::Value = serde_json::from_str(&text)
+                .map_err(|e| format!("Error reading archive of {}: {}", table, &e))?;
+            tables.insert(table.to_string(), rows);
+        }
+
+        Ok(serde_json::Value::Object(tables).to_string())
+    }
+
+    /**
+    Dump everything the yearly nuke clears (see [`Store::yearly_archive`])
+    into the `year_archives` table, labeled as being from academic year
+    `year`, as part of transaction `t`.
+
+    Returns the new archive's details, along with the dump itself.
+    */
+    pub async fn archive_academic_year(
+        t: &Transaction<'_>,
+        year: &str,
+    ) -> Result<(YearArchive, String), DbError> {
+        log::trace!("Store::archive_academic_year( [ T ], {:?} ) called.", year);
+
+        let dump = Store::yearly_archive(t).await?;
+        let row = t
+            .query_one(
+                "INSERT INTO year_archives (year, dump) VALUES ($1, $2)
+                RETURNING id, year, archived, LENGTH(dump)::BIGINT AS size",
+                &[&year, &dump],
+            )
+            .await
+            .map_err(|e| {
+                DbError::from(e).annotate(&format!("Error saving archive of {:?}", year))
+            })?;
+
+        Ok((year_archive_from_row(&row)?, dump))
+    }
+
+    /// Return the details of every archived year, most recent first.
+    pub async fn get_year_archives(&self) -> Result<Vec<YearArchive>, DbError> {
+        log::trace!("Store::get_year_archives() called.");
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT id, year, archived, LENGTH(dump)::BIGINT AS size
+                FROM year_archives ORDER BY archived DESC, id DESC",
+                &[],
+            )
+            .await?;
+
+        rows.iter().map(year_archive_from_row).collect()
+    }
+
+    /// Return the details and the JSON dump of the archive `id`, if there
+    /// is one.
+    pub async fn get_year_archive(
+        &self,
+        id: i64,
+    ) -> Result<Option<(YearArchive, String)>, DbError> {
+        log::trace!("Store::get_year_archive( {} ) called.", &id);
+
+        let client = self.connect().await?;
+        let row = client
+            .query_opt(
+                "SELECT id, year, archived, LENGTH(dump)::BIGINT AS size, dump
+                FROM year_archives WHERE id = $1",
+                &[&id],
+            )
+            .await?;
+
+        match row {
+            Some(row) => Ok(Some((year_archive_from_row(&row)?, row.try_get("dump")?))),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+
+    use serial_test::serial;
+
+    use crate::course::Course;
+    use crate::pace::{BookCh, Goal, Source};
+    use crate::store::tests::{insert_users, TEST_CONNECTION};
+    use crate::tests::ensure_logging;
+
+    fn goal(uname: &str, seq: i16) -> Goal {
+        Goal {
+            id: 0,
+            uname: uname.to_owned(),
+            source: Source::Book(BookCh {
+                sym: "pc".to_owned(),
+                seq,
+                level: 0.0,
+            }),
+            review: false,
+            incomplete: false,
+            due: None,
+            done: None,
+            tries: None,
+            weight: 0.0,
+            score: None,
+            tags: Vec::new(),
+            rubric: Vec::new(),
+            part: None,
+            progress: None,
+            requested: false,
+            test: None,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn preview_and_archive() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+
+        let crs = Course::from_reader(File::open("test/good_course_0.mix").unwrap()).unwrap();
+        db.insert_courses(&[crs]).await.unwrap();
+        insert_users(&db, &["berro"], &[("frog", "berro"), ("zack", "berro")]).await;
+        db.insert_goals(&[goal("frog", 1), goal("frog", 2)])
+            .await
+            .unwrap();
+
+        let preview = db.yearly_nuke_preview().await.unwrap();
+        assert_eq!(preview.counts["goals"], 2);
+        assert_eq!(preview.counts["reports"], 0);
+        assert_eq!(preview.unames, ["frog"]);
+        assert_eq!(db.yearly_nuke_preview().await.unwrap(), preview);
+
+        {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            let archive: serde_json::Value =
+                serde_json::from_str(&Store::yearly_archive(&t).await.unwrap()).unwrap();
+            assert_eq!(archive["goals"].as_array().unwrap().len(), 2);
+            assert_eq!(archive["goals"][0]["uname"], "frog");
+            assert!(archive["nmr"].as_array().unwrap().is_empty());
+            assert_eq!(archive["students"].as_array().unwrap().len(), 2);
+        }
+
+        assert!(db.get_year_archives().await.unwrap().is_empty());
+        let (saved, dump) = {
+            let mut client = db.connect().await.unwrap();
+            let t = client.transaction().await.unwrap();
+            let saved = Store::archive_academic_year(&t, "2022--2023").await.unwrap();
+            t.commit().await.unwrap();
+            saved
+        };
+        assert_eq!(saved.year, "2022--2023");
+        assert_eq!(saved.size as usize, dump.len());
+        assert_eq!(db.get_year_archives().await.unwrap(), vec![saved.clone()]);
+        assert_eq!(
+            db.get_year_archive(saved.id).await.unwrap(),
+            Some((saved.clone(), dump))
+        );
+        assert_eq!(db.get_year_archive(saved.id + 1).await.unwrap(), None);
+
+        db.insert_goals(&[goal("zack", 1)]).await.unwrap();
+        let changed = db.yearly_nuke_preview().await.unwrap();
+        assert_ne!(changed.token, preview.token);
+        assert_eq!(changed.unames, ["frog", "zack"]);
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+/*!
+`Store` methods for academic year metadata.
+
+Without any of this, the academic year is guessed from the first day of
+the calendar (see [`Glob::academic_year`](crate::config::Glob::academic_year)).
+An Admin can instead say outright what the current year is called and when
+it begins and ends. The dates are plain calendar days at the school, so
+they mean the same thing whatever time zone the server happens to run in.
+
+```sql
+CREATE TABLE academic_years (
+    label   TEXT PRIMARY KEY,   /* like '2022--2023' */
+    starts  DATE NOT NULL,
+    ends    DATE NOT NULL,
+    active  BOOLEAN NOT NULL DEFAULT FALSE  /* at most one is */
+);
+```
+*/
+use serde::{Deserialize, Serialize};
+use time::Date;
+use tokio_postgres::Row;
+
+use super::{DbError, Store};
+
+/// An academic year, from `starts` through `ends` (inclusive).
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AcademicYear {
+    pub label: String,
+    pub starts: Date,
+    pub ends: Date,
+    /// Whether this is the current academic year.
+    #[serde(default)]
+    pub active: bool,
+}
+
+impl AcademicYear {
+    /// The calendar year in which this academic year starts; this is the
+    /// "year" that report sidecars and completion history are filed under.
+    pub fn start_year(&self) -> i32 {
+        self.starts.year()
+    }
+
+    /// Whether `day` falls in this academic year.
+    pub fn contains(&self, day: &Date) -> bool {
+        self.starts <= *day && *day <= self.ends
+    }
+
+    /// Return an error explaining why this can't be saved, if it can't.
+    pub fn check(&self) -> Result<(), String> {
+        let label = self.label.trim();
+        if label.is_empty() {
+            return Err("An academic year needs a label.".to_owned());
+        }
+        if self.ends < self.starts {
+            return Err(format!(
+                "Academic year {:?} ends ({}) before it starts ({}).",
+                label, &self.ends, &self.starts
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn year_from_row(row: &Row) -> Result<AcademicYear, DbError> {
+    Ok(AcademicYear {
+        label: row.try_get("label")?,
+        starts: row.try_get("starts")?,
+        ends: row.try_get("ends")?,
+        active: row.try_get("active")?,
+    })
+}
+
+impl Store {
+    /// Return all academic years, earliest first.
+    pub async fn get_academic_years(&self) -> Result<Vec<AcademicYear>, DbError> {
+        log::trace!("Store::get_academic_years() called.");
+
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT label, starts, ends, active FROM academic_years ORDER BY starts",
+                &[],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error fetching academic years"))?;
+
+        rows.iter().map(year_from_row).collect()
+    }
+
+    /**
+    Insert or update (by `label`) an academic year. If it's `active`, every
+    other year is made inactive in the same transaction.
+    */
+    pub async fn set_academic_year(&self, year: &AcademicYear) -> Result<(), DbError> {
+        log::trace!("Store::set_academic_year( {:?} ) called.", year);
+
+        year.check().map_err(DbError)?;
+        let label = year.label.trim();
+
+        let mut client = self.connect().await?;
+        let t = client.transaction().await?;
+
+        if year.active {
+            t.execute(
+                "UPDATE academic_years SET active = FALSE WHERE label <> $1",
+                &[&label],
+            )
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error deactivating academic years"))?;
+        }
+        t.execute(
+            "INSERT INTO academic_years (label, starts, ends, active)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (label) DO UPDATE
+            SET starts = $2, ends = $3, active = $4",
+            &[&label, &year.starts, &year.ends, &year.active],
+        )
+        .await
+        .map_err(|e| DbError::from(e).annotate("Error saving academic year"))?;
+
+        t.commit().await?;
+        Ok(())
+    }
+
+    /// Delete the academic year with the given `label`; returns whether
+    /// there was one.
+    pub async fn delete_academic_year(&self, label: &str) -> Result<bool, DbError> {
+        log::trace!("Store::delete_academic_year( {:?} ) called.", label);
+
+        let client = self.connect().await?;
+        let n = client
+            .execute("DELETE FROM academic_years WHERE label = $1", &[&label])
+            .await
+            .map_err(|e| DbError::from(e).annotate("Error deleting academic year"))?;
+
+        Ok(n > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+    use time::macros::date;
+
+    use crate::store::tests::TEST_CONNECTION;
+    use crate::tests::ensure_logging;
+
+    fn year(label: &str, starts: Date, ends: Date, active: bool) -> AcademicYear {
+        AcademicYear {
+            label: label.to_owned(),
+            starts,
+            ends,
+            active,
+        }
+    }
+
+    #[test]
+    fn check_years() {
+        let y = year(
+            "2022--2023",
+            date!(2022 - 08 - 29),
+            date!(2023 - 06 - 02),
+            true,
+        );
+        assert!(y.check().is_ok());
+        assert_eq!(y.start_year(), 2022);
+        assert!(y.contains(&date!(2023 - 01 - 03)));
+        assert!(!y.contains(&date!(2023 - 06 - 03)));
+
+        let backward = year("2023", date!(2023 - 06 - 02), date!(2022 - 08 - 29), false);
+        assert!(backward.check().is_err());
+        let blank = year("  ", date!(2022 - 08 - 29), date!(2023 - 06 - 02), false);
+        assert!(blank.check().is_err());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn academic_years() {
+        ensure_logging();
+
+        let db = Store::new(TEST_CONNECTION.to_owned());
+        db.ensure_db_schema().await.unwrap();
+        db.nuke_database().await.unwrap();
+        db.ensure_db_schema().await.unwrap();
+
+        assert!(db.get_academic_years().await.unwrap().is_empty());
+
+        let mut old = year(
+            "2022--2023",
+            date!(2022 - 08 - 29),
+            date!(2023 - 06 - 02),
+            true,
+        );
+        db.set_academic_year(&old).await.unwrap();
+        let new = year("2023--2024", date!(2023 - 08 - 28), date!(2024 - 05 - 31), true);
+        db.set_academic_year(&new).await.unwrap();
+
+        old.active = false;
+        assert_eq!(
+            db.get_academic_years().await.unwrap(),
+            vec![old.clone(), new.clone()]
+        );
+
+        old.ends = date!(2023 - 06 - 09);
+        db.set_academic_year(&old).await.unwrap();
+        let years = db.get_academic_years().await.unwrap();
+        assert_eq!(&years[0], &old);
+        assert!(years[1].active);
+
+        assert!(db.delete_academic_year("2022--2023").await.unwrap());
+        assert!(!db.delete_academic_year("2022--2023").await.unwrap());
+        assert_eq!(db.get_academic_years().await.unwrap(), vec![new]);
+
+        db.nuke_database().await.unwrap();
+    }
+}
@@ -10,16 +10,21 @@ Internal representations of the four types of users of this system:
 
 Most of the information contained herein is just directly wrapped data from
 the underlying Postgres store, collected and cross-referenced.
+
+Names and email addresses are anonymized when serialized during an
+anonymized request; see [`anon`](crate::anon).
 */
 use std::cmp::Ordering;
 use std::io::Read;
 
 use serde::{Deserialize, Serialize};
 
+use crate::anon;
+
 /// Marks the role of the [`User`].
 ///
 /// The `User` is a sum type, but this distinction is useful elsewhere.
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum Role {
     Admin,
     Boss,
@@ -54,6 +59,62 @@ impl std::str::FromStr for Role {
     }
 }
 
+/**
+Something an Admin can be allowed to do.
+
+Admins with no permissions on record can do anything (including granting
+permissions); these are for restricting Admin accounts that shouldn't.
+See [`inter::admin`](crate::inter::admin) for which actions need which.
+*/
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum Permission {
+    /// Adding and changing users, and students' records.
+    Users,
+    /// Adding and changing courses and chapters.
+    Courses,
+    /// Changing the instructional calendar and special dates.
+    Calendar,
+    /// Deleting things and resetting the year, on top of whichever of the
+    /// above the thing being deleted falls under.
+    Destructive,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 4] = [
+        Permission::Users,
+        Permission::Courses,
+        Permission::Calendar,
+        Permission::Destructive,
+    ];
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let token = match self {
+            Permission::Users => "Users",
+            Permission::Courses => "Courses",
+            Permission::Calendar => "Calendar",
+            Permission::Destructive => "Destructive",
+        };
+
+        write!(f, "{}", token)
+    }
+}
+
+impl std::str::FromStr for Permission {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Users" => Ok(Permission::Users),
+            "Courses" => Ok(Permission::Courses),
+            "Calendar" => Ok(Permission::Calendar),
+            "Destructive" => Ok(Permission::Destructive),
+            _ => Err(format!("{:?} is not a valid Permission.", s)),
+        }
+    }
+}
+
 /// Information common to all users.
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 pub struct BaseUser {
@@ -66,6 +127,7 @@ pub struct BaseUser {
     /// record in the [auth database](crate::auth::Db).
     pub salt: String,
     /// Used largely to verify identity when resetting a password.
+    #[serde(serialize_with = "anon::ser::email")]
     pub email: String,
 }
 
@@ -134,6 +196,7 @@ pub struct Teacher {
     /// uname, salt, email
     pub base: BaseUser,
     /// Display name.
+    #[serde(serialize_with = "anon::ser::name")]
     pub name: String,
 }
 
@@ -144,12 +207,15 @@ Wraps all information about a student except for pace goals.
 pub struct Student {
     pub base: BaseUser,
     /// Last name of the student.
+    #[serde(serialize_with = "anon::ser::last")]
     pub last: String,
     /// The rest of the student's name (first, middle initial, etc.).
+    #[serde(serialize_with = "anon::ser::rest")]
     pub rest: String,
     /// `uname` of the student's teacher.
     pub teacher: String,
     /// Parent email address(es? if possible?).
+    #[serde(serialize_with = "anon::ser::email")]
     pub parent: String,
     /// Mark of Fall Semester Exam (if complete).
     pub fall_exam: Option<String>,
@@ -307,6 +373,49 @@ impl Student {
         );
         Ok(students)
     }
+
+    /**
+    Write `students` (in order by teacher, then name) as CSV in the format
+    read by [`Student::vec_from_csv_reader`], under a comment naming the
+    columns.
+
+    Names and email addresses are anonymized if the current request is; see
+    [`anon`](crate::anon).
+    */
+    pub fn csv_from_students(students: &[&Student]) -> Result<String, String> {
+        log::trace!(
+            "Student::csv_from_students( [ {} students ] ) called.",
+            students.len()
+        );
+
+        let mut students = students.to_vec();
+        students.sort_by(|a, b| {
+            (&a.teacher, &a.last, &a.rest, &a.base.uname)
+                .cmp(&(&b.teacher, &b.last, &b.rest, &b.base.uname))
+        });
+
+        let header = b"#uname, last, rest, email, parent, teacher\n".to_vec();
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(header);
+        for s in students.iter() {
+            csv_writer
+                .write_record([
+                    s.base.uname.as_str(),
+                    &anon::last(&s.last),
+                    &anon::rest(&s.rest),
+                    &anon::email(&s.base.email),
+                    &anon::email(&s.parent),
+                    s.teacher.as_str(),
+                ])
+                .map_err(|e| format!("Error writing CSV record for {:?}: {}", &s.base.uname, &e))?;
+        }
+
+        let bytes = csv_writer
+            .into_inner()
+            .map_err(|e| format!("Error finishing CSV: {}", &e))?;
+        String::from_utf8(bytes).map_err(|e| format!("CSV isn't UTF-8: {}", &e))
+    }
 }
 
 /// Sum type unifying all four types of users.
@@ -398,6 +507,26 @@ mod tests {
         log::trace!("Students:\n{:#?}", &studs);
     }
 
+    #[test]
+    fn students_to_csv() {
+        ensure_logging();
+        let f = std::fs::File::open("test/good_students_0.csv").unwrap();
+        let mut studs = Student::vec_from_csv_reader(f).unwrap();
+        // Parent fields can hold several addresses; they need quoting.
+        studs[0].parent = "a.frog@gmail.com, b.frog@gmail.com".to_owned();
+
+        let refs: Vec<&Student> = studs.iter().collect();
+        let csv = Student::csv_from_students(&refs).unwrap();
+        log::trace!("CSV:\n{}", &csv);
+        assert!(csv.starts_with("#uname"));
+
+        let mut again = Student::vec_from_csv_reader(csv.as_bytes()).unwrap();
+        assert_eq!(again.len(), studs.len());
+        again.sort_by(|a, b| a.base.uname.cmp(&b.base.uname));
+        studs.sort_by(|a, b| a.base.uname.cmp(&b.base.uname));
+        assert_eq!(again, studs);
+    }
+
     #[test]
     fn make_users_serialized() {
         use serde_json::to_writer_pretty;
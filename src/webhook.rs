@@ -0,0 +1,452 @@
+/*!
+Outbound webhooks, so other systems (like an after-school tutoring
+program's) can react to things that happen in camp.
+
+Unrestricted Admins register endpoints (see
+[`webhooks`](crate::inter::webhooks)), each with the [`Event`]s it wants
+and a secret. When one of those events happens,
+[`Glob::fire_webhook`](crate::config::Glob::fire_webhook) queues a
+delivery to every active endpoint that wants it (see
+[`Store::queue_webhook_deliveries`](crate::store::Store::queue_webhook_deliveries)),
+and the [`Outbox`] sends them in the background.
+
+Every delivery is a POST whose body looks like
+```json
+{
+    "event": "report-finalized",
+    "at": "2023-03-01T17:04:12Z",
+    "data": {
+        "uname": "frog", "last": "Frog", "rest": "Frederick", "teacher": "berro",
+        "term": "fall"
+    }
+}
+```
+with the headers
+```text
+content-type: application/json
+x-camp-event: report-finalized
+x-camp-delivery: 1234
+x-camp-timestamp: 1677690252
+x-camp-signature: sha256=8f3d0c5a...
+```
+The signature (see [`sign`]) is the hex HMAC-SHA256, keyed with the
+endpoint's secret, of the timestamp, a `.`, and the body; receivers should
+check it (and that the timestamp is recent) before trusting the body. The
+delivery id stays the same across retries, so receivers can ignore
+duplicates.
+
+A delivery that doesn't get a 2xx response is retried after each of the
+[`RETRY_DELAYS`], and then given up on. Names in payloads are never
+anonymized, for the same reason emails aren't.
+*/
+use std::{
+    fmt::Write,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hyper::{client::HttpConnector, Body, Client, Method, Request};
+use hyper_rustls::HttpsConnector;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use time::{format_description::well_known::Rfc3339, Date, OffsetDateTime};
+use tokio::sync::{Notify, RwLock};
+
+use crate::{
+    pace::{CustomCh, Goal, Source, Term},
+    store::{LagSnapshot, PendingDelivery, Store},
+    user::Student,
+};
+
+/// How long (in seconds) to wait before each retry of a failed delivery.
+pub const RETRY_DELAYS: [u64; 5] = [60, 300, 1800, 7200, 43200];
+/// Most deliveries the [`Outbox`] takes from the queue at once.
+const BATCH: i64 = 20;
+/// How often the [`Outbox`] looks for retries that have come due, when
+/// nothing wakes it sooner.
+const POLL: Duration = Duration::from_secs(60);
+/// How long an endpoint has to respond.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something that can happen that webhooks can ask to hear about.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Event {
+    /// A student's goal was marked done; see [`GoalCompleted`].
+    GoalCompleted,
+    /// A student has fallen behind pace (checked weekly, along with the lag
+    /// snapshots); see [`StudentLagging`].
+    StudentLagging,
+    /// A Teacher finalized a student's report; see [`ReportFinalized`].
+    ReportFinalized,
+}
+
+impl Event {
+    pub const ALL: [Event; 3] = [
+        Event::GoalCompleted,
+        Event::StudentLagging,
+        Event::ReportFinalized,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Event::GoalCompleted => "goal-completed",
+            Event::StudentLagging => "student-lagging",
+            Event::ReportFinalized => "report-finalized",
+        }
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Event {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "goal-completed" => Ok(Event::GoalCompleted),
+            "student-lagging" => Ok(Event::StudentLagging),
+            "report-finalized" => Ok(Event::ReportFinalized),
+            _ => Err(format!("{:?} is not a webhook event.", s)),
+        }
+    }
+}
+
+/// Where a delivery stands.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Not sent yet, or waiting to be retried.
+    Pending,
+    Delivered,
+    /// Retried as many times as it's going to be.
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for DeliveryStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(DeliveryStatus::Pending),
+            "delivered" => Ok(DeliveryStatus::Delivered),
+            "failed" => Ok(DeliveryStatus::Failed),
+            _ => Err(format!("{:?} is not a valid delivery status.", s)),
+        }
+    }
+}
+
+/// The student an event is about, as it appears in every payload.
+#[derive(Debug, Serialize)]
+pub struct StudentData<'a> {
+    pub uname: &'a str,
+    pub last: &'a str,
+    pub rest: &'a str,
+    pub teacher: &'a str,
+}
+
+impl<'a> From<&'a Student> for StudentData<'a> {
+    fn from(s: &'a Student) -> Self {
+        StudentData {
+            uname: &s.base.uname,
+            last: &s.last,
+            rest: &s.rest,
+            teacher: &s.teacher,
+        }
+    }
+}
+
+/// The `data` of a `goal-completed` event. Goals from courses have a `sym`
+/// and `seq`; custom goals have the `custom` chapter's id instead.
+#[derive(Debug, Serialize)]
+pub struct GoalCompleted<'a> {
+    #[serde(flatten)]
+    pub student: StudentData<'a>,
+    pub sym: Option<&'a str>,
+    pub seq: Option<i16>,
+    pub custom: Option<i64>,
+    pub done: Date,
+    pub score: Option<&'a str>,
+}
+
+impl<'a> GoalCompleted<'a> {
+    /// The event for the done goal `g` of the student `s`.
+    pub fn new(s: &'a Student, g: &'a Goal, done: Date) -> Self {
+        let (sym, seq, custom) = match &g.source {
+            Source::Book(b) => (Some(b.sym.as_str()), Some(b.seq), None),
+            Source::Custom(CustomCh(id)) => (None, None, Some(*id)),
+        };
+        GoalCompleted {
+            student: s.into(),
+            sym,
+            seq,
+            custom,
+            done,
+            score: g.score.as_deref(),
+        }
+    }
+}
+
+/// The `data` of a `student-lagging` event: the student's lag snapshot from
+/// the week they fell behind.
+#[derive(Debug, Serialize)]
+pub struct StudentLagging<'a> {
+    #[serde(flatten)]
+    pub student: StudentData<'a>,
+    #[serde(flatten)]
+    pub snapshot: &'a LagSnapshot,
+}
+
+/// The `data` of a `report-finalized` event.
+#[derive(Debug, Serialize)]
+pub struct ReportFinalized<'a> {
+    #[serde(flatten)]
+    pub student: StudentData<'a>,
+    pub term: &'static str,
+}
+
+impl<'a> ReportFinalized<'a> {
+    pub fn new(s: &'a Student, term: Term) -> Self {
+        ReportFinalized {
+            student: s.into(),
+            term: term.as_str(),
+        }
+    }
+}
+
+/// Return the body of a delivery of `event` with the details in `data`.
+pub fn payload<T: Serialize>(event: Event, data: &T) -> Result<String, String> {
+    let at = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .map_err(|e| format!("Error formatting timestamp: {}", &e))?;
+    let data = serde_json::to_value(data)
+        .map_err(|e| format!("Error serializing {} event: {}", &event, &e))?;
+    Ok(json!({ "event": event, "at": at, "data": data }).to_string())
+}
+
+/// Return the value of the `x-camp-signature` header for the `body` sent
+/// with the `timestamp`, under the endpoint's `secret`.
+pub fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let mut ctx = hmac::Context::with_key(&key);
+    ctx.update(timestamp.to_string().as_bytes());
+    ctx.update(b".");
+    ctx.update(body.as_bytes());
+
+    let mut sig = String::from("sha256=");
+    for b in ctx.sign().as_ref() {
+        // Writing to a `String` can't fail.
+        write!(&mut sig, "{:02x}", b).unwrap();
+    }
+    sig
+}
+
+/// How long (in seconds) to wait before retrying a delivery that has
+/// failed `attempts` times, or `None` if it's time to give up.
+pub fn retry_delay(attempts: i16) -> Option<u64> {
+    let n = usize::try_from(attempts).ok()?.checked_sub(1)?;
+    RETRY_DELAYS.get(n).copied()
+}
+
+/// Check that `url` is somewhere deliveries can be sent.
+pub fn check_url(url: &str) -> Result<(), String> {
+    let uri: hyper::Uri = url
+        .parse()
+        .map_err(|e| format!("{:?} is not a valid URL: {}", url, &e))?;
+    match uri.scheme_str() {
+        Some("https") | Some("http") => {}
+        _ => {
+            return Err(format!("{:?} is not an http:// or https:// URL.", url));
+        }
+    }
+    if uri.host().is_none() {
+        return Err(format!("{:?} has no host.", url));
+    }
+    Ok(())
+}
+
+/// How one attempt to send a delivery went.
+#[derive(Debug, PartialEq)]
+pub struct Attempt {
+    /// HTTP status of the endpoint's response, if it responded.
+    pub response: Option<u16>,
+    /// What went wrong; `None` means it was delivered.
+    pub error: Option<String>,
+}
+
+type HttpsClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+fn https_client() -> HttpsClient {
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    Client::builder().build(https)
+}
+
+/// Make one attempt to send the delivery `d`.
+async fn send(client: &HttpsClient, d: &PendingDelivery) -> Attempt {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(&d.url)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .header("x-camp-event", d.event.as_str())
+        .header("x-camp-delivery", d.id)
+        .header("x-camp-timestamp", timestamp)
+        .header("x-camp-signature", sign(&d.secret, timestamp, &d.payload))
+        .body(Body::from(d.payload.clone()));
+    let req = match req {
+        Ok(req) => req,
+        Err(e) => {
+            return Attempt {
+                response: None,
+                error: Some(format!("Error building request: {}", &e)),
+            };
+        }
+    };
+
+    match tokio::time::timeout(TIMEOUT, client.request(req)).await {
+        Err(_) => Attempt {
+            response: None,
+            error: Some(format!("No response in {} seconds.", TIMEOUT.as_secs())),
+        },
+        Ok(Err(e)) => Attempt {
+            response: None,
+            error: Some(format!("Error sending request: {}", &e)),
+        },
+        Ok(Ok(resp)) => {
+            let status = resp.status();
+            Attempt {
+                response: Some(status.as_u16()),
+                error: (!status.is_success()).then(|| format!("Endpoint returned {}.", &status)),
+            }
+        }
+    }
+}
+
+/// Sends queued webhook deliveries in the background; see the module-level
+/// docs.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    notify: Notify,
+}
+
+impl Outbox {
+    pub fn new() -> Outbox {
+        Outbox::default()
+    }
+
+    /// Let the outbox know there are new deliveries to send.
+    pub fn wake(&self) {
+        self.notify.notify_one();
+    }
+
+    /// Send the deliveries in `due`, and record how each went.
+    async fn send_all(client: &HttpsClient, store: &Store, due: &[PendingDelivery]) {
+        for d in due.iter() {
+            let attempt = send(client, d).await;
+            let retry_in = match &attempt.error {
+                None => None,
+                Some(e) => {
+                    log::warn!("Webhook delivery {} to {:?} failed: {}", &d.id, &d.url, e);
+                    retry_delay(d.attempts + 1)
+                }
+            };
+            if let Err(e) = store.record_webhook_attempt(d.id, &attempt, retry_in).await {
+                log::error!("Error recording webhook delivery {}: {}", &d.id, &e);
+            }
+        }
+    }
+
+    /**
+    Send deliveries as they're queued (and retries as they come due)
+    from `store`, forever.
+
+    This is meant to be `tokio::spawn()`ed when the server starts up.
+    */
+    pub async fn run(self: Arc<Self>, store: Arc<RwLock<Store>>) {
+        let client = https_client();
+        loop {
+            let due = store.read().await.get_due_webhook_deliveries(BATCH).await;
+            match due {
+                Ok(due) if !due.is_empty() => {
+                    Outbox::send_all(&client, &*store.read().await, &due).await;
+                }
+                Ok(_) => {
+                    tokio::select! {
+                        _ = self.notify.notified() => {},
+                        _ = tokio::time::sleep(POLL) => {},
+                    }
+                }
+                Err(e) => {
+                    log::error!("Error reading queued webhook deliveries: {}", &e);
+                    tokio::time::sleep(POLL).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events() {
+        for e in Event::ALL.iter() {
+            assert_eq!(e.as_str().parse::<Event>().unwrap(), *e);
+            assert_eq!(serde_json::to_value(e).unwrap(), e.as_str());
+        }
+        assert!("goal-done".parse::<Event>().is_err());
+    }
+
+    #[test]
+    fn signatures() {
+        // echo -n '1677690252.{"event":"ping"}' | openssl dgst -sha256 -hmac shh
+        assert_eq!(
+            sign("shh", 1677690252, r#"{"event":"ping"}"#),
+            "sha256=cb3a5e6395a5c40467d7a5bf277c0d3c4370f54ac4db1fada8eac9f6accb32df"
+        );
+        assert_ne!(
+            sign("shh", 1677690253, r#"{"event":"ping"}"#),
+            sign("shh", 1677690252, r#"{"event":"ping"}"#)
+        );
+    }
+
+    #[test]
+    fn retries() {
+        assert_eq!(retry_delay(1), Some(60));
+        assert_eq!(retry_delay(5), Some(43200));
+        assert_eq!(retry_delay(6), None);
+        assert_eq!(retry_delay(0), None);
+    }
+
+    #[test]
+    fn urls() {
+        assert!(check_url("https://tutoring.example.org/camp-hook").is_ok());
+        assert!(check_url("http://10.0.0.5:8080/hook").is_ok());
+        assert!(check_url("ftp://example.org/hook").is_err());
+        assert!(check_url("/hook").is_err());
+        assert!(check_url("not a url").is_err());
+    }
+}